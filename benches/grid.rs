@@ -0,0 +1,123 @@
+//! Benchmarks of the grid hot paths, run every frame or on every placement: the center of
+//! gravity and plate rotation, the cell coordinate conversions, and the placement itself.
+
+use bevy::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use libracity::{Buildable, BuildableId, BuildableRegistry, Grid, Pivot, TiltModel};
+
+/// Grid sizes benchmarked, from the shipped levels to much larger plates.
+const SIZES: [i32; 6] = [8, 16, 32, 64, 128, 256];
+
+/// Balance factor of the benchmarked levels.
+const BALANCE_FACTOR: f32 = 0.1;
+
+/// Register a single buildable without any asset, and return its identifier.
+fn register_buildable(buildables: &mut BuildableRegistry) -> BuildableId {
+    buildables.register("hut", Buildable::without_assets("hut", 1.0))
+}
+
+/// Create an empty grid of the given size.
+fn empty_grid(size: i32) -> Grid {
+    let mut grid = Grid::new();
+    grid.set_size(&IVec2::splat(size));
+    grid
+}
+
+/// Create a grid of the given size with about one cell in three occupied, with varied weights.
+fn filled_grid(size: i32, bref: BuildableId) -> Grid {
+    let mut grid = empty_grid(size);
+    let min = grid.min_pos();
+    let max = grid.max_pos();
+    for j in min.y..=max.y {
+        for i in min.x..=max.x {
+            if (i + 2 * j) % 3 == 0 {
+                let weight = 1.0 + ((i * 7 + j * 13).rem_euclid(5)) as f32;
+                grid.spawn_item(&IVec2::new(i, j), bref, weight, Entity::from_raw(0));
+            }
+        }
+    }
+    grid
+}
+
+fn bench_cog(c: &mut Criterion) {
+    let mut buildables = BuildableRegistry::new();
+    let bref = register_buildable(&mut buildables);
+    let mut group = c.benchmark_group("calc_cog_offset");
+    for size in SIZES {
+        let grid = filled_grid(size, bref);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &grid, |b, grid| {
+            b.iter(|| grid.calc_cog_offset(black_box(BALANCE_FACTOR)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_rot(c: &mut Criterion) {
+    let mut buildables = BuildableRegistry::new();
+    let bref = register_buildable(&mut buildables);
+    let mut group = c.benchmark_group("calc_rot");
+    for size in SIZES {
+        let grid = filled_grid(size, bref);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &grid, |b, grid| {
+            b.iter(|| {
+                grid.calc_rot(
+                    black_box(BALANCE_FACTOR),
+                    &Pivot::default(),
+                    &TiltModel::Linear,
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_coords(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index_fpos");
+    for size in SIZES {
+        let grid = empty_grid(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &grid, |b, grid| {
+            // Convert the coordinates of every cell, like a full grid scan does
+            let min = grid.min_pos();
+            let max = grid.max_pos();
+            b.iter(|| {
+                let mut acc = Vec2::ZERO;
+                for j in min.y..=max.y {
+                    for i in min.x..=max.x {
+                        let pos = IVec2::new(i, j);
+                        acc += grid.fpos(&pos) * grid.index(&pos) as f32;
+                    }
+                }
+                acc
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_placement(c: &mut Criterion) {
+    let mut buildables = BuildableRegistry::new();
+    let bref = register_buildable(&mut buildables);
+    let mut group = c.benchmark_group("placement");
+    for size in SIZES {
+        // Place a buildable on a free cell of a busy grid, then update the plate rotation, like
+        // the game does on each placement
+        let pos = IVec2::new(1, 0);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched_ref(
+                || filled_grid(size, bref),
+                |grid| {
+                    if grid.can_spawn_item(&pos) {
+                        grid.spawn_item(&pos, bref, 1.0, Entity::from_raw(0));
+                    }
+                    grid.calc_rot(BALANCE_FACTOR, &Pivot::default(), &TiltModel::Linear)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_cog, bench_rot, bench_coords, bench_placement);
+criterion_main!(benches);
@@ -0,0 +1,442 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_tweening::{
+    component_animator_system,
+    lens::{TextColorLens, TransformPositionLens, TransformScaleLens, UiPositionLens},
+    Animator, EaseFunction, EaseMethod, Lens, Tracks, Tween, TweenCompleted, TweeningType,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+
+use crate::config::Config;
+
+/// Delay between the animations of two consecutive entries of a list, in seconds. See
+/// [`PlayAnimation::staggered()`].
+pub const STAGGER_INTERVAL: f32 = 0.08;
+
+/// Duration of the tracks of the animations cut to their final state with the reduced motion.
+const CUT_DURATION: Duration = Duration::from_millis(1);
+
+/// Easing curve of an animation track.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadraticIn,
+    QuadraticOut,
+    QuadraticInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    BackOut,
+    ElasticOut,
+    BounceOut,
+}
+
+impl From<Ease> for EaseMethod {
+    fn from(ease: Ease) -> Self {
+        match ease {
+            Ease::Linear => EaseMethod::Linear,
+            Ease::QuadraticIn => EaseFunction::QuadraticIn.into(),
+            Ease::QuadraticOut => EaseFunction::QuadraticOut.into(),
+            Ease::QuadraticInOut => EaseFunction::QuadraticInOut.into(),
+            Ease::CubicIn => EaseFunction::CubicIn.into(),
+            Ease::CubicOut => EaseFunction::CubicOut.into(),
+            Ease::CubicInOut => EaseFunction::CubicInOut.into(),
+            Ease::SineIn => EaseFunction::SineIn.into(),
+            Ease::SineOut => EaseFunction::SineOut.into(),
+            Ease::SineInOut => EaseFunction::SineInOut.into(),
+            Ease::BackOut => EaseFunction::BackOut.into(),
+            Ease::ElasticOut => EaseFunction::ElasticOut.into(),
+            Ease::BounceOut => EaseFunction::BounceOut.into(),
+        }
+    }
+}
+
+/// Looping of an animation track.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Repeat {
+    /// Play once from start to end.
+    #[default]
+    Once,
+    /// Restart from the start each time the end is reached.
+    Loop,
+    /// Play back and forth indefinitely.
+    PingPong,
+}
+
+impl From<Repeat> for TweeningType {
+    fn from(repeat: Repeat) -> Self {
+        match repeat {
+            Repeat::Once => TweeningType::Once,
+            Repeat::Loop => TweeningType::Loop,
+            Repeat::PingPong => TweeningType::PingPong,
+        }
+    }
+}
+
+/// Position of a UI node, in pixels. Unset sides are left undefined.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct UiRectDesc {
+    pub left: Option<f32>,
+    pub right: Option<f32>,
+    pub top: Option<f32>,
+    pub bottom: Option<f32>,
+}
+
+impl From<UiRectDesc> for Rect<Val> {
+    fn from(desc: UiRectDesc) -> Self {
+        let val = |side: Option<f32>| side.map_or(Val::Undefined, Val::Px);
+        Rect {
+            left: val(desc.left),
+            right: val(desc.right),
+            top: val(desc.top),
+            bottom: val(desc.bottom),
+        }
+    }
+}
+
+/// Property animated by a track, with its start and end values.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "property", rename_all = "snake_case")]
+pub enum AnimProperty {
+    /// Translation of the [`Transform`].
+    Translation { from: Vec3, to: Vec3 },
+    /// Rotation of the [`Transform`], as XYZ Euler angles in degrees.
+    Rotation { from: Vec3, to: Vec3 },
+    /// Scale of the [`Transform`].
+    Scale { from: Vec3, to: Vec3 },
+    /// [`RotationOffset`] of the entity, as XYZ Euler angles in degrees.
+    RotationOffset { from: Vec3, to: Vec3 },
+    /// Color of the first section of the [`Text`], as sRGBA.
+    TextColor { from: [f32; 4], to: [f32; 4] },
+    /// Position of the UI node [`Style`].
+    UiPosition { from: UiRectDesc, to: UiRectDesc },
+    /// Height of the UI node [`Style`], in percent of the parent height.
+    UiHeight { from: f32, to: f32 },
+}
+
+/// Single track of an animation, animating one property.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrackDesc {
+    #[serde(flatten)]
+    pub property: AnimProperty,
+    /// Duration of the track, in seconds.
+    pub duration: f32,
+    #[serde(default)]
+    pub ease: Ease,
+    #[serde(default)]
+    pub repeat: Repeat,
+}
+
+impl TrackDesc {
+    /// Tween playing the track, or jumping to its end value if cut.
+    fn tween<T>(&self, lens: impl Lens<T> + Send + Sync + 'static, cut: bool) -> Tween<T> {
+        if cut {
+            return Tween::new(EaseMethod::Linear, TweeningType::Once, CUT_DURATION, lens);
+        }
+        Tween::new(
+            self.ease,
+            self.repeat.into(),
+            Duration::from_secs_f32(self.duration),
+            lens,
+        )
+    }
+}
+
+/// Description of an animation, made of one or more tracks played in parallel.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationDesc {
+    pub tracks: Vec<TrackDesc>,
+    /// Does the animation move large parts of the screen? Such animations are cut to their final
+    /// state when the `reduce_motion` config is set.
+    #[serde(default)]
+    pub large_motion: bool,
+}
+
+/// Collect the tweens of a single component into an animator, raising the completed event on the
+/// first tween if any.
+fn make_animator<T: Component>(
+    mut tweens: Vec<Tween<T>>,
+    completed_event: &mut Option<u64>,
+) -> Option<Animator<T>> {
+    if let Some(user_data) = completed_event.take() {
+        if let Some(tween) = tweens.first_mut() {
+            tween.set_completed_event(true, user_data);
+        } else {
+            *completed_event = Some(user_data);
+        }
+    }
+    match tweens.len() {
+        0 => None,
+        1 => tweens.pop().map(Animator::new),
+        _ => Some(Animator::new(Tracks::new(tweens))),
+    }
+}
+
+impl AnimationDesc {
+    /// Insert the animators playing this animation on an entity, replacing any animation already
+    /// playing on the same components. Cut animations jump to the end values of their tracks.
+    fn insert(
+        &self,
+        entity_commands: &mut EntityCommands,
+        completed_event: Option<u64>,
+        cut: bool,
+    ) {
+        let mut transform_tweens = vec![];
+        let mut offset_tweens = vec![];
+        let mut text_tweens = vec![];
+        let mut style_tweens = vec![];
+        for track in &self.tracks {
+            match track.property {
+                AnimProperty::Translation { from, to } => transform_tweens.push(track.tween(
+                    TransformPositionLens {
+                        start: from,
+                        end: to,
+                    },
+                    cut,
+                )),
+                AnimProperty::Rotation { from, to } => transform_tweens.push(track.tween(
+                    EulerRotationLens {
+                        start: from,
+                        end: to,
+                    },
+                    cut,
+                )),
+                AnimProperty::Scale { from, to } => transform_tweens.push(track.tween(
+                    TransformScaleLens {
+                        start: from,
+                        end: to,
+                    },
+                    cut,
+                )),
+                AnimProperty::RotationOffset { from, to } => offset_tweens.push(track.tween(
+                    RotationOffsetLens {
+                        start: from,
+                        end: to,
+                    },
+                    cut,
+                )),
+                AnimProperty::TextColor { from, to } => text_tweens.push(track.tween(
+                    TextColorLens {
+                        start: Color::rgba(from[0], from[1], from[2], from[3]),
+                        end: Color::rgba(to[0], to[1], to[2], to[3]),
+                        section: 0,
+                    },
+                    cut,
+                )),
+                AnimProperty::UiPosition { from, to } => style_tweens.push(track.tween(
+                    UiPositionLens {
+                        start: from.into(),
+                        end: to.into(),
+                    },
+                    cut,
+                )),
+                AnimProperty::UiHeight { from, to } => style_tweens.push(track.tween(
+                    UiHeightLens {
+                        start: from,
+                        end: to,
+                    },
+                    cut,
+                )),
+            }
+        }
+        let mut completed_event = completed_event;
+        if let Some(animator) = make_animator(transform_tweens, &mut completed_event) {
+            entity_commands.insert(animator);
+        }
+        if let Some(animator) = make_animator(offset_tweens, &mut completed_event) {
+            entity_commands.insert(animator);
+        }
+        if let Some(animator) = make_animator(text_tweens, &mut completed_event) {
+            entity_commands.insert(animator);
+        }
+        if let Some(animator) = make_animator(style_tweens, &mut completed_event) {
+            entity_commands.insert(animator);
+        }
+    }
+}
+
+/// Resource holding all the animations of the game, loaded from `animations.json` by name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct AnimationLibrary(HashMap<String, AnimationDesc>);
+
+impl AnimationLibrary {
+    pub fn new() -> Self {
+        AnimationLibrary::default()
+    }
+
+    pub fn from_json(json_content: &str) -> serde_json::Result<AnimationLibrary> {
+        serde_json::from_str(json_content)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AnimationDesc> {
+        self.0.get(name)
+    }
+}
+
+/// Additional rotation applied on top of the rotation set by gameplay systems, for animations
+/// which must not fight with them, like the plate wobble.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct RotationOffset(pub Quat);
+
+impl Default for RotationOffset {
+    fn default() -> Self {
+        RotationOffset(Quat::IDENTITY)
+    }
+}
+
+/// Additional translation applied on top of the translation set by gameplay systems, for
+/// animations which must not fight with them, like the cursor gliding between cells.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct TranslationOffset(pub Vec3);
+
+/// Lens interpolating a [`TranslationOffset`].
+pub struct TranslationOffsetLens {
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+impl Lens<TranslationOffset> for TranslationOffsetLens {
+    fn lerp(&mut self, target: &mut TranslationOffset, ratio: f32) {
+        target.0 = self.start.lerp(self.end, ratio);
+    }
+}
+
+/// Component requesting to play an animation of the [`AnimationLibrary`] on its entity. The
+/// component is removed once the animation started.
+///
+/// Delayed animations only apply their first values once started, so the entity should be
+/// spawned looking like the start of the animation, like a transparent text fading in.
+#[derive(Debug, Clone, Component)]
+pub struct PlayAnimation {
+    name: String,
+    completed_event: Option<u64>,
+    /// Time left before the animation starts, in seconds.
+    delay: f32,
+}
+
+impl PlayAnimation {
+    pub fn new(name: &str) -> Self {
+        PlayAnimation {
+            name: name.to_owned(),
+            completed_event: None,
+            delay: 0.0,
+        }
+    }
+
+    /// Start the animation after the given delay, in seconds.
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Start the animation after the ones of the entries before it in a list, so that the entries
+    /// animate one after the other.
+    pub fn staggered(self, index: usize) -> Self {
+        self.with_delay(index as f32 * STAGGER_INTERVAL)
+    }
+
+    /// Raise a [`TweenCompleted`] event with the given user data when the animation completes.
+    pub fn with_completed_event(mut self, user_data: u64) -> Self {
+        self.completed_event = Some(user_data);
+        self
+    }
+}
+
+/// Lens interpolating the Euler angles of a rotation, to allow full turns.
+struct EulerRotationLens {
+    start: Vec3,
+    end: Vec3,
+}
+
+/// Rotation from XYZ Euler angles in degrees.
+pub fn euler_quat(degrees: Vec3) -> Quat {
+    Quat::from_euler(
+        EulerRot::XYZ,
+        degrees.x.to_radians(),
+        degrees.y.to_radians(),
+        degrees.z.to_radians(),
+    )
+}
+
+impl Lens<Transform> for EulerRotationLens {
+    fn lerp(&mut self, target: &mut Transform, ratio: f32) {
+        target.rotation = euler_quat(self.start.lerp(self.end, ratio));
+    }
+}
+
+struct RotationOffsetLens {
+    start: Vec3,
+    end: Vec3,
+}
+
+impl Lens<RotationOffset> for RotationOffsetLens {
+    fn lerp(&mut self, target: &mut RotationOffset, ratio: f32) {
+        target.0 = euler_quat(self.start.lerp(self.end, ratio));
+    }
+}
+
+struct UiHeightLens {
+    start: f32,
+    end: f32,
+}
+
+impl Lens<Style> for UiHeightLens {
+    fn lerp(&mut self, target: &mut Style, ratio: f32) {
+        target.size.height = Val::Percent(self.start + (self.end - self.start) * ratio);
+    }
+}
+
+/// Start the animations requested with [`PlayAnimation`], once their delay elapsed.
+fn play_animations(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<Config>,
+    library: Res<AnimationLibrary>,
+    mut ev_tween_completed: EventWriter<TweenCompleted>,
+    mut query: Query<(Entity, &mut PlayAnimation)>,
+) {
+    for (entity, mut play) in query.iter_mut() {
+        if play.delay > 0.0 {
+            play.delay -= time.delta_seconds();
+            continue;
+        }
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.remove::<PlayAnimation>();
+        if let Some(anim) = library.get(&play.name) {
+            let cut = anim.large_motion && config.reduce_motion;
+            trace!(
+                "Play animation '{}' on {:?}, cut={}",
+                play.name,
+                entity,
+                cut
+            );
+            anim.insert(&mut entity_commands, play.completed_event, cut);
+        } else {
+            warn!("Unknown animation '{}'", play.name);
+            // Complete immediately so that anything waiting on the animation can proceed
+            if let Some(user_data) = play.completed_event {
+                ev_tween_completed.send(TweenCompleted { entity, user_data });
+            }
+        }
+    }
+}
+
+/// Plugin playing the data-driven animations of the [`AnimationLibrary`].
+pub struct AnimPlugin;
+
+impl Plugin for AnimPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AnimationLibrary::new())
+            .add_system(play_animations)
+            .add_system(component_animator_system::<RotationOffset>)
+            .add_system(component_animator_system::<TranslationOffset>);
+    }
+}
@@ -0,0 +1,340 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    boot::UiResources,
+    game::{Game, GameEvent, GameSequence, GameplaySystem},
+    inventory::Inventory,
+    rules::Rules,
+    storage, AppState, Level, Levels, LoadLevel, LoadLevelEvent, ResetPlateEvent,
+    RestartLevelEvent,
+};
+
+/// Save file of the assist history, in the profile storage.
+pub const ASSIST_FILE: &str = "assist.json";
+
+/// Number of failures and restarts of a level before suggesting an easier level or the assist.
+const SUGGEST_AFTER: u32 = 3;
+
+/// Key to retry the failed level with the assist.
+const ASSIST_KEY: KeyCode = KeyCode::G;
+
+/// Key to play the suggested easier level instead.
+const EASIER_KEY: KeyCode = KeyCode::L;
+
+/// Struggles of the player on a single level.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct LevelStruggle {
+    /// Number of failures and restarts since the level was last cleared.
+    failures: u32,
+    /// Is the assist enabled for the level?
+    assisted: bool,
+}
+
+/// Resource tracking the failures of the player on each level, to suggest an easier level or to
+/// enable the assist once they keep failing.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Assist {
+    /// Struggles of the player, by level name. Cleared levels are not listed.
+    levels: HashMap<String, LevelStruggle>,
+}
+
+impl Assist {
+    /// Load the assist history of the active profile saved by a previous session, if any.
+    pub fn load() -> Self {
+        storage::read(ASSIST_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(assist) => Some(assist),
+                Err(err) => {
+                    let location = storage::location(ASSIST_FILE);
+                    warn!("Failed to parse assist history '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(ASSIST_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(ASSIST_FILE);
+            warn!("Failed to save assist history to '{}': {}", location, err);
+        }
+    }
+
+    /// Number of failures and restarts of a level since it was last cleared.
+    pub fn failures(&self, level: &str) -> u32 {
+        self.levels
+            .get(level)
+            .map_or(0, |struggle| struggle.failures)
+    }
+
+    /// Is the assist enabled for a level?
+    pub fn is_assisted(&self, level: &str) -> bool {
+        self.levels
+            .get(level)
+            .is_some_and(|struggle| struggle.assisted)
+    }
+
+    /// Record a failure or a restart of a level.
+    fn record_failure(&mut self, level: &str) {
+        self.levels.entry(level.to_owned()).or_default().failures += 1;
+        self.save();
+    }
+
+    /// Enable the assist for a level, until it's cleared.
+    fn enable(&mut self, level: &str) {
+        self.levels.entry(level.to_owned()).or_default().assisted = true;
+        self.save();
+    }
+
+    /// Forget the struggles on a level once it's cleared.
+    fn clear(&mut self, level: &str) {
+        if self.levels.remove(level).is_some() {
+            self.save();
+        }
+    }
+}
+
+/// Suggestions offered to a player struggling on a level.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+struct Suggestion {
+    /// Can the player retry with the assist? Not offered if already enabled.
+    assist: bool,
+    /// Index of an easier level to play instead, if any.
+    easier_level: Option<usize>,
+}
+
+impl Suggestion {
+    fn is_empty(&self) -> bool {
+        !self.assist && self.easier_level.is_none()
+    }
+}
+
+/// Marker for the root of the suggestion panel.
+#[derive(Component)]
+struct SuggestionPanel;
+
+/// Enable the assist of the level being played, if any, whenever it starts.
+fn apply_assist(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    assist: Res<Assist>,
+    mut rules: ResMut<Rules>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    let assisted = assist.is_assisted(level.name());
+    if rules.assisted != assisted {
+        debug!("Assist for level '{}': {}", level.name(), assisted);
+        rules.assisted = assisted;
+    }
+}
+
+/// Count the restarts of a level in progress as failures. Runs before [`GameplaySystem::Reset`]
+/// to see the level as it was before it restarted.
+fn track_restarts(
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    game: Res<Game>,
+    level: Res<Level>,
+    inventory: Res<Inventory>,
+    mut assist: ResMut<Assist>,
+) {
+    // Retrying a failed level was already counted as a failure
+    if ev_restart.iter().last().is_some()
+        && game.sequence() == GameSequence::Play
+        && inventory.placed_count() > 0
+    {
+        assist.record_failure(level.name());
+        trace!(
+            "Level '{}' restarted, {} failure(s)",
+            level.name(),
+            assist.failures(level.name())
+        );
+    }
+}
+
+/// Count the failures of the current level, and forget them once it's cleared.
+fn track_results(
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    mut assist: ResMut<Assist>,
+) {
+    for ev in ev_game.iter() {
+        match ev {
+            GameEvent::LevelFailed { .. } => {
+                assist.record_failure(level.name());
+                trace!(
+                    "Level '{}' failed, {} failure(s)",
+                    level.name(),
+                    assist.failures(level.name())
+                );
+            }
+            GameEvent::LevelCleared { .. } => assist.clear(level.name()),
+            _ => {}
+        }
+    }
+}
+
+/// Offer an easier level or the assist on the defeat panel, once the player failed the level
+/// several times.
+fn show_suggestion_panel(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    assist: Res<Assist>,
+    ui_resources: Res<UiResources>,
+) {
+    let failed = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelFailed { .. }))
+        .last()
+        .is_some();
+    if !failed || assist.failures(level.name()) < SUGGEST_AFTER {
+        return;
+    }
+    let suggestion = Suggestion {
+        assist: !assist.is_assisted(level.name()),
+        easier_level: levels.easier_than(level.index()),
+    };
+    if suggestion.is_empty() {
+        return;
+    }
+    let mut lines = vec!["Having trouble?".to_owned()];
+    if suggestion.assist {
+        lines.push(format!(
+            "[{:?}] Retry with a wider victory margin",
+            ASSIST_KEY
+        ));
+    }
+    if let Some(level_desc) = suggestion.easier_level.and_then(|index| levels.get(index)) {
+        lines.push(format!(
+            "[{:?}] Try an easier level: '{}'",
+            EASIER_KEY, level_desc.name
+        ));
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(60.0),
+                    left: Val::Px(60.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                lines.join("\n"),
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 28.0,
+                    color: Color::rgb_u8(240, 200, 120),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("SuggestionPanel"))
+        .insert(SuggestionPanel)
+        .insert(suggestion);
+}
+
+/// Retry with the assist or load the easier level suggested on the defeat panel.
+fn suggestion_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    level: Res<Level>,
+    mut game: ResMut<Game>,
+    mut assist: ResMut<Assist>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+    query: Query<&Suggestion, With<SuggestionPanel>>,
+) {
+    if game.sequence() != GameSequence::Defeat {
+        return;
+    }
+    let suggestion = match query.get_single() {
+        Ok(suggestion) => suggestion,
+        Err(_) => return,
+    };
+    if suggestion.assist && keyboard_input.just_pressed(ASSIST_KEY) {
+        info!("Assist enabled for level '{}'", level.name());
+        assist.enable(level.name());
+        ev_restart.send(RestartLevelEvent);
+    } else if let Some(index) = suggestion
+        .easier_level
+        .filter(|_| keyboard_input.just_pressed(EASIER_KEY))
+    {
+        info!("Switching to easier level #{}", index);
+        game.reset_sequence();
+        ev_load_level.send(LoadLevelEvent(LoadLevel::ByIndex(index)));
+    }
+}
+
+/// Hide the suggestion panel along with the defeat panel.
+fn hide_suggestion_panel(
+    mut commands: Commands,
+    game: Res<Game>,
+    query: Query<Entity, With<SuggestionPanel>>,
+) {
+    if game.sequence() == GameSequence::Defeat {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn suggestion_cleanup(mut commands: Commands, query: Query<Entity, With<SuggestionPanel>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the adaptive difficulty, suggesting an easier level or a wider victory margin to
+/// players failing the same level again and again.
+pub struct AssistPlugin;
+
+impl Plugin for AssistPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Assist::load())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Input)
+                    .with_system(suggestion_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Input)
+                    .before(GameplaySystem::Reset)
+                    .with_system(track_restarts),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::VictoryCheck)
+                    .before(GameplaySystem::Ui)
+                    .with_system(track_results),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Reset)
+                    .after(GameplaySystem::Input)
+                    .with_system(apply_assist),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(show_suggestion_panel)
+                    .with_system(hide_suggestion_panel),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(suggestion_cleanup));
+    }
+}
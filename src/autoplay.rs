@@ -0,0 +1,253 @@
+use bevy::{app::AppExit, prelude::*};
+use rand::prelude::*;
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    controls::PlayerAction,
+    game::{run_if_playing, Game, GameEvent, GameMode, GameplaySystem},
+    interlude::InterludePlayer,
+    inventory::Inventory,
+    rng::GameRng,
+    rules::Rules,
+    serialize::{BuildableRegistry, Levels},
+    solver, AppState, Cursor, Grid, Level, PlaceBuildableEvent, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Average delay between two actions of the bot, in seconds.
+const STEP_DELAY: f32 = 0.25;
+
+/// Number of failed attempts at a level before the bot gives up and exits.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Resource holding the state of the bot playing the game on its own.
+pub struct Autoplay {
+    /// Placements left to perform, in order.
+    plan: VecDeque<PlaceBuildableEvent>,
+    /// Delay until the next action.
+    timer: Timer,
+    /// Number of failed attempts at the current level.
+    failures: u32,
+}
+
+impl Autoplay {
+    pub fn new() -> Self {
+        Autoplay {
+            plan: VecDeque::new(),
+            timer: Timer::from_seconds(STEP_DELAY, false),
+            failures: 0,
+        }
+    }
+
+    /// Wait a random human-ish delay before the next action.
+    fn wait(&mut self, rng: &mut GameRng) {
+        let delay = STEP_DELAY * rng.gen_range(0.6..1.4);
+        self.timer.set_duration(Duration::from_secs_f32(delay));
+        self.timer.reset();
+    }
+}
+
+/// Start a solo game as soon as the game data is loaded.
+fn autoplay_start(
+    levels: Res<Levels>,
+    mut game_mode: ResMut<GameMode>,
+    mut state: ResMut<State<AppState>>,
+    mut interludes: ResMut<InterludePlayer>,
+) {
+    if levels.is_empty() {
+        return;
+    }
+    if state.set(AppState::InGame).is_ok() {
+        info!("Autoplay: starting {} levels", levels.len());
+        *game_mode = GameMode::Solo;
+        interludes.set_enabled(false);
+    }
+}
+
+/// Forget the current plan whenever the level starts or restarts, and move on from the recap card
+/// of the levels cleared.
+fn autoplay_reset(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    mut game: ResMut<Game>,
+    mut autoplay: ResMut<Autoplay>,
+    mut rng: ResMut<GameRng>,
+) {
+    let cleared = ev_game
+        .iter()
+        .filter_map(|ev| match ev {
+            GameEvent::LevelCleared { level_index } => Some(*level_index),
+            _ => None,
+        })
+        .last();
+    if let Some(level_index) = cleared {
+        let name = level.desc().map_or("?", |level_desc| &level_desc.name[..]);
+        info!(
+            "Autoplay: level #{} '{}' cleared after {} failed attempts",
+            level_index, name, autoplay.failures
+        );
+        autoplay.failures = 0;
+        game.proceed();
+    }
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if reset || restart {
+        autoplay.plan.clear();
+        autoplay.wait(&mut rng);
+    }
+}
+
+/// Plan the placements of the level with the solver, then move the cursor and place the
+/// buildables one action at a time, like a player would.
+fn autoplay_system(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    buildables: Res<BuildableRegistry>,
+    inventory: Res<Inventory>,
+    mut autoplay: ResMut<Autoplay>,
+    mut rng: ResMut<GameRng>,
+    mut ev_action: EventWriter<PlayerAction>,
+    query: Query<&Cursor>,
+) {
+    if !autoplay.timer.tick(time.delta()).finished() {
+        return;
+    }
+    autoplay.wait(&mut rng);
+    let cursor = query.single();
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+
+    if inventory.is_empty() || !cursor.enabled() {
+        return;
+    }
+
+    // Plan the next placements
+    if autoplay.plan.is_empty() {
+        let victory_margin = rules.victory_margin(level_desc);
+        match solver::solve(
+            &grid,
+            &inventory,
+            &buildables,
+            &level_desc.pivot,
+            victory_margin,
+        ) {
+            Some(steps) => autoplay.plan.extend(steps),
+            None => {
+                warn!(
+                    "Autoplay: no solution for level '{}', placing greedily",
+                    level_desc.name
+                );
+                autoplay.plan.extend(solver::best_placement(
+                    &grid,
+                    &inventory,
+                    &buildables,
+                    &level_desc.pivot,
+                ));
+            }
+        }
+    }
+    let target = match autoplay.plan.front() {
+        Some(target) => *target,
+        None => return,
+    };
+    // Replan if the layout changed in the meantime
+    if !grid.can_spawn_item(&target.pos) {
+        autoplay.plan.clear();
+        return;
+    }
+
+    let pos = cursor.pos();
+    if pos.x != target.pos.x {
+        let delta = IVec2::new((target.pos.x - pos.x).signum(), 0);
+        ev_action.send(PlayerAction::MoveCursor(delta));
+    } else if pos.y != target.pos.y {
+        let delta = IVec2::new(0, (target.pos.y - pos.y).signum());
+        ev_action.send(PlayerAction::MoveCursor(delta));
+    } else if inventory.selected_slot().map(|slot| slot.bref()) != Some(target.bref) {
+        if let Some(index) = inventory
+            .slots()
+            .iter()
+            .position(|slot| slot.bref() == target.bref)
+        {
+            ev_action.send(PlayerAction::SelectSlot(index));
+        } else {
+            autoplay.plan.clear();
+        }
+    } else {
+        ev_action.send(PlayerAction::Place);
+        autoplay.plan.pop_front();
+    }
+}
+
+/// Retry a failed level, up to [`MAX_ATTEMPTS`] times.
+fn autoplay_retry(
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    mut autoplay: ResMut<Autoplay>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+    mut ev_exit: EventWriter<AppExit>,
+) {
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelFailed { .. }))
+        .last()
+        .is_none()
+    {
+        return;
+    }
+    autoplay.failures += 1;
+    warn!(
+        "Autoplay: failed attempt #{} at level '{}'",
+        autoplay.failures,
+        level.name()
+    );
+    if autoplay.failures >= MAX_ATTEMPTS {
+        error!("Autoplay: giving up on level '{}'", level.name());
+        ev_exit.send(AppExit);
+    } else {
+        ev_restart.send(RestartLevelEvent);
+    }
+}
+
+/// Exit once the last level is cleared, ending the run.
+fn autoplay_exit(mut ev_exit: EventWriter<AppExit>) {
+    info!("Autoplay: all levels cleared");
+    ev_exit.send(AppExit);
+}
+
+/// Plugin for a bot playing all the levels on its own at human-ish speed, following the
+/// placements found by the [`solver`]. Used for demos, smoke tests of the full game loop, and
+/// stress testing levels. Enabled with the `autoplay` feature and the `--autoplay` command line
+/// flag.
+pub struct AutoplayPlugin;
+
+impl Plugin for AutoplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Autoplay::new())
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(autoplay_start))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Input)
+                    .with_system(autoplay_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Reset)
+                    .after(GameplaySystem::Input)
+                    .with_system(autoplay_reset),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(autoplay_retry),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::TheEnd).with_system(autoplay_exit));
+    }
+}
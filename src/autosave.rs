@@ -0,0 +1,514 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    boot::UiResources,
+    cheats::Cheats,
+    controls::{button_glyph, key_glyph, ActiveInputDevice, InputDevice},
+    coop::Coop,
+    game::{GameEvent, GameMode, GameplaySystem},
+    inventory::{Inventory, RegenerateInventoryUiEvent, UpdateInventorySlots},
+    journal::LevelJournal,
+    mainmenu::MenuOverlay,
+    rules::Rules,
+    serialize::{BuildableRegistry, Levels},
+    snapshot::{read_snapshot, write_snapshot, LevelSnapshot},
+    storage, AppState, Cursor, Grid, Level, LoadLevel, LoadLevelEvent,
+};
+
+/// Save file of the autosave of the level in progress, in the profile storage.
+pub const AUTOSAVE_FILE: &str = "autosave.json";
+
+/// Number of placements between two autosaves. In hard mode, where the placements can't be
+/// undone, every placement is saved so quitting the game can't be used to undo them.
+const AUTOSAVE_PLACEMENTS: usize = 5;
+
+/// Keys and gamepad buttons to resume or discard the autosave from the prompt.
+const RESUME_KEY: KeyCode = KeyCode::Return;
+const DISCARD_KEY: KeyCode = KeyCode::Escape;
+const RESUME_BUTTON: GamepadButtonType = GamepadButtonType::South;
+const DISCARD_BUTTON: GamepadButtonType = GamepadButtonType::East;
+
+/// Resource tracking the autosave of the level in progress, so a level interrupted by a crash or a
+/// closed tab can be resumed on the next launch. Only the solo levels of the game data are saved.
+#[derive(Debug, Default)]
+pub struct Autosave {
+    /// Number of placements of the level as of the last write.
+    placements: usize,
+    /// Generation of the journal as of the last write, see [`LevelJournal::generation()`].
+    generation: u32,
+    /// Did the level change since the last write?
+    dirty: bool,
+    /// Snapshot to restore once its level is loaded, when resuming from the prompt.
+    resume: Option<LevelSnapshot>,
+    /// Was the level of the snapshot to resume requested, and then loaded?
+    resume_requested: bool,
+    resume_loaded: bool,
+}
+
+impl Autosave {
+    /// Note the state of the journal of the level in progress, and tell if it's due for a write.
+    /// Rewinds and restarts are written straight away, new placements once enough accumulated.
+    fn update(&mut self, placements: usize, generation: u32, interval: usize) -> bool {
+        if placements != self.placements || generation != self.generation {
+            self.dirty = true;
+        }
+        self.dirty
+            && (generation != self.generation
+                || placements < self.placements
+                || placements - self.placements >= interval)
+    }
+
+    /// Write a snapshot of the level in progress, or remove the autosave if nothing was placed.
+    fn write(&mut self, snapshot: &LevelSnapshot, levels: &Levels, buildables: &BuildableRegistry) {
+        self.placements = snapshot.placement_count();
+        self.dirty = false;
+        if self.placements == 0 {
+            Autosave::remove();
+            return;
+        }
+        debug!(
+            "Autosave: level #{} with {} placement(s)",
+            snapshot.level_index(),
+            self.placements
+        );
+        write_snapshot(AUTOSAVE_FILE, snapshot, levels, buildables);
+    }
+
+    /// Forget the level in progress, when a level starts or is cleared.
+    fn reset(&mut self, generation: u32) {
+        self.placements = 0;
+        self.generation = generation;
+        self.dirty = false;
+        Autosave::remove();
+    }
+
+    fn remove() {
+        if let Err(err) = storage::remove(AUTOSAVE_FILE) {
+            let location = storage::location(AUTOSAVE_FILE);
+            warn!("Failed to remove autosave '{}': {}", location, err);
+        }
+    }
+}
+
+/// Is the level in progress one of the game data played solo, which can be resumed by index?
+fn is_resumable(game_mode: GameMode, level: &Level, levels: &Levels) -> bool {
+    game_mode == GameMode::Solo
+        && level.desc().is_some()
+        && levels
+            .get(level.index())
+            .map_or(false, |desc| desc.name == level.name())
+}
+
+/// Autosave the level in progress every few placements, and whenever the window loses the focus,
+/// as the game may be killed or the tab closed without notice from then on.
+fn autosave_level(
+    game_mode: Res<GameMode>,
+    windows: Res<Windows>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    rules: Res<Rules>,
+    inventory: Res<Inventory>,
+    journal: Res<LevelJournal>,
+    cheats: Res<Cheats>,
+    mut autosave: ResMut<Autosave>,
+    query: Query<&Cursor>,
+) {
+    if autosave.resume.is_some() || !is_resumable(*game_mode, &level, &levels) {
+        return;
+    }
+    let interval = if rules.can_undo() {
+        AUTOSAVE_PLACEMENTS
+    } else {
+        1
+    };
+    let due = autosave.update(journal.placements().len(), journal.generation(), interval);
+    let unfocused = !windows
+        .get_primary()
+        .map_or(true, |window| window.is_focused());
+    if !due && !(autosave.dirty && unfocused) {
+        return;
+    }
+    let cursor = query.single();
+    let snapshot = LevelSnapshot::capture(
+        level.index(),
+        &journal,
+        &inventory,
+        cursor.pos(),
+        cheats.is_used(),
+    );
+    autosave.generation = journal.generation();
+    autosave.write(&snapshot, &levels, &buildables);
+}
+
+/// Forget the autosave when a new level starts, and once the level is cleared.
+fn reset_autosave(
+    mut ev_game: EventReader<GameEvent>,
+    journal: Res<LevelJournal>,
+    mut autosave: ResMut<Autosave>,
+) {
+    let reset = ev_game
+        .iter()
+        .filter(|ev| {
+            matches!(
+                ev,
+                GameEvent::LevelLoaded { .. } | GameEvent::LevelCleared { .. }
+            )
+        })
+        .last()
+        .is_some();
+    if reset && autosave.resume.is_none() {
+        autosave.reset(journal.generation());
+    }
+}
+
+/// Save the level in progress when leaving the game, if it changed since the last autosave.
+fn save_on_exit(
+    game_mode: Res<GameMode>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    inventory: Res<Inventory>,
+    journal: Res<LevelJournal>,
+    cheats: Res<Cheats>,
+    mut autosave: ResMut<Autosave>,
+    query: Query<&Cursor>,
+) {
+    autosave.resume = None;
+    if !autosave.dirty || !is_resumable(*game_mode, &level, &levels) {
+        return;
+    }
+    let cursor_pos = query
+        .get_single()
+        .map_or(IVec2::ZERO, |cursor| cursor.pos());
+    let snapshot = LevelSnapshot::capture(
+        level.index(),
+        &journal,
+        &inventory,
+        cursor_pos,
+        cheats.is_used(),
+    );
+    autosave.write(&snapshot, &levels, &buildables);
+}
+
+/// Load the level of the snapshot to resume, then restore the snapshot once the level is ready to
+/// be played.
+fn resume_level(
+    mut commands: Commands,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+    mut ev_game: EventReader<GameEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    mut grid: ResMut<Grid>,
+    level: Res<Level>,
+    buildables: Res<BuildableRegistry>,
+    mut inventory: ResMut<Inventory>,
+    mut coop: ResMut<Coop>,
+    mut journal: ResMut<LevelJournal>,
+    mut cheats: ResMut<Cheats>,
+    mut autosave: ResMut<Autosave>,
+    mut query: Query<(&mut Cursor, &mut Transform, &mut Visibility)>,
+) {
+    let level_index = match &autosave.resume {
+        Some(snapshot) => snapshot.level_index(),
+        None => return,
+    };
+    if !autosave.resume_requested {
+        ev_load_level.send(LoadLevelEvent(LoadLevel::ByIndex(level_index)));
+        autosave.resume_requested = true;
+        return;
+    }
+    // Wait for the requested level, not the first level loaded by default when the game starts
+    if ev_game
+        .iter()
+        .any(|ev| *ev == GameEvent::LevelLoaded { level_index })
+    {
+        autosave.resume_loaded = true;
+    }
+    let (mut cursor, mut transform, mut visibility) = query.single_mut();
+    if !autosave.resume_loaded || !cursor.enabled() || level.index() != level_index {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    let snapshot = autosave.resume.take().unwrap();
+    info!(
+        "Resume level #{} with {} placement(s)",
+        level_index,
+        snapshot.placement_count()
+    );
+    snapshot.restore(
+        &mut commands,
+        &mut grid,
+        cursor.spawn_root_entity,
+        &buildables,
+        &mut inventory,
+        coop.inventory_mut(),
+        &mut journal,
+        level_desc,
+    );
+    if snapshot.is_cheated() {
+        cheats.set_used();
+    }
+    cursor.set_pos(snapshot.cursor_pos(), &grid, &mut transform);
+    visibility.is_visible = !inventory.is_empty();
+    ev_regen_ui.send(RegenerateInventoryUiEvent);
+    ev_update_slots.send(UpdateInventorySlots);
+    // The autosave on disk already matches the restored level
+    autosave.placements = snapshot.placement_count();
+    autosave.generation = journal.generation();
+    autosave.dirty = false;
+}
+
+/// Resource holding the prompt offering to resume the autosave found on launch, if any.
+#[derive(Debug, Default)]
+pub struct ResumePrompt {
+    /// Was the profile storage checked for an autosave yet?
+    checked: bool,
+    /// Snapshot of the autosave, while the prompt is open.
+    snapshot: Option<LevelSnapshot>,
+}
+
+impl ResumePrompt {
+    pub fn is_open(&self) -> bool {
+        self.snapshot.is_some()
+    }
+}
+
+/// Marker for the UI nodes of the resume prompt, kept visible while it's open.
+#[derive(Component)]
+struct ResumePromptUi;
+
+/// Check once on launch for an autosave left by a level in progress, and offer to resume it.
+fn check_autosave(
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    mut prompt: ResMut<ResumePrompt>,
+) {
+    if prompt.checked || levels.is_empty() {
+        return;
+    }
+    prompt.checked = true;
+    prompt.snapshot = read_snapshot(AUTOSAVE_FILE, &levels, &buildables)
+        .filter(|snapshot| snapshot.placement_count() > 0);
+    if let Some(snapshot) = &prompt.snapshot {
+        info!(
+            "Found autosave of level #{} with {} placement(s)",
+            snapshot.level_index(),
+            snapshot.placement_count()
+        );
+    }
+}
+
+/// Resume or discard the autosave from the prompt. Runs right after the input is updated, to hide
+/// the keys from the rest of the menu while the prompt is open.
+fn resume_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut gamepad_input: ResMut<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    mut prompt: ResMut<ResumePrompt>,
+    mut autosave: ResMut<Autosave>,
+    mut game_mode: ResMut<GameMode>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !prompt.is_open() {
+        return;
+    }
+    let button_pressed = |button_type| {
+        gamepads
+            .iter()
+            .any(|gamepad| gamepad_input.just_pressed(GamepadButton(*gamepad, button_type)))
+    };
+    if keyboard_input.just_pressed(RESUME_KEY) || button_pressed(RESUME_BUTTON) {
+        if state.set(AppState::InGame).is_ok() {
+            *game_mode = GameMode::Solo;
+            *autosave = Autosave {
+                resume: prompt.snapshot.take(),
+                ..Default::default()
+            };
+        }
+    } else if keyboard_input.just_pressed(DISCARD_KEY) || button_pressed(DISCARD_BUTTON) {
+        info!("Discarded autosave");
+        prompt.snapshot = None;
+        Autosave::remove();
+    }
+    // Keep the keys and buttons from also triggering the menu entries
+    keyboard_input.clear();
+    gamepad_input.clear();
+}
+
+/// Choices of the resume prompt, with the glyphs of a device.
+fn prompt_choices(device: InputDevice) -> String {
+    let (resume, discard) = match device {
+        InputDevice::Gamepad => (
+            button_glyph(RESUME_BUTTON).to_owned(),
+            button_glyph(DISCARD_BUTTON).to_owned(),
+        ),
+        InputDevice::Keyboard | InputDevice::Touch => {
+            (key_glyph(RESUME_KEY), key_glyph(DISCARD_KEY))
+        }
+    };
+    format!("{} Resume   {} Discard", resume, discard)
+}
+
+/// Show the resume prompt, with the level of the autosave and its progress.
+fn update_resume_prompt(
+    mut commands: Commands,
+    prompt: Res<ResumePrompt>,
+    device: Res<ActiveInputDevice>,
+    levels: Res<Levels>,
+    ui_resources: Res<UiResources>,
+    panel_query: Query<Entity, (With<ResumePromptUi>, Without<Parent>)>,
+) {
+    if !prompt.is_changed() && !device.is_changed() {
+        return;
+    }
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let snapshot = match &prompt.snapshot {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    let level_name = levels
+        .get(snapshot.level_index())
+        .map_or("", |desc| &desc.name[..]);
+    let style = |font_size, color| TextStyle {
+        font: ui_resources.text_font(),
+        font_size,
+        color,
+    };
+    let sections = [
+        "Resume where you left off?".to_owned(),
+        format!(
+            "\n\n{} - {} buildable(s) placed",
+            level_name,
+            snapshot.placement_count()
+        ),
+        format!("\n\n{}", prompt_choices(device.0)),
+    ]
+    .into_iter()
+    .zip([
+        (36.0, Color::WHITE),
+        (24.0, Color::rgb_u8(255, 220, 120)),
+        (20.0, Color::WHITE),
+    ])
+    .map(|(value, (font_size, color))| TextSection {
+        value,
+        style: style(font_size, color),
+    })
+    .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("ResumePrompt"))
+        .insert(MenuOverlay)
+        .insert(ResumePromptUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .insert(ResumePromptUi);
+        });
+}
+
+fn resume_prompt_cleanup(
+    mut commands: Commands,
+    mut prompt: ResMut<ResumePrompt>,
+    panel_query: Query<Entity, (With<ResumePromptUi>, Without<Parent>)>,
+) {
+    prompt.snapshot = None;
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin autosaving the solo level in progress every few placements and when leaving the game,
+/// and offering to resume it on the next launch if the game was closed or crashed mid-level.
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Autosave::default())
+            .insert_resource(ResumePrompt::default())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Placement)
+                    .with_system(resume_level.before(autosave_level))
+                    .with_system(autosave_level)
+                    .with_system(reset_autosave.before(autosave_level)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(save_on_exit))
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(check_autosave))
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(AppState::MainMenu)
+                    .after(InputSystem)
+                    .after("profile_menu_input")
+                    .before("stats_input")
+                    .before("calendar_input")
+                    .before("encyclopedia_input")
+                    .with_system(resume_input.label("resume_input")),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu).with_system(update_resume_prompt),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(resume_prompt_cleanup),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_flag() {
+        let mut autosave = Autosave::default();
+        assert!(!autosave.update(0, 0, 5));
+        assert!(!autosave.dirty);
+        assert!(!autosave.update(3, 0, 5));
+        assert!(autosave.dirty);
+        assert!(autosave.update(5, 0, 5));
+        autosave.placements = 5;
+        autosave.dirty = false;
+        assert!(!autosave.update(5, 0, 5));
+        // Rewinds and restarts are saved straight away
+        assert!(autosave.update(0, 0, 5));
+        assert!(autosave.update(4, 1, 5));
+        autosave.placements = 4;
+        autosave.generation = 1;
+        autosave.dirty = false;
+        // Every placement is saved in hard mode
+        assert!(autosave.update(5, 1, 1));
+    }
+
+    #[test]
+    fn prompt() {
+        assert_eq!(
+            prompt_choices(InputDevice::Gamepad),
+            "(A) Resume   (B) Discard"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use crate::{
+    game::GameplaySystem,
+    rules::Rules,
+    serialize::LevelDesc,
+    tilt::{self, Pivot, TiltModel},
+    AppState, Grid, Level,
+};
+
+/// Resource holding a snapshot of the balance of the plate, updated once per frame after the
+/// placements, so that the plate animation, the UI, the audio, and the gameplay checks all read
+/// the same values instead of each recalculating them from the [`Grid`].
+///
+/// The resource is only mutated when the balance actually changes, so consumers can rely on
+/// change detection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceState {
+    /// Sum of the weights of the buildables times their lever position, as calculated by
+    /// [`Grid::calc_cog_offset`].
+    pub cog: Vec2,
+    /// Offset of the COG from the pivot, which is zero when the plate is balanced.
+    pub offset: Vec2,
+    /// Tilt angles of the plate toward the X and Y axes of the grid, in radians.
+    pub tilt_angles: Vec2,
+    /// Magnitude of the tilt angle, in radians, or of the most tilted plate of a seesaw level.
+    pub tilt: f32,
+    /// Rotation of the plate. The plates of a seesaw tilt on their own, so this is the identity
+    /// on seesaw levels.
+    pub rotation: Quat,
+    /// Victory margin of the level, with the current rules.
+    pub victory_margin: f32,
+    /// Offset of the COG from the pivot relative to the victory margin, or the largest one of
+    /// the plates of a seesaw level. The plate is balanced under 1.
+    pub margin_fraction: f32,
+    /// Digest of the weights of all the grid cells; see [`Grid::weights_digest`].
+    pub weights_digest: u64,
+}
+
+impl Default for BalanceState {
+    fn default() -> Self {
+        BalanceState {
+            cog: Vec2::ZERO,
+            offset: Vec2::ZERO,
+            tilt_angles: Vec2::ZERO,
+            tilt: 0.0,
+            rotation: Quat::IDENTITY,
+            victory_margin: 0.0,
+            margin_fraction: 0.0,
+            weights_digest: 0,
+        }
+    }
+}
+
+impl BalanceState {
+    /// Balance of a single plate.
+    pub fn new(
+        grid: &Grid,
+        balance_factor: f32,
+        pivot: &Pivot,
+        tilt_model: &TiltModel,
+        victory_margin: f32,
+    ) -> Self {
+        let cog = grid.calc_cog_offset(balance_factor);
+        let offset = pivot.balance_offset(cog, grid.total_weight());
+        let tilt_angles = tilt_model.angles(offset, balance_factor);
+        BalanceState {
+            cog,
+            offset,
+            tilt_angles,
+            tilt: tilt_angles.length(),
+            rotation: tilt::rotation(tilt_angles),
+            victory_margin,
+            margin_fraction: offset.length() / victory_margin,
+            weights_digest: grid.weights_digest(),
+        }
+    }
+
+    /// Balance of the plate of a level, or of both plates of a seesaw level.
+    pub fn from_level(grid: &Grid, level_desc: &LevelDesc, victory_margin: f32) -> Self {
+        let mut state = BalanceState::new(
+            grid,
+            level_desc.balance_factor,
+            &level_desc.pivot,
+            &level_desc.tilt_model,
+            victory_margin,
+        );
+        if let Some(seesaw) = &level_desc.seesaw {
+            let offset = seesaw
+                .balance_offsets(grid)
+                .iter()
+                .map(|offset| offset.length())
+                .fold(0.0, f32::max);
+            state.tilt = level_desc.calc_tilt(grid);
+            state.rotation = Quat::IDENTITY;
+            state.margin_fraction = offset / victory_margin;
+        }
+        state
+    }
+
+    /// Is the plate balanced within the victory margin?
+    pub fn is_balanced(&self) -> bool {
+        self.margin_fraction < 1.0
+    }
+}
+
+fn update_balance_state(
+    grid: Res<Grid>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    mut balance: ResMut<BalanceState>,
+) {
+    let state = match level.desc() {
+        Some(level_desc) => {
+            BalanceState::from_level(&grid, level_desc, rules.victory_margin(level_desc))
+        }
+        None => BalanceState::default(),
+    };
+    if *balance != state {
+        *balance = state;
+    }
+}
+
+fn balance_cleanup(mut balance: ResMut<BalanceState>) {
+    *balance = BalanceState::default();
+}
+
+/// Plugin updating the [`BalanceState`] snapshot each frame.
+pub struct BalancePlugin;
+
+impl Plugin for BalancePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BalanceState::default())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Balance)
+                    .after(GameplaySystem::Placement)
+                    .with_system(update_balance_state.label("balance_state")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(balance_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inventory::Buildable, serialize::BuildableRegistry};
+
+    #[test]
+    fn snapshot() {
+        let mut grid = Grid::new();
+        grid.set_size(&IVec2::new(3, 3));
+        let bref = BuildableRegistry::new().register("hut", Buildable::without_assets("hut", 1.0));
+        let pivot = Pivot::default();
+        let tilt_model = TiltModel::default();
+        let empty = BalanceState::new(&grid, 1.0, &pivot, &tilt_model, 0.5);
+        assert_eq!(empty.offset, Vec2::ZERO);
+        assert!(empty.is_balanced());
+
+        grid.spawn_item(&IVec2::new(1, 0), bref, 1.0, Entity::from_raw(0));
+        let state = BalanceState::new(&grid, 1.0, &pivot, &tilt_model, 0.5);
+        assert_eq!(state.cog, grid.calc_cog_offset(1.0));
+        assert_eq!(state.offset, grid.calc_balance_offset(1.0, &pivot));
+        assert_eq!(state.tilt, grid.calc_tilt(1.0, &pivot, &tilt_model));
+        assert_eq!(state.rotation, grid.calc_rot(1.0, &pivot, &tilt_model));
+        assert_eq!(state.is_balanced(), grid.is_victory(1.0, &pivot, 0.5));
+        assert!(!state.is_balanced());
+        assert_ne!(state.weights_digest, empty.weights_digest);
+    }
+}
@@ -1,5 +1,9 @@
-use crate::{loader::Loader, text_asset::TextAsset, AppState, Config};
+use crate::{
+    anim::AnimationLibrary, config::BaseConfig, fallback::fallback_font, loader::Loader, profile,
+    sfx::SfxTable, text_asset::TextAsset, ui_layout::UiLayouts, AppState, Config,
+};
 use bevy::{
+    asset::LoadState,
     prelude::*,
     reflect::TypeUuid,
     render::{camera::OrthographicProjection, mesh::shape},
@@ -200,6 +204,9 @@ fn boot_setup(
     // Create the loader component itself, and enqueue all asset loading requests
     let mut loader = Loader::new();
     loader.enqueue("config.json");
+    loader.enqueue("animations.json");
+    loader.enqueue("ui_layouts.json");
+    loader.enqueue("audio/sfx.json");
     loader.enqueue("fonts/pacifico/Pacifico-Regular.ttf");
     loader.enqueue("fonts/mochiy_pop_one/MochiyPopOne-Regular.ttf");
     loader.submit();
@@ -218,8 +225,13 @@ fn boot(
     asset_server: Res<AssetServer>,
     text_assets: Res<Assets<TextAsset>>,
     mut config: ResMut<Config>,
+    mut base_config: ResMut<BaseConfig>,
+    mut animations: ResMut<AnimationLibrary>,
+    mut ui_layouts: ResMut<UiLayouts>,
+    mut sfx_table: ResMut<SfxTable>,
     mut query: Query<(Entity, &mut Loader, &mut Boot)>,
     mut ui_resouces: ResMut<UiResources>,
+    mut fonts: ResMut<Assets<Font>>,
     mut state: ResMut<State<AppState>>,
     mut shader_query: Query<(&mut Sprite, &mut ProgressBarUniform)>,
 ) {
@@ -233,13 +245,51 @@ fn boot(
             commands.entity(*id).despawn();
         }
 
-        // Assign the loaded config if any
+        // Assign the loaded config if any, with the settings of the save profile over it
         if let Some(handle) = loader.take("config.json") {
             let handle = handle.typed::<TextAsset>();
             // The Loader completes when the asset is successfully loaded, or cannot be loaded.
             // Since this is a config file, and is therefore optional, it may not exist.
-            if let Some(json_config) = text_assets.get(handle) {
-                *config = Config::from_json(&json_config.value[..]).unwrap();
+            base_config.0 = text_assets
+                .get(handle)
+                .map(|json_config| json_config.value.clone());
+        }
+        match Config::from_json_with_overrides(base_config.0.as_deref(), &profile::settings()) {
+            Ok(json_config) => *config = json_config,
+            Err(err) => error!("Failed to load config.json, using defaults: {:?}", err),
+        }
+
+        // Assign the animations, which are needed by the main menu already
+        if let Some(handle) = loader.take("animations.json") {
+            let handle = handle.typed::<TextAsset>();
+            if let Some(json_animations) = text_assets.get(handle) {
+                match AnimationLibrary::from_json(&json_animations.value[..]) {
+                    Ok(library) => *animations = library,
+                    Err(err) => error!("Failed to parse animations.json: {}", err),
+                }
+            }
+        }
+
+        // Assign the UI layouts, keeping the built-in ones if the file is missing or invalid
+        if let Some(handle) = loader.take("ui_layouts.json") {
+            let handle = handle.typed::<TextAsset>();
+            if let Some(json_layouts) = text_assets.get(&handle) {
+                match UiLayouts::from_json(&json_layouts.value[..]) {
+                    Ok(layouts) => *ui_layouts = layouts.with_handle(handle),
+                    Err(err) => error!("Failed to parse ui_layouts.json: {}", err),
+                }
+            }
+        }
+
+        // Assign the sound effects table, keeping the built-in one if the file is missing or
+        // invalid
+        if let Some(handle) = loader.take("audio/sfx.json") {
+            let handle = handle.typed::<TextAsset>();
+            if let Some(json_table) = text_assets.get(&handle) {
+                match SfxTable::from_json(&json_table.value[..]) {
+                    Ok(table) => *sfx_table = table.with_handle(handle),
+                    Err(err) => error!("Failed to parse audio/sfx.json: {}", err),
+                }
             }
         }
 
@@ -247,15 +297,18 @@ fn boot(
         // boot sequence to allow user interaction and optionally continue loading some other
         // assets, but this time with a basic set of assets (fonts, notably) already loaded,
         // allowing to render some less terse user interface than a single progress bar without
-        // any text.
-        let title_font: Handle<Font> = loader
-            .take("fonts/pacifico/Pacifico-Regular.ttf")
-            .unwrap()
-            .typed::<Font>();
-        let text_font: Handle<Font> = loader
-            .take("fonts/mochiy_pop_one/MochiyPopOne-Regular.ttf")
-            .unwrap()
-            .typed::<Font>();
+        // any text. Fall back to the font embedded in the binary for the fonts failing to load.
+        let mut take_font = |path| {
+            let handle = loader.take(path).unwrap().typed::<Font>();
+            if asset_server.get_load_state(&handle) == LoadState::Failed {
+                error!("Failed to load {}, using the built-in font", path);
+                fallback_font(&mut fonts)
+            } else {
+                handle
+            }
+        };
+        let title_font = take_font("fonts/pacifico/Pacifico-Regular.ttf");
+        let text_font = take_font("fonts/mochiy_pop_one/MochiyPopOne-Regular.ttf");
         *ui_resouces = UiResources {
             title_font,
             text_font,
@@ -282,6 +335,7 @@ pub struct BootPlugin;
 impl Plugin for BootPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Config::default())
+            .insert_resource(BaseConfig::default())
             .insert_resource(UiResources::new())
             .add_startup_system(boot_setup)
             .add_system_set(SystemSet::on_update(AppState::Boot).with_system(boot));
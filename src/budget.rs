@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources, cinematic::Hud, game::GameplaySystem, inventory::Inventory,
+    market::BuildQueue, rules::Rules, serialize::BuildableRegistry, solver, units::format_weight,
+    AppState, Grid, Level,
+};
+
+/// Color of the weight budget while a balanced layout may still be reachable.
+const BUDGET_COLOR: Color = Color::rgb(0.85, 0.85, 0.85);
+
+/// Color of the warning once a balanced layout is out of reach.
+const WARNING_COLOR: Color = Color::rgb(0.9, 0.47, 0.43);
+
+/// Estimate of the weight budget of the level being played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WeightBudget {
+    /// Total weight of the buildables left in the inventory.
+    remaining: f32,
+    /// Least weight needed to bring the plate back within the victory margin.
+    needed: f32,
+    /// Can more buildables still be added to the inventory, by a delivery or the market?
+    more_to_come: bool,
+}
+
+impl WeightBudget {
+    /// Is a balanced layout out of reach with what's left of the inventory?
+    fn is_out_of_reach(&self) -> bool {
+        !self.more_to_come && self.remaining < self.needed
+    }
+}
+
+/// Marker for the text of the weight budget.
+#[derive(Component)]
+struct BudgetText;
+
+fn spawn_budget_text(mut commands: Commands, ui_resources: Res<UiResources>) {
+    let style = TextStyle {
+        font: ui_resources.text_font(),
+        font_size: 24.0,
+        color: BUDGET_COLOR,
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(20.0),
+                    right: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: String::new(),
+                        style: style.clone(),
+                    },
+                    TextSection {
+                        value: String::new(),
+                        style: TextStyle {
+                            color: WARNING_COLOR,
+                            ..style
+                        },
+                    },
+                ],
+                alignment: TextAlignment::default(),
+            },
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Name::new("BudgetText"))
+        .insert(Hud)
+        .insert(BudgetText);
+}
+
+/// Update the weight budget whenever a buildable is placed or the inventory changes, and warn
+/// once the plate cannot be balanced anymore with what's left.
+fn update_budget_text(
+    grid: Res<Grid>,
+    inventory: Res<Inventory>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    buildables: Res<BuildableRegistry>,
+    build_queue: Res<BuildQueue>,
+    mut query: Query<(&mut Text, &mut Visibility), With<BudgetText>>,
+) {
+    if !grid.is_changed() && !inventory.is_changed() && !rules.is_changed() {
+        return;
+    }
+    let budget = level.desc().map(|level_desc| WeightBudget {
+        remaining: solver::remaining_weight(&inventory, &buildables),
+        needed: solver::min_balancing_weight(
+            &grid,
+            &level_desc.pivot,
+            rules.victory_margin(level_desc),
+        ),
+        more_to_come: inventory.has_pending_deliveries() || build_queue.upcoming().next().is_some(),
+    });
+    for (mut text, mut visibility) in query.iter_mut() {
+        // Hidden weights are for the player to figure out
+        let budget = match budget.filter(|_| !rules.hidden_weights) {
+            Some(budget) => budget,
+            None => {
+                visibility.is_visible = false;
+                continue;
+            }
+        };
+        let unit = level.weight_unit();
+        text.sections[0].value = if budget.needed.is_finite() {
+            format!(
+                "Weight left: {} / needed: {}",
+                format_weight(budget.remaining, unit),
+                format_weight(budget.needed, unit)
+            )
+        } else {
+            format!("Weight left: {}", format_weight(budget.remaining, unit))
+        };
+        text.sections[1].value = if budget.is_out_of_reach() {
+            "\nOut of balance for good, press [R] to restart".to_owned()
+        } else {
+            String::new()
+        };
+        visibility.is_visible = true;
+    }
+    if let Some(budget) = budget.filter(WeightBudget::is_out_of_reach) {
+        trace!(
+            "Weight budget out of reach: {:.2} left < {:.2} needed",
+            budget.remaining,
+            budget.needed
+        );
+    }
+}
+
+fn budget_cleanup(mut commands: Commands, query: Query<Entity, With<BudgetText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the HUD summary of the weight left in the inventory against the weight needed to
+/// balance the plate, warning early when the level cannot be solved anymore.
+pub struct WeightBudgetPlugin;
+
+impl Plugin for WeightBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_budget_text))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_budget_text),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(budget_cleanup));
+    }
+}
@@ -0,0 +1,329 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    game::{run_if_playing, GameMode, GameplaySystem},
+    ghost::{ReplayAction, ReplayFrame, ReplayRecorder},
+    interlude::InterludePlayer,
+    layout::GridLayout,
+    rng::GameRng,
+    scores::ScoreTracker,
+    serialize::BuildableRegistry,
+    AppState, Cursor, CursorId, Grid, Level, LoadLevel, LoadLevelEvent, PlaceBuildableEvent,
+    ResetPlateEvent, RestartLevelEvent,
+};
+
+/// File the bug report is written to on native platforms, in the data folder.
+#[cfg(not(target_arch = "wasm32"))]
+const BUG_REPORT_FILE: &str = "bug_report.json";
+
+/// Duration of the recorded inputs bundled in a bug report, in seconds of level play time.
+const RECORDING_SECONDS: f32 = 60.0;
+
+/// Bundle of everything needed to reproduce a bug in a level: the level, the config, the recent
+/// player actions, and the grid layout they led to. Replayed with `--replay <file>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BugReport {
+    /// Version of the game the report was made with.
+    pub version: String,
+    pub mode: GameMode,
+    /// Name of the level.
+    pub level: String,
+    /// Config of the game, as JSON.
+    pub config: serde_json::Value,
+    /// Seed of the session, to draw the same random values. See [`GameRng`].
+    #[serde(default)]
+    pub seed: u64,
+    /// Player actions, with their time relative to the start of the recording. The recording
+    /// starts from an empty plate, either at the level start or at a restart.
+    pub frames: Vec<ReplayFrame>,
+    /// Grid layout when the report was made, expected at the end of the replay.
+    pub layout: GridLayout,
+}
+
+impl BugReport {
+    /// Load a bug report from a file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> anyhow::Result<BugReport> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+/// Recorded frames covering at least the last `seconds` of play time before the last action,
+/// starting from an empty plate so they replay deterministically. The frame times are rebased on
+/// the first frame kept.
+fn recent_frames(frames: &[ReplayFrame], seconds: f32) -> Vec<ReplayFrame> {
+    let cutoff = frames.last().map_or(0.0, |frame| frame.time) - seconds;
+    let (start, start_time) = frames
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, frame)| frame.time <= cutoff && matches!(frame.action, ReplayAction::Restart))
+        .map_or((0, 0.0), |(index, frame)| (index + 1, frame.time));
+    frames[start..]
+        .iter()
+        .map(|frame| ReplayFrame {
+            time: frame.time - start_time,
+            action: frame.action.clone(),
+        })
+        .collect()
+}
+
+/// Export the report for the player to attach to a bug report. Returns a status message.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_report(json: &str) -> String {
+    let path = crate::paths::data_file(BUG_REPORT_FILE);
+    match std::fs::write(&path, json) {
+        Ok(()) => format!("Bug report saved to '{}'.", path.display()),
+        Err(err) => format!(
+            "Failed to save the bug report to '{}': {}",
+            path.display(),
+            err
+        ),
+    }
+}
+
+/// Copy the report to the browser clipboard, which `web-sys` only exposes with unstable APIs, so
+/// call it dynamically. The report is also printed to the browser console, in case the clipboard
+/// is not available.
+#[cfg(target_arch = "wasm32")]
+fn export_report(json: &str) -> String {
+    use wasm_bindgen::JsCast;
+    info!("Bug report:\n{}", json);
+    let copied = web_sys::window()
+        .and_then(|window| js_sys::Reflect::get(&window.navigator(), &"clipboard".into()).ok())
+        .and_then(|clipboard| {
+            let write_text = js_sys::Reflect::get(&clipboard, &"writeText".into()).ok()?;
+            let write_text: js_sys::Function = write_text.dyn_into().ok()?;
+            write_text.call1(&clipboard, &json.into()).ok()
+        })
+        .is_some();
+    if copied {
+        "Bug report copied to the clipboard.".to_owned()
+    } else {
+        "Bug report printed to the browser console.".to_owned()
+    }
+}
+
+/// Bundle the current level, config, recent inputs, and grid layout into a bug report on F8.
+fn report_bug(
+    keyboard_input: Res<Input<KeyCode>>,
+    config: Res<Config>,
+    game_mode: Res<GameMode>,
+    level: Res<Level>,
+    grid: Res<Grid>,
+    buildables: Res<BuildableRegistry>,
+    recorder: Res<ReplayRecorder>,
+    rng: Res<GameRng>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F8) || level.desc().is_none() {
+        return;
+    }
+    let config = match serde_json::to_value(&*config) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("Failed to serialize the config for the bug report: {}", err);
+            return;
+        }
+    };
+    let report = BugReport {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        mode: *game_mode,
+        level: level.name().to_owned(),
+        config,
+        seed: rng.seed(),
+        frames: recent_frames(recorder.frames(), RECORDING_SECONDS),
+        layout: grid.to_layout(&buildables),
+    };
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => info!("{}", export_report(&json)),
+        Err(err) => warn!("Failed to serialize the bug report: {}", err),
+    }
+}
+
+/// Resource driving the replay of a bug report.
+pub struct BugReplay {
+    report: BugReport,
+    /// Was the level of the report requested?
+    level_requested: bool,
+    /// Is the level of the report started and being replayed?
+    playing: bool,
+    /// Index of the next frame to replay.
+    next_frame: usize,
+}
+
+impl BugReplay {
+    pub fn new(report: BugReport) -> Self {
+        BugReplay {
+            report,
+            level_requested: false,
+            playing: false,
+            next_frame: 0,
+        }
+    }
+}
+
+/// Apply the config and the seed of the report and start the game in its mode, as soon as the
+/// game data is loaded.
+fn replay_start(
+    mut config: ResMut<Config>,
+    mut rng: ResMut<GameRng>,
+    mut game_mode: ResMut<GameMode>,
+    mut state: ResMut<State<AppState>>,
+    mut interludes: ResMut<InterludePlayer>,
+    replay: Res<BugReplay>,
+) {
+    if replay.level_requested {
+        return;
+    }
+    match serde_json::from_value(replay.report.config.clone()) {
+        Ok(report_config) => *config = report_config,
+        Err(err) => warn!(
+            "Bug replay: failed to apply the config of the report: {}",
+            err
+        ),
+    }
+    if state.set(AppState::InGame).is_ok() {
+        info!(
+            "Bug replay: level '{}' in {:?} mode, {} actions",
+            replay.report.level,
+            replay.report.mode,
+            replay.report.frames.len()
+        );
+        *game_mode = replay.report.mode;
+        rng.set_seed(replay.report.seed);
+        interludes.set_enabled(false);
+    }
+}
+
+/// Load the level of the report, and start replaying once it started.
+fn replay_level(
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    mut replay: ResMut<BugReplay>,
+) {
+    if !replay.level_requested {
+        ev_load_level.send(LoadLevelEvent(LoadLevel::ByName(
+            replay.report.level.clone(),
+        )));
+        replay.level_requested = true;
+        return;
+    }
+    if ev_reset_plate.iter().last().is_some() && level.name() == replay.report.level {
+        replay.playing = true;
+        replay.next_frame = 0;
+    }
+}
+
+/// Replay the actions of the report in sync with the level play time, then check the layout
+/// they led to.
+fn replay_actions(
+    mut ev_place: EventWriter<PlaceBuildableEvent>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+    grid: Res<Grid>,
+    buildables: Res<BuildableRegistry>,
+    tracker: Res<ScoreTracker>,
+    mut replay: ResMut<BugReplay>,
+    mut query: Query<(&mut Cursor, &mut Transform)>,
+) {
+    if !replay.playing {
+        return;
+    }
+    let replay = &mut *replay;
+    let (mut cursor, mut transform) = query.single_mut();
+    while let Some(frame) = replay.report.frames.get(replay.next_frame) {
+        if frame.time > tracker.time() {
+            return;
+        }
+        replay.next_frame += 1;
+        match &frame.action {
+            ReplayAction::Move(pos) => cursor.set_pos(*pos, &grid, &mut transform),
+            ReplayAction::Place(pos, name) | ReplayAction::PartnerPlace(pos, name) => {
+                let cursor = match frame.action {
+                    ReplayAction::PartnerPlace(..) => CursorId::Partner,
+                    _ => CursorId::Player,
+                };
+                match buildables.id(name) {
+                    Some(bref) => ev_place.send(PlaceBuildableEvent {
+                        pos: *pos,
+                        bref,
+                        cursor,
+                    }),
+                    None => warn!("Bug replay: unknown buildable '{}'", name),
+                }
+            }
+            ReplayAction::Restart => ev_restart.send(RestartLevelEvent),
+        }
+    }
+    replay.playing = false;
+    if grid.to_layout(&buildables) == replay.report.layout {
+        info!("Bug replay: done, the layout matches the report");
+    } else {
+        warn!("Bug replay: done, but the layout differs from the report");
+    }
+}
+
+/// Plugin bundling the current level, config, recent inputs, and grid layout into a bug report
+/// on F8, written to a file on native platforms and copied to the clipboard on the web.
+pub struct BugReportPlugin;
+
+impl Plugin for BugReportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(report_bug));
+    }
+}
+
+/// Plugin replaying a [`BugReport`], to reproduce a bug deterministically. Enabled with the
+/// `--replay <file>` command line argument.
+pub struct BugReplayPlugin(pub BugReport);
+
+impl Plugin for BugReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BugReplay::new(self.0.clone()))
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(replay_start))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Reset)
+                    .after(GameplaySystem::Input)
+                    .with_system(replay_level),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Input)
+                    .with_system(replay_actions),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(time: f32, action: ReplayAction) -> ReplayFrame {
+        ReplayFrame { time, action }
+    }
+
+    #[test]
+    fn recent_frames_start_from_empty_plate() {
+        let frames = vec![
+            frame(1.0, ReplayAction::Move(IVec2::new(1, 0))),
+            frame(2.0, ReplayAction::Restart),
+            frame(3.0, ReplayAction::Move(IVec2::new(0, 1))),
+            frame(50.0, ReplayAction::Restart),
+            frame(90.0, ReplayAction::Place(IVec2::ZERO, "hut".to_owned())),
+        ];
+
+        // Everything fits in the window
+        assert_eq!(recent_frames(&frames, 100.0).len(), 5);
+
+        // Back to the last restart before the window
+        let recent = recent_frames(&frames, 50.0);
+        assert_eq!(recent.len(), 3);
+        assert!((recent[0].time - 1.0).abs() < 1e-5);
+        assert!((recent[2].time - 88.0).abs() < 1e-5);
+    }
+}
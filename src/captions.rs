@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    sfx::{PlaySfxEvent, SfxTable},
+    ui_layout::UiLayouts,
+    AppState, Config,
+};
+
+/// Time in seconds a caption stays on screen.
+const CAPTION_DURATION: f32 = 2.5;
+
+/// Maximum number of captions on screen at once. Older ones hide first.
+const MAX_CAPTIONS: usize = 3;
+
+/// Captions shown for the sounds played recently, oldest first, with their time left on screen.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CaptionLines(Vec<(String, f32)>);
+
+impl CaptionLines {
+    pub fn new() -> Self {
+        CaptionLines::default()
+    }
+
+    /// Show a caption. A caption already shown moves to the end instead of showing twice, like
+    /// for a sound repeated quickly.
+    pub fn push(&mut self, caption: &str) {
+        self.0.retain(|(text, _)| text != caption);
+        self.0.push((caption.to_owned(), CAPTION_DURATION));
+        if self.0.len() > MAX_CAPTIONS {
+            self.0.remove(0);
+        }
+    }
+
+    /// Advance the time by `dt` seconds, hiding the captions whose time is over. Returns `true`
+    /// if any caption hid.
+    pub fn tick(&mut self, dt: f32) -> bool {
+        let count = self.0.len();
+        for (_, time_left) in self.0.iter_mut() {
+            *time_left -= dt;
+        }
+        self.0.retain(|(_, time_left)| *time_left > 0.0);
+        self.0.len() != count
+    }
+
+    /// Text displaying the captions, one per line.
+    pub fn text(&self) -> String {
+        self.0
+            .iter()
+            .map(|(text, _)| &text[..])
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Text displaying the captions of the sounds played, when enabled.
+#[derive(Component)]
+struct CaptionText(CaptionLines);
+
+fn spawn_captions(
+    mut commands: Commands,
+    ui_resources: Res<UiResources>,
+    ui_layouts: Res<UiLayouts>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: ui_layouts.metric("captions.font_size"),
+                    color: ui_layouts.color("captions.text"),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("Captions"))
+        .insert(Hud)
+        .insert(CaptionText(CaptionLines::new()));
+}
+
+/// Caption the sounds played which have a caption in the [`SfxTable`], when the `captions` config
+/// is set. Captions show even with the sound disabled, for deaf and hard-of-hearing players.
+fn update_captions(
+    time: Res<Time>,
+    config: Res<Config>,
+    table: Res<SfxTable>,
+    mut ev_play_sfx: EventReader<PlaySfxEvent>,
+    mut query: Query<(&mut Text, &mut CaptionText)>,
+) {
+    for (mut text, mut caption_text) in query.iter_mut() {
+        let lines = &mut caption_text.0;
+        let mut changed = lines.tick(time.delta_seconds());
+        if config.captions {
+            for ev in ev_play_sfx.iter() {
+                if let Some(caption) = table.get(ev.0).and_then(|desc| desc.caption.as_ref()) {
+                    lines.push(caption.get(&config.language));
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            text.sections[0].value = lines.text();
+        }
+    }
+}
+
+fn captions_cleanup(mut commands: Commands, query: Query<Entity, With<CaptionText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin showing captions for the gameplay sounds, like `[chime: balanced]`, as an accessibility
+/// option. See the `captions` config.
+pub struct CaptionsPlugin;
+
+impl Plugin for CaptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_captions))
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(update_captions))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(captions_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let mut lines = CaptionLines::new();
+        lines.push("[thud]");
+        lines.push("[buzz]");
+        assert_eq!(lines.text(), "[thud]\n[buzz]");
+        // Repeated captions move to the end
+        lines.push("[thud]");
+        assert_eq!(lines.text(), "[buzz]\n[thud]");
+        lines.push("[chime]");
+        lines.push("[creak]");
+        assert_eq!(lines.text(), "[thud]\n[chime]\n[creak]");
+
+        assert!(!lines.tick(CAPTION_DURATION * 0.5));
+        lines.push("[thud]");
+        assert!(lines.tick(CAPTION_DURATION * 0.6));
+        assert_eq!(lines.text(), "[thud]");
+    }
+}
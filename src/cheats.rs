@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+
+use crate::{
+    config::Config,
+    game::{run_if_playing, Game, GameplaySystem},
+    inventory::{Inventory, UpdateInventorySlots},
+    journal::{LevelJournal, LevelOp},
+    level::{LoadLevel, LoadLevelEvent},
+    rules::Rules,
+    serialize::{BuildableRegistry, Levels},
+    solver, AppState, Grid, Level, PlaceBuildableEvent, ResetPlateEvent,
+};
+
+/// Number of buildables each slot is kept topped up to with the infinite inventory cheat.
+const INFINITE_COUNT: u32 = 99;
+
+/// Resource tracking the developer cheats used on the current level.
+#[derive(Debug, Default)]
+pub struct Cheats {
+    /// Was any cheat used since the level started?
+    used: bool,
+    /// Keep all inventory slots topped up.
+    infinite_inventory: bool,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Cheats::default()
+    }
+
+    /// Was any cheat used since the level started? Scores and saves of the level are then
+    /// marked as cheated.
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    /// Mark the current level as cheated, for example when restoring a cheated save.
+    pub fn set_used(&mut self) {
+        self.used = true;
+    }
+}
+
+/// Handle the cheat keys, if enabled in the config: N to skip the level, B to auto-balance the
+/// current layout, and I to toggle the infinite inventory.
+fn cheat_keys(
+    config: Res<Config>,
+    keyboard_input: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    rules: Res<Rules>,
+    buildables: Res<BuildableRegistry>,
+    inventory: Res<Inventory>,
+    mut game: ResMut<Game>,
+    mut cheats: ResMut<Cheats>,
+    mut app_state: ResMut<State<AppState>>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+    mut ev_place: EventWriter<PlaceBuildableEvent>,
+) {
+    if !config.debug.cheats {
+        return;
+    }
+
+    // Skip level
+    if keyboard_input.just_pressed(KeyCode::N) {
+        if level.index() + 1 < levels.len() {
+            info!("Cheat: skip level '{}'", level.name());
+            game.reset_sequence();
+            ev_load_level.send(LoadLevelEvent(LoadLevel::Next));
+        } else {
+            info!("Cheat: skip last level '{}'", level.name());
+            app_state.set(AppState::TheEnd).unwrap();
+        }
+        return;
+    }
+
+    // Place the rest of the inventory to balance the plate, keeping the buildables already placed
+    if keyboard_input.just_pressed(KeyCode::B) {
+        if cheats.infinite_inventory {
+            warn!("Cheat: disable the infinite inventory to auto-balance");
+            return;
+        }
+        let level_desc = match level.desc() {
+            Some(level_desc) => level_desc,
+            None => return,
+        };
+        let victory_margin = rules.victory_margin(level_desc);
+        match solver::solve(
+            &grid,
+            &inventory,
+            &buildables,
+            &level_desc.pivot,
+            victory_margin,
+        ) {
+            Some(steps) => {
+                info!("Cheat: auto-balance with {} placement(s)", steps.len());
+                cheats.used = true;
+                for step in steps {
+                    ev_place.send(step);
+                }
+            }
+            None => {
+                warn!("Cheat: no balanced layout from the current placements; press R to restart")
+            }
+        }
+    }
+
+    // Infinite inventory
+    if keyboard_input.just_pressed(KeyCode::I) {
+        cheats.infinite_inventory = !cheats.infinite_inventory;
+        info!(
+            "Cheat: infinite inventory {}",
+            if cheats.infinite_inventory {
+                "ON"
+            } else {
+                "OFF"
+            }
+        );
+    }
+}
+
+/// Clear the cheated mark whenever a new level starts, unless a cheat is still active.
+fn reset_cheats(mut ev_reset_plate: EventReader<ResetPlateEvent>, mut cheats: ResMut<Cheats>) {
+    if ev_reset_plate.iter().last().is_some() {
+        cheats.used = false;
+    }
+}
+
+/// Keep all the inventory slots topped up while the infinite inventory cheat is active.
+fn infinite_inventory(
+    mut cheats: ResMut<Cheats>,
+    mut inventory: ResMut<Inventory>,
+    mut journal: ResMut<LevelJournal>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+) {
+    if !cheats.infinite_inventory {
+        return;
+    }
+    cheats.used = true;
+    let missing: Vec<_> = inventory
+        .slots()
+        .iter()
+        .filter(|slot| slot.count() < INFINITE_COUNT)
+        .map(|slot| (slot.bref(), INFINITE_COUNT - slot.count()))
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    for (bref, count) in missing {
+        inventory.add_items(bref, count);
+        journal.record(LevelOp::Refill { bref, count });
+    }
+    ev_update_slots.send(UpdateInventorySlots);
+}
+
+/// Plugin for the developer cheats helping playtesters reach late levels, enabled with the
+/// `debug.cheats` config flag. Levels cleared with cheats have their score and saves marked as
+/// cheated.
+pub struct CheatsPlugin;
+
+impl Plugin for CheatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Cheats::new())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Input)
+                    .with_system(cheat_keys),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Reset)
+                    .after(GameplaySystem::Input)
+                    .with_system(reset_cheats),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Placement)
+                    .before(GameplaySystem::VictoryCheck)
+                    .with_system(infinite_inventory),
+            );
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use crate::{anim::PlayAnimation, AppState};
+
+/// Resource controlling the cinematic mode used by scripted sequences. While enabled, letterbox
+/// bars are shown, the HUD is hidden, and player input is ignored.
+#[derive(Debug, Default)]
+pub struct CinematicMode {
+    enabled: bool,
+}
+
+impl CinematicMode {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    pub fn disable(&mut self) {
+        self.enabled = false;
+    }
+}
+
+/// Marker for the root of a HUD element hidden during cinematics, with all its descendants.
+#[derive(Debug, Component)]
+pub struct Hud;
+
+/// Marker for a letterbox bar.
+#[derive(Component)]
+struct LetterboxBar;
+
+fn spawn_letterbox(mut commands: Commands) {
+    for top in [true, false] {
+        let position = if top {
+            Rect {
+                top: Val::Px(0.0),
+                ..Default::default()
+            }
+        } else {
+            Rect {
+                bottom: Val::Px(0.0),
+                ..Default::default()
+            }
+        };
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(0.0)),
+                    ..Default::default()
+                },
+                color: UiColor(Color::BLACK),
+                ..Default::default()
+            })
+            .insert(Name::new("LetterboxBar"))
+            .insert(LetterboxBar);
+    }
+}
+
+/// Animate the letterbox bars in or out when the cinematic mode changes.
+fn animate_letterbox(
+    mut commands: Commands,
+    cinematic: Res<CinematicMode>,
+    mut last_enabled: Local<bool>,
+    query: Query<Entity, With<LetterboxBar>>,
+) {
+    if cinematic.is_enabled() == *last_enabled {
+        return;
+    }
+    *last_enabled = cinematic.is_enabled();
+    let name = if cinematic.is_enabled() {
+        "letterbox_in"
+    } else {
+        "letterbox_out"
+    };
+    for entity in query.iter() {
+        commands.entity(entity).insert(PlayAnimation::new(name));
+    }
+}
+
+fn set_visible_recursive(
+    entity: Entity,
+    visible: bool,
+    children_query: &Query<&Children>,
+    visibility_query: &mut Query<&mut Visibility>,
+) {
+    if let Ok(mut visibility) = visibility_query.get_mut(entity) {
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+    if let Ok(children) = children_query.get(entity) {
+        for child in children.iter() {
+            set_visible_recursive(*child, visible, children_query, visibility_query);
+        }
+    }
+}
+
+/// Hide the HUD during cinematics, and show it again afterwards.
+fn update_hud_visibility(
+    cinematic: Res<CinematicMode>,
+    hud_query: Query<Entity, With<Hud>>,
+    added_query: Query<Entity, Added<Hud>>,
+    children_query: Query<&Children>,
+    mut visibility_query: Query<&mut Visibility>,
+) {
+    let visible = !cinematic.is_enabled();
+    if cinematic.is_changed() {
+        for entity in hud_query.iter() {
+            set_visible_recursive(entity, visible, &children_query, &mut visibility_query);
+        }
+    } else if !visible {
+        // HUD elements spawned during a cinematic
+        for entity in added_query.iter() {
+            set_visible_recursive(entity, visible, &children_query, &mut visibility_query);
+        }
+    }
+}
+
+fn cleanup_cinematic(
+    mut commands: Commands,
+    mut cinematic: ResMut<CinematicMode>,
+    query: Query<Entity, With<LetterboxBar>>,
+) {
+    cinematic.disable();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the cinematic mode of scripted sequences. See [`CinematicMode`].
+pub struct CinematicPlugin;
+
+impl Plugin for CinematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CinematicMode::default())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_letterbox))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(animate_letterbox)
+                    .with_system(update_hud_visibility),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(cleanup_cinematic));
+    }
+}
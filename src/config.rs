@@ -1,10 +1,49 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::Error;
+use crate::{schema::Schema, Error};
 
+/// Language of the texts missing a translation in the configured language.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Game config. Older versions of the schema are upgraded on load, see [`Schema`].
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
+    /// Version of the schema of the file.
+    pub schema_version: u32,
     pub sound: SoundConfig,
+    #[serde(default)]
+    pub leaderboard: LeaderboardConfig,
+    /// Race a ghost of the best replay of each level.
+    #[serde(default)]
+    pub speedrun: bool,
+    /// Cut the large animations, like the plate wobble and the sliding banners, to their final
+    /// state, for motion-sensitive players.
+    #[serde(default)]
+    pub reduce_motion: bool,
+    /// Show a caption when a gameplay sound plays, like `[chime: balanced]`, for deaf and
+    /// hard-of-hearing players.
+    #[serde(default)]
+    pub captions: bool,
+    #[serde(default)]
+    pub input: InputConfig,
+    #[serde(default)]
+    pub graphics: GraphicsConfig,
+    #[serde(default)]
+    pub debug: DebugConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub presence: PresenceConfig,
+    /// Language of the texts, like `en` or `fr`.
+    #[serde(default = "default_language")]
+    pub language: String,
+}
+
+fn default_language() -> String {
+    DEFAULT_LANGUAGE.to_owned()
 }
 
 impl Config {
@@ -13,16 +52,62 @@ impl Config {
     }
 
     pub fn from_json(json_content: &str) -> Result<Config, Error> {
-        let mut config: Config = serde_json::from_str(json_content)?;
+        Config::from_json_with_overrides(Some(json_content), &Value::Null)
+    }
+
+    /// Parse the game config, or start from the default config if `None`, with some settings
+    /// overriding it. The overrides are a partial config in the same format, like
+    /// `{ "sound": { "volume": 0.5 } }`. Null values in the overrides are ignored.
+    pub fn from_json_with_overrides(
+        json_content: Option<&str>,
+        overrides: &Value,
+    ) -> Result<Config, Error> {
+        let mut value = match json_content {
+            Some(json_content) => Schema::Config.parse(json_content)?,
+            None => serde_json::to_value(Config::default())?,
+        };
+        merge(&mut value, overrides);
+        let mut config: Config = serde_json::from_value(value)?;
         config.sound.volume = config.sound.volume.clamp(0.0, 1.0);
+        config.graphics.screen_shake = config.graphics.screen_shake.clamp(0.0, 1.0);
         Ok(config)
     }
 }
 
+/// Recursively merge the fields of the overrides into a JSON value.
+fn merge(value: &mut Value, overrides: &Value) {
+    match (value, overrides) {
+        (Value::Object(object), Value::Object(overrides)) => {
+            for (key, field) in overrides.iter().filter(|(_, field)| !field.is_null()) {
+                merge(object.entry(key.clone()).or_insert(Value::Null), field);
+            }
+        }
+        (_, Value::Null) => {}
+        (value, overrides) => *value = overrides.clone(),
+    }
+}
+
+/// Content of `config.json`, if any, kept to apply the settings of another save profile over it.
+/// See [`Config::from_json_with_overrides()`].
+#[derive(Debug, Default)]
+pub struct BaseConfig(pub Option<String>);
+
 impl Default for Config {
     fn default() -> Self {
         Config {
+            schema_version: Schema::Config.current_version(),
             sound: SoundConfig::default(),
+            leaderboard: LeaderboardConfig::default(),
+            speedrun: false,
+            reduce_motion: false,
+            captions: false,
+            input: InputConfig::default(),
+            graphics: GraphicsConfig::default(),
+            debug: DebugConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            sync: SyncConfig::default(),
+            presence: PresenceConfig::default(),
+            language: default_language(),
         }
     }
 }
@@ -47,3 +132,185 @@ impl Default for SoundConfig {
         }
     }
 }
+
+/// Configuration of the online leaderboard client, used when the `leaderboard` feature is enabled.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LeaderboardConfig {
+    /// Base URL of the leaderboard REST endpoint. The leaderboard is disabled if not set.
+    pub url: Option<String>,
+    /// Player name submitted along with the scores.
+    #[serde(default)]
+    pub player: String,
+}
+
+/// Configuration of the cursor input buffering and auto-repeat. All durations are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct InputConfig {
+    /// Duration a cursor action stays buffered while the cursor cannot perform it yet.
+    pub buffer_window: f32,
+    /// Delay before a held direction starts repeating.
+    pub repeat_delay: f32,
+    /// Interval between two repeated moves of a held direction.
+    pub repeat_interval: f32,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig {
+            buffer_window: 0.15,
+            repeat_delay: 0.35,
+            repeat_interval: 0.1,
+        }
+    }
+}
+
+/// Quality of the shadows cast by the buildables on the plate.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    /// Size in texels of the shadow map, or `None` if shadows are disabled.
+    pub fn shadow_map_size(&self) -> Option<usize> {
+        match self {
+            ShadowQuality::Off => None,
+            ShadowQuality::Low => Some(512),
+            ShadowQuality::Medium => Some(1024),
+            ShadowQuality::High => Some(2048),
+        }
+    }
+}
+
+/// Power-saving mode capping the frame rate, to reduce the fan noise and the battery drain.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerSaving {
+    /// Never cap the frame rate.
+    Off,
+    /// Cap the frame rate while the window is unfocused or the device runs on battery.
+    #[default]
+    Auto,
+    /// Always cap the frame rate.
+    On,
+}
+
+impl Default for ShadowQuality {
+    fn default() -> Self {
+        // WebGL2 shadows are costly and not supported on all browsers
+        if cfg!(target_arch = "wasm32") {
+            ShadowQuality::Off
+        } else {
+            ShadowQuality::Medium
+        }
+    }
+}
+
+/// Configuration of the rendering quality.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    /// Quality of the shadows, or `off` to disable them. Defaults to `off` on the web build.
+    pub shadows: ShadowQuality,
+    /// Grade the colors of the scene per world and darken the screen corners.
+    pub post_processing: bool,
+    /// Flash a glow over the scene when a level is cleared, with the post-processing enabled.
+    pub bloom: bool,
+    /// Intensity of the screen shake on impacts, from 0 to disable it to 1 for the full shake.
+    pub screen_shake: f32,
+    /// Cap the frame rate to save power: `auto` while the window is unfocused or on battery, `on`
+    /// always, or `off`. Only on native platforms.
+    pub power_saving: PowerSaving,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        GraphicsConfig {
+            shadows: ShadowQuality::default(),
+            post_processing: true,
+            bloom: true,
+            screen_shake: 1.0,
+            power_saving: PowerSaving::default(),
+        }
+    }
+}
+
+/// Configuration of the anonymous gameplay telemetry. See [`TelemetryPlugin`].
+///
+/// [`TelemetryPlugin`]: crate::telemetry::TelemetryPlugin
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TelemetryConfig {
+    /// Consent of the player to record anonymous gameplay events. Disabled by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// URL the events are posted to, one JSON object per request, with the `telemetry` feature.
+    /// Events are appended to a local `telemetry.jsonl` file otherwise.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+/// Configuration of the cloud sync of the save files, used when the `cloud_sync` feature is
+/// enabled. See [`SaveSync`].
+///
+/// [`SaveSync`]: crate::sync::SaveSync
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SyncConfig {
+    /// Base URL of the WebDAV folder the save files are synced with. Sync is disabled if not set.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Value of the `Authorization` header sent to the server, like `Basic <base64 credentials>`.
+    #[serde(default)]
+    pub authorization: Option<String>,
+}
+
+/// Configuration of the rich presence, used when the `presence` feature is enabled.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PresenceConfig {
+    /// Client ID of the Discord application showing the activity. Discord is skipped if not set.
+    #[serde(default)]
+    pub discord_client_id: Option<String>,
+}
+
+/// Configuration of the developer tools.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DebugConfig {
+    /// Enable the developer cheats: N to skip the level, B to auto-balance the current layout, and
+    /// I to toggle an infinite inventory. Scores and saves are marked as cheated when used.
+    #[serde(default)]
+    pub cheats: bool,
+    /// Enable the in-game log console, toggled with the [`] key. Always enabled in debug builds.
+    #[serde(default)]
+    pub console: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn settings_overrides() {
+        let json = r#"{ "schema_version": 1, "sound": { "enabled": true, "volume": 0.8 } }"#;
+        let overrides = json!({ "sound": { "volume": 0.5 }, "speedrun": true, "debug": null });
+        let config = Config::from_json_with_overrides(Some(json), &overrides).unwrap();
+        assert!(config.sound.enabled);
+        assert_eq!(config.sound.volume, 0.5);
+        assert!(config.speedrun);
+        assert!(!config.reduce_motion);
+
+        // Overrides of the default config, clamped like the config itself
+        let overrides = json!({ "sound": { "volume": 2.0 }, "graphics": { "screen_shake": -1.0 } });
+        let config = Config::from_json_with_overrides(None, &overrides).unwrap();
+        assert_eq!(config.sound.volume, 1.0);
+        assert_eq!(config.graphics.screen_shake, 0.0);
+        assert_eq!(
+            config.input.buffer_window,
+            InputConfig::default().buffer_window
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::{prelude::*, utils::tracing::Level};
+
+use crate::{
+    boot::UiResources,
+    config::Config,
+    logging::{log_line_count, recent_log_lines, LogLine},
+    AppState,
+};
+
+/// Number of log lines displayed at once.
+const VISIBLE_LINES: usize = 20;
+
+/// Most verbose levels the console can be filtered to, cycled in this order from the default.
+const LEVELS: [Level; 4] = [Level::INFO, Level::DEBUG, Level::ERROR, Level::WARN];
+
+/// Resource holding the state of the in-game log console.
+pub struct Console {
+    /// Root entity of the console UI, if open.
+    entity: Option<Entity>,
+    /// Index into [`LEVELS`] of the most verbose level displayed.
+    level: usize,
+    /// Module whose lines are displayed, or all modules if `None`.
+    module: Option<String>,
+    /// Number of lines scrolled up from the most recent one.
+    scroll: usize,
+    /// Log line count when the text was last updated.
+    line_count: usize,
+    /// Does the text need updating even if no line was logged?
+    dirty: bool,
+    /// Is the console shown in the separate debug window instead of over the game?
+    detached: bool,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            entity: None,
+            level: 0,
+            module: None,
+            scroll: 0,
+            line_count: 0,
+            dirty: false,
+            detached: false,
+        }
+    }
+
+    /// Show the console in the separate debug window instead of over the game, closing the
+    /// overlay if open.
+    pub fn set_detached(&mut self, detached: bool, commands: &mut Commands) {
+        self.detached = detached;
+        if detached {
+            if let Some(entity) = self.entity.take() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+
+    fn level(&self) -> Level {
+        LEVELS[self.level]
+    }
+
+    /// Does a line pass the level and module filters?
+    fn accepts(&self, line: &LogLine) -> bool {
+        // More verbose levels compare greater
+        line.level <= self.level()
+            && self
+                .module
+                .as_ref()
+                .is_none_or(|module| module_of(&line.target) == module)
+    }
+}
+
+/// Top-level module of a log target, like `libracity` for `libracity::game`.
+fn module_of(target: &str) -> &str {
+    target.split("::").next().unwrap_or(target)
+}
+
+pub fn level_color(level: Level) -> Color {
+    match level {
+        Level::ERROR => Color::rgb(1.0, 0.35, 0.3),
+        Level::WARN => Color::rgb(1.0, 0.85, 0.3),
+        Level::INFO => Color::WHITE,
+        _ => Color::GRAY,
+    }
+}
+
+/// Marker for the text of the console.
+#[derive(Component)]
+struct ConsoleText;
+
+/// Open or close the console with the [`] key. The console is only available in debug builds, or
+/// with the `debug.console` config.
+fn toggle_console(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    config: Res<Config>,
+    state: Res<State<AppState>>,
+    mut console: ResMut<Console>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Grave) {
+        return;
+    }
+    // The UI font is only available once booted
+    if !(cfg!(debug_assertions) || config.debug.console)
+        || *state.current() == AppState::Boot
+        || console.detached
+    {
+        return;
+    }
+    if let Some(entity) = console.entity.take() {
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+    let entity = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                padding: Rect::all(Val::Px(6.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+            ..Default::default()
+        })
+        .insert(Name::new("Console"))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![],
+                        alignment: TextAlignment::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(ConsoleText);
+        })
+        .id();
+    console.entity = Some(entity);
+    console.dirty = true;
+}
+
+/// Change the console filters and scroll, and display the filtered recent log lines.
+///
+/// Keys: [F2] cycles the level, [F3] cycles the module, [PAGE UP]/[PAGE DOWN] scroll.
+fn update_console(
+    keyboard_input: Res<Input<KeyCode>>,
+    ui_resources: Res<UiResources>,
+    mut console: ResMut<Console>,
+    mut query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if console.entity.is_none() {
+        return;
+    }
+    let lines = recent_log_lines();
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        console.level = (console.level + 1) % LEVELS.len();
+        console.scroll = 0;
+        console.dirty = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        // Cycle through all modules, then back to none
+        let mut modules: Vec<_> = lines.iter().map(|line| module_of(&line.target)).collect();
+        modules.sort_unstable();
+        modules.dedup();
+        let next = match &console.module {
+            None => modules.first(),
+            Some(module) => modules.iter().find(|&&m| m > &module[..]),
+        };
+        console.module = next.map(|module| module.to_string());
+        console.scroll = 0;
+        console.dirty = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        console.scroll += VISIBLE_LINES / 2;
+        console.dirty = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::PageDown) {
+        console.scroll = console.scroll.saturating_sub(VISIBLE_LINES / 2);
+        console.dirty = true;
+    }
+    let line_count = log_line_count();
+    if !console.dirty && line_count == console.line_count {
+        return;
+    }
+    console.dirty = false;
+    console.line_count = line_count;
+
+    let filtered: Vec<_> = lines.iter().filter(|line| console.accepts(line)).collect();
+    let max_scroll = filtered.len().saturating_sub(VISIBLE_LINES);
+    console.scroll = console.scroll.min(max_scroll);
+    let end = filtered.len() - console.scroll;
+    let start = end.saturating_sub(VISIBLE_LINES);
+
+    let font = ui_resources.text_font();
+    let mut sections = vec![TextSection {
+        value: format!(
+            "Console: level <= {} [F2], module {} [F3], {} lines [PAGE UP/DOWN]\n",
+            console.level(),
+            console.module.as_deref().unwrap_or("all"),
+            filtered.len()
+        ),
+        style: TextStyle {
+            font: font.clone(),
+            font_size: 14.0,
+            color: Color::CYAN,
+        },
+    }];
+    sections.extend(filtered[start..end].iter().map(|line| TextSection {
+        value: format!("{}\n", line),
+        style: TextStyle {
+            font: font.clone(),
+            font_size: 14.0,
+            color: level_color(line.level),
+        },
+    }));
+    if let Ok(mut text) = query.get_single_mut() {
+        text.sections = sections;
+    }
+}
+
+/// Plugin for the in-game log console overlay, showing the recent log lines. Useful on the web,
+/// where players rarely open the browser console to report what went wrong.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Console::new())
+            .add_system(toggle_console.label("toggle_console"))
+            .add_system(update_console.after("toggle_console"));
+    }
+}
@@ -0,0 +1,629 @@
+use bevy::{
+    ecs::system::SystemParam,
+    input::{keyboard::KeyboardInput, ElementState, InputSystem},
+    prelude::*,
+};
+use std::{
+    collections::{HashSet, VecDeque},
+    marker::PhantomData,
+};
+
+use crate::{
+    cinematic::CinematicMode, config::Config, controls_screen::ControlsScreen,
+    game::GameplaySystem, AppState,
+};
+
+/// Input device the player used last, to show the glyphs of its buttons.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputDevice {
+    #[default]
+    Keyboard,
+    Gamepad,
+    /// Touch screen, which can't play the levels; the keyboard glyphs are shown instead.
+    Touch,
+}
+
+/// Resource holding the input device the player used last.
+#[derive(Debug, Default)]
+pub struct ActiveInputDevice(pub InputDevice);
+
+/// Glyph of a keyboard key, for display.
+pub fn key_glyph(key: KeyCode) -> String {
+    let name = match key {
+        KeyCode::Key1 => "1",
+        KeyCode::Key2 => "2",
+        KeyCode::Key3 => "3",
+        KeyCode::Key4 => "4",
+        KeyCode::Key5 => "5",
+        KeyCode::Return => "Enter",
+        KeyCode::NumpadEnter => "Numpad Enter",
+        KeyCode::RShift => "Right Shift",
+        KeyCode::Escape => "Esc",
+        _ => return format!("[{:?}]", key),
+    };
+    format!("[{}]", name)
+}
+
+/// Glyph of a gamepad button, named after the Xbox layout, for display.
+pub fn button_glyph(button: GamepadButtonType) -> &'static str {
+    match button {
+        GamepadButtonType::South => "(A)",
+        GamepadButtonType::East => "(B)",
+        GamepadButtonType::West => "(X)",
+        GamepadButtonType::North => "(Y)",
+        GamepadButtonType::LeftTrigger => "(LB)",
+        GamepadButtonType::RightTrigger => "(RB)",
+        GamepadButtonType::LeftTrigger2 => "(LT)",
+        GamepadButtonType::RightTrigger2 => "(RT)",
+        GamepadButtonType::Select => "(Select)",
+        GamepadButtonType::Start => "(Start)",
+        GamepadButtonType::DPadUp => "(D-pad Up)",
+        GamepadButtonType::DPadDown => "(D-pad Down)",
+        GamepadButtonType::DPadLeft => "(D-pad Left)",
+        GamepadButtonType::DPadRight => "(D-pad Right)",
+        _ => "(?)",
+    }
+}
+
+/// Keys and gamepad buttons bound to an action, for display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub action: String,
+    pub keys: Vec<KeyCode>,
+    pub buttons: Vec<GamepadButtonType>,
+}
+
+impl Binding {
+    /// Glyphs of the inputs bound on a device, or an empty string if not bound on that device.
+    pub fn glyphs(&self, device: InputDevice) -> String {
+        let glyphs: Vec<_> = match device {
+            InputDevice::Keyboard | InputDevice::Touch => {
+                self.keys.iter().map(|key| key_glyph(*key)).collect()
+            }
+            InputDevice::Gamepad => self
+                .buttons
+                .iter()
+                .map(|button| button_glyph(*button).to_owned())
+                .collect(),
+        };
+        glyphs.join(" ")
+    }
+}
+
+/// Set of input devices controlling the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlScheme {
+    /// Whole keyboard (arrows and WASD, Space to place) and any gamepad. Used in solo.
+    All,
+    /// Left half of the keyboard: WASD to move, Space to place.
+    KeyboardLeft,
+    /// Right half of the keyboard: arrows to move, Enter to place; or any gamepad.
+    KeyboardRight,
+}
+
+impl ControlScheme {
+    /// Short human-readable description of the controls, for display.
+    pub fn description(&self) -> &'static str {
+        match self {
+            ControlScheme::All => "Arrows/WASD + Space",
+            ControlScheme::KeyboardLeft => "WASD + Space",
+            ControlScheme::KeyboardRight => "Arrows + Enter / Gamepad",
+        }
+    }
+
+    fn left_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All => &[KeyCode::Left, KeyCode::A],
+            ControlScheme::KeyboardLeft => &[KeyCode::A],
+            ControlScheme::KeyboardRight => &[KeyCode::Left],
+        }
+    }
+
+    fn right_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All => &[KeyCode::Right, KeyCode::D],
+            ControlScheme::KeyboardLeft => &[KeyCode::D],
+            ControlScheme::KeyboardRight => &[KeyCode::Right],
+        }
+    }
+
+    fn up_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All => &[KeyCode::Up, KeyCode::W],
+            ControlScheme::KeyboardLeft => &[KeyCode::W],
+            ControlScheme::KeyboardRight => &[KeyCode::Up],
+        }
+    }
+
+    fn down_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All => &[KeyCode::Down, KeyCode::S],
+            ControlScheme::KeyboardLeft => &[KeyCode::S],
+            ControlScheme::KeyboardRight => &[KeyCode::Down],
+        }
+    }
+
+    fn place_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All | ControlScheme::KeyboardLeft => &[KeyCode::Space],
+            ControlScheme::KeyboardRight => &[KeyCode::Return, KeyCode::NumpadEnter],
+        }
+    }
+
+    fn restart_keys(&self) -> &'static [KeyCode] {
+        &[KeyCode::R]
+    }
+
+    fn prev_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All | ControlScheme::KeyboardLeft => &[KeyCode::Q],
+            ControlScheme::KeyboardRight => &[],
+        }
+    }
+
+    fn next_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All | ControlScheme::KeyboardLeft => &[KeyCode::E, KeyCode::Tab],
+            ControlScheme::KeyboardRight => &[KeyCode::RShift],
+        }
+    }
+
+    /// Keys selecting the inventory slots, by slot index.
+    fn slot_keys(&self) -> &'static [KeyCode] {
+        match self {
+            ControlScheme::All | ControlScheme::KeyboardLeft => &[
+                KeyCode::Key1,
+                KeyCode::Key2,
+                KeyCode::Key3,
+                KeyCode::Key4,
+                KeyCode::Key5,
+            ],
+            ControlScheme::KeyboardRight => &[],
+        }
+    }
+
+    /// Does the control scheme accept input from the gamepads?
+    pub fn accepts_gamepad(&self) -> bool {
+        matches!(self, ControlScheme::All | ControlScheme::KeyboardRight)
+    }
+
+    /// Keys and gamepad button of each direction moving the cursor.
+    fn direction_bindings(&self) -> [(&'static [KeyCode], GamepadButtonType, IVec2); 4] {
+        [
+            (
+                self.left_keys(),
+                GamepadButtonType::DPadLeft,
+                IVec2::new(-1, 0),
+            ),
+            (
+                self.right_keys(),
+                GamepadButtonType::DPadRight,
+                IVec2::new(1, 0),
+            ),
+            (self.up_keys(), GamepadButtonType::DPadUp, IVec2::new(0, 1)),
+            (
+                self.down_keys(),
+                GamepadButtonType::DPadDown,
+                IVec2::new(0, -1),
+            ),
+        ]
+    }
+
+    /// Keys and gamepad button of each action other than the moves and the slot selection.
+    fn action_bindings(&self) -> [(&'static [KeyCode], GamepadButtonType, PlayerAction); 4] {
+        [
+            (
+                self.place_keys(),
+                GamepadButtonType::South,
+                PlayerAction::Place,
+            ),
+            (
+                self.prev_keys(),
+                GamepadButtonType::West,
+                PlayerAction::CyclePrev,
+            ),
+            (
+                self.next_keys(),
+                GamepadButtonType::East,
+                PlayerAction::CycleNext,
+            ),
+            (
+                self.restart_keys(),
+                GamepadButtonType::Select,
+                PlayerAction::Restart,
+            ),
+        ]
+    }
+
+    /// Bindings of the actions of the control scheme, for display. Built from the same input map
+    /// as the [`ActionMapper`], so the controls displayed never drift from the actual ones.
+    pub fn bindings(&self) -> Vec<Binding> {
+        let moves = self
+            .direction_bindings()
+            .into_iter()
+            .map(|(keys, button, delta)| (keys, button, PlayerAction::MoveCursor(delta)));
+        let mut bindings: Vec<_> = moves
+            .chain(self.action_bindings())
+            .map(|(keys, button, action)| Binding {
+                action: action.description(),
+                keys: keys.to_vec(),
+                buttons: if self.accepts_gamepad() {
+                    vec![button]
+                } else {
+                    vec![]
+                },
+            })
+            .collect();
+        if !self.slot_keys().is_empty() {
+            bindings.push(Binding {
+                action: "Select the buildable of a slot".to_owned(),
+                keys: self.slot_keys().to_vec(),
+                buttons: vec![],
+            });
+        }
+        bindings
+    }
+}
+
+/// Resource holding the control scheme of the player currently controlling the cursor.
+pub struct ActiveControls(pub ControlScheme);
+
+/// Resource holding the keys tapped this frame, in press order.
+///
+/// Unlike [`Input::just_pressed`], this counts each tap of the same key when several taps land
+/// in a single frame, which happens on slow frames (notably on the web build). Keys held down
+/// are only reported once, ignoring the repeated press events of the OS.
+#[derive(Debug, Default)]
+pub struct KeyTaps {
+    /// Keys currently held down.
+    held: HashSet<KeyCode>,
+    /// Keys tapped this frame.
+    tapped: Vec<KeyCode>,
+}
+
+impl KeyTaps {
+    pub fn new() -> Self {
+        KeyTaps::default()
+    }
+
+    /// Number of taps this frame of any of the given keys.
+    pub fn count(&self, keys: &[KeyCode]) -> usize {
+        self.tapped.iter().filter(|key| keys.contains(key)).count()
+    }
+}
+
+/// Event for an action of a player, independent of the input device.
+///
+/// Every source of gameplay input (keyboard, gamepad, or the autoplayer) emits these actions,
+/// and the gameplay systems only ever consume them, never the raw input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAction {
+    /// Move the cursor by the given number of cells.
+    MoveCursor(IVec2),
+    /// Place the selected buildable at the cursor position.
+    Place,
+    /// Restart the current level.
+    Restart,
+    /// Select the previous inventory slot.
+    CyclePrev,
+    /// Select the next inventory slot.
+    CycleNext,
+    /// Select the inventory slot at the given index.
+    SelectSlot(usize),
+}
+
+impl PlayerAction {
+    /// Is this an action of the cursor, buffered in [`CursorInput`] until the cursor is able to
+    /// perform it?
+    pub fn is_cursor_action(&self) -> bool {
+        matches!(self, PlayerAction::MoveCursor(_) | PlayerAction::Place)
+    }
+
+    /// Short human-readable description of the action, for display.
+    pub fn description(&self) -> String {
+        match self {
+            PlayerAction::MoveCursor(delta) => {
+                let direction = if delta.x < 0 {
+                    "left"
+                } else if delta.x > 0 {
+                    "right"
+                } else if delta.y > 0 {
+                    "up"
+                } else {
+                    "down"
+                };
+                format!("Move the cursor {}", direction)
+            }
+            PlayerAction::Place => "Place the buildable".to_owned(),
+            PlayerAction::Restart => "Restart the level".to_owned(),
+            PlayerAction::CyclePrev => "Select the previous buildable".to_owned(),
+            PlayerAction::CycleNext => "Select the next buildable".to_owned(),
+            PlayerAction::SelectSlot(index) => {
+                format!("Select the buildable of slot {}", index + 1)
+            }
+        }
+    }
+}
+
+/// Mapping of the input devices of a control scheme to [`PlayerAction`]s.
+///
+/// Held directions repeat at a fixed rate, independently of the frame rate.
+#[derive(Debug, Default)]
+pub struct ActionMapper {
+    /// Direction held during the last update.
+    held_dir: IVec2,
+    /// Time in seconds of the next repeated move of the held direction.
+    next_repeat: f64,
+}
+
+impl ActionMapper {
+    pub fn new() -> Self {
+        ActionMapper::default()
+    }
+
+    /// Actions requested through the given control scheme since the last update, in request
+    /// order. No action is requested while a cinematic is playing or the controls screen is open.
+    pub fn update(&mut self, scheme: ControlScheme, input: &ControlsInput) -> Vec<PlayerAction> {
+        let mut actions = vec![];
+        if input.cinematic.is_enabled() || input.controls_screen.is_open() {
+            self.reset();
+            return actions;
+        }
+        let now = input.time.seconds_since_startup();
+        let config = &input.config.input;
+        let keyboard_input = &input.keyboard_input;
+        let gamepad_input = &input.gamepad_input;
+        let gamepads = &input.gamepads;
+        let taps = &input.taps;
+
+        let button = |button_type: GamepadButtonType| {
+            scheme.accepts_gamepad()
+                && gamepads
+                    .iter()
+                    .any(|gamepad| gamepad_input.just_pressed(GamepadButton(*gamepad, button_type)))
+        };
+        let held_button = |button_type: GamepadButtonType| {
+            scheme.accepts_gamepad()
+                && gamepads
+                    .iter()
+                    .any(|gamepad| gamepad_input.pressed(GamepadButton(*gamepad, button_type)))
+        };
+        let held_key = |keys: &[KeyCode]| keys.iter().any(|key| keyboard_input.pressed(*key));
+        let directions = scheme.direction_bindings();
+
+        // Taps
+        for &(keys, button_type, delta) in &directions {
+            let count = taps.count(keys) + button(button_type) as usize;
+            for _ in 0..count {
+                actions.push(PlayerAction::MoveCursor(delta));
+            }
+        }
+        for (keys, button_type, action) in scheme.action_bindings() {
+            let count = taps.count(keys) + button(button_type) as usize;
+            for _ in 0..count {
+                actions.push(action);
+            }
+        }
+        for (index, key) in scheme.slot_keys().iter().enumerate() {
+            for _ in 0..taps.count(&[*key]) {
+                actions.push(PlayerAction::SelectSlot(index));
+            }
+        }
+
+        // Auto-repeat of the held direction
+        let held_dir = directions
+            .iter()
+            .filter(|(keys, button_type, _)| held_key(keys) || held_button(*button_type))
+            .fold(IVec2::ZERO, |dir, &(_, _, delta)| dir + delta);
+        if held_dir != self.held_dir {
+            self.held_dir = held_dir;
+            self.next_repeat = now + config.repeat_delay.max(0.0) as f64;
+        } else if held_dir != IVec2::ZERO {
+            let repeat_interval = config.repeat_interval.max(0.01) as f64;
+            while self.next_repeat <= now {
+                actions.push(PlayerAction::MoveCursor(held_dir));
+                self.next_repeat += repeat_interval;
+            }
+        }
+        actions
+    }
+
+    /// Restart the auto-repeat delay of any held direction.
+    pub fn reset(&mut self) {
+        self.held_dir = IVec2::ZERO;
+    }
+}
+
+/// Buffer of the cursor actions of a player.
+///
+/// Actions are queued with the time they were requested, and consumed in order by the cursor
+/// once it is able to perform them, so an action requested slightly too early (for example
+/// during a transition) is not lost. Actions not consumed within the buffer window expire.
+#[derive(Debug, Default)]
+pub struct CursorInput {
+    /// Buffered actions, with the time in seconds they were requested.
+    actions: VecDeque<(PlayerAction, f64)>,
+}
+
+impl CursorInput {
+    pub fn new() -> Self {
+        CursorInput::default()
+    }
+
+    /// Drop the actions requested more than `buffer_window` seconds before `now`.
+    pub fn expire(&mut self, now: f64, buffer_window: f32) {
+        let buffer_window = buffer_window.max(0.0) as f64;
+        while let Some(&(_, time)) = self.actions.front() {
+            if now - time <= buffer_window {
+                break;
+            }
+            self.actions.pop_front();
+        }
+    }
+
+    /// Buffer an action requested at the given time, in seconds since startup.
+    pub fn push(&mut self, action: PlayerAction, time: f64) {
+        self.actions.push_back((action, time));
+    }
+
+    /// Take the oldest buffered action, if any.
+    pub fn pop(&mut self) -> Option<PlayerAction> {
+        self.actions.pop_front().map(|(action, _)| action)
+    }
+
+    /// Drop all buffered actions.
+    pub fn clear(&mut self) {
+        self.actions.clear();
+    }
+}
+
+/// Track the input device used last, switching on any key, button, or tap, and when a gamepad is
+/// connected.
+fn detect_input_device(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    touches: Res<Touches>,
+    mut ev_gamepad: EventReader<GamepadEvent>,
+    mut device: ResMut<ActiveInputDevice>,
+) {
+    let connected = ev_gamepad
+        .iter()
+        .any(|ev| ev.1 == GamepadEventType::Connected);
+    let detected = if touches.iter_just_pressed().next().is_some() {
+        InputDevice::Touch
+    } else if connected || gamepad_input.get_just_pressed().next().is_some() {
+        InputDevice::Gamepad
+    } else if keyboard_input.get_just_pressed().next().is_some() {
+        InputDevice::Keyboard
+    } else {
+        return;
+    };
+    if device.0 != detected {
+        device.0 = detected;
+    }
+}
+
+/// Collect the keys tapped this frame from the raw keyboard events.
+fn key_taps_system(mut ev_keyboard: EventReader<KeyboardInput>, mut taps: ResMut<KeyTaps>) {
+    taps.tapped.clear();
+    for ev in ev_keyboard.iter() {
+        let key = match ev.key_code {
+            Some(key) => key,
+            None => continue,
+        };
+        match ev.state {
+            ElementState::Pressed => {
+                if taps.held.insert(key) {
+                    taps.tapped.push(key);
+                }
+            }
+            ElementState::Released => {
+                taps.held.remove(&key);
+            }
+        }
+    }
+}
+
+/// Input devices and settings mapped to [`PlayerAction`]s.
+#[derive(SystemParam)]
+pub struct ControlsInput<'w, 's> {
+    time: Res<'w, Time>,
+    config: Res<'w, Config>,
+    taps: Res<'w, KeyTaps>,
+    keyboard_input: Res<'w, Input<KeyCode>>,
+    gamepad_input: Res<'w, Input<GamepadButton>>,
+    gamepads: Res<'w, Gamepads>,
+    cinematic: Res<'w, CinematicMode>,
+    controls_screen: Res<'w, ControlsScreen>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// Translate the raw keyboard and gamepad input of the active controls into [`PlayerAction`]
+/// events.
+fn controls_system(
+    input: ControlsInput,
+    controls: Res<ActiveControls>,
+    mut mapper: ResMut<ActionMapper>,
+    mut ev_action: EventWriter<PlayerAction>,
+) {
+    for action in mapper.update(controls.0, &input) {
+        ev_action.send(action);
+    }
+}
+
+/// Buffer the cursor actions requested with [`PlayerAction`] events, and expire the ones older
+/// than the buffer window. Actions are dropped while a cinematic is playing.
+fn buffer_cursor_actions(
+    time: Res<Time>,
+    config: Res<Config>,
+    cinematic: Res<CinematicMode>,
+    mut ev_action: EventReader<PlayerAction>,
+    mut cursor_input: ResMut<CursorInput>,
+) {
+    if cinematic.is_enabled() {
+        ev_action.iter().last();
+        cursor_input.clear();
+        return;
+    }
+    let now = time.seconds_since_startup();
+    cursor_input.expire(now, config.input.buffer_window);
+    for action in ev_action.iter().filter(|action| action.is_cursor_action()) {
+        cursor_input.push(*action, now);
+    }
+}
+
+fn controls_cleanup(mut mapper: ResMut<ActionMapper>, mut cursor_input: ResMut<CursorInput>) {
+    mapper.reset();
+    cursor_input.clear();
+}
+
+/// Plugin mapping the input devices of the active player to player actions.
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ActiveControls(ControlScheme::All))
+            .insert_resource(KeyTaps::new())
+            .insert_resource(ActionMapper::new())
+            .insert_resource(CursorInput::new())
+            .insert_resource(ActiveInputDevice::default())
+            .add_event::<PlayerAction>()
+            .add_system_to_stage(CoreStage::PreUpdate, key_taps_system.after(InputSystem))
+            .add_system_to_stage(CoreStage::PreUpdate, detect_input_device.after(InputSystem))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Input)
+                    .with_system(controls_system.label("controls"))
+                    .with_system(buffer_cursor_actions.after("controls")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(controls_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings() {
+        let bindings = ControlScheme::All.bindings();
+        assert_eq!(bindings[0].action, "Move the cursor left");
+        assert_eq!(bindings[0].glyphs(InputDevice::Keyboard), "[Left] [A]");
+        assert_eq!(bindings[0].glyphs(InputDevice::Gamepad), "(D-pad Left)");
+        let place = &bindings[4];
+        assert_eq!(place.action, "Place the buildable");
+        assert_eq!(place.glyphs(InputDevice::Gamepad), "(A)");
+
+        // The gamepads don't control the left half of the keyboard
+        let bindings = ControlScheme::KeyboardLeft.bindings();
+        assert!(bindings.iter().all(|binding| binding.buttons.is_empty()));
+        let bindings = ControlScheme::KeyboardRight.bindings();
+        assert_eq!(
+            bindings[4].glyphs(InputDevice::Keyboard),
+            "[Enter] [Numpad Enter]"
+        );
+        // No slot keys on the right half of the keyboard
+        assert_eq!(bindings.len(), 8);
+    }
+}
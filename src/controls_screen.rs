@@ -0,0 +1,285 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    boot::UiResources,
+    controls::{button_glyph, key_glyph, ActiveControls, ActiveInputDevice, Binding, InputDevice},
+    daily::DailyMenu,
+    encyclopedia::EncyclopediaMenu,
+    inspect,
+    level_code::ImportDialog,
+    mainmenu::MenuOverlay,
+    minimap,
+    playtime::StatsMenu,
+    radial,
+    wardrobe::WardrobeMenu,
+    AppState,
+};
+
+/// Key to open and close the controls screen, in the main menu and in game. Not F1, which toggles
+/// the world inspector of the debug builds.
+pub const CONTROLS_KEY: KeyCode = KeyCode::F6;
+
+/// Gamepad button to open and close the controls screen.
+pub const CONTROLS_BUTTON: GamepadButtonType = GamepadButtonType::Start;
+
+/// Resource holding the state of the controls screen.
+#[derive(Debug, Default)]
+pub struct ControlsScreen {
+    /// Is the controls screen open?
+    open: bool,
+}
+
+impl ControlsScreen {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+/// Marker for the UI nodes of the controls screen, kept visible while it's open.
+#[derive(Component)]
+struct ControlsScreenUi;
+
+/// Binding of the controls screen itself, of the radial menu of the gamepads, of the inspection
+/// mode, and of the minimap, listed with the bindings of the actions.
+fn extra_bindings() -> [Binding; 5] {
+    [
+        Binding {
+            action: "Pick a buildable from the radial menu".to_owned(),
+            keys: vec![],
+            buttons: vec![radial::OPEN_BUTTON],
+        },
+        Binding {
+            action: "Inspect the building under the cursor".to_owned(),
+            keys: vec![inspect::INSPECT_KEY],
+            buttons: vec![inspect::INSPECT_BUTTON],
+        },
+        Binding {
+            action: "Remove the inspected building".to_owned(),
+            keys: inspect::REMOVE_KEYS.to_vec(),
+            buttons: vec![inspect::REMOVE_BUTTON],
+        },
+        Binding {
+            action: "Show the minimap".to_owned(),
+            keys: vec![minimap::MINIMAP_KEY],
+            buttons: vec![minimap::MINIMAP_BUTTON],
+        },
+        Binding {
+            action: "Show the controls".to_owned(),
+            keys: vec![CONTROLS_KEY],
+            buttons: vec![CONTROLS_BUTTON],
+        },
+    ]
+}
+
+/// Glyph of the input opening the controls screen on a device, for the prompts referencing it.
+pub fn controls_glyph(device: InputDevice) -> String {
+    match device {
+        InputDevice::Gamepad => button_glyph(CONTROLS_BUTTON).to_owned(),
+        InputDevice::Keyboard | InputDevice::Touch => key_glyph(CONTROLS_KEY),
+    }
+}
+
+/// Lines listing the bindings of the control scheme on a device, skipping the actions not bound
+/// on that device.
+pub fn binding_lines(bindings: &[Binding], device: InputDevice) -> Vec<String> {
+    bindings
+        .iter()
+        .filter_map(|binding| {
+            let glyphs = binding.glyphs(device);
+            if glyphs.is_empty() {
+                None
+            } else {
+                Some(format!("{} - {}", glyphs, binding.action))
+            }
+        })
+        .collect()
+}
+
+/// Open and close the controls screen. Runs right after the input is updated, to hide the keys
+/// from the rest of the menu or the game while it's open.
+fn controls_screen_input(
+    state: Res<State<AppState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut gamepad_input: ResMut<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    encyclopedia_menu: Res<EncyclopediaMenu>,
+    daily_menu: Res<DailyMenu>,
+    import_dialog: Res<ImportDialog>,
+    stats_menu: Res<StatsMenu>,
+    mut screen: ResMut<ControlsScreen>,
+) {
+    if !matches!(state.current(), AppState::MainMenu | AppState::InGame) {
+        return;
+    }
+    let button_pressed = |button_type| {
+        gamepads
+            .iter()
+            .any(|gamepad| gamepad_input.just_pressed(GamepadButton(*gamepad, button_type)))
+    };
+    let toggled = keyboard_input.just_pressed(CONTROLS_KEY) || button_pressed(CONTROLS_BUTTON);
+    if !screen.open {
+        if toggled
+            && !wardrobe_menu.is_open()
+            && !encyclopedia_menu.is_open()
+            && !daily_menu.is_open()
+            && !import_dialog.is_open()
+            && !stats_menu.is_open()
+        {
+            screen.open = true;
+            keyboard_input.clear();
+            gamepad_input.clear();
+        }
+        return;
+    }
+    if toggled
+        || keyboard_input.just_pressed(KeyCode::Escape)
+        || button_pressed(GamepadButtonType::East)
+    {
+        screen.open = false;
+    }
+    // Keep the keys and buttons from also triggering the menu entries or the game
+    keyboard_input.clear();
+    gamepad_input.clear();
+}
+
+/// Show the controls screen, listing the bindings of the active control scheme with the glyphs of
+/// the input device used last.
+fn update_controls_screen(
+    mut commands: Commands,
+    screen: Res<ControlsScreen>,
+    controls: Res<ActiveControls>,
+    device: Res<ActiveInputDevice>,
+    ui_resources: Res<UiResources>,
+    panel_query: Query<Entity, (With<ControlsScreenUi>, Without<Parent>)>,
+) {
+    if !screen.is_changed() && !device.is_changed() {
+        return;
+    }
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !screen.open {
+        return;
+    }
+
+    let (title, note) = match device.0 {
+        InputDevice::Keyboard => ("Keyboard", ""),
+        InputDevice::Gamepad => ("Gamepad", ""),
+        InputDevice::Touch => ("Keyboard", "\n\nConnect a keyboard or a gamepad to play"),
+    };
+    let mut bindings = controls.0.bindings();
+    bindings.extend(extra_bindings());
+    let lines: String = binding_lines(&bindings, device.0)
+        .into_iter()
+        .map(|line| format!("\n{}", line))
+        .collect();
+    let style = |font_size, color| TextStyle {
+        font: ui_resources.text_font(),
+        font_size,
+        color,
+    };
+    let sections = [
+        format!("Controls - {}", title),
+        format!("\n{}{}", lines, note),
+        format!("\n\n{} to close", controls_glyph(device.0)),
+    ]
+    .into_iter()
+    .zip([
+        (36.0, Color::WHITE),
+        (22.0, Color::rgb_u8(200, 200, 200)),
+        (18.0, Color::WHITE),
+    ])
+    .map(|(value, (font_size, color))| TextSection {
+        value,
+        style: style(font_size, color),
+    })
+    .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("ControlsScreen"))
+        .insert(MenuOverlay)
+        .insert(ControlsScreenUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .insert(ControlsScreenUi);
+        });
+}
+
+fn controls_screen_cleanup(
+    mut commands: Commands,
+    mut screen: ResMut<ControlsScreen>,
+    panel_query: Query<Entity, (With<ControlsScreenUi>, Without<Parent>)>,
+) {
+    screen.open = false;
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the controls screen, listing the bindings of the controls from the main menu and in
+/// game, with the glyphs of the keyboard or the gamepad depending on the device used last.
+pub struct ControlsScreenPlugin;
+
+impl Plugin for ControlsScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ControlsScreen::default())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                controls_screen_input
+                    .after(InputSystem)
+                    .after("profile_menu_input")
+                    .after("resume_input")
+                    .before("stats_input")
+                    .before("calendar_input")
+                    .before("encyclopedia_input"),
+            );
+        for state in [AppState::MainMenu, AppState::InGame] {
+            app.add_system_set(SystemSet::on_update(state).with_system(update_controls_screen))
+                .add_system_set(SystemSet::on_exit(state).with_system(controls_screen_cleanup));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::ControlScheme;
+
+    #[test]
+    fn lines() {
+        let mut bindings = ControlScheme::All.bindings();
+        bindings.extend(extra_bindings());
+        let keyboard = binding_lines(&bindings, InputDevice::Keyboard);
+        let gamepad = binding_lines(&bindings, InputDevice::Gamepad);
+        assert_eq!(keyboard[4], "[Space] - Place the buildable");
+        assert_eq!(gamepad[4], "(A) - Place the buildable");
+        // Slots on the keyboard only, radial menu on the gamepad only
+        assert_eq!(keyboard.len(), gamepad.len());
+        assert!(keyboard
+            .iter()
+            .any(|line| line.starts_with("[1] [2] [3] [4] [5] - ")));
+        assert_eq!(keyboard.last().unwrap(), "[F6] - Show the controls");
+        assert_eq!(controls_glyph(InputDevice::Gamepad), "(Start)");
+    }
+}
@@ -0,0 +1,219 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use serde::Deserialize;
+
+use crate::{
+    game::{run_if_playing, GameplaySystem},
+    journal::LevelJournal,
+    level::{mark_for_despawn, PendingDespawn},
+    slide_buildable, AppState, Grid, Level, Placed, Plate, TilesSpawnedEvent,
+};
+
+/// Elevation of the arrow of a conveyor tile above the terrace of its cell.
+const ARROW_ELEVATION: f32 = 0.06;
+
+/// Conveyor tile of a level, which shifts the buildable placed on it one cell in a direction at
+/// fixed intervals.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ConveyorDesc {
+    /// Grid coordinates of the tile.
+    pub pos: IVec2,
+    /// Grid direction the buildable is shifted toward, one of the 4 unit axes.
+    pub direction: IVec2,
+    /// Time in seconds between two shifts.
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+}
+
+fn default_interval() -> f32 {
+    3.0
+}
+
+/// Arrow of a conveyor tile, at the given grid coordinates.
+#[derive(Debug, Component)]
+pub struct ConveyorTile {
+    pub pos: IVec2,
+    direction: IVec2,
+    timer: Timer,
+}
+
+/// Resource holding the mesh and material of the conveyor arrows.
+#[derive(Debug, Default)]
+struct ConveyorAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+/// Create a flat arrow mesh in the XZ plane, facing up (+Y) and pointing toward -Z, the +Y
+/// direction of the grid.
+fn create_arrow_mesh() -> Mesh {
+    let outline = [
+        [-0.08, 0.3],
+        [-0.08, -0.05],
+        [-0.25, -0.05],
+        [0.0, -0.35],
+        [0.25, -0.05],
+        [0.08, -0.05],
+        [0.08, 0.3],
+    ];
+    let positions: Vec<_> = outline.iter().map(|&[x, z]| [x, 0.0, z]).collect();
+    let normals = vec![[0.0, 1.0, 0.0]; outline.len()];
+    let uvs: Vec<_> = outline.iter().map(|&[x, z]| [x + 0.5, z + 0.5]).collect();
+    // Shaft, then head; counter-clockwise when seen from above
+    let indices = vec![0, 6, 1, 1, 6, 5, 2, 4, 3];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Rotation of an arrow pointing toward the given grid direction. Plate local space has Z
+/// pointing toward -Y.
+fn arrow_rotation(direction: IVec2) -> Quat {
+    Quat::from_rotation_y((-direction.x as f32).atan2(direction.y as f32))
+}
+
+fn setup_conveyor_assets(
+    mut assets: ResMut<ConveyorAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    assets.mesh = meshes.add(create_arrow_mesh());
+    assets.material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.95, 0.8, 0.35),
+        unlit: true,
+        ..Default::default()
+    });
+}
+
+/// Spawn the arrows of the conveyor tiles once the tiles of the plate regenerated.
+fn spawn_conveyor_tiles(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    level: Res<Level>,
+    assets: Res<ConveyorAssets>,
+    mut ev_tiles_spawned: EventReader<TilesSpawnedEvent>,
+    conveyor_query: Query<Entity, With<ConveyorTile>>,
+    plate_query: Query<Entity, With<Plate>>,
+) {
+    if ev_tiles_spawned.iter().last().is_none() {
+        return;
+    }
+    for entity in conveyor_query.iter() {
+        mark_for_despawn(&mut commands, entity);
+    }
+    let (level_desc, plate) = match (level.desc(), plate_query.get_single()) {
+        (Some(level_desc), Ok(plate)) => (level_desc, plate),
+        _ => return,
+    };
+    for conveyor in &level_desc.conveyors {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: assets.mesh.clone(),
+                material: assets.material.clone(),
+                transform: Transform {
+                    translation: grid.translation(&conveyor.pos, ARROW_ELEVATION),
+                    rotation: arrow_rotation(conveyor.direction),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Name::new(format!("Conveyor{:?}", conveyor.pos.to_array())))
+            .insert(ConveyorTile {
+                pos: conveyor.pos,
+                direction: conveyor.direction,
+                timer: Timer::from_seconds(conveyor.interval, true),
+            })
+            .insert(Parent(plate));
+    }
+}
+
+/// Shift the buildables on the conveyor tiles to the next cell at each interval, if that cell is
+/// free. A buildable moves at most once per frame, even if shifted onto another conveyor.
+fn conveyor_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut grid: ResMut<Grid>,
+    mut journal: ResMut<LevelJournal>,
+    mut conveyor_query: Query<&mut ConveyorTile>,
+    mut placed_query: Query<(Entity, &mut Placed, &Transform), Without<PendingDespawn>>,
+) {
+    let mut moved = vec![];
+    for mut conveyor in conveyor_query.iter_mut() {
+        if !conveyor.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let from = conveyor.pos;
+        let to = from + conveyor.direction;
+        if grid.clamp(from) != from
+            || moved.contains(&from)
+            || grid.cell(&from).buildable.is_none()
+            || grid.clamp(to) != to
+            || !grid.can_spawn_item(&to)
+        {
+            continue;
+        }
+        if let Some((entity, mut placed, transform)) = placed_query
+            .iter_mut()
+            .find(|(_, placed, _)| placed.0 == from)
+        {
+            slide_buildable(
+                &mut commands,
+                &mut grid,
+                &mut journal,
+                entity,
+                &mut placed,
+                transform,
+                to,
+            );
+            moved.push(to);
+        }
+    }
+}
+
+/// Plugin for the conveyor tiles, shifting the buildables placed on them.
+pub struct ConveyorPlugin;
+
+impl Plugin for ConveyorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ConveyorAssets::default())
+            .add_startup_system(setup_conveyor_assets)
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Balance)
+                    .after(GameplaySystem::Placement)
+                    .with_system(spawn_conveyor_tiles),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Placement)
+                    .after(GameplaySystem::Cursor)
+                    .with_system(conveyor_system),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow() {
+        // The arrow mesh points toward -Z, which is +Y on the grid
+        let tip = Vec3::new(0.0, 0.0, -1.0);
+        for (direction, expected) in [
+            (IVec2::Y, Vec3::new(0.0, 0.0, -1.0)),
+            (-IVec2::Y, Vec3::new(0.0, 0.0, 1.0)),
+            (IVec2::X, Vec3::new(1.0, 0.0, 0.0)),
+            (-IVec2::X, Vec3::new(-1.0, 0.0, 0.0)),
+        ] {
+            let tip = arrow_rotation(direction) * tip;
+            assert!((tip - expected).length() < 1e-5, "{:?}", direction);
+        }
+    }
+}
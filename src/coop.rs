@@ -0,0 +1,335 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    config::Config,
+    controls::{
+        ActionMapper, ActiveControls, ControlScheme, ControlsInput, CursorInput, PlayerAction,
+    },
+    game::{run_if_playing, GameMode, GameplaySystem},
+    inventory::{Inventory, SelectSlot, Slot, UpdateInventorySlots},
+    journal::{LevelJournal, LevelOp},
+    serialize::BuildableRegistry,
+    AppState, Cursor, CursorId, Grid, PlaceBuildableEvent, Plate, ResetPlateEvent,
+    RestartLevelEvent,
+};
+
+/// Control scheme of the partner. The first player uses [`ControlScheme::KeyboardLeft`].
+const PARTNER_CONTROLS: ControlScheme = ControlScheme::KeyboardRight;
+
+/// Resource holding the state of the partner (second player) in coop mode.
+pub struct Coop {
+    /// Partner inventory, split from the level inventory.
+    inventory: Inventory,
+    /// Position of the partner cursor on the board, in cell coordinates.
+    pos: IVec2,
+    /// Mapping of the partner controls to actions.
+    mapper: ActionMapper,
+    /// Buffered actions of the partner.
+    input: CursorInput,
+    /// Partner cursor entity, once spawned.
+    entity: Option<Entity>,
+    /// Partner cursor material when the cell under it can receive a buildable.
+    valid_mat: Handle<StandardMaterial>,
+    /// Partner cursor material when the cell under it is occupied.
+    invalid_mat: Handle<StandardMaterial>,
+}
+
+impl Coop {
+    pub fn new() -> Self {
+        Coop {
+            inventory: Inventory::new(),
+            pos: IVec2::ZERO,
+            mapper: ActionMapper::new(),
+            input: CursorInput::new(),
+            entity: None,
+            valid_mat: Default::default(),
+            invalid_mat: Default::default(),
+        }
+    }
+
+    /// Has the partner placed all their buildables? This is always `true` outside coop mode.
+    pub fn is_done(&self) -> bool {
+        self.inventory.is_empty()
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+}
+
+/// Marker for the text listing the partner inventory.
+#[derive(Component)]
+struct CoopText;
+
+/// Marker for the partner cursor.
+#[derive(Component)]
+struct PartnerCursor;
+
+fn coop_setup(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    ui_resources: Res<UiResources>,
+    mut controls: ResMut<ActiveControls>,
+) {
+    if *game_mode != GameMode::Coop {
+        return;
+    }
+    controls.0 = ControlScheme::KeyboardLeft;
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(40.0),
+                    left: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 20.0,
+                    color: Color::rgb_u8(230, 150, 60),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("CoopText"))
+        .insert(Hud)
+        .insert(CoopText);
+}
+
+/// Spawn the partner cursor as a child of the plate, once the plate is spawned.
+fn spawn_partner_cursor(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut coop: ResMut<Coop>,
+    query: Query<Entity, Added<Plate>>,
+) {
+    if *game_mode != GameMode::Coop {
+        return;
+    }
+    for plate in query.iter() {
+        coop.valid_mat = materials.add(Color::rgb(0.95, 0.6, 0.25).into());
+        coop.invalid_mat = materials.add(Color::rgb(0.55, 0.25, 0.2).into());
+        let entity = commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.7 })),
+                material: coop.valid_mat.clone(),
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(Name::new("PartnerCursor"))
+            .insert(PartnerCursor)
+            .insert(Parent(plate))
+            .id();
+        coop.entity = Some(entity);
+    }
+}
+
+/// Give half of the level inventory to the partner whenever the level starts or restarts.
+fn coop_split_inventory(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    game_mode: Res<GameMode>,
+    grid: Res<Grid>,
+    mut inventory: ResMut<Inventory>,
+    mut journal: ResMut<LevelJournal>,
+    mut coop: ResMut<Coop>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if !(reset || restart) || *game_mode != GameMode::Coop {
+        return;
+    }
+    coop.inventory = inventory.split();
+    journal.record(LevelOp::Split);
+    coop.pos = grid.max_pos();
+    trace!("Coop: partner inventory {:?}", coop.inventory.slots());
+    ev_update_slots.send(UpdateInventorySlots);
+}
+
+/// Buffer the actions of the partner. Those don't go through the [`PlayerAction`] events, which
+/// drive the first player.
+fn partner_input_system(
+    game_mode: Res<GameMode>,
+    time: Res<Time>,
+    config: Res<Config>,
+    input: ControlsInput,
+    mut coop: ResMut<Coop>,
+) {
+    if *game_mode != GameMode::Coop {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    let coop = &mut *coop;
+    coop.input.expire(now, config.input.buffer_window);
+    for action in coop.mapper.update(PARTNER_CONTROLS, &input) {
+        coop.input.push(action, now);
+    }
+}
+
+/// Move the partner cursor, and request to place the buildable of the selected partner slot at
+/// the partner cursor position. The placement itself is shared with the player, see
+/// [`PlaceBuildableEvent`].
+fn partner_cursor_system(
+    mut ev_place: EventWriter<PlaceBuildableEvent>,
+    game_mode: Res<GameMode>,
+    grid: Res<Grid>,
+    mut coop: ResMut<Coop>,
+    query_cursor: Query<&Cursor>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut Visibility,
+            &mut Handle<StandardMaterial>,
+        ),
+        With<PartnerCursor>,
+    >,
+) {
+    if *game_mode != GameMode::Coop {
+        return;
+    }
+    let cursor = query_cursor.single();
+    let (mut transform, mut visibility, mut material) = match query.get_single_mut() {
+        Ok(partner) => partner,
+        Err(_) => return,
+    };
+    visibility.is_visible = cursor.enabled() && !coop.is_done();
+    if !visibility.is_visible {
+        return;
+    }
+
+    while let Some(action) = coop.input.pop() {
+        match action {
+            PlayerAction::MoveCursor(delta) => coop.pos = grid.clamp(coop.pos + delta),
+            // Cycle through the partner slots
+            PlayerAction::CyclePrev => {
+                coop.inventory.select_slot(&SelectSlot::Prev);
+            }
+            PlayerAction::CycleNext => {
+                coop.inventory.select_slot(&SelectSlot::Next);
+            }
+            PlayerAction::Place => {
+                if let Some(slot) = coop
+                    .inventory
+                    .selected_slot()
+                    .filter(|slot| !slot.is_empty())
+                {
+                    ev_place.send(PlaceBuildableEvent {
+                        pos: coop.pos,
+                        bref: slot.bref(),
+                        cursor: CursorId::Partner,
+                    });
+                }
+            }
+            // The level restart is shared with the first player
+            PlayerAction::Restart | PlayerAction::SelectSlot(_) => {}
+        }
+    }
+    transform.translation = grid.translation(&coop.pos, 0.1);
+
+    let mat = if grid.can_spawn_item(&coop.pos) {
+        &coop.valid_mat
+    } else {
+        &coop.invalid_mat
+    };
+    if *material != *mat {
+        *material = mat.clone();
+    }
+}
+
+fn coop_text(
+    coop: Res<Coop>,
+    buildables: Res<BuildableRegistry>,
+    mut query: Query<&mut Text, With<CoopText>>,
+) {
+    for mut text in query.iter_mut() {
+        let selected = coop.inventory.selected_slot().map(Slot::bref);
+        let slots = coop
+            .inventory()
+            .slots()
+            .iter()
+            .filter_map(|slot| {
+                let name = buildables.get(slot.bref())?.name();
+                Some(if Some(slot.bref()) == selected {
+                    format!("[{} x{}]", name, slot.count())
+                } else {
+                    format!("{} x{}", name, slot.count())
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        text.sections[0].value = format!(
+            "Player 2 ({}, Right Shift to switch): {}",
+            PARTNER_CONTROLS.description(),
+            slots
+        );
+    }
+}
+
+fn coop_cleanup(
+    mut commands: Commands,
+    mut coop: ResMut<Coop>,
+    mut controls: ResMut<ActiveControls>,
+    query: Query<Entity, With<CoopText>>,
+) {
+    controls.0 = ControlScheme::All;
+    if let Some(entity) = coop.entity.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *coop = Coop::new();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the local two-player cooperative mode, where each player has their own cursor
+/// and half of the level inventory, and both balance the same plate together.
+pub struct CoopPlugin;
+
+impl Plugin for CoopPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Coop::new())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(coop_setup))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Input)
+                    .with_system(partner_input_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Reset)
+                    .after("journal_start")
+                    .before(GameplaySystem::Cursor)
+                    .with_system(spawn_partner_cursor)
+                    .with_system(coop_split_inventory),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Cursor)
+                    .after(GameplaySystem::Reset)
+                    .with_system(partner_cursor_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(coop_text),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(coop_cleanup));
+    }
+}
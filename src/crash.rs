@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::paths;
+use crate::{boot::UiResources, logging::recent_log_lines, AppState};
+
+/// File the report of the last crash is written to in the data folder, on native platforms.
+#[cfg(not(target_arch = "wasm32"))]
+const CRASH_FILE: &str = "crash_report.txt";
+
+/// Key of the browser local storage the report of the last crash is written to, on the web.
+#[cfg(target_arch = "wasm32")]
+const CRASH_STORAGE_KEY: &str = "libracity.crash_report";
+
+/// Number of lines of the report shown in the main menu.
+const PREVIEW_LINES: usize = 24;
+
+/// Number of lines at the top of the report, with the panic message, always shown.
+const PREVIEW_HEAD_LINES: usize = 4;
+
+/// Format the report of a panic from its panic info, with the recent log lines leading to it.
+fn format_report(info: &dyn std::fmt::Display) -> String {
+    let mut report = format!(
+        "Libra City {} ({} {}) crashed.\n\n{}\n\nRecent log:\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        info
+    );
+    for line in recent_log_lines() {
+        report.push_str(&line.to_string());
+        report.push('\n');
+    }
+    report
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_report(report: &str) {
+    let path = paths::data_file(CRASH_FILE);
+    if let Err(err) = std::fs::write(&path, report) {
+        eprintln!(
+            "Failed to write crash report to '{}': {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_report(report: &str) {
+    if let Some(storage) =
+        web_sys::window().and_then(|window| window.local_storage().ok().flatten())
+    {
+        let _ = storage.set_item(CRASH_STORAGE_KEY, report);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_report() -> Option<String> {
+    std::fs::read_to_string(paths::data_file(CRASH_FILE)).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_report() -> Option<String> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(CRASH_STORAGE_KEY).ok().flatten())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn delete_report() {
+    let _ = std::fs::remove_file(paths::data_file(CRASH_FILE));
+}
+
+#[cfg(target_arch = "wasm32")]
+fn delete_report() {
+    if let Some(storage) =
+        web_sys::window().and_then(|window| window.local_storage().ok().flatten())
+    {
+        let _ = storage.remove_item(CRASH_STORAGE_KEY);
+    }
+}
+
+/// Copy the report to the clipboard. Returns a status message for the player.
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_report(report: &str) -> String {
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(report.to_owned()))
+    {
+        Ok(()) => "Report copied to the clipboard.".to_owned(),
+        Err(err) => format!("Failed to copy the report: {}", err),
+    }
+}
+
+/// The browser clipboard needs a user gesture on the page; log the report to the browser console
+/// instead, where it can be copied from.
+#[cfg(target_arch = "wasm32")]
+fn copy_report(report: &str) -> String {
+    warn!("Crash report:\n{}", report);
+    "Report printed to the browser console.".to_owned()
+}
+
+/// Install a panic hook writing a crash report with the panic message and the recent log lines,
+/// shown on the next launch. The previous hook, like the one printing the panic to the browser
+/// console on the web, still runs afterwards.
+pub fn install_panic_hook() {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        save_report(&format_report(info));
+        previous_hook(info);
+    }));
+}
+
+/// Resource holding the report of the crash of the previous session, if any.
+pub struct CrashReport {
+    report: Option<String>,
+    /// Is the full report displayed, or only the notice?
+    expanded: bool,
+    /// Status of the last action.
+    status: String,
+    /// Root entity of the crash notice UI.
+    entity: Option<Entity>,
+}
+
+impl CrashReport {
+    /// Load the report written by the panic hook of the previous session, if any.
+    pub fn load() -> Self {
+        let report = load_report();
+        if report.is_some() {
+            warn!("The previous session crashed.");
+        }
+        CrashReport {
+            report,
+            expanded: false,
+            status: String::new(),
+            entity: None,
+        }
+    }
+
+    /// Text of the crash notice.
+    fn text(&self) -> String {
+        let mut text = "Libra City crashed last time. Sorry about that!\n\
+            [R] to show the report, [Y] to copy it for a bug report, [X] to dismiss"
+            .to_owned();
+        if !self.status.is_empty() {
+            text.push('\n');
+            text.push_str(&self.status);
+        }
+        if let (true, Some(report)) = (self.expanded, &self.report) {
+            text.push_str("\n\n");
+            let lines: Vec<_> = report.lines().collect();
+            if lines.len() > PREVIEW_LINES {
+                // Keep the panic message on top, and the most recent log lines
+                let tail = lines.len() - (PREVIEW_LINES - PREVIEW_HEAD_LINES);
+                text.push_str(&lines[..PREVIEW_HEAD_LINES].join("\n"));
+                text.push_str("\n...\n");
+                text.push_str(&lines[tail..].join("\n"));
+            } else {
+                text.push_str(report);
+            }
+        }
+        text
+    }
+}
+
+/// Marker for the text of the crash notice.
+#[derive(Component)]
+struct CrashText;
+
+fn spawn_crash_notice(
+    mut commands: Commands,
+    ui_resources: Res<UiResources>,
+    mut crash_report: ResMut<CrashReport>,
+) {
+    if crash_report.report.is_none() {
+        return;
+    }
+    let entity = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(8.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.3, 0.05, 0.05, 0.9)),
+            ..Default::default()
+        })
+        .insert(Name::new("CrashNotice"))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        crash_report.text(),
+                        TextStyle {
+                            font: ui_resources.text_font(),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(CrashText);
+        })
+        .id();
+    crash_report.entity = Some(entity);
+}
+
+/// Show, copy, or dismiss the crash report.
+fn crash_notice(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut crash_report: ResMut<CrashReport>,
+    mut query: Query<&mut Text, With<CrashText>>,
+) {
+    let report = match (&crash_report.report, crash_report.entity) {
+        (Some(report), Some(_)) => report.clone(),
+        _ => return,
+    };
+    if keyboard_input.just_pressed(KeyCode::R) {
+        crash_report.expanded = !crash_report.expanded;
+    } else if keyboard_input.just_pressed(KeyCode::Y) {
+        crash_report.status = copy_report(&report);
+    } else if keyboard_input.just_pressed(KeyCode::X) {
+        keyboard_input.reset(KeyCode::X);
+        delete_report();
+        crash_report.report = None;
+        if let Some(entity) = crash_report.entity.take() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    } else {
+        return;
+    }
+    if let Ok(mut text) = query.get_single_mut() {
+        text.sections[0].value = crash_report.text();
+    }
+}
+
+fn crash_notice_cleanup(mut commands: Commands, mut crash_report: ResMut<CrashReport>) {
+    if let Some(entity) = crash_report.entity.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin offering in the main menu to show and copy the report of the crash of the previous
+/// session, if any, to make bug reports actionable. The report is written by the panic hook of
+/// [`install_panic_hook()`].
+pub struct CrashPlugin;
+
+impl Plugin for CrashPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CrashReport::load())
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(spawn_crash_notice))
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(crash_notice))
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(crash_notice_cleanup),
+            );
+    }
+}
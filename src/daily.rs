@@ -0,0 +1,569 @@
+use bevy::{input::InputSystem, prelude::*};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, sync::Arc};
+
+use crate::{
+    boot::UiResources,
+    encyclopedia::EncyclopediaMenu,
+    game::{GameEvent, GameMode},
+    mainmenu::MenuOverlay,
+    remix::remix,
+    serialize::{BuildableRegistry, LevelDesc, Levels},
+    storage,
+    wardrobe::WardrobeMenu,
+    AppState, LoadLevel, LoadLevelEvent,
+};
+
+/// Save file of the daily challenges completed, in the profile storage.
+pub const DAILY_FILE: &str = "daily.json";
+
+/// Key to open and close the daily challenge calendar in the main menu.
+const DAILY_KEY: KeyCode = KeyCode::D;
+
+/// Seed of the daily challenges, mixed with the day so each day draws a different level.
+const DAILY_SEED: u64 = 0x6c69_6272_6164_6179;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Size of a day of the calendar, in pixels.
+const CALENDAR_CELL_SIZE: f32 = 56.0;
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Current time in seconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+fn now_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Current time in seconds since the Unix epoch.
+#[cfg(target_arch = "wasm32")]
+fn now_seconds() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+/// Current day as a number of days since the Unix epoch, in UTC, and the time left until the next
+/// day in seconds.
+fn today() -> (u32, u64) {
+    let now = now_seconds();
+    (
+        (now / SECONDS_PER_DAY) as u32,
+        SECONDS_PER_DAY - now % SECONDS_PER_DAY,
+    )
+}
+
+/// Calendar date of a day since the Unix epoch, as a year, month in [1:12], and day of the month
+/// in [1:31].
+pub fn civil_date(day: u32) -> (u32, u32, u32) {
+    // Shift the epoch to March 1st of year 0, so that leap days end the years
+    let z = day + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u32::from(month <= 2);
+    (year, month, day_of_month)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Level of the daily challenge of a day, a remix of a level of the game drawn from the day, with
+/// the index of the original level. The same day always draws the same challenge, for all the
+/// players. Returns `None` if no level can be remixed.
+pub fn daily_level(
+    day: u32,
+    levels: &Levels,
+    buildables: &BuildableRegistry,
+) -> Option<(usize, LevelDesc)> {
+    let mut rng = ChaCha8Rng::seed_from_u64(DAILY_SEED ^ u64::from(day));
+    let mut indices: Vec<_> = (0..levels.len()).collect();
+    indices.shuffle(&mut rng);
+    indices.into_iter().find_map(|index| {
+        let mut desc = remix(levels.get(index)?, buildables, &mut rng)?;
+        let (year, month, day_of_month) = civil_date(day);
+        desc.name = format!("Daily challenge {}-{:02}-{:02}", year, month, day_of_month);
+        Some((index, desc))
+    })
+}
+
+/// Resource holding the daily challenges completed by the active profile, and the streaks of
+/// consecutive days completed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyProgress {
+    /// Days completed, in days since the Unix epoch.
+    completed: BTreeSet<u32>,
+    /// Longest streak of consecutive days completed.
+    best_streak: u32,
+}
+
+impl DailyProgress {
+    /// Load the daily challenges completed by the active profile in a previous session, if any.
+    pub fn load() -> Self {
+        storage::read(DAILY_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(progress) => Some(progress),
+                Err(err) => {
+                    let location = storage::location(DAILY_FILE);
+                    warn!("Failed to parse daily challenges '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(DAILY_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(DAILY_FILE);
+            warn!("Failed to save daily challenges to '{}': {}", location, err);
+        }
+    }
+
+    pub fn is_completed(&self, day: u32) -> bool {
+        self.completed.contains(&day)
+    }
+
+    /// Number of consecutive days completed up to the given day. The streak isn't broken until
+    /// the day is over, so it counts from the day before if the day isn't completed yet.
+    pub fn streak(&self, today: u32) -> u32 {
+        let mut day = today;
+        if !self.is_completed(day) {
+            day = match day.checked_sub(1) {
+                Some(day) => day,
+                None => return 0,
+            };
+        }
+        let mut streak = 0;
+        while self.is_completed(day) {
+            streak += 1;
+            day = match day.checked_sub(1) {
+                Some(day) => day,
+                None => break,
+            };
+        }
+        streak
+    }
+
+    pub fn best_streak(&self) -> u32 {
+        self.best_streak
+    }
+
+    /// Record the daily challenge of a day as completed, returning `true` if it's the first time.
+    fn record_clear(&mut self, day: u32) -> bool {
+        if !self.completed.insert(day) {
+            return false;
+        }
+        self.best_streak = self.best_streak.max(self.streak(day));
+        self.save();
+        true
+    }
+}
+
+/// Resource holding the daily challenge being played, if any.
+#[derive(Debug, Default)]
+pub struct DailyChallenge {
+    /// Day of the challenge, in days since the Unix epoch.
+    day: u32,
+    /// Level of the challenge, with the index of the original level.
+    level: Option<(usize, Arc<LevelDesc>)>,
+    /// Was the level of the challenge requested since the game started?
+    requested: bool,
+}
+
+impl DailyChallenge {
+    /// Level of the daily challenge to load, with the index of the original level. Called by the
+    /// level loading for the [`LoadLevel::Daily`] requests.
+    pub fn level(&self) -> Option<(usize, Arc<LevelDesc>)> {
+        self.level.clone()
+    }
+}
+
+/// Resource holding the state of the calendar screen.
+#[derive(Debug, Default)]
+pub struct DailyMenu {
+    /// Is the calendar open?
+    open: bool,
+}
+
+impl DailyMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+/// Marker for the UI nodes of the calendar, kept visible while it's open.
+#[derive(Component)]
+struct CalendarUi;
+
+/// Marker for the text counting down to the next daily challenge.
+#[derive(Component)]
+struct CountdownText;
+
+/// Open and close the calendar, and start the daily challenge from it. Runs right after the input
+/// is updated, to hide the keys used by the calendar from the rest of the menu while it's open.
+fn calendar_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    levels: Res<Levels>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    encyclopedia_menu: Res<EncyclopediaMenu>,
+    mut menu: ResMut<DailyMenu>,
+    mut game_mode: ResMut<GameMode>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !menu.open {
+        if keyboard_input.just_pressed(DAILY_KEY)
+            && !levels.is_empty()
+            && !wardrobe_menu.is_open()
+            && !encyclopedia_menu.is_open()
+        {
+            menu.open = true;
+            keyboard_input.clear();
+        }
+        return;
+    }
+    if keyboard_input.just_pressed(DAILY_KEY) || keyboard_input.just_pressed(KeyCode::Escape) {
+        menu.open = false;
+    } else if keyboard_input.just_pressed(KeyCode::Return) && state.set(AppState::InGame).is_ok() {
+        menu.open = false;
+        *game_mode = GameMode::Daily;
+    }
+    // Keep the keys from also triggering the menu entries
+    keyboard_input.clear();
+}
+
+fn countdown_text(seconds: u64) -> String {
+    format!(
+        "Next daily challenge in {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Show the calendar of the current month with the daily challenges completed, and the streaks.
+fn update_calendar_panel(
+    mut commands: Commands,
+    menu: Res<DailyMenu>,
+    progress: Res<DailyProgress>,
+    ui_resources: Res<UiResources>,
+    panel_query: Query<Entity, (With<CalendarUi>, Without<Parent>)>,
+    mut countdown_query: Query<&mut Text, With<CountdownText>>,
+) {
+    let (today, time_left) = today();
+    if !menu.is_changed() && !progress.is_changed() {
+        if let Ok(mut text) = countdown_query.get_single_mut() {
+            let countdown = countdown_text(time_left);
+            if text.sections[0].value != countdown {
+                text.sections[0].value = countdown;
+            }
+        }
+        return;
+    }
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !menu.open {
+        return;
+    }
+
+    let (year, month, day_of_month) = civil_date(today);
+    let first_day = today + 1 - day_of_month;
+    let style = |font_size, color| TextStyle {
+        font: ui_resources.text_font(),
+        font_size,
+        color,
+    };
+    let header = [
+        format!("{} {}", MONTH_NAMES[month as usize - 1], year),
+        format!(
+            "\nStreak: {} day(s) - Best: {} day(s)",
+            progress.streak(today),
+            progress.best_streak()
+        ),
+        format!(
+            "\n[ENTER] Play today's challenge{}, [{:?}] to close",
+            if progress.is_completed(today) {
+                " again"
+            } else {
+                ""
+            },
+            DAILY_KEY
+        ),
+    ];
+    let header_sections = header
+        .into_iter()
+        .zip([
+            (36.0, Color::WHITE),
+            (24.0, Color::rgb_u8(255, 220, 120)),
+            (18.0, Color::WHITE),
+        ])
+        .map(|(value, (font_size, color))| TextSection {
+            value,
+            style: style(font_size, color),
+        })
+        .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("DailyCalendar"))
+        .insert(MenuOverlay)
+        .insert(CalendarUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: header_sections,
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .insert(CalendarUi);
+            // Days of the month, in weeks starting on Monday; the epoch was a Thursday
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(CALENDAR_CELL_SIZE * 7.0), Val::Auto),
+                        flex_wrap: FlexWrap::Wrap,
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..Default::default()
+                })
+                .insert(CalendarUi)
+                .with_children(|parent| {
+                    let offset = (first_day + 3) % 7;
+                    let count = days_in_month(year, month);
+                    for cell in 0..offset + count {
+                        let day = if cell >= offset {
+                            Some(first_day + cell - offset)
+                        } else {
+                            None
+                        };
+                        let color = match day {
+                            None => Color::NONE,
+                            Some(day) if progress.is_completed(day) => Color::rgb(0.3, 0.6, 0.4),
+                            Some(day) if day == today => Color::rgb(0.45, 0.45, 0.45),
+                            Some(_) => Color::rgb(0.2, 0.2, 0.2),
+                        };
+                        let text_color = if day.is_some_and(|day| day > today) {
+                            Color::rgb(0.5, 0.5, 0.5)
+                        } else {
+                            Color::WHITE
+                        };
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(
+                                        Val::Px(CALENDAR_CELL_SIZE - 4.0),
+                                        Val::Px(CALENDAR_CELL_SIZE - 4.0),
+                                    ),
+                                    margin: Rect::all(Val::Px(2.0)),
+                                    align_items: AlignItems::Center,
+                                    justify_content: JustifyContent::Center,
+                                    ..Default::default()
+                                },
+                                color: UiColor(color),
+                                ..Default::default()
+                            })
+                            .insert(CalendarUi)
+                            .with_children(|parent| {
+                                if let Some(day) = day {
+                                    parent
+                                        .spawn_bundle(TextBundle {
+                                            text: Text::with_section(
+                                                (day + 1 - first_day).to_string(),
+                                                style(20.0, text_color),
+                                                TextAlignment::default(),
+                                            ),
+                                            ..Default::default()
+                                        })
+                                        .insert(CalendarUi);
+                                }
+                            });
+                    }
+                });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        countdown_text(time_left),
+                        style(20.0, Color::rgb_u8(200, 200, 200)),
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(CalendarUi)
+                .insert(CountdownText);
+        });
+}
+
+fn calendar_cleanup(
+    mut commands: Commands,
+    mut menu: ResMut<DailyMenu>,
+    panel_query: Query<Entity, (With<CalendarUi>, Without<Parent>)>,
+) {
+    menu.open = false;
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Draw the daily challenge of the day and load it, once the game starts in daily mode.
+fn load_daily_level(
+    game_mode: Res<GameMode>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    mut daily: ResMut<DailyChallenge>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+) {
+    if *game_mode != GameMode::Daily || daily.requested {
+        return;
+    }
+    daily.requested = true;
+    let (day, _) = today();
+    match daily_level(day, &levels, &buildables) {
+        Some((index, desc)) => {
+            info!("Daily challenge: '{}' from level #{}", desc.name, index);
+            daily.day = day;
+            daily.level = Some((index, Arc::new(desc)));
+            ev_load_level.send(LoadLevelEvent(LoadLevel::Daily));
+        }
+        None => warn!("No level can be remixed into a daily challenge"),
+    }
+}
+
+/// Record the daily challenge as completed once cleared. The challenge counts for the day it was
+/// drawn on, even if cleared after midnight.
+fn record_daily_clear(
+    mut ev_game: EventReader<GameEvent>,
+    game_mode: Res<GameMode>,
+    daily: Res<DailyChallenge>,
+    mut progress: ResMut<DailyProgress>,
+) {
+    let cleared = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some();
+    if !cleared || *game_mode != GameMode::Daily || daily.level.is_none() {
+        return;
+    }
+    if progress.record_clear(daily.day) {
+        info!(
+            "Daily challenge completed, streak of {} day(s)",
+            progress.streak(daily.day)
+        );
+    }
+}
+
+fn daily_cleanup(mut daily: ResMut<DailyChallenge>) {
+    *daily = DailyChallenge::default();
+}
+
+/// Plugin for the daily challenge, a remix of a level drawn from the date, with a calendar of the
+/// challenges completed and the streak of consecutive days in the main menu.
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DailyProgress::load())
+            .insert_resource(DailyChallenge::default())
+            .insert_resource(DailyMenu::default())
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(AppState::MainMenu)
+                    .after(InputSystem)
+                    .after("profile_menu_input")
+                    .before("encyclopedia_input")
+                    .with_system(calendar_input.label("calendar_input")),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu).with_system(update_calendar_panel),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(calendar_cleanup))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(load_daily_level)
+                    .with_system(record_daily_clear),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(daily_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(59), (1970, 3, 1));
+        // 2024 is a leap year
+        assert_eq!(civil_date(19_782), (2024, 2, 29));
+        assert_eq!(civil_date(19_783), (2024, 3, 1));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(countdown_text(3725), "Next daily challenge in 01:02:05");
+    }
+
+    #[test]
+    fn streaks() {
+        let mut progress = DailyProgress::default();
+        assert_eq!(progress.streak(100), 0);
+        progress.completed.extend([95, 96, 98, 99]);
+        // Not broken until the day is over
+        assert_eq!(progress.streak(100), 2);
+        assert_eq!(progress.streak(99), 2);
+        assert_eq!(progress.streak(101), 0);
+        progress.completed.insert(97);
+        assert_eq!(progress.streak(100), 5);
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::{
+    prelude::*,
+    render::{render_graph::RenderGraph, RenderApp},
+    window::{CreateWindow, WindowId},
+};
+use bevy_inspector_egui::{
+    bevy_egui::{self, EguiContext},
+    egui, WorldInspectorParams,
+};
+
+use crate::{
+    console::{level_color, Console},
+    logging::recent_log_lines,
+    platform::GAME_TITLE,
+};
+
+/// Key to move the world inspector and the log console to the debug window, and back.
+const DETACH_KEY: KeyCode = KeyCode::F4;
+
+/// Render pass drawing the egui UI of the debug window.
+const DEBUG_EGUI_PASS: &str = "debug_window_egui_pass";
+
+/// Resource holding the separate OS window the debug tools can be detached to, so they don't
+/// obscure the game viewport while playtesting.
+///
+/// Bevy can't close a secondary window, and closing any window exits the app, so once created the
+/// window stays open for the rest of the session. Attaching the tools back only moves them to the
+/// game window.
+pub struct DebugWindow {
+    id: WindowId,
+    /// Was the window created?
+    created: bool,
+    /// Are the debug tools shown in the debug window instead of the game window?
+    detached: bool,
+}
+
+impl DebugWindow {
+    fn new() -> Self {
+        DebugWindow {
+            id: WindowId::new(),
+            created: false,
+            detached: false,
+        }
+    }
+}
+
+/// Detach the world inspector and the log console to the debug window on [F4], creating the
+/// window the first time, or attach them back to the game window.
+fn toggle_debug_window(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ev_create_window: EventWriter<CreateWindow>,
+    mut debug_window: ResMut<DebugWindow>,
+    mut inspector: ResMut<WorldInspectorParams>,
+    mut console: ResMut<Console>,
+) {
+    if !keyboard_input.just_pressed(DETACH_KEY) {
+        return;
+    }
+    debug_window.detached = !debug_window.detached;
+    if debug_window.detached && !debug_window.created {
+        ev_create_window.send(CreateWindow {
+            id: debug_window.id,
+            descriptor: WindowDescriptor {
+                title: format!("{} — Debug", GAME_TITLE),
+                width: 800.0,
+                height: 900.0,
+                ..Default::default()
+            },
+        });
+        debug_window.created = true;
+    }
+    if debug_window.detached {
+        inspector.window = debug_window.id;
+        inspector.enabled = true;
+    } else {
+        inspector.window = WindowId::primary();
+    }
+    console.set_detached(debug_window.detached, &mut commands);
+    debug!("Debug tools detached: {}", debug_window.detached);
+}
+
+/// Draw the recent log lines over the whole debug window, under the world inspector.
+fn debug_window_ui(debug_window: Res<DebugWindow>, mut egui_context: ResMut<EguiContext>) {
+    if !debug_window.detached {
+        return;
+    }
+    let ctx = match egui_context.try_ctx_for_window_mut(debug_window.id) {
+        Some(ctx) => ctx,
+        None => return,
+    };
+    // The panel covers the whole window, which has no camera to clear it
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.label(format!("[{:?}] Attach back to the game window", DETACH_KEY));
+        egui::ScrollArea::vertical()
+            .stick_to_bottom()
+            .show(ui, |ui| {
+                for line in recent_log_lines() {
+                    let [r, g, b, _] = level_color(line.level).as_rgba_f32();
+                    let color = egui::Color32::from_rgb(
+                        (r * 255.0) as u8,
+                        (g * 255.0) as u8,
+                        (b * 255.0) as u8,
+                    );
+                    ui.colored_label(color, line.to_string());
+                }
+            });
+    });
+}
+
+/// Plugin to detach the world inspector and the log console to a separate OS window. Only
+/// available in native debug builds.
+pub struct DebugWindowPlugin;
+
+impl Plugin for DebugWindowPlugin {
+    fn build(&self, app: &mut App) {
+        let debug_window = DebugWindow::new();
+        {
+            let render_app = app.sub_app_mut(RenderApp);
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            bevy_egui::setup_pipeline(
+                &mut graph,
+                bevy_egui::RenderGraphConfig {
+                    window_id: debug_window.id,
+                    egui_pass: DEBUG_EGUI_PASS,
+                },
+            );
+        }
+        app.insert_resource(debug_window)
+            .add_system(toggle_debug_window.label("toggle_debug_window"))
+            .add_system(debug_window_ui.after("toggle_debug_window"));
+    }
+}
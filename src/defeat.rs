@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources,
+    game::{DefeatReason, Game, GameEvent, GameSequence, GameplaySystem},
+    AppState, RestartLevelEvent,
+};
+
+/// Action of a button of the defeat panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+enum DefeatAction {
+    /// Restart the failed level.
+    Retry,
+    /// Go back to the main menu.
+    Menu,
+}
+
+/// Marker for the root of the defeat panel.
+#[derive(Component)]
+struct DefeatPanel;
+
+fn spawn_button(parent: &mut ChildBuilder, font: Handle<Font>, label: &str, action: DefeatAction) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(220.0), Val::Px(60.0)),
+                margin: Rect::all(Val::Px(10.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgb(0.25, 0.25, 0.25)),
+            ..Default::default()
+        })
+        .insert(action)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+/// Show the defeat panel when the level is failed.
+fn show_defeat_panel(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    ui_resources: Res<UiResources>,
+) {
+    let reason = match ev_game
+        .iter()
+        .filter_map(|ev| match ev {
+            GameEvent::LevelFailed { reason, .. } => Some(*reason),
+            _ => None,
+        })
+        .last()
+    {
+        Some(reason) => reason,
+        None => return,
+    };
+    let message = match reason {
+        DefeatReason::Unbalanced => "The plate is not balanced.",
+        DefeatReason::Toppled => "The plate toppled over!",
+        DefeatReason::TileBroken => "A tile gave way under the weight!",
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+            ..Default::default()
+        })
+        .insert(Name::new("DefeatPanel"))
+        .insert(DefeatPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "Defeat",
+                    TextStyle {
+                        font: ui_resources.title_font(),
+                        font_size: 120.0,
+                        color: Color::rgb_u8(230, 120, 110),
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    message,
+                    TextStyle {
+                        font: ui_resources.text_font(),
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    let font = ui_resources.text_font();
+                    spawn_button(parent, font.clone(), "[R] Retry", DefeatAction::Retry);
+                    spawn_button(parent, font, "[M] Menu", DefeatAction::Menu);
+                });
+        });
+}
+
+/// Retry or go back to the menu from the defeat panel, with the keyboard or the buttons.
+/// The [R] key restarts the level in any sequence, see `player_actions_system()`.
+fn defeat_input(
+    game: Res<Game>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+    query: Query<(&Interaction, &DefeatAction), Changed<Interaction>>,
+) {
+    if game.sequence() != GameSequence::Defeat {
+        return;
+    }
+    let mut action = query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, action)| *action);
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        action = Some(DefeatAction::Retry);
+    } else if keyboard_input.just_pressed(KeyCode::M) {
+        // BUGBUG -- https://bevy-cheatbook.github.io/programming/states.html
+        keyboard_input.reset(KeyCode::M);
+        action = Some(DefeatAction::Menu);
+    }
+    match action {
+        Some(DefeatAction::Retry) => ev_restart.send(RestartLevelEvent),
+        Some(DefeatAction::Menu) => {
+            info!("Back to the main menu");
+            app_state.set(AppState::MainMenu).unwrap();
+        }
+        None => {}
+    }
+}
+
+/// Hide the defeat panel once the level restarted.
+fn hide_defeat_panel(
+    mut commands: Commands,
+    game: Res<Game>,
+    query: Query<Entity, With<DefeatPanel>>,
+) {
+    if game.sequence() == GameSequence::Defeat {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn defeat_cleanup(mut commands: Commands, query: Query<Entity, With<DefeatPanel>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the defeat panel shown when a level is failed, offering to retry the level or to
+/// go back to the main menu.
+pub struct DefeatPlugin;
+
+impl Plugin for DefeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Input)
+                .with_system(defeat_input),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Ui)
+                .after(GameplaySystem::VictoryCheck)
+                .with_system(show_defeat_panel)
+                .with_system(hide_defeat_panel),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(defeat_cleanup));
+    }
+}
@@ -0,0 +1,450 @@
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{
+    boot::UiResources,
+    inventory::Inventory,
+    mainmenu::MenuOverlay,
+    serialize::{BuildableId, BuildableRegistry},
+    storage,
+    units::format_weight_range,
+    wardrobe::WardrobeMenu,
+    AppState, Level,
+};
+
+/// Save file of the buildables discovered so far, in the profile storage.
+pub const ENCYCLOPEDIA_FILE: &str = "encyclopedia.json";
+
+/// Key to open and close the encyclopedia, in the main menu and in game.
+const ENCYCLOPEDIA_KEY: KeyCode = KeyCode::K;
+
+/// Rotation speed of the turntable preview, in radians per second.
+const TURNTABLE_SPEED: f32 = 0.8;
+
+/// Tilt of the turntable preview toward the camera, in radians.
+const TURNTABLE_TILT: f32 = 0.35;
+
+/// Resource holding the buildables discovered so far, listed in the encyclopedia.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Encyclopedia {
+    /// Names of the buildables discovered in the inventory of a level.
+    discovered: HashSet<String>,
+}
+
+impl Encyclopedia {
+    /// Load the buildables discovered by the active profile in a previous session, if any.
+    pub fn load() -> Self {
+        storage::read(ENCYCLOPEDIA_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(encyclopedia) => Some(encyclopedia),
+                Err(err) => {
+                    let location = storage::location(ENCYCLOPEDIA_FILE);
+                    warn!("Failed to parse encyclopedia '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(ENCYCLOPEDIA_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(ENCYCLOPEDIA_FILE);
+            warn!("Failed to save encyclopedia to '{}': {}", location, err);
+        }
+    }
+
+    pub fn is_discovered(&self, name: &str) -> bool {
+        self.discovered.contains(name)
+    }
+
+    /// Record some buildables as discovered, saving them if any is new.
+    fn discover<'a>(&mut self, names: impl Iterator<Item = &'a str>) {
+        let mut any_new = false;
+        for name in names {
+            if self.discovered.insert(name.to_owned()) {
+                info!("Discovered buildable '{}'", name);
+                any_new = true;
+            }
+        }
+        if any_new {
+            self.save();
+        }
+    }
+}
+
+/// Resource holding the state of the encyclopedia screen.
+#[derive(Debug, Default)]
+pub struct EncyclopediaMenu {
+    /// Is the encyclopedia open?
+    open: bool,
+    /// Index of the highlighted buildable in the registry.
+    index: usize,
+}
+
+impl EncyclopediaMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+/// Marker for the UI nodes of the encyclopedia, kept visible while it's open.
+#[derive(Component)]
+struct EncyclopediaUi;
+
+/// Marker for the text of the encyclopedia.
+#[derive(Component)]
+struct EncyclopediaText;
+
+/// Marker for the root of the 3D preview, in front of the camera.
+#[derive(Component)]
+struct PreviewStage;
+
+/// Turntable spinning the model of the highlighted buildable.
+#[derive(Component)]
+struct Turntable {
+    bref: BuildableId,
+    angle: f32,
+}
+
+/// Highlighted buildable, if any.
+fn highlighted(menu: &EncyclopediaMenu, buildables: &BuildableRegistry) -> Option<BuildableId> {
+    buildables.iter().nth(menu.index).map(|(id, _)| id)
+}
+
+/// Open, close, and browse the encyclopedia. Runs right after the input is updated, to hide the
+/// keys used to browse it from the rest of the game while it's open.
+fn encyclopedia_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    buildables: Res<BuildableRegistry>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    mut menu: ResMut<EncyclopediaMenu>,
+) {
+    let count = buildables.iter().count();
+    if !menu.open {
+        if keyboard_input.just_pressed(ENCYCLOPEDIA_KEY) && count > 0 && !wardrobe_menu.is_open() {
+            menu.open = true;
+            menu.index = menu.index.min(count - 1);
+            keyboard_input.clear();
+        }
+        return;
+    }
+    if keyboard_input.just_pressed(ENCYCLOPEDIA_KEY) || keyboard_input.just_pressed(KeyCode::Escape)
+    {
+        menu.open = false;
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        menu.index = (menu.index + count - 1) % count;
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        menu.index = (menu.index + 1) % count;
+    }
+    // Keep the keys from also triggering the menu and gameplay actions
+    keyboard_input.clear();
+}
+
+/// Record the buildables of the inventory of the level being played as discovered.
+fn discover_buildables(
+    inventory: Res<Inventory>,
+    buildables: Res<BuildableRegistry>,
+    mut encyclopedia: ResMut<Encyclopedia>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+    let names = inventory
+        .slots()
+        .iter()
+        .filter_map(|slot| buildables.name(slot.bref()));
+    if names.clone().any(|name| !encyclopedia.is_discovered(name)) {
+        encyclopedia.discover(names);
+    }
+}
+
+/// Show the list of buildables and the details of the highlighted one.
+fn update_encyclopedia_panel(
+    mut commands: Commands,
+    menu: Res<EncyclopediaMenu>,
+    buildables: Res<BuildableRegistry>,
+    encyclopedia: Res<Encyclopedia>,
+    level: Res<Level>,
+    ui_resources: Res<UiResources>,
+    panel_query: Query<Entity, (With<EncyclopediaUi>, Without<EncyclopediaText>)>,
+    mut text_query: Query<&mut Text, With<EncyclopediaText>>,
+) {
+    if !menu.is_changed() && !encyclopedia.is_changed() {
+        return;
+    }
+    if !menu.open {
+        for entity in panel_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let total = buildables.iter().count();
+    let discovered = buildables
+        .iter()
+        .filter(|(id, _)| {
+            buildables
+                .name(*id)
+                .is_some_and(|name| encyclopedia.is_discovered(name))
+        })
+        .count();
+    let mut lines = vec![
+        format!("Encyclopedia: {}/{} discovered", discovered, total),
+        format!("[UP]/[DOWN] to browse, [{:?}] to close", ENCYCLOPEDIA_KEY),
+        String::new(),
+    ];
+    let mut name = String::new();
+    let mut details = String::new();
+    let mut description = String::new();
+    for (index, (id, buildable)) in buildables.iter().enumerate() {
+        let is_discovered = buildables
+            .name(id)
+            .is_some_and(|name| encyclopedia.is_discovered(name));
+        let marker = if index == menu.index { ">" } else { " " };
+        let entry = if is_discovered {
+            buildable.name()
+        } else {
+            "???"
+        };
+        lines.push(format!("{} {}", marker, entry));
+        if index != menu.index {
+            continue;
+        }
+        if !is_discovered {
+            name = "\n???".to_owned();
+            details = "\nNot discovered yet. Play more levels to find it.".to_owned();
+            continue;
+        }
+        name = format!("\n{}", buildable.name());
+        details = format!(
+            "\nWeight: {}",
+            format_weight_range(
+                buildable.weight(),
+                buildable.weight_variance(),
+                level.weight_unit()
+            )
+        );
+        details.push_str(if buildable.stackable() {
+            "\nCan be stacked on other buildables."
+        } else {
+            "\nOne per cell, cannot be stacked."
+        });
+        if !buildable.description().is_empty() {
+            description = format!("\n\n{}", buildable.description());
+        }
+    }
+    let sections = [lines.join("\n"), name, details, description];
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        for (section, value) in text.sections.iter_mut().zip(sections) {
+            section.value = value;
+        }
+        return;
+    }
+    let style = |font_size, color| TextStyle {
+        font: ui_resources.text_font(),
+        font_size,
+        color,
+    };
+    let colors = [
+        (20.0, Color::WHITE),
+        (28.0, Color::rgb_u8(255, 220, 120)),
+        (18.0, Color::rgb_u8(200, 200, 200)),
+        (18.0, Color::WHITE),
+    ];
+    let sections = sections
+        .into_iter()
+        .zip(colors)
+        .map(|(value, (font_size, color))| TextSection {
+            value,
+            style: style(font_size, color),
+        })
+        .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(50.0), Val::Percent(100.0)),
+                padding: Rect::all(Val::Px(40.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("Encyclopedia"))
+        .insert(MenuOverlay)
+        .insert(EncyclopediaUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        max_size: Size::new(Val::Percent(100.0), Val::Undefined),
+                        ..Default::default()
+                    },
+                    text: Text {
+                        sections,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(EncyclopediaUi)
+                .insert(EncyclopediaText);
+        });
+}
+
+/// Set up the 3D preview in front of the camera while the encyclopedia is open, with a backdrop
+/// hiding the scene behind. Brings its own camera and light if there is none, like in the main
+/// menu.
+fn update_preview_stage(
+    mut commands: Commands,
+    menu: Res<EncyclopediaMenu>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera_query: Query<&GlobalTransform, With<PerspectiveProjection>>,
+    stage_query: Query<Entity, With<PreviewStage>>,
+) {
+    if !menu.is_changed() {
+        return;
+    }
+    let stage = stage_query.get_single().ok();
+    match (menu.open, stage) {
+        (true, None) => {
+            let camera = camera_query.get_single().ok().copied();
+            let backdrop = PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Quad::new(Vec2::new(40.0, 40.0)))),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::rgb(0.1, 0.1, 0.1),
+                    unlit: true,
+                    ..Default::default()
+                }),
+                transform: Transform::from_xyz(0.0, 0.0, -10.0),
+                ..Default::default()
+            };
+            commands
+                .spawn_bundle((
+                    camera.map(Transform::from).unwrap_or_default(),
+                    GlobalTransform::identity(),
+                ))
+                .insert(Name::new("EncyclopediaPreview"))
+                .insert(PreviewStage)
+                .with_children(|parent| {
+                    parent.spawn_bundle(backdrop);
+                    if camera.is_none() {
+                        parent.spawn_bundle(PerspectiveCameraBundle::default());
+                        parent.spawn_bundle(DirectionalLightBundle {
+                            directional_light: DirectionalLight {
+                                illuminance: 10000.0,
+                                ..Default::default()
+                            },
+                            transform: Transform::from_rotation(Quat::from_euler(
+                                EulerRot::YXZ,
+                                -0.5,
+                                -0.5,
+                                0.,
+                            )),
+                            ..Default::default()
+                        });
+                    }
+                });
+        }
+        (false, Some(stage)) => commands.entity(stage).despawn_recursive(),
+        _ => {}
+    }
+}
+
+/// Spin the model of the highlighted buildable on the turntable, if discovered.
+fn update_turntable(
+    mut commands: Commands,
+    time: Res<Time>,
+    menu: Res<EncyclopediaMenu>,
+    buildables: Res<BuildableRegistry>,
+    encyclopedia: Res<Encyclopedia>,
+    stage_query: Query<Entity, With<PreviewStage>>,
+    mut turntable_query: Query<(Entity, &mut Turntable, &mut Transform)>,
+) {
+    let stage = match stage_query.get_single() {
+        Ok(stage) => stage,
+        Err(_) => return,
+    };
+    let bref = highlighted(&menu, &buildables).filter(|bref| {
+        buildables
+            .name(*bref)
+            .is_some_and(|name| encyclopedia.is_discovered(name))
+    });
+    if let Ok((entity, mut turntable, mut transform)) = turntable_query.get_single_mut() {
+        if Some(turntable.bref) == bref {
+            turntable.angle += TURNTABLE_SPEED * time.delta_seconds();
+            transform.rotation =
+                Quat::from_rotation_x(TURNTABLE_TILT) * Quat::from_rotation_y(turntable.angle);
+            return;
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+    let (bref, buildable) = match bref.and_then(|bref| Some((bref, buildables.get(bref)?))) {
+        Some(highlighted) => highlighted,
+        None => return,
+    };
+    // On the right of the screen, next to the encyclopedia panel
+    let turntable = commands
+        .spawn_bundle((
+            Transform::from_xyz(1.5, -0.3, -4.0),
+            GlobalTransform::identity(),
+        ))
+        .insert(Turntable { bref, angle: 0.0 })
+        .with_children(|parent| buildable.spawn_model(parent))
+        .id();
+    commands.entity(stage).add_child(turntable);
+}
+
+fn encyclopedia_cleanup(
+    mut commands: Commands,
+    mut menu: ResMut<EncyclopediaMenu>,
+    panel_query: Query<Entity, (With<EncyclopediaUi>, Without<EncyclopediaText>)>,
+    stage_query: Query<Entity, With<PreviewStage>>,
+) {
+    menu.open = false;
+    for entity in panel_query.iter().chain(stage_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the encyclopedia listing the buildables discovered so far, with a turntable
+/// preview of their model, their weight, placement rules, and lore. Opens from the main menu and
+/// in game.
+pub struct EncyclopediaPlugin;
+
+impl Plugin for EncyclopediaPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Encyclopedia::load())
+            .insert_resource(EncyclopediaMenu::default())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame).with_system(discover_buildables),
+            );
+        for state in [AppState::MainMenu, AppState::InGame] {
+            app.add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(state)
+                    .after(InputSystem)
+                    .after("profile_menu_input")
+                    .with_system(encyclopedia_input.label("encyclopedia_input")),
+            )
+            .add_system_set(
+                SystemSet::on_update(state)
+                    .with_system(update_encyclopedia_panel)
+                    .with_system(update_preview_stage)
+                    .with_system(update_turntable),
+            )
+            .add_system_set(SystemSet::on_exit(state).with_system(encyclopedia_cleanup));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::render_resource::{
+        Extent3d, Face, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+    },
+};
+use std::{f32::consts::*, sync::Arc};
+
+use crate::{serialize::WorldDesc, AppState, Level, ResetPlateEvent};
+
+/// Radius of the sky dome, and half size of the cubemap skybox.
+const SKY_RADIUS: f32 = 100.0;
+
+/// Distance of the clouds to the plate.
+const CLOUD_DISTANCE: f32 = 70.0;
+
+/// Rotation speed of the cloud layer, in radians per second.
+const CLOUD_SPEED: f32 = 0.01;
+
+/// Files of the cubemap faces, with the direction of each face from the center of the skybox and
+/// the up vector of its image.
+const CUBEMAP_FACES: [(&str, [f32; 3], [f32; 3]); 6] = [
+    ("px.png", [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ("nx.png", [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ("py.png", [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ("ny.png", [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ("pz.png", [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ("nz.png", [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+/// Resource tracking the environment currently displayed.
+#[derive(Debug, Default)]
+pub struct Environment {
+    /// World whose environment is displayed, if any.
+    world: Option<Arc<WorldDesc>>,
+    /// Root entity of the environment.
+    root: Option<Entity>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+}
+
+/// Marker for the cloud layer, slowly rotating around the plate.
+#[derive(Component)]
+struct CloudLayer;
+
+/// Create a vertical gradient texture, from the top color at V=0 to the horizon color at V=0.5
+/// and below.
+fn create_sky_image(top: Color, horizon: Color) -> Image {
+    const TEX_HEIGHT: u32 = 64;
+    let mut data = Vec::<u8>::with_capacity(TEX_HEIGHT as usize * 4);
+    for j in 0..TEX_HEIGHT {
+        let t = (j as f32 / (TEX_HEIGHT / 2) as f32).min(1.0);
+        let color = Vec4::from(top.as_rgba_f32()).lerp(Vec4::from(horizon.as_rgba_f32()), t);
+        for c in color.to_array() {
+            data.push((c.clamp(0.0, 1.0) * 255.0) as u8);
+        }
+    }
+    let mut image = Image::new(
+        Extent3d {
+            width: 1,
+            height: TEX_HEIGHT,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = SamplerDescriptor {
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    };
+    image
+}
+
+/// Create a soft round blob texture for the clouds.
+fn create_cloud_image() -> Image {
+    const TEX_SIZE: u32 = 64;
+    let mut data = Vec::<u8>::with_capacity(TEX_SIZE as usize * TEX_SIZE as usize * 4);
+    let half = TEX_SIZE as f32 / 2.0;
+    for j in 0..TEX_SIZE {
+        for i in 0..TEX_SIZE {
+            let d = Vec2::new(i as f32 - half, (j as f32 - half) * 2.0).length() / half;
+            let alpha = (1.0 - d).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (alpha * alpha * 200.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: TEX_SIZE,
+            height: TEX_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Spawn the environment of the world of the level whenever the level changes world. The sky and
+/// clouds only use unlit standard materials, so render the same with the WebGL2 backend.
+fn update_environment(
+    mut commands: Commands,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    asset_server: Res<AssetServer>,
+    mut environment: ResMut<Environment>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    let world = level.desc().and_then(|level_desc| level_desc.world.clone());
+    if world == environment.world {
+        return;
+    }
+    if let Some(root) = environment.root.take() {
+        commands.entity(root).despawn_recursive();
+    }
+    environment.world = world.clone();
+    let world = match world {
+        Some(world) => world,
+        None => return,
+    };
+    debug!("Environment: world '{}'", world.name);
+
+    let root = commands
+        .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+        .insert(Name::new(format!("Environment({})", world.name)))
+        .id();
+    environment.root = Some(root);
+
+    if let Some(cubemap) = &world.cubemap {
+        // Cubemap skybox, one unlit quad per face looking toward the center
+        let quad = meshes.add(Mesh::from(shape::Quad::new(Vec2::splat(2.0 * SKY_RADIUS))));
+        for (file, dir, up) in CUBEMAP_FACES {
+            let image = asset_server.load(&format!("textures/{}/{}", cubemap, file)[..]);
+            let material = materials.add(StandardMaterial {
+                base_color_texture: Some(image),
+                unlit: true,
+                cull_mode: None,
+                ..Default::default()
+            });
+            let center = Vec3::from(dir) * SKY_RADIUS;
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: quad.clone(),
+                    material,
+                    transform: Transform::from_translation(center)
+                        .looking_at(center * 2.0, Vec3::from(up)),
+                    ..Default::default()
+                })
+                .insert(NotShadowCaster)
+                .insert(NotShadowReceiver)
+                .insert(Parent(root));
+        }
+    } else {
+        // Gradient sky dome, seen from the inside
+        let image = images.add(create_sky_image(world.sky_top, world.sky_horizon));
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(image),
+            unlit: true,
+            cull_mode: Some(Face::Front),
+            ..Default::default()
+        });
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::UVSphere {
+                    radius: SKY_RADIUS,
+                    sectors: 32,
+                    stacks: 16,
+                })),
+                material,
+                // The sphere poles are along Z; make V=0 the zenith
+                transform: Transform::from_rotation(Quat::from_rotation_x(-FRAC_PI_2)),
+                ..Default::default()
+            })
+            .insert(NotShadowCaster)
+            .insert(NotShadowReceiver)
+            .insert(Parent(root));
+    }
+
+    // Distant clouds, spread around the plate with the golden angle
+    if world.clouds > 0 {
+        let layer = commands
+            .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+            .insert(Name::new("Clouds"))
+            .insert(CloudLayer)
+            .insert(Parent(root))
+            .id();
+        let quad = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(24.0, 8.0))));
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(images.add(create_cloud_image())),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
+            ..Default::default()
+        });
+        let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+        for index in 0..world.clouds {
+            let angle = index as f32 * golden_angle;
+            let height = 12.0 + 10.0 * ((index * 7) % 5) as f32 / 4.0;
+            let pos = Vec3::new(
+                CLOUD_DISTANCE * angle.cos(),
+                height,
+                CLOUD_DISTANCE * angle.sin(),
+            );
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: quad.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(pos)
+                        .looking_at(Vec3::new(0.0, height, 0.0), Vec3::Y),
+                    ..Default::default()
+                })
+                .insert(NotShadowCaster)
+                .insert(NotShadowReceiver)
+                .insert(Parent(layer));
+        }
+    }
+}
+
+fn rotate_clouds(time: Res<Time>, mut query: Query<&mut Transform, With<CloudLayer>>) {
+    for mut transform in query.iter_mut() {
+        transform.rotate(Quat::from_rotation_y(CLOUD_SPEED * time.delta_seconds()));
+    }
+}
+
+fn environment_cleanup(mut commands: Commands, mut environment: ResMut<Environment>) {
+    if let Some(root) = environment.root.take() {
+        commands.entity(root).despawn_recursive();
+    }
+    environment.world = None;
+}
+
+/// Plugin for the environment around the plate, a gradient sky or a cubemap skybox with distant
+/// clouds, configured per world in the game data.
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Environment::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(update_environment)
+                    .with_system(rotate_clouds),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(environment_cleanup));
+    }
+}
@@ -1,6 +1,14 @@
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Error {
     LoadLevels,
+    /// The level with the given index does not exist in the game data.
+    LevelNotFound(usize),
+    /// A grid layout references an unknown buildable or a cell outside the grid.
+    InvalidLayout,
+    /// A versioned file has a missing root object or an invalid schema version.
+    InvalidSchema,
+    /// A versioned file has the given schema version, newer than supported by this build.
+    UnsupportedSchema(u32),
 }
 
 impl From<std::io::Error> for Error {
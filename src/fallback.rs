@@ -0,0 +1,43 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+
+use crate::serialize::GameDataArchive;
+
+/// Minimal game data embedded in the binary, with a single tutorial level and two buildables, used
+/// when `levels.json` is missing or invalid so the game always boots into something playable.
+const FALLBACK_GAME_DATA: &str = include_str!("../assets/fallback/levels.json");
+
+/// Font embedded in the binary, used for the UI when the fonts of the assets fail to load.
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/fonts/montserrat/Montserrat-Regular.ttf");
+
+const FALLBACK_FONT_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Font::TYPE_UUID, 0x5d3c_8a1f_4b2e_9067);
+
+/// Game data of the offline tutorial, embedded in the binary.
+pub fn fallback_game_data() -> GameDataArchive {
+    GameDataArchive::from_json(FALLBACK_GAME_DATA).expect("Invalid embedded fallback game data")
+}
+
+/// Font embedded in the binary, added to the fonts on first use.
+pub fn fallback_font(fonts: &mut Assets<Font>) -> Handle<Font> {
+    let handle = FALLBACK_FONT_HANDLE.typed::<Font>();
+    if fonts.get(&handle).is_none() {
+        let font = Font::try_from_bytes(FALLBACK_FONT.to_vec()).expect("Invalid embedded font");
+        fonts.set_untracked(handle.clone_weak(), font);
+    }
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate_game_data;
+
+    #[test]
+    fn fallback_game_data_is_valid() {
+        let report = validate_game_data(FALLBACK_GAME_DATA);
+        assert!(!report.has_errors(), "{}", report);
+        assert_eq!(report.level_count, 1);
+        assert_eq!(report.buildable_count, 2);
+        assert!(Font::try_from_bytes(FALLBACK_FONT.to_vec()).is_ok());
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    game::GameplaySystem,
+    journal::{LevelJournal, LevelOp},
+    level::{mark_for_despawn, PendingDespawn},
+    serialize::BuildableId,
+    wear::Tile,
+    AppState, Grid, Level, Placed, Plate, TilesSpawnedEvent,
+};
+
+/// Fraction of the capacity of a fragile tile at which it shows cracks, warning the player.
+const CRACK_RATIO: f32 = 0.5;
+
+/// Elevation of the crack overlay of a fragile tile above the terrace of its cell.
+const OVERLAY_ELEVATION: f32 = 0.06;
+
+/// Fragile tile of a level, which breaks once the weight placed on it exceeds its capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct FragileTileDesc {
+    /// Grid coordinates of the tile.
+    pub pos: IVec2,
+    /// Largest weight the tile holds.
+    pub capacity: f32,
+}
+
+/// Visual state of a fragile tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crack {
+    Intact,
+    /// The weight on the tile is close to its capacity.
+    Cracked,
+    /// The weight exceeded the capacity, and the cell became a hole.
+    Broken,
+}
+
+impl Crack {
+    /// State of a tile holding the given weight.
+    pub fn from_load(weight: f32, capacity: f32) -> Self {
+        if weight > capacity {
+            Crack::Broken
+        } else if weight >= capacity * CRACK_RATIO {
+            Crack::Cracked
+        } else {
+            Crack::Intact
+        }
+    }
+}
+
+/// Event sent when a fragile tile breaks under the weight placed on it, losing its buildable.
+#[derive(Debug)]
+pub struct TileBrokenEvent {
+    /// Grid coordinates of the tile.
+    pub pos: IVec2,
+    /// Buildable lost with the tile, if any.
+    pub buildable: Option<BuildableId>,
+    /// Weight on the tile when it broke.
+    pub weight: f32,
+}
+
+/// Crack overlay of a fragile tile, at the given grid coordinates.
+#[derive(Debug, Component)]
+pub struct FragileTile {
+    pub pos: IVec2,
+    crack: Crack,
+}
+
+/// Resource holding the mesh and materials of the crack overlays.
+#[derive(Debug, Default)]
+struct FragileAssets {
+    mesh: Handle<Mesh>,
+    intact: Handle<StandardMaterial>,
+    cracked: Handle<StandardMaterial>,
+}
+
+fn setup_fragile_assets(
+    mut assets: ResMut<FragileAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    assets.mesh = meshes.add(Mesh::from(shape::Box::new(0.8, 0.01, 0.8)));
+    assets.intact = materials.add(Color::rgb(0.75, 0.85, 0.95).into());
+    assets.cracked = materials.add(Color::rgb(0.55, 0.25, 0.2).into());
+}
+
+/// Spawn the crack overlays of the fragile tiles once the tiles of the plate regenerated.
+fn spawn_fragile_tiles(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    level: Res<Level>,
+    assets: Res<FragileAssets>,
+    mut ev_tiles_spawned: EventReader<TilesSpawnedEvent>,
+    fragile_query: Query<Entity, With<FragileTile>>,
+    plate_query: Query<Entity, With<Plate>>,
+) {
+    if ev_tiles_spawned.iter().last().is_none() {
+        return;
+    }
+    for entity in fragile_query.iter() {
+        mark_for_despawn(&mut commands, entity);
+    }
+    let (level_desc, plate) = match (level.desc(), plate_query.get_single()) {
+        (Some(level_desc), Ok(plate)) => (level_desc, plate),
+        _ => return,
+    };
+    for tile in &level_desc.fragile {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: assets.mesh.clone(),
+                material: assets.intact.clone(),
+                transform: Transform::from_translation(
+                    grid.translation(&tile.pos, OVERLAY_ELEVATION),
+                ),
+                ..Default::default()
+            })
+            .insert(Name::new(format!("Fragile{:?}", tile.pos.to_array())))
+            .insert(FragileTile {
+                pos: tile.pos,
+                crack: Crack::Intact,
+            })
+            .insert(Parent(plate));
+    }
+}
+
+/// Crack the fragile tiles as the weight on them grows, and break those whose capacity is
+/// exceeded, losing their buildable. The tiles follow the holes of the grid, so a restart or a
+/// rewind clearing the grid restores the broken tiles.
+fn fragile_load_system(
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    assets: Res<FragileAssets>,
+    mut fragile_query: Query<(
+        &mut FragileTile,
+        &mut Handle<StandardMaterial>,
+        &mut Visibility,
+    )>,
+    mut tile_query: Query<(&Tile, &mut Visibility), Without<FragileTile>>,
+    placed_query: Query<(Entity, &Placed), Without<PendingDespawn>>,
+    mut journal: ResMut<LevelJournal>,
+    mut ev_tile_broken: EventWriter<TileBrokenEvent>,
+) {
+    if !grid.is_changed() {
+        return;
+    }
+    for (mut fragile, mut material, mut visibility) in fragile_query.iter_mut() {
+        // Tiles of the previous grid are despawned at the end of the frame
+        if grid.clamp(fragile.pos) != fragile.pos {
+            continue;
+        }
+        let cell = *grid.cell(&fragile.pos);
+        let crack = if cell.hole {
+            Crack::Broken
+        } else {
+            Crack::from_load(cell.weight, grid.capacity(&fragile.pos))
+        };
+        if crack == fragile.crack {
+            continue;
+        }
+        let was_broken = fragile.crack == Crack::Broken;
+        fragile.crack = crack;
+        let is_visible = crack != Crack::Broken;
+        match crack {
+            Crack::Intact => *material = assets.intact.clone(),
+            Crack::Cracked => *material = assets.cracked.clone(),
+            // Holes restored along with the grid content are already empty
+            Crack::Broken if cell.hole => {}
+            Crack::Broken => {
+                let entity = placed_query
+                    .iter()
+                    .find(|(_, placed)| placed.0 == fragile.pos)
+                    .map(|(entity, _)| entity);
+                if let Some(entity) = entity {
+                    mark_for_despawn(&mut commands, entity);
+                }
+                let cell = grid.break_tile(&fragile.pos, entity);
+                info!("Fragile tile {:?} broke under {}", fragile.pos, cell.weight);
+                journal.record(LevelOp::Break { pos: fragile.pos });
+                ev_tile_broken.send(TileBrokenEvent {
+                    pos: fragile.pos,
+                    buildable: cell.buildable,
+                    weight: cell.weight,
+                });
+            }
+        }
+        if was_broken || !is_visible {
+            visibility.is_visible = is_visible;
+            for (_, mut visibility) in tile_query
+                .iter_mut()
+                .filter(|(tile, _)| tile.pos == fragile.pos)
+            {
+                visibility.is_visible = is_visible;
+            }
+        }
+    }
+}
+
+/// Plugin for the fragile tiles, breaking under too much weight.
+pub struct FragilePlugin;
+
+impl Plugin for FragilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FragileAssets::default())
+            .add_event::<TileBrokenEvent>()
+            .add_startup_system(setup_fragile_assets)
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Balance)
+                    .after(GameplaySystem::Placement)
+                    .with_system(spawn_fragile_tiles)
+                    .with_system(fragile_load_system),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crack() {
+        assert_eq!(Crack::from_load(0.0, 2.0), Crack::Intact);
+        assert_eq!(Crack::from_load(0.9, 2.0), Crack::Intact);
+        assert_eq!(Crack::from_load(1.0, 2.0), Crack::Cracked);
+        assert_eq!(Crack::from_load(2.0, 2.0), Crack::Cracked);
+        assert_eq!(Crack::from_load(2.1, 2.0), Crack::Broken);
+        // Buildables lighter than air never break a tile
+        assert_eq!(Crack::from_load(-3.0, 2.0), Crack::Intact);
+        // Regular tiles hold any weight
+        assert_eq!(Crack::from_load(100.0, f32::INFINITY), Crack::Intact);
+    }
+}
@@ -1,7 +1,11 @@
 use crate::{
-    AppState, CheckLevelResultEvent, Cursor, Grid, Level, Levels, LoadLevel, LoadLevelEvent,
+    anim::PlayAnimation, balance::BalanceState, cinematic::CinematicMode, coop::Coop,
+    fragile::TileBrokenEvent, level::LevelErrorEvent, serialize::BuildableId, AppState,
+    CheckLevelResultEvent, Cursor, Error, Level, Levels, LoadLevel, LoadLevelEvent, Plate,
+    RestartLevelEvent,
 };
-use bevy::prelude::*;
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum GameSequence {
@@ -9,11 +13,120 @@ pub enum GameSequence {
     Intro,
     Play,
     Victory,
+    /// The level was failed, waiting for the player to retry or go back to the menu.
+    Defeat,
+}
+
+/// Maximum plate tilt angle, in radians, before the plate topples and the level is lost.
+pub const TOPPLE_TILT: f32 = 10.0 * std::f32::consts::PI / 180.0;
+
+/// Labels of the phases of the gameplay systems of the [`AppState::InGame`] state, in execution
+/// order within a frame.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, SystemLabel)]
+pub enum GameplaySystem {
+    /// Read the player input and turn it into actions and events.
+    Input,
+    /// Reset the board and the inventory when a level starts or restarts.
+    Reset,
+    /// Move the cursors and request placements. Only runs during [`GameSequence::Play`].
+    Cursor,
+    /// Place the buildables requested with [`PlaceBuildableEvent`]. Only runs during
+    /// [`GameSequence::Play`].
+    ///
+    /// [`PlaceBuildableEvent`]: crate::PlaceBuildableEvent
+    Placement,
+    /// Tilt the plate according to the buildables placed.
+    Balance,
+    /// Evaluate the level result and advance the game sequence.
+    VictoryCheck,
+    /// Update the HUD and the visual feedback.
+    Ui,
+}
+
+/// Run criteria for the systems running in the [`AppState::InGame`] state only during the
+/// [`GameSequence::Play`] sequence, while the player can place buildables.
+pub fn run_if_playing(state: Res<State<AppState>>, game: Res<Game>) -> ShouldRun {
+    if *state.current() == AppState::InGame && game.sequence() == GameSequence::Play {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Game mode selected from the main menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Single player.
+    Solo,
+    /// Two players taking turns on the same plate. See [`VersusPlugin`].
+    ///
+    /// [`VersusPlugin`]: crate::versus::VersusPlugin
+    Versus,
+    /// Two players with their own cursor and inventory balancing the same plate together.
+    /// See [`CoopPlugin`].
+    ///
+    /// [`CoopPlugin`]: crate::coop::CoopPlugin
+    Coop,
+    /// Single player with the weight of buildables hidden until placed, and a relaxed victory
+    /// margin. See [`Rules`].
+    ///
+    /// [`Rules`]: crate::rules::Rules
+    WeightReveal,
+    /// Single player drawing the next buildable from a weighted random queue instead of a fixed
+    /// inventory. See [`BuildQueue`].
+    ///
+    /// [`BuildQueue`]: crate::market::BuildQueue
+    Market,
+    /// Single player playing the daily challenge, a remix of a level drawn from the date, then
+    /// back to the main menu. See [`DailyPlugin`].
+    ///
+    /// [`DailyPlugin`]: crate::daily::DailyPlugin
+    Daily,
+    /// Single player playing a level imported from a level code, then back to the main menu. See
+    /// [`LevelCodePlugin`].
+    ///
+    /// [`LevelCodePlugin`]: crate::level_code::LevelCodePlugin
+    Imported,
+}
+
+/// Event sent by the gameplay systems when something notable happens in a level, for the audio,
+/// the analytics, and the UI to react to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// A buildable was placed on the grid.
+    BuildablePlaced { pos: IVec2, buildable: BuildableId },
+    /// The player selected an inventory slot.
+    SlotSelected { index: usize },
+    /// A level was loaded and its plate reset.
+    LevelLoaded { level_index: usize },
+    /// The current level has been cleared.
+    LevelCleared { level_index: usize },
+    /// The current level has been failed.
+    LevelFailed {
+        level_index: usize,
+        reason: DefeatReason,
+    },
+    /// The plate tilted over [`TOPPLE_TILT`]. In versus this ends the round, otherwise the level
+    /// fails right after.
+    PlateToppled { level_index: usize },
+}
+
+/// Reason of a [`GameEvent::LevelFailed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefeatReason {
+    /// All the buildables were placed but the plate is not balanced.
+    Unbalanced,
+    /// The plate tilted over [`TOPPLE_TILT`].
+    Toppled,
+    /// A fragile tile broke under the weight placed on it.
+    TileBroken,
 }
 
 pub struct Game {
     sequence: GameSequence,
     timer: Timer,
+    /// Did the player choose to move on from the [`GameSequence::Victory`] sequence?
+    proceed: bool,
 }
 
 impl Game {
@@ -21,12 +134,26 @@ impl Game {
         Game {
             sequence: GameSequence::Intro,
             timer: Timer::from_seconds(3.0, false),
+            proceed: false,
         }
     }
 
+    pub fn sequence(&self) -> GameSequence {
+        self.sequence
+    }
+
     pub fn reset_sequence(&mut self) {
         self.timer.reset();
         self.sequence = GameSequence::Intro;
+        self.proceed = false;
+    }
+
+    /// Hold the [`GameSequence::Intro`] sequence, restarting its timer, for example while an
+    /// interlude plays.
+    pub fn hold_intro(&mut self) {
+        if self.sequence == GameSequence::Intro {
+            self.timer.reset();
+        }
     }
 
     pub fn advance_sequence(&mut self) -> GameSequence {
@@ -35,32 +162,92 @@ impl Game {
         self.sequence = match prev_sequence {
             GameSequence::Intro => GameSequence::Play,
             GameSequence::Play => GameSequence::Victory,
-            GameSequence::Victory => {
-                panic!("Cannot advance sequence from last sequence (Victory).")
+            GameSequence::Victory | GameSequence::Defeat => {
+                panic!(
+                    "Cannot advance sequence from last sequence ({:?}).",
+                    prev_sequence
+                )
             }
         };
         trace!("Game sequence: {:?} => {:?}", prev_sequence, self.sequence);
         self.sequence
     }
+
+    /// Fail the level being played, until the player retries it.
+    pub fn defeat(&mut self) {
+        trace!("Game sequence: {:?} => Defeat", self.sequence);
+        self.sequence = GameSequence::Defeat;
+    }
+
+    /// Resume playing a failed level, for example once it restarted.
+    pub fn resume(&mut self) {
+        if self.sequence == GameSequence::Defeat {
+            trace!("Game sequence: Defeat => Play");
+            self.sequence = GameSequence::Play;
+        }
+    }
+
+    /// Leave the [`GameSequence::Victory`] sequence once its animation finished, for the next
+    /// level or back to the main menu. See [`RecapPlugin`].
+    ///
+    /// [`RecapPlugin`]: crate::recap::RecapPlugin
+    pub fn proceed(&mut self) {
+        if self.sequence == GameSequence::Victory {
+            self.proceed = true;
+        }
+    }
+}
+
+/// Fail the current level: stop the player input and move to the Defeat sequence.
+fn fail_level(
+    game: &mut Game,
+    level: &Level,
+    reason: DefeatReason,
+    ev_game: &mut EventWriter<GameEvent>,
+    query: &mut Query<(&mut Cursor, &mut Visibility)>,
+) {
+    info!(
+        "Defeat! Level #{} '{}' failed: {:?}",
+        level.index(),
+        level.name(),
+        reason
+    );
+    let (mut cursor, mut visibility) = query.single_mut();
+    cursor.set_enabled(false);
+    visibility.is_visible = false;
+    ev_game.send(GameEvent::LevelFailed {
+        level_index: level.index(),
+        reason,
+    });
+    game.defeat();
 }
 
 fn game_sequence(
     time: Res<Time>,
-    grid: Res<Grid>,
+    balance: Res<BalanceState>,
     level: Res<Level>,
     levels: Res<Levels>,
     mut game: ResMut<Game>,
     mut ev_check_level: EventReader<CheckLevelResultEvent>,
     mut ev_load_level: EventWriter<LoadLevelEvent>,
+    mut ev_level_error: EventWriter<LevelErrorEvent>,
+    mut ev_game: EventWriter<GameEvent>,
+    game_mode: Res<GameMode>,
+    coop: Res<Coop>,
     mut app_state: ResMut<State<AppState>>,
+    mut cinematic: ResMut<CinematicMode>,
     mut query: Query<(&mut Cursor, &mut Visibility)>,
 ) {
     match game.sequence {
         GameSequence::Intro => {
+            if !cinematic.is_enabled() {
+                cinematic.enable();
+            }
             if game.timer.tick(time.delta()).just_finished() {
                 let (mut cursor, mut visibility) = query.single_mut();
                 cursor.set_enabled(true);
                 visibility.is_visible = true;
+                cinematic.disable();
                 game.advance_sequence();
             }
         }
@@ -69,10 +256,20 @@ fn game_sequence(
             // This is generally sent after the last builable has been added to the plate,
             // once the inventory is empty.
             if let Some(ev) = ev_check_level.iter().last() {
+                // In coop, wait for the partner to place all their buildables too
+                if !coop.is_done() {
+                    return;
+                }
                 let level_index = level.index();
-                let level_desc = &levels.levels()[level_index];
+                let level_desc = match level.desc() {
+                    Some(level_desc) => level_desc,
+                    None => {
+                        ev_level_error.send(LevelErrorEvent(Error::LevelNotFound(level_index)));
+                        return;
+                    }
+                };
                 // If current level was cleared, move to Victory sequence
-                if grid.is_victory(level_desc.balance_factor, level_desc.victory_margin) {
+                if balance.is_balanced() {
                     info!(
                         "Victory! Level #{} '{}' cleared.",
                         level_index, level_desc.name
@@ -80,15 +277,32 @@ fn game_sequence(
                     let (mut cursor, mut visibility) = query.single_mut();
                     cursor.set_enabled(false);
                     visibility.is_visible = false;
+                    ev_game.send(GameEvent::LevelCleared { level_index });
                     game.advance_sequence();
+                } else if *game_mode != GameMode::Versus {
+                    // Versus replays the round itself
+                    fail_level(
+                        &mut game,
+                        &level,
+                        DefeatReason::Unbalanced,
+                        &mut ev_game,
+                        &mut query,
+                    );
                 }
             }
         }
         GameSequence::Victory => {
+            if !cinematic.is_enabled() {
+                cinematic.enable();
+            }
             // TODO - tick sequence animation
-            if game.timer.tick(time.delta()).just_finished() {
+            // Wait for the player to move on from the recap card
+            if game.timer.tick(time.delta()).finished() && game.proceed {
                 let level_index = level.index();
-                if level_index + 1 < levels.levels().len() {
+                if matches!(*game_mode, GameMode::Daily | GameMode::Imported) {
+                    trace!("Game sequence: Victory => MainMenu({:?})", *game_mode);
+                    app_state.set(AppState::MainMenu).unwrap();
+                } else if level_index + 1 < levels.len() {
                     trace!("Game sequence: Victory => Intro(next)");
                     game.reset_sequence();
                     ev_load_level.send(LoadLevelEvent(LoadLevel::Next));
@@ -98,6 +312,107 @@ fn game_sequence(
                 }
             }
         }
+        // Wait for the player to retry; see retry_level()
+        GameSequence::Defeat => {}
+    }
+}
+
+/// Fail the level as soon as the plate topples. In versus, this ends the round instead.
+fn topple_check(
+    game_mode: Res<GameMode>,
+    balance: Res<BalanceState>,
+    level: Res<Level>,
+    mut game: ResMut<Game>,
+    mut ev_game: EventWriter<GameEvent>,
+    mut query: Query<(&mut Cursor, &mut Visibility)>,
+) {
+    if *game_mode == GameMode::Versus || game.sequence() != GameSequence::Play {
+        return;
+    }
+    if level.desc().is_some() && balance.tilt > TOPPLE_TILT {
+        ev_game.send(GameEvent::PlateToppled {
+            level_index: level.index(),
+        });
+        fail_level(
+            &mut game,
+            &level,
+            DefeatReason::Toppled,
+            &mut ev_game,
+            &mut query,
+        );
+    }
+}
+
+/// Fail the level as soon as a fragile tile breaks, before the level result is evaluated with
+/// the buildable lost. In versus, the round goes on without it.
+fn tile_break_check(
+    game_mode: Res<GameMode>,
+    level: Res<Level>,
+    mut game: ResMut<Game>,
+    mut ev_tile_broken: EventReader<TileBrokenEvent>,
+    mut ev_game: EventWriter<GameEvent>,
+    mut query: Query<(&mut Cursor, &mut Visibility)>,
+) {
+    if ev_tile_broken.iter().last().is_none()
+        || *game_mode == GameMode::Versus
+        || game.sequence() != GameSequence::Play
+    {
+        return;
+    }
+    fail_level(
+        &mut game,
+        &level,
+        DefeatReason::TileBroken,
+        &mut ev_game,
+        &mut query,
+    );
+}
+
+/// Resume playing a failed level once it restarted. A level cleared and restarted to improve the
+/// score plays again from its intro.
+fn retry_level(
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut game: ResMut<Game>,
+    mut query: Query<&mut Cursor>,
+) {
+    if ev_restart.iter().last().is_none() {
+        return;
+    }
+    match game.sequence() {
+        GameSequence::Defeat => {
+            game.resume();
+            query.single_mut().set_enabled(true);
+        }
+        GameSequence::Victory => {
+            trace!("Game sequence: Victory => Intro(retry)");
+            game.reset_sequence();
+        }
+        GameSequence::Intro | GameSequence::Play => {}
+    }
+}
+
+/// Start each game from the level intro, whatever the sequence the previous game ended in.
+fn reset_game(mut game: ResMut<Game>) {
+    game.reset_sequence();
+}
+
+/// Spin the plate to celebrate a cleared level.
+fn victory_animation(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    query: Query<Entity, With<Plate>>,
+) {
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some()
+    {
+        for entity in query.iter() {
+            commands
+                .entity(entity)
+                .insert(PlayAnimation::new("plate_victory"));
+        }
     }
 }
 
@@ -107,6 +422,28 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Game::new())
-            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(game_sequence));
+            .insert_resource(GameMode::Solo)
+            .add_event::<GameEvent>()
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(reset_game))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Reset)
+                    .after(GameplaySystem::Input)
+                    .with_system(retry_level),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::VictoryCheck)
+                    .after(GameplaySystem::Balance)
+                    .with_system(tile_break_check.label("tile_break_check"))
+                    .with_system(game_sequence.after("tile_break_check"))
+                    .with_system(topple_check),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(victory_animation),
+            );
     }
 }
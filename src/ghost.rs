@@ -0,0 +1,414 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    cheats::Cheats,
+    config::Config,
+    game::GameEvent,
+    journal::{LevelJournal, LevelOp},
+    practice::Practice,
+    scores::ScoreTracker,
+    serialize::{BuildableId, BuildableRegistry},
+    storage, AppState, Cursor, CursorId, Grid, Level, Plate, ResetPlateEvent,
+};
+
+/// Save file of the best replays, in the profile storage.
+pub const REPLAYS_FILE: &str = "replays.json";
+
+/// Height of the ghost entities above the plate origin.
+const GHOST_HEIGHT: f32 = 0.1;
+
+/// Single player action recorded in a replay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplayAction {
+    /// The cursor moved to the given cell.
+    Move(IVec2),
+    /// A buildable, referenced by name, was placed in the given cell.
+    Place(IVec2, String),
+    /// A buildable, referenced by name, was placed in the given cell by the partner of the coop
+    /// mode.
+    PartnerPlace(IVec2, String),
+    /// The level was restarted, clearing the plate.
+    Restart,
+}
+
+/// Replay action timestamped with the level play time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// Play time of the level when the action occurred, in seconds. See [`ScoreTracker::time`].
+    pub time: f32,
+    pub action: ReplayAction,
+}
+
+/// Recording of a level solution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// Total time to clear the level, in seconds.
+    pub time: f32,
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// Resource holding the fastest replay of each level, by level name.
+pub struct BestReplays {
+    replays: HashMap<String, Replay>,
+}
+
+impl BestReplays {
+    pub fn new() -> Self {
+        BestReplays {
+            replays: HashMap::new(),
+        }
+    }
+
+    /// Load the best replays of the active profile saved by a previous session, if any.
+    pub fn load() -> Self {
+        let replays = storage::read(REPLAYS_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(replays) => Some(replays),
+                Err(err) => {
+                    let location = storage::location(REPLAYS_FILE);
+                    warn!("Failed to parse replays '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        BestReplays { replays }
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(&self.replays)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(REPLAYS_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(REPLAYS_FILE);
+            warn!("Failed to save replays to '{}': {}", location, err);
+        }
+    }
+
+    pub fn get(&self, level: &str) -> Option<&Replay> {
+        self.replays.get(level)
+    }
+
+    /// Keep the replay if it is faster than the best one of the level. Returns `true` if the
+    /// replay is the new best.
+    pub fn submit(&mut self, level: &str, replay: Replay) -> bool {
+        if self
+            .replays
+            .get(level)
+            .map_or(false, |best| best.time <= replay.time)
+        {
+            return false;
+        }
+        self.replays.insert(level.to_owned(), replay);
+        self.save();
+        true
+    }
+}
+
+/// Resource recording the player actions on the current level.
+pub struct ReplayRecorder {
+    frames: Vec<ReplayFrame>,
+    last_pos: Option<IVec2>,
+    /// Generation and length of the level journal already recorded, if any.
+    journal_pos: Option<(u32, usize)>,
+}
+
+impl ReplayRecorder {
+    pub fn new() -> Self {
+        ReplayRecorder {
+            frames: vec![],
+            last_pos: None,
+            journal_pos: None,
+        }
+    }
+
+    /// Actions recorded since the level started.
+    pub fn frames(&self) -> &[ReplayFrame] {
+        &self.frames
+    }
+}
+
+/// Resource driving the playback of the best replay of the current level as a ghost.
+pub struct Ghost {
+    /// Replay being played back, if any.
+    replay: Option<Replay>,
+    /// Index of the next frame to play.
+    next_frame: usize,
+    /// Ghost cursor entity.
+    cursor: Option<Entity>,
+    /// Ghost placement entities.
+    placements: Vec<Entity>,
+    mesh: Handle<Mesh>,
+    cursor_mat: Handle<StandardMaterial>,
+    placement_mat: Handle<StandardMaterial>,
+}
+
+impl Ghost {
+    pub fn new() -> Self {
+        Ghost {
+            replay: None,
+            next_frame: 0,
+            cursor: None,
+            placements: vec![],
+            mesh: Default::default(),
+            cursor_mat: Default::default(),
+            placement_mat: Default::default(),
+        }
+    }
+
+    fn clear_placements(&mut self, commands: &mut Commands) {
+        for entity in self.placements.drain(..) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    fn clear(&mut self, commands: &mut Commands) {
+        self.clear_placements(commands);
+        if let Some(cursor) = self.cursor.take() {
+            commands.entity(cursor).despawn_recursive();
+        }
+        self.replay = None;
+        self.next_frame = 0;
+    }
+}
+
+/// Create the shared ghost mesh and materials.
+fn setup_ghost(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ghost: ResMut<Ghost>,
+) {
+    ghost.mesh = meshes.add(Mesh::from(shape::Box::new(0.6, 0.6, 0.6)));
+    ghost.cursor_mat = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.6, 0.8, 1.0, 0.3),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..Default::default()
+    });
+    ghost.placement_mat = materials.add(StandardMaterial {
+        base_color: Color::rgba(1.0, 1.0, 1.0, 0.25),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..Default::default()
+    });
+}
+
+/// Restart the recording, and the ghost playback in speedrun mode, when a new level starts.
+fn reset_ghost(
+    mut commands: Commands,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    config: Res<Config>,
+    level: Res<Level>,
+    best_replays: Res<BestReplays>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut ghost: ResMut<Ghost>,
+    query: Query<Entity, With<Plate>>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    *recorder = ReplayRecorder::new();
+    ghost.clear(&mut commands);
+    if !config.speedrun {
+        return;
+    }
+    let replay = match best_replays.get(level.name()) {
+        Some(replay) => replay.clone(),
+        None => return,
+    };
+    debug!(
+        "Ghost: racing best replay of '{}' ({:.1}s)",
+        level.name(),
+        replay.time
+    );
+    ghost.replay = Some(replay);
+    let plate = query.single();
+    let cursor = commands
+        .spawn_bundle(PbrBundle {
+            mesh: ghost.mesh.clone(),
+            material: ghost.cursor_mat.clone(),
+            transform: Transform::from_xyz(0.0, GHOST_HEIGHT, 0.0),
+            ..Default::default()
+        })
+        .insert(Name::new("GhostCursor"))
+        .insert(Parent(plate))
+        .id();
+    ghost.cursor = Some(cursor);
+}
+
+/// Replay action of a placement by a cursor, if the buildable has a name.
+fn place_action(
+    pos: IVec2,
+    bref: BuildableId,
+    cursor: CursorId,
+    buildables: &BuildableRegistry,
+) -> Option<ReplayAction> {
+    let name = buildables.name(bref)?.to_owned();
+    Some(match cursor {
+        CursorId::Player => ReplayAction::Place(pos, name),
+        CursorId::Partner => ReplayAction::PartnerPlace(pos, name),
+    })
+}
+
+/// Record the player cursor moves, and the placements and restarts of the level journal.
+fn record_replay(
+    journal: Res<LevelJournal>,
+    buildables: Res<BuildableRegistry>,
+    tracker: Res<ScoreTracker>,
+    mut recorder: ResMut<ReplayRecorder>,
+    query: Query<&Cursor>,
+) {
+    let time = tracker.time();
+    let cursor = query.single();
+    let pos = cursor.pos();
+    if cursor.enabled() && recorder.last_pos != Some(pos) {
+        recorder.frames.push(ReplayFrame {
+            time,
+            action: ReplayAction::Move(pos),
+        });
+        recorder.last_pos = Some(pos);
+    }
+
+    let ops = journal.ops();
+    let (generation, recorded) = recorder.journal_pos.unwrap_or((journal.generation(), 0));
+    let mut actions = vec![];
+    let removed = ops
+        .iter()
+        .skip(recorded)
+        .any(|op| matches!(op, LevelOp::Remove { .. }));
+    if generation != journal.generation() || recorded > ops.len() || removed {
+        // The journal was rewound by a quick load or the practice mode, or a buildable was
+        // removed, so restart and place the buildables left
+        actions.push(ReplayAction::Restart);
+        actions.extend(
+            journal
+                .placements()
+                .into_iter()
+                .filter_map(|(pos, bref, cursor)| place_action(pos, bref, cursor, &buildables)),
+        );
+    } else {
+        for (index, op) in ops.iter().enumerate().skip(recorded) {
+            match *op {
+                // The first start is the one of the recording
+                LevelOp::Start if index > 0 => actions.push(ReplayAction::Restart),
+                LevelOp::Place { pos, bref, cursor } => {
+                    actions.extend(place_action(pos, bref, cursor, &buildables));
+                }
+                _ => {}
+            }
+        }
+    }
+    recorder.frames.extend(
+        actions
+            .into_iter()
+            .map(|action| ReplayFrame { time, action }),
+    );
+    recorder.journal_pos = Some((journal.generation(), ops.len()));
+}
+
+/// Save the recording as the best replay of the level if it beats the previous one.
+fn save_replay(
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    tracker: Res<ScoreTracker>,
+    cheats: Res<Cheats>,
+    practice: Res<Practice>,
+    recorder: Res<ReplayRecorder>,
+    mut best_replays: ResMut<BestReplays>,
+) {
+    for _ in ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+    {
+        if cheats.is_used() || practice.is_used() {
+            continue;
+        }
+        let replay = Replay {
+            time: tracker.time(),
+            frames: recorder.frames.clone(),
+        };
+        if best_replays.submit(level.name(), replay) {
+            info!(
+                "New best replay for '{}': {:.1}s",
+                level.name(),
+                tracker.time()
+            );
+        }
+    }
+}
+
+/// Play back the ghost replay in sync with the level play time.
+fn play_ghost(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    tracker: Res<ScoreTracker>,
+    mut ghost: ResMut<Ghost>,
+    mut query: Query<&mut Transform>,
+    query_plate: Query<Entity, With<Plate>>,
+) {
+    let ghost = &mut *ghost;
+    let replay = match &ghost.replay {
+        Some(replay) => replay,
+        None => return,
+    };
+    let time = tracker.time();
+    while let Some(frame) = replay.frames.get(ghost.next_frame) {
+        if frame.time > time {
+            break;
+        }
+        ghost.next_frame += 1;
+        match &frame.action {
+            ReplayAction::Move(pos) => {
+                if let Some(mut transform) = ghost.cursor.and_then(|e| query.get_mut(e).ok()) {
+                    transform.translation = grid.translation(pos, GHOST_HEIGHT);
+                }
+            }
+            ReplayAction::Place(pos, _) | ReplayAction::PartnerPlace(pos, _) => {
+                let entity = commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: ghost.mesh.clone(),
+                        material: ghost.placement_mat.clone(),
+                        transform: Transform::from_translation(
+                            grid.translation(pos, GHOST_HEIGHT + 0.3),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(Name::new("GhostPlacement"))
+                    .insert(Parent(query_plate.single()))
+                    .id();
+                ghost.placements.push(entity);
+            }
+            ReplayAction::Restart => {
+                for entity in ghost.placements.drain(..) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+/// Despawn the ghost when leaving the game.
+fn cleanup_ghost(mut commands: Commands, mut ghost: ResMut<Ghost>) {
+    ghost.clear(&mut commands);
+}
+
+/// Plugin recording the best replay of each level, and racing it as a ghost in speedrun mode.
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BestReplays::load())
+            .insert_resource(ReplayRecorder::new())
+            .insert_resource(Ghost::new())
+            .add_startup_system(setup_ghost)
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(reset_ghost.label("reset_ghost").after("track_score"))
+                    .with_system(record_replay.after("reset_ghost").after("journal_start"))
+                    .with_system(save_replay.after("reset_ghost"))
+                    .with_system(play_ghost.after("reset_ghost")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(cleanup_ghost));
+    }
+}
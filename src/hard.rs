@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{
+    boot::UiResources, cheats::Cheats, game::GameEvent, rules::Rules, storage,
+    wardrobe::WardrobeMenu, AppState, Level,
+};
+
+/// Save file of the hard mode, in the profile storage.
+pub const HARD_MODE_FILE: &str = "hard.json";
+
+/// Key to toggle the hard mode in the main menu. Not X, which dismisses the crash report.
+const HARD_MODE_KEY: KeyCode = KeyCode::U;
+
+/// Resource holding the hard mode of the active profile. In hard mode the victory margins are
+/// narrower, the center of gravity marker is hidden, and the quick load and the practice mode are
+/// disabled. See [`Rules::hard`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HardMode {
+    /// Is the hard mode enabled?
+    enabled: bool,
+    /// Names of the levels cleared in hard mode, earning a badge on the level selection.
+    cleared: HashSet<String>,
+}
+
+impl HardMode {
+    /// Load the hard mode of the active profile saved by a previous session, if any.
+    pub fn load() -> Self {
+        storage::read(HARD_MODE_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(hard_mode) => Some(hard_mode),
+                Err(err) => {
+                    let location = storage::location(HARD_MODE_FILE);
+                    warn!("Failed to parse hard mode '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(HARD_MODE_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(HARD_MODE_FILE);
+            warn!("Failed to save hard mode to '{}': {}", location, err);
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Was a level cleared in hard mode? The level selection shows a badge for those levels.
+    pub fn has_badge(&self, level: &str) -> bool {
+        self.cleared.contains(level)
+    }
+
+    /// Record a level cleared in hard mode, returning `true` if it's the first time.
+    fn record_clear(&mut self, level: &str) -> bool {
+        if self.cleared.insert(level.to_owned()) {
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.save();
+    }
+}
+
+/// Marker for the text of the hard mode in the main menu.
+#[derive(Component)]
+struct HardModeText;
+
+/// Marker for the badge displayed while playing in hard mode.
+#[derive(Component)]
+struct HardModeBadge;
+
+fn hard_mode_text(hard_mode: &HardMode) -> String {
+    format!(
+        "[{:?}] Hard mode: {}",
+        HARD_MODE_KEY,
+        if hard_mode.is_enabled() { "on" } else { "off" }
+    )
+}
+
+fn spawn_hard_mode_text(
+    mut commands: Commands,
+    ui_resources: Res<UiResources>,
+    hard_mode: Res<HardMode>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(90.0),
+                    left: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                hard_mode_text(&hard_mode),
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("HardModeText"))
+        .insert(HardModeText);
+}
+
+/// Toggle the hard mode in the main menu. Runs after the profile menu, which hides the keys typed
+/// in the name of a new profile.
+fn hard_mode_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    mut hard_mode: ResMut<HardMode>,
+) {
+    if wardrobe_menu.is_open() || !keyboard_input.just_pressed(HARD_MODE_KEY) {
+        return;
+    }
+    hard_mode.toggle();
+    info!("Hard mode: {}", hard_mode.is_enabled());
+    keyboard_input.reset(HARD_MODE_KEY);
+}
+
+fn update_hard_mode_text(
+    hard_mode: Res<HardMode>,
+    mut query: Query<&mut Text, With<HardModeText>>,
+) {
+    if !hard_mode.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = hard_mode_text(&hard_mode);
+    }
+}
+
+fn hard_mode_menu_cleanup(mut commands: Commands, query: Query<Entity, With<HardModeText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_hard_mode_badge(
+    mut commands: Commands,
+    ui_resources: Res<UiResources>,
+    hard_mode: Res<HardMode>,
+) {
+    if !hard_mode.is_enabled() {
+        return;
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(20.0),
+                    right: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "HARD",
+                TextStyle {
+                    font: ui_resources.title_font(),
+                    font_size: 28.0,
+                    color: Color::rgb_u8(230, 90, 70),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("HardModeBadge"))
+        .insert(HardModeBadge);
+}
+
+/// Record the levels cleared in hard mode, without cheats.
+fn record_hard_clears(
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    cheats: Res<Cheats>,
+    mut hard_mode: ResMut<HardMode>,
+) {
+    let cleared = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some();
+    if cleared && rules.hard && !cheats.is_used() && hard_mode.record_clear(level.name()) {
+        info!("Level '{}' cleared in hard mode", level.name());
+    }
+}
+
+fn hard_mode_badge_cleanup(mut commands: Commands, query: Query<Entity, With<HardModeBadge>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the hard mode of each save profile, toggled in the main menu. See [`HardMode`].
+pub struct HardModePlugin;
+
+impl Plugin for HardModePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HardMode::load())
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu).with_system(spawn_hard_mode_text),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(hard_mode_input.after("profile_menu_input"))
+                    .with_system(update_hard_mode_text),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(hard_mode_menu_cleanup),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame).with_system(spawn_hard_mode_badge),
+            )
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(record_hard_clears))
+            .add_system_set(
+                SystemSet::on_exit(AppState::InGame).with_system(hard_mode_badge_cleanup),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let hard_mode: HardMode =
+            serde_json::from_str(r#"{ "enabled": true, "cleared": ["Sandbox"] }"#).unwrap();
+        assert!(hard_mode.is_enabled());
+        assert!(hard_mode.has_badge("Sandbox"));
+        assert!(!hard_mode.has_badge("Tutorial"));
+
+        let hard_mode: HardMode = serde_json::from_str("{}").unwrap();
+        assert!(!hard_mode.is_enabled());
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use serde::Deserialize;
+
+use crate::anim::PlayAnimation;
+
+/// Animation of the smoke puffs in the [`AnimationLibrary`].
+///
+/// [`AnimationLibrary`]: crate::anim::AnimationLibrary
+const SMOKE_ANIMATION: &str = "smoke_puff";
+
+/// Ambient idle animation of a placed buildable, from the game data.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct IdleDesc {
+    /// Animation of the [`AnimationLibrary`] looping on the buildable model, like a windmill
+    /// rotation or a tree sway.
+    ///
+    /// [`AnimationLibrary`]: crate::anim::AnimationLibrary
+    #[serde(default)]
+    pub animation: Option<String>,
+    /// Position of a chimney puffing smoke, relative to the buildable origin.
+    #[serde(default)]
+    pub smoke: Option<Vec3>,
+}
+
+/// Component of the pivot entity holding the model of a placed buildable, playing its idle
+/// animation.
+#[derive(Debug, Component)]
+pub struct IdleAnimation(pub IdleDesc);
+
+/// Resource holding the shared assets of the idle effects.
+#[derive(Debug, Default)]
+struct IdleAssets {
+    smoke_mesh: Handle<Mesh>,
+    smoke_material: Handle<StandardMaterial>,
+}
+
+fn setup(
+    mut assets: ResMut<IdleAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    assets.smoke_mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 0.05,
+        subdivisions: 1,
+    }));
+    assets.smoke_material = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.9, 0.9, 0.9, 0.6),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..Default::default()
+    });
+}
+
+/// Start the idle animation of the newly placed buildables.
+fn start_idle_animations(
+    mut commands: Commands,
+    assets: Res<IdleAssets>,
+    query: Query<(Entity, &IdleAnimation), Added<IdleAnimation>>,
+) {
+    for (entity, idle) in query.iter() {
+        if let Some(animation) = &idle.0.animation {
+            commands
+                .entity(entity)
+                .insert(PlayAnimation::new(animation));
+        }
+        if let Some(smoke) = idle.0.smoke {
+            // The smoke puff animation translates from the origin, so offset a parent instead
+            commands.entity(entity).with_children(|parent| {
+                parent
+                    .spawn_bundle((
+                        Transform::from_translation(smoke),
+                        GlobalTransform::identity(),
+                    ))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(PbrBundle {
+                                mesh: assets.smoke_mesh.clone(),
+                                material: assets.smoke_material.clone(),
+                                ..Default::default()
+                            })
+                            .insert(Name::new("Smoke"))
+                            .insert(NotShadowCaster)
+                            .insert(PlayAnimation::new(SMOKE_ANIMATION));
+                    });
+            });
+        }
+    }
+}
+
+/// Plugin for the ambient idle animations of the placed buildables, making the city feel alive.
+pub struct IdlePlugin;
+
+impl Plugin for IdlePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(IdleAssets::default())
+            .add_startup_system(setup)
+            .add_system(start_idle_animations);
+    }
+}
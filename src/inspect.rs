@@ -0,0 +1,332 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    controls::{button_glyph, key_glyph, ActiveInputDevice, InputDevice},
+    game::{run_if_playing, Game, GameMode, GameSequence, GameplaySystem},
+    inventory::{Inventory, RegenerateInventoryUiEvent, UpdateInventorySlots},
+    journal::{LevelJournal, LevelOp},
+    level::{mark_for_despawn, PendingDespawn},
+    rules::Rules,
+    scores::ScoreTracker,
+    serialize::BuildableRegistry,
+    tilt::Pivot,
+    units::format_weight,
+    AppState, Cursor, Grid, Level, Placed,
+};
+
+/// Key to toggle the inspection mode.
+pub const INSPECT_KEY: KeyCode = KeyCode::V;
+
+/// Gamepad button to toggle the inspection mode.
+pub const INSPECT_BUTTON: GamepadButtonType = GamepadButtonType::North;
+
+/// Keys removing the inspected building, back into the inventory.
+pub const REMOVE_KEYS: [KeyCode; 2] = [KeyCode::Delete, KeyCode::Back];
+
+/// Gamepad button removing the inspected building.
+pub const REMOVE_BUTTON: GamepadButtonType = GamepadButtonType::RightTrigger2;
+
+/// Resource holding the state of the inspection mode, showing the building under the cursor.
+#[derive(Debug, Default)]
+pub struct InspectMode {
+    /// Is the inspection panel shown?
+    enabled: bool,
+}
+
+impl InspectMode {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Can the buildings be removed in the game mode? Removing one puts it back into the inventory,
+/// so like the practice mode not the multiplayer modes nor the market, and it counts as an undo
+/// which the hard mode disables.
+fn can_remove(game_mode: GameMode, rules: &Rules) -> bool {
+    matches!(game_mode, GameMode::Solo | GameMode::WeightReveal) && rules.can_undo()
+}
+
+/// Contribution of the weight of a cell to the offset of the COG from the pivot, the lever arm
+/// of the cell weighted by its weight.
+pub fn contribution(grid: &Grid, pivot: &Pivot, pos: &IVec2) -> Vec2 {
+    grid.cell(pos).weight * pivot.lever(grid.lever_pos(pos))
+}
+
+/// Prompt of the remove action on a device.
+fn remove_prompt(device: InputDevice) -> String {
+    match device {
+        InputDevice::Gamepad => button_glyph(REMOVE_BUTTON).to_owned(),
+        InputDevice::Keyboard | InputDevice::Touch => key_glyph(REMOVE_KEYS[0]),
+    }
+}
+
+/// Marker for the root of the inspection panel.
+#[derive(Component)]
+struct InspectPanel;
+
+/// Marker for the text of the inspection panel.
+#[derive(Component)]
+struct InspectText;
+
+fn spawn_inspect_panel(mut commands: Commands, ui_resources: Res<UiResources>) {
+    let font = ui_resources.text_font();
+    let style = |font_size, color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(100.0),
+                    right: Val::Px(20.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(320.0), Val::Auto),
+                padding: Rect::all(Val::Px(12.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.4)),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Name::new("InspectPanel"))
+        .insert(Hud)
+        .insert(InspectPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        max_size: Size::new(Val::Px(296.0), Val::Undefined),
+                        ..Default::default()
+                    },
+                    text: Text {
+                        sections: vec![
+                            // Name
+                            TextSection {
+                                value: String::new(),
+                                style: style(28.0, Color::rgb_u8(255, 220, 120)),
+                            },
+                            // Cell, weight, and contribution
+                            TextSection {
+                                value: String::new(),
+                                style: style(18.0, Color::rgb_u8(200, 200, 200)),
+                            },
+                            // Remove prompt
+                            TextSection {
+                                value: String::new(),
+                                style: style(18.0, Color::WHITE),
+                            },
+                        ],
+                        ..Default::default()
+                    },
+                    visibility: Visibility { is_visible: false },
+                    ..Default::default()
+                })
+                .insert(InspectText);
+        });
+}
+
+/// Toggle the inspection mode, and remove the inspected building back into the inventory.
+fn inspect_input(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    game: Res<Game>,
+    game_mode: Res<GameMode>,
+    rules: Res<Rules>,
+    mut inspect: ResMut<InspectMode>,
+    mut grid: ResMut<Grid>,
+    mut inventory: ResMut<Inventory>,
+    mut journal: ResMut<LevelJournal>,
+    mut tracker: ResMut<ScoreTracker>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    cursor_query: Query<&Cursor>,
+    placed_query: Query<(Entity, &Placed), Without<PendingDespawn>>,
+) {
+    let button_pressed = |button_type| {
+        gamepad_input
+            .get_just_pressed()
+            .any(|button| button.1 == button_type)
+    };
+    if keyboard_input.just_pressed(INSPECT_KEY) || button_pressed(INSPECT_BUTTON) {
+        inspect.enabled = !inspect.enabled;
+    }
+    if !inspect.enabled
+        || game.sequence() != GameSequence::Play
+        || !can_remove(*game_mode, &rules)
+        || !(keyboard_input.any_just_pressed(REMOVE_KEYS) || button_pressed(REMOVE_BUTTON))
+    {
+        return;
+    }
+    let cursor = match cursor_query.get_single() {
+        Ok(cursor) if cursor.enabled() => cursor,
+        _ => return,
+    };
+    let pos = cursor.pos();
+    let bref = match grid.cell(&pos).buildable {
+        Some(bref) => bref,
+        None => return,
+    };
+    let entity = placed_query
+        .iter()
+        .find(|(_, placed)| placed.0 == pos)
+        .map(|(entity, _)| entity);
+    if let Some(entity) = entity {
+        mark_for_despawn(&mut commands, entity);
+    }
+    grid.remove_item(&pos, entity);
+    debug!("Inspect: removed {:?} from {:?}", bref, pos);
+    if inventory.add_items(bref, 1) {
+        ev_regen_ui.send(RegenerateInventoryUiEvent);
+    }
+    journal.record(LevelOp::Remove { pos, bref });
+    tracker.record_undo();
+    ev_update_slots.send(UpdateInventorySlots);
+}
+
+/// Display the name, cell, weight, and contribution to the COG of the building under the cursor
+/// while the inspection mode is on.
+fn update_inspect_panel(
+    inspect: Res<InspectMode>,
+    grid: Res<Grid>,
+    level: Res<Level>,
+    buildables: Res<BuildableRegistry>,
+    game_mode: Res<GameMode>,
+    rules: Res<Rules>,
+    device: Res<ActiveInputDevice>,
+    cursor_query: Query<&Cursor>,
+    mut panel_query: Query<&mut Visibility, Or<(With<InspectPanel>, With<InspectText>)>>,
+    mut text_query: Query<&mut Text, With<InspectText>>,
+) {
+    let inspected = cursor_query
+        .get_single()
+        .ok()
+        .filter(|_| inspect.enabled)
+        .map(|cursor| cursor.pos())
+        .and_then(|pos| grid.cell(&pos).buildable.map(|bref| (pos, bref)))
+        .and_then(|(pos, bref)| buildables.get(bref).map(|buildable| (pos, buildable)));
+    for mut visibility in panel_query.iter_mut() {
+        if visibility.is_visible != inspected.is_some() {
+            visibility.is_visible = inspected.is_some();
+        }
+    }
+    let (pos, buildable, level_desc) = match (inspected, level.desc()) {
+        (Some((pos, buildable)), Some(level_desc)) => (pos, buildable, level_desc),
+        _ => return,
+    };
+    let lever = contribution(&grid, &level_desc.pivot, &pos);
+    let details = format!(
+        "\nCell: {}, {}\nWeight: {}\nPull on the COG: {:+.1}, {:+.1}",
+        pos.x,
+        pos.y,
+        format_weight(grid.cell(&pos).weight, level.weight_unit()),
+        lever.x,
+        lever.y
+    );
+    let prompt = if can_remove(*game_mode, &rules) {
+        format!("\n\n{}: Remove", remove_prompt(device.0))
+    } else {
+        "\n\nCannot be removed in this mode.".to_owned()
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != buildable.name()
+            || text.sections[1].value != details
+            || text.sections[2].value != prompt
+        {
+            text.sections[0].value = buildable.name().to_owned();
+            text.sections[1].value = details.clone();
+            text.sections[2].value = prompt.clone();
+        }
+    }
+}
+
+fn inspect_cleanup(
+    mut commands: Commands,
+    mut inspect: ResMut<InspectMode>,
+    query: Query<Entity, With<InspectPanel>>,
+) {
+    inspect.enabled = false;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the inspection mode, showing a panel for the building under the cursor with a
+/// shortcut to remove it.
+pub struct InspectPlugin;
+
+impl Plugin for InspectPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InspectMode::default())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_inspect_panel))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Input)
+                    .with_system(inspect_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_inspect_panel),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(inspect_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inventory::Buildable,
+        layout::{GridLayout, LayoutPlacement},
+    };
+
+    #[test]
+    fn contributions() {
+        let mut buildables = BuildableRegistry::new();
+        buildables.register("house", Buildable::without_assets("house", 2.0));
+        let layout = GridLayout {
+            size: IVec2::new(3, 3),
+            placements: vec![
+                LayoutPlacement {
+                    pos: IVec2::new(1, 0),
+                    buildable: "house".to_owned(),
+                    weight: 2.0,
+                },
+                LayoutPlacement {
+                    pos: IVec2::new(-1, 1),
+                    buildable: "house".to_owned(),
+                    weight: 3.0,
+                },
+            ],
+        };
+        let grid = Grid::from_layout(&layout, &buildables).unwrap();
+        let pivot = Pivot::default();
+        assert_eq!(
+            contribution(&grid, &pivot, &IVec2::new(1, 0)),
+            Vec2::new(2.0, 0.0)
+        );
+        assert_eq!(
+            contribution(&grid, &pivot, &IVec2::new(-1, 1)),
+            Vec2::new(-3.0, 3.0)
+        );
+        assert_eq!(contribution(&grid, &pivot, &IVec2::ZERO), Vec2::ZERO);
+        let pivot = Pivot {
+            offset: Vec2::new(1.0, 0.0),
+            knife_edge: None,
+        };
+        assert_eq!(contribution(&grid, &pivot, &IVec2::new(1, 0)), Vec2::ZERO);
+        assert_eq!(
+            contribution(&grid, &pivot, &IVec2::new(-1, 1)),
+            Vec2::new(-6.0, 3.0)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use bevy::{ecs::system::Resource, prelude::*};
+use bevy_inspector_egui::{
+    bevy_egui::EguiContext, egui, reflect::ui_for_reflect, Context, WorldInspectorParams,
+};
+
+use crate::{inventory::Inventory, Grid, Level};
+
+/// Draw an editable view of a gameplay resource. The view edits a copy of the resource, applied
+/// back only when edited, so the resource isn't flagged as changed on each frame.
+fn resource_ui<T: Resource + Reflect>(
+    world: &mut World,
+    ui: &mut egui::Ui,
+    ctx: &egui::Context,
+    name: &str,
+) {
+    ui.collapsing(name, |ui| {
+        world.resource_scope(|world, mut resource: Mut<T>| {
+            let mut value = resource.clone_value();
+            let mut context = Context::new_world_access(Some(ctx), world);
+            if ui_for_reflect(&mut *value, ui, &mut context) {
+                resource.apply(&*value);
+            }
+        });
+    });
+}
+
+/// Draw the gameplay resources next to the world inspector, which only lists the entities, while
+/// the inspector is enabled.
+fn gameplay_inspector_ui(world: &mut World) {
+    let params = world.resource::<WorldInspectorParams>();
+    if !params.enabled {
+        return;
+    }
+    let window = params.window;
+    let ctx = match world
+        .resource_mut::<EguiContext>()
+        .try_ctx_for_window_mut(window)
+    {
+        Some(ctx) => ctx.clone(),
+        None => return,
+    };
+    egui::Window::new("Gameplay")
+        .vscroll(true)
+        .show(&ctx, |ui| {
+            resource_ui::<Grid>(world, ui, &ctx, "Grid");
+            resource_ui::<Inventory>(world, ui, &ctx, "Inventory");
+            resource_ui::<Level>(world, ui, &ctx, "Level");
+        });
+}
+
+/// Plugin showing the gameplay resources like the grid and the inventory in the inspector, to
+/// inspect and tweak them live. Only available in debug builds.
+pub struct GameplayInspectorPlugin;
+
+impl Plugin for GameplayInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(gameplay_inspector_ui.exclusive_system());
+    }
+}
@@ -0,0 +1,417 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    boot::UiResources,
+    config::DEFAULT_LANGUAGE,
+    game::{Game, GameplaySystem},
+    serialize::LevelDesc,
+    AppState, Config, Level, ResetPlateEvent,
+};
+
+/// Speed of the typewriter effect, in characters per second.
+const TYPEWRITER_SPEED: f32 = 40.0;
+
+/// Keys completing the page being typed, or turning to the next page.
+const NEXT_KEYS: [KeyCode; 2] = [KeyCode::Space, KeyCode::Return];
+
+/// Key skipping the rest of the interlude.
+const SKIP_KEY: KeyCode = KeyCode::Escape;
+
+/// Size of the portrait of the speaker, in pixels.
+const PORTRAIT_SIZE: f32 = 128.0;
+
+/// Text in several languages, or a single text for all of them.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum LocalizedText {
+    Plain(String),
+    /// Translations by language code, like `en` or `fr`.
+    Translated(HashMap<String, String>),
+}
+
+impl LocalizedText {
+    /// Text in the given language, falling back to [`DEFAULT_LANGUAGE`] if not translated.
+    pub fn get(&self, language: &str) -> &str {
+        match self {
+            LocalizedText::Plain(text) => text,
+            LocalizedText::Translated(texts) => texts
+                .get(language)
+                .or_else(|| texts.get(DEFAULT_LANGUAGE))
+                .or_else(|| {
+                    texts
+                        .iter()
+                        .min_by_key(|(key, _)| *key)
+                        .map(|(_, text)| text)
+                })
+                .map_or("", |text| &text[..]),
+        }
+    }
+}
+
+/// Story interlude played between the levels, as pages of dialogue from a speaker.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct InterludeDesc {
+    /// Portrait of the speaker, relative to the textures/ folder, if any.
+    #[serde(default)]
+    pub portrait: Option<String>,
+    /// Name of the speaker, if any.
+    #[serde(default)]
+    pub speaker: Option<LocalizedText>,
+    /// Pages of text, shown one at a time.
+    pub pages: Vec<LocalizedText>,
+}
+
+/// Resource holding the interludes of the game data, by name.
+#[derive(Debug, Default)]
+pub struct Interludes(HashMap<String, Arc<InterludeDesc>>);
+
+impl Interludes {
+    pub fn new(interludes: &HashMap<String, InterludeDesc>) -> Self {
+        Interludes(
+            interludes
+                .iter()
+                .map(|(name, desc)| (name.clone(), Arc::new(desc.clone())))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<InterludeDesc>> {
+        self.0.get(name)
+    }
+}
+
+/// Name of the interlude to play before a level: the interlude of the level itself, or else the
+/// interlude of its world when entering that world from another one.
+fn interlude_name<'a>(level_desc: &'a LevelDesc, prev_world: Option<&str>) -> Option<&'a str> {
+    level_desc.interlude.as_deref().or_else(|| {
+        let world = level_desc.world.as_ref()?;
+        if prev_world == Some(&world.name[..]) {
+            None
+        } else {
+            world.interlude.as_deref()
+        }
+    })
+}
+
+/// Beginning of a page revealed by the typewriter effect, up to the given number of characters.
+fn typed_text(page: &str, chars: usize) -> &str {
+    page.char_indices()
+        .nth(chars)
+        .map_or(page, |(index, _)| &page[..index])
+}
+
+/// Interlude being played.
+#[derive(Debug)]
+struct ActiveInterlude {
+    desc: Arc<InterludeDesc>,
+    /// Index of the page shown.
+    page: usize,
+    /// Number of characters of the page revealed so far.
+    chars: f32,
+    /// Root entity of the interlude UI.
+    root: Entity,
+}
+
+/// Resource tracking the interlude being played, if any.
+#[derive(Debug)]
+pub struct InterludePlayer {
+    /// Are interludes played? Disabled for the bots playing the game on their own.
+    enabled: bool,
+    /// Index of the last level started.
+    last_level: Option<usize>,
+    /// World of the last level started, if any.
+    last_world: Option<String>,
+    active: Option<ActiveInterlude>,
+}
+
+impl InterludePlayer {
+    pub fn new() -> Self {
+        InterludePlayer {
+            enabled: true,
+            last_level: None,
+            last_world: None,
+            active: None,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Is an interlude being played?
+    pub fn is_playing(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Is the interlude of the level with the given index, if any, yet to start?
+    pub fn is_pending(&self, level_index: usize) -> bool {
+        self.last_level != Some(level_index)
+    }
+}
+
+/// Marker for the text of the page of the interlude being played.
+#[derive(Component)]
+struct InterludeText;
+
+/// Spawn the dialogue box of an interlude at the bottom of the screen.
+fn spawn_interlude_ui(
+    commands: &mut Commands,
+    desc: &InterludeDesc,
+    language: &str,
+    asset_server: &AssetServer,
+    ui_resources: &UiResources,
+) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(40.0),
+                    right: Val::Px(40.0),
+                    bottom: Val::Px(40.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Auto, Val::Px(PORTRAIT_SIZE + 40.0)),
+                padding: Rect::all(Val::Px(20.0)),
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.75)),
+            ..Default::default()
+        })
+        .insert(Name::new("Interlude"))
+        .with_children(|parent| {
+            if let Some(portrait) = &desc.portrait {
+                parent.spawn_bundle(ImageBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(PORTRAIT_SIZE), Val::Px(PORTRAIT_SIZE)),
+                        margin: Rect {
+                            right: Val::Px(20.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    image: UiImage(asset_server.load(&format!("textures/{}", portrait)[..])),
+                    ..Default::default()
+                });
+            }
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        flex_grow: 1.0,
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    if let Some(speaker) = &desc.speaker {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                speaker.get(language),
+                                TextStyle {
+                                    font: ui_resources.title_font(),
+                                    font_size: 36.0,
+                                    color: Color::rgb_u8(255, 220, 120),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    }
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                flex_grow: 1.0,
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                String::new(),
+                                TextStyle {
+                                    font: ui_resources.text_font(),
+                                    font_size: 24.0,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(InterludeText);
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "[SPACE] next    [ESC] skip",
+                            TextStyle {
+                                font: ui_resources.text_font(),
+                                font_size: 18.0,
+                                color: Color::rgb_u8(160, 160, 160),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                });
+        })
+        .id()
+}
+
+/// Start the interlude of a level when it starts for the first time.
+fn start_interlude(
+    mut commands: Commands,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    interludes: Res<Interludes>,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    ui_resources: Res<UiResources>,
+    mut player: ResMut<InterludePlayer>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    // Restarting a level does not replay its interlude
+    if player.last_level == Some(level.index()) {
+        return;
+    }
+    let name = interlude_name(level_desc, player.last_world.as_deref()).map(str::to_owned);
+    player.last_level = Some(level.index());
+    player.last_world = level_desc.world.as_ref().map(|world| world.name.clone());
+    if !player.enabled || player.is_playing() {
+        return;
+    }
+    let desc = match name.as_ref().and_then(|name| interludes.get(name)) {
+        Some(desc) if !desc.pages.is_empty() => desc.clone(),
+        _ => return,
+    };
+    debug!("Interlude '{}'", name.unwrap());
+    let root = spawn_interlude_ui(
+        &mut commands,
+        &desc,
+        &config.language,
+        &asset_server,
+        &ui_resources,
+    );
+    player.active = Some(ActiveInterlude {
+        desc,
+        page: 0,
+        chars: 0.0,
+        root,
+    });
+}
+
+/// Type the pages of the interlude being played, turning them on player input, and hold the
+/// intro of the level until the interlude ends or is skipped.
+fn play_interlude(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<Config>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game: ResMut<Game>,
+    mut player: ResMut<InterludePlayer>,
+    mut text_query: Query<&mut Text, With<InterludeText>>,
+) {
+    let active = match player.active.as_mut() {
+        Some(active) => active,
+        None => return,
+    };
+    game.hold_intro();
+
+    let mut done = keyboard_input.just_pressed(SKIP_KEY);
+    if done {
+        keyboard_input.clear_just_pressed(SKIP_KEY);
+    } else {
+        let page = active.desc.pages[active.page].get(&config.language);
+        let len = page.chars().count() as f32;
+        if NEXT_KEYS
+            .iter()
+            .any(|&key| keyboard_input.just_pressed(key))
+        {
+            if active.chars < len {
+                active.chars = len;
+            } else if active.page + 1 < active.desc.pages.len() {
+                active.page += 1;
+                active.chars = 0.0;
+            } else {
+                done = true;
+            }
+        } else {
+            active.chars = (active.chars + TYPEWRITER_SPEED * time.delta_seconds()).min(len);
+        }
+    }
+    if done {
+        commands.entity(active.root).despawn_recursive();
+        player.active = None;
+        return;
+    }
+
+    let page = active.desc.pages[active.page].get(&config.language);
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let typed = typed_text(page, active.chars as usize);
+        if text.sections[0].value != typed {
+            text.sections[0].value = typed.to_owned();
+        }
+    }
+}
+
+/// Forget the interlude being played and the levels played when leaving the game, so a new game
+/// plays the interludes again.
+fn interlude_cleanup(mut commands: Commands, mut player: ResMut<InterludePlayer>) {
+    if let Some(active) = player.active.take() {
+        commands.entity(active.root).despawn_recursive();
+    }
+    player.last_level = None;
+    player.last_world = None;
+}
+
+/// Plugin for the story interludes played between the levels.
+pub struct InterludePlugin;
+
+impl Plugin for InterludePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Interludes::default())
+            .insert_resource(InterludePlayer::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(start_interlude)
+                    .with_system(play_interlude.after(start_interlude)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(interlude_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn localized() {
+        let text: LocalizedText = serde_json::from_str(r#""Hello""#).unwrap();
+        assert_eq!(text.get("fr"), "Hello");
+
+        let text: LocalizedText =
+            serde_json::from_str(r#"{ "en": "Hello", "fr": "Bonjour" }"#).unwrap();
+        assert_eq!(text.get("fr"), "Bonjour");
+        assert_eq!(text.get("de"), "Hello");
+
+        let text: LocalizedText = serde_json::from_str(r#"{ "fr": "Bonjour" }"#).unwrap();
+        assert_eq!(text.get("en"), "Bonjour");
+    }
+
+    #[test]
+    fn typewriter() {
+        assert_eq!(typed_text("Hello", 0), "");
+        assert_eq!(typed_text("Hello", 3), "Hel");
+        assert_eq!(typed_text("Hello", 10), "Hello");
+        // Never splits a character
+        assert_eq!(typed_text("Déjà vu", 2), "Dé");
+        assert_eq!(typed_text("Déjà vu", 4), "Déjà");
+    }
+}
@@ -1,6 +1,18 @@
-use bevy::prelude::*;
-
-use crate::serialize::{BuildableRef, Buildables};
+use bevy::{asset::HandleId, prelude::*};
+use bevy_tweening::TweenCompleted;
+
+use crate::{
+    anim::PlayAnimation,
+    cinematic::Hud,
+    game::GameEvent,
+    idle::IdleDesc,
+    rules::{RevealedWeights, Rules},
+    serialize::{BuildableId, BuildableRegistry, DeliveryDesc, LevelDesc, MarketDesc},
+    ui_atlas::AtlasImage,
+    ui_layout::{UiLayouts, UiLayoutsReloadedEvent},
+    wardrobe::Achievement,
+    Level,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SlotState {
@@ -23,16 +35,116 @@ impl SlotState {
     }
 }
 
+/// 3D model of a [`BuildablePart`].
+#[derive(Debug, Clone)]
+pub enum PartModel {
+    /// Scene with its own meshes and materials.
+    Scene(Handle<Scene>),
+    /// Single mesh with a material.
+    Mesh {
+        mesh: Handle<Mesh>,
+        material: Handle<StandardMaterial>,
+    },
+}
+
+/// Part of a buildable assembled from several models, like the roof or the chimney of a house,
+/// spawned as a child of the main model with its own transform.
+#[derive(Debug, Clone)]
+pub struct BuildablePart {
+    pub model: PartModel,
+    /// Transform relative to the origin of the buildable.
+    pub transform: Transform,
+}
+
+impl BuildablePart {
+    /// Weak handle of the model and material of the part, loaded with the level using it.
+    fn handle_ids(&self) -> Vec<HandleId> {
+        match &self.model {
+            PartModel::Scene(scene) => vec![scene.id],
+            PartModel::Mesh { mesh, material } => vec![mesh.id, material.id],
+        }
+    }
+
+    fn spawn(&self, parent: &mut ChildBuilder) {
+        match &self.model {
+            PartModel::Scene(scene) => {
+                parent
+                    .spawn_bundle((self.transform, GlobalTransform::identity()))
+                    .with_children(|parent| {
+                        parent.spawn_scene(scene.clone());
+                    });
+            }
+            PartModel::Mesh { mesh, material } => {
+                parent.spawn_bundle(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: self.transform,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// Cosmetic variant of a buildable, replacing its 3D model and frame image.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    /// Display name.
+    name: String,
+    /// Handle to the 3D model.
+    mesh: Handle<Scene>,
+    /// Parts assembled with the model, replacing the ones of the buildable if set.
+    parts: Option<Vec<BuildablePart>>,
+    /// Handle to the frame image in default state.
+    frame_image: Handle<Image>,
+    /// Achievement unlocking the skin, if it's not available from the start.
+    unlock: Option<Achievement>,
+}
+
+impl Skin {
+    pub fn new(
+        name: &str,
+        mesh: Handle<Scene>,
+        frame_image: Handle<Image>,
+        unlock: Option<Achievement>,
+    ) -> Self {
+        Skin {
+            name: name.to_owned(),
+            mesh,
+            parts: None,
+            frame_image,
+            unlock,
+        }
+    }
+
+    /// Replace the parts of the buildable with other ones for this skin.
+    pub fn set_parts(&mut self, parts: Option<Vec<BuildablePart>>) {
+        self.parts = parts;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unlock(&self) -> Option<&Achievement> {
+        self.unlock.as_ref()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Buildable {
     /// Display name.
     name: String,
     /// Weight.
     weight: f32,
+    /// Maximum deviation of the weight of each placed instance from the nominal weight.
+    weight_variance: f32,
     /// Is the buildable stackable?
     stackable: bool,
     /// Handle to the 3D model.
     mesh: Handle<Scene>,
+    /// Parts assembled with the 3D model, if any.
+    parts: Vec<BuildablePart>,
     /// Handle to the material of the 3D model.
     material: Handle<StandardMaterial>,
     /// Handle to the frame image in default state.
@@ -43,6 +155,16 @@ pub struct Buildable {
     color_selected: Color,
     /// Color in empty state.
     color_empty: Color,
+    /// Alternate skins, in addition to the default model and frame.
+    skins: Vec<Skin>,
+    /// Index of the selected skin, or `None` for the default one.
+    skin: Option<usize>,
+    /// Flavor text shown in the lore panel when the buildable is selected.
+    description: String,
+    /// Ambient animation once placed, if any.
+    idle: Option<IdleDesc>,
+    /// Materials of the 3D model and its parts recolored per world, with their palette slot.
+    palette: Vec<(Handle<StandardMaterial>, String)>,
 }
 
 impl Buildable {
@@ -60,18 +182,66 @@ impl Buildable {
         Buildable {
             name: name.to_owned(),
             weight,
+            weight_variance: 0.0,
             stackable,
             mesh,
+            parts: vec![],
             material,
             frame_image,
             color_unselected,
             color_selected,
             color_empty,
+            skins: vec![],
+            skin: None,
+            description: String::new(),
+            idle: None,
+            palette: vec![],
         }
     }
 
+    /// Create a buildable with only a name and a weight, without any asset, for the tests, the
+    /// benchmarks, and the validation of the game data.
+    pub fn without_assets(name: &str, weight: f32) -> Self {
+        Buildable::new(
+            name,
+            weight,
+            false,
+            Handle::default(),
+            Handle::default(),
+            Handle::default(),
+            Color::WHITE,
+            Color::WHITE,
+            Color::WHITE,
+        )
+    }
+
+    pub fn add_skin(&mut self, skin: Skin) {
+        self.skins.push(skin);
+    }
+
+    pub fn skins(&self) -> &[Skin] {
+        &self.skins
+    }
+
+    /// Index of the selected skin, or `None` for the default one.
+    pub fn selected_skin(&self) -> Option<usize> {
+        self.skin
+    }
+
+    /// Select the skin used to spawn the buildable and display its frame. Invalid indices select
+    /// the default skin.
+    pub fn select_skin(&mut self, skin: Option<usize>) {
+        self.skin = skin.filter(|&index| index < self.skins.len());
+    }
+
+    fn skin(&self) -> Option<&Skin> {
+        self.skin.map(|index| &self.skins[index])
+    }
+
     pub fn frame_image(&self) -> Handle<Image> {
-        self.frame_image.clone()
+        self.skin()
+            .map_or(&self.frame_image, |skin| &skin.frame_image)
+            .clone()
     }
 
     /// Get the frame color for the given state, inferred from the item count and selection state.
@@ -83,47 +253,123 @@ impl Buildable {
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn weight(&self) -> f32 {
         self.weight
     }
 
+    /// Maximum deviation of the weight of each placed instance from [`Buildable::weight()`], zero
+    /// if all instances weigh the same.
+    pub fn weight_variance(&self) -> f32 {
+        self.weight_variance
+    }
+
+    pub fn set_weight_variance(&mut self, weight_variance: f32) {
+        self.weight_variance = weight_variance;
+    }
+
+    /// Largest weight a placed instance can have, in absolute value.
+    pub fn max_weight(&self) -> f32 {
+        self.weight.abs() + self.weight_variance
+    }
+
+    pub fn stackable(&self) -> bool {
+        self.stackable
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    pub fn idle(&self) -> Option<&IdleDesc> {
+        self.idle.as_ref()
+    }
+
+    pub fn set_idle(&mut self, idle: Option<IdleDesc>) {
+        self.idle = idle;
+    }
+
     pub fn mesh(&self) -> &Handle<Scene> {
-        &self.mesh
+        self.skin().map_or(&self.mesh, |skin| &skin.mesh)
+    }
+
+    /// Parts assembled with the 3D model, with the selected skin.
+    pub fn parts(&self) -> &[BuildablePart] {
+        self.skin()
+            .and_then(|skin| skin.parts.as_deref())
+            .unwrap_or(&self.parts)
+    }
+
+    pub fn set_parts(&mut self, parts: Vec<BuildablePart>) {
+        self.parts = parts;
+    }
+
+    /// Weak handles of the 3D model and its parts, with the selected skin.
+    pub fn model_handle_ids(&self) -> Vec<HandleId> {
+        let mut ids = vec![self.mesh().id];
+        ids.extend(self.parts().iter().flat_map(BuildablePart::handle_ids));
+        ids
+    }
+
+    /// Spawn the 3D model and its parts, with the selected skin, as children of the parent.
+    pub fn spawn_model(&self, parent: &mut ChildBuilder) {
+        parent.spawn_scene(self.mesh().clone());
+        for part in self.parts() {
+            part.spawn(parent);
+        }
     }
 
     pub fn material(&self) -> &Handle<StandardMaterial> {
         &self.material
     }
+
+    /// Materials recolored per world, with their palette slot. See [`WorldPalette`].
+    ///
+    /// [`WorldPalette`]: crate::palette::WorldPalette
+    pub fn palette(&self) -> &[(Handle<StandardMaterial>, String)] {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Vec<(Handle<StandardMaterial>, String)>) {
+        self.palette = palette;
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Reflect, FromReflect)]
 pub struct Slot {
-    bref: BuildableRef,
+    bref: BuildableId,
     count: u32,
 }
 
 impl Slot {
-    pub fn new(bref: BuildableRef, count: u32) -> Self {
+    pub fn new(bref: BuildableId, count: u32) -> Self {
         Slot { bref, count }
     }
 
-    pub fn bref(&self) -> &BuildableRef {
-        &self.bref
+    pub fn bref(&self) -> BuildableId {
+        self.bref
     }
 
     pub fn count(&self) -> u32 {
         self.count
     }
 
-    pub fn pop_item(&mut self) -> Option<BuildableRef> {
+    pub fn pop_item(&mut self) -> Option<BuildableId> {
         if self.count > 0 {
             self.count -= 1;
             trace!(
-                "Removed 1 item from slot '{}', left: {}",
-                self.bref.0,
+                "Removed 1 item from slot {:?}, left: {}",
+                self.bref,
                 self.count
             );
-            Some(self.bref.clone())
+            Some(self.bref)
         } else {
             None
         }
@@ -134,11 +380,27 @@ impl Slot {
     }
 }
 
-#[derive(Debug, Clone, Component)]
+/// Gameplay state of an [`Inventory`], without its UI entities.
+#[derive(Debug, Clone)]
+pub struct InventorySnapshot {
+    /// Inventory slots, in order.
+    pub slots: Vec<Slot>,
+    /// Index of the selected slot.
+    pub selected_index: usize,
+    /// Number of buildables placed since the start of the level.
+    pub placed_count: u32,
+}
+
+#[derive(Debug, Clone, Component, Reflect)]
 pub struct Inventory {
     slots: Vec<Slot>,
     selected_index: usize,
     root_node: Option<Entity>,
+    /// Number of buildables placed since the start of the level.
+    placed_count: u32,
+    /// Deliveries not triggered yet, sorted by trigger order.
+    #[reflect(ignore)]
+    pending_deliveries: Vec<DeliveryDesc>,
 }
 
 impl Inventory {
@@ -147,7 +409,127 @@ impl Inventory {
             slots: vec![],
             selected_index: 0,
             root_node: None,
+            placed_count: 0,
+            pending_deliveries: vec![],
+        }
+    }
+
+    /// Reset the inventory to the initial content of the given level, including its
+    /// pending deliveries.
+    pub fn reset_from_level(&mut self, level_desc: &LevelDesc) {
+        self.set_slots(
+            level_desc
+                .inventory
+                .iter()
+                .map(|(&bref, &count)| Slot::new(bref, count)),
+        );
+        self.placed_count = 0;
+        self.pending_deliveries = level_desc.deliveries.clone();
+        self.pending_deliveries
+            .sort_by_key(|delivery| delivery.after_placements);
+    }
+
+    /// Reset the inventory for the market mode, with one empty slot per buildable of the market
+    /// and no delivery. Buildables are added one by one as they are drawn.
+    pub fn reset_from_market(&mut self, market: &MarketDesc) {
+        self.set_slots(market.weights.iter().map(|&(bref, _)| Slot::new(bref, 0)));
+        self.placed_count = 0;
+        self.pending_deliveries.clear();
+    }
+
+    /// Record that a buildable was placed, and return the deliveries triggered by that placement
+    /// if any. The deliveries are not applied; use [`deliver`] to add them to the inventory.
+    ///
+    /// [`deliver`]: Inventory::deliver
+    pub fn record_placement(&mut self) -> Vec<DeliveryDesc> {
+        self.placed_count += 1;
+        let placed_count = self.placed_count;
+        let count = self
+            .pending_deliveries
+            .iter()
+            .take_while(|delivery| delivery.after_placements <= placed_count)
+            .count();
+        self.pending_deliveries.drain(..count).collect()
+    }
+
+    /// Add the content of a delivery to the inventory, refilling existing slots or creating
+    /// new ones. Returns `true` if any new slot was created.
+    pub fn deliver(&mut self, delivery: &DeliveryDesc) -> bool {
+        let mut new_slot = false;
+        for (&bref, &count) in delivery.inventory.iter() {
+            new_slot |= self.add_items(bref, count);
+            trace!("Delivered {:?} x {}", bref, count);
         }
+        new_slot
+    }
+
+    /// Add some buildables to the inventory, refilling the existing slot or creating a new one.
+    /// Returns `true` if a new slot was created.
+    pub fn add_items(&mut self, bref: BuildableId, count: u32) -> bool {
+        if let Some(slot) = self.slots.iter_mut().find(|slot| slot.bref == bref) {
+            slot.count += count;
+            false
+        } else {
+            self.add_slot(bref, count);
+            true
+        }
+    }
+
+    /// Take one buildable of the given type out of the inventory. Returns `false` if none is
+    /// left.
+    pub fn take_item(&mut self, bref: BuildableId) -> bool {
+        self.slots
+            .iter_mut()
+            .find(|slot| slot.bref == bref && !slot.is_empty())
+            .and_then(Slot::pop_item)
+            .is_some()
+    }
+
+    /// Are more buildables still to be delivered later in the level?
+    pub fn has_pending_deliveries(&self) -> bool {
+        !self.pending_deliveries.is_empty()
+    }
+
+    /// Number of buildables placed since the start of the level.
+    pub fn placed_count(&self) -> u32 {
+        self.placed_count
+    }
+
+    /// Move half of the items of each slot, rounded down, into a new inventory with the same
+    /// slots. Pending deliveries stay in this inventory.
+    pub fn split(&mut self) -> Inventory {
+        let mut other = Inventory::new();
+        other.set_slots(self.slots.iter_mut().map(|slot| {
+            let count = slot.count / 2;
+            slot.count -= count;
+            Slot::new(slot.bref, count)
+        }));
+        other
+    }
+
+    /// Capture the gameplay state of the inventory.
+    pub fn snapshot(&self) -> InventorySnapshot {
+        InventorySnapshot {
+            slots: self.slots.clone(),
+            selected_index: self.selected_index,
+            placed_count: self.placed_count,
+        }
+    }
+
+    /// Restore the gameplay state of the inventory from a snapshot taken while playing the given
+    /// level. The pending deliveries are recomputed from the level and the number of placements.
+    pub fn restore(&mut self, snapshot: &InventorySnapshot, level_desc: &LevelDesc) {
+        self.selected_index = snapshot.selected_index;
+        self.set_slots(snapshot.slots.iter().cloned());
+        self.placed_count = snapshot.placed_count;
+        self.pending_deliveries = level_desc
+            .deliveries
+            .iter()
+            .filter(|delivery| delivery.after_placements > snapshot.placed_count)
+            .cloned()
+            .collect();
+        self.pending_deliveries
+            .sort_by_key(|delivery| delivery.after_placements);
     }
 
     pub fn set_slots<I>(&mut self, slots: I)
@@ -163,7 +545,7 @@ impl Inventory {
         };
     }
 
-    pub fn add_slot(&mut self, bref: BuildableRef, count: u32) -> &Slot {
+    pub fn add_slot(&mut self, bref: BuildableId, count: u32) -> &Slot {
         self.slots.push(Slot { bref, count });
         self.slots.last().as_ref().unwrap()
     }
@@ -296,17 +678,33 @@ impl UiResources {
 /// Event to regenerate the UI of the inventory.
 pub struct RegenerateInventoryUiEvent;
 
+/// Event signaling that some inventory was delivered in the middle of a level.
+#[derive(Debug)]
+pub struct DeliveryEvent(pub DeliveryDesc);
+
+/// Marker for the animated banner announcing a delivery.
+#[derive(Component)]
+struct DeliveryBanner;
+
+/// User data of the [`TweenCompleted`] event raised when a delivery banner animation ends.
+const DELIVERY_BANNER_TWEEN_DONE: u64 = 0x_de11_7e57;
+
 fn setup(asset_server: Res<AssetServer>, mut ui_resouces: ResMut<UiResources>) {
     let font = asset_server.load("fonts/mochiy_pop_one/MochiyPopOne-Regular.ttf");
     *ui_resouces = UiResources { font }
 }
 
 fn update_slots(
-    buildables: Res<Buildables>,
+    mut commands: Commands,
+    buildables: Res<BuildableRegistry>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    revealed: Res<RevealedWeights>,
     mut inventory: ResMut<Inventory>,
     mut ev_select_slot: EventReader<SelectSlotEvent>,
     mut ev_update_slots: EventReader<UpdateInventorySlots>,
-    mut slot_query: Query<(&mut InventorySlot, &mut UiImage, &mut UiColor, &Children)>,
+    mut ev_game: EventWriter<GameEvent>,
+    mut slot_query: Query<(Entity, &mut InventorySlot, &mut AtlasImage, &Children)>,
     mut text_query: Query<&mut Text>,
 ) {
     // Consume all events in order and calculate the new slot index
@@ -314,24 +712,51 @@ fn update_slots(
     for ev in ev_select_slot.iter() {
         changed = changed || inventory.select_slot(&ev.0);
     }
+    if changed {
+        ev_game.send(GameEvent::SlotSelected {
+            index: inventory.selected_index,
+        });
+    }
 
     // Update all inventory slots
     if changed || ev_update_slots.iter().count() > 0 {
         let selected_index = inventory.selected_index;
         trace!("UpdateInventorySlots: sel={}", selected_index);
-        for (mut slot, mut ui_image, mut ui_color, children) in slot_query.iter_mut() {
-            let mut text = text_query.get_mut(children[0]).unwrap();
+        for (entity, mut slot, mut atlas_image, children) in slot_query.iter_mut() {
+            // The atlas inserts its region as the first child, so look the text up
+            let text_entity = match children
+                .iter()
+                .find(|&&child| text_query.get(child).is_ok())
+            {
+                Some(&text_entity) => text_entity,
+                None => continue,
+            };
+            let mut text = text_query.get_mut(text_entity).unwrap();
             let index = slot.index;
             if let Some(slot_def) = inventory.slot(index) {
                 let bref = slot_def.bref();
                 let count = slot_def.count();
                 if let Some(buildable) = buildables.get(bref) {
+                    if count < slot.count {
+                        commands
+                            .entity(slot.text)
+                            .insert(PlayAnimation::new("count_bump"));
+                    }
+                    if changed && index == selected_index as u32 {
+                        commands
+                            .entity(entity)
+                            .insert(PlayAnimation::new("slot_pop"));
+                    }
                     slot.count = count;
                     text.sections[0].value = format!("x{}", count).to_string();
+                    text.sections[1].value = format!(
+                        "\n{}",
+                        rules.weight_text(&revealed, bref, buildable, level.weight_unit())
+                    );
                     trace!("-- slot: idx={} cnt={}", index, count);
                     let slot_state = SlotState::from_data(count, index == selected_index as u32);
-                    ui_image.0 = buildable.frame_image();
-                    ui_color.0 = buildable.get_frame_color(&slot_state);
+                    atlas_image.image = buildable.frame_image();
+                    atlas_image.color = buildable.get_frame_color(&slot_state);
                 }
             }
         }
@@ -343,8 +768,12 @@ fn regenerate_ui(
     mut ev_regen_ui: EventReader<RegenerateInventoryUiEvent>,
     asset_server: Res<AssetServer>,
     mut inventory: ResMut<Inventory>,
-    buildables: Res<Buildables>,
+    buildables: Res<BuildableRegistry>,
     ui_resouces: Res<UiResources>,
+    ui_layouts: Res<UiLayouts>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    revealed: Res<RevealedWeights>,
 ) {
     if let Some(ev) = ev_regen_ui.iter().last() {
         trace!("regenerate_ui() -- GOT EVENT!");
@@ -366,6 +795,7 @@ fn regenerate_ui(
                     ..Default::default()
                 })
                 .insert(Name::new("Inventory"))
+                .insert(Hud)
                 .with_children(|parent| {
                     if inventory.slots().len() == 0 {
                         error!("Empty inventory!");
@@ -375,20 +805,27 @@ fn regenerate_ui(
                         "Generating inventory with {} slots",
                         inventory.slots().len()
                     );
-                    let mut xpos = 100.0 + 200.0 * (inventory.slots().len() - 1) as f32;
+                    let slot_size = ui_layouts.metric("inventory.slot_size");
+                    let spacing = ui_layouts.metric("inventory.slot_spacing");
+                    let bottom = ui_layouts.metric("inventory.margin_bottom");
+                    let mut xpos = ui_layouts.metric("inventory.margin_right")
+                        + spacing * (inventory.slots().len() - 1) as f32;
                     let font = ui_resouces.font.clone();
+                    let color = ui_layouts.color("inventory.text");
+                    let count_font_size = ui_layouts.metric("inventory.count_font_size");
+                    let weight_font_size = ui_layouts.metric("inventory.weight_font_size");
                     for (index, slot) in inventory.slots().iter().enumerate() {
                         let bref = slot.bref();
                         let count = slot.count();
-                        trace!("[#{}] {} x {}", index, bref.0, count);
+                        trace!("[#{}] {:?} x {}", index, bref, count);
                         if let Some(buildable) = buildables.get(bref) {
                             // Item slot with frame and item image
                             let mut frame = parent.spawn_bundle(NodeBundle {
                                 style: Style {
-                                    size: Size::new(Val::Px(128.0), Val::Px(128.0)),
+                                    size: Size::new(Val::Px(slot_size), Val::Px(slot_size)),
                                     position_type: PositionType::Absolute,
                                     position: Rect {
-                                        bottom: Val::Px(100.0),
+                                        bottom: Val::Px(bottom),
                                         right: Val::Px(xpos),
                                         ..Default::default()
                                     },
@@ -402,33 +839,53 @@ fn regenerate_ui(
                                     justify_content: JustifyContent::Center,
                                     ..Default::default()
                                 },
-                                image: UiImage(buildable.frame_image()),
-                                color: UiColor(
-                                    buildable
-                                        .get_frame_color(&SlotState::from_data(count, index == 0)),
-                                ),
+                                color: UiColor(Color::NONE),
                                 ..Default::default()
                             });
                             frame.insert(Name::new(format!("Slot #{}", index)));
+                            frame.insert(AtlasImage::new(
+                                buildable.frame_image(),
+                                buildable.get_frame_color(&SlotState::from_data(count, index == 0)),
+                            ));
                             let text = frame
                                 .with_children(|parent| {
-                                    // Item count in slot
+                                    // Item count and weight in slot
                                     parent.spawn_bundle(TextBundle {
-                                        text: Text::with_section(
-                                            format!("x{}", count).to_string(),
-                                            TextStyle {
-                                                font: font.clone(),
-                                                font_size: 90.0,
-                                                color: Color::rgb_u8(111, 188, 165),
-                                            },
-                                            Default::default(), // TextAlignment
-                                        ),
+                                        text: Text {
+                                            sections: vec![
+                                                TextSection {
+                                                    value: format!("x{}", count).to_string(),
+                                                    style: TextStyle {
+                                                        font: font.clone(),
+                                                        font_size: count_font_size,
+                                                        color,
+                                                    },
+                                                },
+                                                TextSection {
+                                                    value: format!(
+                                                        "\n{}",
+                                                        rules.weight_text(
+                                                            &revealed,
+                                                            bref,
+                                                            buildable,
+                                                            level.weight_unit(),
+                                                        )
+                                                    ),
+                                                    style: TextStyle {
+                                                        font: font.clone(),
+                                                        font_size: weight_font_size,
+                                                        color,
+                                                    },
+                                                },
+                                            ],
+                                            ..Default::default()
+                                        },
                                         ..Default::default()
                                     });
                                 })
                                 .id();
                             frame.insert(InventorySlot::new(index as u32, count, text));
-                            xpos -= 200.0;
+                            xpos -= spacing;
                         } else {
                             error!("Unknown buildable reference {:?}", bref);
                         }
@@ -443,6 +900,84 @@ fn regenerate_ui(
     }
 }
 
+/// Regenerate the inventory UI, if any, when the layouts are reloaded.
+fn relayout_inventory(
+    mut ev_reloaded: EventReader<UiLayoutsReloadedEvent>,
+    inventory: Res<Inventory>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+) {
+    if ev_reloaded.iter().last().is_some() && inventory.root_node.is_some() {
+        ev_regen_ui.send(RegenerateInventoryUiEvent);
+        ev_update_slots.send(UpdateInventorySlots);
+    }
+}
+
+/// Spawn an animated banner listing the delivered buildables for each delivery.
+fn spawn_delivery_banner(
+    mut commands: Commands,
+    mut ev_delivery: EventReader<DeliveryEvent>,
+    buildables: Res<BuildableRegistry>,
+    ui_resouces: Res<UiResources>,
+    ui_layouts: Res<UiLayouts>,
+) {
+    for ev in ev_delivery.iter() {
+        let items =
+            ev.0.inventory
+                .iter()
+                .filter_map(|(bref, count)| {
+                    buildables
+                        .get(*bref)
+                        .map(|buildable| format!("+{} {}", count, buildable.name()))
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+        trace!("spawn_delivery_banner: {}", items);
+
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(240.0),
+                        right: Val::Px(100.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    format!("Delivery! {}", items),
+                    TextStyle {
+                        font: ui_resouces.font.clone(),
+                        font_size: ui_layouts.metric("delivery_banner.font_size"),
+                        color: ui_layouts.color("delivery_banner.text"),
+                    },
+                    Default::default(), // TextAlignment
+                ),
+                ..Default::default()
+            })
+            .insert(Name::new("DeliveryBanner"))
+            .insert(DeliveryBanner)
+            .insert(
+                PlayAnimation::new("delivery_banner")
+                    .with_completed_event(DELIVERY_BANNER_TWEEN_DONE),
+            );
+    }
+}
+
+/// Despawn the delivery banners once their animation completed.
+fn despawn_delivery_banner(
+    mut commands: Commands,
+    mut ev_tween_completed: EventReader<TweenCompleted>,
+    query: Query<(), With<DeliveryBanner>>,
+) {
+    for ev in ev_tween_completed.iter() {
+        if ev.user_data == DELIVERY_BANNER_TWEEN_DONE && query.get(ev.entity).is_ok() {
+            commands.entity(ev.entity).despawn_recursive();
+        }
+    }
+}
+
 /// Plugin for managing the inventory while a level is being played.
 pub struct InventoryPlugin;
 
@@ -451,13 +986,19 @@ impl Plugin for InventoryPlugin {
         // Add Inventory resource and SelectSlotEvent event
         app.insert_resource(Inventory::new())
             .insert_resource(UiResources::new())
+            .register_type::<Slot>()
+            .register_type::<Inventory>()
             .add_event::<RegenerateInventoryUiEvent>()
+            .add_event::<DeliveryEvent>()
             .add_event::<SelectSlotEvent>()
             .add_event::<UpdateInventorySlots>();
 
         // Add system to manage the inventory
         app.add_startup_system(setup)
             .add_system(update_slots)
-            .add_system(regenerate_ui);
+            .add_system(regenerate_ui)
+            .add_system(relayout_inventory)
+            .add_system(spawn_delivery_banner)
+            .add_system(despawn_delivery_banner);
     }
 }
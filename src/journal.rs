@@ -0,0 +1,525 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::GameplaySystem,
+    inventory::Inventory,
+    serialize::{BuildableId, BuildableRegistry, LevelDesc},
+    AppState, CursorId, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Mutation of the state of the level in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LevelOp {
+    /// The plate was cleared, and the inventory reset to the one of the level.
+    Start,
+    /// The plate was cleared, and the inventory emptied to draw from the market of the level.
+    StartMarket,
+    /// Half of the inventory was given to the partner of the coop mode. See [`Inventory::split`].
+    Split,
+    /// A buildable of the inventory of a cursor was placed in the given cell, triggering any
+    /// delivery due.
+    Place {
+        pos: IVec2,
+        bref: BuildableId,
+        cursor: CursorId,
+    },
+    /// Some buildables were added to the inventory, drawn from the market or given by cheats.
+    Refill { bref: BuildableId, count: u32 },
+    /// A buildable placed in the given cell was taken back into the inventory, from the
+    /// inspection mode.
+    Remove { pos: IVec2, bref: BuildableId },
+    /// A placed buildable slid to a free cell, carried by a conveyor or sliding on ice.
+    Move { from: IVec2, to: IVec2 },
+    /// A fragile tile broke under the weight placed on it, losing its buildable and leaving a
+    /// hole.
+    Break { pos: IVec2 },
+}
+
+/// Serialized form of a [`LevelOp`], referencing the buildables by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LevelOpArchive {
+    Start,
+    StartMarket,
+    Split,
+    Place(IVec2, String),
+    PartnerPlace(IVec2, String),
+    Refill(String, u32),
+    Remove(IVec2, String),
+    Move(IVec2, IVec2),
+    Break(IVec2),
+}
+
+/// Buildable placed on the grid, with the cell it's in and the cursor which placed it.
+pub type Placement = (IVec2, BuildableId, CursorId);
+
+/// State of a level rebuilt from its journal.
+pub struct JournalState {
+    /// Buildables placed on the grid since the last start, in placement order.
+    pub placements: Vec<Placement>,
+    /// Cells whose fragile tile broke since the last start.
+    pub holes: Vec<IVec2>,
+    pub inventory: Inventory,
+    /// Inventory of the partner of the coop mode, empty in the other modes.
+    pub partner_inventory: Inventory,
+}
+
+/// Resource holding the journal of the level in progress, the ordered operations which fully
+/// determine the grid content and the inventory since the level started. The replays, the quick
+/// saves, the practice timeline, and the bug reports are all derived from it.
+///
+/// The partner of the coop mode draws from an inventory of their own, split from the level
+/// inventory by [`LevelOp::Split`], and their placements are recorded with their cursor.
+#[derive(Debug, Default, Clone)]
+pub struct LevelJournal {
+    ops: Vec<LevelOp>,
+    /// Number of times the journal was replaced, to tell appended operations from a rewind.
+    generation: u32,
+}
+
+impl LevelJournal {
+    pub fn new() -> Self {
+        LevelJournal::default()
+    }
+
+    /// Operations since the level started, in order.
+    pub fn ops(&self) -> &[LevelOp] {
+        &self.ops
+    }
+
+    /// Number of times the journal was replaced with [`LevelJournal::restore`].
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Append an operation already applied to the level.
+    pub fn record(&mut self, op: LevelOp) {
+        self.ops.push(op);
+    }
+
+    /// Forget all operations, when a new level starts.
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// Replace the operations with the ones of another journal, when the level state is rewound.
+    pub fn restore(&mut self, other: &LevelJournal) {
+        self.ops = other.ops.clone();
+        self.generation += 1;
+    }
+
+    /// Buildables placed since the last start and not removed nor lost since, in placement order,
+    /// in the cell they last moved to.
+    pub fn placements(&self) -> Vec<Placement> {
+        let start = self
+            .ops
+            .iter()
+            .rposition(|op| matches!(op, LevelOp::Start | LevelOp::StartMarket))
+            .map_or(0, |index| index + 1);
+        let mut placements = vec![];
+        for op in &self.ops[start..] {
+            match *op {
+                LevelOp::Place { pos, bref, cursor } => placements.push((pos, bref, cursor)),
+                LevelOp::Remove { pos, bref } => {
+                    remove_placement(&mut placements, pos, bref);
+                }
+                LevelOp::Move { from, to } => move_placement(&mut placements, from, to),
+                LevelOp::Break { pos } => placements.retain(|&(placed, _, _)| placed != pos),
+                _ => {}
+            }
+        }
+        placements
+    }
+
+    /// Rebuild the state of the level by applying the operations in order, the same way the level
+    /// applied them while being played.
+    pub fn replay(&self, level_desc: &LevelDesc) -> JournalState {
+        let mut placements = vec![];
+        let mut holes = vec![];
+        let mut inventory = Inventory::new();
+        let mut partner_inventory = Inventory::new();
+        inventory.reset_from_level(level_desc);
+        for op in self.ops.iter() {
+            match *op {
+                LevelOp::Start => {
+                    placements.clear();
+                    holes.clear();
+                    inventory.reset_from_level(level_desc);
+                    partner_inventory = Inventory::new();
+                }
+                LevelOp::StartMarket => {
+                    placements.clear();
+                    holes.clear();
+                    inventory.reset_from_market(&level_desc.market());
+                    partner_inventory = Inventory::new();
+                }
+                LevelOp::Split => partner_inventory = inventory.split(),
+                LevelOp::Place { pos, bref, cursor } => {
+                    let source = match cursor {
+                        CursorId::Player => &mut inventory,
+                        CursorId::Partner => &mut partner_inventory,
+                    };
+                    if source.take_item(bref) {
+                        placements.push((pos, bref, cursor));
+                        // The deliveries count the placements of both cursors
+                        for delivery in inventory.record_placement() {
+                            inventory.deliver(&delivery);
+                        }
+                    }
+                }
+                LevelOp::Refill { bref, count } => {
+                    inventory.add_items(bref, count);
+                }
+                LevelOp::Remove { pos, bref } => {
+                    if remove_placement(&mut placements, pos, bref) {
+                        inventory.add_items(bref, 1);
+                    }
+                }
+                LevelOp::Move { from, to } => move_placement(&mut placements, from, to),
+                LevelOp::Break { pos } => {
+                    placements.retain(|&(placed, _, _)| placed != pos);
+                    holes.push(pos);
+                }
+            }
+        }
+        JournalState {
+            placements,
+            holes,
+            inventory,
+            partner_inventory,
+        }
+    }
+
+    /// Serialize the operations, referencing the buildables by name.
+    pub fn to_archive(&self, buildables: &BuildableRegistry) -> Option<Vec<LevelOpArchive>> {
+        self.ops
+            .iter()
+            .map(|op| {
+                Some(match *op {
+                    LevelOp::Start => LevelOpArchive::Start,
+                    LevelOp::StartMarket => LevelOpArchive::StartMarket,
+                    LevelOp::Split => LevelOpArchive::Split,
+                    LevelOp::Place { pos, bref, cursor } => {
+                        let name = buildables.name(bref)?.to_owned();
+                        match cursor {
+                            CursorId::Player => LevelOpArchive::Place(pos, name),
+                            CursorId::Partner => LevelOpArchive::PartnerPlace(pos, name),
+                        }
+                    }
+                    LevelOp::Refill { bref, count } => {
+                        LevelOpArchive::Refill(buildables.name(bref)?.to_owned(), count)
+                    }
+                    LevelOp::Remove { pos, bref } => {
+                        LevelOpArchive::Remove(pos, buildables.name(bref)?.to_owned())
+                    }
+                    LevelOp::Move { from, to } => LevelOpArchive::Move(from, to),
+                    LevelOp::Break { pos } => LevelOpArchive::Break(pos),
+                })
+            })
+            .collect()
+    }
+
+    /// Deserialize the operations, returning `None` if a buildable is unknown.
+    pub fn from_archive(ops: &[LevelOpArchive], buildables: &BuildableRegistry) -> Option<Self> {
+        let ops = ops
+            .iter()
+            .map(|op| {
+                Some(match op {
+                    LevelOpArchive::Start => LevelOp::Start,
+                    LevelOpArchive::StartMarket => LevelOp::StartMarket,
+                    LevelOpArchive::Split => LevelOp::Split,
+                    LevelOpArchive::Place(pos, name) => LevelOp::Place {
+                        pos: *pos,
+                        bref: buildables.id(name)?,
+                        cursor: CursorId::Player,
+                    },
+                    LevelOpArchive::PartnerPlace(pos, name) => LevelOp::Place {
+                        pos: *pos,
+                        bref: buildables.id(name)?,
+                        cursor: CursorId::Partner,
+                    },
+                    LevelOpArchive::Refill(name, count) => LevelOp::Refill {
+                        bref: buildables.id(name)?,
+                        count: *count,
+                    },
+                    LevelOpArchive::Remove(pos, name) => LevelOp::Remove {
+                        pos: *pos,
+                        bref: buildables.id(name)?,
+                    },
+                    LevelOpArchive::Move(from, to) => LevelOp::Move {
+                        from: *from,
+                        to: *to,
+                    },
+                    LevelOpArchive::Break(pos) => LevelOp::Break { pos: *pos },
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(LevelJournal { ops, generation: 0 })
+    }
+}
+
+/// Forget the last placement of a buildable in a cell. Returns `false` if the buildable is not in
+/// that cell, leaving the placements alone.
+fn remove_placement(placements: &mut Vec<Placement>, pos: IVec2, bref: BuildableId) -> bool {
+    match placements
+        .iter()
+        .rposition(|&(placed, placed_bref, _)| (placed, placed_bref) == (pos, bref))
+    {
+        Some(index) => {
+            placements.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Move the last placement in a cell to another cell, keeping its place in the placement order.
+fn move_placement(placements: &mut [Placement], from: IVec2, to: IVec2) {
+    if let Some(placement) = placements
+        .iter_mut()
+        .rev()
+        .find(|(placed, _, _)| *placed == from)
+    {
+        placement.0 = to;
+    }
+}
+
+/// Start a new journal when a level starts, and record its restarts. Systems recording the
+/// operations of the level start run after the `"journal_start"` label.
+fn journal_start(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut journal: ResMut<LevelJournal>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if reset {
+        journal.clear();
+    }
+    if reset || restart {
+        journal.record(LevelOp::Start);
+    }
+}
+
+/// Plugin for the [`LevelJournal`] of the level in progress.
+pub struct JournalPlugin;
+
+impl Plugin for JournalPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LevelJournal::new()).add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label("journal_start")
+                .after(GameplaySystem::Reset)
+                .before(GameplaySystem::Cursor)
+                .with_system(journal_start),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inventory::Buildable, serialize::LevelDescArchive};
+    use std::collections::HashMap;
+
+    #[test]
+    fn replay() {
+        let mut buildables = BuildableRegistry::new();
+        for name in ["hut", "tower"] {
+            buildables.register(name, Buildable::without_assets(name, 1.0));
+        }
+        let archive: LevelDescArchive = serde_json::from_str(
+            r#"{ "name": "Journal", "grid_size": [3, 3], "balance_factor": 1.0,
+                 "victory_margin": 0.2, "inventory": { "hut": 2 },
+                 "deliveries": [ { "after_placements": 1, "inventory": { "tower": 1 } } ] }"#,
+        )
+        .unwrap();
+        let level_desc = archive.to_desc(&buildables, &HashMap::new());
+        let hut = buildables.id("hut").unwrap();
+        let tower = buildables.id("tower").unwrap();
+
+        let mut journal = LevelJournal::new();
+        journal.record(LevelOp::Start);
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(0, 0),
+            bref: hut,
+            cursor: CursorId::Player,
+        });
+        journal.record(LevelOp::Start);
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(1, 1),
+            bref: hut,
+            cursor: CursorId::Player,
+        });
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(2, 2),
+            bref: tower,
+            cursor: CursorId::Player,
+        });
+
+        // Only the placements since the restart are on the grid, and the delivery is applied
+        let state = journal.replay(&level_desc);
+        assert_eq!(
+            state.placements,
+            vec![
+                (IVec2::new(1, 1), hut, CursorId::Player),
+                (IVec2::new(2, 2), tower, CursorId::Player)
+            ]
+        );
+        assert_eq!(journal.placements(), state.placements);
+        assert_eq!(state.inventory.placed_count(), 2);
+        let counts: Vec<_> = state
+            .inventory
+            .slots()
+            .iter()
+            .map(|slot| (slot.bref(), slot.count()))
+            .collect();
+        assert_eq!(counts, vec![(hut, 1), (tower, 0)]);
+
+        // A removal takes the buildable back into the inventory
+        journal.record(LevelOp::Remove {
+            pos: IVec2::new(1, 1),
+            bref: hut,
+        });
+        let state = journal.replay(&level_desc);
+        assert_eq!(
+            state.placements,
+            vec![(IVec2::new(2, 2), tower, CursorId::Player)]
+        );
+        assert_eq!(journal.placements(), state.placements);
+        assert_eq!(state.inventory.slots()[0].count(), 2);
+
+        // Round trip through the archive
+        let ops = journal.to_archive(&buildables).unwrap();
+        let restored = LevelJournal::from_archive(&ops, &buildables).unwrap();
+        assert_eq!(restored.ops(), journal.ops());
+    }
+
+    #[test]
+    fn moves_and_breaks() {
+        let mut buildables = BuildableRegistry::new();
+        buildables.register("hut", Buildable::without_assets("hut", 1.0));
+        let archive: LevelDescArchive = serde_json::from_str(
+            r#"{ "name": "Journal", "grid_size": [3, 3], "balance_factor": 1.0,
+                 "victory_margin": 0.2, "inventory": { "hut": 3 } }"#,
+        )
+        .unwrap();
+        let level_desc = archive.to_desc(&buildables, &HashMap::new());
+        let hut = buildables.id("hut").unwrap();
+
+        // A buildable slides away from the cell it was placed in, then another one is placed in
+        // that cell
+        let mut journal = LevelJournal::new();
+        journal.record(LevelOp::Start);
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(0, 0),
+            bref: hut,
+            cursor: CursorId::Player,
+        });
+        journal.record(LevelOp::Move {
+            from: IVec2::new(0, 0),
+            to: IVec2::new(1, 0),
+        });
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(0, 0),
+            bref: hut,
+            cursor: CursorId::Player,
+        });
+        let state = journal.replay(&level_desc);
+        assert_eq!(
+            state.placements,
+            vec![
+                (IVec2::new(1, 0), hut, CursorId::Player),
+                (IVec2::new(0, 0), hut, CursorId::Player)
+            ]
+        );
+        assert_eq!(journal.placements(), state.placements);
+
+        // Removing from the cell the buildable slid away from only takes back the other one
+        journal.record(LevelOp::Remove {
+            pos: IVec2::new(0, 0),
+            bref: hut,
+        });
+        journal.record(LevelOp::Remove {
+            pos: IVec2::new(0, 0),
+            bref: hut,
+        });
+        let state = journal.replay(&level_desc);
+        assert_eq!(
+            state.placements,
+            vec![(IVec2::new(1, 0), hut, CursorId::Player)]
+        );
+        assert_eq!(state.inventory.slots()[0].count(), 2);
+
+        // A broken tile loses its buildable and stays a hole until the next start
+        journal.record(LevelOp::Break {
+            pos: IVec2::new(1, 0),
+        });
+        let state = journal.replay(&level_desc);
+        assert!(state.placements.is_empty());
+        assert_eq!(journal.placements(), state.placements);
+        assert_eq!(state.holes, vec![IVec2::new(1, 0)]);
+        assert_eq!(state.inventory.slots()[0].count(), 2);
+
+        // The quick saves restore the moves and the holes
+        let ops = journal.to_archive(&buildables).unwrap();
+        let restored = LevelJournal::from_archive(&ops, &buildables).unwrap();
+        assert_eq!(restored.ops(), journal.ops());
+        assert_eq!(restored.replay(&level_desc).holes, state.holes);
+
+        journal.record(LevelOp::Start);
+        assert!(journal.replay(&level_desc).holes.is_empty());
+    }
+
+    #[test]
+    fn coop() {
+        let mut buildables = BuildableRegistry::new();
+        buildables.register("hut", Buildable::without_assets("hut", 1.0));
+        let archive: LevelDescArchive = serde_json::from_str(
+            r#"{ "name": "Journal", "grid_size": [3, 3], "balance_factor": 1.0,
+                 "victory_margin": 0.2, "inventory": { "hut": 4 } }"#,
+        )
+        .unwrap();
+        let level_desc = archive.to_desc(&buildables, &HashMap::new());
+        let hut = buildables.id("hut").unwrap();
+
+        // Each player places from their half of the inventory
+        let mut journal = LevelJournal::new();
+        journal.record(LevelOp::Start);
+        journal.record(LevelOp::Split);
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(0, 0),
+            bref: hut,
+            cursor: CursorId::Partner,
+        });
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(1, 0),
+            bref: hut,
+            cursor: CursorId::Partner,
+        });
+        journal.record(LevelOp::Place {
+            pos: IVec2::new(2, 0),
+            bref: hut,
+            cursor: CursorId::Player,
+        });
+        let state = journal.replay(&level_desc);
+        assert_eq!(state.placements.len(), 3);
+        assert_eq!(journal.placements(), state.placements);
+        assert_eq!(state.inventory.slots()[0].count(), 1);
+        assert!(state.partner_inventory.is_empty());
+        assert_eq!(state.inventory.placed_count(), 3);
+
+        // The quick saves restore the placements of the partner
+        let ops = journal.to_archive(&buildables).unwrap();
+        let restored = LevelJournal::from_archive(&ops, &buildables).unwrap();
+        assert_eq!(restored.ops(), journal.ops());
+
+        // A restart gives the partner their half again
+        journal.record(LevelOp::Start);
+        journal.record(LevelOp::Split);
+        let state = journal.replay(&level_desc);
+        assert!(state.placements.is_empty());
+        assert_eq!(state.inventory.slots()[0].count(), 2);
+        assert_eq!(state.partner_inventory.slots()[0].count(), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Buildable placed in a single cell of a [`GridLayout`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutPlacement {
+    /// Grid coordinates of the cell.
+    pub pos: IVec2,
+    /// Name of the buildable in the game data.
+    pub buildable: String,
+    /// Total weight of the cell.
+    pub weight: f32,
+}
+
+/// Serializable content of a [`Grid`], referencing buildables by name. Used to save and restore
+/// a level in progress, and to record layouts for testing the balance math.
+///
+/// [`Grid`]: crate::Grid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridLayout {
+    /// Grid size, in cells.
+    pub size: IVec2,
+    /// Placed buildables, in row order.
+    pub placements: Vec<LayoutPlacement>,
+}
+
+impl GridLayout {
+    pub fn from_ron(ron_content: &str) -> Result<GridLayout, ron::Error> {
+        ron::from_str(ron_content)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inventory::Buildable,
+        serialize::{BuildableRegistry, TerrainDescArchive},
+        tilt::{Pivot, TiltModel},
+        Grid,
+    };
+    use std::path::Path;
+
+    /// Tolerance of the comparison of computed values with the expected ones.
+    const EPSILON: f32 = 1e-5;
+
+    #[derive(Debug, Deserialize)]
+    struct GoldenExpected {
+        cog_offset: Vec2,
+        tilt: f32,
+        victory: bool,
+    }
+
+    /// Layout recorded in `tests/golden/` with the expected outcome of the balance math.
+    #[derive(Debug, Deserialize)]
+    struct GoldenCase {
+        layout: GridLayout,
+        balance_factor: f32,
+        #[serde(default)]
+        pivot: Pivot,
+        #[serde(default)]
+        tilt_model: TiltModel,
+        #[serde(default)]
+        terrain: Option<TerrainDescArchive>,
+        victory_margin: f32,
+        expected: GoldenExpected,
+    }
+
+    /// Registry with the buildables of the layout, without any asset.
+    fn registry(layout: &GridLayout) -> BuildableRegistry {
+        let mut buildables = BuildableRegistry::new();
+        for placement in &layout.placements {
+            buildables.register(
+                &placement.buildable,
+                Buildable::without_assets(&placement.buildable, placement.weight),
+            );
+        }
+        buildables
+    }
+
+    #[test]
+    fn round_trip() {
+        let layout = GridLayout {
+            size: IVec2::new(3, 3),
+            placements: vec![LayoutPlacement {
+                pos: IVec2::new(-1, 0),
+                buildable: "hut".to_owned(),
+                weight: 1.0,
+            }],
+        };
+        let ron_content = layout.to_ron().unwrap();
+        assert_eq!(GridLayout::from_ron(&ron_content).unwrap(), layout);
+
+        let buildables = registry(&layout);
+        let grid = Grid::from_layout(&layout, &buildables).unwrap();
+        assert_eq!(grid.to_layout(&buildables), layout);
+    }
+
+    #[test]
+    fn golden() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let mut paths: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "No golden layout in {:?}", dir);
+
+        let mut failures = vec![];
+        for path in &paths {
+            let case: GoldenCase = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            let buildables = registry(&case.layout);
+            let mut grid = Grid::from_layout(&case.layout, &buildables).unwrap();
+            if let Some(terrain) = case.terrain.and_then(|t| t.to_desc(case.layout.size)) {
+                grid.set_terrain(terrain.heights, terrain.lever_arm);
+            }
+            let cog_offset = grid.calc_cog_offset(case.balance_factor);
+            let tilt = grid.calc_tilt(case.balance_factor, &case.pivot, &case.tilt_model);
+            let victory = grid.is_victory(case.balance_factor, &case.pivot, case.victory_margin);
+            if (cog_offset - case.expected.cog_offset).length() > EPSILON
+                || (tilt - case.expected.tilt).abs() > EPSILON
+                || victory != case.expected.victory
+            {
+                failures.push(format!(
+                    "{}: got cog_offset={:?} tilt={} victory={}, expected {:?}",
+                    path.file_name().unwrap().to_string_lossy(),
+                    cog_offset,
+                    tilt,
+                    victory,
+                    case.expected
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
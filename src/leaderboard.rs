@@ -0,0 +1,311 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task},
+};
+use futures_lite::future;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    boot::UiResources,
+    config::Config,
+    scores::{ScoreEvent, SignedScore},
+    AppState, ResetPlateEvent,
+};
+
+/// Number of entries fetched and displayed for each level.
+const TOP_COUNT: usize = 10;
+
+/// Timeout of a single HTTP request to the leaderboard endpoint.
+const REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Single entry of a leaderboard.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LeaderboardEntry {
+    /// Player name.
+    pub player: String,
+    /// Normalized level score.
+    pub score: u32,
+}
+
+/// Body of a score submission.
+#[derive(Debug, Serialize)]
+struct Submission<'a> {
+    player: &'a str,
+    #[serde(flatten)]
+    score: &'a SignedScore,
+}
+
+/// Result of a leaderboard request for a level, produced by an async task.
+type RequestResult = Result<Vec<LeaderboardEntry>, String>;
+
+/// Submit a batch of scores, then fetch the top scores of the given level.
+///
+/// Endpoints, relative to the configured base URL:
+/// - `POST /scores` with a JSON [`Submission`] body;
+/// - `GET /scores/top?level=<name>&limit=<count>` returning a JSON array of [`LeaderboardEntry`].
+fn submit_and_fetch(url: &str, player: &str, scores: &[SignedScore], level: &str) -> RequestResult {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build();
+    for score in scores {
+        let body = serde_json::to_value(Submission { player, score }).map_err(|e| e.to_string())?;
+        agent
+            .post(&format!("{}/scores", url))
+            .send_json(body)
+            .map_err(|e| e.to_string())?;
+    }
+    agent
+        .get(&format!("{}/scores/top", url))
+        .query("level", level)
+        .query("limit", &TOP_COUNT.to_string())
+        .call()
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| e.to_string())
+}
+
+/// Resource holding the state of the online leaderboard.
+pub struct Leaderboard {
+    /// In-flight request, with the name of the level it fetches.
+    task: Option<(String, Task<RequestResult>)>,
+    /// Signed scores not submitted yet, retried on the next request.
+    pending: Vec<SignedScore>,
+    /// Scores being submitted by the in-flight request.
+    in_flight: Vec<SignedScore>,
+    /// Last fetched top scores, per level name.
+    top: HashMap<String, Vec<LeaderboardEntry>>,
+    /// Best local scores of this session, per level name, used when offline.
+    local: HashMap<String, Vec<u32>>,
+    /// Did the last request fail?
+    offline: bool,
+}
+
+impl Leaderboard {
+    pub fn new() -> Self {
+        Leaderboard {
+            task: None,
+            pending: vec![],
+            in_flight: vec![],
+            top: HashMap::new(),
+            local: HashMap::new(),
+            offline: false,
+        }
+    }
+
+    /// Entries to display for a level: the online top scores if available, or the local best
+    /// scores otherwise.
+    pub fn entries(&self, level: &str, player: &str) -> Vec<LeaderboardEntry> {
+        match self.top.get(level) {
+            Some(top) if !self.offline => top.clone(),
+            _ => self
+                .local
+                .get(level)
+                .map(|scores| {
+                    scores
+                        .iter()
+                        .map(|&score| LeaderboardEntry {
+                            player: player.to_owned(),
+                            score,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    fn record_local(&mut self, level: &str, score: u32) {
+        let scores = self.local.entry(level.to_owned()).or_default();
+        scores.push(score);
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+        scores.truncate(TOP_COUNT);
+    }
+}
+
+/// Event sent when the leaderboard of a level is available for display.
+#[derive(Debug)]
+pub struct LeaderboardUpdatedEvent(pub String);
+
+/// Marker for the leaderboard panel displayed on the victory screen.
+#[derive(Component)]
+struct LeaderboardPanel;
+
+/// Start a request submitting the scores of completed levels.
+fn submit_scores(
+    mut ev_score: EventReader<ScoreEvent>,
+    mut ev_updated: EventWriter<LeaderboardUpdatedEvent>,
+    config: Res<Config>,
+    pool: Res<IoTaskPool>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    for ev in ev_score.iter() {
+        let score = &ev.0;
+        if score.cheated {
+            debug!(
+                "Leaderboard: not submitting cheated score for '{}'",
+                score.level
+            );
+            continue;
+        }
+        leaderboard.record_local(&score.level, score.score);
+        leaderboard.pending.push(SignedScore::sign(score.clone()));
+
+        let url = match &config.leaderboard.url {
+            Some(url) => url.trim_end_matches('/').to_owned(),
+            None => {
+                // No endpoint configured; display local scores only
+                leaderboard.offline = true;
+                ev_updated.send(LeaderboardUpdatedEvent(score.level.clone()));
+                continue;
+            }
+        };
+        if leaderboard.task.is_some() {
+            // Submitted with the next request
+            continue;
+        }
+
+        let scores = std::mem::take(&mut leaderboard.pending);
+        leaderboard.in_flight = scores.clone();
+        let player = config.leaderboard.player.clone();
+        let level = score.level.clone();
+        trace!(
+            "Leaderboard: submitting {} score(s) to {}",
+            scores.len(),
+            url
+        );
+        let task = pool.spawn(async move { submit_and_fetch(&url, &player, &scores, &level) });
+        leaderboard.task = Some((score.level.clone(), task));
+    }
+}
+
+/// Poll the in-flight leaderboard request, if any.
+fn poll_leaderboard(
+    mut ev_updated: EventWriter<LeaderboardUpdatedEvent>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let result = match &mut leaderboard.task {
+        Some((_, task)) => match future::block_on(future::poll_once(task)) {
+            Some(result) => result,
+            None => return,
+        },
+        None => return,
+    };
+    let (level, _) = leaderboard.task.take().unwrap();
+    match result {
+        Ok(top) => {
+            debug!("Leaderboard: fetched {} entries for '{}'", top.len(), level);
+            leaderboard.in_flight.clear();
+            leaderboard.offline = false;
+            leaderboard.top.insert(level.clone(), top);
+        }
+        Err(err) => {
+            warn!("Leaderboard unavailable, using local scores: {}", err);
+            let mut in_flight = std::mem::take(&mut leaderboard.in_flight);
+            in_flight.append(&mut leaderboard.pending);
+            leaderboard.pending = in_flight;
+            leaderboard.offline = true;
+        }
+    }
+    ev_updated.send(LeaderboardUpdatedEvent(level));
+}
+
+/// Display the leaderboard of a level on the victory screen.
+fn spawn_leaderboard_panel(
+    mut commands: Commands,
+    mut ev_updated: EventReader<LeaderboardUpdatedEvent>,
+    config: Res<Config>,
+    leaderboard: Res<Leaderboard>,
+    ui_resources: Res<UiResources>,
+    query: Query<Entity, With<LeaderboardPanel>>,
+) {
+    let level = match ev_updated.iter().last() {
+        Some(ev) => &ev.0,
+        None => return,
+    };
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let title = if leaderboard.is_offline() {
+        format!("{} - Best scores (offline)", level)
+    } else {
+        format!("{} - Top {}", level, TOP_COUNT)
+    };
+    let lines = leaderboard
+        .entries(level, &config.leaderboard.player)
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| format!("{}. {} - {}", index + 1, entry.player, entry.score))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let style = TextStyle {
+        font: ui_resources.text_font(),
+        font_size: 20.0,
+        color: Color::rgb_u8(111, 188, 165),
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(100.0),
+                    right: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: format!("{}\n", title),
+                        style: style.clone(),
+                    },
+                    TextSection {
+                        value: lines,
+                        style,
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Name::new("LeaderboardPanel"))
+        .insert(LeaderboardPanel);
+}
+
+/// Remove the leaderboard panel when the next level starts or the game ends.
+fn despawn_leaderboard_panel(
+    mut commands: Commands,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    state: Res<State<AppState>>,
+    query: Query<Entity, With<LeaderboardPanel>>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    if reset || *state.current() != AppState::InGame {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Plugin to submit scores to an online leaderboard and display the top scores of each level.
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Leaderboard::new())
+            .add_event::<LeaderboardUpdatedEvent>()
+            .add_system(submit_scores)
+            .add_system(poll_leaderboard.label("poll_leaderboard"))
+            .add_system(
+                spawn_leaderboard_panel
+                    .label("spawn_leaderboard_panel")
+                    .after("poll_leaderboard"),
+            )
+            .add_system(despawn_leaderboard_panel.after("spawn_leaderboard_panel"));
+    }
+}
@@ -1,9 +1,16 @@
 use bevy::{app::CoreStage, asset::AssetStage, prelude::*};
+use std::sync::Arc;
 
 use crate::{
-    inventory::{Inventory, Slot},
-    serialize::{Buildables, Levels},
-    AppState, Cursor, Grid, RegenerateInventoryUiEvent, ResetPlateEvent,
+    anim::PlayAnimation,
+    daily::DailyChallenge,
+    game::GameEvent,
+    inventory::Inventory,
+    level_code::ImportedLevel,
+    remix::BonusStages,
+    serialize::{BuildableRegistry, LevelDesc, Levels},
+    units::{WeightUnit, TONS},
+    AppState, Cursor, Error, Grid, RegenerateInventoryUiEvent, ResetPlateEvent,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,30 +18,69 @@ pub enum LoadLevel {
     Next,
     ByName(String),
     ByIndex(usize),
+    /// The daily challenge drawn by the [`DailyChallenge`].
+    Daily,
+    /// The level imported from a level code into the [`ImportedLevel`].
+    Imported,
 }
 
 /// Event to load a level.
 #[derive(Debug)]
 pub struct LoadLevelEvent(pub LoadLevel);
 
+/// Event emitted when a system fails to access the data of the current level, for example
+/// because the game data changed under it.
+#[derive(Debug)]
+pub struct LevelErrorEvent(pub Error);
+
+/// Reason a level is being unloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnloadReason {
+    /// The level restarts from scratch.
+    Restart,
+    /// Another level is loaded in place of the current one.
+    ChangeLevel,
+    /// The game returns to the menu.
+    Quit,
+}
+
+/// Event sent when the entities of the level are marked for removal with [`PendingDespawn`].
+/// The entities stay alive until the end of the next frame, so that systems observing this event
+/// can still access them safely.
+#[derive(Debug)]
+pub struct LevelUnloading(pub UnloadReason);
+
+/// Marker for an entity to despawn, with all its descendants, at the end of the frame following
+/// the one it was marked. Use [`mark_for_despawn()`] instead of despawning level entities
+/// directly.
+#[derive(Debug, Component)]
+pub struct PendingDespawn;
+
+/// Mark an entity and its descendants for a deferred despawn.
+pub fn mark_for_despawn(commands: &mut Commands, entity: Entity) {
+    commands.entity(entity).insert(PendingDespawn);
+}
+
 /// Marker for the Text component displaying the level name.
 #[derive(Debug, Component)]
 pub struct LevelNameText;
 
 /// Resource representing the current level being played.
-#[derive(Debug)]
+#[derive(Debug, Reflect)]
 pub struct Level {
     /// Index into [`Levels`].
     index: usize,
-    /// Display name.
-    name: String,
+    /// Description of the active level, shared with [`Levels`]. This is `None` until
+    /// a level is loaded.
+    #[reflect(ignore)]
+    desc: Option<Arc<LevelDesc>>,
 }
 
 impl Level {
     pub fn new() -> Self {
         Level {
             index: 0,
-            name: String::new(),
+            desc: None,
         }
     }
 
@@ -42,8 +88,25 @@ impl Level {
         self.index
     }
 
+    /// Display name of the level, or an empty string if no level is loaded.
     pub fn name(&self) -> &str {
-        &self.name
+        self.desc.as_ref().map_or("", |desc| &desc.name[..])
+    }
+
+    /// Description of the active level, if any level is loaded.
+    pub fn desc(&self) -> Option<&LevelDesc> {
+        self.desc.as_deref()
+    }
+
+    /// Unit the weights are displayed in, tons if no level is loaded.
+    pub fn weight_unit(&self) -> &WeightUnit {
+        self.desc.as_ref().map_or(&TONS, |desc| desc.weight_unit())
+    }
+
+    /// Replace the description of the active level, for example after the game data was
+    /// reloaded, without otherwise changing the level being played.
+    pub fn set_desc(&mut self, desc: Arc<LevelDesc>) {
+        self.desc = Some(desc);
     }
 }
 
@@ -51,28 +114,41 @@ impl Level {
 /// The system runs toward the beginning of the frame, before assets are loaded,
 /// so it can enqueue some asset loading.
 fn change_level_system(
+    mut commands: Commands,
     mut level: ResMut<Level>,
     mut inventory: ResMut<Inventory>,
+    mut bonus_stages: ResMut<BonusStages>,
+    daily: Res<DailyChallenge>,
+    imported: Res<ImportedLevel>,
     levels: Res<Levels>,
-    buildables: Res<Buildables>,
+    buildables: Res<BuildableRegistry>,
     grid: Res<Grid>,
     mut ev_load_level: EventReader<LoadLevelEvent>,
-    mut query_level_name_text: Query<&mut Text, With<LevelNameText>>,
+    mut query_level_name_text: Query<(Entity, &mut Text), With<LevelNameText>>,
     mut query_cursor: Query<(&Cursor, &mut Visibility, &mut Transform)>,
     mut state: ResMut<State<AppState>>,
     mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
     mut ev_reset_plate: EventWriter<ResetPlateEvent>,
+    mut ev_unloading: EventWriter<LevelUnloading>,
+    mut ev_game: EventWriter<GameEvent>,
 ) {
     // Consume all events, and only act on last one, ignoring others
     if let Some(load_level_event) = ev_load_level.iter().last() {
         // Find level to load
+        let bonus_desc;
+        let daily_desc;
+        let imported_desc;
         let (level_index, level_desc) = match &load_level_event.0 {
             LoadLevel::Next => {
                 info!("Load level: Next");
                 let next_level_index = level.index() + 1;
-                let levels = levels.levels();
-                if next_level_index < levels.len() {
-                    let level_desc = &levels[next_level_index];
+                // Play the bonus stages queued after a world before moving on to the next one,
+                // keeping the index of the last level of the world
+                if let Some(desc) = bonus_stages.next() {
+                    info!("=> Bonus stage: '{}'", desc.name);
+                    bonus_desc = desc;
+                    (level.index(), &bonus_desc)
+                } else if let Some(level_desc) = levels.get(next_level_index) {
                     info!("=> Next level: #{} '{}'", next_level_index, level_desc.name);
                     (next_level_index, level_desc)
                 } else {
@@ -83,13 +159,9 @@ fn change_level_system(
             }
             LoadLevel::ByName(level_name) => {
                 info!("Load level: {}", level_name);
+                bonus_stages.clear();
                 // Find by name
-                if let Some((level_index, level_desc)) = levels
-                    .levels()
-                    .iter()
-                    .enumerate()
-                    .find(|(_, l)| l.name == *level_name)
-                {
+                if let Some((level_index, level_desc)) = levels.by_name(level_name) {
                     info!("=> Level '{}': #{}", level_name, level_index);
                     (level_index, level_desc)
                 } else {
@@ -102,10 +174,10 @@ fn change_level_system(
             }
             LoadLevel::ByIndex(level_index) => {
                 info!("Load level: #{}", level_index);
+                bonus_stages.clear();
                 // Find by index
                 let level_index = *level_index;
-                if level_index < levels.levels().len() {
-                    let level_desc = &levels.levels()[level_index];
+                if let Some(level_desc) = levels.get(level_index) {
                     info!("=> Level #{}: '{}'", level_index, level_desc.name);
                     (level_index, level_desc)
                 } else {
@@ -116,29 +188,57 @@ fn change_level_system(
                     return;
                 }
             }
+            LoadLevel::Daily => {
+                info!("Load level: Daily");
+                bonus_stages.clear();
+                // Keep the index of the level the challenge is a remix of
+                if let Some((level_index, level_desc)) = daily.level() {
+                    info!("=> Daily challenge: '{}'", level_desc.name);
+                    daily_desc = level_desc;
+                    (level_index, &daily_desc)
+                } else {
+                    error!("Failed to handle LoadLevelEvent: No daily challenge drawn.");
+                    return;
+                }
+            }
+            LoadLevel::Imported => {
+                info!("Load level: Imported");
+                bonus_stages.clear();
+                // Keep the index of the current level, the imported one isn't part of the game
+                if let Some(level_desc) = imported.level() {
+                    info!("=> Imported level: '{}'", level_desc.name);
+                    imported_desc = level_desc;
+                    (level.index(), &imported_desc)
+                } else {
+                    error!("Failed to handle LoadLevelEvent: No level imported.");
+                    return;
+                }
+            }
         };
 
+        // Unload previous level, if any
+        if level.desc().is_some() {
+            ev_unloading.send(LevelUnloading(UnloadReason::ChangeLevel));
+        }
+
         // Load level
         *level = Level {
             index: level_index,
-            name: level_desc.name.clone(),
+            desc: Some(level_desc.clone()),
         };
-        inventory.set_slots(
-            level_desc
-                .inventory
-                .iter()
-                .map(|(bref, &count)| Slot::new(bref.clone(), count)),
-        );
-
-        // Update level name in UI
-        let mut text = query_level_name_text.single_mut();
+        inventory.reset_from_level(level_desc);
+
+        // Update level name in UI, sliding in from the side
+        let (entity, mut text) = query_level_name_text.single_mut();
         text.sections[0].value = level_desc.name.clone();
+        commands
+            .entity(entity)
+            .insert(PlayAnimation::new("level_name_slide_in"));
 
         // Show cursor
         let (cursor, mut visibility, mut transform) = query_cursor.single_mut();
         visibility.is_visible = true;
-        let cursor_fpos = grid.fpos(&cursor.pos);
-        *transform = Transform::from_translation(Vec3::new(cursor_fpos.x, 0.1, -cursor_fpos.y))
+        *transform = Transform::from_translation(grid.translation(&cursor.pos, 0.1))
             * Transform::from_scale(Vec3::new(1.0, 0.3, 1.0));
 
         // Regenerate inventory UI from new level data
@@ -146,6 +246,49 @@ fn change_level_system(
 
         // Reset plate
         ev_reset_plate.send(ResetPlateEvent);
+        ev_game.send(GameEvent::LevelLoaded { level_index });
+    }
+}
+
+/// System reacting to the [`LevelErrorEvent`] event to recover from an invalid current level,
+/// by restarting from the first level if any, or ending the game otherwise.
+fn level_error_system(
+    levels: Res<Levels>,
+    mut ev_level_error: EventReader<LevelErrorEvent>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+    mut state: ResMut<State<AppState>>,
+) {
+    // Consume all events, and only act on last one, ignoring others
+    if let Some(ev) = ev_level_error.iter().last() {
+        error!("Level error: {:?}", ev.0);
+        if levels.is_empty() {
+            error!("No level available, ending game.");
+            state.set(AppState::TheEnd).unwrap();
+        } else {
+            warn!("Restarting from first level.");
+            ev_load_level.send(LoadLevelEvent(LoadLevel::ByIndex(0)));
+        }
+    }
+}
+
+/// Despawn the entities marked with [`PendingDespawn`] during a previous frame. Runs in the
+/// last stage of the frame, once all systems are done with the entities.
+fn despawn_pending(
+    mut commands: Commands,
+    query: Query<(Entity, Option<&Parent>, ChangeTrackers<PendingDespawn>)>,
+    pending_query: Query<(), With<PendingDespawn>>,
+) {
+    for (entity, parent, tracker) in query.iter() {
+        // Keep the entities marked this frame for one more frame
+        if tracker.is_added() {
+            continue;
+        }
+        // Descendants are despawned with their ancestor
+        if parent.is_some_and(|parent| pending_query.get(parent.0).is_ok()) {
+            continue;
+        }
+        trace!("Despawn pending entity {:?}", entity);
+        commands.entity(entity).despawn_recursive();
     }
 }
 
@@ -162,7 +305,12 @@ impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         // Add Level resource and event
         app.insert_resource(Level::new())
-            .add_event::<LoadLevelEvent>();
+            .register_type::<Level>()
+            .add_event::<LoadLevelEvent>()
+            .add_event::<LevelErrorEvent>()
+            .add_event::<LevelUnloading>()
+            .add_system_to_stage(CoreStage::Last, despawn_pending)
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(level_error_system));
 
         // Insert stage after last built-in stage and run load_level_system() there, at the very end
         // of the frame, to ensure that there's no pending entity or component being created/destroyed.
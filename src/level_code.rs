@@ -0,0 +1,543 @@
+use bevy::{input::InputSystem, prelude::*, window::ReceivedCharacter};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use crate::{
+    boot::UiResources,
+    controls_screen::ControlsScreen,
+    daily::DailyMenu,
+    encyclopedia::EncyclopediaMenu,
+    game::GameMode,
+    mainmenu::MenuOverlay,
+    playtime::StatsMenu,
+    serialize::{BuildableRegistry, LevelDesc, LevelDescArchive, Levels},
+    validate::{validate_shared_level, Severity},
+    wardrobe::WardrobeMenu,
+    AppState, LoadLevel, LoadLevelEvent,
+};
+
+/// Prefix of the level codes, followed by the version of their format.
+const CODE_PREFIX: &str = "LIBRA";
+
+/// Version of the format of the level codes written by this build. Codes of older versions stay
+/// readable, codes of newer versions are rejected.
+pub const LEVEL_CODE_VERSION: u32 = 1;
+
+/// Maximum length of a level code, in bytes, so that it can be pasted in a chat message. Codes
+/// are ASCII, so this is also their length in characters.
+const MAX_CODE_LEN: usize = 4000;
+
+/// Maximum size of the level decompressed from a code, in bytes, so that a small code can't
+/// inflate into a huge level.
+const MAX_LEVEL_LEN: usize = 64 * 1024;
+
+/// Maximum width and height of the grid of a level decoded from a code, in cells. The levels of
+/// the game are far smaller.
+const MAX_GRID_SIZE: i32 = 32;
+
+/// Maximum number of cells of the grid of a level decoded from a code, so that an untrusted level
+/// can't allocate a huge grid or stall the solver checking it.
+const MAX_GRID_CELLS: i32 = 256;
+
+/// Compression level of the levels in the codes, the best one since levels are small.
+const COMPRESSION_LEVEL: u8 = 9;
+
+/// Key to open the dialog importing a level code in the main menu.
+const IMPORT_KEY: KeyCode = KeyCode::I;
+
+/// Error reading or writing a level code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelCodeError {
+    /// The level isn't a valid level description.
+    InvalidLevel(String),
+    /// The code is longer than the maximum length, or the level decompresses into too much data.
+    TooLong,
+    /// The text isn't a level code, or it was truncated.
+    InvalidCode,
+    /// The code has the given version, newer than supported by this build.
+    UnsupportedVersion(u32),
+    /// The level has a grid of the given size, larger than allowed for shared levels.
+    GridTooLarge(IVec2),
+}
+
+impl fmt::Display for LevelCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelCodeError::InvalidLevel(err) => write!(f, "invalid level: {}", err),
+            LevelCodeError::TooLong => write!(f, "level too large for a code"),
+            LevelCodeError::InvalidCode => write!(f, "not a level code, or an incomplete one"),
+            LevelCodeError::UnsupportedVersion(version) => write!(
+                f,
+                "level code version {} is newer than this game, update it to play",
+                version
+            ),
+            LevelCodeError::GridTooLarge(size) => write!(
+                f,
+                "grid of {}x{} cells too large, shared levels have at most {} cells",
+                size.x, size.y, MAX_GRID_CELLS
+            ),
+        }
+    }
+}
+
+/// Encode a level, in the format of the levels of `levels.json`, into a level code to share it as
+/// text. The JSON is minified and compressed, then written in URL-safe base64 after a versioned
+/// prefix, like `LIBRA1-...`.
+pub fn encode_level(json_content: &str) -> Result<String, LevelCodeError> {
+    let value: serde_json::Value = serde_json::from_str(json_content)
+        .map_err(|err| LevelCodeError::InvalidLevel(err.to_string()))?;
+    let minified = value.to_string();
+    if minified.len() > MAX_LEVEL_LEN {
+        return Err(LevelCodeError::TooLong);
+    }
+    serde_json::from_str::<LevelDescArchive>(&minified)
+        .map_err(|err| LevelCodeError::InvalidLevel(err.to_string()))?;
+    let compressed = miniz_oxide::deflate::compress_to_vec(minified.as_bytes(), COMPRESSION_LEVEL);
+    let code = format!(
+        "{}{}-{}",
+        CODE_PREFIX,
+        LEVEL_CODE_VERSION,
+        base64::encode_config(compressed, base64::URL_SAFE_NO_PAD)
+    );
+    if code.len() > MAX_CODE_LEN {
+        return Err(LevelCodeError::TooLong);
+    }
+    Ok(code)
+}
+
+/// Decode a level code written by [`encode_level()`]. Whitespace around the code, like from a
+/// chat message, is ignored. Levels with a grid larger than allowed for shared levels are
+/// rejected before anything is built from them.
+pub fn decode_level(code: &str) -> Result<LevelDescArchive, LevelCodeError> {
+    let code = code.trim();
+    if code.len() > MAX_CODE_LEN {
+        return Err(LevelCodeError::TooLong);
+    }
+    let (version, payload) = code
+        .strip_prefix(CODE_PREFIX)
+        .and_then(|code| code.split_once('-'))
+        .ok_or(LevelCodeError::InvalidCode)?;
+    let version: u32 = version.parse().map_err(|_| LevelCodeError::InvalidCode)?;
+    if version > LEVEL_CODE_VERSION {
+        return Err(LevelCodeError::UnsupportedVersion(version));
+    }
+    let compressed = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| LevelCodeError::InvalidCode)?;
+    let json = miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, MAX_LEVEL_LEN)
+        .map_err(|err| match err {
+            miniz_oxide::inflate::TINFLStatus::HasMoreOutput => LevelCodeError::TooLong,
+            _ => LevelCodeError::InvalidCode,
+        })?;
+    let level: LevelDescArchive = serde_json::from_slice(&json)
+        .map_err(|err| LevelCodeError::InvalidLevel(err.to_string()))?;
+    let size = level.grid_size;
+    if size.x > MAX_GRID_SIZE
+        || size.y > MAX_GRID_SIZE
+        || size.x.max(0) * size.y.max(0) > MAX_GRID_CELLS
+    {
+        return Err(LevelCodeError::GridTooLarge(size));
+    }
+    Ok(level)
+}
+
+/// Decode a level code and validate the level against the buildables and the worlds of the game,
+/// into a level ready to play. Returns a message for the player if the level can't be played.
+fn import_level(
+    code: &str,
+    levels: &Levels,
+    buildables: &BuildableRegistry,
+) -> Result<LevelDesc, String> {
+    let level = decode_level(code).map_err(|err| err.to_string())?;
+    let worlds: HashMap<_, _> = levels
+        .levels()
+        .iter()
+        .filter_map(|desc| desc.world.clone())
+        .map(|world| (world.name.clone(), world))
+        .collect();
+    let world_names: HashSet<_> = worlds.keys().map(|name| &name[..]).collect();
+    let report = validate_shared_level(&level, buildables, &world_names);
+    if let Some(finding) = report
+        .findings
+        .iter()
+        .find(|finding| finding.severity == Severity::Error)
+    {
+        return Err(format!("Invalid level: {}", finding.message));
+    }
+    Ok(level.to_desc(buildables, &worlds))
+}
+
+/// Resource holding the level imported from a level code, if any.
+#[derive(Debug, Default)]
+pub struct ImportedLevel {
+    /// Level imported to play.
+    level: Option<Arc<LevelDesc>>,
+    /// Was the level requested to load already?
+    requested: bool,
+}
+
+impl ImportedLevel {
+    /// Level imported to play. Called by the level loading for the [`LoadLevel::Imported`]
+    /// requests.
+    pub fn level(&self) -> Option<Arc<LevelDesc>> {
+        self.level.clone()
+    }
+}
+
+/// Resource holding the state of the dialog importing a level code.
+#[derive(Debug, Default)]
+pub struct ImportDialog {
+    /// Is the dialog open?
+    open: bool,
+    /// Code typed or pasted so far.
+    code: String,
+    /// Result of the last import attempt, if failed.
+    message: String,
+}
+
+impl ImportDialog {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+/// Marker for the UI nodes of the import dialog, kept visible while it's open.
+#[derive(Component)]
+struct ImportDialogUi;
+
+/// Text of the clipboard, if any.
+#[cfg(not(target_arch = "wasm32"))]
+fn paste() -> Option<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| warn!("Failed to read the clipboard: {}", err))
+        .ok()
+}
+
+/// Ask the player to paste the code in a browser prompt, since reading the browser clipboard is
+/// asynchronous and needs a permission.
+#[cfg(target_arch = "wasm32")]
+fn paste() -> Option<String> {
+    web_sys::window()?
+        .prompt_with_message("Paste a level code")
+        .ok()
+        .flatten()
+}
+
+/// Open the import dialog, type or paste a code in it, and play the level once imported. Runs
+/// right after the input is updated, to hide the keys typed from the rest of the menu.
+fn import_dialog_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut ev_received_character: EventReader<ReceivedCharacter>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    encyclopedia_menu: Res<EncyclopediaMenu>,
+    daily_menu: Res<DailyMenu>,
+    stats_menu: Res<StatsMenu>,
+    controls_screen: Res<ControlsScreen>,
+    mut dialog: ResMut<ImportDialog>,
+    mut imported: ResMut<ImportedLevel>,
+    mut game_mode: ResMut<GameMode>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let characters: String = ev_received_character.iter().map(|ev| ev.char).collect();
+    if !dialog.open {
+        if keyboard_input.just_pressed(IMPORT_KEY)
+            && !levels.is_empty()
+            && !wardrobe_menu.is_open()
+            && !encyclopedia_menu.is_open()
+            && !daily_menu.is_open()
+            && !stats_menu.is_open()
+            && !controls_screen.is_open()
+        {
+            dialog.open = true;
+            dialog.code.clear();
+            dialog.message.clear();
+            keyboard_input.clear();
+        }
+        return;
+    }
+    let ctrl = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        dialog.open = false;
+    } else if ctrl && keyboard_input.just_pressed(KeyCode::V) {
+        if let Some(text) = paste() {
+            dialog.code = text
+                .trim()
+                .chars()
+                .filter(|c| is_code_char(*c))
+                .take(MAX_CODE_LEN)
+                .collect();
+            dialog.message.clear();
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Back) {
+        dialog.code.pop();
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        match import_level(&dialog.code, &levels, &buildables) {
+            Ok(level_desc) => {
+                if state.set(AppState::InGame).is_ok() {
+                    info!("Imported level '{}'", level_desc.name);
+                    imported.level = Some(Arc::new(level_desc));
+                    *game_mode = GameMode::Imported;
+                    dialog.open = false;
+                }
+            }
+            Err(message) => dialog.message = message,
+        }
+    } else if !ctrl {
+        let typed = characters.chars().filter(|c| is_code_char(*c));
+        let room = MAX_CODE_LEN.saturating_sub(dialog.code.len());
+        dialog.code.extend(typed.take(room));
+    }
+    // Keep the keys from also triggering the menu entries
+    keyboard_input.clear();
+}
+
+/// Is the character part of the URL-safe base64 alphabet of the level codes? Other characters
+/// typed or pasted in the dialog are dropped, which also keeps the code ASCII.
+fn is_code_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Code shown in the dialog, shortened in the middle if too long to fit on screen.
+fn code_preview(code: &str) -> String {
+    const PREVIEW_LEN: usize = 48;
+    let len = code.chars().count();
+    if code.is_empty() {
+        "<empty>".to_owned()
+    } else if len <= PREVIEW_LEN {
+        code.to_owned()
+    } else {
+        let half = PREVIEW_LEN / 2;
+        let head: String = code.chars().take(half).collect();
+        let tail: String = code.chars().skip(len - half).collect();
+        format!("{}...{} ({} characters)", head, tail, len)
+    }
+}
+
+/// Show the import dialog with the code typed so far, and the error of the last import if any.
+fn update_import_dialog(
+    mut commands: Commands,
+    dialog: Res<ImportDialog>,
+    ui_resources: Res<UiResources>,
+    panel_query: Query<Entity, (With<ImportDialogUi>, Without<Text>)>,
+    mut text_query: Query<&mut Text, With<ImportDialogUi>>,
+) {
+    if !dialog.is_changed() {
+        return;
+    }
+    if !dialog.open {
+        for entity in panel_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let sections = [
+        "Import a level code".to_owned(),
+        "\n[CTRL+V] Paste, [ENTER] Play, [ESC] Cancel\n\n".to_owned(),
+        code_preview(&dialog.code),
+        format!("\n\n{}", dialog.message),
+    ];
+    if let Ok(mut text) = text_query.get_single_mut() {
+        for (section, value) in text.sections.iter_mut().zip(sections) {
+            section.value = value;
+        }
+        return;
+    }
+    let colors = [
+        (36.0, Color::WHITE),
+        (18.0, Color::rgb_u8(200, 200, 200)),
+        (20.0, Color::rgb_u8(255, 220, 120)),
+        (20.0, Color::rgb(0.9, 0.4, 0.4)),
+    ];
+    let sections = sections
+        .into_iter()
+        .zip(colors)
+        .map(|(value, (font_size, color))| TextSection {
+            value,
+            style: TextStyle {
+                font: ui_resources.text_font(),
+                font_size,
+                color,
+            },
+        })
+        .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("ImportDialog"))
+        .insert(MenuOverlay)
+        .insert(ImportDialogUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .insert(ImportDialogUi);
+        });
+}
+
+fn import_dialog_cleanup(
+    mut commands: Commands,
+    mut dialog: ResMut<ImportDialog>,
+    panel_query: Query<Entity, (With<ImportDialogUi>, Without<Text>)>,
+) {
+    dialog.open = false;
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Load the imported level once the game starts in imported mode.
+fn load_imported_level(
+    game_mode: Res<GameMode>,
+    mut imported: ResMut<ImportedLevel>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+) {
+    if *game_mode != GameMode::Imported || imported.requested || imported.level.is_none() {
+        return;
+    }
+    imported.requested = true;
+    ev_load_level.send(LoadLevelEvent(LoadLevel::Imported));
+}
+
+fn imported_level_cleanup(mut imported: ResMut<ImportedLevel>) {
+    *imported = ImportedLevel::default();
+}
+
+/// Plugin importing the levels shared as level codes, from a dialog of the main menu. See
+/// [`encode_level()`] to write a code.
+pub struct LevelCodePlugin;
+
+impl Plugin for LevelCodePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ImportDialog::default())
+            .insert_resource(ImportedLevel::default())
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(AppState::MainMenu)
+                    .after(InputSystem)
+                    .after("profile_menu_input")
+                    .before("calendar_input")
+                    .before("encyclopedia_input")
+                    .with_system(import_dialog_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu).with_system(update_import_dialog),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(import_dialog_cleanup),
+            )
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(load_imported_level))
+            .add_system_set(
+                SystemSet::on_exit(AppState::InGame).with_system(imported_level_cleanup),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let json = r#"{
+            "name": "Shared",
+            "grid_size": [3, 3],
+            "balance_factor": 1.0,
+            "victory_margin": 0.2,
+            "inventory": { "hut": 2 }
+        }"#;
+        let code = encode_level(json).unwrap();
+        assert!(code.starts_with("LIBRA1-"));
+        assert!(!code.contains(['+', '/', '=', ' ']));
+        let level = decode_level(&format!("  {}\n", code)).unwrap();
+        assert_eq!(level.name, "Shared");
+        assert_eq!(level.inventory["hut"], 2);
+
+        assert!(matches!(
+            encode_level(r#"{ "name": "No grid" }"#),
+            Err(LevelCodeError::InvalidLevel(_))
+        ));
+        assert_eq!(decode_level("hello"), Err(LevelCodeError::InvalidCode));
+        assert_eq!(
+            decode_level(&code[..code.len() / 2]),
+            Err(LevelCodeError::InvalidCode)
+        );
+        assert_eq!(
+            decode_level(&code.replacen("LIBRA1", "LIBRA2", 1)),
+            Err(LevelCodeError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn decompression_limit() {
+        // A small code can't inflate into a huge level
+        let padding = " ".repeat(MAX_LEVEL_LEN);
+        let compressed = miniz_oxide::deflate::compress_to_vec(padding.as_bytes(), 9);
+        let code = format!(
+            "LIBRA1-{}",
+            base64::encode_config(compressed, base64::URL_SAFE_NO_PAD)
+        );
+        assert!(code.len() < MAX_CODE_LEN);
+        assert_eq!(decode_level(&code), Err(LevelCodeError::TooLong));
+    }
+
+    #[test]
+    fn grid_limit() {
+        let level = |width: i32, height: i32| {
+            format!(
+                r#"{{ "name": "Big", "grid_size": [{}, {}], "balance_factor": 1.0,
+                    "victory_margin": 0.2, "inventory": {{ "hut": 1 }} }}"#,
+                width, height
+            )
+        };
+        for (width, height) in [(16, 16), (32, 8), (1, 32)] {
+            let code = encode_level(&level(width, height)).unwrap();
+            assert!(decode_level(&code).is_ok());
+        }
+        for (width, height) in [(33, 1), (1, i32::MAX), (17, 16), (i32::MAX, i32::MAX)] {
+            let code = encode_level(&level(width, height)).unwrap();
+            assert_eq!(
+                decode_level(&code),
+                Err(LevelCodeError::GridTooLarge(IVec2::new(width, height)))
+            );
+        }
+    }
+
+    #[test]
+    fn preview() {
+        assert_eq!(code_preview(""), "<empty>");
+        assert_eq!(code_preview("LIBRA1-abc"), "LIBRA1-abc");
+        // Multibyte characters don't split in the middle of a character
+        let code = "é".repeat(50);
+        assert_eq!(
+            code_preview(&code),
+            format!("{}...{} (50 characters)", "é".repeat(24), "é".repeat(24))
+        );
+        assert!("LIBRA1-a_b\u{e9}\u{1f600}"
+            .chars()
+            .filter(|c| is_code_char(*c))
+            .eq("LIBRA1-a_b".chars()));
+    }
+}
@@ -0,0 +1,2000 @@
+#![allow(dead_code, unused_imports, unused_variables)]
+
+use bevy::{
+    app::AppExit,
+    asset::AssetServerSettings,
+    core_pipeline::ClearColor,
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    ecs::schedule::ReportExecutionOrderAmbiguities,
+    gltf::{Gltf, GltfMesh},
+    log::LogPlugin,
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        camera::PerspectiveProjection,
+        mesh::Indices,
+        render_resource::{Extent3d, PrimitiveTopology, Texture, TextureDimension, TextureFormat},
+    },
+    sprite::collide_aabb::{collide, Collision},
+    window::PresentMode,
+};
+use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin};
+use bevy_tweening::{
+    lens::TransformPositionLens, Animator, EaseFunction, Tween, TweeningPlugin, TweeningType,
+};
+//use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
+use serde::Deserialize;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    f32::consts::*,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::Read,
+    time::Duration,
+};
+
+#[cfg(debug_assertions)]
+use bevy_inspector_egui::{WorldInspectorParams, WorldInspectorPlugin};
+
+mod anim;
+mod assist;
+#[cfg(feature = "autoplay")]
+mod autoplay;
+mod autosave;
+mod balance;
+mod boot;
+mod budget;
+mod bugreport;
+mod captions;
+mod cheats;
+mod cinematic;
+mod config;
+mod console;
+mod controls;
+mod controls_screen;
+mod conveyor;
+mod coop;
+mod crash;
+mod daily;
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+mod debug_window;
+mod defeat;
+mod encyclopedia;
+mod environment;
+mod error;
+mod fallback;
+mod fragile;
+mod game;
+mod ghost;
+mod hard;
+mod idle;
+mod inspect;
+#[cfg(debug_assertions)]
+mod inspector;
+mod interlude;
+mod inventory;
+mod journal;
+mod layout;
+#[cfg(all(feature = "leaderboard", not(target_arch = "wasm32")))]
+mod leaderboard;
+mod level;
+mod level_code;
+mod lifetime;
+mod loader;
+mod logging;
+mod lore;
+mod mainmenu;
+mod market;
+mod minimap;
+mod onboarding;
+mod palette;
+#[cfg(not(target_arch = "wasm32"))]
+mod paths;
+mod platform;
+mod playtime;
+mod postprocess;
+#[cfg(not(target_arch = "wasm32"))]
+mod power;
+mod practice;
+#[cfg(all(feature = "presence", not(target_arch = "wasm32")))]
+mod presence;
+mod profile;
+mod qr;
+mod radial;
+mod recap;
+mod remix;
+mod rng;
+mod rules;
+mod schema;
+mod scores;
+mod script;
+mod seesaw;
+mod serialize;
+mod sfx;
+mod shadows;
+mod shake;
+mod snapshot;
+mod solver;
+mod stabilize;
+mod storage;
+mod sync;
+mod telemetry;
+mod text_asset;
+mod the_end;
+mod thumbnail;
+mod tilt;
+mod ui_atlas;
+mod ui_layout;
+mod units;
+mod validate;
+mod versus;
+mod victory_ring;
+mod wardrobe;
+mod wear;
+mod weather;
+mod world_label;
+
+pub use crate::{
+    anim::AnimPlugin, assist::AssistPlugin, autosave::AutosavePlugin, balance::BalancePlugin,
+    boot::BootPlugin, budget::WeightBudgetPlugin, bugreport::BugReportPlugin,
+    captions::CaptionsPlugin, cheats::CheatsPlugin, cinematic::CinematicPlugin,
+    console::ConsolePlugin, controls::ControlsPlugin, controls_screen::ControlsScreenPlugin,
+    conveyor::ConveyorPlugin, coop::CoopPlugin, crash::CrashPlugin, daily::DailyPlugin,
+    defeat::DefeatPlugin, encyclopedia::EncyclopediaPlugin, environment::EnvironmentPlugin,
+    fragile::FragilePlugin, game::GamePlugin, ghost::GhostPlugin, hard::HardModePlugin,
+    idle::IdlePlugin, inspect::InspectPlugin, interlude::InterludePlugin,
+    inventory::InventoryPlugin, journal::JournalPlugin, level::LevelPlugin,
+    level_code::LevelCodePlugin, lifetime::AssetLifetimePlugin, loader::LoaderPlugin,
+    logging::LoggingPlugin, lore::LorePlugin, mainmenu::MainMenuPlugin, market::MarketPlugin,
+    minimap::MinimapPlugin, onboarding::OnboardingPlugin, palette::PalettePlugin,
+    playtime::PlaytimePlugin, postprocess::PostProcessPlugin, practice::PracticePlugin,
+    profile::ProfilePlugin, radial::RadialMenuPlugin, recap::RecapPlugin, remix::RemixPlugin,
+    rng::RngPlugin, rules::RulesPlugin, scores::ScoresPlugin, script::ScriptPlugin,
+    seesaw::SeesawPlugin, serialize::SerializePlugin, sfx::SfxPlugin, shadows::ShadowsPlugin,
+    shake::ScreenShakePlugin, snapshot::QuickSavePlugin, stabilize::StabilizePlugin,
+    sync::SaveSyncPlugin, telemetry::TelemetryPlugin, text_asset::TextAssetPlugin,
+    the_end::TheEndPlugin, thumbnail::ThumbnailPlugin, ui_atlas::UiAtlasPlugin,
+    ui_layout::UiLayoutPlugin, versus::VersusPlugin, victory_ring::VictoryRingPlugin,
+    wardrobe::WardrobePlugin, wear::TileWearPlugin, weather::WeatherPlugin,
+    world_label::WorldLabelPlugin,
+};
+use crate::{
+    anim::{PlayAnimation, RotationOffset, TranslationOffset, TranslationOffsetLens},
+    cinematic::CinematicMode,
+    config::Config,
+    controls::{CursorInput, PlayerAction},
+    conveyor::ConveyorDesc,
+    coop::Coop,
+    error::Error,
+    fragile::FragileTileDesc,
+    game::{run_if_playing, GameplaySystem},
+    idle::IdleAnimation,
+    inventory::{
+        DeliveryEvent, Inventory, RegenerateInventoryUiEvent, SelectSlot, SelectSlotEvent, Slot,
+        SlotState, UpdateInventorySlots,
+    },
+    journal::{LevelJournal, LevelOp},
+    layout::{GridLayout, LayoutPlacement},
+    level::{
+        mark_for_despawn, Level, LevelErrorEvent, LevelNameText, LevelUnloading, LoadLevel,
+        LoadLevelEvent, UnloadReason,
+    },
+    loader::Loader,
+    market::BuildQueue,
+    serialize::Levels,
+    sfx::{PlaySfxEvent, Sfx},
+    text_asset::TextAsset,
+    wear::Tile,
+};
+pub use crate::{
+    balance::BalanceState,
+    game::{DefeatReason, Game, GameEvent, GameSequence},
+    interlude::InterludePlayer,
+    inventory::Buildable,
+    level_code::{encode_level, LevelCodeError},
+    onboarding::Onboarding,
+    qr::{QrCode, QrError},
+    serialize::{BuildableId, BuildableRegistry},
+    solver::solve_current_level,
+    tilt::{KnifeEdge, Pivot, TiltModel},
+    validate::{validate_path, ValidationReport},
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AppState {
+    /// Boot sequence (critical assets loading).
+    Boot,
+    /// Main menu.
+    MainMenu,
+    /// Playing a game level.
+    InGame,
+    /// End screen.
+    TheEnd,
+}
+
+struct EntityManager {
+    // HACK to delete everything on TheEnd screen
+    all_entities: Vec<Entity>,
+}
+
+impl EntityManager {
+    pub fn new() -> EntityManager {
+        EntityManager {
+            all_entities: vec![],
+        }
+    }
+}
+
+// fn exit_system(mut exit: EventWriter<AppExit>) {
+//     exit.send(AppExit);
+// }
+
+pub struct ResetPlateEvent;
+
+/// Event signaling that the last tiles of the plate regenerated on [`ResetPlateEvent`] were
+/// spawned.
+pub struct TilesSpawnedEvent;
+
+/// Event to restart the current level from its initial state.
+pub struct RestartLevelEvent;
+
+/// Cursor placing a buildable, which takes it out of its own inventory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorId {
+    /// Cursor of the player, drawing from the [`Inventory`] resource.
+    Player,
+    /// Cursor of the partner in coop mode, drawing from the inventory of the [`Coop`] partner.
+    Partner,
+}
+
+/// Event requesting to place a buildable of the inventory on the plate. Sent by the cursors, and
+/// by anything else placing buildables on behalf of the player.
+#[derive(Debug, Clone, Copy)]
+pub struct PlaceBuildableEvent {
+    /// Grid coordinates of the cell to place the buildable in.
+    pub pos: IVec2,
+    /// Buildable to place, taken out of the inventory of the cursor.
+    pub bref: BuildableId,
+    /// Cursor placing the buildable.
+    pub cursor: CursorId,
+}
+
+/// Grid position of a buildable placed on the plate.
+#[derive(Component)]
+struct Placed(IVec2);
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct Plate {
+    entity: Entity,
+}
+
+// Placeholder for the type registry only; the plate is always spawned with its entity.
+impl FromWorld for Plate {
+    fn from_world(_world: &mut World) -> Self {
+        Plate::new(Entity::from_raw(u32::MAX))
+    }
+}
+
+impl Plate {
+    pub fn new(entity: Entity) -> Plate {
+        Plate { entity }
+    }
+}
+
+fn plate_reset_system(
+    mut commands: Commands,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut grid: ResMut<Grid>,
+    query_plate: Query<&Plate>,
+    mut query_cursor: Query<(&mut Cursor, &mut Transform)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    level: Res<Level>,
+) {
+    // Consume all reset events, do the work once
+    if let Some(_) = ev_reset_plate.iter().last() {
+        trace!("plate_reset_system() - GOT EVENT");
+
+        // Resize and clear grid
+        if let Some(level_desc) = level.desc() {
+            grid.set_size(&level_desc.grid_size);
+            if let Some(terrain) = &level_desc.terrain {
+                grid.set_terrain(terrain.heights.clone(), terrain.lever_arm);
+            }
+            if !level_desc.fragile.is_empty() {
+                grid.set_capacities(&level_desc.fragile);
+            }
+            if !level_desc.conveyors.is_empty() {
+                grid.set_conveyors(&level_desc.conveyors);
+            }
+            grid.set_weight_scale(level_desc.weather.weight_scale());
+        }
+        grid.clear(Some(&mut commands));
+
+        // Rebuild plate with N copies of a single 'cell' mesh laid out in grid
+        let plate = query_plate.single();
+        // TODO - cache mesh
+        let cell_mesh = meshes.add(Mesh::from(shape::Box::new(1.0, TILE_THICKNESS, 1.0)));
+        grid.regenerate(&mut commands, cell_mesh.clone(), plate.entity);
+
+        // Keep the cursor inside the (possibly smaller) new grid
+        let (mut cursor, mut transform) = query_cursor.single_mut();
+        let pos = cursor.pos;
+        cursor.set_pos(pos, &grid, &mut transform);
+    }
+}
+
+/// The game cursor controlled by the player.
+#[derive(Debug, Component, Reflect)]
+#[reflect(Component)]
+pub struct Cursor {
+    /// Is the cursor enabled (reacts to user input)?
+    enabled: bool,
+    /// Position of the cursor on the board, in cell coordinates.
+    pos: IVec2,
+    /// Cell the cursor entity is gliding to, lagging behind `pos` while gliding.
+    glide_pos: IVec2,
+    /// Cells left to glide through after `glide_pos`, up to `pos`.
+    #[reflect(ignore)]
+    path: VecDeque<IVec2>,
+    /// Was the cursor moved without gliding since the last glide update, cancelling any glide in
+    /// progress?
+    snapped: bool,
+    move_speed: f32,
+    //weight: f32,
+    /// Entity representing the cursor and owning the render object.
+    cursor_entity: Entity,
+    /// Cursor mesh.
+    cursor_mesh: Handle<Mesh>,
+    /// Cursor material.
+    cursor_mat: Handle<StandardMaterial>,
+    /// Cursor material variant when the cell under the cursor can receive a buildable.
+    valid_mat: Handle<StandardMaterial>,
+    /// Cursor material variant when the cell under the cursor is occupied or blocked.
+    invalid_mat: Handle<StandardMaterial>,
+    /// The entity to parent the cursor entity to.
+    spawn_root_entity: Entity,
+}
+
+impl Cursor {
+    pub fn new(cursor_entity: Entity, spawn_root_entity: Entity) -> Cursor {
+        Cursor {
+            enabled: false,
+            pos: IVec2::ZERO,
+            glide_pos: IVec2::ZERO,
+            path: VecDeque::new(),
+            snapped: false,
+            move_speed: 1.0,
+            //weight: 1.0,
+            cursor_entity,
+            cursor_mesh: Default::default(),
+            cursor_mat: Default::default(),
+            valid_mat: Default::default(),
+            invalid_mat: Default::default(),
+            spawn_root_entity,
+        }
+    }
+
+    pub fn set_cursor(&mut self, mesh: Handle<Mesh>, mat: Handle<StandardMaterial>) {
+        self.cursor_mesh = mesh;
+        self.cursor_mat = mat;
+    }
+
+    pub fn set_validity_materials(
+        &mut self,
+        valid_mat: Handle<StandardMaterial>,
+        invalid_mat: Handle<StandardMaterial>,
+    ) {
+        self.valid_mat = valid_mat;
+        self.invalid_mat = invalid_mat;
+    }
+
+    /// Get the cursor material variant for the given placement validity.
+    pub fn validity_material(&self, valid: bool) -> &Handle<StandardMaterial> {
+        if valid {
+            &self.valid_mat
+        } else {
+            &self.invalid_mat
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Position of the cursor on the board, in cell coordinates.
+    pub fn pos(&self) -> IVec2 {
+        self.pos
+    }
+
+    /// Move the cursor to the given cell without gliding, updating the transform of the cursor
+    /// entity. Any glide in progress is stopped.
+    pub fn set_pos(&mut self, pos: IVec2, grid: &Grid, transform: &mut Transform) {
+        self.pos = grid.clamp(pos);
+        self.glide_pos = self.pos;
+        self.path.clear();
+        self.snapped = true;
+        transform.translation = cursor_translation(grid, self.pos);
+    }
+
+    // pub fn set_alpha(&mut self, alpha: f32) {
+    //      self.cursor_mat
+    // }
+}
+
+// Placeholder for the type registry only; the cursor is always spawned with its entities.
+impl FromWorld for Cursor {
+    fn from_world(_world: &mut World) -> Self {
+        Cursor::new(Entity::from_raw(u32::MAX), Entity::from_raw(u32::MAX))
+    }
+}
+
+/// Maximum number of plate tiles spawned per frame, spreading the spawn of large plates over
+/// several frames to avoid a hitch.
+const TILES_PER_FRAME: usize = 64;
+
+/// Spawn the tiles of the plate regenerated on [`ResetPlateEvent`] a few per frame, holding the
+/// level intro until they're all spawned.
+fn spawn_tiles_system(
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    mut game: ResMut<Game>,
+    mut ev_tiles_spawned: EventWriter<TilesSpawnedEvent>,
+) {
+    if !grid.is_spawning_tiles() {
+        return;
+    }
+    game.hold_intro();
+    if grid.spawn_tiles(&mut commands, TILES_PER_FRAME) {
+        trace!("All tiles spawned");
+        ev_tiles_spawned.send(TilesSpawnedEvent);
+    }
+}
+
+/// Tiles of the [`Grid`] left to spawn after it regenerated.
+#[derive(Debug)]
+struct PendingTiles {
+    mesh: Handle<Mesh>,
+    parent: Entity,
+    /// Index of the next tile to spawn, like [`Grid::index()`].
+    next: usize,
+}
+
+/// Content of a single cell of the [`Grid`].
+#[derive(Debug, Default, Clone, Copy, Reflect, FromReflect)]
+pub struct Cell {
+    /// Total weight of the buildables placed in the cell.
+    pub weight: f32,
+    /// Buildable placed in the cell, if any.
+    pub buildable: Option<BuildableId>,
+    /// Whether the tile of the cell broke, leaving a hole nothing can be placed in.
+    pub hole: bool,
+}
+
+#[derive(Debug, Reflect)]
+pub struct Grid {
+    size: IVec2,
+    content: Vec<Cell>,
+    /// Origin offset. Odd sizes have the middle cell of the grid at the world origin, while even sizes
+    /// are offset by 0.5 units such that the center of the grid (between cells) is at the world origin.
+    foffset: Vec2,
+    grid_blocks: Vec<Entity>,
+    entities: Vec<Entity>,
+    material: Handle<StandardMaterial>,
+    /// Terrace height of each cell, indexed like the content. The plate is flat if empty.
+    heights: Vec<f32>,
+    /// Extra lever arm of the elevated cells per unit of height. See [`Grid::lever_pos()`].
+    lever_arm: f32,
+    /// Weight capacity of each cell, indexed like the content. No tile is fragile if empty.
+    capacities: Vec<f32>,
+    /// Direction of the conveyor tile of each cell, indexed like the content, or zero if the tile
+    /// is not a conveyor. No tile is a conveyor if empty.
+    conveyors: Vec<IVec2>,
+    /// Multiplier applied to the weight of the buildables placed, like in the rain.
+    weight_scale: f32,
+    /// Seed of the deviation of the weight of the buildables placed from their nominal weight.
+    /// See [`rng::weight_jitter()`].
+    weight_seed: u64,
+    /// Tiles left to spawn since the grid regenerated, if any.
+    #[reflect(ignore)]
+    pending_tiles: Option<PendingTiles>,
+}
+
+/// Thickness of the tiles of the plate.
+const TILE_THICKNESS: f32 = 0.1;
+
+impl Grid {
+    pub fn new() -> Grid {
+        let mut grid = Grid {
+            size: IVec2::ZERO,
+            content: vec![],
+            foffset: Vec2::ZERO,
+            grid_blocks: vec![],
+            entities: vec![],
+            material: Default::default(),
+            heights: vec![],
+            lever_arm: 0.0,
+            capacities: vec![],
+            conveyors: vec![],
+            weight_scale: 1.0,
+            weight_seed: 0,
+            pending_tiles: None,
+        };
+        grid.set_size(&IVec2::new(8, 8));
+        grid
+    }
+
+    pub fn set_material(&mut self, material: Handle<StandardMaterial>) {
+        self.material = material;
+    }
+
+    /// Material of the tiles of the plate.
+    pub fn material(&self) -> &Handle<StandardMaterial> {
+        &self.material
+    }
+
+    pub fn set_size(&mut self, size: &IVec2) {
+        trace!("Grid::set_size({}, {})", size.x, size.y);
+        self.size = *size;
+        self.foffset = Vec2::new((1 - self.size.x % 2) as f32, (1 - self.size.y % 2) as f32) * 0.5;
+        self.heights.clear();
+        self.capacities.clear();
+        self.conveyors.clear();
+        self.weight_scale = 1.0;
+        self.clear(None);
+    }
+
+    /// Set the terrace height of each cell, indexed like [`Grid::index()`], and the extra lever
+    /// arm of the elevated cells per unit of height. No heights make a flat plate.
+    pub fn set_terrain(&mut self, heights: Vec<f32>, lever_arm: f32) {
+        self.heights = heights;
+        self.lever_arm = lever_arm;
+    }
+
+    /// Terrace height of the cell at the given grid coordinates.
+    pub fn height(&self, pos: &IVec2) -> f32 {
+        self.heights.get(self.index(pos)).copied().unwrap_or(0.0)
+    }
+
+    /// Set the weight capacity of the fragile tiles, at the given grid coordinates. The other
+    /// tiles hold any weight.
+    pub fn set_capacities(&mut self, fragile: &[FragileTileDesc]) {
+        self.capacities = vec![f32::INFINITY; self.content.len()];
+        for tile in fragile {
+            let index = self.index(&tile.pos);
+            self.capacities[index] = tile.capacity;
+        }
+    }
+
+    /// Weight capacity of the tile at the given grid coordinates, infinite if not fragile.
+    pub fn capacity(&self, pos: &IVec2) -> f32 {
+        self.capacities
+            .get(self.index(pos))
+            .copied()
+            .unwrap_or(f32::INFINITY)
+    }
+
+    /// Set the conveyor tiles, which shift the buildable placed on them.
+    pub fn set_conveyors(&mut self, conveyors: &[ConveyorDesc]) {
+        self.conveyors = vec![IVec2::ZERO; self.content.len()];
+        for conveyor in conveyors {
+            let index = self.index(&conveyor.pos);
+            self.conveyors[index] = conveyor.direction;
+        }
+    }
+
+    /// Direction the conveyor tile at the given grid coordinates shifts its buildable toward, if
+    /// the tile is a conveyor.
+    pub fn conveyor(&self, pos: &IVec2) -> Option<IVec2> {
+        self.conveyors
+            .get(self.index(pos))
+            .copied()
+            .filter(|&direction| direction != IVec2::ZERO)
+    }
+
+    /// Set the multiplier applied to the weight of the buildables placed from now on.
+    pub fn set_weight_scale(&mut self, weight_scale: f32) {
+        self.weight_scale = weight_scale;
+    }
+
+    /// Multiplier applied to the weight of the buildables placed.
+    pub fn weight_scale(&self) -> f32 {
+        self.weight_scale
+    }
+
+    /// Set the seed of the deviation of the weight of the buildables placed from now on.
+    pub fn set_weight_seed(&mut self, weight_seed: u64) {
+        self.weight_seed = weight_seed;
+    }
+
+    /// Weight of a buildable once placed in a cell, within its weight variance of its nominal
+    /// weight, and scaled like the other weights of the grid.
+    pub fn instance_weight(&self, pos: &IVec2, bref: BuildableId, buildable: &Buildable) -> f32 {
+        let mut weight = buildable.weight();
+        if buildable.weight_variance() > 0.0 {
+            let jitter = rng::weight_jitter(self.weight_seed, *pos, bref);
+            weight += buildable.weight_variance() * jitter;
+        }
+        weight * self.weight_scale
+    }
+
+    /// Position in plate local space of a point at some elevation above the terrace of a cell.
+    pub fn translation(&self, pos: &IVec2, elevation: f32) -> Vec3 {
+        let fpos = self.fpos(pos);
+        Vec3::new(fpos.x, self.height(pos) + elevation, -fpos.y)
+    }
+
+    /// Transform of the tile of a cell, stretched down from its terrace to the plate.
+    pub fn tile_transform(&self, pos: &IVec2) -> Transform {
+        let height = self.height(pos);
+        let fpos = self.fpos(pos);
+        Transform {
+            translation: Vec3::new(fpos.x, height / 2.0, -fpos.y),
+            scale: Vec3::new(1.0, 1.0 + height / TILE_THICKNESS, 1.0),
+            ..Default::default()
+        }
+    }
+
+    /// Despawn the tiles of the grid, and queue new ones for the current size, spawned a few per
+    /// frame by [`Grid::spawn_tiles()`] as children of the given parent.
+    pub fn regenerate(&mut self, commands: &mut Commands, mesh: Handle<Mesh>, parent: Entity) {
+        trace!("Grid::regenerate() size={}", self.size);
+
+        // Destroy previous grid
+        for ent in self.grid_blocks.iter() {
+            mark_for_despawn(commands, *ent);
+        }
+        self.grid_blocks.clear();
+
+        self.pending_tiles = Some(PendingTiles {
+            mesh,
+            parent,
+            next: 0,
+        });
+    }
+
+    /// Are tiles left to spawn since the grid regenerated?
+    pub fn is_spawning_tiles(&self) -> bool {
+        self.pending_tiles.is_some()
+    }
+
+    /// Spawn up to `budget` of the tiles left to spawn since the grid regenerated, in cell index
+    /// order. Returns `true` if the last tile was spawned.
+    pub fn spawn_tiles(&mut self, commands: &mut Commands, budget: usize) -> bool {
+        let mut pending = match self.pending_tiles.take() {
+            Some(pending) => pending,
+            None => return false,
+        };
+        let count = self.content.len();
+        let end = pending.next.saturating_add(budget).min(count);
+        let min = self.min_pos();
+        for index in pending.next..end {
+            let pos = min + IVec2::new(index as i32 % self.size.x, index as i32 / self.size.x);
+            self.grid_blocks.push(
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: pending.mesh.clone(),
+                        material: self.material.clone(),
+                        transform: self.tile_transform(&pos),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(format!("Tile({},{})", pos.x, pos.y)))
+                    .insert(Tile::new(pos))
+                    .insert(Parent(pending.parent))
+                    .id(),
+            );
+        }
+        if end < count {
+            pending.next = end;
+            self.pending_tiles = Some(pending);
+            return false;
+        }
+        true
+    }
+
+    pub fn min_pos(&self) -> IVec2 {
+        let x_min = -self.size.x / 2;
+        let y_min = -self.size.y / 2;
+        IVec2::new(x_min, y_min)
+    }
+
+    pub fn max_pos(&self) -> IVec2 {
+        let x_max = (self.size.x - 1) / 2;
+        let y_max = (self.size.y - 1) / 2;
+        IVec2::new(x_max, y_max)
+    }
+
+    pub fn clamp(&self, pos: IVec2) -> IVec2 {
+        let min = self.min_pos();
+        let max = self.max_pos();
+        IVec2::new(pos.x.clamp(min.x, max.x), pos.y.clamp(min.y, max.y))
+    }
+
+    pub fn hit_test(&self, pos: &Vec2) -> Option<IVec2> {
+        let min = self.min_pos();
+        let max = self.max_pos();
+        if pos.x <= min.x as f32
+            || pos.x >= max.x as f32
+            || pos.y <= min.y as f32
+            || pos.y >= max.y as f32
+        {
+            None
+        } else {
+            let x = pos.x as i32;
+            let y = pos.y as i32;
+            Some(IVec2::new(x, y))
+        }
+    }
+
+    pub fn index(&self, pos: &IVec2) -> usize {
+        let min = self.min_pos();
+        let i0 = (pos.x - min.x) as usize;
+        let j0 = (pos.y - min.y) as usize;
+        i0 + j0 * self.size.x as usize
+    }
+
+    /// Position of the center of the cell from its grid coordinates.
+    pub fn fpos(&self, pos: &IVec2) -> Vec2 {
+        Vec2::new(pos.x as f32 + self.foffset.x, pos.y as f32 + self.foffset.y)
+    }
+
+    /// Position of the cell as the lever of its weight. Elevated cells act as if farther from the
+    /// grid center, by the lever arm of the terrain per unit of height.
+    pub fn lever_pos(&self, pos: &IVec2) -> Vec2 {
+        self.fpos(pos) * (1.0 + self.lever_arm * self.height(pos))
+    }
+
+    /// Content of the cell at the given grid coordinates.
+    pub fn cell(&self, pos: &IVec2) -> &Cell {
+        &self.content[self.index(pos)]
+    }
+
+    pub fn can_spawn_item(&self, pos: &IVec2) -> bool {
+        let index = self.index(pos);
+        !self.content[index].hole && self.content[index].weight < 0.1
+    }
+
+    pub fn spawn_item(&mut self, pos: &IVec2, bref: BuildableId, weight: f32, entity: Entity) {
+        let index = self.index(pos);
+        let cell = &mut self.content[index];
+        cell.weight += weight;
+        cell.buildable = Some(bref);
+        self.entities.push(entity);
+    }
+
+    /// Break the tile of a cell, losing the buildable placed on it, whose entity is given if any.
+    /// The cell becomes a hole until the grid is cleared. Returns the content of the cell before
+    /// it broke.
+    pub fn break_tile(&mut self, pos: &IVec2, entity: Option<Entity>) -> Cell {
+        let index = self.index(pos);
+        let cell = self.content[index];
+        self.content[index] = Cell {
+            hole: true,
+            ..Default::default()
+        };
+        if let Some(entity) = entity {
+            self.entities.retain(|&ent| ent != entity);
+        }
+        cell
+    }
+
+    /// Remove the buildable placed in a cell, whose entity is given if any, leaving the cell free.
+    /// Returns the content of the cell before the removal.
+    pub fn remove_item(&mut self, pos: &IVec2, entity: Option<Entity>) -> Cell {
+        let index = self.index(pos);
+        let cell = std::mem::take(&mut self.content[index]);
+        if let Some(entity) = entity {
+            self.entities.retain(|&ent| ent != entity);
+        }
+        cell
+    }
+
+    /// Move the content of a cell to a free cell, like a buildable carried by a conveyor tile.
+    pub fn move_item(&mut self, from: &IVec2, to: &IVec2) {
+        let from = self.index(from);
+        let to = self.index(to);
+        self.content[to] = self.content[from];
+        self.content[from] = Cell::default();
+    }
+
+    /// List all the buildables placed on the grid, with their grid coordinates.
+    pub fn placements(&self) -> Vec<(IVec2, BuildableId)> {
+        let min = self.min_pos();
+        let max = self.max_pos();
+        let mut placements = vec![];
+        for j in min.y..max.y + 1 {
+            for i in min.x..max.x + 1 {
+                let pos = IVec2::new(i, j);
+                if let Some(bref) = self.cell(&pos).buildable {
+                    placements.push((pos, bref));
+                }
+            }
+        }
+        placements
+    }
+
+    /// Capture the buildables placed on the grid and their weight into a serializable layout.
+    /// Buildables missing from the registry are skipped.
+    pub fn to_layout(&self, buildables: &BuildableRegistry) -> GridLayout {
+        let placements = self
+            .placements()
+            .into_iter()
+            .filter_map(|(pos, bref)| {
+                Some(LayoutPlacement {
+                    pos,
+                    buildable: buildables.name(bref)?.to_owned(),
+                    weight: self.cell(&pos).weight,
+                })
+            })
+            .collect();
+        GridLayout {
+            size: self.size,
+            placements,
+        }
+    }
+
+    /// Create a grid from a layout, without any entity. Use [`spawn_buildable`] to spawn the
+    /// buildables of the layout instead to populate the grid of the level being played.
+    pub fn from_layout(layout: &GridLayout, buildables: &BuildableRegistry) -> Result<Grid, Error> {
+        let mut grid = Grid::new();
+        grid.set_size(&layout.size);
+        for placement in &layout.placements {
+            if grid.clamp(placement.pos) != placement.pos {
+                error!("Layout placement {:?} outside grid.", placement.pos);
+                return Err(Error::InvalidLayout);
+            }
+            let bref = buildables.id(&placement.buildable).ok_or_else(|| {
+                error!("Unknown buildable '{}' in layout.", placement.buildable);
+                Error::InvalidLayout
+            })?;
+            let index = grid.index(&placement.pos);
+            grid.content[index] = Cell {
+                weight: placement.weight,
+                buildable: Some(bref),
+                ..Default::default()
+            };
+        }
+        Ok(grid)
+    }
+
+    pub fn calc_cog_offset(&self, balance_factor: f32) -> Vec2 {
+        let min = self.min_pos();
+        let max = self.max_pos();
+        let mut w00 = Vec2::ZERO;
+        //println!("calc_rot: min={:?} max={:?}", min, max);
+        for j in min.y..max.y + 1 {
+            for i in min.x..max.x + 1 {
+                let ij = IVec2::new(i, j);
+                let index = self.index(&ij);
+                let fpos = self.lever_pos(&ij);
+                // println!(
+                //     "calc_rot: index={:?} ij={},{} fpos={:?} w={}",
+                //     index, i, j, fpos, self.content[index]
+                // );
+                w00 += self.content[index].weight * fpos;
+            }
+        }
+        //println!("calc_rot: w00={:?}", w00);
+        w00
+    }
+
+    /// Total weight of the buildables placed on the grid.
+    pub fn total_weight(&self) -> f32 {
+        self.content.iter().map(|cell| cell.weight).sum()
+    }
+
+    /// Digest of the weights of all the cells, which changes whenever the weight of any cell
+    /// changes.
+    pub fn weights_digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for cell in &self.content {
+            cell.weight.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Offset of the COG from the pivot, which is zero when the plate is balanced.
+    pub fn calc_balance_offset(&self, balance_factor: f32, pivot: &Pivot) -> Vec2 {
+        pivot.balance_offset(self.calc_cog_offset(balance_factor), self.total_weight())
+    }
+
+    /// Tilt angles of the plate toward the X and Y axes of the grid, in radians.
+    pub fn calc_tilt_angles(
+        &self,
+        balance_factor: f32,
+        pivot: &Pivot,
+        tilt_model: &TiltModel,
+    ) -> Vec2 {
+        tilt_model.angles(
+            self.calc_balance_offset(balance_factor, pivot),
+            balance_factor,
+        )
+    }
+
+    /// Magnitude of the plate tilt angle, in radians.
+    pub fn calc_tilt(&self, balance_factor: f32, pivot: &Pivot, tilt_model: &TiltModel) -> f32 {
+        self.calc_tilt_angles(balance_factor, pivot, tilt_model)
+            .length()
+    }
+
+    pub fn calc_rot(&self, balance_factor: f32, pivot: &Pivot, tilt_model: &TiltModel) -> Quat {
+        let angles = self.calc_tilt_angles(balance_factor, pivot, tilt_model);
+        //println!("calc_rot: rx={} ry={}", angles.x, angles.y);
+        tilt::rotation(angles)
+    }
+
+    /// Despawn the grid blocks and the buildables, when leaving the game.
+    pub fn despawn(&mut self, commands: &mut Commands) {
+        for ent in self.grid_blocks.iter() {
+            mark_for_despawn(commands, *ent);
+        }
+        self.grid_blocks.clear();
+        self.pending_tiles = None;
+        self.clear(Some(commands));
+    }
+
+    pub fn clear(&mut self, commands: Option<&mut Commands>) {
+        trace!(
+            "Grid::clear({})",
+            if commands.is_some() { "commands" } else { "-" }
+        );
+        self.content.clear();
+        self.content
+            .resize(self.size.x as usize * self.size.y as usize, Cell::default());
+        if let Some(commands) = commands {
+            self.entities.iter().for_each(|ent| {
+                mark_for_despawn(commands, *ent);
+            });
+            self.entities.clear();
+        }
+    }
+
+    pub fn is_victory(&self, balance_factor: f32, pivot: &Pivot, victory_margin: f32) -> bool {
+        let w00 = self.calc_balance_offset(balance_factor, pivot);
+        debug!("victory: w00={:?} len={}", w00, w00.length());
+        w00.length() < victory_margin
+    }
+}
+
+#[cfg(debug_assertions)]
+fn inspector_toggle(
+    keyboard_input: ResMut<Input<KeyCode>>,
+    mut inspector: ResMut<WorldInspectorParams>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        inspector.enabled = !inspector.enabled;
+    }
+}
+
+static DEBUG: &str = "debug";
+
+/// Options of the game app, set by the executable embedding the game.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Folder of the game assets, relative to the executable. Overridden by the
+    /// `LIBRACITY_ASSETS` environment variable on native platforms.
+    pub asset_folder: String,
+    /// CSS selector of the canvas to render into, on the web only. A new canvas is created if not
+    /// set.
+    pub canvas: Option<String>,
+    /// Let a bot play the game on its own. Only available with the `autoplay` feature.
+    pub autoplay: bool,
+    /// Bug report file to replay, on native platforms only.
+    pub replay: Option<String>,
+    /// Save profile to play with, created if needed. Defaults to the profile last selected in the
+    /// main menu.
+    pub profile: Option<String>,
+    /// Keep the save files and settings next to the executable instead of the user directory, on
+    /// native platforms only.
+    pub portable: bool,
+    /// Seed of the gameplay randomness, to reproduce a run. Random if not set.
+    pub seed: Option<u64>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            asset_folder: "assets".to_string(),
+            canvas: None,
+            autoplay: false,
+            replay: None,
+            profile: None,
+            portable: false,
+            seed: None,
+        }
+    }
+}
+
+/// Build the game app with all its plugins, ready to run.
+pub fn build_app(config: AppConfig) -> App {
+    #[cfg(target_arch = "wasm32")]
+    console_error_panic_hook::set_once();
+
+    // Locate the save files, before anything reads or writes them
+    #[cfg(not(target_arch = "wasm32"))]
+    paths::set_portable(config.portable);
+
+    // Write a crash report on panic, shown on the next launch
+    crash::install_panic_hook();
+
+    let mut diag = LogDiagnosticsPlugin::default();
+    diag.debug = true;
+
+    // Main window, rendering into the canvas of the page on the web
+    #[allow(unused_mut)]
+    let mut window = WindowDescriptor {
+        title: platform::GAME_TITLE.to_string(),
+        present_mode: PresentMode::Fifo, // vsync
+        ..Default::default()
+    };
+    #[cfg(target_arch = "wasm32")]
+    {
+        window.canvas = config.canvas;
+    }
+
+    let mut app = App::new();
+    app
+        // Logging and diagnostics
+        .insert_resource(bevy::log::LogSettings {
+            level: bevy::log::Level::INFO,
+            filter: "wgpu=error,bevy_render=info,libracity=trace".to_string(),
+        })
+        .add_plugin(LoggingPlugin)
+        .add_plugin(diag)
+        //.add_plugin(FrameTimeDiagnosticsPlugin::default())
+        // Asset server configuration
+        .insert_resource(AssetServerSettings {
+            #[cfg(not(target_arch = "wasm32"))]
+            asset_folder: paths::asset_folder(&config.asset_folder),
+            #[cfg(target_arch = "wasm32")]
+            asset_folder: config.asset_folder,
+            watch_for_changes: cfg!(feature = "hot_reload"),
+        })
+        // Main window
+        .insert_resource(window);
+
+    // Clear screen in transparent black by default to hide any artifact, but in bright magenta
+    // in debug to highlight those artifacts (which need to be fixed).
+    #[cfg(debug_assertions)]
+    app.insert_resource(ClearColor(Color::rgb(1.0, 0.0, 1.0)));
+    #[cfg(not(debug_assertions))]
+    app.insert_resource(ClearColor(Color::NONE));
+
+    // Only enable MSAA on non-web platforms
+    #[cfg(not(target_arch = "wasm32"))]
+    app.insert_resource(Msaa { samples: 4 });
+
+    // // Report ambiguous systems in debug
+    // #[cfg(debug_assertions)]
+    // app.insert_resource(ReportExecutionOrderAmbiguities);
+
+    app
+        // Helper to exit with ESC key
+        .add_system(bevy::input::system::exit_on_esc_system)
+        // Default plugins, with the logging set up by LoggingPlugin instead
+        .add_plugins_with(DefaultPlugins, |group| group.disable::<LogPlugin>());
+
+    // // Shaders shipped with bevy_prototype_debug_lines are not compatible with WebGL due to version
+    // // https://github.com/mrk-its/bevy_webgl2/issues/21
+    // #[cfg(not(target_arch = "wasm32"))]
+    // app.add_plugin(DebugLinesPlugin)
+    //     .insert_resource(DebugLines {
+    //         depth_test: true,
+    //         ..Default::default()
+    //     });
+
+    // In Debug build only, add egui inspector to help
+    #[cfg(debug_assertions)]
+    app.add_plugin(WorldInspectorPlugin::new())
+        .add_plugin(inspector::GameplayInspectorPlugin)
+        .add_system(inspector_toggle);
+
+    // In native Debug build only, detach the inspector and the console to a separate window
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    app.add_plugin(debug_window::DebugWindowPlugin);
+
+    // Audio (Kira), silent if no audio device is available
+    app.add_plugin(AudioPlugin);
+
+    // Game states, plugins, and systems, with the save data of the requested profile
+    if let Some(profile) = &config.profile {
+        profile::launch_with_profile(profile);
+    }
+    if let Some(seed) = config.seed {
+        app.insert_resource(rng::GameRng::new(seed));
+    }
+    add_game_plugins(&mut app);
+
+    // Bot playing on its own, only if enabled and requested
+    #[cfg(feature = "autoplay")]
+    if config.autoplay {
+        app.add_plugin(autoplay::AutoplayPlugin);
+    }
+
+    // Replay of a bug report, only if requested
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = &config.replay {
+        match bugreport::BugReport::load(path) {
+            Ok(report) => {
+                app.add_plugin(bugreport::BugReplayPlugin(report));
+            }
+            Err(err) => error!("Failed to load bug report '{}': {}", path, err),
+        }
+    }
+
+    // Window icon, title, and taskbar progress
+    app.add_plugin(platform::PlatformPlugin);
+
+    // Frame rate capping in the background and on battery
+    #[cfg(not(target_arch = "wasm32"))]
+    app.add_plugin(power::PowerSavingPlugin);
+
+    // Online leaderboard, only if enabled
+    #[cfg(all(feature = "leaderboard", not(target_arch = "wasm32")))]
+    app.add_plugin(leaderboard::LeaderboardPlugin);
+
+    // Rich presence, only if enabled
+    #[cfg(all(feature = "presence", not(target_arch = "wasm32")))]
+    app.add_plugin(presence::PresencePlugin);
+
+    app
+}
+
+/// Add the game states, plugins, and systems to an app already set up with the engine plugins.
+/// Used by [`build_app()`], and to run the game headless in tests.
+pub fn add_game_plugins(app: &mut App) {
+    // Initial state
+    let initial_state = AppState::Boot;
+    app.add_state(initial_state)
+        .add_state_to_stage(CoreStage::First, initial_state) // BUG #1671
+        .add_state_to_stage(CoreStage::PreUpdate, initial_state) // BUG #1671
+        .add_state_to_stage(CoreStage::PostUpdate, initial_state) // BUG #1671
+        .add_state_to_stage(CoreStage::Last, initial_state); // BUG #1671
+
+    app
+        // Save profiles, before the plugins loading the save data of the active profile
+        .add_plugin(ProfilePlugin)
+        // Cloud sync of the save files of the active profile, on launch and exit
+        .add_plugin(SaveSyncPlugin)
+        // Seeded randomness of the gameplay
+        .add_plugin(RngPlugin)
+        // Sound effects
+        .add_plugin(SfxPlugin)
+        // Captions of the sound effects, for deaf and hard-of-hearing players
+        .add_plugin(CaptionsPlugin)
+        // Input devices
+        .add_plugin(ControlsPlugin)
+        // Controls screen listing the bindings, from the main menu and in game
+        .add_plugin(ControlsScreenPlugin)
+        // Events
+        .add_event::<CheckLevelResultEvent>()
+        .add_event::<ResetPlateEvent>()
+        .add_event::<TilesSpawnedEvent>()
+        .add_event::<RestartLevelEvent>()
+        .add_event::<PlaceBuildableEvent>()
+        // Resources
+        .insert_resource(Grid::new())
+        .insert_resource(EntityManager::new())
+        // Gameplay types shown in the inspector
+        .register_type::<Cell>()
+        .register_type::<Grid>()
+        .register_type::<Cursor>()
+        .register_type::<Plate>()
+        // Asset loading
+        .add_plugin(TextAssetPlugin)
+        .add_plugin(SerializePlugin)
+        .add_plugin(LoaderPlugin)
+        .add_plugin(AssetLifetimePlugin)
+        // Animation
+        .add_plugin(TweeningPlugin)
+        .add_plugin(AnimPlugin)
+        .add_plugin(IdlePlugin)
+        .add_plugin(CinematicPlugin)
+        // Data-driven UI layouts, rebuilt when their file changes with the hot_reload feature
+        .add_plugin(UiLayoutPlugin)
+        // Registry of the UI images, packing the buildable frames into an atlas
+        .add_plugin(UiAtlasPlugin)
+        // Shadows of the buildables
+        .add_plugin(ShadowsPlugin)
+        // Game logic
+        .add_plugin(GamePlugin)
+        .add_plugin(RulesPlugin)
+        // Hard mode of the save profile, toggled in the main menu
+        .add_plugin(HardModePlugin)
+        // Bonus stages remixing the levels of a cleared world
+        .add_plugin(RemixPlugin)
+        // Daily challenge remixing a level drawn from the date, with its calendar in the menu
+        .add_plugin(DailyPlugin)
+        // Import of the levels shared as level codes, from a dialog in the menu
+        .add_plugin(LevelCodePlugin)
+        // Continuous victory of the levels cleared by keeping the plate balanced
+        .add_plugin(StabilizePlugin)
+        // Defeat panel to retry a failed level or go back to the menu
+        .add_plugin(DefeatPlugin)
+        // Easier level and assist suggestions after repeated failures
+        .add_plugin(AssistPlugin)
+        // Level management
+        .add_plugin(LevelPlugin)
+        // Inventory management
+        .add_plugin(InventoryPlugin)
+        // Journal of the level operations, from which replays and saves are derived
+        .add_plugin(JournalPlugin)
+        // Gamepad radial menu to pick a buildable
+        .add_plugin(RadialMenuPlugin)
+        // Weight left in the inventory against the weight needed to balance the plate
+        .add_plugin(WeightBudgetPlugin)
+        // Description of the selected buildable
+        .add_plugin(LorePlugin)
+        // Panel of the placed building under the cursor
+        .add_plugin(InspectPlugin)
+        // Top-down schematic of the plate
+        .add_plugin(MinimapPlugin)
+        // Labels following the 3D world on screen, like the weight popups
+        .add_plugin(WorldLabelPlugin)
+        // Scripted hints and feedback of the levels
+        .add_plugin(ScriptPlugin)
+        // Story interludes between the worlds and before some levels
+        .add_plugin(InterludePlugin)
+        // First-run tutorial with a card of the controls of the input device used
+        .add_plugin(OnboardingPlugin)
+        // Snapshot of the plate balance, read by the systems below
+        .add_plugin(BalancePlugin)
+        // Victory margin and COG visualization
+        .add_plugin(VictoryRingPlugin)
+        // End-of-level recap of the COG path
+        .add_plugin(RecapPlugin)
+        // Two plates linked by a beam in the seesaw levels
+        .add_plugin(SeesawPlugin)
+        // Tiles darkening and sagging under heavy buildables
+        .add_plugin(TileWearPlugin)
+        // Fragile tiles cracking and breaking under too much weight
+        .add_plugin(FragilePlugin)
+        // Conveyor tiles shifting the buildables placed on them
+        .add_plugin(ConveyorPlugin)
+        // Weather of the level, with its gameplay modifiers and ambience
+        .add_plugin(WeatherPlugin)
+        // Sky and clouds of the world of the level
+        .add_plugin(EnvironmentPlugin)
+        // Buildable materials recolored with the palette of the world of the level
+        .add_plugin(PalettePlugin)
+        // Color grade, vignette, and victory bloom over the scene
+        .add_plugin(PostProcessPlugin)
+        // Camera shake on heavy placements and topples
+        .add_plugin(ScreenShakePlugin)
+        // Quick save and load within a level
+        .add_plugin(QuickSavePlugin)
+        // Autosave of the level in progress, resumed after a crash
+        .add_plugin(AutosavePlugin)
+        // Practice mode rewinding the placements of a level
+        .add_plugin(PracticePlugin)
+        // Level scoring and score export
+        .add_plugin(ScoresPlugin)
+        .add_plugin(TelemetryPlugin)
+        // Best replay recording and ghost race
+        .add_plugin(GhostPlugin)
+        // Bug report bundles, with the recent inputs
+        .add_plugin(BugReportPlugin)
+        // Two-player versus mode
+        .add_plugin(VersusPlugin)
+        // Two-player cooperative mode
+        .add_plugin(CoopPlugin)
+        // Weighted random buildable queue
+        .add_plugin(MarketPlugin)
+        // Developer cheats, if enabled in the config
+        .add_plugin(CheatsPlugin)
+        // In-game log console, if enabled in the config
+        .add_plugin(ConsolePlugin)
+        // == Boot state ==
+        .add_plugin(BootPlugin)
+        // == MainMenu state ==
+        .add_plugin(MainMenuPlugin)
+        .add_plugin(CrashPlugin)
+        // Cosmetic skins and wardrobe menu
+        .add_plugin(WardrobePlugin)
+        // Encyclopedia of the buildables discovered so far
+        .add_plugin(EncyclopediaPlugin)
+        // Local playtime tracking and statistics page
+        .add_plugin(PlaytimePlugin)
+        // Level thumbnails for the level selection
+        .add_plugin(ThumbnailPlugin)
+        // == InGame state ==
+        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup3d.label("setup3d")))
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Input)
+                .with_system(player_actions_system.after("controls")),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Reset)
+                .after(GameplaySystem::Input)
+                .with_system(plate_reset_system.label("plate_reset"))
+                .with_system(spawn_tiles_system.after("plate_reset"))
+                .with_system(restart_level_system),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(run_if_playing)
+                .label(GameplaySystem::Cursor)
+                .after(GameplaySystem::Reset)
+                .with_system(cursor_movement_system),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(run_if_playing)
+                .label(GameplaySystem::Placement)
+                .after(GameplaySystem::Cursor)
+                .with_system(placement_system),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Balance)
+                .after(GameplaySystem::Placement)
+                .with_system(plate_balance_system.after("balance_state")),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Ui)
+                .after(GameplaySystem::VictoryCheck)
+                // .with_system(draw_debug_axes_system)
+                .with_system(cursor_validity_system)
+                .with_system(cursor_glide_system),
+        )
+        //.add_stage_after(CoreStage::Update, DEBUG, SystemStage::single_threaded())
+        // The entities are only marked for despawn, so other systems can still access them this
+        // frame; see https://github.com/bevyengine/bevy/issues/1743#issuecomment-806335175
+        .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(cleanup3d))
+        // == TheEnd state ==
+        .add_plugin(TheEndPlugin);
+}
+
+/// Perform the [`PlayerAction`]s not related to the cursor: restart the level, and change the
+/// selected slot. The cursor actions are buffered for [`cursor_movement_system`].
+fn player_actions_system(
+    cinematic: Res<CinematicMode>,
+    mut ev_action: EventReader<PlayerAction>,
+    mut ev_select_slot: EventWriter<SelectSlotEvent>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+) {
+    for action in ev_action.iter() {
+        if cinematic.is_enabled() {
+            continue;
+        }
+        match *action {
+            PlayerAction::Restart => ev_restart.send(RestartLevelEvent),
+            PlayerAction::CyclePrev => ev_select_slot.send(SelectSlotEvent(SelectSlot::Prev)),
+            PlayerAction::CycleNext => ev_select_slot.send(SelectSlotEvent(SelectSlot::Next)),
+            PlayerAction::SelectSlot(index) => {
+                ev_select_slot.send(SelectSlotEvent(SelectSlot::Index(index)))
+            }
+            PlayerAction::MoveCursor(_) | PlayerAction::Place => {}
+        }
+    }
+}
+
+fn create_line_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    );
+    mesh.set_indices(Some(Indices::U32(vec![0, 1])));
+    mesh
+}
+
+fn create_axes_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ],
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_COLOR,
+        vec![
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0],
+        ],
+    );
+    mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, 3, 4, 5])));
+    mesh
+}
+
+// #[cfg(debug_assertions)]
+// #[cfg(not(target_arch = "wasm32"))]
+// fn draw_debug_axes_system(mut query: Query<(&Plate, &Transform)>, mut lines: ResMut<DebugLines>) {
+//     // if let Ok((cursor, transform)) = query.single_mut() {
+//     //     //lines.line_colored(Vec3::ZERO, *transform * Vec3::X, 0.0, Color::RED);
+//     //     //lines.line_colored(Vec3::ZERO, *transform * Vec3::Y, 0.0, Color::GREEN);
+//     //     //lines.line_colored(Vec3::ZERO, *transform * Vec3::Z, 0.0, Color::BLUE);
+//     //     lines.line_colored(Vec3::ZERO, *transform * Vec3::Y, 0.0, Color::BLACK);
+//     // }
+// }
+
+#[cfg(target_arch = "wasm32")]
+fn draw_debug_axes_system() {}
+
+struct CheckLevelResultEvent();
+
+/// Spawn the entity of a buildable at the given grid position as a child of the plate, record it
+/// into the grid, and wobble the plate.
+fn spawn_buildable(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    plate: Entity,
+    pos: &IVec2,
+    bref: BuildableId,
+    buildable: &Buildable,
+) -> Entity {
+    let fpos = grid.fpos(pos);
+    debug!("Spawn buildable at pos={:?} fpos={:?}", pos, fpos);
+    let entity = commands
+        .spawn_bundle((
+            Transform::from_translation(grid.translation(pos, 0.1)),
+            GlobalTransform::identity(),
+        ))
+        .with_children(|parent| {
+            // Model under a pivot free to play the idle animation of the buildable
+            let mut pivot =
+                parent.spawn_bundle((Transform::identity(), GlobalTransform::identity()));
+            pivot.with_children(|parent| buildable.spawn_model(parent));
+            if let Some(idle) = buildable.idle() {
+                pivot.insert(IdleAnimation(idle.clone()));
+            }
+        })
+        .insert(Placed(*pos))
+        .insert(Parent(plate))
+        .id();
+    let weight = grid.instance_weight(pos, bref, buildable);
+    grid.spawn_item(pos, bref, weight, entity);
+    commands
+        .entity(plate)
+        .insert(PlayAnimation::new("plate_wobble"));
+    entity
+}
+
+/// Duration in seconds of the slide of a buildable to a neighbor cell.
+const SLIDE_TIME: f32 = 0.4;
+
+/// Slide a placed buildable to a free cell, moving its content in the grid, recording the move in
+/// the journal, and animating its entity there.
+fn slide_buildable(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    journal: &mut LevelJournal,
+    entity: Entity,
+    placed: &mut Placed,
+    transform: &Transform,
+    to: IVec2,
+) {
+    debug!("Slide buildable from {:?} to {:?}", placed.0, to);
+    grid.move_item(&placed.0, &to);
+    journal.record(LevelOp::Move { from: placed.0, to });
+    placed.0 = to;
+    commands.entity(entity).insert(Animator::new(Tween::new(
+        EaseFunction::QuadraticInOut,
+        TweeningType::Once,
+        Duration::from_secs_f32(SLIDE_TIME),
+        TransformPositionLens {
+            start: transform.translation,
+            end: grid.translation(&to, 0.1),
+        },
+    )));
+}
+
+/// Move the cursor around the grid, and request to place the buildable of the selected slot at
+/// the cursor position.
+fn cursor_movement_system(
+    mut ev_place: EventWriter<PlaceBuildableEvent>,
+    grid: Res<Grid>,
+    mut cursor_input: ResMut<CursorInput>,
+    cinematic: Res<CinematicMode>,
+    inventory: Res<Inventory>,
+    mut query: Query<&mut Cursor>,
+) {
+    let mut cursor = query.single_mut();
+    // If cursor is disabled or a cinematic is playing, do nothing
+    if !cursor.enabled() || cinematic.is_enabled() {
+        return;
+    }
+
+    // Perform the buffered cursor actions, in request order
+    while let Some(action) = cursor_input.pop() {
+        match action {
+            PlayerAction::MoveCursor(delta) => {
+                // Move cursor around the grid, gliding through each cell
+                let pos = grid.clamp(cursor.pos + delta);
+                if pos != cursor.pos {
+                    cursor.pos = pos;
+                    cursor.path.push_back(pos);
+                }
+            }
+            PlayerAction::Place => {
+                // Request to place the selected buildable at cursor position
+                if let Some(slot) = inventory.selected_slot().filter(|slot| !slot.is_empty()) {
+                    ev_place.send(PlaceBuildableEvent {
+                        pos: cursor.pos,
+                        bref: slot.bref(),
+                        cursor: CursorId::Player,
+                    });
+                }
+            }
+            // Other actions are not buffered
+            _ => {}
+        }
+    }
+}
+
+/// Duration in seconds of the glide of the cursor from one cell to the next.
+const CURSOR_GLIDE_TIME: f32 = 0.08;
+
+/// Translation of the cursor entity over the given cell.
+fn cursor_translation(grid: &Grid, pos: IVec2) -> Vec3 {
+    grid.translation(&pos, 0.1)
+}
+
+/// Glide the cursor entity through the cells the cursor moved across, one cell at a time. The
+/// glide speeds up while more moves are queued, so the cursor never lags far behind.
+fn cursor_glide_system(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    mut query: Query<(Entity, &mut Cursor, &mut Transform, &mut TranslationOffset)>,
+    animator_query: Query<&Animator<TranslationOffset>>,
+) {
+    let (entity, mut cursor, mut transform, mut offset) = query.single_mut();
+    if cursor.snapped {
+        // Moved with Cursor::set_pos(), drop the glide to the previous cell
+        cursor.snapped = false;
+        commands
+            .entity(entity)
+            .remove::<Animator<TranslationOffset>>();
+        offset.0 = Vec3::ZERO;
+        transform.translation = cursor_translation(&grid, cursor.glide_pos);
+        return;
+    }
+    let gliding = animator_query
+        .get(entity)
+        .is_ok_and(|animator| animator.progress() < 1.0);
+    if !gliding {
+        if let Some(next) = cursor.path.pop_front() {
+            // Start from wherever the cursor entity currently is
+            let from = cursor_translation(&grid, cursor.glide_pos) + offset.0;
+            cursor.glide_pos = next;
+            let duration = CURSOR_GLIDE_TIME / (1 + cursor.path.len()) as f32;
+            commands.entity(entity).insert(Animator::new(Tween::new(
+                EaseFunction::QuadraticOut,
+                TweeningType::Once,
+                Duration::from_secs_f32(duration),
+                TranslationOffsetLens {
+                    start: from - cursor_translation(&grid, next),
+                    end: Vec3::ZERO,
+                },
+            )));
+            transform.translation = from;
+            return;
+        }
+    }
+    transform.translation = cursor_translation(&grid, cursor.glide_pos) + offset.0;
+}
+
+/// Place the buildables requested with [`PlaceBuildableEvent`], taking them out of the inventory
+/// of the cursor placing them, then deliver any additional inventory, select the next non-empty
+/// slot of that cursor, and trigger the level result check once the inventories are empty.
+fn placement_system(
+    mut commands: Commands,
+    mut ev_place: EventReader<PlaceBuildableEvent>,
+    mut ev_check_level: EventWriter<CheckLevelResultEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    mut ev_play_sfx: EventWriter<PlaySfxEvent>,
+    mut ev_delivery: EventWriter<DeliveryEvent>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    mut ev_game: EventWriter<GameEvent>,
+    mut grid: ResMut<Grid>,
+    buildables: Res<BuildableRegistry>,
+    mut inventory: ResMut<Inventory>,
+    mut coop: ResMut<Coop>,
+    mut journal: ResMut<LevelJournal>,
+    mut build_queue: ResMut<BuildQueue>,
+    mut cursor_input: ResMut<CursorInput>,
+    mut query: Query<(&Cursor, &mut Visibility)>,
+) {
+    let (cursor, mut visible) = query.single_mut();
+    for ev in ev_place.iter() {
+        if !grid.can_spawn_item(&ev.pos) {
+            debug!("Cannot spawn buildable at occupied pos={:?}", ev.pos);
+            ev_play_sfx.send(PlaySfxEvent(Sfx::PlacementError));
+            continue;
+        }
+        let buildable = match buildables.get(ev.bref) {
+            Some(buildable) => buildable,
+            None => continue,
+        };
+        let source = match ev.cursor {
+            CursorId::Player => &mut *inventory,
+            CursorId::Partner => coop.inventory_mut(),
+        };
+        if !source.take_item(ev.bref) {
+            debug!(
+                "No buildable {:?} left to place at pos={:?} for {:?}",
+                ev.bref, ev.pos, ev.cursor
+            );
+            continue;
+        }
+        spawn_buildable(
+            &mut commands,
+            &mut grid,
+            cursor.spawn_root_entity,
+            &ev.pos,
+            ev.bref,
+            buildable,
+        );
+        journal.record(LevelOp::Place {
+            pos: ev.pos,
+            bref: ev.bref,
+            cursor: ev.cursor,
+        });
+        ev_game.send(GameEvent::BuildablePlaced {
+            pos: ev.pos,
+            buildable: ev.bref,
+        });
+        // Deliver any additional inventory triggered by this placement. The deliveries count the
+        // placements of both cursors, and always go to the player inventory.
+        for delivery in inventory.record_placement() {
+            if inventory.deliver(&delivery) {
+                ev_regen_ui.send(RegenerateInventoryUiEvent);
+            }
+            ev_delivery.send(DeliveryEvent(delivery));
+        }
+        // In market mode, draw the next buildable from the queue
+        if let Some(bref) = build_queue.next() {
+            inventory.add_items(bref, 1);
+            journal.record(LevelOp::Refill { bref, count: 1 });
+        }
+        // Check if current slot of the cursor has any item available left
+        let source = match ev.cursor {
+            CursorId::Player => &mut *inventory,
+            CursorId::Partner => coop.inventory_mut(),
+        };
+        if source.selected_slot().map_or(true, Slot::is_empty) {
+            // Try to select another slot with some item(s) left
+            if let Some(slot_index) = source.find_non_empty_slot_index() {
+                source.select_slot(&SelectSlot::Index(slot_index as usize));
+            } else if ev.cursor == CursorId::Player {
+                // No more of any item in any slot; hide cursor and check level result, which
+                // waits for the partner in coop mode
+                visible.is_visible = false;
+                ev_check_level.send(CheckLevelResultEvent {});
+                cursor_input.clear();
+            } else if inventory.is_empty() {
+                // The partner placed their last buildable after the player
+                ev_check_level.send(CheckLevelResultEvent {});
+            }
+        }
+        ev_update_slots.send(UpdateInventorySlots);
+    }
+}
+
+/// Restart the current level, clearing the grid and resetting the inventory.
+fn restart_level_system(
+    mut commands: Commands,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    mut ev_level_error: EventWriter<LevelErrorEvent>,
+    mut ev_unloading: EventWriter<LevelUnloading>,
+    mut grid: ResMut<Grid>,
+    level: Res<Level>,
+    mut inventory: ResMut<Inventory>,
+    mut query: Query<&mut Visibility, With<Cursor>>,
+) {
+    // Consume all restart events, do the work once
+    if ev_restart.iter().last().is_some() {
+        let level_desc = match level.desc() {
+            Some(level_desc) => level_desc,
+            None => {
+                ev_level_error.send(LevelErrorEvent(Error::LevelNotFound(level.index())));
+                return;
+            }
+        };
+        // Clear grid
+        grid.clear(Some(&mut commands));
+        ev_unloading.send(LevelUnloading(UnloadReason::Restart));
+        // Reset inventory
+        inventory.reset_from_level(level_desc);
+        // Re-show cursor
+        query.single_mut().is_visible = true;
+        // Update inventory slots
+        ev_update_slots.send(UpdateInventorySlots);
+    }
+}
+
+/// Tint the cursor depending on whether the cell under it can receive a buildable.
+fn cursor_validity_system(
+    grid: Res<Grid>,
+    mut query: Query<(&Cursor, &mut Handle<StandardMaterial>)>,
+) {
+    let (cursor, mut material) = query.single_mut();
+    if !cursor.enabled() {
+        return;
+    }
+    let mat = cursor.validity_material(grid.can_spawn_item(&cursor.pos));
+    if *material != *mat {
+        *material = mat.clone();
+    }
+}
+
+fn plate_balance_system(
+    level: Res<Level>,
+    balance: Res<BalanceState>,
+    mut query: Query<(&Plate, &RotationOffset, &mut Transform)>,
+) {
+    let (plate, offset, mut transform) = query.single_mut();
+    // Nothing to balance until a level is loaded
+    if level.desc().is_none() {
+        return;
+    }
+    // The plates of a seesaw tilt on their own, see [`SeesawPlugin`]
+    transform.rotation = balance.rotation * offset.0;
+}
+
+fn create_grid_image() -> Image {
+    const TEX_SIZE: u32 = 32;
+    let mut data = Vec::<u8>::with_capacity(TEX_SIZE as usize * TEX_SIZE as usize * 4);
+    for j in 0..TEX_SIZE {
+        for i in 0..TEX_SIZE {
+            if i == 0 || i == TEX_SIZE - 1 || j == 0 || j == TEX_SIZE - 1 {
+                data.push(192);
+                data.push(192);
+                data.push(192);
+                data.push(255);
+            } else {
+                data.push(128);
+                data.push(128);
+                data.push(128);
+                data.push(255);
+            }
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: TEX_SIZE,
+            height: TEX_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+    )
+}
+
+/// set up a simple 3D scene
+fn setup3d(
+    mut clear_color: ResMut<ClearColor>,
+    mut entity_manager: ResMut<EntityManager>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+) {
+    // Set clear color to background color
+    clear_color.0 = Color::rgb(0.15, 0.15, 0.15);
+
+    // Grid size is set from the level data when the level is loaded and the plate reset
+
+    // Create grid material
+    let grid_image = images.add(create_grid_image());
+    let grid_material = materials.add(StandardMaterial {
+        base_color_texture: Some(grid_image),
+        //unlit: true,
+        ..Default::default()
+    });
+    grid.set_material(grid_material.clone());
+
+    // // Axes
+    // commands.spawn_bundle(PbrBundle {
+    //     mesh: meshes.add(create_axes_mesh()),
+    //     material: materials.add(StandardMaterial {
+    //         base_color: Color::rgba(1.0, 1.0, 1.0, 0.0),
+    //         unlit: true,
+    //         ..Default::default()
+    //     }),
+    //     transform: Transform::from_scale(Vec3::new(5.0, 5.0, 5.0)),
+    //     ..Default::default()
+    // });
+
+    // // plane
+    // commands.spawn_bundle(PbrBundle {
+    //     mesh: meshes.add(Mesh::from(shape::Plane { size: 5.0 })),
+    //     material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+    //     ..Default::default()
+    // });
+
+    // Plate
+    let mut plate_cmds = commands.spawn();
+    let plate = plate_cmds.id();
+    entity_manager.all_entities.push(plate);
+    plate_cmds
+        .insert(Name::new("Plate"))
+        .insert(Transform::identity())
+        .insert(GlobalTransform::identity())
+        .insert(RotationOffset::default())
+        .insert(Plate::new(plate));
+
+    // Grid blocks
+    let cell_mesh = meshes.add(Mesh::from(shape::Box::new(1.0, TILE_THICKNESS, 1.0)));
+    grid.regenerate(&mut commands, cell_mesh.clone(), plate);
+
+    // Cursor
+    let cursor_mesh = meshes.add(Mesh::from(shape::Cube { size: 0.9 }));
+    let cursor_mat = materials.add(Color::rgb(0.6, 0.7, 0.8).into());
+    let cursor_valid_mat = materials.add(Color::rgb(0.5, 0.85, 0.55).into());
+    let cursor_invalid_mat = materials.add(Color::rgb(0.9, 0.4, 0.4).into());
+    let cursor_fpos = grid.fpos(&IVec2::ZERO);
+    debug!("Spawn cursor at fpos={:?}", cursor_fpos);
+    let mut cursor_entity_cmds = commands.spawn_bundle(PbrBundle {
+        mesh: cursor_mesh.clone(),
+        material: cursor_mat.clone(),
+        transform: Transform::from_translation(grid.translation(&IVec2::ZERO, 0.1))
+            * Transform::from_scale(Vec3::new(1.0, 0.3, 1.0)),
+        ..Default::default()
+    });
+    cursor_entity_cmds
+        .insert(Name::new("Cursor"))
+        .insert(NotShadowCaster)
+        .insert(PlayAnimation::new("cursor_pulse"))
+        .insert(TranslationOffset::default())
+        .insert(Parent(plate));
+    let mut cursor = Cursor::new(cursor_entity_cmds.id(), plate);
+    cursor.set_cursor(cursor_mesh, cursor_mat);
+    cursor.set_validity_materials(cursor_valid_mat, cursor_invalid_mat);
+    cursor_entity_cmds.insert(cursor);
+
+    // Light
+    let light = commands
+        .spawn_bundle(DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                illuminance: 10000.0,
+                ..Default::default()
+            },
+            transform: Transform::from_rotation(Quat::from_euler(
+                EulerRot::YXZ,
+                30_f32.to_degrees(),
+                30_f32.to_degrees(),
+                0.,
+            )),
+            ..Default::default()
+        })
+        .id();
+    entity_manager.all_entities.push(light);
+
+    // Camera
+    let camera = commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(-3.0, 3.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+            // perspective_projection: PerspectiveProjection {
+            //     fov: 60.0,
+            //     aspect_ratio: 1.0,
+            //     near: 0.01,
+            //     far: 100.0,
+            // },
+            ..Default::default()
+        })
+        .id();
+    entity_manager.all_entities.push(camera);
+
+    // UI camera
+    let ui_camera = commands.spawn_bundle(UiCameraBundle::default()).id();
+    entity_manager.all_entities.push(ui_camera);
+
+    // Level name
+    let level_name = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..Default::default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Name::new("LevelName"))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_self: AlignSelf::FlexEnd,
+                        position_type: PositionType::Relative,
+                        position: Rect {
+                            top: Val::Auto,
+                            bottom: Val::Auto,
+                            left: Val::Px(0.0),
+                            right: Val::Px(0.0),
+                        },
+                        size: Size::new(Val::Percent(100.), Val::Px(120.)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::NONE), //Color::rgb(131. / 255., 156. / 255., 144. / 255.)),
+                    ..Default::default()
+                })
+                .insert(Name::new("Background"))
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                align_self: AlignSelf::FlexEnd,
+                                position_type: PositionType::Absolute,
+                                position: Rect {
+                                    bottom: Val::Px(5.0),
+                                    left: Val::Px(15.0),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                String::new(), // set when the level is loaded
+                                TextStyle {
+                                    font: asset_server.load("fonts/pacifico/Pacifico-Regular.ttf"),
+                                    font_size: 100.0,
+                                    color: Color::rgb_u8(111, 188, 165),
+                                },
+                                TextAlignment {
+                                    horizontal: HorizontalAlign::Left,
+                                    ..Default::default()
+                                },
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(Name::new("Text"))
+                        .insert(LevelNameText); // marker to allow finding this text to change it
+                });
+        })
+        .id();
+    entity_manager.all_entities.push(level_name);
+
+    // Load first level by default (this allows skipping the main menu while developping)
+    ev_load_level.send(LoadLevelEvent(LoadLevel::ByIndex(0)));
+}
+
+fn cleanup3d(
+    //mut query: Query<(&mut Visible,)>,
+    mut entity_manager: ResMut<EntityManager>,
+    mut commands: Commands,
+    // mut query: Query<(&mut Transform,)>,
+    mut inventory: ResMut<Inventory>,
+    mut grid: ResMut<Grid>,
+    mut ev_unloading: EventWriter<LevelUnloading>,
+) {
+    // LAZY HACK -- Hide literally EVERYTHING since we didn't keep track of things we need to hide/despawn
+    // for (mut vis,) in query.iter_mut() {
+    //     vis.is_visible = false;
+    // }
+
+    trace!("Entities: {}", entity_manager.all_entities.len());
+    for ent in entity_manager.all_entities.iter() {
+        trace!("Entity: {:?}", *ent);
+        mark_for_despawn(&mut commands, *ent);
+    }
+    ev_unloading.send(LevelUnloading(UnloadReason::Quit));
+    entity_manager.all_entities.clear();
+
+    inventory.clear_entities(&mut commands);
+    grid.despawn(&mut commands);
+}
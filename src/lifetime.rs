@@ -0,0 +1,174 @@
+use bevy::{
+    asset::{Asset, HandleId},
+    prelude::*,
+};
+use bevy_kira_audio::AudioSource;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    serialize::{BuildableId, BuildableRegistry},
+    AppState, Level, ResetPlateEvent,
+};
+
+/// Scope of the assets tracked by the [`AssetLifetimes`], released when the scope ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetScope {
+    /// Assets of the main menu, released when starting a game.
+    MainMenu,
+    /// Assets of the level being played, released when changing level or leaving the game.
+    Level,
+}
+
+/// Resource holding the strong handles of the assets used by the current state and level.
+///
+/// Bevy frees an asset once no strong handle references it anymore, so assets only needed for a
+/// while are referenced elsewhere with weak handles, and kept alive here by their scope. Releasing
+/// a scope drops its handles, and with them all the assets not used by another scope. Without
+/// this, a long session keeps all the assets it ever loaded, which on the web build only grows
+/// memory.
+#[derive(Debug, Default)]
+pub struct AssetLifetimes {
+    scopes: HashMap<AssetScope, Vec<HandleUntyped>>,
+    /// Paths of the assets referenced by weak handles, to load them on demand.
+    paths: HashMap<HandleId, String>,
+}
+
+impl AssetLifetimes {
+    pub fn new() -> Self {
+        AssetLifetimes::default()
+    }
+
+    /// Weak handle to an asset from its path, which neither loads the asset nor keeps it loaded.
+    /// The asset is loaded on demand with [`load()`].
+    ///
+    /// [`load()`]: AssetLifetimes::load
+    pub fn weak_handle<T: Asset>(&mut self, path: &str) -> Handle<T> {
+        let id = HandleId::from(path);
+        self.paths.insert(id, path.to_owned());
+        Handle::weak(id)
+    }
+
+    /// Load an asset referenced by a weak handle from [`weak_handle()`], returning a strong
+    /// handle to keep it loaded.
+    ///
+    /// [`weak_handle()`]: AssetLifetimes::weak_handle
+    pub fn load(&self, id: HandleId, asset_server: &AssetServer) -> Option<HandleUntyped> {
+        self.paths
+            .get(&id)
+            .map(|path| asset_server.load_untyped(&path[..]))
+    }
+
+    /// Keep an asset loaded until the end of the given scope.
+    pub fn track(&mut self, scope: AssetScope, handle: HandleUntyped) {
+        self.scopes.entry(scope).or_default().push(handle);
+    }
+
+    /// Replace all the assets of a scope. Assets both in the old and new sets stay loaded.
+    pub fn replace(&mut self, scope: AssetScope, handles: Vec<HandleUntyped>) {
+        self.scopes.insert(scope, handles);
+    }
+
+    /// Release all the assets of a scope, and return how many handles were dropped.
+    pub fn release(&mut self, scope: AssetScope) -> usize {
+        self.scopes
+            .remove(&scope)
+            .map_or(0, |handles| handles.len())
+    }
+
+    /// Number of assets kept loaded by a scope.
+    pub fn count(&self, scope: AssetScope) -> usize {
+        self.scopes.get(&scope).map_or(0, |handles| handles.len())
+    }
+}
+
+/// Load the 3D models and parts of the buildables of the level being started, and release those
+/// of the previous level not used anymore. The [`BuildableRegistry`] only holds weak handles to
+/// the models, so that the game data doesn't keep all of them loaded.
+fn track_level_assets(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    buildables: Res<BuildableRegistry>,
+    asset_server: Res<AssetServer>,
+    mut lifetimes: ResMut<AssetLifetimes>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+
+    // All the buildables which can be placed during the level, including deliveries and market
+    // draws, since the mode of the game is not known here
+    let mut brefs: HashSet<BuildableId> = level_desc.inventory.keys().copied().collect();
+    for delivery in &level_desc.deliveries {
+        brefs.extend(delivery.inventory.keys().copied());
+    }
+    brefs.extend(level_desc.market().weights.iter().map(|(bref, _)| *bref));
+
+    // Load the new handles before dropping the old ones, to keep loaded the models shared with
+    // the previous level
+    let handles: Vec<HandleUntyped> = brefs
+        .into_iter()
+        .filter_map(|bref| buildables.get(bref))
+        .flat_map(|buildable| buildable.model_handle_ids())
+        .filter_map(|id| lifetimes.load(id, &asset_server))
+        .collect();
+    debug!(
+        "Asset lifetimes: level '{}' uses {} models (was {})",
+        level_desc.name,
+        handles.len(),
+        lifetimes.count(AssetScope::Level)
+    );
+    lifetimes.replace(AssetScope::Level, handles);
+}
+
+fn release_level_assets(mut lifetimes: ResMut<AssetLifetimes>) {
+    let count = lifetimes.release(AssetScope::Level);
+    debug!("Asset lifetimes: released {} level assets", count);
+}
+
+fn release_mainmenu_assets(mut lifetimes: ResMut<AssetLifetimes>) {
+    let count = lifetimes.release(AssetScope::MainMenu);
+    debug!("Asset lifetimes: released {} main menu assets", count);
+}
+
+/// Log the number of loaded assets after each state transition, to watch memory over long
+/// sessions.
+fn log_asset_counts(
+    state: Res<State<AppState>>,
+    meshes: Res<Assets<Mesh>>,
+    images: Res<Assets<Image>>,
+    materials: Res<Assets<StandardMaterial>>,
+    scenes: Res<Assets<Scene>>,
+    audio_sources: Res<Assets<AudioSource>>,
+) {
+    if state.is_changed() {
+        debug!(
+            "Assets in {:?}: {} meshes, {} images, {} materials, {} scenes, {} audio sources",
+            state.current(),
+            meshes.len(),
+            images.len(),
+            materials.len(),
+            scenes.len(),
+            audio_sources.len()
+        );
+    }
+}
+
+/// Plugin managing the lifetime of the assets, freeing on state and level transitions the assets
+/// not used anymore.
+pub struct AssetLifetimePlugin;
+
+impl Plugin for AssetLifetimePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AssetLifetimes::new())
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(track_level_assets))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(release_level_assets))
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(release_mainmenu_assets),
+            )
+            .add_system(log_asset_counts);
+    }
+}
@@ -35,7 +35,7 @@ pub enum State {
 ///
 /// # Example
 ///
-/// ```rust
+/// ```ignore
 /// // Create the loader and enqueue requests, generally from a startup system.
 /// fn setup(mut commands: Commands) {
 ///   let mut loader = Loader::new();
@@ -200,7 +200,7 @@ impl Loader {
         for path in request_queue.drain(..) {
             let handle = asset_server.load_untyped(&path[..]);
             // Only enqueue if not loaded; otherwise either the resource is already loading
-            // (need to wait), is loaded (nothing to do), or failed (no point retrying).
+            // (need to wait), is loaded (complete now), or failed (no point retrying).
             match asset_server.get_load_state(&handle) {
                 bevy::asset::LoadState::NotLoaded | bevy::asset::LoadState::Loading => {
                     trace!("Start loading asset: {} -> {:?}", path, &handle);
@@ -208,7 +208,10 @@ impl Loader {
                 }
                 bevy::asset::LoadState::Loaded | bevy::asset::LoadState::Failed | bevy::asset::LoadState::Unloaded => {
                     trace!("Asset: {} -> {:?}", path, &handle);
-                    self.count.fetch_sub(1, Ordering::Release);
+                    self.complete_queue.lock().insert(path, handle);
+                    if self.count.fetch_sub(1, Ordering::Acquire) == 1 {
+                        *self.state.write() = State::Done;
+                    }
                 }
             }
         }
@@ -0,0 +1,122 @@
+use bevy::{
+    log::LogSettings,
+    prelude::*,
+    utils::tracing::{
+        self,
+        field::{Field, Visit},
+        Event, Level, Subscriber,
+    },
+};
+use parking_lot::{const_mutex, Mutex};
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tracing_log::LogTracer;
+use tracing_subscriber::{layer::Context, prelude::*, registry::Registry, EnvFilter, Layer};
+
+/// Number of recent log lines kept in memory.
+const CAPACITY: usize = 256;
+
+/// Recent log lines, oldest first.
+static RECENT_LINES: Mutex<VecDeque<LogLine>> = const_mutex(VecDeque::new());
+
+/// Total number of lines captured since startup.
+static LINE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Single captured log line.
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: Level,
+    /// Module path the line was logged from.
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>5} {}: {}", self.level, self.target, self.message)
+    }
+}
+
+/// Copy of the recent log lines, oldest first. Returns nothing if the lines are being written,
+/// like when called from a panic raised while logging.
+pub fn recent_log_lines() -> Vec<LogLine> {
+    RECENT_LINES
+        .try_lock()
+        .map(|lines| lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Total number of log lines captured since startup, to detect new lines.
+pub fn log_line_count() -> usize {
+    LINE_COUNT.load(Ordering::Acquire)
+}
+
+/// Visitor formatting the fields of a log event into a single line.
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Tracing layer keeping the recent log lines in memory. Trace lines are too verbose to be
+/// useful in a report, and are skipped.
+struct CaptureLayer;
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() == Level::TRACE {
+            return;
+        }
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        let mut lines = RECENT_LINES.lock();
+        if lines.len() >= CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(LogLine {
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message: visitor.0,
+        });
+        LINE_COUNT.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Plugin setting up logging like Bevy's `LogPlugin`, which it replaces, additionally keeping the
+/// recent log lines in memory for the crash reports. Configured with the [`LogSettings`] resource.
+pub struct LoggingPlugin;
+
+impl Plugin for LoggingPlugin {
+    fn build(&self, app: &mut App) {
+        let default_filter = {
+            let settings = app.world.get_resource_or_insert_with(LogSettings::default);
+            format!("{},{}", settings.level, settings.filter)
+        };
+        LogTracer::init().unwrap();
+        let filter_layer = EnvFilter::try_from_default_env()
+            .or_else(|_| EnvFilter::try_new(&default_filter))
+            .unwrap();
+        let subscriber = Registry::default().with(filter_layer).with(CaptureLayer);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let subscriber = subscriber.with(tracing_subscriber::fmt::Layer::default());
+
+        #[cfg(target_arch = "wasm32")]
+        let subscriber = subscriber.with(tracing_wasm::WASMLayer::new(
+            tracing_wasm::WASMLayerConfig::default(),
+        ));
+
+        tracing::subscriber::set_global_default(subscriber)
+            .expect("Could not set global default tracing subscriber.");
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    game::GameplaySystem,
+    inventory::Inventory,
+    rules::{RevealedWeights, Rules},
+    serialize::BuildableRegistry,
+    AppState, Level,
+};
+
+/// Marker for the root of the lore panel.
+#[derive(Component)]
+struct LorePanel;
+
+/// Marker for the text of the lore panel.
+#[derive(Component)]
+struct LoreText;
+
+fn spawn_lore_panel(mut commands: Commands, ui_resources: Res<UiResources>) {
+    let font = ui_resources.text_font();
+    let style = |font_size, color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(100.0),
+                    right: Val::Px(20.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(320.0), Val::Auto),
+                padding: Rect::all(Val::Px(12.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.4)),
+            ..Default::default()
+        })
+        .insert(Name::new("LorePanel"))
+        .insert(Hud)
+        .insert(LorePanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        max_size: Size::new(Val::Px(296.0), Val::Undefined),
+                        ..Default::default()
+                    },
+                    text: Text {
+                        sections: vec![
+                            // Name
+                            TextSection {
+                                value: String::new(),
+                                style: style(28.0, Color::rgb_u8(255, 220, 120)),
+                            },
+                            // Weight and placement rules
+                            TextSection {
+                                value: String::new(),
+                                style: style(18.0, Color::rgb_u8(200, 200, 200)),
+                            },
+                            // Description
+                            TextSection {
+                                value: String::new(),
+                                style: style(18.0, Color::WHITE),
+                            },
+                        ],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(LoreText);
+        });
+}
+
+/// Display the name, weight, placement rules, and description of the buildable of the selected
+/// inventory slot.
+fn update_lore_panel(
+    inventory: Res<Inventory>,
+    buildables: Res<BuildableRegistry>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    revealed: Res<RevealedWeights>,
+    mut panel_query: Query<&mut Visibility, With<LorePanel>>,
+    mut text_query: Query<&mut Text, With<LoreText>>,
+) {
+    if !inventory.is_changed() && !revealed.is_changed() && !rules.is_changed() {
+        return;
+    }
+    let selected = inventory
+        .selected_slot()
+        .filter(|slot| !slot.is_empty())
+        .and_then(|slot| buildables.get(slot.bref()).map(|b| (slot.bref(), b)));
+    for mut visibility in panel_query.iter_mut() {
+        visibility.is_visible = selected.is_some();
+    }
+    let (bref, buildable) = match selected {
+        Some(selected) => selected,
+        None => return,
+    };
+
+    let mut details = format!(
+        "\nWeight: {}",
+        rules.weight_text(&revealed, bref, buildable, level.weight_unit())
+    );
+    if rules.hidden_weights && !revealed.contains(bref) {
+        details.push_str("\nThe weight is revealed once placed.");
+    }
+    if !buildable.stackable() {
+        details.push_str("\nOne per cell, cannot be stacked.");
+    }
+    let description = if buildable.description().is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}", buildable.description())
+    };
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = buildable.name().to_owned();
+        text.sections[1].value = details.clone();
+        text.sections[2].value = description.clone();
+    }
+}
+
+fn lore_cleanup(mut commands: Commands, query: Query<Entity, With<LorePanel>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the side panel describing the buildable of the selected inventory slot.
+pub struct LorePlugin;
+
+impl Plugin for LorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_lore_panel))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_lore_panel),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(lore_cleanup));
+    }
+}
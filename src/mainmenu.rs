@@ -1,43 +1,78 @@
 use crate::{
+    anim::PlayAnimation,
     boot::UiResources,
-    inventory::Buildable,
+    fallback::fallback_game_data,
+    game::GameMode,
+    interlude::Interludes,
+    inventory::{Buildable, BuildablePart, Skin},
+    lifetime::{AssetLifetimes, AssetScope},
     loader::Loader,
-    serialize::{BuildableRef, Buildables, GameDataArchive, LevelDesc, Levels},
+    serialize::{BuildablePartArchive, BuildableRegistry, GameDataArchive, Levels},
     text_asset::TextAsset,
+    ui_atlas::UiImages,
+    ui_layout::{UiLayouts, UiLayoutsReloadedEvent},
+    wardrobe::{Wardrobe, WardrobeMenu},
     AppState, Config, Error,
 };
 use bevy::{app::AppExit, prelude::*};
 use bevy_kira_audio::{Audio, AudioSource};
-use bevy_tweening::{
-    lens::{TextColorLens, UiPositionLens},
-    Animator, EaseFunction, EaseMethod, Tween, TweeningType,
-};
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc};
 
 /// Main menu component.
 #[derive(Component)]
 struct MainMenu {
     can_start: bool,
+    /// Is the game data the offline tutorial embedded in the binary, because `levels.json` is
+    /// missing or invalid?
+    offline: bool,
     //root_entity: Entity,
     entities: Vec<Entity>,
+    /// Root node of the menu layout, rebuilt when the layouts are reloaded.
+    layout: Option<Entity>,
 }
 
 impl MainMenu {
     pub fn new() -> Self {
         MainMenu {
             can_start: false,
+            offline: false,
             entities: vec![],
+            layout: None,
         }
     }
 }
 
-#[derive(Component)]
-struct StatusText;
+/// Marker for the status text, with the menu entries listed under it.
+#[derive(Component, Default)]
+struct StatusText {
+    /// Were the menu entries listed?
+    entries_shown: bool,
+}
+
+/// Entries of the main menu, with the key starting each game mode, listed once the game data is
+/// loaded.
+const MENU_ENTRIES: [&str; 11] = [
+    "[ENTER] Start",
+    "[H] Hidden weights",
+    "[M] Market",
+    "[V] 2-player versus",
+    "[C] 2-player coop",
+    "[D] Daily challenge",
+    "[I] Import a level code",
+    "[W] Wardrobe",
+    "[K] Encyclopedia",
+    "[S] Statistics",
+    "[F6] Controls",
+];
+
+/// Entries of the main menu when the game data failed to load, playing the offline tutorial
+/// embedded in the binary instead.
+const OFFLINE_MENU_ENTRIES: [&str; 2] = ["[ENTER] Play the offline tutorial", "[ESC] Quit"];
 
 fn mainmenu_setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
     ui_resouces: Res<UiResources>,
+    ui_layouts: Res<UiLayouts>,
     //mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     // Start loading game assets
@@ -45,14 +80,6 @@ fn mainmenu_setup(
     loader.enqueue("levels.json");
     loader.submit();
 
-    let title_font = ui_resouces.title_font();
-    let text_font = ui_resouces.text_font();
-
-    let text_align = TextAlignment {
-        horizontal: HorizontalAlign::Center,
-        vertical: VerticalAlign::Center,
-    };
-
     let mut menu_data = MainMenu::new();
 
     // // Root
@@ -83,148 +110,8 @@ fn mainmenu_setup(
             .id(),
     );
 
-    let transparent_color = Color::NONE;
-    let background_color = Color::rgb(0.15, 0.15, 0.15);
-    let title_color = Color::rgb_u8(111, 188, 165);
-
-    // Background filling the entire screen
-    // Also using that as the hack of https://github.com/bevyengine/bevy/issues/676 to align the text
-    let root = commands
-        .spawn_bundle(NodeBundle {
-            style: Style {
-                position: Rect::all(Val::Px(0.0)),
-                position_type: PositionType::Absolute,
-                ..Default::default()
-            },
-            color: UiColor(background_color),
-            ..Default::default()
-        })
-        .id();
-    menu_data.entities.push(root);
-
-    // Title
-    let title_tween = Tween::new(
-        EaseFunction::QuadraticInOut,
-        TweeningType::Once,
-        Duration::from_secs(3),
-        UiPositionLens {
-            start: Rect {
-                left: Val::Px(0.0),
-                right: Val::Px(0.0),
-                top: Val::Px(30.0),
-                bottom: Val::Px(0.0),
-            },
-            end: Rect::all(Val::Px(0.0)),
-        },
-    );
-    let text_tween = Tween::new(
-        EaseMethod::Linear,
-        TweeningType::Once,
-        Duration::from_secs(3),
-        TextColorLens {
-            start: background_color, // BUG #3204 // transparent_color,
-            end: title_color,
-            section: 0,
-        },
-    );
-    menu_data.entities.push(
-        commands
-            .spawn_bundle(NodeBundle {
-                style: Style {
-                    min_size: Size::new(Val::Px(800.0), Val::Px(300.0)),
-                    position: Rect::all(Val::Px(0.0)),
-                    position_type: PositionType::Absolute,
-
-                    // I expect one of these to center the text in the node
-                    align_content: AlignContent::Center,
-                    align_items: AlignItems::Center,
-                    align_self: AlignSelf::Center,
-
-                    // this line aligns the content
-                    justify_content: JustifyContent::Center,
-
-                    ..Default::default()
-                },
-                color: UiColor(transparent_color),
-                ..Default::default()
-            })
-            .insert(Animator::new(title_tween))
-            .insert(Parent(root))
-            .with_children(|parent| {
-                // Title itself
-                parent
-                    .spawn_bundle(TextBundle {
-                        text: Text::with_section(
-                            "Libra City",
-                            TextStyle {
-                                font: title_font.clone(),
-                                font_size: 250.0,
-                                color: background_color,
-                            },
-                            text_align,
-                        ),
-                        ..Default::default()
-                    })
-                    .insert(Animator::new(text_tween));
-            })
-            .id(),
-    );
-    menu_data.entities.push(
-        commands
-            .spawn_bundle(NodeBundle {
-                style: Style {
-                    min_size: Size::new(Val::Px(800.0), Val::Px(300.0)),
-                    position: Rect {
-                        bottom: Val::Px(100.0),
-                        left: Val::Px(0.0),
-                        right: Val::Px(0.0),
-                        ..Default::default()
-                    },
-                    position_type: PositionType::Absolute,
-                    align_content: AlignContent::Center,
-                    align_items: AlignItems::Center,
-                    align_self: AlignSelf::Center,
-                    justify_content: JustifyContent::Center,
-                    ..Default::default()
-                },
-                color: UiColor(Color::rgb(0.15, 0.15, 0.15)),
-                ..Default::default()
-            })
-            .insert(Parent(root))
-            .with_children(|parent| {
-                // Title itself
-                parent
-                    .spawn_bundle(TextBundle {
-                        text: Text {
-                            sections: vec![
-                                TextSection {
-                                    value: "Loading...".to_string(),
-                                    style: TextStyle {
-                                        font: text_font.clone(),
-                                        font_size: 40.0,
-                                        color: Color::WHITE,
-                                    },
-                                },
-                                TextSection {
-                                    value: "\nThis game plays with a keyboard only".to_string(),
-                                    style: TextStyle {
-                                        font: text_font.clone(),
-                                        font_size: 20.0,
-                                        color: Color::GRAY,
-                                    },
-                                },
-                            ],
-                            alignment: TextAlignment {
-                                vertical: VerticalAlign::Center,
-                                horizontal: HorizontalAlign::Center,
-                            },
-                        },
-                        ..Default::default()
-                    })
-                    .insert(StatusText);
-            })
-            .id(),
-    );
+    // Background, title, and status text, from the layouts
+    menu_data.layout = spawn_menu_layout(&mut commands, &ui_layouts, &ui_resouces);
 
     // Spawn main menu
     commands
@@ -234,31 +121,78 @@ fn mainmenu_setup(
         .insert(loader);
 }
 
+/// Spawn the nodes of the menu from its layout, returning the root node.
+fn spawn_menu_layout(
+    commands: &mut Commands,
+    ui_layouts: &UiLayouts,
+    ui_resources: &UiResources,
+) -> Option<Entity> {
+    let layout = ui_layouts.spawn(commands, ui_resources, "main_menu")?;
+    match layout.node("StatusText") {
+        Some(status_text) => {
+            commands.entity(status_text).insert(StatusText::default());
+        }
+        None => error!("Main menu layout without a StatusText node"),
+    }
+    Some(layout.root())
+}
+
+/// Rebuild the menu from the layouts when they are reloaded.
+fn rebuild_menu_layout(
+    mut commands: Commands,
+    mut ev_reloaded: EventReader<UiLayoutsReloadedEvent>,
+    mut menu_query: Query<&mut MainMenu>,
+    ui_layouts: Res<UiLayouts>,
+    ui_resources: Res<UiResources>,
+) {
+    if ev_reloaded.iter().last().is_none() {
+        return;
+    }
+    for mut main_menu in menu_query.iter_mut() {
+        if let Some(root) = main_menu.layout.take() {
+            commands.entity(root).despawn_recursive();
+        }
+        main_menu.layout = spawn_menu_layout(&mut commands, &ui_layouts, &ui_resources);
+    }
+}
+
 fn mainmenu(
     asset_server: Res<AssetServer>,
     mut menu_query: Query<(&mut Loader, &mut MainMenu)>,
-    mut status_text_query: Query<&mut Text, With<StatusText>>,
     mut keyboard_input: ResMut<Input<KeyCode>>,
     mut state: ResMut<State<AppState>>,
     text_assets: Res<Assets<TextAsset>>,
-    commands: Commands,
     mut levels_res: ResMut<Levels>,
-    mut buildables_res: ResMut<Buildables>,
+    mut buildables_res: ResMut<BuildableRegistry>,
+    mut interludes_res: ResMut<Interludes>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut exit: EventWriter<AppExit>,
+    mut game_mode: ResMut<GameMode>,
+    wardrobe: Res<Wardrobe>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    mut lifetimes: ResMut<AssetLifetimes>,
+    mut ui_images: ResMut<UiImages>,
 ) {
     let (mut loader, mut main_menu) = menu_query.single_mut();
     // Once all assets are loaded, allow the user to start playing
     if loader.is_done() {
-        // Retrieve and parse JSON, load assets from it
-        let handle = loader.take("levels.json").unwrap().typed::<TextAsset>();
-        let json_content = text_assets.get(handle).unwrap();
-        let mut game_data_archive = match GameDataArchive::from_json(&json_content.value[..]) {
-            Ok(game_data_archive) => game_data_archive,
-            Err(err) => {
-                error!("Error loading game data: {:?}", err);
-                exit.send(AppExit);
-                return;
+        // Retrieve and parse JSON, load assets from it. If missing or invalid, fall back to the
+        // offline tutorial embedded in the binary, so the game remains playable.
+        let game_data = loader
+            .take("levels.json")
+            .and_then(|handle| text_assets.get(handle.typed::<TextAsset>()))
+            .map(|json_content| GameDataArchive::from_json(&json_content.value[..]));
+        let game_data_archive = match game_data {
+            Some(Ok(game_data_archive)) => game_data_archive,
+            Some(Err(err)) => {
+                error!("Error loading game data, playing offline: {:?}", err);
+                main_menu.offline = true;
+                fallback_game_data()
+            }
+            None => {
+                error!("Missing game data levels.json, playing offline");
+                main_menu.offline = true;
+                fallback_game_data()
             }
         };
 
@@ -269,92 +203,299 @@ fn mainmenu(
         let color_selected = Color::rgba(1.0, 1.0, 1.0, 1.0);
         let color_empty = Color::rgba(1.0, 0.8, 0.8, 0.5);
 
-        // Load referenced assets
-        let mut buildables = HashMap::new();
-        for (item_name, rules) in game_data_archive.inventory.iter() {
-            // Load 3D model
-            let mesh: Handle<Scene> = asset_server.load(&format!("models/{}", rules.model)[..]);
-            let material = materials.add(StandardMaterial {
-                // TODO - from file?
-                base_color: Color::rgb(0.8, 0.7, 0.6),
-                ..Default::default()
-            });
-
-            // Load 2D frame
-            let frame_image: Handle<Image> =
-                asset_server.load(&format!("textures/{}", rules.frame)[..]);
+        // All buildables share the same material
+        let material = materials.add(StandardMaterial {
+            // TODO - from file?
+            base_color: Color::rgb(0.8, 0.7, 0.6),
+            ..Default::default()
+        });
+
+        // Load referenced assets. Register buildables in name order so that their identifiers
+        // do not depend on the hash map iteration order.
+        let mut buildables = BuildableRegistry::new();
+        let mut item_names: Vec<_> = game_data_archive.buildables.keys().cloned().collect();
+        item_names.sort();
+        for item_name in item_names.iter() {
+            let rules = &game_data_archive.buildables[item_name];
+            // Reference the 3D model, loaded only while playing a level using it
+            let mesh: Handle<Scene> = lifetimes.weak_handle(&format!("models/{}", rules.model));
+
+            // Load 2D frame, packed into the UI atlas with the other frames
+            let frame_image = ui_images.load(&rules.frame, &asset_server);
 
             // Create Buildable
-            buildables.insert(
-                BuildableRef(item_name.clone()),
-                Buildable::new(
-                    &rules.name,
-                    rules.weight,
-                    false,
-                    mesh,
-                    material,
-                    frame_image,
-                    color_unselected,
-                    color_selected,
-                    color_empty,
-                ),
+            let mut buildable = Buildable::new(
+                &rules.name,
+                rules.weight,
+                false,
+                mesh.clone(),
+                material.clone(),
+                frame_image.clone(),
+                color_unselected,
+                color_selected,
+                color_empty,
             );
+            buildable.set_description(&rules.description);
+            buildable.set_weight_variance(rules.weight_variance);
+            buildable.set_idle(rules.idle.clone());
+            let parts = resolve_parts(
+                item_name,
+                &rules.parts,
+                buildable.material(),
+                &mut lifetimes,
+            );
+            buildable.set_parts(parts);
+            let palette = rules
+                .palette
+                .iter()
+                .map(|(path, slot)| {
+                    let material = lifetimes.weak_handle(&format!("models/{}", path));
+                    (material, slot.clone())
+                })
+                .collect();
+            buildable.set_palette(palette);
+
+            // Load cosmetic skins, defaulting to the assets of the buildable itself
+            for skin in &rules.skins {
+                let mesh = skin.model.as_ref().map_or(mesh.clone(), |model| {
+                    lifetimes.weak_handle(&format!("models/{}", model))
+                });
+                let frame_image = skin.frame.as_ref().map_or(frame_image.clone(), |frame| {
+                    ui_images.load(frame, &asset_server)
+                });
+                let parts = skin.parts.as_ref().map(|parts| {
+                    resolve_parts(item_name, parts, buildable.material(), &mut lifetimes)
+                });
+                let mut skin = Skin::new(&skin.name, mesh, frame_image, skin.unlock.clone());
+                skin.set_parts(parts);
+                buildable.add_skin(skin);
+            }
+
+            buildables.register(item_name, buildable);
         }
-        *buildables_res = Buildables::with_buildables(buildables);
+        wardrobe.apply(&mut buildables);
+
+        // Convert worlds, shared by their levels
+        let worlds: HashMap<_, _> = game_data_archive
+            .worlds
+            .iter()
+            .map(|(name, world)| (name.clone(), Arc::new(world.to_desc(name))))
+            .collect();
 
-        // Convert levels
+        // Convert levels, resolving buildable names into identifiers
         let levels: Vec<_> = game_data_archive
             .levels
-            .drain(..)
-            .map(|desc| LevelDesc {
-                name: desc.name,
-                grid_size: desc.grid_size,
-                balance_factor: desc.balance_factor,
-                victory_margin: desc.victory_margin,
-                inventory: desc
-                    .inventory
-                    .iter()
-                    .map(|(k, v)| (BuildableRef(k.clone()), *v))
-                    .collect(),
-            })
+            .iter()
+            .map(|desc| desc.to_desc(&buildables, &worlds))
             .collect();
         *levels_res = Levels::with_levels(levels);
+        *buildables_res = buildables;
+        *interludes_res = Interludes::new(&game_data_archive.interludes);
 
-        // Update status text
-        let mut text = status_text_query.single_mut();
-        text.sections[0].value = "Press [ENTER] to start".to_owned();
-
-        // Enable player input
+        // Enable player input, and list the menu entries
         main_menu.can_start = true;
     }
 
-    if main_menu.can_start {
-        if keyboard_input.just_pressed(KeyCode::Return) {
+    if main_menu.can_start && !wardrobe_menu.is_open() {
+        if main_menu.offline {
+            // Only the offline tutorial can be played, or the game quit
+            if keyboard_input.just_pressed(KeyCode::Return) {
+                *game_mode = GameMode::Solo;
+                state.set(AppState::InGame).unwrap();
+                keyboard_input.reset(KeyCode::Return);
+            } else if keyboard_input.just_pressed(KeyCode::Escape) {
+                exit.send(AppExit);
+            }
+        } else if keyboard_input.just_pressed(KeyCode::Return) {
+            *game_mode = GameMode::Solo;
             state.set(AppState::InGame).unwrap();
             // BUGBUG -- https://bevy-cheatbook.github.io/programming/states.html
             keyboard_input.reset(KeyCode::Return);
+        } else if keyboard_input.just_pressed(KeyCode::H) {
+            *game_mode = GameMode::WeightReveal;
+            state.set(AppState::InGame).unwrap();
+            keyboard_input.reset(KeyCode::H);
+        } else if keyboard_input.just_pressed(KeyCode::M) {
+            *game_mode = GameMode::Market;
+            state.set(AppState::InGame).unwrap();
+            keyboard_input.reset(KeyCode::M);
+        } else if keyboard_input.just_pressed(KeyCode::V) {
+            *game_mode = GameMode::Versus;
+            state.set(AppState::InGame).unwrap();
+            keyboard_input.reset(KeyCode::V);
+        } else if keyboard_input.just_pressed(KeyCode::C) {
+            *game_mode = GameMode::Coop;
+            state.set(AppState::InGame).unwrap();
+            keyboard_input.reset(KeyCode::C);
+        }
+    }
+}
+
+/// Convert the parts of a buildable, reporting and skipping the invalid ones.
+fn resolve_parts(
+    item_name: &str,
+    parts: &[BuildablePartArchive],
+    material: &Handle<StandardMaterial>,
+    lifetimes: &mut AssetLifetimes,
+) -> Vec<BuildablePart> {
+    parts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, part)| match part.to_part(lifetimes, material) {
+            Ok(part) => Some(part),
+            Err(err) => {
+                error!(
+                    "Invalid part #{} of buildable '{}': {}",
+                    index, item_name, err
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// Update the status text, and list the menu entries fading in one after the other, once the game
+/// data is loaded and again after the menu is rebuilt.
+fn show_menu_entries(
+    mut commands: Commands,
+    menu_query: Query<&MainMenu>,
+    mut status_text_query: Query<(&mut Text, &mut StatusText, &Parent)>,
+    ui_layouts: Res<UiLayouts>,
+) {
+    let (status, entries) = match menu_query.get_single() {
+        Ok(menu) if menu.can_start && menu.offline => {
+            ("The game data failed to load", &OFFLINE_MENU_ENTRIES[..])
         }
+        Ok(menu) if menu.can_start => ("Choose a game mode", &MENU_ENTRIES[..]),
+        _ => return,
+    };
+    let font_size = ui_layouts.metric("main_menu.entry_font_size");
+    let indent = ui_layouts.metric("main_menu.entry_indent");
+    for (mut text, mut status_text, panel) in status_text_query.iter_mut() {
+        if status_text.entries_shown {
+            continue;
+        }
+        status_text.entries_shown = true;
+        let font = match text.sections.first_mut() {
+            Some(section) => {
+                section.value = status.to_owned();
+                section.style.font.clone()
+            }
+            None => continue,
+        };
+        commands.entity(panel.0).with_children(|parent| {
+            for (index, entry) in entries.iter().enumerate() {
+                parent
+                    .spawn_bundle(TextBundle {
+                        style: Style {
+                            position_type: PositionType::Relative,
+                            position: Rect {
+                                left: Val::Px(indent),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            *entry,
+                            TextStyle {
+                                font: font.clone(),
+                                font_size,
+                                color: Color::NONE,
+                            },
+                            TextAlignment::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(PlayAnimation::new("menu_entry_fade_in").staggered(index));
+            }
+        });
     }
 }
 
-fn mainmenu_exit(mut commands: Commands, mut query: Query<&mut MainMenu>) {
-    let main_menu = query.single_mut();
+fn mainmenu_exit(mut commands: Commands, query: Query<(Entity, &MainMenu)>) {
+    let (menu_entity, main_menu) = query.single();
     // BUGBUG - Didn't manage to root all UI entities to a single one to despawn a tree, always got errors or warnings,
     //          so ended up with a flat list of entities to despawn here.
     //commands.entity(menu_data.root_entity).despawn_recursive();
     main_menu.entities.iter().for_each(|ent| {
         commands.entity(*ent).despawn_recursive();
     });
+    if let Some(root) = main_menu.layout {
+        commands.entity(root).despawn_recursive();
+    }
+    // Also despawn the menu itself, to start afresh when coming back to the menu
+    commands.entity(menu_entity).despawn_recursive();
 }
 
-fn start_background_audio(asset_server: Res<AssetServer>, audio: Res<Audio>, config: Res<Config>) {
+fn start_background_audio(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    config: Res<Config>,
+    mut lifetimes: ResMut<AssetLifetimes>,
+) {
     if config.sound.enabled {
+        // The audio keeps playing once started, so the source is only needed in the menu
         let source: Handle<AudioSource> = asset_server.load("audio/ambient1.ogg");
+        lifetimes.track(AssetScope::MainMenu, source.clone_untyped());
         audio.set_volume(config.sound.volume);
         audio.play_looped(source);
     }
 }
 
+/// Marker for the root UI node of a screen opened over the main menu or the game, like the
+/// encyclopedia. The other UI nodes are hidden while any of those screens is open, and shown again
+/// once all are closed.
+#[derive(Component)]
+pub struct MenuOverlay;
+
+/// Resource holding the UI nodes hidden under the [`MenuOverlay`] screens.
+#[derive(Debug, Default)]
+struct HiddenUnderOverlay {
+    /// Is any overlay screen open?
+    open: bool,
+    /// Other UI nodes hidden when the first overlay screen opened, shown again once all are
+    /// closed.
+    hidden: Vec<Entity>,
+}
+
+/// Hide the other UI nodes while any [`MenuOverlay`] screen is open. Runs after the updates, for
+/// the screens spawned during the frame to never show over the other nodes.
+fn hide_under_overlays(
+    mut state: ResMut<HiddenUnderOverlay>,
+    overlay_query: Query<(), With<MenuOverlay>>,
+    parent_query: Query<&Parent>,
+    mut query: Query<(Entity, &mut Visibility), With<Node>>,
+) {
+    let open = !overlay_query.is_empty();
+    if open == state.open {
+        return;
+    }
+    state.open = open;
+    if open {
+        let in_overlay = |mut entity| loop {
+            if overlay_query.get(entity).is_ok() {
+                return true;
+            }
+            match parent_query.get(entity) {
+                Ok(parent) => entity = parent.0,
+                Err(_) => return false,
+            }
+        };
+        for (entity, mut visibility) in query.iter_mut() {
+            if visibility.is_visible && !in_overlay(entity) {
+                visibility.is_visible = false;
+                state.hidden.push(entity);
+            }
+        }
+    } else {
+        for entity in std::mem::take(&mut state.hidden) {
+            if let Ok((_, mut visibility)) = query.get_mut(entity) {
+                visibility.is_visible = true;
+            }
+        }
+    }
+}
+
 /// Plugin to handle the main menu.
 pub struct MainMenuPlugin;
 
@@ -365,7 +506,14 @@ impl Plugin for MainMenuPlugin {
                 .with_system(mainmenu_setup)
                 .with_system(start_background_audio),
         )
-        .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(mainmenu))
-        .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(mainmenu_exit));
+        .add_system_set(
+            SystemSet::on_update(AppState::MainMenu)
+                .with_system(mainmenu)
+                .with_system(show_menu_entries)
+                .with_system(rebuild_menu_layout),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(mainmenu_exit))
+        .insert_resource(HiddenUnderOverlay::default())
+        .add_system_to_stage(CoreStage::PostUpdate, hide_under_overlays);
     }
 }
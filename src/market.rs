@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::*, rngs::StdRng};
+use std::collections::VecDeque;
+
+use crate::{
+    cinematic::Hud,
+    game::{GameMode, GameplaySystem},
+    inventory::{Inventory, RegenerateInventoryUiEvent, SelectSlot},
+    journal::{LevelJournal, LevelOp},
+    rng::GameRng,
+    serialize::{BuildableId, BuildableRegistry, MarketDesc},
+    ui_atlas::AtlasImage,
+    AppState, Level, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Number of upcoming buildables shown in the queue preview.
+const PREVIEW_LEN: usize = 3;
+
+/// Resource drawing the buildables of the level from a weighted random queue in market mode.
+pub struct BuildQueue {
+    /// Buildables of the market, in weight order.
+    brefs: Vec<BuildableId>,
+    /// Weighted distribution of the buildable indices, if the market is active.
+    distribution: Option<WeightedIndex<f32>>,
+    /// Number of buildables left to draw, excluding the upcoming ones already drawn.
+    remaining: u32,
+    /// Upcoming buildables, already drawn.
+    upcoming: VecDeque<BuildableId>,
+    /// Generator of the draws, forked from the [`GameRng`] on each reset.
+    rng: StdRng,
+}
+
+impl BuildQueue {
+    pub fn new() -> Self {
+        BuildQueue {
+            brefs: vec![],
+            distribution: None,
+            remaining: 0,
+            upcoming: VecDeque::new(),
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    /// Reset the queue to draw from the given market. Returns `false` if the market is invalid,
+    /// in which case the queue is cleared.
+    pub fn reset(&mut self, market: &MarketDesc, rng: &mut GameRng) -> bool {
+        self.clear();
+        self.rng = rng.fork();
+        match WeightedIndex::new(market.weights.iter().map(|&(_, weight)| weight)) {
+            Ok(distribution) => {
+                self.brefs = market.weights.iter().map(|&(bref, _)| bref).collect();
+                self.distribution = Some(distribution);
+                self.remaining = market.count;
+                self.refill();
+                true
+            }
+            Err(err) => {
+                error!("Invalid market {:?}: {}", market, err);
+                false
+            }
+        }
+    }
+
+    /// Deactivate the queue.
+    pub fn clear(&mut self) {
+        self.brefs.clear();
+        self.distribution = None;
+        self.remaining = 0;
+        self.upcoming.clear();
+    }
+
+    /// Is the queue drawing the buildables of the level?
+    pub fn is_active(&self) -> bool {
+        self.distribution.is_some()
+    }
+
+    /// Upcoming buildables, in draw order.
+    pub fn upcoming(&self) -> impl Iterator<Item = BuildableId> + '_ {
+        self.upcoming.iter().copied()
+    }
+
+    /// Take the next buildable from the queue, if any.
+    pub fn next(&mut self) -> Option<BuildableId> {
+        let bref = self.upcoming.pop_front()?;
+        self.refill();
+        Some(bref)
+    }
+
+    fn refill(&mut self) {
+        if let Some(distribution) = &self.distribution {
+            while self.upcoming.len() < PREVIEW_LEN && self.remaining > 0 {
+                let index = distribution.sample(&mut self.rng);
+                self.upcoming.push_back(self.brefs[index]);
+                self.remaining -= 1;
+            }
+        }
+    }
+}
+
+/// Marker for the root of the queue preview.
+#[derive(Component)]
+struct QueuePreview;
+
+/// Slot of the queue preview, displaying the upcoming buildable with the same index.
+#[derive(Component)]
+struct QueuePreviewSlot(usize);
+
+/// Replace the level inventory with the first buildable of the queue whenever the level starts
+/// or restarts in market mode.
+fn market_reset(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    game_mode: Res<GameMode>,
+    level: Res<Level>,
+    mut inventory: ResMut<Inventory>,
+    mut queue: ResMut<BuildQueue>,
+    mut rng: ResMut<GameRng>,
+    mut journal: ResMut<LevelJournal>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if !(reset || restart) {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) if *game_mode == GameMode::Market => level_desc,
+        _ => {
+            queue.clear();
+            return;
+        }
+    };
+    let market = level_desc.market();
+    if !queue.reset(&market, &mut rng) {
+        // Keep the fixed inventory of the level
+        return;
+    }
+    inventory.reset_from_market(&market);
+    journal.record(LevelOp::StartMarket);
+    if let Some(bref) = queue.next() {
+        inventory.add_items(bref, 1);
+        journal.record(LevelOp::Refill { bref, count: 1 });
+    }
+    if let Some(index) = inventory.find_non_empty_slot_index() {
+        inventory.select_slot(&SelectSlot::Index(index as usize));
+    }
+    trace!("Market: {} buildables to draw", market.count);
+    ev_regen_ui.send(RegenerateInventoryUiEvent);
+}
+
+fn spawn_queue_preview(mut commands: Commands, game_mode: Res<GameMode>) {
+    if *game_mode != GameMode::Market {
+        return;
+    }
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(260.0),
+                    right: Val::Px(100.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            },
+            color: UiColor(Color::NONE),
+            ..Default::default()
+        })
+        .insert(Name::new("QueuePreview"))
+        .insert(Hud)
+        .insert(QueuePreview)
+        .with_children(|parent| {
+            for index in 0..PREVIEW_LEN {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(64.0), Val::Px(64.0)),
+                            margin: Rect::all(Val::Px(4.0)),
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::NONE),
+                        ..Default::default()
+                    })
+                    .insert(AtlasImage::new(Handle::default(), Color::NONE))
+                    .insert(QueuePreviewSlot(index));
+            }
+        });
+}
+
+/// Display the frame of the upcoming buildables in the queue preview, the next one first.
+fn update_queue_preview(
+    queue: Res<BuildQueue>,
+    buildables: Res<BuildableRegistry>,
+    mut query: Query<(&QueuePreviewSlot, &mut AtlasImage)>,
+) {
+    if !queue.is_changed() {
+        return;
+    }
+    let upcoming: Vec<_> = queue.upcoming().collect();
+    for (slot, mut atlas_image) in query.iter_mut() {
+        match upcoming.get(slot.0).and_then(|&bref| buildables.get(bref)) {
+            Some(buildable) => {
+                atlas_image.image = buildable.frame_image();
+                // Fade the buildables further down the queue
+                atlas_image.color = Color::rgba(1.0, 1.0, 1.0, 1.0 - 0.25 * slot.0 as f32);
+            }
+            None => atlas_image.color = Color::NONE,
+        }
+    }
+}
+
+fn market_cleanup(
+    mut commands: Commands,
+    mut queue: ResMut<BuildQueue>,
+    query: Query<Entity, With<QueuePreview>>,
+) {
+    queue.clear();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the market mode, where the buildables are drawn one at a time from a weighted
+/// random [`BuildQueue`] instead of a fixed inventory.
+pub struct MarketPlugin;
+
+impl Plugin for MarketPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BuildQueue::new())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_queue_preview))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Reset)
+                    .after("seed_level")
+                    .after("journal_start")
+                    .before(GameplaySystem::Cursor)
+                    .with_system(market_reset),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_queue_preview),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(market_cleanup));
+    }
+}
@@ -0,0 +1,316 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{FilterMode, SamplerDescriptor},
+};
+
+use crate::{
+    cinematic::{CinematicMode, Hud},
+    game::{run_if_playing, GameplaySystem},
+    serialize::LevelDesc,
+    thumbnail::{Schematic, CELL_PIXELS},
+    AppState, Cursor, Grid, Level,
+};
+
+/// Key to show and hide the minimap. Not M, which goes back to the menu from the defeat and recap
+/// screens.
+pub const MINIMAP_KEY: KeyCode = KeyCode::O;
+
+/// Gamepad button to show and hide the minimap.
+pub const MINIMAP_BUTTON: GamepadButtonType = GamepadButtonType::RightTrigger;
+
+/// Largest side of the minimap on screen, in pixels. The schematic is scaled up by a whole factor
+/// to fit, keeping the pixels crisp.
+const MINIMAP_SIZE: f32 = 180.0;
+
+/// Color of the cells with a building placed.
+const OCCUPIED_COLOR: [u8; 4] = [240, 200, 90, 255];
+
+/// Color of the cells whose tile broke.
+const HOLE_COLOR: [u8; 4] = [20, 20, 20, 255];
+
+/// Color of the outline of the cell under the cursor.
+const CURSOR_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+/// Color of the COG marker.
+const COG_COLOR: [u8; 4] = [80, 220, 240, 255];
+
+/// Resource holding the state of the minimap, a top-down schematic of the plate in a corner of
+/// the screen.
+#[derive(Debug, Default)]
+pub struct Minimap {
+    /// Is the minimap shown?
+    visible: bool,
+    /// Image of the minimap, rendered again whenever the plate changes.
+    image: Handle<Image>,
+    /// Level index, weights digest of the grid, and cursor position of the last render.
+    rendered: Option<(usize, u64, Option<IVec2>)>,
+}
+
+impl Minimap {
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+}
+
+/// Render the minimap of the plate, drawing the occupied cells, the holes, the cursor, and the
+/// COG over the [`Schematic`] of the level.
+pub fn render_minimap(level_desc: &LevelDesc, grid: &Grid, cursor_pos: Option<IVec2>) -> Image {
+    let schematic = Schematic::render(level_desc);
+    let width = schematic.width as i32;
+    let height = schematic.height as i32;
+    let mut image = schematic.to_image();
+    let mut set = |x: i32, y: i32, color: [u8; 4]| {
+        if x >= 0 && x < width && y >= 0 && y < height {
+            let index = (x + y * width) as usize * 4;
+            image.data[index..index + 4].copy_from_slice(&color);
+        }
+    };
+    // Pixel at a position of the grid, with the back of the plate at the top
+    let center = Vec2::new((width - 1) as f32 / 2.0, (height - 1) as f32 / 2.0);
+    let pixel = |pos: Vec2| {
+        (center + Vec2::new(pos.x, -pos.y) * CELL_PIXELS as f32)
+            .round()
+            .as_ivec2()
+    };
+
+    // Occupied cells, leaving the border of the tile visible around the building
+    let (min, max) = (grid.min_pos(), grid.max_pos());
+    for j in min.y..=max.y {
+        for i in min.x..=max.x {
+            let pos = IVec2::new(i, j);
+            let cell = grid.cell(&pos);
+            let (radius, color) = if cell.hole {
+                (2, HOLE_COLOR)
+            } else if cell.buildable.is_some() {
+                (1, OCCUPIED_COLOR)
+            } else {
+                continue;
+            };
+            let p = pixel(grid.fpos(&pos));
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    set(p.x + x, p.y + y, color);
+                }
+            }
+        }
+    }
+
+    if let Some(pos) = cursor_pos {
+        let p = pixel(grid.fpos(&pos));
+        for d in -2..=2 {
+            set(p.x + d, p.y - 2, CURSOR_COLOR);
+            set(p.x + d, p.y + 2, CURSOR_COLOR);
+            set(p.x - 2, p.y + d, CURSOR_COLOR);
+            set(p.x + 2, p.y + d, CURSOR_COLOR);
+        }
+    }
+
+    // COG, drawn as a cross once anything is placed
+    let total_weight = grid.total_weight();
+    if total_weight > 0.0 {
+        let cog = grid.calc_cog_offset(level_desc.balance_factor) / total_weight;
+        let p = pixel(cog);
+        for d in -2..=2 {
+            set(p.x + d, p.y, COG_COLOR);
+            set(p.x, p.y + d, COG_COLOR);
+        }
+    }
+
+    image.sampler_descriptor = SamplerDescriptor {
+        mag_filter: FilterMode::Nearest,
+        min_filter: FilterMode::Nearest,
+        ..Default::default()
+    };
+    image
+}
+
+/// Marker for the root of the minimap.
+#[derive(Component)]
+struct MinimapUi;
+
+/// Marker for the image of the minimap, sized after the grid of the level.
+#[derive(Component)]
+struct MinimapImage;
+
+fn spawn_minimap(
+    mut commands: Commands,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    minimap.image = images.add(Image::default());
+    minimap.rendered = None;
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(180.0),
+                    left: Val::Px(20.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(6.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.4)),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Name::new("Minimap"))
+        .insert(Hud)
+        .insert(MinimapUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(ImageBundle {
+                    image: UiImage(minimap.image.clone()),
+                    visibility: Visibility { is_visible: false },
+                    ..Default::default()
+                })
+                .insert(MinimapUi)
+                .insert(MinimapImage);
+        });
+}
+
+/// Show and hide the minimap.
+fn minimap_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    mut minimap: ResMut<Minimap>,
+) {
+    let toggled = keyboard_input.just_pressed(MINIMAP_KEY)
+        || gamepad_input
+            .get_just_pressed()
+            .any(|button| button.1 == MINIMAP_BUTTON);
+    if toggled {
+        minimap.visible = !minimap.visible;
+    }
+}
+
+/// Show the minimap unless hidden or during cinematics, and render it again whenever a building
+/// is placed or removed, or the cursor moves.
+fn update_minimap(
+    level: Res<Level>,
+    grid: Res<Grid>,
+    cinematic: Res<CinematicMode>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    cursor_query: Query<&Cursor>,
+    mut visibility_query: Query<&mut Visibility, With<MinimapUi>>,
+    mut style_query: Query<&mut Style, With<MinimapImage>>,
+) {
+    let visible = minimap.visible && !cinematic.is_enabled();
+    for mut visibility in visibility_query.iter_mut() {
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) if minimap.visible => level_desc,
+        _ => return,
+    };
+    let cursor_pos = cursor_query
+        .get_single()
+        .ok()
+        .filter(|cursor| cursor.enabled())
+        .map(|cursor| cursor.pos());
+    let key = Some((level.index(), grid.weights_digest(), cursor_pos));
+    if minimap.rendered == key {
+        return;
+    }
+    minimap.rendered = key;
+
+    let image = render_minimap(level_desc, &grid, cursor_pos);
+    let size = image.texture_descriptor.size;
+    let scale = (MINIMAP_SIZE / size.width.max(size.height) as f32)
+        .floor()
+        .max(1.0);
+    for mut style in style_query.iter_mut() {
+        style.size = Size::new(
+            Val::Px(size.width as f32 * scale),
+            Val::Px(size.height as f32 * scale),
+        );
+    }
+    if let Some(target) = images.get_mut(&minimap.image) {
+        *target = image;
+    }
+}
+
+fn minimap_cleanup(
+    mut commands: Commands,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    query: Query<Entity, (With<MinimapUi>, Without<Parent>)>,
+) {
+    images.remove(&minimap.image);
+    minimap.rendered = None;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the minimap, a top-down schematic of the plate showing the occupied cells and the
+/// COG, which helps judging the far cells of the large grids.
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Minimap::default())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_minimap))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Input)
+                    .with_system(minimap_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_minimap),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(minimap_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        inventory::Buildable,
+        layout::{GridLayout, LayoutPlacement},
+        serialize::{BuildableRegistry, LevelDescArchive},
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn render() {
+        let archive: LevelDescArchive = serde_json::from_str(
+            r#"{ "name": "A", "grid_size": [3, 3], "balance_factor": 1.0,
+                 "victory_margin": 0.1, "inventory": {} }"#,
+        )
+        .unwrap();
+        let level_desc = archive.to_desc(&BuildableRegistry::new(), &HashMap::new());
+        let mut buildables = BuildableRegistry::new();
+        buildables.register("hut", Buildable::without_assets("hut", 1.0));
+        let layout = GridLayout {
+            size: IVec2::new(3, 3),
+            placements: vec![LayoutPlacement {
+                pos: IVec2::new(1, 1),
+                buildable: "hut".to_owned(),
+                weight: 1.0,
+            }],
+        };
+        let grid = Grid::from_layout(&layout, &buildables).unwrap();
+        let image = render_minimap(&level_desc, &grid, Some(IVec2::new(-1, 0)));
+        let width = 3 * CELL_PIXELS as usize + 1;
+        assert_eq!(image.data.len(), width * width * 4);
+        let color = |x: usize, y: usize| &image.data[(x + y * width) * 4..(x + y * width) * 4 + 4];
+        // The occupied cell is at the back right, in the top right corner of the image, with the
+        // COG right on it
+        assert_eq!(color(16, 4), &OCCUPIED_COLOR);
+        assert_eq!(color(15, 3), &COG_COLOR);
+        assert_eq!(color(15, 2), &COG_COLOR);
+        // Cursor outline around the middle left cell
+        assert_eq!(color(1, 9), &CURSOR_COLOR);
+        assert_ne!(color(3, 9), &CURSOR_COLOR);
+    }
+}
@@ -0,0 +1,369 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    controls::{
+        button_glyph, key_glyph, ActiveInputDevice, ControlScheme, InputDevice, PlayerAction,
+    },
+    controls_screen::{binding_lines, controls_glyph},
+    encyclopedia::ENCYCLOPEDIA_FILE,
+    game::{Game, GameEvent, GameMode, GameSequence, GameplaySystem},
+    interlude::InterludePlayer,
+    serialize::Levels,
+    storage, AppState, Level,
+};
+
+/// Save file of the onboarding progress, in the profile storage.
+pub const ONBOARDING_FILE: &str = "onboarding.json";
+
+/// Keys dismissing the controls card, with the south button of the gamepads or a tap.
+const CONFIRM_KEYS: [KeyCode; 2] = [KeyCode::Return, KeyCode::Space];
+
+/// Prompt of the confirm action dismissing the controls card, on a device.
+fn confirm_prompt(device: InputDevice) -> String {
+    match device {
+        InputDevice::Keyboard => key_glyph(CONFIRM_KEYS[0]),
+        InputDevice::Gamepad => button_glyph(GamepadButtonType::South).to_owned(),
+        InputDevice::Touch => "Tap".to_owned(),
+    }
+}
+
+/// One-line prompt of the placement, shown during the tutorial level, referencing the controls
+/// screen for the other controls.
+fn prompt_line(device: InputDevice) -> String {
+    if device == InputDevice::Touch {
+        return "Connect a keyboard or a gamepad to play".to_owned();
+    }
+    let place = PlayerAction::Place.description();
+    let place_glyphs = ControlScheme::All
+        .bindings()
+        .iter()
+        .find(|binding| binding.action == place)
+        .map_or(String::new(), |binding| binding.glyphs(device));
+    format!(
+        "{}: {}   {}: Controls",
+        place_glyphs,
+        place,
+        controls_glyph(device)
+    )
+}
+
+/// Resource holding the progress of the first-run experience of the active profile.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Onboarding {
+    /// Was the controls card dismissed once? Later runs start in the main menu.
+    done: bool,
+    /// Was the tutorial level started this session?
+    #[serde(skip)]
+    started: bool,
+    /// Controls card shown, if any.
+    #[serde(skip)]
+    card: Option<Entity>,
+}
+
+impl Onboarding {
+    /// Load the onboarding progress of the active profile. Profiles saved before the onboarding
+    /// existed, with other save data, skip it.
+    pub fn load() -> Self {
+        match storage::read(ONBOARDING_FILE) {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+                let location = storage::location(ONBOARDING_FILE);
+                warn!("Failed to parse onboarding '{}': {}", location, err);
+                Onboarding::default()
+            }),
+            None => Onboarding {
+                done: storage::read(ENCYCLOPEDIA_FILE).is_some(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(ONBOARDING_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(ONBOARDING_FILE);
+            warn!("Failed to save onboarding to '{}': {}", location, err);
+        }
+    }
+
+    /// Skip the first-run experience for this session, without saving it. Used by the tests.
+    pub fn skip(&mut self) {
+        self.done = true;
+    }
+
+    /// Is this the first run, not onboarded yet?
+    pub fn is_first_run(&self) -> bool {
+        !self.done
+    }
+}
+
+/// Start the tutorial level straight away on the first run, instead of waiting in the main menu.
+fn start_tutorial(
+    levels: Res<Levels>,
+    mut onboarding: ResMut<Onboarding>,
+    mut game_mode: ResMut<GameMode>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !onboarding.is_first_run() || onboarding.started || levels.is_empty() {
+        return;
+    }
+    if state.set(AppState::InGame).is_ok() {
+        info!("First run, starting the tutorial");
+        onboarding.started = true;
+        *game_mode = GameMode::Solo;
+    }
+}
+
+/// Marker for the UI nodes of the controls card.
+#[derive(Component)]
+struct ControlsCard;
+
+/// Marker for the text of the controls card, updated with the device used.
+#[derive(Component)]
+struct ControlsCardText;
+
+/// Marker for the prompt of the controls shown during the tutorial level.
+#[derive(Component)]
+struct PromptText;
+
+/// Sections of the text of the controls card for a device, listing the bindings of the controls.
+fn card_text(device: InputDevice) -> [String; 3] {
+    let lines = if device == InputDevice::Touch {
+        format!("\n\n{}\n", prompt_line(device))
+    } else {
+        let lines: String = binding_lines(&ControlScheme::All.bindings(), device)
+            .into_iter()
+            .map(|line| format!("\n{}", line))
+            .collect();
+        format!(
+            "\n{}\n\n{} shows the controls again\n",
+            lines,
+            controls_glyph(device)
+        )
+    };
+    [
+        "Controls".to_owned(),
+        lines,
+        format!("\n{} to start", confirm_prompt(device)),
+    ]
+}
+
+fn spawn_controls_card(
+    commands: &mut Commands,
+    ui_resources: &UiResources,
+    device: InputDevice,
+) -> Entity {
+    let sections = card_text(device)
+        .into_iter()
+        .zip([
+            (36.0, Color::WHITE),
+            (24.0, Color::rgb_u8(200, 200, 200)),
+            (20.0, Color::rgb_u8(255, 220, 120)),
+        ])
+        .map(|(value, (font_size, color))| TextSection {
+            value,
+            style: TextStyle {
+                font: ui_resources.text_font(),
+                font_size,
+                color,
+            },
+        })
+        .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.8)),
+            ..Default::default()
+        })
+        .insert(Name::new("ControlsCard"))
+        .insert(ControlsCard)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .insert(ControlsCardText);
+        })
+        .id()
+}
+
+fn spawn_prompt(commands: &mut Commands, ui_resources: &UiResources, device: InputDevice) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(16.0),
+                    left: Val::Px(16.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                prompt_line(device),
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 18.0,
+                    color: Color::rgb_u8(200, 200, 200),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("ControlsPrompt"))
+        .insert(Hud)
+        .insert(PromptText);
+}
+
+/// Show the controls card on the first run, once the interlude of the tutorial level is over,
+/// holding the intro of the level until the card is dismissed with the confirm action. The
+/// controls are then prompted until the level is cleared.
+fn controls_card(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    touches: Res<Touches>,
+    device: Res<ActiveInputDevice>,
+    level: Res<Level>,
+    interlude_player: Res<InterludePlayer>,
+    ui_resources: Res<UiResources>,
+    mut game: ResMut<Game>,
+    mut onboarding: ResMut<Onboarding>,
+) {
+    if !onboarding.is_first_run() || level.desc().is_none() {
+        return;
+    }
+    let card = match onboarding.card {
+        Some(card) => card,
+        None => {
+            if game.sequence() == GameSequence::Intro
+                && !interlude_player.is_pending(level.index())
+                && !interlude_player.is_playing()
+            {
+                onboarding.card = Some(spawn_controls_card(&mut commands, &ui_resources, device.0));
+                game.hold_intro();
+            }
+            // Don't dismiss the card with the input which ended the interlude
+            return;
+        }
+    };
+    game.hold_intro();
+    let confirmed = keyboard_input.any_just_pressed(CONFIRM_KEYS)
+        || gamepad_input
+            .get_just_pressed()
+            .any(|button| button.1 == GamepadButtonType::South)
+        || touches.iter_just_pressed().next().is_some();
+    if confirmed {
+        commands.entity(card).despawn_recursive();
+        onboarding.card = None;
+        onboarding.done = true;
+        onboarding.save();
+        spawn_prompt(&mut commands, &ui_resources, device.0);
+    }
+}
+
+/// Update the controls card and prompt with the buttons of the device used last.
+fn update_prompts(
+    device: Res<ActiveInputDevice>,
+    mut card_query: Query<&mut Text, (With<ControlsCardText>, Without<PromptText>)>,
+    mut prompt_query: Query<&mut Text, With<PromptText>>,
+) {
+    if !device.is_changed() {
+        return;
+    }
+    for mut text in card_query.iter_mut() {
+        for (section, value) in text.sections.iter_mut().zip(card_text(device.0)) {
+            section.value = value;
+        }
+    }
+    for mut text in prompt_query.iter_mut() {
+        text.sections[0].value = prompt_line(device.0);
+    }
+}
+
+/// Hide the prompt of the controls once the tutorial level is cleared.
+fn hide_prompt(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    query: Query<Entity, With<PromptText>>,
+) {
+    let cleared = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some();
+    if cleared {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn onboarding_cleanup(
+    mut commands: Commands,
+    mut onboarding: ResMut<Onboarding>,
+    query: Query<Entity, Or<(With<ControlsCard>, With<PromptText>)>>,
+) {
+    onboarding.card = None;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the first-run experience, starting the first launch in the tutorial level with a
+/// card of the controls, and prompting the controls of the input device used.
+pub struct OnboardingPlugin;
+
+impl Plugin for OnboardingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Onboarding::load())
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(start_tutorial))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(controls_card)
+                    .with_system(update_prompts)
+                    .with_system(hide_prompt),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(onboarding_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts() {
+        assert_eq!(confirm_prompt(InputDevice::Gamepad), "(A)");
+        assert_eq!(
+            prompt_line(InputDevice::Keyboard),
+            "[Space]: Place the buildable   [F6]: Controls"
+        );
+        assert!(card_text(InputDevice::Gamepad)[1].contains("\n(A) - Place the buildable\n"));
+        assert_eq!(
+            card_text(InputDevice::Touch)[1],
+            "\n\nConnect a keyboard or a gamepad to play\n"
+        );
+        assert_eq!(card_text(InputDevice::Touch)[2], "\nTap to start");
+    }
+}
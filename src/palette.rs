@@ -0,0 +1,175 @@
+use bevy::{asset::HandleId, prelude::*, utils::HashMap};
+use std::sync::Arc;
+
+use crate::{
+    serialize::{BuildableRegistry, WorldDesc},
+    AppState, Level, ResetPlateEvent,
+};
+
+/// Resource recoloring the buildable materials with the palette of the world of the level, like
+/// roofs in red in a world and blue in another.
+///
+/// Each material referencing a palette slot gets a variant with the color of the slot in the
+/// world, instantiated when the level changes world, and swapped in for the base material on the
+/// spawned buildables.
+#[derive(Default)]
+pub struct WorldPalette {
+    /// World the variants are instantiated for.
+    world: Option<Arc<WorldDesc>>,
+    /// Base materials waiting to be loaded to instantiate their variant, with its color.
+    pending: Vec<(Handle<StandardMaterial>, Color)>,
+    /// Variants of the base materials for the world, by base material.
+    variants: HashMap<HandleId, Handle<StandardMaterial>>,
+}
+
+impl WorldPalette {
+    pub fn new() -> Self {
+        WorldPalette::default()
+    }
+
+    /// Change the world the variants are instantiated for, dropping the variants of the previous
+    /// one. Returns `true` if the world changed.
+    pub fn set_world(
+        &mut self,
+        world: Option<Arc<WorldDesc>>,
+        buildables: &BuildableRegistry,
+    ) -> bool {
+        if world == self.world {
+            return false;
+        }
+        self.pending.clear();
+        self.variants.clear();
+        if let Some(world) = &world {
+            for (_, buildable) in buildables.iter() {
+                for (material, slot) in buildable.palette() {
+                    let color = match world.palette.get(slot) {
+                        Some(&color) => color,
+                        None => continue,
+                    };
+                    if !self.pending.iter().any(|(base, _)| base == material) {
+                        self.pending.push((material.clone_weak(), color));
+                    }
+                }
+            }
+        }
+        self.world = world;
+        true
+    }
+
+    /// Variant of the given material for the world, if instantiated.
+    pub fn variant(
+        &self,
+        material: &Handle<StandardMaterial>,
+    ) -> Option<&Handle<StandardMaterial>> {
+        self.variants.get(&material.id)
+    }
+
+    /// Instantiate the variants of the base materials loaded since the last call. Returns `true`
+    /// if any variant was instantiated.
+    fn instantiate(&mut self, materials: &mut Assets<StandardMaterial>) -> bool {
+        let variants = &mut self.variants;
+        let count = variants.len();
+        self.pending.retain(|(base, color)| {
+            let mut variant = match materials.get(base) {
+                Some(material) => material.clone(),
+                None => return true,
+            };
+            let mut base_color = *color;
+            base_color.set_a(variant.base_color.a());
+            variant.base_color = base_color;
+            variants.insert(base.id, materials.add(variant));
+            false
+        });
+        variants.len() > count
+    }
+}
+
+/// Instantiate the material variants of the world of the level whenever the level changes world.
+fn update_world_palette(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    buildables: Res<BuildableRegistry>,
+    mut palette: ResMut<WorldPalette>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    let world = level.desc().and_then(|level_desc| level_desc.world.clone());
+    if palette.set_world(world, &buildables) {
+        debug!("Palette: {} material variant(s)", palette.pending.len());
+    }
+}
+
+/// Swap the materials of the spawned entities for their variant in the world. Only the entities
+/// whose material changed are checked, unless new variants were just instantiated.
+fn recolor_materials(
+    mut palette: ResMut<WorldPalette>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<&mut Handle<StandardMaterial>>,
+) {
+    let instantiated = palette.instantiate(&mut materials);
+    if palette.variants.is_empty() {
+        return;
+    }
+    for mut material in query.iter_mut() {
+        if !instantiated && !material.is_changed() {
+            continue;
+        }
+        if let Some(variant) = palette.variant(&material) {
+            *material = variant.clone();
+        }
+    }
+}
+
+fn palette_cleanup(buildables: Res<BuildableRegistry>, mut palette: ResMut<WorldPalette>) {
+    palette.set_world(None, &buildables);
+}
+
+/// Plugin recoloring the buildable materials per world, from the palette slots of the buildables
+/// and the palettes of the worlds in the game data.
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WorldPalette::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(update_world_palette.label("update_world_palette"))
+                    .with_system(recolor_materials.after("update_world_palette")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(palette_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inventory::Buildable, serialize::WorldDescArchive};
+
+    #[test]
+    fn set_world() {
+        let roof = Handle::<StandardMaterial>::weak(HandleId::random::<StandardMaterial>());
+        let wall = Handle::<StandardMaterial>::weak(HandleId::random::<StandardMaterial>());
+        let mut buildable = Buildable::without_assets("Hut", 1.0);
+        buildable.set_palette(vec![
+            (roof.clone(), "roof".to_owned()),
+            (wall.clone(), "wall".to_owned()),
+        ]);
+        let mut buildables = BuildableRegistry::new();
+        buildables.register("hut", buildable);
+        let world: WorldDescArchive = serde_json::from_str(
+            r#"{ "sky_top": [0, 0, 0], "sky_horizon": [0, 0, 0],
+                 "palette": { "roof": [0, 0, 1] } }"#,
+        )
+        .unwrap();
+        let world = Some(Arc::new(world.to_desc("winter")));
+
+        let mut palette = WorldPalette::new();
+        assert!(palette.set_world(world.clone(), &buildables));
+        // Only the slots in the palette of the world get a variant
+        assert_eq!(palette.pending, vec![(roof, Color::rgb(0.0, 0.0, 1.0))]);
+        assert!(!palette.set_world(world, &buildables));
+        assert!(palette.set_world(None, &buildables));
+        assert!(palette.pending.is_empty());
+    }
+}
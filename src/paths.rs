@@ -0,0 +1,69 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+/// Environment variable overriding the folder of the game assets.
+pub const ASSETS_ENV_VAR: &str = "LIBRACITY_ASSETS";
+
+/// Environment variable overriding the folder of the save files and settings, outside of the
+/// portable mode. Used by the tests to keep away from the saves of the player.
+pub const DATA_ENV_VAR: &str = "LIBRACITY_DATA";
+
+/// Folder of the save files and settings in the user data directory.
+const USER_DATA_FOLDER: &str = "libracity";
+
+/// Folder the save files and settings are stored in, once resolved.
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Keep the save files and settings next to the executable instead of the user directory, for
+/// self-contained installs like itch.io zips or USB sticks. Must be called before any save file
+/// is accessed.
+pub fn set_portable(portable: bool) {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let data_dir = match exe_dir.filter(|_| portable) {
+        Some(exe_dir) => exe_dir,
+        None => user_data_dir(),
+    };
+    *DATA_DIR.write().unwrap() = Some(data_dir);
+}
+
+/// Folder of the save files and settings from the [`DATA_ENV_VAR`] environment variable if set,
+/// or in the user directory otherwise, or the working directory if the platform has none.
+fn user_data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(DATA_ENV_VAR).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join(USER_DATA_FOLDER))
+        .unwrap_or_default()
+}
+
+/// Folder the save files and settings are stored in: next to the executable in portable mode,
+/// or in the user directory otherwise.
+pub fn data_dir() -> PathBuf {
+    if let Some(data_dir) = &*DATA_DIR.read().unwrap() {
+        return data_dir.clone();
+    }
+    DATA_DIR
+        .write()
+        .unwrap()
+        .get_or_insert_with(user_data_dir)
+        .clone()
+}
+
+/// Path of a file in the folder of the save files and settings.
+pub fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+/// Folder of the game assets, from the [`ASSETS_ENV_VAR`] environment variable if set, or the
+/// given default one otherwise. Relative folders are relative to the executable.
+pub fn asset_folder(default: &str) -> String {
+    match std::env::var(ASSETS_ENV_VAR) {
+        Ok(folder) if !folder.is_empty() => folder,
+        _ => default.to_owned(),
+    }
+}
@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+
+use crate::{loader::Loader, AppState, Level};
+
+/// Title of the main window, followed by the name of the level being played if any.
+pub const GAME_TITLE: &str = "Libra City";
+
+/// Icon of the main window, embedded to be available before any asset is loaded.
+#[cfg(not(target_arch = "wasm32"))]
+const ICON_PNG: &[u8] = include_bytes!("../assets/textures/icon.png");
+
+/// Set the icon of the main window, once it's created.
+#[cfg(not(target_arch = "wasm32"))]
+fn set_window_icon(
+    mut done: Local<bool>,
+    windows: Res<Windows>,
+    winit_windows: NonSend<bevy::winit::WinitWindows>,
+) {
+    use bevy::render::texture::{CompressedImageFormats, ImageType};
+
+    if *done {
+        return;
+    }
+    let window = match windows
+        .get_primary()
+        .and_then(|window| winit_windows.get_window(window.id()))
+    {
+        Some(window) => window,
+        None => return,
+    };
+    *done = true;
+    let image = match Image::from_buffer(
+        ICON_PNG,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+    ) {
+        Ok(image) => image,
+        Err(err) => {
+            warn!("Failed to decode the window icon: {:?}", err);
+            return;
+        }
+    };
+    let size = image.texture_descriptor.size;
+    match winit::window::Icon::from_rgba(image.data, size.width, size.height) {
+        Ok(icon) => window.set_window_icon(Some(icon)),
+        Err(err) => warn!("Invalid window icon: {}", err),
+    }
+}
+
+/// Show the name of the level being played in the title of the main window.
+fn update_window_title(
+    state: Res<State<AppState>>,
+    level: Res<Level>,
+    mut windows: ResMut<Windows>,
+) {
+    if !state.is_changed() && !level.is_changed() {
+        return;
+    }
+    let title = match level.desc() {
+        Some(_) if *state.current() == AppState::InGame => {
+            format!("{} — {}", GAME_TITLE, level.name())
+        }
+        _ => GAME_TITLE.to_owned(),
+    };
+    if let Some(window) = windows.get_primary_mut() {
+        if window.title() != title {
+            window.set_title(title);
+        }
+    }
+}
+
+/// Progress of the assets being loaded, in [0:1], or `None` if nothing is loading.
+fn loading_progress(loaders: &Query<&Loader>) -> Option<f32> {
+    loaders
+        .iter()
+        .filter(|loader| !loader.is_done() && !loader.is_empty())
+        .map(Loader::percent_done)
+        .reduce(f32::min)
+}
+
+/// Show the loading progress on the taskbar button of the main window.
+#[cfg(target_os = "windows")]
+fn update_taskbar_progress(
+    mut taskbar: NonSendMut<Option<taskbar::Taskbar>>,
+    mut last_progress: Local<Option<f32>>,
+    windows: Res<Windows>,
+    winit_windows: NonSend<bevy::winit::WinitWindows>,
+    loaders: Query<&Loader>,
+) {
+    use winit::platform::windows::WindowExtWindows;
+
+    let progress = loading_progress(&loaders);
+    if progress == *last_progress {
+        return;
+    }
+    let (taskbar, window) = match (
+        taskbar.as_mut(),
+        windows
+            .get_primary()
+            .and_then(|window| winit_windows.get_window(window.id())),
+    ) {
+        (Some(taskbar), Some(window)) => (taskbar, window),
+        _ => return,
+    };
+    *last_progress = progress;
+    taskbar.set_progress(window.hwnd() as _, progress);
+}
+
+/// Taskbar progress of the main window on Windows, through the `ITaskbarList3` COM interface.
+#[cfg(target_os = "windows")]
+mod taskbar {
+    use std::ptr;
+    use winapi::{
+        shared::{windef::HWND, winerror::SUCCEEDED, wtypesbase::CLSCTX_INPROC_SERVER},
+        um::{
+            combaseapi::CoCreateInstance,
+            shobjidl_core::{CLSID_TaskbarList, ITaskbarList3, TBPF_NOPROGRESS, TBPF_NORMAL},
+        },
+        Interface,
+    };
+
+    /// Resolution of the progress reported to the taskbar.
+    const PROGRESS_STEPS: u64 = 1000;
+
+    /// Taskbar list interface, valid on the main thread only.
+    pub struct Taskbar(*mut ITaskbarList3);
+
+    impl Taskbar {
+        /// Create the taskbar list interface. COM is already initialized on the main thread by
+        /// the window.
+        pub fn new() -> Option<Self> {
+            let mut taskbar: *mut ITaskbarList3 = ptr::null_mut();
+            let hr = unsafe {
+                CoCreateInstance(
+                    &CLSID_TaskbarList,
+                    ptr::null_mut(),
+                    CLSCTX_INPROC_SERVER,
+                    &ITaskbarList3::uuidof(),
+                    &mut taskbar as *mut *mut ITaskbarList3 as *mut _,
+                )
+            };
+            if !SUCCEEDED(hr) || taskbar.is_null() {
+                return None;
+            }
+            let taskbar = Taskbar(taskbar);
+            if SUCCEEDED(unsafe { (*taskbar.0).HrInit() }) {
+                Some(taskbar)
+            } else {
+                None
+            }
+        }
+
+        /// Show a progress in [0:1] on the taskbar button of a window, or clear it if `None`.
+        pub fn set_progress(&mut self, hwnd: HWND, progress: Option<f32>) {
+            unsafe {
+                match progress {
+                    Some(progress) => {
+                        let completed = (progress.clamp(0.0, 1.0) * PROGRESS_STEPS as f32) as u64;
+                        (*self.0).SetProgressState(hwnd, TBPF_NORMAL);
+                        (*self.0).SetProgressValue(hwnd, completed, PROGRESS_STEPS);
+                    }
+                    None => {
+                        (*self.0).SetProgressState(hwnd, TBPF_NOPROGRESS);
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for Taskbar {
+        fn drop(&mut self) {
+            unsafe {
+                (*self.0).Release();
+            }
+        }
+    }
+}
+
+/// Plugin for the integration of the main window with the desktop: its icon, a title following
+/// the level being played, and the loading progress on the taskbar on Windows. Needs a window, so
+/// is not added to headless apps.
+pub struct PlatformPlugin;
+
+impl Plugin for PlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_window_title);
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_system(set_window_icon);
+        #[cfg(target_os = "windows")]
+        app.insert_non_send_resource(taskbar::Taskbar::new())
+            .add_system(update_taskbar_progress);
+    }
+}
@@ -0,0 +1,396 @@
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    boot::UiResources, daily::DailyMenu, encyclopedia::EncyclopediaMenu, game::GameEvent,
+    level_code::ImportDialog, mainmenu::MenuOverlay, serialize::Levels, storage,
+    wardrobe::WardrobeMenu, AppState, Level,
+};
+
+/// Save file of the playtime statistics, in the profile storage.
+pub const PLAYTIME_FILE: &str = "playtime.json";
+
+/// Key to open and close the statistics page in the main menu.
+const STATS_KEY: KeyCode = KeyCode::S;
+
+/// Interval between two saves of the playtime, in seconds, so little is lost if the game is
+/// killed. The playtime is also saved when leaving a level.
+const AUTOSAVE_INTERVAL: f64 = 30.0;
+
+/// Maximum time counted for a single frame, in seconds, so a suspended game doesn't count the
+/// time it was asleep.
+const MAX_FRAME_TIME: f64 = 1.0;
+
+/// Number of levels listed on the statistics page.
+const MOST_PLAYED_COUNT: usize = 10;
+
+/// Playtime statistics of a level.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelPlaytime {
+    /// Time spent in the level, in seconds.
+    pub time: f64,
+    /// Number of times the level was cleared.
+    pub clears: u32,
+}
+
+/// Resource holding the playtime statistics of the active profile. Kept locally in the profile
+/// storage only, and never sent anywhere.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Playtime {
+    /// Total time the game was played, in seconds, while its window is focused.
+    total: f64,
+    /// Number of times the game was started with the profile.
+    sessions: u32,
+    /// Statistics of the levels played, by level name.
+    levels: BTreeMap<String, LevelPlaytime>,
+    /// Time counted since the last save, in seconds.
+    #[serde(skip)]
+    unsaved: f64,
+}
+
+impl Playtime {
+    /// Load the playtime statistics of the active profile in the previous sessions, if any.
+    pub fn load() -> Self {
+        storage::read(PLAYTIME_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(playtime) => Some(playtime),
+                Err(err) => {
+                    let location = storage::location(PLAYTIME_FILE);
+                    warn!("Failed to parse playtime '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Load the playtime statistics of the active profile, counting a new session with it.
+    pub fn start_session() -> Self {
+        let mut playtime = Playtime::load();
+        playtime.sessions += 1;
+        playtime.save();
+        playtime
+    }
+
+    fn save(&mut self) {
+        self.unsaved = 0.0;
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(PLAYTIME_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(PLAYTIME_FILE);
+            warn!("Failed to save playtime to '{}': {}", location, err);
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    pub fn sessions(&self) -> u32 {
+        self.sessions
+    }
+
+    /// Statistics of a level, if it was ever played.
+    pub fn level(&self, name: &str) -> Option<&LevelPlaytime> {
+        self.levels.get(name)
+    }
+
+    /// Count some time played, in seconds, and in a level if any. Returns `true` if the playtime
+    /// is due for an autosave.
+    fn add_time(&mut self, seconds: f64, level: Option<&str>) -> bool {
+        self.total += seconds;
+        if let Some(name) = level {
+            self.levels.entry(name.to_owned()).or_default().time += seconds;
+        }
+        self.unsaved += seconds;
+        self.unsaved >= AUTOSAVE_INTERVAL
+    }
+
+    fn record_clear(&mut self, level: &str) {
+        self.levels.entry(level.to_owned()).or_default().clears += 1;
+    }
+
+    /// Levels the most time was spent in, longest first.
+    pub fn most_played(&self, count: usize) -> Vec<(&str, &LevelPlaytime)> {
+        let mut levels: Vec<_> = self
+            .levels
+            .iter()
+            .map(|(name, stats)| (&name[..], stats))
+            .collect();
+        levels.sort_by(|a, b| b.1.time.total_cmp(&a.1.time));
+        levels.truncate(count);
+        levels
+    }
+}
+
+/// Format a playtime in seconds for display, like `2h 05m` or `4m 07s`.
+pub fn format_playtime(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, seconds / 60 % 60)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Count the time played while the window is focused, and the time spent in the level being
+/// played, saving it periodically.
+fn track_playtime(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    state: Res<State<AppState>>,
+    level: Res<Level>,
+    mut playtime: ResMut<Playtime>,
+) {
+    if !windows
+        .get_primary()
+        .map_or(true, |window| window.is_focused())
+    {
+        return;
+    }
+    let seconds = time.delta_seconds_f64().min(MAX_FRAME_TIME);
+    let in_level = *state.current() == AppState::InGame && level.desc().is_some();
+    if playtime.add_time(seconds, in_level.then(|| level.name())) {
+        playtime.save();
+    }
+}
+
+fn record_level_clear(
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    mut playtime: ResMut<Playtime>,
+) {
+    let cleared = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some();
+    if cleared && level.desc().is_some() {
+        playtime.record_clear(level.name());
+        playtime.save();
+    }
+}
+
+fn save_playtime(mut playtime: ResMut<Playtime>) {
+    playtime.save();
+}
+
+/// Resource holding the state of the statistics page.
+#[derive(Debug, Default)]
+pub struct StatsMenu {
+    /// Is the statistics page open?
+    open: bool,
+}
+
+impl StatsMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+/// Marker for the UI nodes of the statistics page, kept visible while it's open.
+#[derive(Component)]
+struct StatsUi;
+
+/// Open and close the statistics page. Runs right after the input is updated, to hide the keys
+/// from the rest of the menu while it's open.
+fn stats_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    levels: Res<Levels>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    encyclopedia_menu: Res<EncyclopediaMenu>,
+    daily_menu: Res<DailyMenu>,
+    import_dialog: Res<ImportDialog>,
+    mut menu: ResMut<StatsMenu>,
+) {
+    if !menu.open {
+        if keyboard_input.just_pressed(STATS_KEY)
+            && !levels.is_empty()
+            && !wardrobe_menu.is_open()
+            && !encyclopedia_menu.is_open()
+            && !daily_menu.is_open()
+            && !import_dialog.is_open()
+        {
+            menu.open = true;
+            keyboard_input.clear();
+        }
+        return;
+    }
+    if keyboard_input.just_pressed(STATS_KEY) || keyboard_input.just_pressed(KeyCode::Escape) {
+        menu.open = false;
+    }
+    // Keep the keys from also triggering the menu entries
+    keyboard_input.clear();
+}
+
+/// Lines of the statistics page listing the levels the most time was spent in.
+fn most_played_lines(playtime: &Playtime) -> String {
+    let levels = playtime.most_played(MOST_PLAYED_COUNT);
+    if levels.is_empty() {
+        return "\nNo level played yet".to_owned();
+    }
+    levels
+        .into_iter()
+        .map(|(name, stats)| {
+            format!(
+                "\n{} - {} - cleared {} time(s)",
+                name,
+                format_playtime(stats.time),
+                stats.clears
+            )
+        })
+        .collect()
+}
+
+/// Show the statistics page, with the total playtime, the sessions, and the levels played the
+/// most, as of when the page opened.
+fn update_stats_panel(
+    mut commands: Commands,
+    menu: Res<StatsMenu>,
+    playtime: Res<Playtime>,
+    ui_resources: Res<UiResources>,
+    panel_query: Query<Entity, (With<StatsUi>, Without<Parent>)>,
+) {
+    if !menu.is_changed() {
+        return;
+    }
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !menu.open {
+        return;
+    }
+
+    let style = |font_size, color| TextStyle {
+        font: ui_resources.text_font(),
+        font_size,
+        color,
+    };
+    let sections = [
+        "Statistics".to_owned(),
+        format!(
+            "\nPlaytime: {} - Sessions: {}",
+            format_playtime(playtime.total()),
+            playtime.sessions()
+        ),
+        "\n\nMost played levels".to_owned(),
+        most_played_lines(&playtime),
+        format!("\n\n[{:?}] to close", STATS_KEY),
+    ]
+    .into_iter()
+    .zip([
+        (36.0, Color::WHITE),
+        (24.0, Color::rgb_u8(255, 220, 120)),
+        (24.0, Color::WHITE),
+        (20.0, Color::rgb_u8(200, 200, 200)),
+        (18.0, Color::WHITE),
+    ])
+    .map(|(value, (font_size, color))| TextSection {
+        value,
+        style: style(font_size, color),
+    })
+    .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("StatsPage"))
+        .insert(MenuOverlay)
+        .insert(StatsUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections,
+                        alignment: TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .insert(StatsUi);
+        });
+}
+
+fn stats_cleanup(
+    mut commands: Commands,
+    mut menu: ResMut<StatsMenu>,
+    panel_query: Query<Entity, (With<StatsUi>, Without<Parent>)>,
+) {
+    menu.open = false;
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin tracking the total playtime, the time spent in each level, and the sessions, locally in
+/// the profile storage, with a statistics page in the main menu. Nothing is sent over the network.
+pub struct PlaytimePlugin;
+
+impl Plugin for PlaytimePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Playtime::start_session())
+            .insert_resource(StatsMenu::default())
+            .add_system(track_playtime)
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(record_level_clear))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(save_playtime))
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(AppState::MainMenu)
+                    .after(InputSystem)
+                    .after("profile_menu_input")
+                    .before("calendar_input")
+                    .before("encyclopedia_input")
+                    .with_system(stats_input.label("stats_input")),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu).with_system(update_stats_panel),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(stats_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playtime() {
+        let mut playtime = Playtime::default();
+        assert!(!playtime.add_time(12.0, None));
+        assert!(!playtime.add_time(10.0, Some("Tutorial")));
+        playtime.record_clear("Tutorial");
+        assert!(playtime.add_time(15.0, Some("Twins")));
+        assert!(playtime.add_time(5.0, Some("Twins")));
+        assert_eq!(playtime.total(), 42.0);
+        assert_eq!(
+            playtime.level("Tutorial"),
+            Some(&LevelPlaytime {
+                time: 10.0,
+                clears: 1
+            })
+        );
+        let most_played: Vec<_> = playtime
+            .most_played(1)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(most_played, ["Twins"]);
+
+        assert_eq!(format_playtime(247.6), "4m 07s");
+        assert_eq!(format_playtime(7500.0), "2h 05m");
+    }
+}
@@ -0,0 +1,334 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{
+        camera::PerspectiveProjection,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+use crate::{config::Config, game::GameEvent, AppState, Level};
+
+/// Distance of the overlays in front of the camera, closer than anything in the scene but past
+/// the near plane.
+const OVERLAY_DISTANCE: f32 = 0.5;
+
+/// Mid gray the scene fades toward, lowering its saturation and contrast.
+const FADE_GRAY: f32 = 0.5;
+
+/// Duration of the bloom flash when a level is cleared, in seconds.
+const BLOOM_DURATION: f32 = 1.5;
+
+/// Peak opacity of the bloom flash.
+const BLOOM_INTENSITY: f32 = 0.4;
+
+/// Color grade of the scene, configured per world in the game data. The grade is applied with
+/// blended overlays in front of the camera rather than a separate render pass, so works the same
+/// with the WebGL2 backend; the fade can only lower the saturation and contrast, not raise them.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ColorGrade {
+    /// RGB color the scene is tinted toward.
+    pub tint: [f32; 3],
+    /// Strength of the tint, from 0 for none to 1 for a flat color.
+    pub tint_strength: f32,
+    /// Fade of the scene toward a mid gray, lowering both its saturation and contrast, from 0 for
+    /// none to 1 for a flat gray.
+    pub fade: f32,
+    /// Darkening of the screen corners, from 0 for none to 1 for black corners.
+    pub vignette: f32,
+}
+
+impl Default for ColorGrade {
+    fn default() -> Self {
+        ColorGrade {
+            tint: [1.0, 1.0, 1.0],
+            tint_strength: 0.0,
+            fade: 0.0,
+            vignette: 0.3,
+        }
+    }
+}
+
+impl ColorGrade {
+    /// Color of the single overlay blended over the scene, equivalent to blending the tint then
+    /// the fade gray.
+    pub fn overlay_color(&self) -> Color {
+        let tint = Vec3::from(self.tint) * self.tint_strength.clamp(0.0, 1.0);
+        let fade = self.fade.clamp(0.0, 1.0);
+        let alpha = 1.0 - (1.0 - self.tint_strength.clamp(0.0, 1.0)) * (1.0 - fade);
+        if alpha <= 0.0 {
+            return Color::NONE;
+        }
+        let color = (tint * (1.0 - fade) + Vec3::splat(FADE_GRAY * fade)) / alpha;
+        Color::rgba(color.x, color.y, color.z, alpha)
+    }
+}
+
+/// Resource tracking the post-processing overlays of the scene camera.
+#[derive(Debug, Default)]
+pub struct PostProcess {
+    /// Root entity of the overlays, parented to the camera.
+    root: Option<Entity>,
+    /// Grade currently applied.
+    grade: Option<ColorGrade>,
+    grade_material: Handle<StandardMaterial>,
+    vignette_material: Handle<StandardMaterial>,
+    bloom_material: Handle<StandardMaterial>,
+    /// Time since the bloom flash started, if playing.
+    bloom_time: Option<f32>,
+}
+
+impl PostProcess {
+    pub fn new() -> Self {
+        PostProcess::default()
+    }
+}
+
+/// Marker for the root of the overlays, scaled to cover the camera view.
+#[derive(Component)]
+struct OverlayRoot;
+
+/// Create a square radial texture, with the given alpha from its center to its corners.
+fn create_radial_image(alpha: impl Fn(f32) -> f32) -> Image {
+    const TEX_SIZE: u32 = 64;
+    let mut data = Vec::<u8>::with_capacity(TEX_SIZE as usize * TEX_SIZE as usize * 4);
+    let half = TEX_SIZE as f32 / 2.0;
+    for j in 0..TEX_SIZE {
+        for i in 0..TEX_SIZE {
+            let d = Vec2::new(i as f32 + 0.5 - half, j as f32 + 0.5 - half).length()
+                / (half * 2.0_f32.sqrt());
+            let alpha = alpha(d).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: TEX_SIZE,
+            height: TEX_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Spawn the overlays in front of the scene camera once it exists, or despawn them when the
+/// post-processing gets disabled in the config.
+fn setup_overlays(
+    mut commands: Commands,
+    config: Res<Config>,
+    mut post_process: ResMut<PostProcess>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera_query: Query<Entity, With<PerspectiveProjection>>,
+) {
+    if !config.graphics.post_processing {
+        if let Some(root) = post_process.root.take() {
+            commands.entity(root).despawn_recursive();
+        }
+        return;
+    }
+    if post_process.root.is_some() {
+        return;
+    }
+    let camera = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    let mut overlay_material = |image: Option<Handle<Image>>| {
+        materials.add(StandardMaterial {
+            base_color: Color::NONE,
+            base_color_texture: image,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
+            ..Default::default()
+        })
+    };
+    post_process.grade_material = overlay_material(None);
+    post_process.vignette_material = overlay_material(Some(
+        images.add(create_radial_image(|d| ((d - 0.4) / 0.6).max(0.0).powi(2))),
+    ));
+    post_process.bloom_material =
+        overlay_material(Some(images.add(create_radial_image(|d| (1.0 - d).powi(2)))));
+    post_process.grade = None;
+
+    // Overlays back to front, so the blending order follows the transparent sorting
+    let quad = meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE)));
+    let overlays = [
+        ("Grade", post_process.grade_material.clone()),
+        ("Vignette", post_process.vignette_material.clone()),
+        ("Bloom", post_process.bloom_material.clone()),
+    ];
+    let root = commands
+        .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+        .insert(Name::new("PostProcess"))
+        .insert(OverlayRoot)
+        .insert(Parent(camera))
+        .with_children(|parent| {
+            for (index, (name, material)) in overlays.into_iter().enumerate() {
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: quad.clone(),
+                        material,
+                        transform: Transform::from_xyz(0.0, 0.0, index as f32 * 0.01),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(name))
+                    .insert(NotShadowCaster)
+                    .insert(NotShadowReceiver);
+            }
+        })
+        .id();
+    post_process.root = Some(root);
+}
+
+/// Scale the overlays to cover the whole view of the camera, following its aspect ratio.
+fn fit_overlays(
+    camera_query: Query<&PerspectiveProjection>,
+    mut query: Query<&mut Transform, With<OverlayRoot>>,
+) {
+    let projection = match camera_query.iter().next() {
+        Some(projection) => projection,
+        None => return,
+    };
+    // Slightly larger than the view, so the edges never show
+    let height = 2.2 * OVERLAY_DISTANCE * (projection.fov / 2.0).tan();
+    let scale = Vec3::new(height * projection.aspect_ratio, height, 1.0);
+    for mut transform in query.iter_mut() {
+        if transform.scale != scale {
+            transform.translation = Vec3::new(0.0, 0.0, -OVERLAY_DISTANCE);
+            transform.scale = scale;
+        }
+    }
+}
+
+/// Apply the color grade of the world of the level whenever it changes.
+fn update_grade(
+    level: Res<Level>,
+    mut post_process: ResMut<PostProcess>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if post_process.root.is_none() {
+        return;
+    }
+    let grade = level
+        .desc()
+        .and_then(|level_desc| level_desc.world.as_ref())
+        .map_or_else(ColorGrade::default, |world| world.grade);
+    if post_process.grade == Some(grade) {
+        return;
+    }
+    debug!("Color grade: {:?}", grade);
+    post_process.grade = Some(grade);
+    if let Some(material) = materials.get_mut(&post_process.grade_material) {
+        material.base_color = grade.overlay_color();
+    }
+    if let Some(material) = materials.get_mut(&post_process.vignette_material) {
+        material.base_color = Color::rgba(0.0, 0.0, 0.0, grade.vignette.clamp(0.0, 1.0));
+    }
+}
+
+/// Flash a warm glow over the scene when a level is cleared.
+fn victory_bloom(
+    time: Res<Time>,
+    config: Res<Config>,
+    mut ev_game: EventReader<GameEvent>,
+    mut post_process: ResMut<PostProcess>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some()
+        && config.graphics.bloom
+        && post_process.root.is_some()
+    {
+        post_process.bloom_time = Some(0.0);
+    }
+    let t = match post_process.bloom_time.as_mut() {
+        Some(t) => {
+            *t += time.delta_seconds();
+            *t
+        }
+        None => return,
+    };
+    let alpha = if t < BLOOM_DURATION {
+        BLOOM_INTENSITY * (PI * t / BLOOM_DURATION).sin()
+    } else {
+        post_process.bloom_time = None;
+        0.0
+    };
+    if let Some(material) = materials.get_mut(&post_process.bloom_material) {
+        material.base_color = Color::rgba(1.0, 0.95, 0.8, alpha);
+    }
+}
+
+/// The overlays are despawned along the camera.
+fn post_process_cleanup(mut post_process: ResMut<PostProcess>) {
+    *post_process = PostProcess::new();
+}
+
+/// Plugin for the post-processing of the scene: a color grade per world, a vignette, and a bloom
+/// flash on victory. Toggled with the `graphics.post_processing` and `graphics.bloom` config.
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PostProcess::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(setup_overlays)
+                    .with_system(fit_overlays)
+                    .with_system(update_grade)
+                    .with_system(victory_bloom),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(post_process_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay() {
+        assert_eq!(ColorGrade::default().overlay_color(), Color::NONE);
+
+        // A full fade is a flat gray, whatever the tint
+        let gray = ColorGrade {
+            tint: [1.0, 0.0, 0.0],
+            tint_strength: 0.5,
+            fade: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            gray.overlay_color(),
+            Color::rgba(FADE_GRAY, FADE_GRAY, FADE_GRAY, 1.0)
+        );
+
+        // A single overlay matches the tint then the fade blended in turn
+        let grade: ColorGrade = serde_json::from_str(
+            r#"{ "tint": [1.0, 0.5, 0.0], "tint_strength": 0.2, "fade": 0.3 }"#,
+        )
+        .unwrap();
+        assert_eq!(grade.vignette, ColorGrade::default().vignette);
+        let blend = |c: Vec3, over: Vec3, alpha: f32| c * (1.0 - alpha) + over * alpha;
+        let scene = Vec3::new(0.1, 0.6, 0.9);
+        let expected = blend(
+            blend(scene, Vec3::new(1.0, 0.5, 0.0), 0.2),
+            Vec3::splat(FADE_GRAY),
+            0.3,
+        );
+        let overlay = grade.overlay_color();
+        let color = Vec3::new(overlay.r(), overlay.g(), overlay.b());
+        assert!((blend(scene, color, overlay.a()) - expected).length() < 1e-5);
+    }
+}
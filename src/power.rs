@@ -0,0 +1,164 @@
+use bevy::{
+    prelude::*,
+    winit::{UpdateMode, WinitSettings},
+};
+use std::time::Duration;
+
+use crate::{config::PowerSaving, Config};
+
+/// Frame rate the game is capped to in low-power mode, plenty for a puzzle game.
+const LOW_POWER_FPS: f64 = 30.0;
+
+/// Maximum time between two updates while the window is unfocused in power-saving mode. The game
+/// also updates on any interaction with the window.
+const UNFOCUSED_MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// Interval between two checks of the power source, in seconds.
+const POWER_SOURCE_CHECK_INTERVAL: f32 = 10.0;
+
+/// Is the device running on battery? `None` if unknown, like on desktops without a battery.
+#[cfg(target_os = "linux")]
+fn on_battery() -> Option<bool> {
+    let mut has_battery = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        match kind.trim() {
+            "Mains" => {
+                let online = std::fs::read_to_string(path.join("online")).unwrap_or_default();
+                if online.trim() == "1" {
+                    return Some(false);
+                }
+            }
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    if has_battery {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Is the device running on battery? `None` if unknown, like on desktops without a battery.
+#[cfg(target_os = "windows")]
+fn on_battery() -> Option<bool> {
+    use winapi::um::winbase::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+/// Is the device running on battery? Unknown on the other platforms.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn on_battery() -> Option<bool> {
+    None
+}
+
+/// Should the game run in low-power mode while its window is focused, then while unfocused?
+fn low_power_modes(power_saving: PowerSaving, on_battery: bool) -> (bool, bool) {
+    match power_saving {
+        PowerSaving::Off => (false, false),
+        PowerSaving::Auto => (on_battery, true),
+        PowerSaving::On => (true, true),
+    }
+}
+
+/// Resource tracking the power source of the device, and the low-power modes applied.
+struct PowerState {
+    /// Is the device running on battery, as of the last check?
+    on_battery: bool,
+    /// Timer of the next check of the power source.
+    timer: Timer,
+    /// Low-power modes applied, while focused and unfocused.
+    applied: Option<(bool, bool)>,
+}
+
+impl Default for PowerState {
+    fn default() -> Self {
+        PowerState {
+            on_battery: on_battery().unwrap_or(false),
+            timer: Timer::from_seconds(POWER_SOURCE_CHECK_INTERVAL, true),
+            applied: None,
+        }
+    }
+}
+
+/// Switch the event loop to the low-power modes following the `graphics.power_saving` config,
+/// the power source, and the focus of the window. The low-power mode caps the frame rate, only
+/// updating faster on input, and the unfocused window only updates about once a second.
+fn update_power_mode(
+    time: Res<Time>,
+    config: Res<Config>,
+    mut power: ResMut<PowerState>,
+    mut winit_settings: ResMut<WinitSettings>,
+) {
+    if power.timer.tick(time.delta()).just_finished() {
+        let on_battery = on_battery().unwrap_or(false);
+        if on_battery != power.on_battery {
+            info!(
+                "Power source: {}",
+                if on_battery { "battery" } else { "AC" }
+            );
+            power.on_battery = on_battery;
+        }
+    }
+    let modes = low_power_modes(config.graphics.power_saving, power.on_battery);
+    if power.applied == Some(modes) {
+        return;
+    }
+    power.applied = Some(modes);
+    let (focused, unfocused) = modes;
+    debug!(
+        "Power saving: low-power mode {} while focused, {} while unfocused",
+        if focused { "on" } else { "off" },
+        if unfocused { "on" } else { "off" }
+    );
+    winit_settings.focused_mode = if focused {
+        UpdateMode::Reactive {
+            max_wait: Duration::from_secs_f64(1.0 / LOW_POWER_FPS),
+        }
+    } else {
+        UpdateMode::Continuous
+    };
+    winit_settings.unfocused_mode = if unfocused {
+        UpdateMode::ReactiveLowPower {
+            max_wait: UNFOCUSED_MAX_WAIT,
+        }
+    } else {
+        UpdateMode::Continuous
+    };
+}
+
+/// Plugin capping the frame rate to reduce the fan noise and the battery drain, while the window
+/// is unfocused or the device runs on battery, or always. See the `graphics.power_saving` config.
+/// Not needed on the web, where the browser already throttles the pages in the background.
+pub struct PowerSavingPlugin;
+
+impl Plugin for PowerSavingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PowerState::default())
+            .add_system(update_power_mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        assert_eq!(low_power_modes(PowerSaving::Off, true), (false, false));
+        assert_eq!(low_power_modes(PowerSaving::Auto, false), (false, true));
+        assert_eq!(low_power_modes(PowerSaving::Auto, true), (true, true));
+        assert_eq!(low_power_modes(PowerSaving::On, false), (true, true));
+    }
+}
@@ -0,0 +1,507 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashSet};
+
+use crate::{
+    boot::UiResources,
+    cheats::Cheats,
+    cinematic::Hud,
+    coop::Coop,
+    game::{Game, GameEvent, GameMode, GameSequence, GameplaySystem},
+    inventory::{Inventory, RegenerateInventoryUiEvent, UpdateInventorySlots},
+    journal::LevelJournal,
+    rules::Rules,
+    scores::ScoreTracker,
+    serialize::BuildableRegistry,
+    snapshot::LevelSnapshot,
+    storage, AppState, Cursor, Grid, Level, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Save file of the levels in practice mode, in the profile storage.
+pub const PRACTICE_FILE: &str = "practice.json";
+
+/// Key to toggle the practice mode of the current level.
+const PRACTICE_KEY: KeyCode = KeyCode::T;
+
+/// Key to rewind the last placement in practice mode.
+const REWIND_KEY: KeyCode = KeyCode::LBracket;
+
+/// Key to replay the next placement rewound in practice mode.
+const FORWARD_KEY: KeyCode = KeyCode::RBracket;
+
+/// Entries recorded one after the other, which can be scrubbed back and forth. Recording a new
+/// entry after rewinding discards the entries rewound past.
+#[derive(Debug)]
+struct Timeline<T> {
+    entries: Vec<T>,
+    /// Index of the current entry, if any.
+    position: usize,
+}
+
+impl<T> Default for Timeline<T> {
+    fn default() -> Self {
+        Timeline {
+            entries: vec![],
+            position: 0,
+        }
+    }
+}
+
+impl<T> Timeline<T> {
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.position = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Record an entry after the current one.
+    fn push(&mut self, entry: T) {
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.position + 1);
+        }
+        self.entries.push(entry);
+        self.position = self.entries.len() - 1;
+    }
+
+    /// Step back to the previous entry, if any.
+    fn rewind(&mut self) -> Option<&T> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        self.entries.get(self.position)
+    }
+
+    /// Step forward to the next entry, if any.
+    fn forward(&mut self) -> Option<&T> {
+        if self.position + 1 >= self.entries.len() {
+            return None;
+        }
+        self.position += 1;
+        self.entries.get(self.position)
+    }
+}
+
+/// Resource tracking the levels in practice mode. Every placement in a practice level snapshots
+/// the level, and the player can freely rewind to any earlier placement; the level then doesn't
+/// count toward the scores, the best replays, or the achievements.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Practice {
+    /// Names of the levels in practice mode.
+    levels: HashSet<String>,
+    /// Was the practice mode enabled since the current level started?
+    #[serde(skip)]
+    used: bool,
+    /// Snapshots of the current level after each placement, starting with the empty grid.
+    #[serde(skip)]
+    timeline: Timeline<LevelSnapshot>,
+}
+
+impl Practice {
+    /// Load the practice levels of the active profile saved by a previous session, if any.
+    pub fn load() -> Self {
+        storage::read(PRACTICE_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(practice) => Some(practice),
+                Err(err) => {
+                    let location = storage::location(PRACTICE_FILE);
+                    warn!("Failed to parse practice levels '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(PRACTICE_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(PRACTICE_FILE);
+            warn!("Failed to save practice levels to '{}': {}", location, err);
+        }
+    }
+
+    /// Is a level in practice mode?
+    pub fn is_practiced(&self, level: &str) -> bool {
+        self.levels.contains(level)
+    }
+
+    /// Was the practice mode enabled since the current level started? The level result then
+    /// doesn't count toward the scores.
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    /// Toggle the practice mode of a level, returning whether it's now enabled.
+    fn toggle(&mut self, level: &str) -> bool {
+        let enabled = if self.levels.remove(level) {
+            false
+        } else {
+            self.levels.insert(level.to_owned());
+            self.used = true;
+            true
+        };
+        self.save();
+        enabled
+    }
+}
+
+/// Can the game mode be practiced? The snapshots only capture a single cursor and a fixed
+/// inventory, so not the multiplayer modes nor the market. The hard mode disables the undo.
+fn can_practice(game_mode: GameMode, rules: &Rules) -> bool {
+    matches!(game_mode, GameMode::Solo | GameMode::WeightReveal) && rules.can_undo()
+}
+
+/// Marker for the root of the practice timeline, rebuilt whenever the timeline changes.
+#[derive(Component)]
+struct PracticePanel;
+
+/// Toggle the practice mode of the level being played, starting the timeline from the current
+/// state of the level.
+fn practice_toggle(
+    keyboard_input: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    game_mode: Res<GameMode>,
+    rules: Res<Rules>,
+    level: Res<Level>,
+    journal: Res<LevelJournal>,
+    inventory: Res<Inventory>,
+    cheats: Res<Cheats>,
+    mut practice: ResMut<Practice>,
+    query: Query<&Cursor>,
+) {
+    if !keyboard_input.just_pressed(PRACTICE_KEY)
+        || !can_practice(*game_mode, &rules)
+        || level.desc().is_none()
+        || !matches!(game.sequence(), GameSequence::Intro | GameSequence::Play)
+    {
+        return;
+    }
+    let enabled = practice.toggle(level.name());
+    info!("Practice mode for level '{}': {}", level.name(), enabled);
+    practice.timeline.clear();
+    if enabled {
+        let snapshot = LevelSnapshot::capture(
+            level.index(),
+            &journal,
+            &inventory,
+            query.single().pos(),
+            cheats.is_used(),
+        );
+        practice.timeline.push(snapshot);
+    }
+}
+
+/// Start a new timeline whenever a practice level starts or restarts.
+fn start_timeline(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    game_mode: Res<GameMode>,
+    rules: Res<Rules>,
+    level: Res<Level>,
+    journal: Res<LevelJournal>,
+    inventory: Res<Inventory>,
+    mut practice: ResMut<Practice>,
+    query: Query<&Cursor>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if !(reset || restart) {
+        return;
+    }
+    practice.timeline.clear();
+    practice.used = can_practice(*game_mode, &rules) && practice.is_practiced(level.name());
+    if practice.used {
+        let snapshot = LevelSnapshot::capture(
+            level.index(),
+            &journal,
+            &inventory,
+            query.single().pos(),
+            false,
+        );
+        practice.timeline.push(snapshot);
+    }
+}
+
+/// Snapshot the practice level after each placement.
+fn record_placements(
+    mut ev_game: EventReader<GameEvent>,
+    level: Res<Level>,
+    journal: Res<LevelJournal>,
+    inventory: Res<Inventory>,
+    cheats: Res<Cheats>,
+    mut practice: ResMut<Practice>,
+    query: Query<&Cursor>,
+) {
+    let placed = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::BuildablePlaced { .. }))
+        .last()
+        .is_some();
+    if !placed || practice.timeline.is_empty() {
+        return;
+    }
+    let snapshot = LevelSnapshot::capture(
+        level.index(),
+        &journal,
+        &inventory,
+        query.single().pos(),
+        cheats.is_used(),
+    );
+    trace!(
+        "Practice: snapshot #{} with {} placement(s)",
+        practice.timeline.len(),
+        snapshot.placement_count()
+    );
+    practice.timeline.push(snapshot);
+}
+
+/// Scrub the timeline of the practice level back and forth, restoring the level to the selected
+/// placement. Rewinding a failed level resumes playing it.
+fn scrub_timeline(
+    mut commands: Commands,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    keyboard_input: Res<Input<KeyCode>>,
+    level: Res<Level>,
+    buildables: Res<BuildableRegistry>,
+    mut game: ResMut<Game>,
+    mut grid: ResMut<Grid>,
+    mut inventory: ResMut<Inventory>,
+    mut coop: ResMut<Coop>,
+    mut journal: ResMut<LevelJournal>,
+    mut cheats: ResMut<Cheats>,
+    mut practice: ResMut<Practice>,
+    mut tracker: ResMut<ScoreTracker>,
+    mut query: Query<(&mut Cursor, &mut Transform, &mut Visibility)>,
+) {
+    if !matches!(game.sequence(), GameSequence::Play | GameSequence::Defeat) {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    let rewind = keyboard_input.just_pressed(REWIND_KEY);
+    let snapshot = if rewind {
+        practice.timeline.rewind().cloned()
+    } else if keyboard_input.just_pressed(FORWARD_KEY) {
+        practice.timeline.forward().cloned()
+    } else {
+        return;
+    };
+    let snapshot = match snapshot {
+        Some(snapshot) if snapshot.level_index() == level.index() => snapshot,
+        _ => return,
+    };
+    debug!(
+        "Practice: scrub to snapshot #{} with {} placement(s)",
+        practice.timeline.position,
+        snapshot.placement_count()
+    );
+
+    let (mut cursor, mut transform, mut visibility) = query.single_mut();
+    snapshot.restore(
+        &mut commands,
+        &mut grid,
+        cursor.spawn_root_entity,
+        &buildables,
+        &mut inventory,
+        coop.inventory_mut(),
+        &mut journal,
+        level_desc,
+    );
+    if snapshot.is_cheated() {
+        cheats.set_used();
+    }
+    if rewind {
+        tracker.record_undo();
+    }
+    if game.sequence() == GameSequence::Defeat {
+        game.resume();
+        cursor.set_enabled(true);
+    }
+    cursor.set_pos(snapshot.cursor_pos(), &grid, &mut transform);
+    visibility.is_visible = !inventory.is_empty();
+    ev_regen_ui.send(RegenerateInventoryUiEvent);
+    ev_update_slots.send(UpdateInventorySlots);
+}
+
+fn spawn_practice_panel(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(60.0),
+                    left: Val::Px(60.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::NONE),
+            ..Default::default()
+        })
+        .insert(Name::new("PracticePanel"))
+        .insert(Hud)
+        .insert(PracticePanel);
+}
+
+/// Show the timeline of the practice level, with a marker per placement and the current one
+/// highlighted.
+fn update_practice_panel(
+    mut commands: Commands,
+    practice: Res<Practice>,
+    ui_resources: Res<UiResources>,
+    query: Query<Entity, With<PracticePanel>>,
+) {
+    if !practice.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_descendants();
+        if practice.timeline.is_empty() {
+            continue;
+        }
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        right: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    format!(
+                        "Practice [{:?}]  [{:?}] rewind  [{:?}] forward",
+                        PRACTICE_KEY, REWIND_KEY, FORWARD_KEY
+                    ),
+                    TextStyle {
+                        font: ui_resources.text_font(),
+                        font_size: 20.0,
+                        color: Color::rgb_u8(160, 220, 240),
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+            for index in 0..practice.timeline.len() {
+                let (size, color) = match index.cmp(&practice.timeline.position) {
+                    Ordering::Less => (10.0, Color::rgb_u8(160, 220, 240)),
+                    Ordering::Equal => (16.0, Color::WHITE),
+                    Ordering::Greater => (10.0, Color::rgba(0.6, 0.6, 0.6, 0.5)),
+                };
+                parent.spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(size), Val::Px(size)),
+                        margin: Rect::all(Val::Px(3.0)),
+                        ..Default::default()
+                    },
+                    color: UiColor(color),
+                    ..Default::default()
+                });
+            }
+        });
+    }
+}
+
+fn practice_cleanup(
+    mut commands: Commands,
+    mut practice: ResMut<Practice>,
+    query: Query<Entity, With<PracticePanel>>,
+) {
+    practice.timeline.clear();
+    practice.used = false;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the practice mode, toggled per level, where every placement can be rewound. The
+/// practice levels don't count toward the scores.
+pub struct PracticePlugin;
+
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Practice::load())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_practice_panel))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Input)
+                    .with_system(practice_toggle)
+                    .with_system(scrub_timeline),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Reset)
+                    .after("journal_start")
+                    .before(GameplaySystem::Cursor)
+                    .with_system(start_timeline),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Placement)
+                    .before(GameplaySystem::Balance)
+                    .with_system(record_placements),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_practice_panel),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(practice_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeline() {
+        let mut timeline = Timeline::default();
+        assert!(timeline.is_empty());
+        assert_eq!(timeline.rewind(), None);
+
+        timeline.push(0);
+        timeline.push(1);
+        timeline.push(2);
+        assert_eq!(timeline.forward(), None);
+        assert_eq!(timeline.rewind(), Some(&1));
+        assert_eq!(timeline.rewind(), Some(&0));
+        assert_eq!(timeline.rewind(), None);
+        assert_eq!(timeline.forward(), Some(&1));
+
+        // Placing after a rewind discards the placements rewound past
+        timeline.push(3);
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline.forward(), None);
+        assert_eq!(timeline.rewind(), Some(&1));
+    }
+
+    #[test]
+    fn deserialize() {
+        let practice: Practice = serde_json::from_str(r#"{ "levels": ["Sandbox"] }"#).unwrap();
+        assert!(practice.is_practiced("Sandbox"));
+        assert!(!practice.is_practiced("Tutorial"));
+        assert!(!practice.is_used());
+        assert!(practice.timeline.is_empty());
+
+        // Only the practice levels are saved
+        let json = serde_json::to_string(&practice).unwrap();
+        assert_eq!(json, r#"{"levels":["Sandbox"]}"#);
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+
+use crate::{config::Config, AppState, Level};
+
+/// Activity of the player shown by the platforms supporting rich presence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresenceState {
+    /// Browsing the main menu.
+    MainMenu,
+    /// Balancing a level, with its 1-based number in the level sequence.
+    Playing { level: String, number: usize },
+    /// Watching the end screen, after clearing all the levels.
+    TheEnd,
+}
+
+impl PresenceState {
+    /// Line describing the activity, like "Balancing 'Downtown' — level 7".
+    pub fn details(&self) -> String {
+        match self {
+            PresenceState::MainMenu => "In the main menu".to_owned(),
+            PresenceState::Playing { level, number } => {
+                format!("Balancing '{}' — level {}", level, number)
+            }
+            PresenceState::TheEnd => "Cleared all the levels".to_owned(),
+        }
+    }
+}
+
+/// Platform showing the activity of the player to their friends.
+pub trait PresenceBackend: Send + Sync + 'static {
+    /// Short name of the platform, for logging.
+    fn name(&self) -> &str;
+
+    /// Show a new activity, replacing the previous one.
+    fn set_presence(&mut self, state: &PresenceState);
+}
+
+/// [`PresenceBackend`] for Discord, through the IPC socket of the local Discord client.
+pub struct DiscordPresence {
+    client: DiscordIpcClient,
+    /// Is the IPC socket connected? Reconnects on the next activity change otherwise, in case
+    /// Discord was started after the game.
+    connected: bool,
+}
+
+impl DiscordPresence {
+    pub fn new(client_id: &str) -> Self {
+        DiscordPresence {
+            client: DiscordIpcClient::new(client_id),
+            connected: false,
+        }
+    }
+}
+
+impl PresenceBackend for DiscordPresence {
+    fn name(&self) -> &str {
+        "Discord"
+    }
+
+    fn set_presence(&mut self, state: &PresenceState) {
+        if !self.connected {
+            if let Err(err) = self.client.connect() {
+                debug!("Discord is not available: {}", err);
+                return;
+            }
+            self.connected = true;
+        }
+        let details = state.details();
+        if let Err(err) = self
+            .client
+            .set_activity(Activity::new().details(details.as_str()))
+        {
+            debug!("Failed to set the Discord activity: {}", err);
+            self.connected = false;
+        }
+    }
+}
+
+impl Drop for DiscordPresence {
+    fn drop(&mut self) {
+        if self.connected {
+            let _ = self.client.close();
+        }
+    }
+}
+
+/// Resource forwarding the activity of the player to the rich presence backends.
+#[derive(Default)]
+pub struct Presence {
+    backends: Vec<Box<dyn PresenceBackend>>,
+    /// Activity last shown, if any.
+    current: Option<PresenceState>,
+    /// Client ID of the Discord application the backends were created for, if any.
+    discord_client_id: Option<String>,
+}
+
+impl Presence {
+    pub fn new() -> Self {
+        Presence::default()
+    }
+
+    /// Add a backend, showing the current activity right away.
+    pub fn add_backend(&mut self, mut backend: impl PresenceBackend) {
+        info!("Rich presence enabled on {}", backend.name());
+        if let Some(state) = &self.current {
+            backend.set_presence(state);
+        }
+        self.backends.push(Box::new(backend));
+    }
+
+    /// Show a new activity on all the backends, if it changed.
+    pub fn set_presence(&mut self, state: PresenceState) {
+        if self.current.as_ref() == Some(&state) {
+            return;
+        }
+        trace!("Presence: {}", state.details());
+        for backend in &mut self.backends {
+            backend.set_presence(&state);
+        }
+        self.current = Some(state);
+    }
+}
+
+/// Follow the app state and the level being played, on the backends enabled by the config. The
+/// backends only reconnect when their config changes, not on any other change of the config.
+fn update_presence(
+    config: Res<Config>,
+    state: Res<State<AppState>>,
+    level: Res<Level>,
+    mut presence: ResMut<Presence>,
+) {
+    if config.is_changed() && config.presence.discord_client_id != presence.discord_client_id {
+        presence.backends.clear();
+        presence.discord_client_id = config.presence.discord_client_id.clone();
+        if let Some(client_id) = &config.presence.discord_client_id {
+            presence.add_backend(DiscordPresence::new(client_id));
+        }
+    }
+    let presence_state = match state.current() {
+        AppState::Boot => return,
+        AppState::MainMenu => PresenceState::MainMenu,
+        AppState::InGame => match level.desc() {
+            Some(_) => PresenceState::Playing {
+                level: level.name().to_owned(),
+                number: level.index() + 1,
+            },
+            None => return,
+        },
+        AppState::TheEnd => PresenceState::TheEnd,
+    };
+    presence.set_presence(presence_state);
+}
+
+/// Plugin for the rich presence, showing what the player is up to on the platforms configured.
+pub struct PresencePlugin;
+
+impl Plugin for PresencePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Presence::new())
+            .add_system(update_presence);
+    }
+}
@@ -0,0 +1,383 @@
+use bevy::{ecs::event::Events, input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    assist::Assist,
+    boot::UiResources,
+    config::{BaseConfig, Config},
+    daily::DailyProgress,
+    encyclopedia::Encyclopedia,
+    ghost::BestReplays,
+    hard::HardMode,
+    onboarding::Onboarding,
+    playtime::Playtime,
+    practice::Practice,
+    serialize::BuildableRegistry,
+    snapshot::QuickSave,
+    storage,
+    wardrobe::{Wardrobe, WardrobeMenu},
+    AppState,
+};
+
+/// File listing the save profiles and the active one, shared by all the profiles.
+const PROFILES_FILE: &str = "profiles.json";
+
+/// Save file of the settings of a profile overriding the game config, in the profile storage.
+/// See [`Config::from_json_with_overrides()`].
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Display name of the default profile, which always exists.
+const DEFAULT_PROFILE_NAME: &str = "Default";
+
+/// Key to switch to the next profile in the main menu.
+const NEXT_PROFILE_KEY: KeyCode = KeyCode::P;
+
+/// Key to create a new profile in the main menu.
+const NEW_PROFILE_KEY: KeyCode = KeyCode::N;
+
+/// Resource listing the save profiles, each with its own progress, settings, and statistics.
+/// The save files of the active profile are accessed through the [`storage`] module.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    /// Names of the profiles, besides the default one, in creation order.
+    names: Vec<String>,
+    /// Name of the active profile, or `None` for the default profile.
+    active: Option<String>,
+}
+
+impl Profiles {
+    /// Load the list of profiles saved by a previous session, if any.
+    pub fn load() -> Self {
+        let mut profiles: Profiles = storage::read_shared(PROFILES_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(profiles) => Some(profiles),
+                Err(err) => {
+                    warn!("Failed to parse profiles '{}': {}", PROFILES_FILE, err);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        profiles
+            .names
+            .retain(|name| storage::is_valid_profile_name(name));
+        if let Some(active) = &profiles.active {
+            if !profiles.names.contains(active) {
+                warn!("Unknown active profile '{}', using the default one", active);
+                profiles.active = None;
+            }
+        }
+        profiles
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                storage::write_shared(PROFILES_FILE, &json).map_err(anyhow::Error::from)
+            });
+        if let Err(err) = result {
+            warn!("Failed to save profiles to '{}': {}", PROFILES_FILE, err);
+        }
+    }
+
+    /// Display name of the active profile.
+    pub fn active_name(&self) -> &str {
+        self.active.as_deref().unwrap_or(DEFAULT_PROFILE_NAME)
+    }
+
+    /// Number of profiles, including the default one.
+    pub fn len(&self) -> usize {
+        self.names.len() + 1
+    }
+
+    /// Can a new profile be created with that name?
+    pub fn can_create(&self, name: &str) -> bool {
+        storage::is_valid_profile_name(name)
+            && !name.eq_ignore_ascii_case(DEFAULT_PROFILE_NAME)
+            && !self
+                .names
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(name))
+    }
+
+    /// Create a new profile and make it active, starting with the settings of the profile active
+    /// until then. Returns `false` if the name is not available.
+    pub fn create(&mut self, name: &str) -> bool {
+        if !self.can_create(name) {
+            return false;
+        }
+        let settings = settings();
+        self.names.push(name.to_owned());
+        self.activate(Some(name.to_owned()));
+        if !settings.is_null() {
+            save_settings(&settings);
+        }
+        true
+    }
+
+    /// Make the profile after the active one active, wrapping around through the default one.
+    pub fn activate_next(&mut self) {
+        let next = match &self.active {
+            None => self.names.first(),
+            Some(active) => {
+                let index = self.names.iter().position(|name| name == active);
+                index.and_then(|index| self.names.get(index + 1))
+            }
+        };
+        self.activate(next.cloned());
+    }
+
+    /// Make a profile active, or the default one if `None`, and scope the save files to it.
+    fn activate(&mut self, profile: Option<String>) {
+        storage::set_profile(profile.as_deref());
+        self.active = profile;
+        self.save();
+        info!("Active profile: {}", self.active_name());
+    }
+}
+
+/// Activate a profile by name at launch, creating it if needed. Must be called before the game
+/// plugins are added, for them to load the save files of that profile.
+pub fn launch_with_profile(name: &str) {
+    let mut profiles = Profiles::load();
+    if profiles.names.iter().any(|other| other == name) {
+        profiles.activate(Some(name.to_owned()));
+    } else if !profiles.create(name) {
+        error!("Invalid profile name '{}', using the active profile", name);
+    }
+}
+
+/// Settings of the active profile overriding the game config, or `Null` if none.
+pub fn settings() -> Value {
+    storage::read(SETTINGS_FILE)
+        .and_then(|json| match serde_json::from_str(&json) {
+            Ok(settings) => Some(settings),
+            Err(err) => {
+                let location = storage::location(SETTINGS_FILE);
+                warn!("Failed to parse profile settings '{}': {}", location, err);
+                None
+            }
+        })
+        .unwrap_or(Value::Null)
+}
+
+/// Save the settings of the active profile overriding the game config, replacing any previous
+/// ones. See [`settings()`].
+pub fn save_settings(settings: &Value) {
+    let result = serde_json::to_string_pretty(settings)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| storage::write(SETTINGS_FILE, &json).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        let location = storage::location(SETTINGS_FILE);
+        warn!("Failed to save profile settings to '{}': {}", location, err);
+    }
+}
+
+/// Event sent when another profile becomes active, or when the save files of the active profile
+/// were updated, for the save data to be reloaded.
+#[derive(Debug)]
+pub struct ProfileChangedEvent;
+
+/// Resource holding the state of the profile selection in the main menu.
+#[derive(Debug, Default)]
+pub struct ProfileMenu {
+    /// Name of the new profile being typed, if any.
+    new_name: Option<String>,
+}
+
+/// Marker for the text of the active profile in the main menu.
+#[derive(Component)]
+struct ProfileText;
+
+fn spawn_profile_text(
+    mut commands: Commands,
+    ui_resources: Res<UiResources>,
+    profiles: Res<Profiles>,
+    menu: Res<ProfileMenu>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(20.0),
+                    left: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                profile_text(&profiles, &menu),
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("ProfileText"))
+        .insert(ProfileText);
+}
+
+/// Switch to the next profile, or type the name of a new one, in the main menu. Runs right after
+/// the input is updated, to hide the keys typed in the name from the rest of the main menu.
+fn profile_menu_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut ev_char: EventReader<ReceivedCharacter>,
+    mut ev_profile_changed: EventWriter<ProfileChangedEvent>,
+    wardrobe_menu: Res<WardrobeMenu>,
+    mut profiles: ResMut<Profiles>,
+    mut menu: ResMut<ProfileMenu>,
+) {
+    let new_name = match &mut menu.new_name {
+        Some(new_name) => new_name,
+        None => {
+            ev_char.iter().last();
+            if wardrobe_menu.is_open() {
+                return;
+            }
+            if keyboard_input.just_pressed(NEXT_PROFILE_KEY) && profiles.len() > 1 {
+                profiles.activate_next();
+                ev_profile_changed.send(ProfileChangedEvent);
+            } else if keyboard_input.just_pressed(NEW_PROFILE_KEY) {
+                menu.new_name = Some(String::new());
+                keyboard_input.clear();
+            }
+            return;
+        }
+    };
+    for ev in ev_char.iter() {
+        if !ev.char.is_control() {
+            new_name.push(ev.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        new_name.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        if profiles.create(new_name) {
+            ev_profile_changed.send(ProfileChangedEvent);
+            menu.new_name = None;
+        } else {
+            warn!("Cannot create a profile named '{}'", new_name);
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        menu.new_name = None;
+    }
+    // Keep the typed keys from also triggering the main menu actions
+    keyboard_input.clear();
+}
+
+/// Reload the save data of the profile which became active.
+fn reload_profile(
+    mut ev_profile_changed: EventReader<ProfileChangedEvent>,
+    base_config: Res<BaseConfig>,
+    mut config: ResMut<Config>,
+    mut buildables: ResMut<BuildableRegistry>,
+    mut wardrobe: ResMut<Wardrobe>,
+    mut commands: Commands,
+) {
+    if ev_profile_changed.iter().last().is_none() {
+        return;
+    }
+    match Config::from_json_with_overrides(base_config.0.as_deref(), &settings()) {
+        Ok(profile_config) => *config = profile_config,
+        Err(err) => error!("Failed to apply the profile settings: {:?}", err),
+    }
+    *wardrobe = Wardrobe::load();
+    wardrobe.apply(&mut buildables);
+    commands.insert_resource(Assist::load());
+    commands.insert_resource(Encyclopedia::load());
+    commands.insert_resource(BestReplays::load());
+    commands.insert_resource(QuickSave::new());
+    commands.insert_resource(Practice::load());
+    commands.insert_resource(HardMode::load());
+    commands.insert_resource(Playtime::start_session());
+    commands.insert_resource(DailyProgress::load());
+    commands.insert_resource(Onboarding::load());
+}
+
+/// Text showing the active profile, or the name of the new profile being typed.
+fn profile_text(profiles: &Profiles, menu: &ProfileMenu) -> String {
+    match &menu.new_name {
+        Some(new_name) => {
+            let hint = if profiles.can_create(new_name) {
+                "[ENTER] create"
+            } else {
+                "letters, digits, spaces, - and _"
+            };
+            format!("New profile: {}_\n{}, [ESC] cancel", new_name, hint)
+        }
+        None if profiles.len() > 1 => format!(
+            "Profile: {}\n[{:?}] switch, [{:?}] new",
+            profiles.active_name(),
+            NEXT_PROFILE_KEY,
+            NEW_PROFILE_KEY
+        ),
+        None => format!(
+            "Profile: {}\n[{:?}] new",
+            profiles.active_name(),
+            NEW_PROFILE_KEY
+        ),
+    }
+}
+
+fn update_profile_text(
+    profiles: Res<Profiles>,
+    menu: Res<ProfileMenu>,
+    mut query: Query<&mut Text, With<ProfileText>>,
+) {
+    if !profiles.is_changed() && !menu.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = profile_text(&profiles, &menu);
+    }
+}
+
+fn profile_cleanup(
+    mut commands: Commands,
+    mut menu: ResMut<ProfileMenu>,
+    query: Query<Entity, With<ProfileText>>,
+) {
+    menu.new_name = None;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the save profiles, selected in the main menu. This must be added before the plugins
+/// loading save data, for them to load the data of the active profile.
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        let profiles = Profiles::load();
+        storage::set_profile(profiles.active.as_deref());
+        info!("Active profile: {}", profiles.active_name());
+        // Typed characters come from the window, which the headless app does not have
+        if !app.world.contains_resource::<Events<ReceivedCharacter>>() {
+            app.add_event::<ReceivedCharacter>();
+        }
+        app.insert_resource(profiles)
+            .insert_resource(ProfileMenu::default())
+            .add_event::<ProfileChangedEvent>()
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(spawn_profile_text))
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(AppState::MainMenu)
+                    .after(InputSystem)
+                    .with_system(profile_menu_input.label("profile_menu_input")),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(reload_profile)
+                    .with_system(update_profile_text),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(profile_cleanup));
+    }
+}
@@ -0,0 +1,537 @@
+use std::fmt;
+
+/// Number of error correction codewords per block, by version, at the medium error correction
+/// level. Index 0 is unused.
+const ECC_CODEWORDS_PER_BLOCK: [usize; 41] = [
+    0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+];
+
+/// Number of error correction blocks, by version, at the medium error correction level. Index 0
+/// is unused.
+const NUM_ECC_BLOCKS: [usize; 41] = [
+    0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23,
+    25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+];
+
+/// Format bits of the medium error correction level, which recovers about 15% of the codewords,
+/// enough for a code scanned from a screen.
+const ECC_LEVEL_BITS: u32 = 0b00;
+
+/// Width in modules of the light margin around the code, required by the scanners.
+pub const QUIET_ZONE: usize = 4;
+
+/// Error encoding a QR code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrError {
+    /// The data of the given length in bytes doesn't fit in the largest QR code.
+    DataTooLong(usize),
+}
+
+impl fmt::Display for QrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QrError::DataTooLong(len) => write!(
+                f,
+                "{} bytes don't fit in a QR code, the maximum is {}",
+                len,
+                byte_capacity(40)
+            ),
+        }
+    }
+}
+
+/// Number of data modules of a version, excluding the function patterns.
+fn raw_data_modules(version: usize) -> usize {
+    let mut result = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let num_align = version / 7 + 2;
+        result -= (25 * num_align - 10) * num_align - 55;
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+/// Number of data codewords of a version, excluding the error correction.
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_CODEWORDS_PER_BLOCK[version] * NUM_ECC_BLOCKS[version]
+}
+
+/// Number of bits of the length of the data in byte mode.
+fn count_bits(version: usize) -> usize {
+    if version <= 9 {
+        8
+    } else {
+        16
+    }
+}
+
+/// Maximum number of bytes of data in a code of a version, in byte mode.
+fn byte_capacity(version: usize) -> usize {
+    (data_codewords(version) * 8 - 4 - count_bits(version)) / 8
+}
+
+/// Product of two elements of the Galois field GF(2^8) of the Reed-Solomon code.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u8 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1D);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+/// Generator polynomial of a Reed-Solomon code of the given degree, highest coefficient first
+/// and without the leading 1.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+/// Error correction codewords of a block of data, the remainder of its division by the
+/// generator polynomial.
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_mul(y, factor);
+        }
+    }
+    result
+}
+
+/// Split the data codewords into blocks, append the error correction codewords of each block,
+/// and interleave the blocks into the final sequence of codewords.
+fn add_ecc_and_interleave(version: usize, data: &[u8]) -> Vec<u8> {
+    let num_blocks = NUM_ECC_BLOCKS[version];
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[version];
+    let raw_codewords = raw_data_modules(version) / 8;
+    let num_short_blocks = num_blocks - raw_codewords % num_blocks;
+    let short_block_len = raw_codewords / num_blocks;
+    let divisor = rs_divisor(ecc_len);
+
+    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut start = 0;
+    for i in 0..num_blocks {
+        let len = short_block_len - ecc_len + usize::from(i >= num_short_blocks);
+        let mut block = data[start..start + len].to_vec();
+        start += len;
+        let ecc = rs_remainder(&block, &divisor);
+        // Pad the short blocks to interleave all blocks with the same length
+        if i < num_short_blocks {
+            block.push(0);
+        }
+        block.extend(ecc);
+        blocks.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_block_len - ecc_len || j >= num_short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+/// Is the module at the given position flipped by the mask pattern?
+fn mask_bit(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// Penalty of a line of modules, for its runs of modules of the same color and its patterns
+/// looking like a finder pattern, which confuse the scanners.
+fn line_penalty(line: &[bool]) -> u32 {
+    let mut penalty = 0;
+    let mut run = 1;
+    for i in 1..=line.len() {
+        if i < line.len() && line[i] == line[i - 1] {
+            run += 1;
+            continue;
+        }
+        if run >= 5 {
+            penalty += 3 + (run - 5);
+        }
+        run = 1;
+    }
+
+    const FINDER_LIKE: [bool; 11] = [
+        true, false, true, true, true, false, true, false, false, false, false,
+    ];
+    let mut padded = vec![false; QUIET_ZONE];
+    padded.extend_from_slice(line);
+    padded.extend(std::iter::repeat_n(false, QUIET_ZONE));
+    for window in padded.windows(FINDER_LIKE.len()) {
+        if window == FINDER_LIKE || window.iter().eq(FINDER_LIKE.iter().rev()) {
+            penalty += 40;
+        }
+    }
+    penalty
+}
+
+/// QR code of some data, in byte mode and with the medium error correction level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrCode {
+    /// Number of modules on each side.
+    size: usize,
+    /// Modules, row by row, `true` for the dark ones.
+    modules: Vec<bool>,
+    /// Is the module part of a function pattern, which isn't masked? Only used while encoding.
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode some data into a QR code of the smallest version it fits in.
+    pub fn encode(data: &[u8]) -> Result<QrCode, QrError> {
+        let version = (1..=40)
+            .find(|&version| data.len() <= byte_capacity(version))
+            .ok_or(QrError::DataTooLong(data.len()))?;
+
+        // Byte mode segment, terminated and padded to the capacity of the version
+        let capacity = data_codewords(version) * 8;
+        let mut bits = Vec::with_capacity(capacity);
+        let mut push_bits = |value: usize, count: usize| {
+            bits.extend((0..count).rev().map(|i| (value >> i) & 1 != 0));
+        };
+        push_bits(0b0100, 4);
+        push_bits(data.len(), count_bits(version));
+        for &byte in data {
+            push_bits(byte as usize, 8);
+        }
+        let terminator = (capacity - bits.len()).min(4);
+        bits.extend(std::iter::repeat_n(false, terminator));
+        bits.resize(bits.len().div_ceil(8) * 8, false);
+        let mut codewords: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | u8::from(bit)))
+            .collect();
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if codewords.len() >= capacity / 8 {
+                break;
+            }
+            codewords.push(pad);
+        }
+        let codewords = add_ecc_and_interleave(version, &codewords);
+
+        let size = version * 4 + 17;
+        let mut qr = QrCode {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+        qr.draw_function_patterns(version);
+        qr.draw_codewords(&codewords);
+
+        // Keep the mask with the lowest penalty, the easiest to scan
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                qr.apply_mask(mask);
+                qr.draw_format_bits(mask);
+                let penalty = qr.penalty();
+                qr.apply_mask(mask);
+                penalty
+            })
+            .unwrap();
+        qr.apply_mask(mask);
+        qr.draw_format_bits(mask);
+        qr.function.clear();
+        Ok(qr)
+    }
+
+    /// Number of modules on each side, without the quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Is the module at the given position dark? Positions outside of the code, like in the quiet
+    /// zone, are light.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    /// Render the code into a grayscale image with the quiet zone, each module taking `scale`
+    /// pixels on each side. Returns the width of the square image and its pixels, row by row.
+    pub fn to_luma(&self, scale: usize) -> (usize, Vec<u8>) {
+        let width = (self.size + QUIET_ZONE * 2) * scale;
+        let mut pixels = Vec::with_capacity(width * width);
+        for py in 0..width {
+            for px in 0..width {
+                let x = (px / scale).wrapping_sub(QUIET_ZONE);
+                let y = (py / scale).wrapping_sub(QUIET_ZONE);
+                pixels.push(if self.is_dark(x, y) { 0 } else { 255 });
+            }
+        }
+        (width, pixels)
+    }
+
+    /// Save the code as a grayscale PNG image, each module taking `scale` pixels on each side.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_png(&self, path: &std::path::Path, scale: usize) -> anyhow::Result<()> {
+        let (width, pixels) = self.to_luma(scale);
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width as u32, width as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        let index = y * self.size + x;
+        self.modules[index] = dark;
+        self.function[index] = true;
+    }
+
+    /// Draw the patterns at fixed positions, around which the codewords are drawn.
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        // Finder patterns in three corners, with their light separator
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i32..=4 {
+                for dx in -4i32..=4 {
+                    let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                    if x >= 0 && y >= 0 && (x as usize) < size && (y as usize) < size {
+                        let dist = dx.abs().max(dy.abs());
+                        self.set_function(x as usize, y as usize, dist != 2 && dist != 4);
+                    }
+                }
+            }
+        }
+
+        // Alignment patterns on a grid, except where the finder patterns are
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &cx) in positions.iter().enumerate() {
+            for (j, &cy) in positions.iter().enumerate() {
+                if [(0, 0), (0, last), (last, 0)].contains(&(i, j)) {
+                    continue;
+                }
+                for dy in 0..5 {
+                    for dx in 0..5 {
+                        let dist = (dx as i32 - 2).abs().max((dy as i32 - 2).abs());
+                        self.set_function(cx + dx - 2, cy + dy - 2, dist != 1);
+                    }
+                }
+            }
+        }
+
+        // Reserve the format bits, drawn once the mask is known
+        self.draw_format_bits(0);
+
+        if version >= 7 {
+            let mut rem = version as u32;
+            for _ in 0..12 {
+                rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+            }
+            let bits = (version as u32) << 12 | rem;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 != 0;
+                let a = size - 11 + i % 3;
+                let b = i / 3;
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    /// Draw the two copies of the format bits, encoding the error correction level and the mask.
+    fn draw_format_bits(&mut self, mask: u32) {
+        let data = ECC_LEVEL_BITS << 3 | mask;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        // Always dark module
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Draw the codewords in zigzag columns of two modules, from the bottom right corner and
+    /// around the function patterns.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let mut index = 0;
+        let mut right = size - 1;
+        loop {
+            // Skip the vertical timing pattern
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if !self.function[y * size + x] && index < codewords.len() * 8 {
+                        let byte = codewords[index / 8];
+                        self.modules[y * size + x] = (byte >> (7 - index % 8)) & 1 != 0;
+                        index += 1;
+                    }
+                }
+            }
+            if right < 3 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Flip the data modules matching a mask pattern. Applying the same mask again undoes it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let index = y * self.size + x;
+                if !self.function[index] && mask_bit(mask, x, y) {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+
+    /// Penalty of the modules for the patterns hard to scan, lower being better.
+    fn penalty(&self) -> u32 {
+        let size = self.size;
+        let mut penalty = 0;
+        let mut column = Vec::with_capacity(size);
+        for i in 0..size {
+            penalty += line_penalty(&self.modules[i * size..(i + 1) * size]);
+            column.clear();
+            column.extend((0..size).map(|y| self.modules[y * size + i]));
+            penalty += line_penalty(&column);
+        }
+
+        // Blocks of 2x2 modules of the same color
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.modules[y * size + x];
+                if self.modules[y * size + x + 1] == dark
+                    && self.modules[(y + 1) * size + x] == dark
+                    && self.modules[(y + 1) * size + x + 1] == dark
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        // Balance of dark and light modules, by steps of 5% away from half
+        let total = (size * size) as i64;
+        let dark = self.modules.iter().filter(|&&dark| dark).count() as i64;
+        let k = ((dark * 20 - total * 10).abs() + total - 1) / total - 1;
+        penalty + k as u32 * 10
+    }
+}
+
+/// Positions of the centers of the alignment patterns on each axis, by increasing position.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return vec![];
+    }
+    let size = version * 4 + 17;
+    let num_align = version / 7 + 2;
+    let step = (version * 8 + num_align * 3 + 5) / (num_align * 4 - 4) * 2;
+    let mut result: Vec<_> = (0..num_align - 1).map(|i| size - 7 - i * step).collect();
+    result.push(6);
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reed_solomon() {
+        // "HELLO WORLD" in the 1-M code of the QR code specification examples
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            rs_remainder(&data, &rs_divisor(10)),
+            vec![196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+        assert_eq!(alignment_positions(7), vec![6, 22, 38]);
+        assert_eq!(alignment_positions(32), vec![6, 34, 60, 86, 112, 138]);
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(byte_capacity(1), 14);
+        assert_eq!(byte_capacity(10), 213);
+        assert_eq!(byte_capacity(40), 2331);
+
+        let qr = QrCode::encode(b"LIBRA1-abc").unwrap();
+        assert_eq!(qr.size(), 21);
+        // Finder patterns and the dark module
+        for (x, y) in [(0, 0), (6, 6), (20, 0), (0, 20), (8, 13)] {
+            assert!(qr.is_dark(x, y));
+        }
+        // Both copies of the format bits match
+        let first: Vec<_> = (0..=5)
+            .map(|i| qr.is_dark(8, i))
+            .chain([qr.is_dark(8, 7), qr.is_dark(8, 8), qr.is_dark(7, 8)])
+            .chain((9..15).map(|i| qr.is_dark(14 - i, 8)))
+            .collect();
+        let second: Vec<_> = (0..8)
+            .map(|i| qr.is_dark(20 - i, 8))
+            .chain((8..15).map(|i| qr.is_dark(8, 6 + i)))
+            .collect();
+        assert_eq!(first, second);
+
+        assert_eq!(QrCode::encode(&[0; 200]).unwrap().size(), 4 * 10 + 17);
+        assert_eq!(QrCode::encode(&[0; 2332]), Err(QrError::DataTooLong(2332)));
+
+        let (width, pixels) = qr.to_luma(2);
+        assert_eq!(width, (21 + QUIET_ZONE * 2) * 2);
+        assert_eq!(pixels[0], 255);
+        assert_eq!(pixels[QUIET_ZONE * 2 * width + QUIET_ZONE * 2], 0);
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+use crate::{
+    boot::UiResources,
+    cinematic::CinematicMode,
+    controls::ActiveControls,
+    game::{run_if_playing, Game, GameSequence, GameplaySystem},
+    inventory::{Inventory, SelectSlot, SelectSlotEvent, SlotState},
+    serialize::BuildableRegistry,
+    ui_atlas::AtlasImage,
+    AppState,
+};
+
+/// Gamepad button held to open the radial menu.
+pub const OPEN_BUTTON: GamepadButtonType = GamepadButtonType::LeftTrigger2;
+
+/// Minimum deflection of the right stick to point at an item.
+const STICK_DEADZONE: f32 = 0.5;
+
+/// Distance in pixels of the center of the items from the screen center.
+const RADIUS: f32 = 200.0;
+
+/// Size in pixels of the items.
+const ITEM_SIZE: f32 = 128.0;
+
+/// Resource holding the state of the radial menu, picking an inventory slot with a gamepad.
+#[derive(Debug, Default)]
+pub struct RadialMenu {
+    open: bool,
+    /// Index of the slot the right stick points at, if any.
+    hovered: Option<usize>,
+}
+
+impl RadialMenu {
+    pub fn new() -> Self {
+        RadialMenu::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+        self.hovered = None;
+    }
+}
+
+/// Index of the item a stick direction points at, in a radial menu of `count` items laid out
+/// clockwise from the top. Returns `None` if the stick is within the deadzone.
+fn radial_index(dir: Vec2, count: usize) -> Option<usize> {
+    if count == 0 || dir.length() < STICK_DEADZONE {
+        return None;
+    }
+    let step = TAU / count as f32;
+    let angle = dir.x.atan2(dir.y).rem_euclid(TAU);
+    Some((angle / step).round() as usize % count)
+}
+
+/// Marker for the root of the radial menu UI.
+#[derive(Component)]
+struct RadialMenuRoot;
+
+/// Open the radial menu while the trigger is held, point at a slot with the right stick, and
+/// select it on release.
+fn radial_menu_input(
+    gamepads: Res<Gamepads>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    controls: Res<ActiveControls>,
+    inventory: Res<Inventory>,
+    mut radial: ResMut<RadialMenu>,
+    mut ev_select_slot: EventWriter<SelectSlotEvent>,
+) {
+    if !controls.0.accepts_gamepad() {
+        return;
+    }
+    if !radial.open {
+        let pressed = gamepads
+            .iter()
+            .any(|gamepad| gamepad_input.just_pressed(GamepadButton(*gamepad, OPEN_BUTTON)));
+        if pressed && !inventory.slots().is_empty() {
+            trace!("Radial menu: open");
+            radial.open = true;
+        }
+        return;
+    }
+
+    let held = gamepads
+        .iter()
+        .any(|gamepad| gamepad_input.pressed(GamepadButton(*gamepad, OPEN_BUTTON)));
+    if !held {
+        if let Some(index) = radial.hovered {
+            trace!("Radial menu: select slot #{}", index);
+            ev_select_slot.send(SelectSlotEvent(SelectSlot::Index(index)));
+        }
+        radial.close();
+        return;
+    }
+
+    // Point at the slot in the direction of the most deflected right stick, ignoring the empty
+    // slots. Keep the last slot pointed at when the stick is released.
+    let dir = gamepads
+        .iter()
+        .map(|gamepad| {
+            let x = axes.get(GamepadAxis(*gamepad, GamepadAxisType::RightStickX));
+            let y = axes.get(GamepadAxis(*gamepad, GamepadAxisType::RightStickY));
+            Vec2::new(x.unwrap_or(0.0), y.unwrap_or(0.0))
+        })
+        .fold(Vec2::ZERO, |best, dir| {
+            if dir.length() > best.length() {
+                dir
+            } else {
+                best
+            }
+        });
+    let hovered = radial_index(dir, inventory.slots().len())
+        .filter(|&index| !inventory.slots()[index].is_empty());
+    if hovered.is_some() && hovered != radial.hovered {
+        radial.hovered = hovered;
+    }
+}
+
+/// Close the radial menu when the player cannot pick a slot anymore.
+fn radial_menu_close(
+    game: Res<Game>,
+    cinematic: Res<CinematicMode>,
+    mut radial: ResMut<RadialMenu>,
+) {
+    if radial.open && (game.sequence() != GameSequence::Play || cinematic.is_enabled()) {
+        radial.close();
+    }
+}
+
+/// Rebuild the radial menu UI whenever it opens, closes, or points at another slot.
+fn radial_menu_ui(
+    mut commands: Commands,
+    radial: Res<RadialMenu>,
+    inventory: Res<Inventory>,
+    buildables: Res<BuildableRegistry>,
+    ui_resources: Res<UiResources>,
+    query: Query<Entity, With<RadialMenuRoot>>,
+) {
+    if !radial.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !radial.open {
+        return;
+    }
+
+    let count = inventory.slots().len();
+    let font = ui_resources.text_font();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.4)),
+            ..Default::default()
+        })
+        .insert(Name::new("RadialMenu"))
+        .insert(RadialMenuRoot)
+        .with_children(|parent| {
+            let size = 2.0 * RADIUS + ITEM_SIZE;
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(size), Val::Px(size)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (index, slot) in inventory.slots().iter().enumerate() {
+                        let buildable = match buildables.get(slot.bref()) {
+                            Some(buildable) => buildable,
+                            None => continue,
+                        };
+                        let angle = index as f32 * TAU / count as f32;
+                        let state =
+                            SlotState::from_data(slot.count(), radial.hovered == Some(index));
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(ITEM_SIZE), Val::Px(ITEM_SIZE)),
+                                    position_type: PositionType::Absolute,
+                                    position: Rect {
+                                        left: Val::Px(RADIUS * (1.0 + angle.sin())),
+                                        top: Val::Px(RADIUS * (1.0 - angle.cos())),
+                                        ..Default::default()
+                                    },
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                color: UiColor(Color::NONE),
+                                ..Default::default()
+                            })
+                            .insert(Name::new(format!("RadialSlot #{}", index)))
+                            .insert(AtlasImage::new(
+                                buildable.frame_image(),
+                                buildable.get_frame_color(&state),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        format!("x{}", slot.count()),
+                                        TextStyle {
+                                            font: font.clone(),
+                                            font_size: 48.0,
+                                            color: Color::rgb_u8(111, 188, 165),
+                                        },
+                                        TextAlignment::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        });
+}
+
+fn radial_menu_cleanup(
+    mut commands: Commands,
+    mut radial: ResMut<RadialMenu>,
+    query: Query<Entity, With<RadialMenuRoot>>,
+) {
+    radial.close();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the radial menu to pick a buildable with a gamepad, opened by holding the left
+/// trigger and pointing at an inventory slot with the right stick.
+pub struct RadialMenuPlugin;
+
+impl Plugin for RadialMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RadialMenu::new())
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Input)
+                    .with_system(radial_menu_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Input)
+                    .with_system(radial_menu_close),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(radial_menu_ui),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(radial_menu_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stick_direction() {
+        assert_eq!(radial_index(Vec2::new(0.0, 0.2), 4), None);
+        assert_eq!(radial_index(Vec2::Y, 0), None);
+        assert_eq!(radial_index(Vec2::Y, 4), Some(0));
+        assert_eq!(radial_index(Vec2::X, 4), Some(1));
+        assert_eq!(radial_index(-Vec2::Y, 4), Some(2));
+        assert_eq!(radial_index(-Vec2::X, 4), Some(3));
+        // Up and slightly left is still the top item
+        assert_eq!(radial_index(Vec2::new(-0.1, 1.0), 4), Some(0));
+        assert_eq!(radial_index(Vec2::new(1.0, -1.0), 2), Some(1));
+        assert_eq!(radial_index(Vec2::new(0.5, 0.5), 1), Some(0));
+    }
+}
@@ -0,0 +1,475 @@
+use bevy::{pbr::NotShadowCaster, prelude::*};
+
+use crate::{
+    balance::BalanceState,
+    boot::UiResources,
+    game::{Game, GameEvent, GameSequence, GameplaySystem},
+    rules::Rules,
+    scores::{compute_score, star_rating, ScoreTracker},
+    the_end::format_duration,
+    AppState, Grid, Level, Plate, RestartLevelEvent,
+};
+
+/// Height of the COG path above the plate origin, just below the live COG marker.
+const TRAIL_HEIGHT: f32 = 0.1;
+
+/// Thickness of the segments of the COG path.
+const TRAIL_WIDTH: f32 = 0.03;
+
+/// Resource recording the center of gravity (COG) offset of the plate after each placement of
+/// the current level.
+#[derive(Debug, Default)]
+pub struct CogHistory {
+    /// COG offset after each placement, in grid coordinates, starting with the empty plate.
+    points: Vec<Vec2>,
+    /// Number of buildables on the grid when the last point was recorded.
+    placed: usize,
+}
+
+impl CogHistory {
+    pub fn new() -> Self {
+        CogHistory::default()
+    }
+
+    /// COG offsets recorded so far, in placement order.
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    fn reset(&mut self, cog: Vec2, placed: usize) {
+        self.points.clear();
+        self.points.push(cog);
+        self.placed = placed;
+    }
+}
+
+/// Marker for the entities of the end-of-level recap, the COG path drawn on the plate and the
+/// recap card.
+#[derive(Component)]
+struct Recap;
+
+/// Record the COG offset whenever a buildable is placed. The history restarts whenever the grid
+/// loses some buildables, on level start or restart and on quick load.
+fn record_cog(
+    grid: Res<Grid>,
+    level: Res<Level>,
+    balance: Res<BalanceState>,
+    mut history: ResMut<CogHistory>,
+) {
+    if !grid.is_changed() || level.desc().is_none() {
+        return;
+    }
+    let placed = grid.placements().len();
+    let cog = balance.cog;
+    if placed < history.placed || history.points.is_empty() {
+        history.reset(cog, placed);
+    } else if placed > history.placed {
+        history.points.push(cog);
+        history.placed = placed;
+    }
+}
+
+/// On victory, draw the path the COG took across the plate.
+fn show_cog_trail(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    history: Res<CogHistory>,
+    query: Query<Entity, With<Plate>>,
+) {
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_none()
+    {
+        return;
+    }
+
+    // COG path on the plate, fading from the first placement to the last one
+    let plate = query.single();
+    let dot_mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 0.04,
+        subdivisions: 1,
+    }));
+    let segment_mesh = meshes.add(Mesh::from(shape::Box::new(1.0, TRAIL_WIDTH, TRAIL_WIDTH)));
+    let count = history.points().len();
+    for (index, &cog) in history.points().iter().enumerate() {
+        let t = if count > 1 {
+            index as f32 / (count - 1) as f32
+        } else {
+            1.0
+        };
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgba(1.0, 0.85, 0.3, 0.3 + 0.7 * t),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        });
+        // The COG is expressed in grid coordinates; plate local space has Z pointing toward -Y.
+        let pos = Vec3::new(cog.x, TRAIL_HEIGHT, -cog.y);
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: dot_mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(pos),
+                ..Default::default()
+            })
+            .insert(Name::new(format!("CogTrail#{}", index)))
+            .insert(Recap)
+            .insert(NotShadowCaster)
+            .insert(Parent(plate));
+        if index == 0 {
+            continue;
+        }
+        let prev = history.points()[index - 1];
+        let prev_pos = Vec3::new(prev.x, TRAIL_HEIGHT, -prev.y);
+        let delta = pos - prev_pos;
+        if delta.length() < 1e-4 {
+            continue;
+        }
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: segment_mesh.clone(),
+                material,
+                transform: Transform {
+                    translation: (pos + prev_pos) / 2.0,
+                    rotation: Quat::from_rotation_y(-delta.z.atan2(delta.x)),
+                    scale: Vec3::new(delta.length(), 1.0, 1.0),
+                },
+                ..Default::default()
+            })
+            .insert(Recap)
+            .insert(NotShadowCaster)
+            .insert(Parent(plate));
+    }
+}
+
+/// Statistics of a cleared level shown on the recap card.
+#[derive(Debug, Clone, PartialEq)]
+struct LevelRecap {
+    /// Time spent playing the level, in seconds.
+    time: f32,
+    /// Number of buildables placed.
+    placements: usize,
+    /// Number of placements undone.
+    undos: u32,
+    /// Final COG offset, in grid units.
+    offset: f32,
+    /// Victory margin of the level under the current rules, in grid units.
+    margin: f32,
+    /// Rating of the clear, from 1 to 3 stars.
+    stars: u32,
+}
+
+impl LevelRecap {
+    /// Fraction of the victory margin the final COG offset left unused, between 0 and 1.
+    fn margin_left(&self) -> f32 {
+        if self.margin > 0.0 {
+            (1.0 - self.offset / self.margin).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Lines of text of the statistics on the card.
+    fn lines(&self) -> Vec<String> {
+        vec![
+            format!("Time: {}", format_duration(self.time)),
+            format!("Placements: {}", self.placements),
+            format!("Undos: {}", self.undos),
+            format!("Margin left: {:.0}%", self.margin_left() * 100.0),
+        ]
+    }
+}
+
+/// Action of a button of the recap card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+enum RecapAction {
+    /// Move on to the next level.
+    Next,
+    /// Restart the level to improve the score.
+    Retry,
+    /// Go back to the main menu.
+    Menu,
+}
+
+fn spawn_button(parent: &mut ChildBuilder, font: Handle<Font>, label: &str, action: RecapAction) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(180.0), Val::Px(50.0)),
+                margin: Rect::all(Val::Px(8.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgb(0.25, 0.25, 0.25)),
+            ..Default::default()
+        })
+        .insert(action)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+/// On victory, show the recap card with the statistics of the level and its star rating.
+fn show_recap_card(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    ui_resources: Res<UiResources>,
+    history: Res<CogHistory>,
+    tracker: Res<ScoreTracker>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+) {
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_none()
+    {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    let score = compute_score(
+        tracker.time(),
+        tracker.moves(),
+        level_desc.par_time,
+        level_desc.par_moves,
+    );
+    let recap = LevelRecap {
+        time: tracker.time(),
+        placements: history.points().len().saturating_sub(1),
+        undos: tracker.undos(),
+        offset: history.points().last().map_or(0.0, |cog| cog.length()),
+        margin: rules.victory_margin(level_desc),
+        stars: star_rating(score),
+    };
+    debug!("Recap: {:?}", recap);
+
+    let font = ui_resources.text_font();
+    let mut stars = vec![];
+    for index in 0..3 {
+        stars.push(TextSection {
+            value: if index == 0 { "*" } else { " *" }.to_owned(),
+            style: TextStyle {
+                font: font.clone(),
+                font_size: 56.0,
+                color: if index < recap.stars {
+                    Color::rgb_u8(255, 220, 120)
+                } else {
+                    Color::rgb_u8(90, 90, 90)
+                },
+            },
+        });
+    }
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(40.0),
+                    left: Val::Px(40.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                padding: Rect::all(Val::Px(16.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+            ..Default::default()
+        })
+        .insert(Name::new("Recap"))
+        .insert(Recap)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    level_desc.name.clone(),
+                    TextStyle {
+                        font: ui_resources.title_font(),
+                        font_size: 48.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections: stars,
+                    alignment: TextAlignment::default(),
+                },
+                ..Default::default()
+            });
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    recap.lines().join("\n"),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                ),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, font.clone(), "[ENTER] Next", RecapAction::Next);
+                    spawn_button(parent, font.clone(), "[R] Retry", RecapAction::Retry);
+                    spawn_button(parent, font, "[M] Menu", RecapAction::Menu);
+                });
+        });
+}
+
+/// Move on, retry, or go back to the menu from the recap card, with the keyboard or the buttons.
+/// Retrying starts a new score for the level.
+fn recap_input(
+    mut game: ResMut<Game>,
+    mut tracker: ResMut<ScoreTracker>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+    query: Query<(&Interaction, &RecapAction), Changed<Interaction>>,
+) {
+    if game.sequence() != GameSequence::Victory {
+        return;
+    }
+    let mut action = query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, action)| *action);
+    if keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::Space]) {
+        action = Some(RecapAction::Next);
+    } else if keyboard_input.just_pressed(KeyCode::R) {
+        action = Some(RecapAction::Retry);
+    } else if keyboard_input.just_pressed(KeyCode::M) {
+        // BUGBUG -- https://bevy-cheatbook.github.io/programming/states.html
+        keyboard_input.reset(KeyCode::M);
+        action = Some(RecapAction::Menu);
+    }
+    match action {
+        Some(RecapAction::Next) => game.proceed(),
+        Some(RecapAction::Retry) => {
+            info!("Retry the cleared level");
+            tracker.reset();
+            ev_restart.send(RestartLevelEvent);
+        }
+        Some(RecapAction::Menu) => {
+            info!("Back to the main menu");
+            app_state.set(AppState::MainMenu).unwrap();
+        }
+        None => {}
+    }
+}
+
+/// Remove the recap once the game moves on from the victory, to the next level or to retry the
+/// level.
+fn hide_recap(mut commands: Commands, game: Res<Game>, query: Query<Entity, With<Recap>>) {
+    if game.sequence() == GameSequence::Victory {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn recap_cleanup(
+    mut commands: Commands,
+    mut history: ResMut<CogHistory>,
+    query: Query<Entity, With<Recap>>,
+) {
+    *history = CogHistory::new();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the end-of-level recap, showing the path the center of gravity took across the
+/// plate as the buildables were placed, and a card with the statistics and the star rating of
+/// the level. The card waits for the player to move on to the next level, to retry the level for
+/// a better score, or to go back to the main menu.
+pub struct RecapPlugin;
+
+impl Plugin for RecapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CogHistory::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Input)
+                    .with_system(recap_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Balance)
+                    .before(GameplaySystem::VictoryCheck)
+                    .with_system(record_cog),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(show_cog_trail)
+                    .with_system(show_recap_card)
+                    .with_system(hide_recap),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(recap_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recap() {
+        let recap = LevelRecap {
+            time: 65.4,
+            placements: 6,
+            undos: 1,
+            offset: 0.05,
+            margin: 0.2,
+            stars: star_rating(750),
+        };
+        assert_eq!(recap.stars, 2);
+        assert_eq!(
+            recap.lines(),
+            vec![
+                "Time: 1:05",
+                "Placements: 6",
+                "Undos: 1",
+                "Margin left: 75%"
+            ]
+        );
+        assert_eq!(star_rating(0), 1);
+        assert_eq!(star_rating(1000), 3);
+    }
+}
@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    game::{Game, GameEvent, GameMode},
+    rng::GameRng,
+    serialize::{BuildableRegistry, LevelDesc, Levels},
+    solver, AppState, Level, LoadLevel, LoadLevelEvent,
+};
+
+/// Multiplier applied to the victory margin of the remixed levels.
+pub const REMIX_MARGIN_SCALE: f32 = 0.8;
+
+/// Number of bonus stages offered after clearing a world.
+const REMIXES_PER_WORLD: usize = 2;
+
+/// Number of inventory shuffles tried per level before giving up on remixing it.
+const SHUFFLE_ATTEMPTS: usize = 8;
+
+/// Key to skip the bonus stages.
+const SKIP_KEY: KeyCode = KeyCode::J;
+
+/// Create a variant of a level with the same layout, the inventory counts shuffled between its
+/// buildables, and a tighter victory margin. Returns `None` if no shuffle changes the inventory
+/// or keeps the level solvable. Deliveries are kept as is.
+pub fn remix(
+    level_desc: &LevelDesc,
+    buildables: &BuildableRegistry,
+    rng: &mut impl Rng,
+) -> Option<LevelDesc> {
+    if level_desc.seesaw.is_some() {
+        return None;
+    }
+    let mut brefs: Vec<_> = level_desc.inventory.keys().copied().collect();
+    brefs.sort();
+    let counts: Vec<_> = brefs
+        .iter()
+        .map(|bref| level_desc.inventory[bref])
+        .collect();
+    let victory_margin = level_desc.victory_margin * REMIX_MARGIN_SCALE;
+    for _ in 0..SHUFFLE_ATTEMPTS {
+        let mut shuffled = counts.clone();
+        shuffled.shuffle(rng);
+        if shuffled == counts {
+            continue;
+        }
+        let mut remix = level_desc.clone();
+        remix.inventory = brefs.iter().copied().zip(shuffled).collect();
+        remix.victory_margin = victory_margin;
+        if solver::solve_level(&remix, buildables, victory_margin).is_some() {
+            remix.name = format!("{} (remix)", level_desc.name);
+            // Par values of the original level don't apply to the new inventory
+            remix.par_time = None;
+            remix.par_moves = None;
+            remix.interlude = None;
+            return Some(remix);
+        }
+    }
+    None
+}
+
+/// Is the level the last one of its world, with more levels after it?
+fn ends_world(levels: &Levels, index: usize) -> bool {
+    let world = match levels.get(index).and_then(|desc| desc.world.as_ref()) {
+        Some(world) => world,
+        None => return false,
+    };
+    match levels.get(index + 1) {
+        Some(next) => next
+            .world
+            .as_ref()
+            .map_or(true, |next| next.name != world.name),
+        None => false,
+    }
+}
+
+/// Resource holding the bonus stages queued after clearing a world, remixes of its levels played
+/// before moving on to the next world.
+#[derive(Debug, Default)]
+pub struct BonusStages {
+    /// Remixed levels left to play.
+    queue: VecDeque<Arc<LevelDesc>>,
+    /// Is a bonus stage being played?
+    active: bool,
+}
+
+impl BonusStages {
+    pub fn new() -> Self {
+        BonusStages::default()
+    }
+
+    /// Is a bonus stage being played?
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Take the next bonus stage to play instead of the next level, if any. Called by the level
+    /// loading for each [`LoadLevel::Next`] request.
+    pub fn next(&mut self) -> Option<Arc<LevelDesc>> {
+        let next = self.queue.pop_front();
+        self.active = next.is_some();
+        next
+    }
+
+    /// Forget the bonus stages left.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.active = false;
+    }
+}
+
+/// Marker for the banner of the bonus stage being played, empty otherwise.
+#[derive(Component)]
+struct BonusBanner;
+
+/// Queue remixes of the levels of a world once its last level is cleared in solo. The bonus stages
+/// themselves are not remixed again.
+fn offer_bonus_stages(
+    mut ev_game: EventReader<GameEvent>,
+    game_mode: Res<GameMode>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    mut bonus_stages: ResMut<BonusStages>,
+    mut rng: ResMut<GameRng>,
+) {
+    let cleared = ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some();
+    if !cleared
+        || *game_mode != GameMode::Solo
+        || bonus_stages.is_active()
+        || !ends_world(&levels, level.index())
+    {
+        return;
+    }
+    let world = match level.desc().and_then(|desc| desc.world.clone()) {
+        Some(world) => world,
+        None => return,
+    };
+    // Remix the latest levels of the world first, the hardest ones
+    let remixes: Vec<_> = levels.levels()[..=level.index()]
+        .iter()
+        .rev()
+        .take_while(|desc| desc.world.as_ref().is_some_and(|w| w.name == world.name))
+        .filter_map(|desc| remix(desc, &buildables, &mut *rng))
+        .take(REMIXES_PER_WORLD)
+        .map(Arc::new)
+        .collect();
+    if !remixes.is_empty() {
+        info!(
+            "World '{}' cleared, {} bonus stage(s) unlocked",
+            world.name,
+            remixes.len()
+        );
+        bonus_stages.queue.extend(remixes);
+    }
+}
+
+/// Skip the bonus stages left, moving on to the next world.
+fn skip_bonus_stages(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut game: ResMut<Game>,
+    mut bonus_stages: ResMut<BonusStages>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+) {
+    if !bonus_stages.is_active() || !keyboard_input.just_pressed(SKIP_KEY) {
+        return;
+    }
+    info!("Skipping the bonus stages");
+    bonus_stages.clear();
+    game.reset_sequence();
+    ev_load_level.send(LoadLevelEvent(LoadLevel::Next));
+}
+
+fn spawn_bonus_banner(mut commands: Commands, ui_resources: Res<UiResources>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(60.0),
+                    right: Val::Px(60.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 24.0,
+                    color: Color::rgb_u8(250, 210, 90),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("BonusBanner"))
+        .insert(Hud)
+        .insert(BonusBanner);
+}
+
+fn update_bonus_banner(
+    bonus_stages: Res<BonusStages>,
+    mut query: Query<&mut Text, With<BonusBanner>>,
+) {
+    if !bonus_stages.is_changed() {
+        return;
+    }
+    let banner = if bonus_stages.is_active() {
+        format!("Bonus stage\n[{:?}] skip", SKIP_KEY)
+    } else {
+        String::new()
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = banner.clone();
+    }
+}
+
+fn bonus_cleanup(
+    mut commands: Commands,
+    mut bonus_stages: ResMut<BonusStages>,
+    query: Query<Entity, With<BonusBanner>>,
+) {
+    bonus_stages.clear();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the bonus stages, remixes of the levels of a world with a shuffled inventory and a
+/// tighter margin, offered once the world is cleared.
+pub struct RemixPlugin;
+
+impl Plugin for RemixPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BonusStages::new())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_bonus_banner))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(offer_bonus_stages)
+                    .with_system(skip_bonus_stages)
+                    .with_system(update_bonus_banner),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(bonus_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inventory::Buildable, serialize::LevelDescArchive};
+    use std::collections::HashMap;
+
+    fn registry() -> BuildableRegistry {
+        let mut buildables = BuildableRegistry::new();
+        for (name, weight) in [("hut", 1.0), ("tower", 2.0)] {
+            buildables.register(name, Buildable::without_assets(name, weight));
+        }
+        buildables
+    }
+
+    #[test]
+    fn shuffled_inventory() {
+        let buildables = registry();
+        let archive: LevelDescArchive = serde_json::from_str(
+            r#"{ "name": "Twins", "grid_size": [5, 5], "balance_factor": 1.0,
+                 "victory_margin": 0.2, "inventory": { "hut": 2, "tower": 1 }, "par_moves": 10 }"#,
+        )
+        .unwrap();
+        let level_desc = archive.to_desc(&buildables, &HashMap::new());
+        let mut rng = StdRng::seed_from_u64(42);
+        let remixed = remix(&level_desc, &buildables, &mut rng).unwrap();
+        assert_eq!(remixed.grid_size, level_desc.grid_size);
+        assert_eq!(remixed.victory_margin, 0.2 * REMIX_MARGIN_SCALE);
+        assert_eq!(remixed.par_moves, None);
+        let hut = buildables.id("hut").unwrap();
+        let tower = buildables.id("tower").unwrap();
+        assert_eq!(remixed.inventory[&hut], 1);
+        assert_eq!(remixed.inventory[&tower], 2);
+        assert!(solver::solve_level(&remixed, &buildables, remixed.victory_margin).is_some());
+
+        // Nothing to shuffle with a single buildable
+        let archive: LevelDescArchive = serde_json::from_str(
+            r#"{ "name": "Solo", "grid_size": [3, 3], "balance_factor": 1.0,
+                 "victory_margin": 0.2, "inventory": { "hut": 2 } }"#,
+        )
+        .unwrap();
+        let level_desc = archive.to_desc(&buildables, &HashMap::new());
+        assert!(remix(&level_desc, &buildables, &mut rng).is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::GameplaySystem, serialize::BuildableId, AppState, Grid, Level, ResetPlateEvent,
+    RestartLevelEvent,
+};
+
+/// Resource holding the random number generator of the gameplay. All the randomness of the game
+/// (market draws, remixes, bot delays, ...) comes from it, instead of [`thread_rng()`], so that a
+/// run can be reproduced from its seed, for example to replay a bug report or to share a daily
+/// challenge.
+///
+/// The generator is reseeded from the session seed and the level name each time a level starts
+/// or restarts, so a level draws the same sequence on every attempt regardless of the levels
+/// played before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "GameRngState", into = "GameRngState")]
+pub struct GameRng {
+    /// Seed of the session.
+    seed: u64,
+    /// Seed of the current level, derived from the session seed.
+    level_seed: u64,
+    rng: ChaCha8Rng,
+}
+
+/// Serialized state of a [`GameRng`], enough to resume its sequence.
+#[derive(Serialize, Deserialize)]
+struct GameRngState {
+    seed: u64,
+    level_seed: u64,
+    /// Position of the generator in the sequence of the current level, in 32-bit words.
+    word_pos: u128,
+}
+
+impl From<GameRngState> for GameRng {
+    fn from(state: GameRngState) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(state.level_seed);
+        rng.set_word_pos(state.word_pos);
+        GameRng {
+            seed: state.seed,
+            level_seed: state.level_seed,
+            rng,
+        }
+    }
+}
+
+impl From<GameRng> for GameRngState {
+    fn from(game_rng: GameRng) -> Self {
+        GameRngState {
+            seed: game_rng.seed,
+            level_seed: game_rng.level_seed,
+            word_pos: game_rng.rng.get_word_pos(),
+        }
+    }
+}
+
+impl GameRng {
+    /// Create a generator for a session with the given seed.
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            level_seed: seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Create a generator for a session with a random seed.
+    pub fn from_entropy() -> Self {
+        GameRng::new(ChaCha8Rng::from_entropy().next_u64())
+    }
+
+    /// Seed of the session, to reproduce it.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restart the session with another seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        *self = GameRng::new(seed);
+    }
+
+    /// Restart the sequence of a level, derived from the session seed and the level name.
+    pub fn seed_level(&mut self, level_name: &str) {
+        // FNV-1a, stable across platforms and Rust versions unlike the std hashers
+        let hash = level_name
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+        self.level_seed = self.seed ^ hash;
+        self.rng = ChaCha8Rng::seed_from_u64(self.level_seed);
+    }
+
+    /// Seed of the current level, derived from the session seed.
+    pub fn level_seed(&self) -> u64 {
+        self.level_seed
+    }
+
+    /// Create an independent generator seeded from this one, for a system drawing a sequence of
+    /// its own.
+    pub fn fork(&mut self) -> StdRng {
+        StdRng::seed_from_u64(self.rng.next_u64())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Deviation of the weight of a buildable placed in a cell from its nominal weight, in `[-1, 1]`
+/// units of its weight variance. Drawn from the seed of the level, the cell, and the buildable,
+/// without consuming the sequence of the level, so a layout rebuilt from a journal weighs the
+/// same as the one it was recorded from.
+pub fn weight_jitter(level_seed: u64, pos: IVec2, bref: BuildableId) -> f32 {
+    let key = (pos.x as u32 as u64) | (pos.y as u32 as u64) << 32;
+    let salt = (bref.index() as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    let mut rng = ChaCha8Rng::seed_from_u64(level_seed ^ key ^ salt);
+    rng.gen_range(-1.0..=1.0)
+}
+
+/// Reseed the generator for the level whenever it starts or restarts, along with the weights of
+/// the buildables placed.
+fn seed_level(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    level: Res<Level>,
+    mut rng: ResMut<GameRng>,
+    mut grid: ResMut<Grid>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if reset || restart {
+        rng.seed_level(level.name());
+        grid.set_weight_seed(rng.level_seed);
+        trace!(
+            "Level '{}' seeded with {:016x}",
+            level.name(),
+            rng.level_seed
+        );
+    }
+}
+
+/// Plugin for the [`GameRng`] resource, with a random seed. A custom [`GameRng`] inserted
+/// beforehand replaces it, for example with the seed of the `--seed <seed>` command line argument.
+/// Systems drawing from it right after a level starts or restarts run after the `"seed_level"`
+/// label.
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<GameRng>() {
+            app.insert_resource(GameRng::from_entropy());
+        }
+        info!("Game seed: {:016x}", app.world.resource::<GameRng>().seed());
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label("seed_level")
+                .after(GameplaySystem::Reset)
+                .before(GameplaySystem::Cursor)
+                .with_system(seed_level),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_sequences() {
+        let mut rng = GameRng::new(42);
+        rng.seed_level("Sandbox");
+        let first: Vec<u32> = (0..4).map(|_| rng.gen()).collect();
+
+        // Same sequence on every attempt, whatever was drawn before
+        rng.seed_level("Tutorial");
+        let _: u64 = rng.gen();
+        rng.seed_level("Sandbox");
+        let again: Vec<u32> = (0..4).map(|_| rng.gen()).collect();
+        assert_eq!(first, again);
+
+        // Other sequence with another seed
+        let mut rng = GameRng::new(43);
+        rng.seed_level("Sandbox");
+        let other: Vec<u32> = (0..4).map(|_| rng.gen()).collect();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn serialize() {
+        let mut rng = GameRng::new(7);
+        rng.seed_level("Sandbox");
+        let _: u64 = rng.gen();
+        let json = serde_json::to_string(&rng).unwrap();
+        let mut restored: GameRng = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.seed(), 7);
+        for _ in 0..8 {
+            assert_eq!(restored.gen::<u64>(), rng.gen::<u64>());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+use crate::{
+    game::GameMode,
+    hard::HardMode,
+    inventory::{Buildable, UpdateInventorySlots},
+    serialize::{BuildableId, LevelDesc},
+    units::{self, WeightUnit},
+    AppState, Grid, ResetPlateEvent,
+};
+
+/// Multiplier applied to the victory margin of the levels played with the assist.
+pub const ASSIST_MARGIN_SCALE: f32 = 1.5;
+
+/// Multiplier applied to the victory margin of the levels played in hard mode.
+pub const HARD_MARGIN_SCALE: f32 = 0.5;
+
+/// Gameplay rules of the current game mode.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    /// Short name of the rules, reported with the scores.
+    pub name: &'static str,
+    /// Hide the weight of buildables until one of them is placed.
+    pub hidden_weights: bool,
+    /// Multiplier applied to the victory margin of the levels.
+    pub victory_margin_scale: f32,
+    /// Multiplier applied to the level scores.
+    pub score_multiplier: f32,
+    /// Is the assist enabled for the current level, widening its victory margin? See
+    /// [`AssistPlugin`].
+    ///
+    /// [`AssistPlugin`]: crate::assist::AssistPlugin
+    pub assisted: bool,
+    /// Is the hard mode enabled, narrowing the victory margins, hiding the center of gravity
+    /// marker, and disabling the undo? See [`HardMode`].
+    pub hard: bool,
+}
+
+impl Rules {
+    /// Standard rules of the base game.
+    pub fn standard() -> Self {
+        Rules {
+            name: "standard",
+            hidden_weights: false,
+            victory_margin_scale: 1.0,
+            score_multiplier: 1.0,
+            assisted: false,
+            hard: false,
+        }
+    }
+
+    /// Rules for a game mode.
+    pub fn for_mode(mode: GameMode) -> Self {
+        match mode {
+            GameMode::WeightReveal => Rules {
+                name: "weight_reveal",
+                hidden_weights: true,
+                victory_margin_scale: 2.0,
+                score_multiplier: 1.5,
+                assisted: false,
+                hard: false,
+            },
+            _ => Rules::standard(),
+        }
+    }
+
+    /// Victory margin of a level under these rules.
+    pub fn victory_margin(&self, level_desc: &LevelDesc) -> f32 {
+        let mut scale = self.victory_margin_scale;
+        if self.assisted {
+            scale *= ASSIST_MARGIN_SCALE;
+        }
+        if self.hard {
+            scale *= HARD_MARGIN_SCALE;
+        }
+        level_desc.victory_margin * scale
+    }
+
+    /// Can the placements be undone, with the quick load or the practice mode?
+    pub fn can_undo(&self) -> bool {
+        !self.hard
+    }
+
+    /// Text displaying the weight of a buildable in some unit, with its variance if any, or "?" if
+    /// the weight is not revealed yet.
+    pub fn weight_text(
+        &self,
+        revealed: &RevealedWeights,
+        bref: BuildableId,
+        buildable: &Buildable,
+        unit: &WeightUnit,
+    ) -> String {
+        if self.hidden_weights && !revealed.contains(bref) {
+            "?".to_owned()
+        } else {
+            units::format_weight_range(buildable.weight(), buildable.weight_variance(), unit)
+        }
+    }
+}
+
+/// Resource tracking the buildables whose weight was revealed in the current level.
+#[derive(Debug, Default)]
+pub struct RevealedWeights(HashSet<BuildableId>);
+
+impl RevealedWeights {
+    pub fn contains(&self, bref: BuildableId) -> bool {
+        self.0.contains(&bref)
+    }
+}
+
+fn rules_setup(game_mode: Res<GameMode>, hard_mode: Res<HardMode>, mut rules: ResMut<Rules>) {
+    *rules = Rules::for_mode(*game_mode);
+    rules.hard = hard_mode.is_enabled();
+    debug!("Rules: {:?}", *rules);
+}
+
+/// Reveal the weight of the buildables placed on the grid, and hide all weights again when a new
+/// level starts.
+fn reveal_weights(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    rules: Res<Rules>,
+    grid: Res<Grid>,
+    mut revealed: ResMut<RevealedWeights>,
+) {
+    if !rules.hidden_weights {
+        return;
+    }
+    if ev_reset_plate.iter().last().is_some() {
+        revealed.0.clear();
+    }
+    let mut changed = false;
+    for (_, bref) in grid.placements() {
+        changed |= revealed.0.insert(bref);
+    }
+    if changed {
+        ev_update_slots.send(UpdateInventorySlots);
+    }
+}
+
+/// Plugin selecting the gameplay [`Rules`] of the current game mode.
+pub struct RulesPlugin;
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Rules::standard())
+            .insert_resource(RevealedWeights::default())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(rules_setup))
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(reveal_weights));
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use serde_json::{Map, Value};
+
+use crate::Error;
+
+/// Name of the field holding the schema version, at the root of the versioned files.
+const VERSION_FIELD: &str = "schema_version";
+
+/// Version of the files without a version field, written before the schema was versioned.
+const UNVERSIONED: u32 = 1;
+
+/// Upgrade of the root object of a file from one schema version to the next.
+type Migration = fn(&mut Map<String, Value>);
+
+/// Versioned file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    /// Game data of `levels.json`. See [`GameDataArchive`].
+    ///
+    /// [`GameDataArchive`]: crate::serialize::GameDataArchive
+    GameData,
+    /// Game config of `config.json`. See [`Config`].
+    ///
+    /// [`Config`]: crate::config::Config
+    Config,
+}
+
+impl Schema {
+    fn name(&self) -> &'static str {
+        match self {
+            Schema::GameData => "game data",
+            Schema::Config => "config",
+        }
+    }
+
+    /// Migrations from each version to the next, starting at the unversioned files. Bump the
+    /// version of a schema by adding a migration here.
+    fn migrations(&self) -> &'static [Migration] {
+        match self {
+            Schema::GameData => &[game_data_v1_to_v2],
+            Schema::Config => &[],
+        }
+    }
+
+    /// Version of the schema written and read by this build.
+    pub fn current_version(&self) -> u32 {
+        UNVERSIONED + self.migrations().len() as u32
+    }
+
+    /// Parse a JSON file of this schema and upgrade it to the current version, ready to be
+    /// deserialized. Fails if the file was written by a newer build.
+    pub fn parse(&self, json_content: &str) -> Result<Value, Error> {
+        let mut value: Value = serde_json::from_str(json_content)?;
+        migrate(&mut value, self.name(), self.migrations())?;
+        Ok(value)
+    }
+}
+
+/// Upgrade a file to the version after the given migrations, setting its version field.
+fn migrate(value: &mut Value, name: &str, migrations: &[Migration]) -> Result<(), Error> {
+    let current = UNVERSIONED + migrations.len() as u32;
+    let root = match value.as_object_mut() {
+        Some(root) => root,
+        None => {
+            error!("Invalid {}: expected a JSON object.", name);
+            return Err(Error::InvalidSchema);
+        }
+    };
+    let version = match root.get(VERSION_FIELD) {
+        None => UNVERSIONED,
+        Some(version) => match version.as_u64() {
+            Some(version) if version >= UNVERSIONED as u64 => version as u32,
+            _ => {
+                error!("Invalid {} schema version: {}", name, version);
+                return Err(Error::InvalidSchema);
+            }
+        },
+    };
+    if version > current {
+        error!(
+            "The {} has schema version {}, but this build only supports up to version {}. \
+            Update the game to load it.",
+            name, version, current
+        );
+        return Err(Error::UnsupportedSchema(version));
+    }
+    if version < current {
+        info!(
+            "Upgrading {} from schema version {} to {}",
+            name, version, current
+        );
+    }
+    for migration in &migrations[(version - UNVERSIONED) as usize..] {
+        migration(root);
+    }
+    root.insert(VERSION_FIELD.to_owned(), Value::from(current));
+    Ok(())
+}
+
+/// Rename a field of an object, if present and not already renamed.
+fn rename_field(object: &mut Map<String, Value>, from: &str, to: &str) {
+    if object.contains_key(to) {
+        return;
+    }
+    if let Some(value) = object.remove(from) {
+        object.insert(to.to_owned(), value);
+    }
+}
+
+/// The buildable definitions moved from `inventory` to `buildables`, to tell them apart from the
+/// inventory of each level.
+fn game_data_v1_to_v2(root: &mut Map<String, Value>) {
+    rename_field(root, "inventory", "buildables");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn game_data_upgrade() {
+        let json = r#"{ "inventory": { "hut": {} }, "levels": [] }"#;
+        let value = Schema::GameData.parse(json).unwrap();
+        assert_eq!(
+            value,
+            json!({ "schema_version": 2, "buildables": { "hut": {} }, "levels": [] })
+        );
+
+        // Already current
+        let json = r#"{ "schema_version": 2, "buildables": {}, "levels": [] }"#;
+        let value = Schema::GameData.parse(json).unwrap();
+        assert_eq!(value["buildables"], json!({}));
+    }
+
+    #[test]
+    fn newer_schema() {
+        let json = r#"{ "schema_version": 99, "sound": {} }"#;
+        assert_eq!(
+            Schema::Config.parse(json),
+            Err(Error::UnsupportedSchema(99))
+        );
+        let json = r#"{ "schema_version": "1" }"#;
+        assert_eq!(Schema::Config.parse(json), Err(Error::InvalidSchema));
+    }
+
+    #[test]
+    fn migration_chain() {
+        fn add_a(root: &mut Map<String, Value>) {
+            root.insert("a".to_owned(), Value::from(1));
+        }
+        fn rename_a(root: &mut Map<String, Value>) {
+            rename_field(root, "a", "b");
+        }
+        let migrations: &[Migration] = &[add_a, rename_a];
+
+        let mut value = json!({});
+        migrate(&mut value, "test", migrations).unwrap();
+        assert_eq!(value, json!({ "schema_version": 3, "b": 1 }));
+
+        // Only the migrations after the file version run
+        let mut value = json!({ "schema_version": 2, "c": 0 });
+        migrate(&mut value, "test", migrations).unwrap();
+        assert_eq!(value, json!({ "schema_version": 3, "c": 0 }));
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::{
+    cheats::Cheats, game::GameEvent, inventory::Inventory, practice::Practice, rules::Rules,
+    storage, AppState, Cursor, Level, ResetPlateEvent,
+};
+
+/// Version of the signed score format, bumped on any change to [`LevelScore`].
+const SCORE_FORMAT_VERSION: u32 = 4;
+
+/// Key used to sign the exported scores. Release builds are expected to provide their own key
+/// via the `LIBRACITY_SCORE_KEY` environment variable at compile time. The key is embedded in the
+/// binary, so the signature only guards against casual tampering with the exported files.
+const SCORE_SIGNING_KEY: &str = match option_env!("LIBRACITY_SCORE_KEY") {
+    Some(key) => key,
+    None => "libracity-dev-score-key",
+};
+
+/// Maximum score for a level under the standard rules, reached when playing at or under par.
+/// Other rules may scale it with [`Rules::score_multiplier`].
+pub const MAX_SCORE: u32 = 1000;
+
+/// Minimum normalized score of a level clear rated with two stars, then three stars. Any clear is
+/// rated at least one star.
+const STAR_SCORES: [u32; 2] = [MAX_SCORE * 6 / 10, MAX_SCORE * 9 / 10];
+
+/// Save file the signed scores are appended to on native platforms, in the profile storage, one
+/// JSON object per line.
+#[cfg(not(target_arch = "wasm32"))]
+const SCORES_FILE: &str = "scores.jsonl";
+
+/// Score of a completed level.
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelScore {
+    /// Display name of the level.
+    pub level: String,
+    /// Name of the gameplay rules the level was played with.
+    pub rules: String,
+    /// Time spent playing the level, in seconds.
+    pub time: f32,
+    /// Number of moves (cursor moves and placements) to clear the level.
+    pub moves: u32,
+    /// Reference completion time of the level, if any.
+    pub par_time: Option<f32>,
+    /// Reference number of moves of the level, if any.
+    pub par_moves: Option<u32>,
+    /// Normalized score, between 0 and [`MAX_SCORE`] times the rules score multiplier.
+    pub score: u32,
+    /// Were developer cheats used to clear the level? See [`Cheats`].
+    ///
+    /// [`Cheats`]: crate::cheats::Cheats
+    pub cheated: bool,
+    /// Was the level cleared in hard mode? See [`HardMode`].
+    ///
+    /// [`HardMode`]: crate::hard::HardMode
+    pub hard: bool,
+}
+
+/// Score signed with [`SCORE_SIGNING_KEY`], ready to be submitted to a leaderboard.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedScore {
+    /// Format version.
+    pub version: u32,
+    /// Score payload.
+    pub score: LevelScore,
+    /// Hex-encoded HMAC-SHA256 of the JSON serialization of the payload.
+    pub signature: String,
+}
+
+impl SignedScore {
+    /// Sign a score.
+    pub fn sign(score: LevelScore) -> SignedScore {
+        let payload = serde_json::to_string(&score).unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(SCORE_SIGNING_KEY.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        let signature = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        SignedScore {
+            version: SCORE_FORMAT_VERSION,
+            score,
+            signature,
+        }
+    }
+}
+
+/// Compute the normalized score of a level from the player performance and the level par.
+///
+/// Time and moves each account for half of the score. A par value which is not defined
+/// awards the full half.
+pub fn compute_score(time: f32, moves: u32, par_time: Option<f32>, par_moves: Option<u32>) -> u32 {
+    let time_ratio = par_time.map_or(1.0, |par| (par / time.max(0.001)).min(1.0));
+    let moves_ratio = par_moves.map_or(1.0, |par| (par as f32 / moves.max(1) as f32).min(1.0));
+    ((time_ratio + moves_ratio) * 0.5 * MAX_SCORE as f32).round() as u32
+}
+
+/// Rate a level clear from 1 to 3 stars from its normalized score, before any rules multiplier.
+/// See [`compute_score()`].
+pub fn star_rating(score: u32) -> u32 {
+    1 + STAR_SCORES
+        .iter()
+        .filter(|&&min_score| score >= min_score)
+        .count() as u32
+}
+
+/// Event sent once the score of a completed level has been computed.
+#[derive(Debug)]
+pub struct ScoreEvent(pub LevelScore);
+
+/// Resource tracking the player performance on the current level.
+pub struct ScoreTracker {
+    /// Time spent with the cursor enabled, in seconds.
+    time: f32,
+    /// Number of cursor moves and placements.
+    moves: u32,
+    /// Cursor position last frame.
+    last_pos: Option<IVec2>,
+    /// Number of placements last frame.
+    last_placed_count: u32,
+    /// Number of placements undone, with the quick load or the practice timeline.
+    undos: u32,
+}
+
+impl ScoreTracker {
+    pub fn new() -> Self {
+        ScoreTracker {
+            time: 0.0,
+            moves: 0,
+            last_pos: None,
+            last_placed_count: 0,
+            undos: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = ScoreTracker::new();
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn moves(&self) -> u32 {
+        self.moves
+    }
+
+    pub fn undos(&self) -> u32 {
+        self.undos
+    }
+
+    /// Count an undo, restoring the level to an earlier placement.
+    pub fn record_undo(&mut self) {
+        self.undos += 1;
+    }
+}
+
+/// Reset the score tracker whenever a new level starts.
+fn reset_score_tracker(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut tracker: ResMut<ScoreTracker>,
+) {
+    if ev_reset_plate.iter().last().is_some() {
+        tracker.reset();
+    }
+}
+
+/// Accumulate the play time and count the player moves.
+fn track_score(
+    time: Res<Time>,
+    inventory: Res<Inventory>,
+    mut tracker: ResMut<ScoreTracker>,
+    query: Query<&Cursor>,
+) {
+    let cursor = query.single();
+    if !cursor.enabled() {
+        return;
+    }
+    tracker.time += time.delta_seconds();
+    let pos = cursor.pos();
+    if tracker.last_pos.map_or(false, |last_pos| last_pos != pos) {
+        tracker.moves += 1;
+    }
+    tracker.last_pos = Some(pos);
+    // Placement count drops back to zero when restarting the level
+    let placed_count = inventory.placed_count();
+    if placed_count > tracker.last_placed_count {
+        tracker.moves += placed_count - tracker.last_placed_count;
+    }
+    tracker.last_placed_count = placed_count;
+}
+
+/// Compute the score of the level on completion, and export it signed. Levels cleared in practice
+/// mode are not scored.
+fn score_level(
+    mut ev_game: EventReader<GameEvent>,
+    mut ev_score: EventWriter<ScoreEvent>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    cheats: Res<Cheats>,
+    practice: Res<Practice>,
+    tracker: Res<ScoreTracker>,
+) {
+    for _ in ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+    {
+        if practice.is_used() {
+            info!(
+                "Level '{}' cleared in practice mode, not scored",
+                level.name()
+            );
+            continue;
+        }
+        let level_desc = match level.desc() {
+            Some(level_desc) => level_desc,
+            None => continue,
+        };
+        let score = compute_score(
+            tracker.time(),
+            tracker.moves(),
+            level_desc.par_time,
+            level_desc.par_moves,
+        );
+        let score = LevelScore {
+            level: level_desc.name.clone(),
+            rules: rules.name.to_owned(),
+            time: tracker.time(),
+            moves: tracker.moves(),
+            par_time: level_desc.par_time,
+            par_moves: level_desc.par_moves,
+            score: (score as f32 * rules.score_multiplier).round() as u32,
+            cheated: cheats.is_used(),
+            hard: rules.hard,
+        };
+        info!(
+            "Level '{}' score: {} (time={:.1}s moves={}{}{})",
+            score.level,
+            score.score,
+            score.time,
+            score.moves,
+            if score.hard { ", hard" } else { "" },
+            if score.cheated { ", cheated" } else { "" }
+        );
+        export_score(&SignedScore::sign(score.clone()));
+        ev_score.send(ScoreEvent(score));
+    }
+}
+
+/// Append the signed score to [`SCORES_FILE`].
+#[cfg(not(target_arch = "wasm32"))]
+fn export_score(signed_score: &SignedScore) {
+    let result = serde_json::to_string(signed_score)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| storage::append_line(SCORES_FILE, &json).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        let location = storage::location(SCORES_FILE);
+        warn!("Failed to export score to '{}': {}", location, err);
+    }
+}
+
+/// Post the signed score to the page hosting the game, or to its parent if embedded in a frame.
+#[cfg(target_arch = "wasm32")]
+fn export_score(signed_score: &SignedScore) {
+    let json = match serde_json::to_string(signed_score) {
+        Ok(json) => json,
+        Err(err) => {
+            warn!("Failed to serialize score: {}", err);
+            return;
+        }
+    };
+    if let Some(window) = web_sys::window() {
+        let target = window.parent().ok().flatten().unwrap_or(window);
+        if target
+            .post_message(&wasm_bindgen::JsValue::from_str(&json), "*")
+            .is_err()
+        {
+            warn!("Failed to post score message.");
+        }
+    }
+}
+
+/// Plugin to score completed levels against their par, and export the scores for leaderboards.
+pub struct ScoresPlugin;
+
+impl Plugin for ScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScoreTracker::new())
+            .add_event::<ScoreEvent>()
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(reset_score_tracker.before("track_score"))
+                    .with_system(track_score.label("track_score"))
+                    .with_system(score_level.after("track_score")),
+            );
+    }
+}
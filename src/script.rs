@@ -0,0 +1,353 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    balance::BalanceState,
+    boot::UiResources,
+    cinematic::Hud,
+    game::{Game, GameEvent, GameSequence},
+    inventory::Inventory,
+    serialize::BuildableRegistry,
+    sfx::{PlaySfxEvent, Sfx},
+    shake::ScreenShake,
+    ui_layout::UiLayouts,
+    AppState, Level, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Time in seconds a hint of a level script stays on screen.
+const HINT_DURATION: f32 = 6.0;
+
+/// Condition firing a rule of a level script.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptTrigger {
+    /// The level started or restarted, once the intro is over.
+    Start,
+    /// The given number of buildables were placed since the level started.
+    AfterPlacements(u32),
+    /// The given time in seconds elapsed since the level started.
+    AfterSeconds(f32),
+    /// A buildable with the given name was placed.
+    Placed(String),
+    /// The COG moved into the victory margin.
+    EnterMargin,
+    /// The COG moved out of the victory margin.
+    LeaveMargin,
+}
+
+/// Action of a level script. Scripts can't change the state of the level, only give feedback to
+/// the player.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptAction {
+    /// Show a hint for a few seconds, replacing the one shown if any.
+    Hint(String),
+    /// Play a sound effect.
+    Sound(Sfx),
+    /// Shake the screen, with an amount of trauma in [0:1]. See [`ScreenShake`].
+    Shake(f32),
+}
+
+/// Rule of a level script, running its actions in order when its trigger matches.
+///
+/// ```json
+/// { "when": { "after_placements": 3 }, "do": [ { "hint": "Heavy buildables near the center" } ] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptRule {
+    pub when: ScriptTrigger,
+    #[serde(rename = "do")]
+    pub actions: Vec<ScriptAction>,
+    /// Fire the rule each time its trigger matches, instead of once per attempt.
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+/// State of the level observed by the triggers on a frame.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptFrame<'a> {
+    /// Number of buildables placed since the level started.
+    pub placements: u32,
+    /// Time since the previous frame, in seconds.
+    pub dt: f32,
+    /// Names of the buildables placed on this frame.
+    pub placed: Vec<&'a str>,
+    /// Is the COG within the victory margin?
+    pub balanced: bool,
+}
+
+/// Interpreter of the script of the level in progress. The triggers fire on the frame their
+/// condition becomes true, and each rule fires at most once per frame, so a script can't loop.
+#[derive(Debug, Default)]
+pub struct ScriptRunner {
+    /// Rules already fired since the level started, by index.
+    fired: Vec<bool>,
+    /// Is the level waiting for the first frame of play to fire the start triggers?
+    starting: bool,
+    /// Time since the level started, in seconds.
+    elapsed: f32,
+    /// State observed on the previous frame, if any since the level started.
+    previous: Option<(u32, bool)>,
+}
+
+impl ScriptRunner {
+    pub fn new() -> Self {
+        ScriptRunner::default()
+    }
+
+    /// Forget the rules fired, when the level starts or restarts.
+    pub fn restart(&mut self) {
+        self.fired.clear();
+        self.starting = true;
+        self.elapsed = 0.0;
+        self.previous = None;
+    }
+
+    /// Evaluate the rules on a frame, returning the actions to run in order.
+    pub fn step(&mut self, rules: &[ScriptRule], frame: &ScriptFrame) -> Vec<ScriptAction> {
+        self.fired.resize(rules.len(), false);
+        let (previous_placements, was_balanced) = match self.previous {
+            Some((placements, balanced)) => (placements, Some(balanced)),
+            None => (0, None),
+        };
+        let previous_elapsed = self.elapsed;
+        self.elapsed += frame.dt;
+        let mut actions = vec![];
+        for (rule, fired) in rules.iter().zip(self.fired.iter_mut()) {
+            if *fired && !rule.repeat {
+                continue;
+            }
+            let matched = match &rule.when {
+                ScriptTrigger::Start => self.starting,
+                ScriptTrigger::AfterPlacements(count) => {
+                    previous_placements < *count && frame.placements >= *count
+                }
+                ScriptTrigger::AfterSeconds(seconds) => {
+                    previous_elapsed < *seconds && self.elapsed >= *seconds
+                }
+                ScriptTrigger::Placed(name) => frame.placed.contains(&&name[..]),
+                ScriptTrigger::EnterMargin => was_balanced == Some(false) && frame.balanced,
+                ScriptTrigger::LeaveMargin => was_balanced == Some(true) && !frame.balanced,
+            };
+            if matched {
+                *fired = true;
+                actions.extend(rule.actions.iter().cloned());
+            }
+        }
+        self.starting = false;
+        self.previous = Some((frame.placements, frame.balanced));
+        actions
+    }
+}
+
+/// Event to run an action of the level script.
+#[derive(Debug)]
+pub struct ScriptActionEvent(pub ScriptAction);
+
+/// Run the script of the level in progress. The time and the triggers only advance while playing,
+/// so the intro doesn't count.
+fn run_level_script(
+    time: Res<Time>,
+    game: Res<Game>,
+    level: Res<Level>,
+    balance: Res<BalanceState>,
+    inventory: Res<Inventory>,
+    buildables: Res<BuildableRegistry>,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut ev_game: EventReader<GameEvent>,
+    mut runner: ResMut<ScriptRunner>,
+    mut ev_script: EventWriter<ScriptActionEvent>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if reset || restart {
+        runner.restart();
+    }
+    let placed: Vec<_> = ev_game
+        .iter()
+        .filter_map(|ev| match ev {
+            GameEvent::BuildablePlaced { buildable, .. } => buildables.name(*buildable),
+            _ => None,
+        })
+        .collect();
+    let rules = match level.desc() {
+        Some(level_desc) if !level_desc.script.is_empty() => &level_desc.script,
+        _ => return,
+    };
+    if game.sequence() != GameSequence::Play {
+        return;
+    }
+    let frame = ScriptFrame {
+        placements: inventory.placed_count(),
+        dt: time.delta_seconds(),
+        placed,
+        balanced: balance.is_balanced(),
+    };
+    for action in runner.step(rules, &frame) {
+        trace!("Level script: {:?}", action);
+        ev_script.send(ScriptActionEvent(action));
+    }
+}
+
+/// Hint text shown by the level script, empty when no hint is shown.
+#[derive(Component)]
+struct ScriptHint {
+    /// Time left before the hint hides.
+    timer: Timer,
+}
+
+fn spawn_script_hint(
+    mut commands: Commands,
+    ui_resources: Res<UiResources>,
+    ui_layouts: Res<UiLayouts>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: ui_layouts.metric("script_hint.font_size"),
+                    color: ui_layouts.color("script_hint.text"),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("ScriptHint"))
+        .insert(Hud)
+        .insert(ScriptHint {
+            timer: Timer::from_seconds(HINT_DURATION, false),
+        });
+}
+
+/// Run the actions of the level script, and hide the hint once its time is over.
+fn apply_script_actions(
+    time: Res<Time>,
+    mut ev_script: EventReader<ScriptActionEvent>,
+    mut ev_play_sfx: EventWriter<PlaySfxEvent>,
+    mut shake: ResMut<ScreenShake>,
+    mut hint_query: Query<(&mut Text, &mut ScriptHint)>,
+) {
+    for ev in ev_script.iter() {
+        match &ev.0 {
+            ScriptAction::Hint(hint) => {
+                for (mut text, mut script_hint) in hint_query.iter_mut() {
+                    text.sections[0].value = hint.clone();
+                    script_hint.timer.reset();
+                }
+            }
+            ScriptAction::Sound(sfx) => ev_play_sfx.send(PlaySfxEvent(*sfx)),
+            ScriptAction::Shake(trauma) => shake.add_trauma(*trauma),
+        }
+    }
+    for (mut text, mut script_hint) in hint_query.iter_mut() {
+        if script_hint.timer.tick(time.delta()).just_finished() {
+            text.sections[0].value.clear();
+        }
+    }
+}
+
+fn script_cleanup(
+    mut commands: Commands,
+    mut runner: ResMut<ScriptRunner>,
+    query: Query<Entity, With<ScriptHint>>,
+) {
+    *runner = ScriptRunner::new();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin running the scripts of the levels, rules attached to the level data to show hints and
+/// play feedback at scripted moments. See [`ScriptRule`].
+pub struct ScriptPlugin;
+
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScriptRunner::new())
+            .add_event::<ScriptActionEvent>()
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_script_hint))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(
+                        run_level_script
+                            .label("run_level_script")
+                            .after("balance_state"),
+                    )
+                    .with_system(apply_script_actions.after("run_level_script")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(script_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers() {
+        let rules: Vec<ScriptRule> = serde_json::from_str(
+            r#"[ { "when": "start", "do": [ { "hint": "Welcome" } ] },
+                 { "when": { "after_placements": 2 }, "do": [ { "shake": 0.5 } ] },
+                 { "when": { "placed": "tower" }, "do": [ { "sound": "place" } ], "repeat": true },
+                 { "when": "enter_margin", "do": [ { "sound": "level_cleared" } ] },
+                 { "when": { "after_seconds": 1.5 }, "do": [ { "hint": "Hurry" } ] } ]"#,
+        )
+        .unwrap();
+        let mut runner = ScriptRunner::new();
+        runner.restart();
+        let mut step = |placements, placed: Vec<&'static str>, balanced| {
+            runner.step(
+                &rules,
+                &ScriptFrame {
+                    placements,
+                    dt: 1.0,
+                    placed,
+                    balanced,
+                },
+            )
+        };
+
+        assert_eq!(
+            step(0, vec![], true),
+            vec![ScriptAction::Hint("Welcome".into())]
+        );
+        assert_eq!(
+            step(1, vec!["tower"], false),
+            vec![
+                ScriptAction::Sound(Sfx::Place),
+                ScriptAction::Hint("Hurry".into())
+            ]
+        );
+        assert_eq!(
+            step(2, vec!["tower"], true),
+            vec![
+                ScriptAction::Shake(0.5),
+                ScriptAction::Sound(Sfx::Place),
+                ScriptAction::Sound(Sfx::LevelCleared)
+            ]
+        );
+        // Rules not repeated fire once per attempt
+        assert_eq!(step(3, vec!["hut"], false), vec![]);
+        assert_eq!(step(3, vec![], true), vec![]);
+
+        // Restarting fires them again
+        runner.restart();
+        let actions = runner.step(
+            &rules,
+            &ScriptFrame {
+                dt: 1.0,
+                balanced: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(actions, vec![ScriptAction::Hint("Welcome".into())]);
+    }
+}
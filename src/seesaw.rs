@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    conveyor::ConveyorTile, fragile::FragileTile, game::GameplaySystem, tilt, wear::Tile, AppState,
+    Grid, Level, Placed, Plate,
+};
+
+/// Description of a seesaw level, whose grid is split into two plates linked by a beam. Each
+/// plate balances on a pivot under its own center at one end of the beam, and the beam itself
+/// balances on a fulcrum under the grid center, like a seesaw.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct SeesawDesc {
+    /// First column of the right plate, in grid coordinates. The columns before it make the left
+    /// plate.
+    pub split: i32,
+    /// How much the beam tilts with the weights relative to the plates. At 0 the beam is locked
+    /// and the plates tilt on their own.
+    #[serde(default = "default_coupling")]
+    pub coupling: f32,
+}
+
+fn default_coupling() -> f32 {
+    0.5
+}
+
+impl SeesawDesc {
+    /// Index of the plate a cell belongs to, 0 for the left plate and 1 for the right one.
+    pub fn wing(&self, pos: &IVec2) -> usize {
+        if pos.x < self.split {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Pivots of the plates, under their center, in grid coordinates.
+    pub fn pivots(&self, grid: &Grid) -> [Vec2; 2] {
+        let center = |first: i32, last: i32| {
+            let first = grid.fpos(&IVec2::new(first, 0)).x;
+            let last = grid.fpos(&IVec2::new(last, 0)).x;
+            Vec2::new((first + last) / 2.0, 0.0)
+        };
+        [
+            center(grid.min_pos().x, self.split - 1),
+            center(self.split, grid.max_pos().x),
+        ]
+    }
+
+    /// Offsets of the COG of each plate from its pivot, as if the plates were not linked.
+    pub fn cog_offsets(&self, grid: &Grid) -> [Vec2; 2] {
+        let pivots = self.pivots(grid);
+        let mut offsets = [Vec2::ZERO; 2];
+        for (pos, _) in grid.placements() {
+            let wing = self.wing(&pos);
+            offsets[wing] += grid.cell(&pos).weight * (grid.lever_pos(&pos) - pivots[wing]);
+        }
+        offsets
+    }
+
+    /// Solve the beam constraint, from the COG offsets of the plates on their own and the moment
+    /// of all the weights about the fulcrum of the beam, to the offsets each plate effectively
+    /// tilts with. The plate springs are internal to the beam, so only the moment of all the
+    /// weights tilts the beam, and for small tilts each plate tilts by its own tilt plus the
+    /// beam tilt. The beam only tilts toward the left or the right.
+    pub fn solve(&self, offsets: [Vec2; 2], moment: f32) -> [Vec2; 2] {
+        let beam = Vec2::new(self.coupling.max(0.0) * moment, 0.0);
+        [offsets[0] + beam, offsets[1] + beam]
+    }
+
+    /// Effective COG offsets of the plates from their pivot, once linked by the beam. The level
+    /// is balanced when both are within the victory margin, which doesn't require each plate to
+    /// be balanced on its own.
+    pub fn balance_offsets(&self, grid: &Grid) -> [Vec2; 2] {
+        // The balance factor does not affect the center of gravity offset
+        self.solve(self.cog_offsets(grid), grid.calc_cog_offset(1.0).x)
+    }
+}
+
+/// Resource holding the entities of the two plates of the seesaw levels, children of the plate
+/// which stays level with the beam.
+#[derive(Debug, Default)]
+pub struct Seesaw {
+    wings: Option<[Entity; 2]>,
+}
+
+/// Marker for the entity of one of the two plates of a seesaw level.
+#[derive(Component)]
+struct Wing(usize);
+
+/// Spawn the plates of the seesaw levels once the plate exists.
+fn spawn_wings(
+    mut commands: Commands,
+    mut seesaw: ResMut<Seesaw>,
+    query: Query<Entity, With<Plate>>,
+) {
+    if seesaw.wings.is_some() {
+        return;
+    }
+    let plate = match query.get_single() {
+        Ok(plate) => plate,
+        Err(_) => return,
+    };
+    let mut spawn_wing = |index: usize| {
+        commands
+            .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+            .insert(Name::new(format!("Wing{}", index)))
+            .insert(Wing(index))
+            .insert(Parent(plate))
+            .id()
+    };
+    seesaw.wings = Some([spawn_wing(0), spawn_wing(1)]);
+}
+
+/// Move the new tiles, tile overlays, and buildables of a seesaw level under the plate of their
+/// cell, and the buildables shifted by a conveyor under the plate of their new cell.
+fn attach_to_wings(
+    mut commands: Commands,
+    level: Res<Level>,
+    seesaw: Res<Seesaw>,
+    tile_query: Query<(Entity, &Tile), Added<Tile>>,
+    placed_query: Query<(Entity, &Placed), Changed<Placed>>,
+    fragile_query: Query<(Entity, &FragileTile), Added<FragileTile>>,
+    conveyor_query: Query<(Entity, &ConveyorTile), Added<ConveyorTile>>,
+) {
+    let (desc, wings) = match (level.desc().and_then(|desc| desc.seesaw), seesaw.wings) {
+        (Some(desc), Some(wings)) => (desc, wings),
+        _ => return,
+    };
+    let positions = tile_query
+        .iter()
+        .map(|(entity, tile)| (entity, tile.pos))
+        .chain(
+            placed_query
+                .iter()
+                .map(|(entity, placed)| (entity, placed.0)),
+        )
+        .chain(
+            fragile_query
+                .iter()
+                .map(|(entity, fragile)| (entity, fragile.pos)),
+        )
+        .chain(
+            conveyor_query
+                .iter()
+                .map(|(entity, conveyor)| (entity, conveyor.pos)),
+        );
+    for (entity, pos) in positions {
+        commands
+            .entity(entity)
+            .insert(Parent(wings[desc.wing(&pos)]));
+    }
+}
+
+/// Tilt each plate of a seesaw level about its pivot.
+fn wing_balance_system(
+    grid: Res<Grid>,
+    level: Res<Level>,
+    mut query: Query<(&Wing, &mut Transform)>,
+) {
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    let desc = match level_desc.seesaw {
+        Some(desc) => desc,
+        None => {
+            for (_, mut transform) in query.iter_mut() {
+                *transform = Transform::identity();
+            }
+            return;
+        }
+    };
+    let pivots = desc.pivots(&grid);
+    let offsets = desc.balance_offsets(&grid);
+    for (wing, mut transform) in query.iter_mut() {
+        let angles = level_desc
+            .tilt_model
+            .angles(offsets[wing.0], level_desc.balance_factor);
+        let rotation = tilt::rotation(angles);
+        // Rotate about the pivot; plate local space has Z pointing toward -Y.
+        let pivot = Vec3::new(pivots[wing.0].x, 0.0, -pivots[wing.0].y);
+        transform.rotation = rotation;
+        transform.translation = pivot - rotation * pivot;
+    }
+}
+
+fn seesaw_cleanup(mut seesaw: ResMut<Seesaw>) {
+    // The wings are despawned with the plate
+    seesaw.wings = None;
+}
+
+/// Plugin for the seesaw levels, with two plates linked by a beam.
+pub struct SeesawPlugin;
+
+impl Plugin for SeesawPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Seesaw::default())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Balance)
+                    .after(GameplaySystem::Placement)
+                    .with_system(spawn_wings)
+                    .with_system(attach_to_wings)
+                    .with_system(wing_balance_system),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(seesaw_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inventory::Buildable, serialize::BuildableRegistry};
+
+    #[test]
+    fn solve() {
+        let offsets = [Vec2::new(1.0, 0.5), Vec2::new(-1.0, 0.0)];
+        // With a locked beam the plates tilt on their own
+        let locked = SeesawDesc {
+            split: 0,
+            coupling: 0.0,
+        };
+        assert_eq!(locked.solve(offsets, 3.0), offsets);
+        // otherwise the beam tilts both plates toward the heavier side
+        let seesaw = SeesawDesc {
+            split: 0,
+            coupling: 0.5,
+        };
+        assert_eq!(
+            seesaw.solve(offsets, 2.0),
+            [Vec2::new(2.0, 0.5), Vec2::new(0.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn balance() {
+        // The plates of the seesaw level of the game data, balanced through the beam while
+        // neither plate is balanced on its own
+        let mut grid = Grid::new();
+        grid.set_size(&IVec2::new(6, 3));
+        let bref = BuildableRegistry::new().register("hut", Buildable::without_assets("hut", 1.0));
+        let placements = [
+            ((-3, 0), 1.0),
+            ((-2, 0), 1.0),
+            ((0, -1), 2.0),
+            ((1, 1), 2.0),
+            ((2, 0), 1.0),
+        ];
+        for ((i, j), weight) in placements {
+            grid.spawn_item(&IVec2::new(i, j), bref, weight, Entity::from_raw(0));
+        }
+        let seesaw = SeesawDesc {
+            split: 0,
+            coupling: 0.4,
+        };
+        assert_eq!(
+            seesaw.pivots(&grid),
+            [Vec2::new(-1.5, 0.0), Vec2::new(1.5, 0.0)]
+        );
+        assert_eq!(
+            seesaw.cog_offsets(&grid),
+            [Vec2::new(-1.0, 0.0), Vec2::new(-1.0, 0.0)]
+        );
+        for offset in seesaw.balance_offsets(&grid) {
+            assert!(offset.length() < 1e-5);
+        }
+    }
+}
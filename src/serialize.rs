@@ -1,32 +1,46 @@
-use bevy::{app::AppExit, prelude::*};
-use serde::Deserialize;
-use std::{collections::HashMap, fs::File, io::Read};
+use bevy::{app::AppExit, prelude::*, reflect::impl_from_reflect_value};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs::File, io::Read, sync::Arc};
 
-use crate::{inventory::Buildable, text_asset::TextAsset, AppState, Error};
+use crate::{
+    anim::euler_quat,
+    conveyor::ConveyorDesc,
+    fragile::FragileTileDesc,
+    idle::IdleDesc,
+    interlude::InterludeDesc,
+    inventory::{Buildable, BuildablePart, PartModel},
+    lifetime::AssetLifetimes,
+    postprocess::ColorGrade,
+    schema::Schema,
+    script::ScriptRule,
+    seesaw::SeesawDesc,
+    text_asset::TextAsset,
+    tilt::{Pivot, TiltModel},
+    units::{WeightUnit, TONS},
+    wardrobe::Achievement,
+    weather::Weather,
+    AppState, Error, Grid,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct BuildableRef(pub String);
+/// Interned identifier of a buildable, resolved once from the buildable name when the game
+/// data is loaded. Use the [`BuildableRegistry`] to access the buildable itself or its name.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Reflect,
+)]
+#[reflect_value(PartialEq, Hash, Serialize, Deserialize)]
+pub struct BuildableId(u32);
 
-impl From<&str> for BuildableRef {
-    fn from(s: &str) -> Self {
-        BuildableRef(s.to_owned())
-    }
-}
+impl_from_reflect_value!(BuildableId);
 
-impl From<String> for BuildableRef {
-    fn from(s: String) -> Self {
-        BuildableRef(s)
-    }
-}
-
-impl From<&String> for BuildableRef {
-    fn from(s: &String) -> Self {
-        BuildableRef(s.clone())
+impl BuildableId {
+    /// Index of the buildable into the [`BuildableRegistry`].
+    pub fn index(&self) -> usize {
+        self.0 as usize
     }
 }
 
 /// Description of a single level.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LevelDesc {
     /// Level display name.
     pub name: String,
@@ -34,16 +48,155 @@ pub struct LevelDesc {
     pub grid_size: IVec2,
     /// Balance factor for COG excentricity to plate rotation.
     pub balance_factor: f32,
+    /// Fulcrum the plate balances on.
+    pub pivot: Pivot,
+    /// Split of the grid into two plates linked by a beam, for the seesaw levels.
+    pub seesaw: Option<SeesawDesc>,
+    /// Model of the plate tilt from the weights on it.
+    pub tilt_model: TiltModel,
+    /// Terrace heights of the plate cells, if the plate is not flat.
+    pub terrain: Option<TerrainDesc>,
+    /// Fragile tiles breaking under too much weight.
+    pub fragile: Vec<FragileTileDesc>,
+    /// Conveyor tiles shifting the buildables placed on them.
+    pub conveyors: Vec<ConveyorDesc>,
+    /// Weather of the level, modifying its gameplay.
+    pub weather: Weather,
     /// Victor margin for COG excentricity.
     pub victory_margin: f32,
     /// Map of available buildables count when starting level.
-    pub inventory: HashMap<BuildableRef, u32>,
+    pub inventory: HashMap<BuildableId, u32>,
+    /// Additional inventory delivered while playing the level.
+    pub deliveries: Vec<DeliveryDesc>,
+    /// Reference completion time in seconds, if any, used to score the level.
+    pub par_time: Option<f32>,
+    /// Reference number of moves, if any, used to score the level.
+    pub par_moves: Option<u32>,
+    /// Random draw of buildables replacing the inventory in market mode, if any.
+    pub market: Option<MarketDesc>,
+    /// World the level belongs to, defining its environment, if any.
+    pub world: Option<Arc<WorldDesc>>,
+    /// Time in seconds the plate needs to stay balanced to clear the level with buildables left,
+    /// if any. Otherwise the level is only checked once all the buildables are placed.
+    pub stabilize_time: Option<f32>,
+    /// Difficulty rating, from 1 for the easiest levels, if rated.
+    pub difficulty: Option<u32>,
+    /// Name of the interlude played before the level, if any.
+    pub interlude: Option<String>,
+    /// Rules of the level script, if any.
+    pub script: Vec<ScriptRule>,
+}
+
+impl LevelDesc {
+    /// Random draw of buildables of the level in market mode. Levels without an explicit market
+    /// draw as many buildables as their inventory, weighted by their count.
+    pub fn market(&self) -> MarketDesc {
+        self.market.clone().unwrap_or_else(|| {
+            let mut weights: Vec<_> = self
+                .inventory
+                .iter()
+                .map(|(&bref, &count)| (bref, count as f32))
+                .collect();
+            weights.sort_by_key(|(bref, _)| *bref);
+            MarketDesc {
+                weights,
+                count: self.inventory.values().sum(),
+            }
+        })
+    }
+
+    /// Is the plate balanced within the victory margin, or both plates of a seesaw level?
+    pub fn is_victory(&self, grid: &Grid, victory_margin: f32) -> bool {
+        match &self.seesaw {
+            Some(seesaw) => seesaw
+                .balance_offsets(grid)
+                .iter()
+                .all(|offset| offset.length() < victory_margin),
+            None => grid.is_victory(self.balance_factor, &self.pivot, victory_margin),
+        }
+    }
+
+    /// Magnitude of the plate tilt angle, in radians, or of the most tilted plate of a seesaw
+    /// level.
+    pub fn calc_tilt(&self, grid: &Grid) -> f32 {
+        match &self.seesaw {
+            Some(seesaw) => seesaw
+                .balance_offsets(grid)
+                .iter()
+                .map(|&offset| self.tilt_model.angles(offset, self.balance_factor).length())
+                .fold(0.0, f32::max),
+            None => grid.calc_tilt(self.balance_factor, &self.pivot, &self.tilt_model),
+        }
+    }
+
+    /// Unit the weights of the level are displayed in, from its world if any.
+    pub fn weight_unit(&self) -> &WeightUnit {
+        self.world
+            .as_ref()
+            .map_or(&TONS, |world| &world.weight_unit)
+    }
+}
+
+/// Description of the environment of a world, shared by all its levels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldDesc {
+    /// Name of the world in the game data.
+    pub name: String,
+    /// Color of the sky at the zenith.
+    pub sky_top: Color,
+    /// Color of the sky at the horizon and below.
+    pub sky_horizon: Color,
+    /// Folder of the 6 cubemap faces, relative to the textures/ folder, replacing the sky
+    /// gradient if set.
+    pub cubemap: Option<String>,
+    /// Number of distant clouds.
+    pub clouds: u32,
+    /// Unit the weights are displayed in.
+    pub weight_unit: WeightUnit,
+    /// Name of the interlude played when entering the world, if any.
+    pub interlude: Option<String>,
+    /// Color grade of the scene, giving the world its mood.
+    pub grade: ColorGrade,
+    /// Colors of the buildable materials, by palette slot.
+    pub palette: HashMap<String, Color>,
+}
+
+/// Description of the weighted random draw of buildables of a level in market mode.
+#[derive(Debug, Clone)]
+pub struct MarketDesc {
+    /// Relative weight of each buildable in the draw.
+    pub weights: Vec<(BuildableId, f32)>,
+    /// Total number of buildables drawn.
+    pub count: u32,
+}
+
+/// Description of the terraces of a plate which is not flat.
+#[derive(Debug, Clone)]
+pub struct TerrainDesc {
+    /// Height of each cell, indexed like the grid content.
+    pub heights: Vec<f32>,
+    /// Extra lever arm of the elevated cells per unit of height.
+    pub lever_arm: f32,
+}
+
+/// Description of some inventory delivered in the middle of a level.
+#[derive(Debug, Clone)]
+pub struct DeliveryDesc {
+    /// Number of buildables placed since the start of the level which triggers the delivery.
+    pub after_placements: u32,
+    /// Map of delivered buildables count.
+    pub inventory: HashMap<BuildableId, u32>,
 }
 
 /// Resource describing of all available levels and their rules.
+///
+/// Level descriptions are shared with the [`Level`] resource of the level being played,
+/// which keeps its own reference to the active description.
+///
+/// [`Level`]: crate::level::Level
 #[derive(Debug)]
 pub struct Levels {
-    levels: Vec<LevelDesc>,
+    levels: Vec<Arc<LevelDesc>>,
 }
 
 impl Levels {
@@ -52,33 +205,148 @@ impl Levels {
     }
 
     pub fn with_levels(levels: Vec<LevelDesc>) -> Self {
-        Levels { levels }
+        Levels {
+            levels: levels.into_iter().map(Arc::new).collect(),
+        }
     }
 
-    pub fn levels(&self) -> &[LevelDesc] {
+    pub fn levels(&self) -> &[Arc<LevelDesc>] {
         &self.levels
     }
+
+    /// Get a level by index, if it exists.
+    pub fn get(&self, index: usize) -> Option<&Arc<LevelDesc>> {
+        self.levels.get(index)
+    }
+
+    /// Find a level by its display name, returning its index and description.
+    pub fn by_name(&self, name: &str) -> Option<(usize, &Arc<LevelDesc>)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .find(|(_, level_desc)| level_desc.name == name)
+    }
+
+    /// Find an easier level to suggest instead of the given one: the first level of the hardest
+    /// difficulty rating below the rating of that level. Unrated levels have no easier level.
+    pub fn easier_than(&self, index: usize) -> Option<usize> {
+        let difficulty = self.levels.get(index)?.difficulty?;
+        let easier = self
+            .levels
+            .iter()
+            .filter_map(|level_desc| level_desc.difficulty)
+            .filter(|&rating| rating < difficulty)
+            .max()?;
+        self.levels
+            .iter()
+            .position(|level_desc| level_desc.difficulty == Some(easier))
+    }
+
+    /// Number of levels.
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Is the list of levels empty?
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
 }
 
-/// Resource describing of all buildable items and their characteristics.
-#[derive(Debug)]
-pub struct Buildables {
-    buildables: HashMap<BuildableRef, Buildable>,
+/// Resource registering all buildable items and their characteristics, indexed by [`BuildableId`].
+///
+/// The registry also keeps the name of each buildable as found in the game data, which is only
+/// needed to resolve identifiers at load time and for serialization.
+#[derive(Debug, Default)]
+pub struct BuildableRegistry {
+    buildables: Vec<Buildable>,
+    names: Vec<String>,
+    ids: HashMap<String, BuildableId>,
 }
 
-impl Buildables {
+impl BuildableRegistry {
     pub fn new() -> Self {
-        Buildables {
-            buildables: HashMap::new(),
+        BuildableRegistry {
+            buildables: vec![],
+            names: vec![],
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Register a new buildable with the given name, and return its identifier. If a buildable
+    /// with the same name is already registered, it's replaced and keeps its identifier.
+    pub fn register(&mut self, name: &str, buildable: Buildable) -> BuildableId {
+        if let Some(&id) = self.ids.get(name) {
+            self.buildables[id.index()] = buildable;
+            return id;
         }
+        let id = BuildableId(self.buildables.len() as u32);
+        self.buildables.push(buildable);
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        id
+    }
+
+    pub fn get(&self, id: BuildableId) -> Option<&Buildable> {
+        self.buildables.get(id.index())
+    }
+
+    pub fn get_mut(&mut self, id: BuildableId) -> Option<&mut Buildable> {
+        self.buildables.get_mut(id.index())
+    }
+
+    /// Find the identifier of a buildable from its name.
+    pub fn id(&self, name: &str) -> Option<BuildableId> {
+        self.ids.get(name).copied()
     }
 
-    pub fn with_buildables(buildables: HashMap<BuildableRef, Buildable>) -> Self {
-        Buildables { buildables }
+    /// Get the name of a buildable from its identifier.
+    pub fn name(&self, id: BuildableId) -> Option<&str> {
+        self.names.get(id.index()).map(|name| &name[..])
     }
 
-    pub fn get(&self, id: &BuildableRef) -> Option<&Buildable> {
-        self.buildables.get(id)
+    /// Iterate over all registered buildables.
+    pub fn iter(&self) -> impl Iterator<Item = (BuildableId, &Buildable)> {
+        self.buildables
+            .iter()
+            .enumerate()
+            .map(|(index, buildable)| (BuildableId(index as u32), buildable))
+    }
+
+    /// Resolve a serialized inventory indexed by buildable names into one indexed by identifiers.
+    /// Unknown buildable names are reported and skipped.
+    pub fn resolve_inventory(&self, inventory: &HashMap<String, u32>) -> HashMap<BuildableId, u32> {
+        inventory
+            .iter()
+            .filter_map(|(name, &count)| match self.id(name) {
+                Some(id) => Some((id, count)),
+                None => {
+                    error!("Unknown buildable '{}' in inventory.", name);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Resolve a serialized market indexed by buildable names. Unknown buildable names are
+    /// reported and skipped.
+    pub fn resolve_market(&self, market: &MarketDescArchive) -> MarketDesc {
+        let mut weights: Vec<_> = market
+            .weights
+            .iter()
+            .filter_map(|(name, &weight)| match self.id(name) {
+                Some(id) => Some((id, weight)),
+                None => {
+                    error!("Unknown buildable '{}' in market.", name);
+                    None
+                }
+            })
+            .collect();
+        weights.sort_by_key(|(bref, _)| *bref);
+        MarketDesc {
+            weights,
+            count: market.count,
+        }
     }
 }
 
@@ -93,6 +361,118 @@ pub struct BuildableRulesArchive {
     pub frame: String,
     /// Weight of the buildable.
     pub weight: f32,
+    /// Maximum deviation of the weight of each placed instance, drawn at random within
+    /// `weight ± weight_variance`. All instances weigh the same if zero.
+    #[serde(default)]
+    pub weight_variance: f32,
+    /// Flavor text shown when the buildable is selected.
+    #[serde(default)]
+    pub description: String,
+    /// Ambient animation once placed, if any.
+    #[serde(default)]
+    pub idle: Option<IdleDesc>,
+    /// Parts assembled with the 3D model, if any.
+    #[serde(default)]
+    pub parts: Vec<BuildablePartArchive>,
+    /// Palette slots of the materials of the 3D model and its parts, by path of the material
+    /// asset relative to the models/ folder, like `"hut.glb#Material1"`. The materials are
+    /// recolored with the palette of the world of the level.
+    #[serde(default)]
+    pub palette: HashMap<String, String>,
+    /// Cosmetic skin variants.
+    #[serde(default)]
+    pub skins: Vec<SkinArchive>,
+}
+
+/// Part of a buildable serialized, either a scene or a single mesh.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildablePartArchive {
+    /// Path to the 3D scene asset, relative to the models/ folder, like `"roof.glb#Scene0"`.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Path to a single mesh asset instead of a scene, relative to the models/ folder, like
+    /// `"parts.glb#Mesh0/Primitive0"`.
+    #[serde(default)]
+    pub mesh: Option<String>,
+    /// Path to the material asset of the mesh, relative to the models/ folder, like
+    /// `"parts.glb#Material0"`. Defaults to the material of the buildable.
+    #[serde(default)]
+    pub material: Option<String>,
+    /// Offset from the origin of the buildable.
+    #[serde(default)]
+    pub offset: Vec3,
+    /// Rotation as XYZ Euler angles in degrees.
+    #[serde(default)]
+    pub rotation: Vec3,
+    #[serde(default = "default_part_scale")]
+    pub scale: Vec3,
+}
+
+fn default_part_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+impl BuildablePartArchive {
+    /// Check that the part has either a scene or a mesh, and a material only with a mesh.
+    pub fn check(&self) -> Result<(), &'static str> {
+        match (&self.model, &self.mesh, &self.material) {
+            (Some(_), None, None) | (None, Some(_), _) => Ok(()),
+            (Some(_), None, Some(_)) => Err("material only applies to a mesh"),
+            _ => Err("needs either a model or a mesh"),
+        }
+    }
+
+    /// Convert into a part referencing its assets with weak handles, loaded with the levels using
+    /// them. The mesh defaults to the given material.
+    pub fn to_part(
+        &self,
+        lifetimes: &mut AssetLifetimes,
+        material: &Handle<StandardMaterial>,
+    ) -> Result<BuildablePart, &'static str> {
+        self.check()?;
+        let model = match (&self.model, &self.mesh) {
+            (Some(model), _) => {
+                PartModel::Scene(lifetimes.weak_handle(&format!("models/{}", model)))
+            }
+            (None, Some(mesh)) => PartModel::Mesh {
+                mesh: lifetimes.weak_handle(&format!("models/{}", mesh)),
+                material: self.material.as_ref().map_or(material.clone(), |path| {
+                    lifetimes.weak_handle(&format!("models/{}", path))
+                }),
+            },
+            (None, None) => unreachable!(),
+        };
+        Ok(BuildablePart {
+            model,
+            transform: Transform {
+                translation: self.offset,
+                rotation: euler_quat(self.rotation),
+                scale: self.scale,
+            },
+        })
+    }
+}
+
+/// Cosmetic skin variant of a buildable serialized.
+#[derive(Debug, Deserialize)]
+pub struct SkinArchive {
+    /// Display name.
+    pub name: String,
+    /// Path to the 3D model asset, relative to the models/ folder. Defaults to the model of the
+    /// buildable.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Path to the frame 2D texture asset, relative to the textures/ folder. Defaults to the
+    /// frame of the buildable.
+    #[serde(default)]
+    pub frame: Option<String>,
+    /// Achievement unlocking the skin. The skin is available from the start if not set.
+    #[serde(default)]
+    pub unlock: Option<Achievement>,
+    /// Parts assembled with the 3D model. Defaults to the parts of the buildable.
+    #[serde(default)]
+    pub parts: Option<Vec<BuildablePartArchive>>,
 }
 
 /// Description of a single level serialized.
@@ -104,22 +484,246 @@ pub struct LevelDescArchive {
     pub grid_size: IVec2,
     /// Balance factor for COG excentricity to plate rotation.
     pub balance_factor: f32,
+    /// Fulcrum the plate balances on, a point under the grid center if not set.
+    #[serde(default)]
+    pub pivot: Pivot,
+    /// Split of the grid into two plates linked by a beam, for the seesaw levels, if any. The
+    /// plates balance on their own center, replacing the pivot.
+    #[serde(default)]
+    pub seesaw: Option<SeesawDesc>,
+    /// Model of the plate tilt from the weights on it, the linear mapping if not set.
+    #[serde(default, rename = "tilt")]
+    pub tilt_model: TiltModel,
+    /// Terrace heights of the plate cells, if any. The plate is flat if not set.
+    #[serde(default)]
+    pub terrain: Option<TerrainDescArchive>,
+    /// Fragile tiles breaking under too much weight, if any.
+    #[serde(default)]
+    pub fragile: Vec<FragileTileDesc>,
+    /// Conveyor tiles shifting the buildables placed on them, if any.
+    #[serde(default)]
+    pub conveyors: Vec<ConveyorDesc>,
+    /// Weather of the level, clear if not set.
+    #[serde(default)]
+    pub weather: Weather,
     /// Victor margin for COG excentricity.
     pub victory_margin: f32,
     /// Map of available buildables count when starting level.
     pub inventory: HashMap<String, u32>,
+    /// Additional inventory delivered while playing the level.
+    #[serde(default)]
+    pub deliveries: Vec<DeliveryDescArchive>,
+    /// Reference completion time in seconds, if any, used to score the level.
+    #[serde(default)]
+    pub par_time: Option<f32>,
+    /// Reference number of moves, if any, used to score the level.
+    #[serde(default)]
+    pub par_moves: Option<u32>,
+    /// Random draw of buildables replacing the inventory in market mode, if any.
+    #[serde(default)]
+    pub market: Option<MarketDescArchive>,
+    /// Name of the world the level belongs to, if any.
+    #[serde(default)]
+    pub world: Option<String>,
+    /// Time in seconds the plate needs to stay balanced to clear the level with buildables left,
+    /// if any.
+    #[serde(default)]
+    pub stabilize_time: Option<f32>,
+    /// Difficulty rating, from 1 for the easiest levels, if rated.
+    #[serde(default)]
+    pub difficulty: Option<u32>,
+    /// Name of the interlude played before the level, if any.
+    #[serde(default)]
+    pub interlude: Option<String>,
+    /// Rules of the level script, showing hints and playing feedback at scripted moments, if any.
+    #[serde(default)]
+    pub script: Vec<ScriptRule>,
+}
+
+impl LevelDescArchive {
+    /// Convert into a level description, resolving buildable and world names. Unknown names are
+    /// reported and skipped.
+    pub fn to_desc(
+        &self,
+        buildables: &BuildableRegistry,
+        worlds: &HashMap<String, Arc<WorldDesc>>,
+    ) -> LevelDesc {
+        LevelDesc {
+            name: self.name.clone(),
+            grid_size: self.grid_size,
+            balance_factor: self.balance_factor,
+            pivot: self.pivot,
+            seesaw: self.seesaw,
+            tilt_model: self.tilt_model,
+            terrain: self
+                .terrain
+                .as_ref()
+                .and_then(|terrain| terrain.to_desc(self.grid_size)),
+            fragile: self.fragile.clone(),
+            conveyors: self.conveyors.clone(),
+            weather: self.weather,
+            victory_margin: self.victory_margin,
+            inventory: buildables.resolve_inventory(&self.inventory),
+            deliveries: self
+                .deliveries
+                .iter()
+                .map(|delivery| DeliveryDesc {
+                    after_placements: delivery.after_placements,
+                    inventory: buildables.resolve_inventory(&delivery.inventory),
+                })
+                .collect(),
+            par_time: self.par_time,
+            par_moves: self.par_moves,
+            market: self
+                .market
+                .as_ref()
+                .map(|market| buildables.resolve_market(market)),
+            world: self.world.as_ref().and_then(|name| {
+                let world = worlds.get(name).cloned();
+                if world.is_none() {
+                    error!("Unknown world '{}' in level.", name);
+                }
+                world
+            }),
+            stabilize_time: self.stabilize_time,
+            difficulty: self.difficulty,
+            interlude: self.interlude.clone(),
+            script: self.script.clone(),
+        }
+    }
+}
+
+/// Description of the environment of a world serialized.
+#[derive(Debug, Deserialize)]
+pub struct WorldDescArchive {
+    /// RGB color of the sky at the zenith.
+    pub sky_top: [f32; 3],
+    /// RGB color of the sky at the horizon and below.
+    pub sky_horizon: [f32; 3],
+    /// Folder of the 6 cubemap faces `px.png`, `nx.png`, `py.png`, `ny.png`, `pz.png`, and
+    /// `nz.png`, relative to the textures/ folder. Replaces the sky gradient if set.
+    #[serde(default)]
+    pub cubemap: Option<String>,
+    /// Number of distant clouds.
+    #[serde(default)]
+    pub clouds: u32,
+    /// Unit the weights are displayed in, tons if not set.
+    #[serde(default)]
+    pub weight_unit: WeightUnit,
+    /// Name of the interlude played before the first level of the world, if any.
+    #[serde(default)]
+    pub interlude: Option<String>,
+    /// Color grade of the scene, the default grade if not set.
+    #[serde(default)]
+    pub grade: ColorGrade,
+    /// RGB colors of the buildable materials, by palette slot. Materials with a slot missing
+    /// from the palette keep their own color.
+    #[serde(default)]
+    pub palette: HashMap<String, [f32; 3]>,
+}
+
+impl WorldDescArchive {
+    pub fn to_desc(&self, name: &str) -> WorldDesc {
+        let [r, g, b] = self.sky_top;
+        let sky_top = Color::rgb(r, g, b);
+        let [r, g, b] = self.sky_horizon;
+        let sky_horizon = Color::rgb(r, g, b);
+        WorldDesc {
+            name: name.to_owned(),
+            sky_top,
+            sky_horizon,
+            cubemap: self.cubemap.clone(),
+            clouds: self.clouds,
+            weight_unit: self.weight_unit.clone(),
+            interlude: self.interlude.clone(),
+            grade: self.grade,
+            palette: self
+                .palette
+                .iter()
+                .map(|(slot, &[r, g, b])| (slot.clone(), Color::rgb(r, g, b)))
+                .collect(),
+        }
+    }
+}
+
+/// Description of the weighted random draw of buildables of a level serialized.
+#[derive(Debug, Deserialize)]
+pub struct MarketDescArchive {
+    /// Relative weight of each buildable in the draw, by buildable name.
+    pub weights: HashMap<String, f32>,
+    /// Total number of buildables drawn.
+    pub count: u32,
+}
+
+/// Description of the terraces of a plate serialized.
+#[derive(Debug, Deserialize)]
+pub struct TerrainDescArchive {
+    /// Height of each cell, as one row per line of the grid from the back of the plate to the
+    /// front, like the plate reads on screen.
+    pub heights: Vec<Vec<f32>>,
+    /// Extra lever arm of the elevated cells per unit of height, adding to the weight
+    /// contribution of the buildables placed on them. Heights are only visual if not set.
+    #[serde(default)]
+    pub lever_arm: f32,
+}
+
+impl TerrainDescArchive {
+    /// Check the rows match the grid size.
+    pub fn fits(&self, grid_size: IVec2) -> bool {
+        self.heights.len() == grid_size.y as usize
+            && self
+                .heights
+                .iter()
+                .all(|row| row.len() == grid_size.x as usize)
+    }
+
+    /// Convert into a terrain description for a grid of the given size. Rows not matching the
+    /// grid size are reported and the plate is left flat.
+    pub fn to_desc(&self, grid_size: IVec2) -> Option<TerrainDesc> {
+        if !self.fits(grid_size) {
+            error!(
+                "Terrain heights do not match the grid size {:?}.",
+                grid_size.to_array()
+            );
+            return None;
+        }
+        Some(TerrainDesc {
+            // Grid content starts at the front row
+            heights: self.heights.iter().rev().flatten().copied().collect(),
+            lever_arm: self.lever_arm,
+        })
+    }
+}
+
+/// Description of some inventory delivered in the middle of a level serialized.
+#[derive(Debug, Deserialize)]
+pub struct DeliveryDescArchive {
+    /// Number of buildables placed since the start of the level which triggers the delivery.
+    pub after_placements: u32,
+    /// Map of delivered buildables count.
+    pub inventory: HashMap<String, u32>,
 }
 
-/// Game data serialized.
+/// Game data serialized. Older versions of the schema are upgraded on load, see [`Schema`].
 #[derive(Debug, Deserialize)]
 pub struct GameDataArchive {
-    pub inventory: HashMap<String, BuildableRulesArchive>,
+    /// Version of the schema of the file.
+    pub schema_version: u32,
+    /// Buildable definitions, by buildable name.
+    pub buildables: HashMap<String, BuildableRulesArchive>,
+    /// Environments of the worlds, by world name.
+    #[serde(default)]
+    pub worlds: HashMap<String, WorldDescArchive>,
+    /// Story interludes played between the levels, by interlude name.
+    #[serde(default)]
+    pub interludes: HashMap<String, InterludeDesc>,
     pub levels: Vec<LevelDescArchive>,
 }
 
 impl GameDataArchive {
     pub fn from_json(json_content: &str) -> Result<GameDataArchive, Error> {
-        let file: GameDataArchive = serde_json::from_str(json_content)?;
+        let value = Schema::GameData.parse(json_content)?;
+        let file: GameDataArchive = serde_json::from_value(value)?;
         debug!("Loaded levels.json:");
         for (index, l) in file.levels.iter().enumerate() {
             let inv = l
@@ -149,7 +753,7 @@ enum ConfigLoadState {
     Loaded,
 }
 
-/// Plugin for game data loading. This inserts a [`Levels`] resource and a [`Buildables`]
+/// Plugin for game data loading. This inserts a [`Levels`] resource and a [`BuildableRegistry`]
 /// resource.
 pub struct SerializePlugin;
 
@@ -157,6 +761,7 @@ impl Plugin for SerializePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Levels::new())
             .insert_resource(ConfigLoadState::Unloaded)
-            .insert_resource(Buildables::new());
+            .insert_resource(BuildableRegistry::new())
+            .register_type::<BuildableId>();
     }
 }
@@ -0,0 +1,248 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioSource};
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::{game::GameEvent, interlude::LocalizedText, text_asset::TextAsset, Config};
+
+/// Built-in sound effects table, used until the one in the assets is loaded, and for the sound
+/// effects it doesn't define.
+const BUILTIN_SFX: &str = include_str!("../assets/audio/sfx.json");
+
+/// Sound effects played in response to gameplay actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sfx {
+    /// A buildable could not be placed at the cursor position.
+    PlacementError,
+    /// A buildable was placed on the plate.
+    Place,
+    /// The level was cleared.
+    LevelCleared,
+    /// The level was failed.
+    LevelFailed,
+}
+
+impl Sfx {
+    /// Sound effect to play in response to a gameplay event, if any.
+    pub fn from_game_event(ev: &GameEvent) -> Option<Sfx> {
+        match ev {
+            GameEvent::BuildablePlaced { .. } => Some(Sfx::Place),
+            GameEvent::LevelCleared { .. } => Some(Sfx::LevelCleared),
+            GameEvent::LevelFailed { .. } => Some(Sfx::LevelFailed),
+            _ => None,
+        }
+    }
+}
+
+fn default_range() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+/// Description of a sound effect in the sound effects table.
+///
+/// ```json
+/// "place": { "files": ["audio/place1.wav", "audio/place2.wav"], "pitch": [0.9, 1.1],
+///            "caption": "[thud: placed]" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SfxDesc {
+    /// Variations of the sound effect, as paths to audio assets relative to the assets/ folder.
+    /// Each play picks one at random.
+    pub files: Vec<String>,
+    /// Range of the volume multiplier, picked at random on each play.
+    #[serde(default = "default_range")]
+    pub volume: (f32, f32),
+    /// Range of the pitch multiplier, picked at random on each play.
+    #[serde(default = "default_range")]
+    pub pitch: (f32, f32),
+    /// Caption shown when the sound effect plays, if captions are enabled.
+    #[serde(default)]
+    pub caption: Option<LocalizedText>,
+}
+
+/// Variation of a sound effect picked to play once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SfxVariation<'a> {
+    /// Path to the audio asset, relative to the assets/ folder.
+    pub file: &'a str,
+    /// Volume multiplier.
+    pub volume: f32,
+    /// Pitch multiplier.
+    pub pitch: f32,
+}
+
+impl SfxDesc {
+    /// Pick a variation to play, if the sound effect has any file.
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<SfxVariation<'_>> {
+        let file = self.files.choose(rng)?;
+        let mut in_range = |(min, max): (f32, f32)| {
+            if min < max {
+                rng.gen_range(min..=max)
+            } else {
+                min
+            }
+        };
+        Some(SfxVariation {
+            file,
+            volume: in_range(self.volume),
+            pitch: in_range(self.pitch),
+        })
+    }
+}
+
+/// Resource holding the sound effects table, loaded from `audio/sfx.json` at boot, mapping each
+/// sound effect to its variations, so sounds can change without code changes.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct SfxTable {
+    sfx: HashMap<Sfx, SfxDesc>,
+    /// Source asset, to reload the table when it changes.
+    #[serde(skip)]
+    handle: Handle<TextAsset>,
+}
+
+impl Default for SfxTable {
+    fn default() -> Self {
+        serde_json::from_str(BUILTIN_SFX).expect("Invalid built-in sound effects table")
+    }
+}
+
+impl SfxTable {
+    /// Parse the table from JSON, falling back to the built-in one for the sound effects not
+    /// defined.
+    pub fn from_json(json_content: &str) -> serde_json::Result<SfxTable> {
+        let table: SfxTable = serde_json::from_str(json_content)?;
+        let mut merged = SfxTable::default();
+        merged.sfx.extend(table.sfx);
+        Ok(merged)
+    }
+
+    /// Keep the asset the table was loaded from, to reload it when it changes.
+    pub fn with_handle(mut self, handle: Handle<TextAsset>) -> Self {
+        self.handle = handle;
+        self
+    }
+
+    pub fn get(&self, sfx: Sfx) -> Option<&SfxDesc> {
+        self.sfx.get(&sfx)
+    }
+}
+
+/// Event to play a sound effect.
+#[derive(Debug)]
+pub struct PlaySfxEvent(pub Sfx);
+
+/// Audio channel for sound effects, separate from the main track playing the background music.
+pub struct SfxChannel;
+
+/// Play the sound effects requested, picking a variation of each from the [`SfxTable`]. The
+/// variations are drawn from [`thread_rng()`] and not the gameplay generator, so sounds don't
+/// change the gameplay draws of a reproduced run.
+fn play_sfx(
+    asset_server: Res<AssetServer>,
+    audio: Res<AudioChannel<SfxChannel>>,
+    config: Res<Config>,
+    table: Res<SfxTable>,
+    mut ev_play_sfx: EventReader<PlaySfxEvent>,
+) {
+    #[allow(clippy::disallowed_methods)]
+    let mut rng = thread_rng();
+    for ev in ev_play_sfx.iter() {
+        if !config.sound.enabled {
+            continue;
+        }
+        let variation = match table.get(ev.0).and_then(|desc| desc.pick(&mut rng)) {
+            Some(variation) => variation,
+            None => {
+                warn!("No sound for {:?}", ev.0);
+                continue;
+            }
+        };
+        trace!("play_sfx({:?}) -> {:?}", ev.0, variation);
+        let source: Handle<AudioSource> = asset_server.load(variation.file);
+        audio.set_volume(config.sound.volume * variation.volume);
+        audio.set_playback_rate(variation.pitch);
+        audio.play(source);
+    }
+}
+
+/// Reload the sound effects table when its file changes. Only happens with the `hot_reload`
+/// feature, which makes the asset server watch the asset folder.
+fn reload_sfx_table(
+    mut ev_asset: EventReader<AssetEvent<TextAsset>>,
+    text_assets: Res<Assets<TextAsset>>,
+    mut table: ResMut<SfxTable>,
+) {
+    for ev in ev_asset.iter() {
+        let handle = match ev {
+            AssetEvent::Modified { handle } if *handle == table.handle => handle,
+            _ => continue,
+        };
+        let json_table = match text_assets.get(handle) {
+            Some(json_table) => json_table,
+            None => continue,
+        };
+        match SfxTable::from_json(&json_table.value[..]) {
+            Ok(reloaded) => {
+                *table = reloaded.with_handle(handle.clone());
+                info!("Reloaded audio/sfx.json");
+            }
+            Err(err) => error!(
+                "Failed to parse audio/sfx.json, keeping the sound effects: {}",
+                err
+            ),
+        }
+    }
+}
+
+fn game_event_sfx(mut ev_game: EventReader<GameEvent>, mut ev_play_sfx: EventWriter<PlaySfxEvent>) {
+    for sfx in ev_game.iter().filter_map(Sfx::from_game_event) {
+        ev_play_sfx.send(PlaySfxEvent(sfx));
+    }
+}
+
+/// Plugin to play sound effects on a dedicated audio channel.
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_channel::<SfxChannel>()
+            .insert_resource(SfxTable::default())
+            .add_event::<PlaySfxEvent>()
+            .add_system(reload_sfx_table.before(play_sfx))
+            .add_system(game_event_sfx.before(play_sfx))
+            .add_system(play_sfx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn table() {
+        let table = SfxTable::from_json(
+            r#"{ "place": { "files": ["audio/a.wav", "audio/b.wav"], "pitch": [0.5, 2.0] } }"#,
+        )
+        .unwrap();
+        // Sound effects not defined keep the built-in ones
+        assert_eq!(
+            table.get(Sfx::LevelFailed),
+            SfxTable::default().get(Sfx::LevelFailed)
+        );
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let place = table.get(Sfx::Place).unwrap();
+        for _ in 0..16 {
+            let variation = place.pick(&mut rng).unwrap();
+            assert!(variation.file == "audio/a.wav" || variation.file == "audio/b.wav");
+            assert_eq!(variation.volume, 1.0);
+            assert!((0.5..=2.0).contains(&variation.pitch));
+        }
+
+        assert!(SfxTable::from_json(r#"{ "whoosh": { "files": [] } }"#).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use bevy::{pbr::DirectionalLightShadowMap, prelude::*};
+
+use crate::config::{Config, ShadowQuality};
+
+/// Half extent of the area covered by the shadow map, large enough for the biggest plate.
+const SHADOW_EXTENT: f32 = 8.0;
+
+/// Apply the shadow quality of the config to the directional lights whenever the config changes,
+/// like after it is loaded during boot, and to the lights spawned afterwards.
+fn apply_shadow_quality(
+    config: Res<Config>,
+    mut shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut query: Query<(&mut DirectionalLight, ChangeTrackers<DirectionalLight>)>,
+) {
+    let config_changed = config.is_changed();
+    if !config_changed && !query.iter().any(|(_, tracker)| tracker.is_added()) {
+        return;
+    }
+    let quality = config.graphics.shadows;
+    let size = quality.shadow_map_size();
+    debug!("Shadow quality: {:?}", quality);
+    if let Some(size) = size {
+        if shadow_map.size != size {
+            shadow_map.size = size;
+        }
+    }
+    for (mut light, tracker) in query.iter_mut() {
+        if !config_changed && !tracker.is_added() {
+            continue;
+        }
+        light.shadows_enabled = size.is_some();
+        light.shadow_projection = OrthographicProjection {
+            left: -SHADOW_EXTENT,
+            right: SHADOW_EXTENT,
+            bottom: -SHADOW_EXTENT,
+            top: SHADOW_EXTENT,
+            near: -4.0 * SHADOW_EXTENT,
+            far: 4.0 * SHADOW_EXTENT,
+            ..Default::default()
+        };
+    }
+}
+
+/// Plugin for the shadows cast by the buildables on the plate, a depth cue to judge positions on
+/// the tilted plate. The quality is set with the `graphics.shadows` config.
+pub struct ShadowsPlugin;
+
+impl Plugin for ShadowsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DirectionalLightShadowMap {
+            size: ShadowQuality::default().shadow_map_size().unwrap_or(512),
+        })
+        .add_system(apply_shadow_quality);
+    }
+}
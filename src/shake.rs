@@ -0,0 +1,191 @@
+use bevy::{prelude::*, render::camera::PerspectiveProjection};
+
+use crate::{game::GameEvent, serialize::BuildableRegistry, AppState, Config};
+
+/// Weight from which a placement shakes the screen.
+const HEAVY_WEIGHT: f32 = 2.0;
+
+/// Trauma added by a heavy placement per unit of weight of the buildable.
+const PLACE_TRAUMA_PER_WEIGHT: f32 = 0.15;
+
+/// Trauma added when the plate topples.
+const TOPPLE_TRAUMA: f32 = 0.8;
+
+/// Trauma lost per second.
+const TRAUMA_DECAY: f32 = 1.2;
+
+/// Offset of the camera at full trauma, in its local space.
+const MAX_OFFSET: f32 = 0.12;
+
+/// Roll of the camera at full trauma, in radians.
+const MAX_ROLL: f32 = 0.03;
+
+/// Frequency of the shake, in radians per second.
+const SHAKE_FREQUENCY: f32 = 40.0;
+
+/// Resource centralizing the screen shake. Gameplay feedback adds trauma, which decays over time,
+/// and the camera shakes with the square of the trauma so small amounts stay subtle.
+///
+/// The shake is scaled by the `graphics.screen_shake` config, and disabled with the
+/// `reduce_motion` config.
+#[derive(Debug, Default)]
+pub struct ScreenShake {
+    /// Current trauma, from 0 for no shake to 1.
+    trauma: f32,
+    /// Time the shake has been playing, in seconds, driving the shake pattern.
+    time: f32,
+}
+
+impl ScreenShake {
+    pub fn new() -> Self {
+        ScreenShake::default()
+    }
+
+    /// Add some trauma, up to a full shake.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma.max(0.0)).min(1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Stop shaking immediately.
+    pub fn clear(&mut self) {
+        self.trauma = 0.0;
+        self.time = 0.0;
+    }
+
+    /// Advance the shake pattern and decay the trauma.
+    fn tick(&mut self, dt: f32) {
+        if self.trauma > 0.0 {
+            self.time += dt;
+            self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        } else {
+            self.time = 0.0;
+        }
+    }
+
+    /// Local offset of the camera for the current shake, with the given intensity scale.
+    fn offset(&self, scale: f32) -> Transform {
+        let shake = self.trauma * self.trauma * scale;
+        if shake <= 0.0 {
+            return Transform::identity();
+        }
+        // Sum of unrelated sines per axis, cheap and smooth enough for a short shake
+        let t = self.time * SHAKE_FREQUENCY;
+        let x = t.sin() + 0.5 * (t * 2.3 + 1.1).sin();
+        let y = (t * 1.3 + 0.7).sin() + 0.5 * (t * 2.9 + 2.3).sin();
+        let roll = (t * 0.9 + 1.9).sin();
+        Transform {
+            translation: Vec3::new(x, y, 0.0) * (MAX_OFFSET * shake / 1.5),
+            rotation: Quat::from_rotation_z(roll * MAX_ROLL * shake),
+            ..Default::default()
+        }
+    }
+}
+
+/// Transform of the camera before the shake, saved once the camera is spawned.
+#[derive(Component)]
+struct ShakeBase(Transform);
+
+/// Trauma added by the placement of a buildable of the given weight.
+pub fn placement_trauma(weight: f32) -> f32 {
+    if weight >= HEAVY_WEIGHT {
+        weight * PLACE_TRAUMA_PER_WEIGHT
+    } else {
+        0.0
+    }
+}
+
+/// Add trauma for the heavy placements and the topples.
+fn shake_on_impacts(
+    buildables: Res<BuildableRegistry>,
+    mut shake: ResMut<ScreenShake>,
+    mut ev_game: EventReader<GameEvent>,
+) {
+    for ev in ev_game.iter() {
+        match ev {
+            GameEvent::BuildablePlaced { buildable, .. } => {
+                if let Some(buildable) = buildables.get(*buildable) {
+                    shake.add_trauma(placement_trauma(buildable.weight()));
+                }
+            }
+            GameEvent::PlateToppled { .. } => shake.add_trauma(TOPPLE_TRAUMA),
+            _ => {}
+        }
+    }
+}
+
+/// Shake the scene camera around its base transform.
+fn apply_screen_shake(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<Config>,
+    mut shake: ResMut<ScreenShake>,
+    new_query: Query<(Entity, &Transform), (With<PerspectiveProjection>, Without<ShakeBase>)>,
+    mut query: Query<(&mut Transform, &ShakeBase)>,
+) {
+    for (entity, transform) in new_query.iter() {
+        commands.entity(entity).insert(ShakeBase(*transform));
+    }
+    shake.tick(time.delta_seconds());
+    let scale = if config.reduce_motion {
+        0.0
+    } else {
+        config.graphics.screen_shake
+    };
+    let offset = shake.offset(scale);
+    for (mut transform, base) in query.iter_mut() {
+        let shaken = base.0.mul_transform(offset);
+        if *transform != shaken {
+            *transform = shaken;
+        }
+    }
+}
+
+fn shake_cleanup(mut shake: ResMut<ScreenShake>) {
+    shake.clear();
+}
+
+/// Plugin for the [`ScreenShake`] of the scene camera.
+pub struct ScreenShakePlugin;
+
+impl Plugin for ScreenShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScreenShake::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(shake_on_impacts.before("screen_shake"))
+                    .with_system(apply_screen_shake.label("screen_shake")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(shake_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trauma() {
+        assert_eq!(placement_trauma(1.0), 0.0);
+        assert!(placement_trauma(3.0) > placement_trauma(HEAVY_WEIGHT));
+
+        let mut shake = ScreenShake::new();
+        assert_eq!(shake.offset(1.0), Transform::identity());
+        shake.add_trauma(0.7);
+        shake.add_trauma(0.7);
+        assert_eq!(shake.trauma(), 1.0);
+
+        shake.tick(0.1);
+        assert!(shake.trauma() < 1.0);
+        assert_ne!(shake.offset(1.0), Transform::identity());
+        // No shake with the intensity scaled down to zero, like with the reduced motion
+        assert_eq!(shake.offset(0.0), Transform::identity());
+
+        shake.tick(1.0 / TRAUMA_DECAY);
+        assert_eq!(shake.trauma(), 0.0);
+        assert_eq!(shake.offset(1.0), Transform::identity());
+    }
+}
@@ -0,0 +1,323 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cheats::Cheats,
+    coop::Coop,
+    inventory::{Inventory, RegenerateInventoryUiEvent, SelectSlot, UpdateInventorySlots},
+    journal::{LevelJournal, LevelOpArchive},
+    rules::Rules,
+    scores::ScoreTracker,
+    serialize::{BuildableRegistry, LevelDesc, Levels},
+    spawn_buildable, storage, AppState, Cursor, Grid, Level,
+};
+
+/// Save file of the quick save, in the profile storage.
+pub const QUICK_SAVE_FILE: &str = "quicksave.json";
+
+/// Snapshot of a level in progress, capturing the journal of the level, which determines the grid
+/// content and the inventory, and the cursor.
+#[derive(Debug, Clone)]
+pub struct LevelSnapshot {
+    /// Index of the level the snapshot was taken in.
+    level_index: usize,
+    /// Operations applied to the level.
+    journal: LevelJournal,
+    /// Index of the selected inventory slot.
+    selected_index: usize,
+    /// Cursor position, in cell coordinates.
+    cursor_pos: IVec2,
+    /// Were developer cheats used in the level before the snapshot was taken?
+    cheated: bool,
+}
+
+/// Serialized form of a [`LevelSnapshot`], referencing levels and buildables by name.
+#[derive(Debug, Serialize, Deserialize)]
+struct LevelSnapshotArchive {
+    level: String,
+    ops: Vec<LevelOpArchive>,
+    selected_slot: usize,
+    cursor_pos: IVec2,
+    #[serde(default)]
+    cheated: bool,
+}
+
+impl LevelSnapshot {
+    /// Capture the state of the level in progress.
+    pub fn capture(
+        level_index: usize,
+        journal: &LevelJournal,
+        inventory: &Inventory,
+        cursor_pos: IVec2,
+        cheated: bool,
+    ) -> Self {
+        LevelSnapshot {
+            level_index,
+            journal: journal.clone(),
+            selected_index: inventory.snapshot().selected_index,
+            cursor_pos,
+            cheated,
+        }
+    }
+
+    /// Index of the level the snapshot was taken in.
+    pub fn level_index(&self) -> usize {
+        self.level_index
+    }
+
+    /// Number of buildables placed on the grid.
+    pub fn placement_count(&self) -> usize {
+        self.journal.placements().len()
+    }
+
+    /// Cursor position, in cell coordinates.
+    pub fn cursor_pos(&self) -> IVec2 {
+        self.cursor_pos
+    }
+
+    /// Were developer cheats used in the level before the snapshot was taken?
+    pub fn is_cheated(&self) -> bool {
+        self.cheated
+    }
+
+    /// Restore the grid content, the inventories, and the journal by replaying the journal of the
+    /// snapshot, respawning the buildables under the given root entity. The cursors are left to
+    /// the caller.
+    pub fn restore(
+        &self,
+        commands: &mut Commands,
+        grid: &mut Grid,
+        spawn_root_entity: Entity,
+        buildables: &BuildableRegistry,
+        inventory: &mut Inventory,
+        partner_inventory: &mut Inventory,
+        journal: &mut LevelJournal,
+        level_desc: &LevelDesc,
+    ) {
+        let state = self.journal.replay(level_desc);
+        grid.clear(Some(commands));
+        for pos in state.holes.iter() {
+            grid.break_tile(pos, None);
+        }
+        for (pos, bref, _) in state.placements.iter() {
+            if let Some(buildable) = buildables.get(*bref) {
+                spawn_buildable(commands, grid, spawn_root_entity, pos, *bref, buildable);
+            }
+        }
+        let mut snapshot = state.inventory.snapshot();
+        snapshot.selected_index = self.selected_index;
+        inventory.restore(&snapshot, level_desc);
+        // The selected slot of the partner is not captured
+        *partner_inventory = state.partner_inventory;
+        if let Some(index) = partner_inventory.find_non_empty_slot_index() {
+            partner_inventory.select_slot(&SelectSlot::Index(index as usize));
+        }
+        journal.restore(&self.journal);
+    }
+
+    fn to_archive(
+        &self,
+        levels: &Levels,
+        buildables: &BuildableRegistry,
+    ) -> Option<LevelSnapshotArchive> {
+        Some(LevelSnapshotArchive {
+            level: levels.get(self.level_index)?.name.clone(),
+            ops: self.journal.to_archive(buildables)?,
+            selected_slot: self.selected_index,
+            cursor_pos: self.cursor_pos,
+            cheated: self.cheated,
+        })
+    }
+
+    fn from_archive(
+        archive: LevelSnapshotArchive,
+        levels: &Levels,
+        buildables: &BuildableRegistry,
+    ) -> Option<LevelSnapshot> {
+        let (level_index, _) = levels.by_name(&archive.level)?;
+        Some(LevelSnapshot {
+            level_index,
+            journal: LevelJournal::from_archive(&archive.ops, buildables)?,
+            selected_index: archive.selected_slot,
+            cursor_pos: archive.cursor_pos,
+            cheated: archive.cheated,
+        })
+    }
+}
+
+/// Resource holding the last quick save, if any.
+pub struct QuickSave {
+    snapshot: Option<LevelSnapshot>,
+}
+
+impl QuickSave {
+    pub fn new() -> Self {
+        QuickSave { snapshot: None }
+    }
+
+    /// Write the snapshot to the profile storage, so it survives restarting the game.
+    fn write_to_disk(&self, levels: &Levels, buildables: &BuildableRegistry) {
+        if let Some(snapshot) = &self.snapshot {
+            write_snapshot(QUICK_SAVE_FILE, snapshot, levels, buildables);
+        }
+    }
+
+    /// Read the snapshot from the profile storage, if any.
+    fn read_from_disk(levels: &Levels, buildables: &BuildableRegistry) -> Option<LevelSnapshot> {
+        read_snapshot(QUICK_SAVE_FILE, levels, buildables)
+    }
+}
+
+/// Write a snapshot to a save file of the profile storage, replacing any previous one.
+pub fn write_snapshot(
+    file: &str,
+    snapshot: &LevelSnapshot,
+    levels: &Levels,
+    buildables: &BuildableRegistry,
+) {
+    let archive = match snapshot.to_archive(levels, buildables) {
+        Some(archive) => archive,
+        None => return,
+    };
+    let result = serde_json::to_string(&archive)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| storage::write(file, &json).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        let location = storage::location(file);
+        warn!("Failed to write level snapshot to '{}': {}", location, err);
+    }
+}
+
+/// Read a snapshot from a save file of the profile storage, if any. Snapshots of levels or
+/// buildables not in the game data anymore are ignored.
+pub fn read_snapshot(
+    file: &str,
+    levels: &Levels,
+    buildables: &BuildableRegistry,
+) -> Option<LevelSnapshot> {
+    let json = storage::read(file)?;
+    let archive: LevelSnapshotArchive = match serde_json::from_str(&json) {
+        Ok(archive) => archive,
+        Err(err) => {
+            let location = storage::location(file);
+            warn!("Failed to parse level snapshot '{}': {}", location, err);
+            return None;
+        }
+    };
+    LevelSnapshot::from_archive(archive, levels, buildables)
+}
+
+/// Capture the current level state on F5.
+fn quick_save_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    inventory: Res<Inventory>,
+    journal: Res<LevelJournal>,
+    cheats: Res<Cheats>,
+    mut quick_save: ResMut<QuickSave>,
+    query: Query<&Cursor>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let cursor = query.single();
+    if !cursor.enabled() || level.desc().is_none() {
+        return;
+    }
+    let snapshot = LevelSnapshot::capture(
+        level.index(),
+        &journal,
+        &inventory,
+        cursor.pos(),
+        cheats.is_used(),
+    );
+    debug!(
+        "Quick save: level #{} with {} placement(s)",
+        snapshot.level_index,
+        snapshot.placement_count()
+    );
+    quick_save.snapshot = Some(snapshot);
+    quick_save.write_to_disk(&levels, &buildables);
+}
+
+/// Restore the last quick save on F9, if it was taken in the current level. Disabled in hard mode.
+fn quick_load_system(
+    mut commands: Commands,
+    mut ev_update_slots: EventWriter<UpdateInventorySlots>,
+    mut ev_regen_ui: EventWriter<RegenerateInventoryUiEvent>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut grid: ResMut<Grid>,
+    level: Res<Level>,
+    levels: Res<Levels>,
+    buildables: Res<BuildableRegistry>,
+    rules: Res<Rules>,
+    mut inventory: ResMut<Inventory>,
+    mut coop: ResMut<Coop>,
+    mut journal: ResMut<LevelJournal>,
+    mut cheats: ResMut<Cheats>,
+    mut quick_save: ResMut<QuickSave>,
+    mut tracker: ResMut<ScoreTracker>,
+    mut query: Query<(&mut Cursor, &mut Transform, &mut Visibility)>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F9) || !rules.can_undo() {
+        return;
+    }
+    let (mut cursor, mut transform, mut visibility) = query.single_mut();
+    if !cursor.enabled() {
+        return;
+    }
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    if quick_save.snapshot.is_none() {
+        quick_save.snapshot = QuickSave::read_from_disk(&levels, &buildables);
+    }
+    let snapshot = match &quick_save.snapshot {
+        Some(snapshot) if snapshot.level_index == level.index() => snapshot,
+        _ => {
+            debug!("Quick load: no quick save for level #{}", level.index());
+            return;
+        }
+    };
+    debug!(
+        "Quick load: level #{} with {} placement(s)",
+        snapshot.level_index,
+        snapshot.placement_count()
+    );
+
+    // Respawn the buildables on the grid, then restore inventory and cursor
+    snapshot.restore(
+        &mut commands,
+        &mut grid,
+        cursor.spawn_root_entity,
+        &buildables,
+        &mut inventory,
+        coop.inventory_mut(),
+        &mut journal,
+        level_desc,
+    );
+    if snapshot.cheated {
+        cheats.set_used();
+    }
+    tracker.record_undo();
+    cursor.set_pos(snapshot.cursor_pos, &grid, &mut transform);
+    visibility.is_visible = !inventory.is_empty();
+    ev_regen_ui.send(RegenerateInventoryUiEvent);
+    ev_update_slots.send(UpdateInventorySlots);
+}
+
+/// Plugin to quick save (F5) and quick load (F9) the state of the level in progress.
+pub struct QuickSavePlugin;
+
+impl Plugin for QuickSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(QuickSave::new()).add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(quick_save_system)
+                .with_system(quick_load_system),
+        );
+    }
+}
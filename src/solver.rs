@@ -0,0 +1,494 @@
+use bevy::prelude::*;
+
+use crate::{
+    inventory::Inventory,
+    layout::GridLayout,
+    rules::Rules,
+    serialize::{BuildableId, BuildableRegistry, LevelDesc},
+    tilt::Pivot,
+    CursorId, Grid, Level, PlaceBuildableEvent,
+};
+
+/// Maximum number of partial placements explored before the search gives up.
+const SEARCH_BUDGET: u32 = 200_000;
+
+/// Search state of [`solve()`], shared by all the nodes of the search tree.
+struct Search<'a> {
+    grid: &'a Grid,
+    buildables: &'a BuildableRegistry,
+    /// Free cells of the grid, with their grid coordinates and lever arm about the pivot.
+    cells: Vec<(IVec2, Vec2)>,
+    victory_margin: f32,
+    /// Largest lever arm of a free cell.
+    max_radius: f32,
+    /// Number of nodes left to explore.
+    budget: u32,
+    /// Placements of the branch being explored.
+    steps: Vec<PlaceBuildableEvent>,
+}
+
+impl<'a> Search<'a> {
+    fn weight(&self, bref: BuildableId) -> f32 {
+        self.buildables
+            .get(bref)
+            .map_or(0.0, |b| b.weight() * self.grid.weight_scale())
+    }
+
+    fn weight_variance(&self, bref: BuildableId) -> f32 {
+        self.buildables
+            .get(bref)
+            .map_or(0.0, |b| b.weight_variance() * self.grid.weight_scale())
+    }
+
+    /// Explore the placements of the remaining buildables, given the current offset of the center
+    /// of gravity from the pivot and the free cells used so far. Returns `true` once a winning layout is found, with
+    /// its placements in `steps`.
+    ///
+    /// The offset is computed with the nominal weights, and `uncertainty` bounds how far the
+    /// weight variance of the buildables placed so far can move the center of gravity from it. A
+    /// layout only wins if it's balanced in the worst case.
+    ///
+    /// To avoid exploring the same layout several times, the buildables of a same type are placed
+    /// in increasing cell order, recorded in `last_cell`.
+    fn search(
+        &mut self,
+        inventory: &Inventory,
+        cog_offset: Vec2,
+        uncertainty: f32,
+        used: &mut [bool],
+        last_cell: &mut Vec<(BuildableId, usize)>,
+    ) -> bool {
+        if inventory.is_empty() {
+            return cog_offset.length() + uncertainty < self.victory_margin;
+        }
+        if self.budget == 0 {
+            return false;
+        }
+        self.budget -= 1;
+
+        // Prune branches which cannot bring the center of gravity back inside the margin. This
+        // is only valid if no delivery will add more buildables later.
+        if !inventory.has_pending_deliveries()
+            && cog_offset.length() + uncertainty
+                - remaining_weight(inventory, self.buildables)
+                    * self.grid.weight_scale()
+                    * self.max_radius
+                >= self.victory_margin
+        {
+            return false;
+        }
+
+        // Try the heaviest buildables first, they constrain the layout the most
+        let mut brefs: Vec<_> = inventory
+            .slots()
+            .iter()
+            .filter(|slot| !slot.is_empty())
+            .map(|slot| slot.bref())
+            .collect();
+        brefs.sort_by(|a, b| self.weight(*b).abs().total_cmp(&self.weight(*a).abs()));
+
+        for bref in brefs {
+            let weight = self.weight(bref);
+            let variance = self.weight_variance(bref);
+            let first_cell = last_cell
+                .iter()
+                .rev()
+                .find(|(b, _)| *b == bref)
+                .map_or(0, |&(_, index)| index + 1);
+            // Try first the cells bringing the center of gravity closest to the pivot, skipping
+            // the fragile tiles the heaviest instance of the buildable would break
+            let mut candidates: Vec<_> = (first_cell..self.cells.len())
+                .filter(|&index| {
+                    !used[index] && weight + variance <= self.grid.capacity(&self.cells[index].0)
+                })
+                .map(|index| {
+                    let lever = self.cells[index].1;
+                    (
+                        index,
+                        cog_offset + weight * lever,
+                        uncertainty + variance * lever.length(),
+                    )
+                })
+                .collect();
+            candidates.sort_by(|a, b| (a.1.length() + a.2).total_cmp(&(b.1.length() + b.2)));
+
+            for (index, next_cog_offset, next_uncertainty) in candidates {
+                let mut next_inventory = inventory.clone();
+                if !next_inventory.take_item(bref) {
+                    break;
+                }
+                for delivery in next_inventory.record_placement() {
+                    next_inventory.deliver(&delivery);
+                }
+                used[index] = true;
+                last_cell.push((bref, index));
+                self.steps.push(PlaceBuildableEvent {
+                    pos: self.cells[index].0,
+                    bref,
+                    cursor: CursorId::Player,
+                });
+                if self.search(
+                    &next_inventory,
+                    next_cog_offset,
+                    next_uncertainty,
+                    used,
+                    last_cell,
+                ) {
+                    return true;
+                }
+                self.steps.pop();
+                last_cell.pop();
+                used[index] = false;
+                if self.budget == 0 {
+                    return false;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Free cells of the grid, with their grid coordinates and lever arm about the pivot. The
+/// conveyor tiles are left out, the buildables placed on them shift at a time the search cannot
+/// predict.
+fn free_cells(grid: &Grid, pivot: &Pivot) -> Vec<(IVec2, Vec2)> {
+    let min = grid.min_pos();
+    let max = grid.max_pos();
+    let mut cells = vec![];
+    for j in min.y..max.y + 1 {
+        for i in min.x..max.x + 1 {
+            let pos = IVec2::new(i, j);
+            if grid.can_spawn_item(&pos) && grid.conveyor(&pos).is_none() {
+                cells.push((pos, pivot.lever(grid.lever_pos(&pos))));
+            }
+        }
+    }
+    cells
+}
+
+/// Largest lever arm of a free cell.
+fn max_radius(cells: &[(IVec2, Vec2)]) -> f32 {
+    cells
+        .iter()
+        .map(|(_, lever)| lever.length())
+        .fold(0.0, f32::max)
+}
+
+/// Total weight of the buildables left in the inventory, not counting later deliveries.
+pub fn remaining_weight(inventory: &Inventory, buildables: &BuildableRegistry) -> f32 {
+    inventory
+        .slots()
+        .iter()
+        .map(|slot| {
+            slot.count() as f32
+                * buildables
+                    .get(slot.bref())
+                    .map_or(0.0, |b| b.weight().abs())
+        })
+        .sum()
+}
+
+/// Estimate of the least total weight still needed to bring the center of gravity of the grid
+/// back within the victory margin, if it were all placed on the free cell farthest from the
+/// pivot. This is the same bound the search of [`solve()`] prunes with: with less weight left
+/// the plate cannot be balanced anymore, but more weight does not guarantee a solution. The
+/// weight is that of the buildables in the inventory, before the weather of the level.
+pub fn min_balancing_weight(grid: &Grid, pivot: &Pivot, victory_margin: f32) -> f32 {
+    let excess = grid.calc_balance_offset(1.0, pivot).length() - victory_margin;
+    if excess < 0.0 {
+        return 0.0;
+    }
+    let max_radius = max_radius(&free_cells(grid, pivot));
+    if max_radius > 0.0 {
+        excess / max_radius / grid.weight_scale()
+    } else {
+        f32::INFINITY
+    }
+}
+
+/// Find placements of all the buildables of the inventory, including later deliveries, which
+/// balance the plate within the victory margin, whatever the weights of the buildables with a
+/// weight variance turn out to be. Returns the placements in order, or `None` if no solution was
+/// found within the search budget.
+pub fn solve(
+    grid: &Grid,
+    inventory: &Inventory,
+    buildables: &BuildableRegistry,
+    pivot: &Pivot,
+    victory_margin: f32,
+) -> Option<Vec<PlaceBuildableEvent>> {
+    let cells = free_cells(grid, pivot);
+    let max_radius = max_radius(&cells);
+    let mut search = Search {
+        grid,
+        buildables,
+        victory_margin,
+        max_radius,
+        budget: SEARCH_BUDGET,
+        steps: vec![],
+        cells,
+    };
+    let mut used = vec![false; search.cells.len()];
+    // The balance factor does not affect the center of gravity offset
+    let cog_offset = grid.calc_balance_offset(1.0, pivot);
+    if search.search(inventory, cog_offset, 0.0, &mut used, &mut vec![]) {
+        trace!(
+            "Solver: found {} placements, {} nodes left",
+            search.steps.len(),
+            search.budget
+        );
+        Some(search.steps)
+    } else {
+        debug!("Solver: no solution found, {} nodes left", search.budget);
+        None
+    }
+}
+
+/// Find placements of the whole inventory of a level, including its deliveries, which balance its
+/// empty plate within the victory margin. The seesaw levels are not supported, the search only
+/// knows about a single plate.
+pub fn solve_level(
+    level_desc: &LevelDesc,
+    buildables: &BuildableRegistry,
+    victory_margin: f32,
+) -> Option<Vec<PlaceBuildableEvent>> {
+    if level_desc.seesaw.is_some() {
+        return None;
+    }
+    let layout = GridLayout {
+        size: level_desc.grid_size,
+        placements: vec![],
+    };
+    let mut grid = Grid::from_layout(&layout, buildables).ok()?;
+    if let Some(terrain) = &level_desc.terrain {
+        grid.set_terrain(terrain.heights.clone(), terrain.lever_arm);
+    }
+    if !level_desc.fragile.is_empty() {
+        grid.set_capacities(&level_desc.fragile);
+    }
+    if !level_desc.conveyors.is_empty() {
+        grid.set_conveyors(&level_desc.conveyors);
+    }
+    grid.set_weight_scale(level_desc.weather.weight_scale());
+    let mut inventory = Inventory::new();
+    inventory.reset_from_level(level_desc);
+    solve(
+        &grid,
+        &inventory,
+        buildables,
+        &level_desc.pivot,
+        victory_margin,
+    )
+}
+
+/// Find placements of the rest of the inventory which balance the plate of the level being
+/// played, from the resources of the app world. Used to drive the game from the tests.
+pub fn solve_current_level(world: &World) -> Option<Vec<PlaceBuildableEvent>> {
+    let level_desc = world.get_resource::<Level>()?.desc()?;
+    let victory_margin = world.get_resource::<Rules>()?.victory_margin(level_desc);
+    solve(
+        world.get_resource::<Grid>()?,
+        world.get_resource::<Inventory>()?,
+        world.get_resource::<BuildableRegistry>()?,
+        &level_desc.pivot,
+        victory_margin,
+    )
+}
+
+/// Find the single placement of a buildable of the inventory which brings the center of gravity
+/// closest to the pivot, if any cell is free.
+pub fn best_placement(
+    grid: &Grid,
+    inventory: &Inventory,
+    buildables: &BuildableRegistry,
+    pivot: &Pivot,
+) -> Option<PlaceBuildableEvent> {
+    let cog_offset = grid.calc_balance_offset(1.0, pivot);
+    let min = grid.min_pos();
+    let max = grid.max_pos();
+    let mut best: Option<(PlaceBuildableEvent, f32)> = None;
+    for slot in inventory.slots().iter().filter(|slot| !slot.is_empty()) {
+        let weight = buildables
+            .get(slot.bref())
+            .map_or(0.0, |b| b.weight() * grid.weight_scale());
+        for j in min.y..max.y + 1 {
+            for i in min.x..max.x + 1 {
+                let pos = IVec2::new(i, j);
+                if !grid.can_spawn_item(&pos)
+                    || weight > grid.capacity(&pos)
+                    || grid.conveyor(&pos).is_some()
+                {
+                    continue;
+                }
+                let dist = (cog_offset + weight * pivot.lever(grid.lever_pos(&pos))).length();
+                if best.as_ref().is_none_or(|(_, best_dist)| dist < *best_dist) {
+                    best = Some((
+                        PlaceBuildableEvent {
+                            pos,
+                            bref: slot.bref(),
+                            cursor: CursorId::Player,
+                        },
+                        dist,
+                    ));
+                }
+            }
+        }
+    }
+    best.map(|(placement, _)| placement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        conveyor::ConveyorDesc,
+        fragile::FragileTileDesc,
+        inventory::Buildable,
+        layout::{GridLayout, LayoutPlacement},
+        tilt::KnifeEdge,
+    };
+
+    fn registry(weights: &[(&str, f32)]) -> BuildableRegistry {
+        let mut buildables = BuildableRegistry::new();
+        for &(name, weight) in weights {
+            buildables.register(name, Buildable::without_assets(name, weight));
+        }
+        buildables
+    }
+
+    fn empty_grid(size: IVec2, buildables: &BuildableRegistry) -> Grid {
+        let layout = GridLayout {
+            size,
+            placements: vec![],
+        };
+        Grid::from_layout(&layout, buildables).unwrap()
+    }
+
+    #[test]
+    fn solve_balances_plate() {
+        let buildables = registry(&[("hut", 1.0), ("tower", 2.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let tower = buildables.id("tower").unwrap();
+        let grid = empty_grid(IVec2::new(5, 5), &buildables);
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 2);
+        inventory.add_items(tower, 1);
+
+        let steps = solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).unwrap();
+        assert_eq!(steps.len(), 3);
+        let cog_offset = steps.iter().fold(Vec2::ZERO, |cog, step| {
+            cog + buildables.get(step.bref).unwrap().weight() * grid.fpos(&step.pos)
+        });
+        assert!(cog_offset.length() < 0.1);
+    }
+
+    #[test]
+    fn solve_impossible() {
+        // A single off-center buildable on an even grid can never be balanced
+        let buildables = registry(&[("hut", 1.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let grid = empty_grid(IVec2::new(2, 2), &buildables);
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 1);
+
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_none());
+        assert!(best_placement(&grid, &inventory, &buildables, &Pivot::default()).is_some());
+    }
+
+    #[test]
+    fn weight_budget() {
+        let buildables = registry(&[("hut", 1.0), ("tower", 2.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let grid = empty_grid(IVec2::new(3, 3), &buildables);
+        assert_eq!(min_balancing_weight(&grid, &Pivot::default(), 0.1), 0.0);
+
+        // A tower on the edge needs a counterweight of at least (2 - 0.1) / sqrt(2) in a corner
+        let layout = GridLayout {
+            size: IVec2::new(3, 3),
+            placements: vec![LayoutPlacement {
+                pos: IVec2::new(1, 0),
+                buildable: "tower".to_owned(),
+                weight: 2.0,
+            }],
+        };
+        let grid = Grid::from_layout(&layout, &buildables).unwrap();
+        let needed = min_balancing_weight(&grid, &Pivot::default(), 0.1);
+        assert!((needed - 1.9 / 2f32.sqrt()).abs() < 1e-5);
+
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 1);
+        assert_eq!(remaining_weight(&inventory, &buildables), 1.0);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_none());
+        inventory.add_items(hut, 1);
+        assert_eq!(remaining_weight(&inventory, &buildables), 2.0);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_some());
+    }
+
+    #[test]
+    fn solve_weight_variance() {
+        // Two huts on opposite cells balance on paper, but not in the worst case
+        let mut buildables = registry(&[("hut", 1.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let grid = empty_grid(IVec2::new(3, 1), &buildables);
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 2);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_some());
+
+        buildables.get_mut(hut).unwrap().set_weight_variance(0.2);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_none());
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.5).is_some());
+    }
+
+    #[test]
+    fn solve_knife_edge() {
+        // Same as the impossible plate, but on a knife edge only the column matters
+        let buildables = registry(&[("hut", 1.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let grid = empty_grid(IVec2::new(3, 2), &buildables);
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 1);
+        let pivot = Pivot {
+            offset: Vec2::ZERO,
+            knife_edge: Some(KnifeEdge::Y),
+        };
+
+        let steps = solve(&grid, &inventory, &buildables, &pivot, 0.1).unwrap();
+        assert_eq!(steps[0].pos.x, 0);
+    }
+    #[test]
+    fn solve_fragile() {
+        // The only balanced cell is too fragile for the hut
+        let buildables = registry(&[("hut", 1.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let mut grid = empty_grid(IVec2::new(3, 3), &buildables);
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 1);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_some());
+
+        grid.set_capacities(&[FragileTileDesc {
+            pos: IVec2::ZERO,
+            capacity: 0.5,
+        }]);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_none());
+        let best = best_placement(&grid, &inventory, &buildables, &Pivot::default()).unwrap();
+        assert_ne!(best.pos, IVec2::ZERO);
+    }
+
+    #[test]
+    fn solve_conveyor() {
+        // The only balanced cell would shift the hut away
+        let buildables = registry(&[("hut", 1.0)]);
+        let hut = buildables.id("hut").unwrap();
+        let mut grid = empty_grid(IVec2::new(3, 3), &buildables);
+        let mut inventory = Inventory::new();
+        inventory.add_items(hut, 1);
+        grid.set_conveyors(&[ConveyorDesc {
+            pos: IVec2::ZERO,
+            direction: IVec2::X,
+            interval: 1.0,
+        }]);
+        assert!(solve(&grid, &inventory, &buildables, &Pivot::default(), 0.1).is_none());
+        let best = best_placement(&grid, &inventory, &buildables, &Pivot::default()).unwrap();
+        assert_ne!(best.pos, IVec2::ZERO);
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+
+use crate::{
+    balance::BalanceState,
+    boot::UiResources,
+    cinematic::Hud,
+    game::{run_if_playing, GameplaySystem},
+    inventory::Inventory,
+    AppState, CheckLevelResultEvent, Level, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Resource tracking for how long the plate has been balanced, in levels cleared by keeping the
+/// plate balanced for some time instead of placing all the buildables.
+#[derive(Debug, Default)]
+pub struct Stabilizer {
+    /// Time in seconds the plate needs to stay balanced, if the level has continuous victory.
+    duration: Option<f32>,
+    /// Time in seconds the plate has been balanced so far.
+    elapsed: f32,
+}
+
+impl Stabilizer {
+    pub fn new() -> Self {
+        Stabilizer::default()
+    }
+
+    /// Time in seconds left before the level is cleared, if the plate is currently stabilizing.
+    pub fn remaining(&self) -> Option<f32> {
+        match self.duration {
+            Some(duration) if self.elapsed > 0.0 => Some((duration - self.elapsed).max(0.0)),
+            _ => None,
+        }
+    }
+}
+
+/// Marker for the text of the stabilizing countdown.
+#[derive(Component)]
+struct StabilizeText;
+
+fn spawn_stabilize_text(mut commands: Commands, ui_resources: Res<UiResources>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(60.0),
+                    left: Val::Percent(45.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: ui_resources.title_font(),
+                    font_size: 32.0,
+                    color: Color::rgb_u8(160, 230, 160),
+                },
+                TextAlignment::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Name::new("StabilizeText"))
+        .insert(Hud)
+        .insert(StabilizeText);
+}
+
+/// Restart the countdown whenever the level starts or restarts.
+fn reset_stabilizer(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    level: Res<Level>,
+    mut stabilizer: ResMut<Stabilizer>,
+) {
+    let reset = ev_reset_plate.iter().last().is_some();
+    let restart = ev_restart.iter().last().is_some();
+    if reset || restart {
+        stabilizer.duration = level
+            .desc()
+            .and_then(|level_desc| level_desc.stabilize_time);
+        stabilizer.elapsed = 0.0;
+    }
+}
+
+/// Request the level result check once the plate stayed balanced within the victory margin long
+/// enough, even with buildables left. The check on the last placement still applies.
+fn stabilize_system(
+    time: Res<Time>,
+    level: Res<Level>,
+    balance: Res<BalanceState>,
+    inventory: Res<Inventory>,
+    mut stabilizer: ResMut<Stabilizer>,
+    mut ev_check_level: EventWriter<CheckLevelResultEvent>,
+) {
+    let duration = match (stabilizer.duration, level.desc()) {
+        (Some(duration), Some(_)) => {
+            // An empty plate is balanced, but not much of a city
+            let balanced =
+                inventory.placed_count() > 0 && !inventory.is_empty() && balance.is_balanced();
+            if !balanced {
+                stabilizer.elapsed = 0.0;
+                return;
+            }
+            duration
+        }
+        _ => return,
+    };
+    stabilizer.elapsed += time.delta_seconds();
+    if stabilizer.elapsed >= duration {
+        debug!("Plate stabilized for {:.1}s", stabilizer.elapsed);
+        stabilizer.elapsed = 0.0;
+        ev_check_level.send(CheckLevelResultEvent {});
+    }
+}
+
+/// Show the countdown while the plate is stabilizing.
+fn update_stabilize_text(
+    stabilizer: Res<Stabilizer>,
+    mut query: Query<(&mut Text, &mut Visibility), With<StabilizeText>>,
+) {
+    if !stabilizer.is_changed() {
+        return;
+    }
+    for (mut text, mut visibility) in query.iter_mut() {
+        match stabilizer.remaining() {
+            Some(remaining) => {
+                text.sections[0].value = format!("Stabilizing... {:.1}s", remaining);
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+}
+
+fn stabilize_cleanup(mut commands: Commands, query: Query<Entity, With<StabilizeText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the continuous victory of the levels with a `stabilize_time`, cleared once the
+/// plate stays balanced for that long, with a countdown on the HUD.
+pub struct StabilizePlugin;
+
+impl Plugin for StabilizePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Stabilizer::new())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_stabilize_text))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Reset)
+                    .after(GameplaySystem::Input)
+                    .with_system(reset_stabilizer),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .after(GameplaySystem::Balance)
+                    .before(GameplaySystem::VictoryCheck)
+                    .with_system(stabilize_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(update_stabilize_text),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(stabilize_cleanup));
+    }
+}
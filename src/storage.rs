@@ -0,0 +1,252 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        RwLock,
+    },
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::paths;
+
+/// Folder the named profiles are stored in on native platforms, relative to the data folder. The
+/// default profile is stored in the data folder itself. See [`paths::data_dir()`].
+#[cfg(not(target_arch = "wasm32"))]
+const PROFILES_DIR: &str = "profiles";
+
+/// Prefix of the keys of the browser local storage, on the web.
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY_PREFIX: &str = "libracity";
+
+/// Suffix of the keys of the browser local storage holding the modification time of a save file,
+/// on the web.
+#[cfg(target_arch = "wasm32")]
+const MODIFIED_KEY_SUFFIX: &str = ".modified";
+
+/// Name of the active profile the save files are scoped to, or `None` for the default profile.
+/// The save files are stored in the data folder on native platforms, and in the browser local
+/// storage on the web.
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Number of save files written since launch.
+static WRITE_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// Names of devices reserved by Windows, which can't be used as file or folder names.
+const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+/// Is the name reserved by Windows, like `CON` or `com1`?
+fn is_reserved_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    if RESERVED_NAMES.contains(&upper.as_str()) {
+        return true;
+    }
+    match upper
+        .strip_prefix("COM")
+        .or_else(|| upper.strip_prefix("LPT"))
+    {
+        Some(digit) => matches!(digit.as_bytes(), [b'1'..=b'9']),
+        None => false,
+    }
+}
+
+/// Is the name valid for a profile? Profile names are used as folder names, so are restricted to
+/// letters, digits, spaces, dashes, and underscores, and can't be a name reserved by Windows.
+pub fn is_valid_profile_name(name: &str) -> bool {
+    let name_len = name.chars().count();
+    (1..=24).contains(&name_len)
+        && name.trim() == name
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+        && !is_reserved_name(name)
+}
+
+/// Scope the save files to a profile, or to the default profile if `None`.
+pub fn set_profile(profile: Option<&str>) {
+    debug_assert!(profile.is_none_or(is_valid_profile_name));
+    *PROFILE.write().unwrap() = profile.map(str::to_owned);
+}
+
+/// Name of the active profile, or `None` for the default profile.
+pub fn profile() -> Option<String> {
+    PROFILE.read().unwrap().clone()
+}
+
+/// Number of save files written since launch, for the cloud sync to tell when to push them.
+pub fn write_count() -> u32 {
+    WRITE_COUNT.load(Ordering::Relaxed)
+}
+
+/// Location of a save file of the active profile, for display.
+pub fn location(name: &str) -> String {
+    locate(PROFILE.read().unwrap().as_deref(), name)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn locate(profile: Option<&str>, name: &str) -> String {
+    let path = match profile {
+        Some(profile) => paths::data_file(PROFILES_DIR).join(profile).join(name),
+        None => paths::data_file(name),
+    };
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn locate(profile: Option<&str>, name: &str) -> String {
+    match profile {
+        Some(profile) => format!("{}.{}.{}", STORAGE_KEY_PREFIX, profile, name),
+        None => format!("{}.{}", STORAGE_KEY_PREFIX, name),
+    }
+}
+
+/// Read a save file of the active profile, if it exists.
+pub fn read(name: &str) -> Option<String> {
+    read_at(&location(name))
+}
+
+/// Write a save file of the active profile, replacing its content.
+pub fn write(name: &str, content: &str) -> io::Result<()> {
+    WRITE_COUNT.fetch_add(1, Ordering::Relaxed);
+    write_at(&location(name), content)
+}
+
+/// Remove a save file of the active profile, if it exists.
+pub fn remove(name: &str) -> io::Result<()> {
+    remove_at(&location(name))
+}
+
+/// Last modification time of a save file of the active profile, in seconds since the Unix epoch,
+/// if it exists.
+pub fn modified(name: &str) -> Option<u64> {
+    modified_at(&location(name))
+}
+
+/// Append a line to a save file of the active profile, creating it if needed.
+pub fn append_line(name: &str, line: &str) -> io::Result<()> {
+    WRITE_COUNT.fetch_add(1, Ordering::Relaxed);
+    append_line_at(&location(name), line)
+}
+
+/// Read a file shared by all the profiles, if it exists.
+pub fn read_shared(name: &str) -> Option<String> {
+    read_at(&locate(None, name))
+}
+
+/// Write a file shared by all the profiles, replacing its content.
+pub fn write_shared(name: &str, content: &str) -> io::Result<()> {
+    write_at(&locate(None, name), content)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_at(path: &str) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_at(path: &str, content: &str) -> io::Result<()> {
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, content)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_at(path: &str) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn modified_at(path: &str) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let elapsed = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(elapsed.as_secs())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn append_line_at(path: &str, line: &str) -> io::Result<()> {
+    use std::io::Write;
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> io::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no local storage"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_at(key: &str) -> Option<String> {
+    local_storage().ok()?.get_item(key).ok().flatten()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_at(key: &str, content: &str) -> io::Result<()> {
+    let storage = local_storage()?;
+    let modified = (js_sys::Date::now() / 1000.0) as u64;
+    storage
+        .set_item(key, content)
+        .and_then(|()| {
+            let modified_key = format!("{}{}", key, MODIFIED_KEY_SUFFIX);
+            storage.set_item(&modified_key, &modified.to_string())
+        })
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "local storage is full"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn remove_at(key: &str) -> io::Result<()> {
+    let storage = local_storage()?;
+    storage
+        .remove_item(key)
+        .and_then(|()| storage.remove_item(&format!("{}{}", key, MODIFIED_KEY_SUFFIX)))
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "local storage unavailable"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn modified_at(key: &str) -> Option<u64> {
+    read_at(&format!("{}{}", key, MODIFIED_KEY_SUFFIX))?
+        .parse()
+        .ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn append_line_at(key: &str, line: &str) -> io::Result<()> {
+    let mut content = read_at(key).unwrap_or_default();
+    content.push_str(line);
+    content.push('\n');
+    write_at(key, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names() {
+        assert!(is_valid_profile_name("Alice"));
+        assert!(is_valid_profile_name("Player 2"));
+        assert!(is_valid_profile_name("test_run-3"));
+        assert!(!is_valid_profile_name(""));
+        assert!(!is_valid_profile_name(" Alice"));
+        assert!(!is_valid_profile_name("../saves"));
+        assert!(!is_valid_profile_name("a/b"));
+        assert!(!is_valid_profile_name(&"x".repeat(25)));
+        assert!(!is_valid_profile_name("CON"));
+        assert!(!is_valid_profile_name("nul"));
+        assert!(!is_valid_profile_name("Com1"));
+        assert!(!is_valid_profile_name("LPT9"));
+        assert!(is_valid_profile_name("COM10"));
+        assert!(is_valid_profile_name("LPT"));
+        assert!(is_valid_profile_name("Connor"));
+    }
+}
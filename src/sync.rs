@@ -0,0 +1,461 @@
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    assist::ASSIST_FILE,
+    config::Config,
+    daily::DAILY_FILE,
+    encyclopedia::ENCYCLOPEDIA_FILE,
+    ghost::REPLAYS_FILE,
+    hard::HARD_MODE_FILE,
+    playtime::PLAYTIME_FILE,
+    practice::PRACTICE_FILE,
+    profile::{ProfileChangedEvent, SETTINGS_FILE},
+    snapshot::QUICK_SAVE_FILE,
+    storage,
+    wardrobe::WARDROBE_FILE,
+    AppState,
+};
+
+/// Save files of the active profile synced with the remote storage. Append-only logs like the
+/// scores stay local to each device.
+const SYNCED_FILES: [&str; 10] = [
+    WARDROBE_FILE,
+    ASSIST_FILE,
+    ENCYCLOPEDIA_FILE,
+    REPLAYS_FILE,
+    QUICK_SAVE_FILE,
+    PRACTICE_FILE,
+    HARD_MODE_FILE,
+    SETTINGS_FILE,
+    DAILY_FILE,
+    PLAYTIME_FILE,
+];
+
+/// Remote folder of the save files of the default profile.
+const DEFAULT_PROFILE_FOLDER: &str = "default";
+
+/// Suffix of the backup of the copy of a save file which lost a conflict.
+const BACKUP_SUFFIX: &str = ".bak";
+
+/// Shortest time between two pushes of the save files written while playing, in seconds. The
+/// saves made in a row are pushed together.
+const PUSH_INTERVAL_SECS: f64 = 30.0;
+
+/// Timeout of a single HTTP request to the WebDAV server.
+#[cfg(all(feature = "cloud_sync", not(target_arch = "wasm32")))]
+const REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Copy of a save file in the remote storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSave {
+    /// Last modification time of the save file, in seconds since the Unix epoch.
+    pub modified: u64,
+    /// Content of the save file.
+    pub content: String,
+}
+
+/// Remote storage the save files are synced with, for the progress to follow the player between
+/// devices. Paths are relative to the root of the remote storage, like `Alice/wardrobe.json`.
+/// Calls may block, so are only made on launch, at most every [`PUSH_INTERVAL_SECS`] after a
+/// save, and on exit.
+pub trait SaveSync: Send + Sync + 'static {
+    /// Is there a remote storage to sync with at all?
+    fn is_enabled(&self) -> bool {
+        true
+    }
+
+    /// Fetch the remote copy of a save file, if any.
+    fn fetch(&self, path: &str) -> Result<Option<RemoteSave>, String>;
+
+    /// Replace the remote copy of a save file.
+    fn upload(&self, path: &str, save: &RemoteSave) -> Result<(), String>;
+}
+
+/// Default [`SaveSync`], keeping the save files local.
+pub struct NoSync;
+
+impl SaveSync for NoSync {
+    fn is_enabled(&self) -> bool {
+        false
+    }
+
+    fn fetch(&self, _path: &str) -> Result<Option<RemoteSave>, String> {
+        Ok(None)
+    }
+
+    fn upload(&self, _path: &str, _save: &RemoteSave) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// [`SaveSync`] storing each save file as a JSON [`RemoteSave`] in a WebDAV folder, one
+/// sub-folder per profile. Native builds use an HTTP client, and web builds the synchronous
+/// requests of the browser, which have no timeout.
+#[cfg(feature = "cloud_sync")]
+pub struct WebDavSync {
+    #[cfg(not(target_arch = "wasm32"))]
+    agent: ureq::Agent,
+    /// Base URL of the WebDAV folder.
+    url: String,
+    /// Value of the `Authorization` header of the requests, if any.
+    authorization: Option<String>,
+}
+
+#[cfg(feature = "cloud_sync")]
+impl WebDavSync {
+    pub fn new(url: &str, authorization: Option<String>) -> Self {
+        WebDavSync {
+            #[cfg(not(target_arch = "wasm32"))]
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+                .build(),
+            url: url.trim_end_matches('/').to_owned(),
+            authorization,
+        }
+    }
+
+    /// URL of a remote path.
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.url, path.replace(' ', "%20"))
+    }
+
+    /// Remote folder of a remote path, with a trailing slash.
+    fn folder(path: &str) -> String {
+        let folder = path.rsplit_once('/').map_or("", |(folder, _)| folder);
+        format!("{}/", folder)
+    }
+}
+
+#[cfg(all(feature = "cloud_sync", not(target_arch = "wasm32")))]
+impl WebDavSync {
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self.agent.request(method, &self.url(path));
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+}
+
+#[cfg(all(feature = "cloud_sync", not(target_arch = "wasm32")))]
+impl SaveSync for WebDavSync {
+    fn fetch(&self, path: &str) -> Result<Option<RemoteSave>, String> {
+        match self.request("GET", path).call() {
+            Ok(response) => response.into_json().map(Some).map_err(|e| e.to_string()),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn upload(&self, path: &str, save: &RemoteSave) -> Result<(), String> {
+        let body = serde_json::to_string(save).map_err(|e| e.to_string())?;
+        let put = self
+            .request("PUT", path)
+            .set("Content-Type", "application/json");
+        match put.clone().send_string(&body) {
+            // The folder of the profile does not exist yet
+            Err(ureq::Error::Status(409, _)) => {
+                self.request("MKCOL", &WebDavSync::folder(path))
+                    .call()
+                    .map_err(|e| e.to_string())?;
+                put.send_string(&body).map(drop).map_err(|e| e.to_string())
+            }
+            result => result.map(drop).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[cfg(all(feature = "cloud_sync", target_arch = "wasm32"))]
+impl WebDavSync {
+    /// Send a synchronous request with an optional JSON body, returning the status and the body
+    /// of the response.
+    fn send(&self, method: &str, path: &str, body: Option<&str>) -> Result<(u16, String), String> {
+        let js_err = |err: wasm_bindgen::JsValue| format!("{:?}", err);
+        let request = web_sys::XmlHttpRequest::new().map_err(js_err)?;
+        request
+            .open_with_async(method, &self.url(path), false)
+            .map_err(js_err)?;
+        if let Some(authorization) = &self.authorization {
+            request
+                .set_request_header("Authorization", authorization)
+                .map_err(js_err)?;
+        }
+        if body.is_some() {
+            request
+                .set_request_header("Content-Type", "application/json")
+                .map_err(js_err)?;
+        }
+        request.send_with_opt_str(body).map_err(js_err)?;
+        let status = request.status().map_err(js_err)?;
+        let text = request.response_text().map_err(js_err)?.unwrap_or_default();
+        Ok((status, text))
+    }
+}
+
+#[cfg(all(feature = "cloud_sync", target_arch = "wasm32"))]
+impl SaveSync for WebDavSync {
+    fn fetch(&self, path: &str) -> Result<Option<RemoteSave>, String> {
+        match self.send("GET", path, None)? {
+            (200..=299, text) => serde_json::from_str(&text)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            (404, _) => Ok(None),
+            (status, _) => Err(format!("status code {}", status)),
+        }
+    }
+
+    fn upload(&self, path: &str, save: &RemoteSave) -> Result<(), String> {
+        let body = serde_json::to_string(save).map_err(|e| e.to_string())?;
+        let mut status = self.send("PUT", path, Some(&body))?.0;
+        // The folder of the profile does not exist yet
+        if status == 409 {
+            match self.send("MKCOL", &WebDavSync::folder(path), None)?.0 {
+                200..=299 => status = self.send("PUT", path, Some(&body))?.0,
+                status => return Err(format!("status code {}", status)),
+            }
+        }
+        match status {
+            200..=299 => Ok(()),
+            status => Err(format!("status code {}", status)),
+        }
+    }
+}
+
+/// Copy of a save file kept on a conflict between the local and the remote copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    /// Both copies are the same, or neither exists.
+    UpToDate,
+    /// The remote copy is the latest one.
+    Pull,
+    /// The local copy is the latest one.
+    Push,
+}
+
+/// Resolve a conflict between the copies of a save file from their modification times. The
+/// latest copy wins.
+fn resolve(local: Option<u64>, remote: Option<u64>) -> Resolution {
+    match (local, remote) {
+        (Some(local), Some(remote)) if local > remote => Resolution::Push,
+        (Some(local), Some(remote)) if local < remote => Resolution::Pull,
+        (Some(_), None) => Resolution::Push,
+        (None, Some(_)) => Resolution::Pull,
+        _ => Resolution::UpToDate,
+    }
+}
+
+/// Path of a save file of the active profile in the remote storage.
+fn remote_path(name: &str) -> String {
+    let folder = storage::profile().unwrap_or_else(|| DEFAULT_PROFILE_FOLDER.to_owned());
+    format!("{}/{}", folder, name)
+}
+
+/// Keep the copy of a save file which lost a conflict as a backup next to the local one.
+fn backup(name: &str, content: &str) {
+    let backup_name = format!("{}{}", name, BACKUP_SUFFIX);
+    if let Err(err) = storage::write(&backup_name, content) {
+        let location = storage::location(&backup_name);
+        warn!("Failed to back up save file to '{}': {}", location, err);
+    }
+}
+
+/// Resource syncing the save files of the active profile with a remote storage, pulling the
+/// latest copies on launch and pushing the local changes after they are saved and on exit.
+pub struct CloudSync {
+    backend: Box<dyn SaveSync>,
+    /// Modification time of the synced save files at the last sync, by remote path. Only the
+    /// save files synced on launch are pushed, to never blindly overwrite a remote copy.
+    synced: HashMap<String, u64>,
+    /// Were the save files synced on launch already?
+    launched: bool,
+    /// Number of save files written at the last sync. See [`storage::write_count()`].
+    writes: u32,
+    /// Time of the last push, in seconds since startup.
+    last_push: f64,
+}
+
+impl CloudSync {
+    pub fn new() -> Self {
+        CloudSync::with_backend(NoSync)
+    }
+
+    /// Sync with a custom remote storage, instead of the one of the config.
+    pub fn with_backend(backend: impl SaveSync) -> Self {
+        CloudSync {
+            backend: Box::new(backend),
+            synced: HashMap::new(),
+            launched: false,
+            writes: 0,
+            last_push: 0.0,
+        }
+    }
+
+    /// Update the local save files whose remote copy is more recent. Returns `true` if any was
+    /// updated, for the save data to be reloaded.
+    fn pull_all(&mut self) -> bool {
+        let mut updated = false;
+        for name in SYNCED_FILES {
+            let path = remote_path(name);
+            let remote = match self.backend.fetch(&path) {
+                Ok(remote) => remote,
+                Err(err) => {
+                    warn!("Failed to fetch save file '{}': {}", path, err);
+                    continue;
+                }
+            };
+            let local = storage::modified(name);
+            let remote_modified = remote.as_ref().map(|remote| remote.modified);
+            let local_content = storage::read(name);
+            match (resolve(local, remote_modified), remote) {
+                (Resolution::Pull, Some(remote)) => {
+                    if local_content.as_deref() == Some(&remote.content) {
+                        self.synced.insert(path, local.unwrap_or(remote.modified));
+                        continue;
+                    }
+                    if let Some(local_content) = &local_content {
+                        backup(name, local_content);
+                    }
+                    match storage::write(name, &remote.content) {
+                        Ok(()) => {
+                            debug!("Pulled save file '{}'", path);
+                            updated = true;
+                            // Writing the pulled copy is not a local change to push
+                            let pulled = storage::modified(name).unwrap_or(remote.modified);
+                            self.synced.insert(path, pulled);
+                            continue;
+                        }
+                        Err(err) => {
+                            let location = storage::location(name);
+                            warn!("Failed to write save file '{}': {}", location, err);
+                            continue;
+                        }
+                    }
+                }
+                // The remote copy is overwritten on the next push
+                (Resolution::Push, Some(remote))
+                    if local_content.as_ref() != Some(&remote.content) =>
+                {
+                    backup(name, &remote.content);
+                }
+                _ => {}
+            }
+            self.synced.insert(path, remote_modified.unwrap_or(0));
+        }
+        updated
+    }
+
+    /// Upload the local save files changed since they were last synced.
+    fn push_all(&mut self) {
+        self.writes = storage::write_count();
+        for name in SYNCED_FILES {
+            let path = remote_path(name);
+            let synced = match self.synced.get(&path) {
+                Some(&synced) => synced,
+                None => continue,
+            };
+            let modified = match storage::modified(name) {
+                Some(modified) if modified > synced => modified,
+                _ => continue,
+            };
+            let content = match storage::read(name) {
+                Some(content) => content,
+                None => continue,
+            };
+            let save = RemoteSave { modified, content };
+            match self.backend.upload(&path, &save) {
+                Ok(()) => {
+                    debug!("Pushed save file '{}'", path);
+                    self.synced.insert(path, modified);
+                }
+                Err(err) => warn!("Failed to upload save file '{}': {}", path, err),
+            }
+        }
+    }
+}
+
+impl Default for CloudSync {
+    fn default() -> Self {
+        CloudSync::new()
+    }
+}
+
+/// Pull the latest save files once, when the main menu first shows, and reload them if needed.
+fn sync_on_launch(
+    config: Res<Config>,
+    mut sync: ResMut<CloudSync>,
+    mut ev_profile_changed: EventWriter<ProfileChangedEvent>,
+) {
+    if sync.launched {
+        return;
+    }
+    sync.launched = true;
+    #[cfg(feature = "cloud_sync")]
+    if let Some(url) = config
+        .sync
+        .url
+        .as_deref()
+        .filter(|_| !sync.backend.is_enabled())
+    {
+        info!("Syncing save files with '{}'", url);
+        sync.backend = Box::new(WebDavSync::new(url, config.sync.authorization.clone()));
+    }
+    if sync.backend.is_enabled() && sync.pull_all() {
+        info!("Save files updated from the remote storage");
+        ev_profile_changed.send(ProfileChangedEvent);
+    }
+    // Writing the pulled copies is not a local change to push
+    sync.writes = storage::write_count();
+}
+
+/// Push the save files written while playing, at most every [`PUSH_INTERVAL_SECS`]. The web
+/// builds are closed with their tab, without a chance to push on exit.
+fn sync_on_save(time: Res<Time>, mut sync: ResMut<CloudSync>) {
+    let now = time.seconds_since_startup();
+    if sync.launched
+        && sync.backend.is_enabled()
+        && sync.writes != storage::write_count()
+        && now >= sync.last_push + PUSH_INTERVAL_SECS
+    {
+        sync.last_push = now;
+        sync.push_all();
+    }
+}
+
+/// Push the changed save files when the app exits.
+fn sync_on_exit(mut ev_exit: EventReader<AppExit>, mut sync: ResMut<CloudSync>) {
+    if ev_exit.iter().last().is_some() && sync.backend.is_enabled() {
+        sync.push_all();
+    }
+}
+
+/// Plugin for the cloud sync of the save files, for the progress to follow the player between
+/// devices. A custom [`CloudSync`] resource inserted beforehand replaces the one of the config.
+pub struct SaveSyncPlugin;
+
+impl Plugin for SaveSyncPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CloudSync>() {
+            app.insert_resource(CloudSync::new());
+        }
+        app.add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(sync_on_launch))
+            .add_system(sync_on_save)
+            .add_system_to_stage(CoreStage::Last, sync_on_exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_wins() {
+        assert_eq!(resolve(None, None), Resolution::UpToDate);
+        assert_eq!(resolve(Some(10), None), Resolution::Push);
+        assert_eq!(resolve(None, Some(10)), Resolution::Pull);
+        assert_eq!(resolve(Some(20), Some(10)), Resolution::Push);
+        assert_eq!(resolve(Some(10), Some(20)), Resolution::Pull);
+        assert_eq!(resolve(Some(10), Some(10)), Resolution::UpToDate);
+    }
+}
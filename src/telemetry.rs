@@ -0,0 +1,287 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    balance::BalanceState,
+    config::Config,
+    game::{DefeatReason, GameEvent},
+    inventory::Inventory,
+    rules::Rules,
+    scores::ScoreTracker,
+    storage, AppState, Level, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Save file the telemetry events are appended to on native platforms, in the profile storage,
+/// one JSON object per line.
+#[cfg(not(target_arch = "wasm32"))]
+const TELEMETRY_FILE: &str = "telemetry.jsonl";
+
+/// Anonymous gameplay event. Only the level, rules, and play statistics are recorded, nothing
+/// identifying the player.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TelemetryEvent {
+    /// A level started, or started again after being cleared or quit.
+    Started { level: String, rules: String },
+    /// The level was cleared.
+    Cleared {
+        level: String,
+        rules: String,
+        /// Time spent playing the level, in seconds.
+        time: f32,
+        /// Number of player moves.
+        moves: u32,
+        /// Number of restarts before clearing the level.
+        restarts: u32,
+    },
+    /// All the buildables were placed but the plate is not balanced, or the plate toppled.
+    Failed {
+        level: String,
+        rules: String,
+        time: f32,
+        /// Offset of the center of gravity from the plate center.
+        offset: f32,
+        /// Did the plate topple before all the buildables were placed?
+        toppled: bool,
+    },
+    /// The player restarted the level.
+    Restarted {
+        level: String,
+        rules: String,
+        time: f32,
+        /// Number of buildables placed when restarting.
+        placed: u32,
+    },
+}
+
+/// Telemetry event with its session context, as written out.
+#[derive(Debug, Serialize)]
+struct TelemetryRecord<'a> {
+    /// Random identifier of the game session, to group the events of a single play session.
+    session: &'a str,
+    /// Time since the game started, in seconds.
+    timestamp: f64,
+    #[serde(flatten)]
+    event: &'a TelemetryEvent,
+}
+
+/// Resource holding the state of the telemetry of the current session.
+pub struct Telemetry {
+    /// Did the player consent to telemetry? Copied from the config.
+    enabled: bool,
+    /// Endpoint to post the events to, if any. Copied from the config.
+    endpoint: Option<String>,
+    /// Random session identifier, not persisted across sessions.
+    session: String,
+    /// Number of restarts of the current level.
+    restarts: u32,
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        // Unique to the session on purpose, unlike the seeded gameplay randomness
+        #[allow(clippy::disallowed_methods)]
+        let session = format!("{:016x}", thread_rng().gen::<u64>());
+        Telemetry {
+            enabled: false,
+            endpoint: None,
+            session,
+            restarts: 0,
+        }
+    }
+
+    /// Write out an event, if the player consented to telemetry.
+    fn emit(&self, timestamp: f64, event: TelemetryEvent) {
+        if !self.enabled {
+            return;
+        }
+        trace!("Telemetry: {:?}", event);
+        let record = TelemetryRecord {
+            session: &self.session,
+            timestamp,
+            event: &event,
+        };
+        let json = match serde_json::to_string(&record) {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("Failed to serialize telemetry event: {}", err);
+                return;
+            }
+        };
+        match &self.endpoint {
+            Some(url) => send_to_endpoint(url, json),
+            None => write_to_file(&json),
+        }
+    }
+}
+
+/// Append the JSON event to [`TELEMETRY_FILE`].
+#[cfg(not(target_arch = "wasm32"))]
+fn write_to_file(json: &str) {
+    if let Err(err) = storage::append_line(TELEMETRY_FILE, json) {
+        let location = storage::location(TELEMETRY_FILE);
+        warn!("Failed to write telemetry to '{}': {}", location, err);
+    }
+}
+
+/// There is no local file on the web; an endpoint needs to be configured.
+#[cfg(target_arch = "wasm32")]
+fn write_to_file(_json: &str) {}
+
+/// Post the JSON event to the configured endpoint, on a background thread.
+#[cfg(feature = "telemetry")]
+fn send_to_endpoint(url: &str, json: String) {
+    let url = url.to_owned();
+    std::thread::spawn(move || {
+        if let Err(err) = ureq::post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&json)
+        {
+            warn!("Failed to send telemetry to '{}': {}", url, err);
+        }
+    });
+}
+
+/// Sending to an endpoint requires the `telemetry` feature; fall back to the local file.
+#[cfg(not(feature = "telemetry"))]
+fn send_to_endpoint(_url: &str, json: String) {
+    write_to_file(&json);
+}
+
+/// Names of the level and rules being played, shared by all events.
+fn level_names(level: &Level, rules: &Rules) -> Option<(String, String)> {
+    level
+        .desc()
+        .map(|level_desc| (level_desc.name.clone(), rules.name.to_owned()))
+}
+
+/// Apply the telemetry consent of the config whenever it changes, like after it is loaded during
+/// boot.
+fn apply_telemetry_config(config: Res<Config>, mut telemetry: ResMut<Telemetry>) {
+    if config.is_changed() {
+        telemetry.enabled = config.telemetry.enabled;
+        telemetry.endpoint = config.telemetry.endpoint.clone();
+        if telemetry.enabled {
+            info!("Telemetry enabled, session {}", telemetry.session);
+        }
+    }
+}
+
+fn track_level_start(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    time: Res<Time>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    mut telemetry: ResMut<Telemetry>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    telemetry.restarts = 0;
+    if let Some((level, rules)) = level_names(&level, &rules) {
+        let event = TelemetryEvent::Started { level, rules };
+        telemetry.emit(time.seconds_since_startup(), event);
+    }
+}
+
+fn track_level_restart(
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    time: Res<Time>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    tracker: Res<ScoreTracker>,
+    inventory: Res<Inventory>,
+    mut telemetry: ResMut<Telemetry>,
+) {
+    for _ in ev_restart.iter() {
+        telemetry.restarts += 1;
+        if let Some((level, rules)) = level_names(&level, &rules) {
+            let event = TelemetryEvent::Restarted {
+                level,
+                rules,
+                time: tracker.time(),
+                placed: inventory.placed_count(),
+            };
+            telemetry.emit(time.seconds_since_startup(), event);
+        }
+    }
+}
+
+fn track_level_cleared(
+    mut ev_game: EventReader<GameEvent>,
+    time: Res<Time>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    tracker: Res<ScoreTracker>,
+    telemetry: Res<Telemetry>,
+) {
+    for _ in ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+    {
+        if let Some((level, rules)) = level_names(&level, &rules) {
+            let event = TelemetryEvent::Cleared {
+                level,
+                rules,
+                time: tracker.time(),
+                moves: tracker.moves(),
+                restarts: telemetry.restarts,
+            };
+            telemetry.emit(time.seconds_since_startup(), event);
+        }
+    }
+}
+
+/// Record a failure when the level is failed.
+fn track_level_failed(
+    mut ev_game: EventReader<GameEvent>,
+    time: Res<Time>,
+    level: Res<Level>,
+    rules: Res<Rules>,
+    balance: Res<BalanceState>,
+    tracker: Res<ScoreTracker>,
+    telemetry: Res<Telemetry>,
+) {
+    let reason = match ev_game
+        .iter()
+        .filter_map(|ev| match ev {
+            GameEvent::LevelFailed { reason, .. } => Some(*reason),
+            _ => None,
+        })
+        .last()
+    {
+        Some(reason) => reason,
+        None => return,
+    };
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    let event = TelemetryEvent::Failed {
+        level: level_desc.name.clone(),
+        rules: rules.name.to_owned(),
+        time: tracker.time(),
+        offset: balance.offset.length(),
+        toppled: reason == DefeatReason::Toppled,
+    };
+    telemetry.emit(time.seconds_since_startup(), event);
+}
+
+/// Plugin recording anonymous gameplay events, to tune the level difficulty with real play data.
+/// Disabled unless the player opts in with the `telemetry.enabled` config.
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Telemetry::new())
+            .add_system(apply_telemetry_config)
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(track_level_start)
+                    .with_system(track_level_restart)
+                    .with_system(track_level_cleared)
+                    .with_system(track_level_failed),
+            );
+    }
+}
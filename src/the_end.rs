@@ -0,0 +1,494 @@
+use bevy::{core_pipeline::ClearColor, pbr::NotShadowCaster, prelude::*};
+use bevy_tweening::{lens::TextColorLens, Animator, Delay, EaseFunction, Tween, TweeningType};
+use rand::prelude::*;
+use std::time::Duration;
+
+use crate::{
+    boot::UiResources,
+    create_grid_image,
+    game::GameEvent,
+    layout::GridLayout,
+    scores::{LevelScore, ScoreEvent},
+    serialize::BuildableRegistry,
+    ui_layout::{UiLayouts, UiLayoutsReloadedEvent},
+    AppState, Config, Grid, TILE_THICKNESS,
+};
+
+/// Bobbing amplitude of the plate, in radians.
+const BOB_ANGLE: f32 = 0.04;
+
+/// Spinning speed of the plate around its vertical axis, in radians per second.
+const SPIN_SPEED: f32 = 0.15;
+
+/// Time in seconds between two fireworks.
+const FIREWORK_INTERVAL: f32 = 0.8;
+
+/// Number of sparks of a single firework.
+const FIREWORK_SPARKS: u32 = 48;
+
+/// Downward acceleration of the sparks.
+const SPARK_GRAVITY: f32 = 2.5;
+
+/// Delay in seconds before the first stat line shows, after the title.
+const REVEAL_START: f32 = 1.5;
+
+/// Delay in seconds between two stat lines showing.
+const REVEAL_STEP: f32 = 0.7;
+
+/// Duration in seconds of the fade in of a line of text.
+const REVEAL_FADE: f32 = 0.6;
+
+/// Colors the sparks of the fireworks are drawn from.
+const SPARK_COLORS: [Color; 5] = [
+    Color::rgb(1.0, 0.45, 0.35),
+    Color::rgb(1.0, 0.85, 0.35),
+    Color::rgb(0.45, 0.9, 0.55),
+    Color::rgb(0.45, 0.7, 1.0),
+    Color::rgb(0.85, 0.5, 1.0),
+];
+
+/// Resource holding the layout of the city of the last level cleared, to celebrate it on the end
+/// screen.
+#[derive(Debug, Default)]
+pub struct FinalCity(Option<GridLayout>);
+
+/// Resource accumulating the scores of the levels cleared since leaving the main menu.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RunStats {
+    /// Number of levels cleared.
+    pub levels: u32,
+    /// Total time spent playing the levels cleared, in seconds.
+    pub time: f32,
+    /// Total number of moves to clear the levels.
+    pub moves: u32,
+    /// Total score of the levels cleared.
+    pub score: u32,
+}
+
+impl RunStats {
+    fn record(&mut self, score: &LevelScore) {
+        self.levels += 1;
+        self.time += score.time;
+        self.moves += score.moves;
+        self.score += score.score;
+    }
+
+    /// Lines of text revealed one by one on the end screen.
+    fn lines(&self) -> Vec<String> {
+        vec![
+            format!("Levels cleared: {}", self.levels),
+            format!("Time: {}", format_duration(self.time)),
+            format!("Moves: {}", self.moves),
+            format!("Score: {}", self.score),
+        ]
+    }
+}
+
+/// Format a duration in seconds as minutes and seconds, like `12:05`.
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.max(0.0).round() as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Marker for the plate of the final city, bobbing gently.
+#[derive(Component)]
+struct EndPlate;
+
+/// Spark of a firework, falling and shrinking until it dies out.
+#[derive(Component)]
+struct Spark {
+    velocity: Vec3,
+    life: Timer,
+}
+
+/// Resource launching the fireworks of the end screen.
+struct Fireworks {
+    timer: Timer,
+    rng: StdRng,
+    mesh: Handle<Mesh>,
+    materials: Vec<Handle<StandardMaterial>>,
+}
+
+impl Default for Fireworks {
+    fn default() -> Self {
+        Fireworks {
+            timer: Timer::from_seconds(FIREWORK_INTERVAL, true),
+            rng: StdRng::from_entropy(),
+            mesh: Default::default(),
+            materials: vec![],
+        }
+    }
+}
+
+fn record_final_city(
+    mut ev_game: EventReader<GameEvent>,
+    grid: Res<Grid>,
+    buildables: Res<BuildableRegistry>,
+    mut city: ResMut<FinalCity>,
+) {
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some()
+    {
+        city.0 = Some(grid.to_layout(&buildables));
+    }
+}
+
+fn record_run_stats(mut ev_score: EventReader<ScoreEvent>, mut stats: ResMut<RunStats>) {
+    for ev in ev_score.iter() {
+        stats.record(&ev.0);
+    }
+}
+
+fn reset_run_stats(mut stats: ResMut<RunStats>) {
+    *stats = RunStats::default();
+}
+
+/// Marker for the panel of the end screen text.
+#[derive(Component)]
+struct EndPanel;
+
+/// Spawn a line of text fading in after the given delay.
+fn spawn_reveal_text(
+    parent: &mut ChildBuilder,
+    value: String,
+    font: Handle<Font>,
+    font_size: f32,
+    margin: f32,
+    color: Color,
+    delay: f32,
+) {
+    let transparent = Color::rgba(color.r(), color.g(), color.b(), 0.0);
+    let fade = Tween::new(
+        EaseFunction::QuadraticOut,
+        TweeningType::Once,
+        Duration::from_secs_f32(REVEAL_FADE),
+        TextColorLens {
+            start: transparent,
+            end: color,
+            section: 0,
+        },
+    );
+    let animator = if delay > 0.0 {
+        Animator::new(Delay::new(Duration::from_secs_f32(delay)).then(fade))
+    } else {
+        Animator::new(fade)
+    };
+    parent
+        .spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Px(margin)),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font,
+                    font_size,
+                    color: transparent,
+                },
+                TextAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    vertical: VerticalAlign::Center,
+                },
+            ),
+            ..Default::default()
+        })
+        .insert(animator);
+}
+
+/// Spawn the end screen: the city of the last level on its plate under the fireworks, and the
+/// stats of the run revealed line by line.
+fn spawn_end_screen(
+    mut commands: Commands,
+    mut clear_color: ResMut<ClearColor>,
+    city: Res<FinalCity>,
+    stats: Res<RunStats>,
+    buildables: Res<BuildableRegistry>,
+    ui_resources: Res<UiResources>,
+    ui_layouts: Res<UiLayouts>,
+    mut fireworks: ResMut<Fireworks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    clear_color.0 = Color::rgb(0.04, 0.05, 0.1);
+
+    commands.spawn_bundle(PerspectiveCameraBundle {
+        transform: Transform::from_xyz(0.0, 4.0, 8.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y),
+        ..Default::default()
+    });
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands.spawn_bundle(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 20000.0,
+            ..Default::default()
+        },
+        transform: Transform::from_rotation(Quat::from_euler(EulerRot::YXZ, -0.6, -0.9, 0.0)),
+        ..Default::default()
+    });
+
+    // Final city, or an empty plate if the game ended without clearing a level
+    let mut grid = city
+        .0
+        .as_ref()
+        .and_then(|layout| Grid::from_layout(layout, &buildables).ok())
+        .unwrap_or_else(|| {
+            let mut grid = Grid::new();
+            grid.set_size(&IVec2::new(3, 3));
+            grid
+        });
+    let plate = commands
+        .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+        .insert(Name::new("EndPlate"))
+        .insert(EndPlate)
+        .id();
+    grid.set_material(materials.add(StandardMaterial {
+        base_color_texture: Some(images.add(create_grid_image())),
+        ..Default::default()
+    }));
+    let cell_mesh = meshes.add(Mesh::from(shape::Box::new(1.0, TILE_THICKNESS, 1.0)));
+    grid.regenerate(&mut commands, cell_mesh, plate);
+    grid.spawn_tiles(&mut commands, usize::MAX);
+    for (pos, bref) in grid.placements() {
+        if let Some(buildable) = buildables.get(bref) {
+            commands
+                .spawn_bundle((
+                    Transform::from_translation(grid.translation(&pos, 0.1)),
+                    GlobalTransform::identity(),
+                ))
+                .with_children(|parent| buildable.spawn_model(parent))
+                .insert(Parent(plate));
+        }
+    }
+
+    fireworks.timer.reset();
+    fireworks.mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 0.05,
+        subdivisions: 1,
+    }));
+    fireworks.materials = SPARK_COLORS
+        .iter()
+        .map(|&color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    spawn_end_panel(&mut commands, &stats, &ui_layouts, &ui_resources);
+}
+
+/// Spawn the panel of the end screen from its layout, with a staged text reveal: the title first,
+/// then the stats one line at a time.
+fn spawn_end_panel(
+    commands: &mut Commands,
+    stats: &RunStats,
+    ui_layouts: &UiLayouts,
+    ui_resources: &UiResources,
+) {
+    let layout = match ui_layouts.spawn(commands, ui_resources, "end_screen") {
+        Some(layout) => layout,
+        None => return,
+    };
+    let title_color = ui_layouts.color("end_screen.title");
+    let text_color = ui_layouts.color("end_screen.text");
+    let title_font_size = ui_layouts.metric("end_screen.title_font_size");
+    let line_font_size = ui_layouts.metric("end_screen.line_font_size");
+    let hint_font_size = ui_layouts.metric("end_screen.hint_font_size");
+    let margin = ui_layouts.metric("end_screen.line_margin");
+    let lines = stats.lines();
+    commands
+        .entity(layout.root())
+        .insert(EndPanel)
+        .with_children(|parent| {
+            spawn_reveal_text(
+                parent,
+                "The End".to_owned(),
+                ui_resources.title_font(),
+                title_font_size,
+                margin,
+                title_color,
+                0.0,
+            );
+            for (index, line) in lines.iter().enumerate() {
+                spawn_reveal_text(
+                    parent,
+                    line.clone(),
+                    ui_resources.text_font(),
+                    line_font_size,
+                    margin,
+                    text_color,
+                    REVEAL_START + index as f32 * REVEAL_STEP,
+                );
+            }
+            spawn_reveal_text(
+                parent,
+                "Press [ESC] to quit".to_owned(),
+                ui_resources.text_font(),
+                hint_font_size,
+                margin,
+                text_color,
+                REVEAL_START + (lines.len() + 1) as f32 * REVEAL_STEP,
+            );
+        });
+}
+
+/// Rebuild the panel of the end screen when the layouts are reloaded.
+fn rebuild_end_panel(
+    mut commands: Commands,
+    mut ev_reloaded: EventReader<UiLayoutsReloadedEvent>,
+    stats: Res<RunStats>,
+    ui_layouts: Res<UiLayouts>,
+    ui_resources: Res<UiResources>,
+    query: Query<Entity, With<EndPanel>>,
+) {
+    if ev_reloaded.iter().last().is_none() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_end_panel(&mut commands, &stats, &ui_layouts, &ui_resources);
+}
+
+/// Bob the plate of the final city gently while it slowly spins, unless the motion is reduced.
+fn bob_plate(
+    time: Res<Time>,
+    config: Res<Config>,
+    mut query: Query<&mut Transform, With<EndPlate>>,
+) {
+    if config.reduce_motion {
+        return;
+    }
+    let t = time.seconds_since_startup() as f32;
+    for mut transform in query.iter_mut() {
+        transform.rotation = Quat::from_rotation_y(t * SPIN_SPEED)
+            * Quat::from_rotation_x(BOB_ANGLE * (t * 1.3).sin())
+            * Quat::from_rotation_z(BOB_ANGLE * (t * 0.9).sin());
+        transform.translation.y = 0.1 * (t * 1.1).sin();
+    }
+}
+
+/// Launch a firework at regular intervals, bursting into sparks above and behind the plate.
+fn launch_fireworks(mut commands: Commands, time: Res<Time>, mut fireworks: ResMut<Fireworks>) {
+    if !fireworks.timer.tick(time.delta()).just_finished() || fireworks.materials.is_empty() {
+        return;
+    }
+    let Fireworks {
+        rng,
+        mesh,
+        materials,
+        ..
+    } = &mut *fireworks;
+    let center = Vec3::new(
+        rng.gen_range(-5.0..5.0),
+        rng.gen_range(3.5..6.0),
+        rng.gen_range(-6.0..-2.0),
+    );
+    let material = materials.choose(rng).unwrap().clone();
+    let speed = rng.gen_range(1.5..2.5);
+    for _ in 0..FIREWORK_SPARKS {
+        // Uniform direction on the unit sphere
+        let z: f32 = rng.gen_range(-1.0..1.0);
+        let angle: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+        let r = (1.0 - z * z).sqrt();
+        let dir = Vec3::new(r * angle.cos(), z, r * angle.sin());
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(center),
+                ..Default::default()
+            })
+            .insert(Spark {
+                velocity: dir * speed * rng.gen_range(0.8..1.2),
+                life: Timer::from_seconds(rng.gen_range(1.2..1.8), false),
+            })
+            .insert(NotShadowCaster);
+    }
+}
+
+/// Move the sparks under gravity, shrinking them until they die out.
+fn update_sparks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Spark, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut spark, mut transform) in query.iter_mut() {
+        if spark.life.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        spark.velocity.y -= SPARK_GRAVITY * dt;
+        transform.translation += spark.velocity * dt;
+        transform.scale = Vec3::splat(1.0 - spark.life.percent());
+    }
+}
+
+/// Plugin for the end screen, celebrating the city of the last level with fireworks and the
+/// stats of the run.
+pub struct TheEndPlugin;
+
+impl Plugin for TheEndPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FinalCity::default())
+            .insert_resource(RunStats::default())
+            .insert_resource(Fireworks::default())
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(reset_run_stats))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(record_final_city)
+                    .with_system(record_run_stats),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::TheEnd).with_system(spawn_end_screen))
+            .add_system_set(
+                SystemSet::on_update(AppState::TheEnd)
+                    .with_system(bob_plate)
+                    .with_system(launch_fireworks)
+                    .with_system(update_sparks)
+                    .with_system(rebuild_end_panel),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        assert_eq!(format_duration(0.0), "0:00");
+        assert_eq!(format_duration(65.4), "1:05");
+        assert_eq!(format_duration(725.0), "12:05");
+
+        let mut stats = RunStats::default();
+        for (time, moves, score) in [(30.0, 12, 800), (45.5, 20, 650)] {
+            stats.record(&LevelScore {
+                level: "Level".to_owned(),
+                rules: "standard".to_owned(),
+                time,
+                moves,
+                par_time: None,
+                par_moves: None,
+                score,
+                cheated: false,
+                hard: false,
+            });
+        }
+        assert_eq!(
+            stats.lines(),
+            vec![
+                "Levels cleared: 2".to_owned(),
+                "Time: 1:16".to_owned(),
+                "Moves: 32".to_owned(),
+                "Score: 1450".to_owned(),
+            ]
+        );
+    }
+}
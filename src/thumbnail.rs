@@ -0,0 +1,461 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::{
+    serialize::{LevelDesc, Levels},
+    storage,
+    tilt::KnifeEdge,
+};
+
+/// Cache of the level thumbnails, in the storage shared by all the profiles since the levels do
+/// not depend on the profile.
+pub const THUMBNAILS_FILE: &str = "thumbnails.json";
+
+/// Version of the thumbnail rendering, hashed along the level data so that changing the rendering
+/// invalidates the cached thumbnails.
+const THUMBNAIL_VERSION: u32 = 1;
+
+/// Size of a grid cell in the thumbnails, in pixels, including the cell border.
+pub const CELL_PIXELS: i32 = 6;
+
+/// Maximum number of thumbnails rendered each frame, spreading the work of a cold cache over
+/// several frames instead of rendering all the levels at once.
+const THUMBNAILS_PER_FRAME: usize = 2;
+
+/// Kind of a thumbnail pixel, mapped to a color by [`Swatch::color()`]. Thumbnails are cached as
+/// one character per swatch rather than colors, keeping the cache compact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Swatch {
+    /// Border between the cells.
+    Border,
+    /// Flat cell of the plate.
+    Tile,
+    /// Cell above the plate level.
+    Raised,
+    /// Cell below the plate level.
+    Sunken,
+    /// Fragile tile, breaking under too much weight.
+    Fragile,
+    /// Conveyor tile.
+    Conveyor,
+    /// Side of a conveyor tile the buildables are shifted toward.
+    Arrow,
+    /// Fulcrum the plate balances on.
+    Pivot,
+    /// Beam linking the two plates of a seesaw.
+    Beam,
+}
+
+impl Swatch {
+    const ALL: [Swatch; 9] = [
+        Swatch::Border,
+        Swatch::Tile,
+        Swatch::Raised,
+        Swatch::Sunken,
+        Swatch::Fragile,
+        Swatch::Conveyor,
+        Swatch::Arrow,
+        Swatch::Pivot,
+        Swatch::Beam,
+    ];
+
+    fn color(self) -> [u8; 4] {
+        match self {
+            Swatch::Border => [64, 64, 64, 255],
+            Swatch::Tile => [128, 128, 128, 255],
+            Swatch::Raised => [176, 168, 150, 255],
+            Swatch::Sunken => [90, 96, 110, 255],
+            Swatch::Fragile => [196, 150, 110, 255],
+            Swatch::Conveyor => [80, 110, 150, 255],
+            Swatch::Arrow => [230, 230, 120, 255],
+            Swatch::Pivot => [220, 60, 60, 255],
+            Swatch::Beam => [240, 240, 240, 255],
+        }
+    }
+
+    fn to_char(self) -> char {
+        (b'0' + self as u8) as char
+    }
+
+    fn from_char(c: char) -> Option<Swatch> {
+        let index = (c as u32).checked_sub('0' as u32)? as usize;
+        Swatch::ALL.get(index).copied()
+    }
+}
+
+/// Schematic top-down rendering of a level, with the back of the plate at the top like the plate
+/// reads on screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schematic {
+    pub width: u32,
+    pub height: u32,
+    /// One swatch per pixel, row after row from the top left.
+    pixels: Vec<Swatch>,
+}
+
+/// Schematic as stored in the [`THUMBNAILS_FILE`] cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct SchematicArchive {
+    width: u32,
+    height: u32,
+    /// One [`Swatch`] character per pixel, row after row from the top left.
+    pixels: String,
+}
+
+impl SchematicArchive {
+    /// Convert back into a schematic, unless the pixels don't match the size or aren't all valid
+    /// swatches.
+    fn to_schematic(&self) -> Option<Schematic> {
+        let pixels: Vec<_> = self
+            .pixels
+            .chars()
+            .map(Swatch::from_char)
+            .collect::<Option<_>>()?;
+        if pixels.len() != (self.width * self.height) as usize {
+            return None;
+        }
+        Some(Schematic {
+            width: self.width,
+            height: self.height,
+            pixels,
+        })
+    }
+}
+
+impl From<&Schematic> for SchematicArchive {
+    fn from(schematic: &Schematic) -> Self {
+        SchematicArchive {
+            width: schematic.width,
+            height: schematic.height,
+            pixels: schematic.pixels.iter().map(|s| s.to_char()).collect(),
+        }
+    }
+}
+
+impl Schematic {
+    /// Render the schematic of a level from its data.
+    pub fn render(level_desc: &LevelDesc) -> Self {
+        let size = level_desc.grid_size.max(IVec2::ONE);
+        let min = -size / 2;
+        let width = size.x * CELL_PIXELS + 1;
+        let height = size.y * CELL_PIXELS + 1;
+        let mut swatches = vec![Swatch::Border; (width * height) as usize];
+        let mut set = |x: i32, y: i32, swatch: Swatch| {
+            if x >= 0 && x < width && y >= 0 && y < height {
+                swatches[(x + y * width) as usize] = swatch;
+            }
+        };
+
+        // Cells, with the grid content starting at the front row, at the bottom of the image
+        for j in 0..size.y {
+            for i in 0..size.x {
+                let pos = IVec2::new(i, j) + min;
+                let height_at = level_desc
+                    .terrain
+                    .as_ref()
+                    .and_then(|terrain| terrain.heights.get((i + j * size.x) as usize))
+                    .copied()
+                    .unwrap_or(0.0);
+                let conveyor = level_desc.conveyors.iter().find(|c| c.pos == pos);
+                let swatch = if level_desc.fragile.iter().any(|f| f.pos == pos) {
+                    Swatch::Fragile
+                } else if conveyor.is_some() {
+                    Swatch::Conveyor
+                } else if height_at > 0.0 {
+                    Swatch::Raised
+                } else if height_at < 0.0 {
+                    Swatch::Sunken
+                } else {
+                    Swatch::Tile
+                };
+                let x0 = i * CELL_PIXELS;
+                let y0 = (size.y - 1 - j) * CELL_PIXELS;
+                for y in 1..CELL_PIXELS {
+                    for x in 1..CELL_PIXELS {
+                        let arrow = conveyor.map_or(false, |c| {
+                            (c.direction.x > 0 && x == CELL_PIXELS - 1)
+                                || (c.direction.x < 0 && x == 1)
+                                || (c.direction.y > 0 && y == 1)
+                                || (c.direction.y < 0 && y == CELL_PIXELS - 1)
+                        });
+                        set(x0 + x, y0 + y, if arrow { Swatch::Arrow } else { swatch });
+                    }
+                }
+            }
+        }
+
+        // Border between the two plates of a seesaw
+        if let Some(seesaw) = &level_desc.seesaw {
+            let x = (seesaw.split - min.x) * CELL_PIXELS;
+            for y in 0..height {
+                set(x, y, Swatch::Beam);
+            }
+        }
+
+        // Pivot, offset in cells from the grid center
+        let center = Vec2::new(
+            (width - 1) as f32 / 2.0 + level_desc.pivot.offset.x * CELL_PIXELS as f32,
+            (height - 1) as f32 / 2.0 - level_desc.pivot.offset.y * CELL_PIXELS as f32,
+        )
+        .round()
+        .as_ivec2();
+        match level_desc.pivot.knife_edge {
+            Some(KnifeEdge::X) => (0..width).for_each(|x| set(x, center.y, Swatch::Pivot)),
+            Some(KnifeEdge::Y) => (0..height).for_each(|y| set(center.x, y, Swatch::Pivot)),
+            None => {
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        set(center.x + x, center.y + y, Swatch::Pivot);
+                    }
+                }
+            }
+        }
+
+        Schematic {
+            width: width as u32,
+            height: height as u32,
+            pixels: swatches,
+        }
+    }
+
+    /// Convert into an RGBA image.
+    pub fn to_image(&self) -> Image {
+        let data = self.pixels.iter().flat_map(|s| s.color()).collect();
+        Image::new(
+            Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+}
+
+/// Hash of the level data drawn on its thumbnail, keying the thumbnail cache. The hash is stable
+/// across sessions, so a cached thumbnail is only rendered again once the level changes.
+pub fn level_hash(level_desc: &LevelDesc) -> String {
+    let heights = level_desc
+        .terrain
+        .as_ref()
+        .map(|terrain| terrain.heights.clone());
+    let fragile: Vec<_> = level_desc.fragile.iter().map(|f| f.pos).collect();
+    let conveyors: Vec<_> = level_desc
+        .conveyors
+        .iter()
+        .map(|c| (c.pos, c.direction))
+        .collect();
+    let data = format!(
+        "{} {:?} {:?} {:?} {:?} {:?} {:?}",
+        THUMBNAIL_VERSION,
+        level_desc.grid_size,
+        level_desc.pivot,
+        level_desc.seesaw.as_ref().map(|seesaw| seesaw.split),
+        heights,
+        fragile,
+        conveyors
+    );
+    Sha256::digest(data.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Resource holding a thumbnail of each level, for the level selection. Thumbnails are generated
+/// in the background once the levels are loaded, and cached in the [`THUMBNAILS_FILE`] by
+/// [`level_hash()`] so that later sessions only render the levels which changed.
+pub struct LevelThumbnails {
+    /// Schematics by level hash, loaded from the cache and completed as levels are rendered.
+    cache: HashMap<String, Schematic>,
+    /// Hash of the levels processed so far, by level index.
+    hashes: Vec<String>,
+    /// Thumbnail of the levels processed so far, by level index.
+    images: Vec<Handle<Image>>,
+    /// Was any level rendered since the cache was loaded or saved?
+    dirty: bool,
+}
+
+impl LevelThumbnails {
+    pub fn new() -> Self {
+        LevelThumbnails {
+            cache: HashMap::new(),
+            hashes: vec![],
+            images: vec![],
+            dirty: false,
+        }
+    }
+
+    /// Load the thumbnails cached by a previous session, if any.
+    pub fn load() -> Self {
+        let cache: HashMap<String, SchematicArchive> = storage::read_shared(THUMBNAILS_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    warn!(
+                        "Failed to parse thumbnail cache '{}': {}",
+                        THUMBNAILS_FILE, err
+                    );
+                    None
+                }
+            })
+            .unwrap_or_default();
+        LevelThumbnails {
+            cache: cache
+                .into_iter()
+                .filter_map(|(hash, archive)| Some((hash, archive.to_schematic()?)))
+                .collect(),
+            ..LevelThumbnails::new()
+        }
+    }
+
+    /// Save the thumbnails of the current levels, dropping the ones of levels which changed.
+    fn save(&mut self) {
+        let hashes = &self.hashes;
+        self.cache.retain(|hash, _| hashes.contains(hash));
+        let archives: HashMap<_, SchematicArchive> = self
+            .cache
+            .iter()
+            .map(|(hash, schematic)| (hash, schematic.into()))
+            .collect();
+        let result = serde_json::to_string(&archives)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                storage::write_shared(THUMBNAILS_FILE, &json).map_err(anyhow::Error::from)
+            });
+        if let Err(err) = result {
+            warn!(
+                "Failed to save thumbnail cache '{}': {}",
+                THUMBNAILS_FILE, err
+            );
+        }
+        self.dirty = false;
+    }
+
+    /// Thumbnail of a level, if already generated.
+    pub fn get(&self, level_index: usize) -> Option<&Handle<Image>> {
+        self.images.get(level_index)
+    }
+
+    /// Get the thumbnail of the next level, rendering it unless cached. Returns `true` if the
+    /// level was rendered.
+    fn generate_next(&mut self, level_desc: &LevelDesc, images: &mut Assets<Image>) -> bool {
+        let hash = level_hash(level_desc);
+        let rendered = !self.cache.contains_key(&hash);
+        let schematic = self
+            .cache
+            .entry(hash.clone())
+            .or_insert_with(|| Schematic::render(level_desc));
+        self.images.push(images.add(schematic.to_image()));
+        self.hashes.push(hash);
+        self.dirty |= rendered;
+        rendered
+    }
+}
+
+/// Generate the thumbnails of the levels not processed yet, rendering at most
+/// [`THUMBNAILS_PER_FRAME`] of them each frame, and save the cache once all are done.
+fn generate_thumbnails(
+    levels: Res<Levels>,
+    mut thumbnails: ResMut<LevelThumbnails>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if levels.is_changed() {
+        thumbnails.hashes.clear();
+        thumbnails.images.clear();
+    }
+    if thumbnails.images.len() == levels.len() {
+        return;
+    }
+    let mut rendered = 0;
+    while rendered < THUMBNAILS_PER_FRAME {
+        let level_desc = match levels.get(thumbnails.images.len()) {
+            Some(level_desc) => level_desc.clone(),
+            None => break,
+        };
+        if thumbnails.generate_next(&level_desc, &mut images) {
+            rendered += 1;
+        }
+    }
+    if thumbnails.images.len() == levels.len() && thumbnails.dirty {
+        debug!("Saving {} level thumbnails.", levels.len());
+        thumbnails.save();
+    }
+}
+
+/// Plugin generating the [`LevelThumbnails`] in the background.
+pub struct ThumbnailPlugin;
+
+impl Plugin for ThumbnailPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LevelThumbnails::load())
+            .add_system(generate_thumbnails);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::{BuildableRegistry, LevelDescArchive};
+
+    fn level(json: &str) -> LevelDesc {
+        let archive: LevelDescArchive = serde_json::from_str(json).unwrap();
+        archive.to_desc(&BuildableRegistry::new(), &HashMap::new())
+    }
+
+    #[test]
+    fn render() {
+        let plain = level(
+            r#"{ "name": "A", "grid_size": [3, 2], "balance_factor": 1.0,
+                 "victory_margin": 0.1, "inventory": {} }"#,
+        );
+        let schematic = Schematic::render(&plain);
+        assert_eq!(schematic.width, 3 * CELL_PIXELS as u32 + 1);
+        assert_eq!(schematic.height, 2 * CELL_PIXELS as u32 + 1);
+        assert_eq!(
+            schematic.pixels.len(),
+            (schematic.width * schematic.height) as usize
+        );
+        assert!(schematic.pixels.contains(&Swatch::Pivot));
+        let image = schematic.to_image();
+        assert_eq!(
+            image.data.len(),
+            (schematic.width * schematic.height * 4) as usize
+        );
+
+        let tiles = level(
+            r#"{ "name": "A", "grid_size": [3, 2], "balance_factor": 1.0,
+                 "fragile": [{ "pos": [-1, 0], "capacity": 1.0 }],
+                 "conveyors": [{ "pos": [1, 0], "direction": [1, 0] }],
+                 "victory_margin": 0.1, "inventory": {} }"#,
+        );
+        let tiled = Schematic::render(&tiles);
+        assert_ne!(tiled, schematic);
+        assert!(tiled.pixels.contains(&Swatch::Fragile));
+        assert!(tiled.pixels.contains(&Swatch::Arrow));
+
+        // Only the level data drawn changes the hash
+        assert_eq!(level_hash(&plain), level_hash(&plain));
+        assert_ne!(level_hash(&plain), level_hash(&tiles));
+        let renamed = level(
+            r#"{ "name": "B", "grid_size": [3, 2], "balance_factor": 2.0,
+                 "victory_margin": 0.2, "inventory": {} }"#,
+        );
+        assert_eq!(level_hash(&plain), level_hash(&renamed));
+
+        let json = serde_json::to_string(&SchematicArchive::from(&tiled)).unwrap();
+        let cached: SchematicArchive = serde_json::from_str(&json).unwrap();
+        assert_eq!(cached.to_schematic(), Some(tiled));
+        let truncated = SchematicArchive {
+            pixels: cached.pixels[1..].to_owned(),
+            ..cached
+        };
+        assert_eq!(truncated.to_schematic(), None);
+    }
+}
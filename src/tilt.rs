@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::FRAC_PI_6;
+
+/// Tilt of the plate per unit of COG offset scaled by the balance factor, in radians, for small
+/// offsets.
+const LINEAR_TILT: f32 = FRAC_PI_6;
+
+/// Model mapping the weights on the plate to its tilt, configured per level.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TiltModel {
+    /// Tilt proportional to the COG offset from the pivot, without any limit. This is the
+    /// original mapping, kept for the levels tuned against it.
+    Linear,
+    /// Torque of the weights about the pivot, held back by a spring under the plate. The tilt
+    /// follows the linear model for small torques, and smoothly saturates at the max tilt of
+    /// each axis for large ones.
+    Torque {
+        /// Stiffness of the spring. At 1 the plate tilts like the linear model for small torques,
+        /// and higher values make it tilt less.
+        #[serde(default = "default_stiffness")]
+        stiffness: f32,
+        /// Max tilt toward the X and Y axes of the grid, in radians.
+        max_tilt: Vec2,
+    },
+}
+
+fn default_stiffness() -> f32 {
+    1.0
+}
+
+impl Default for TiltModel {
+    fn default() -> Self {
+        TiltModel::Linear
+    }
+}
+
+impl TiltModel {
+    /// Tilt angles of the plate toward the X and Y axes of the grid, in radians, from the COG
+    /// offset about the pivot. See [`Pivot::balance_offset()`].
+    pub fn angles(&self, cog_offset: Vec2, balance_factor: f32) -> Vec2 {
+        match *self {
+            TiltModel::Linear => LINEAR_TILT * cog_offset * balance_factor,
+            TiltModel::Torque {
+                stiffness,
+                max_tilt,
+            } => {
+                let angles = LINEAR_TILT * cog_offset * balance_factor / stiffness;
+                Vec2::new(
+                    saturate(angles.x, max_tilt.x),
+                    saturate(angles.y, max_tilt.y),
+                )
+            }
+        }
+    }
+}
+
+/// Axis of the grid a knife-edge pivot runs along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KnifeEdge {
+    /// The edge runs along the X axis, and the plate only tilts toward the Y axis.
+    X,
+    /// The edge runs along the Y axis, and the plate only tilts toward the X axis.
+    Y,
+}
+
+/// Fulcrum the plate balances on, configured per level. Defaults to a point under the grid
+/// center, letting the plate tilt in any direction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Pivot {
+    /// Offset of the pivot from the grid center, in cells.
+    pub offset: Vec2,
+    /// Axis of the knife edge, if the pivot is one instead of a point.
+    pub knife_edge: Option<KnifeEdge>,
+}
+
+impl Pivot {
+    /// Lever arm of a weight at some position of the grid about the pivot. The component along
+    /// the knife edge, if any, does not tilt the plate and is dropped.
+    pub fn lever(&self, pos: Vec2) -> Vec2 {
+        self.constrain(pos - self.offset)
+    }
+
+    /// Offset of the COG from the pivot, the weighted sum of the lever arms of all the weights,
+    /// from the COG offset about the grid center and the total weight. The plate is balanced
+    /// when this is zero.
+    pub fn balance_offset(&self, cog_offset: Vec2, total_weight: f32) -> Vec2 {
+        self.constrain(cog_offset - total_weight * self.offset)
+    }
+
+    fn constrain(&self, offset: Vec2) -> Vec2 {
+        match self.knife_edge {
+            None => offset,
+            Some(KnifeEdge::X) => Vec2::new(0.0, offset.y),
+            Some(KnifeEdge::Y) => Vec2::new(offset.x, 0.0),
+        }
+    }
+}
+
+/// Rotation of the plate for the given tilt angles toward the X and Y axes of the grid.
+pub fn rotation(angles: Vec2) -> Quat {
+    Quat::from_rotation_x(-angles.y) * Quat::from_rotation_z(-angles.x)
+}
+
+/// Smoothly limit an angle to `[-max, max]`, leaving the small angles almost unchanged.
+fn saturate(angle: f32, max: f32) -> f32 {
+    if max > 0.0 {
+        max * (angle / max).tanh()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn torque() {
+        let model = TiltModel::Torque {
+            stiffness: 1.0,
+            max_tilt: Vec2::new(0.1, 0.5),
+        };
+        // Small torques tilt like the linear model
+        let small = Vec2::new(0.01, -0.01);
+        let linear = TiltModel::Linear.angles(small, 1.0);
+        assert!((model.angles(small, 1.0) - linear).length() < 1e-4);
+        // Large torques saturate at the max tilt of each axis
+        let angles = model.angles(Vec2::new(100.0, -100.0), 1.0);
+        assert!((angles.x - 0.1).abs() < 1e-4);
+        assert!((angles.y + 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pivot() {
+        let pivot = Pivot {
+            offset: Vec2::new(1.0, 0.0),
+            knife_edge: None,
+        };
+        // A weight right above the pivot balances the plate
+        assert_eq!(pivot.balance_offset(Vec2::new(3.0, 0.0), 3.0), Vec2::ZERO);
+        // and the plate tilts back toward the grid center otherwise
+        assert_eq!(pivot.balance_offset(Vec2::ZERO, 3.0), Vec2::new(-3.0, 0.0));
+        assert_eq!(pivot.lever(Vec2::new(2.0, 1.0)), Vec2::new(1.0, 1.0));
+
+        let knife_edge = Pivot {
+            knife_edge: Some(KnifeEdge::Y),
+            ..pivot
+        };
+        // Weights along the edge don't tilt the plate
+        assert_eq!(knife_edge.lever(Vec2::new(2.0, 1.0)), Vec2::new(1.0, 0.0));
+        assert_eq!(
+            knife_edge.balance_offset(Vec2::new(3.0, 5.0), 3.0),
+            Vec2::ZERO
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        let model: TiltModel = serde_json::from_str(r#""linear""#).unwrap();
+        assert_eq!(model, TiltModel::Linear);
+        let model: TiltModel =
+            serde_json::from_str(r#"{ "torque": { "max_tilt": [0.2, 0.3] } }"#).unwrap();
+        assert_eq!(
+            model,
+            TiltModel::Torque {
+                stiffness: 1.0,
+                max_tilt: Vec2::new(0.2, 0.3),
+            }
+        );
+        let pivot: Pivot =
+            serde_json::from_str(r#"{ "offset": [0.5, 0.0], "knife_edge": "x" }"#).unwrap();
+        assert_eq!(
+            pivot,
+            Pivot {
+                offset: Vec2::new(0.5, 0.0),
+                knife_edge: Some(KnifeEdge::X),
+            }
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use bevy::{asset::LoadState, prelude::*, sprite::TextureAtlasBuilder, utils::HashMap};
+
+/// Region of a packed image in the UI atlas, in pixels from the top left corner of the atlas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    /// Top left corner of the region.
+    pub min: Vec2,
+    /// Size of the region.
+    pub size: Vec2,
+    /// Size of the whole atlas.
+    pub atlas_size: Vec2,
+}
+
+impl AtlasRegion {
+    /// Position and size of a node displaying the whole atlas, such that the region exactly
+    /// covers a parent node of the given size clipping it.
+    pub fn layout(&self, node_size: Vec2) -> (Vec2, Vec2) {
+        let scale = node_size / self.size;
+        (-self.min * scale, self.atlas_size * scale)
+    }
+}
+
+/// Atlas packing the UI images, once loaded, into a single texture.
+struct PackedAtlas {
+    texture: Handle<Image>,
+    regions: HashMap<Handle<Image>, AtlasRegion>,
+}
+
+/// Registry of the UI images like buildable frames and icons, loaded once by path and packed
+/// into a single atlas texture once loaded, so that many slots draw from the same texture.
+///
+/// Until the atlas is packed, and for images which can't be packed, the UI nodes display the
+/// individual image instead. See [`AtlasImage`].
+#[derive(Default)]
+pub struct UiImages {
+    /// Loaded images, by path relative to the textures/ folder.
+    images: HashMap<String, Handle<Image>>,
+    /// Were images registered since the atlas was last packed?
+    dirty: bool,
+    atlas: Option<PackedAtlas>,
+}
+
+impl UiImages {
+    pub fn new() -> Self {
+        UiImages::default()
+    }
+
+    /// Get the image at the given path, relative to the textures/ folder, loading it the first
+    /// time and packing it into the atlas once loaded.
+    pub fn load(&mut self, path: &str, asset_server: &AssetServer) -> Handle<Image> {
+        if let Some(handle) = self.images.get(path) {
+            return handle.clone();
+        }
+        let handle: Handle<Image> = asset_server.load(&format!("textures/{}", path)[..]);
+        self.images.insert(path.to_string(), handle.clone());
+        self.dirty = true;
+        handle
+    }
+
+    /// Texture of the atlas and region of the given image in it, if packed.
+    pub fn region(&self, image: &Handle<Image>) -> Option<(Handle<Image>, AtlasRegion)> {
+        let atlas = self.atlas.as_ref()?;
+        let region = atlas.regions.get(image)?;
+        Some((atlas.texture.clone(), *region))
+    }
+}
+
+/// Image of a UI node drawn from the UI atlas, replacing the [`UiImage`] and [`UiColor`] of the
+/// node which are managed by the atlas.
+///
+/// The atlas region is displayed by a node covering the whole atlas, offset such that a child
+/// node covering the node clips it to the region. That child is always the first child of the
+/// node, so it's drawn below the other children.
+#[derive(Component, Debug, Clone)]
+pub struct AtlasImage {
+    pub image: Handle<Image>,
+    pub color: Color,
+    /// Clipping child node and its child displaying the atlas, if the image is packed.
+    region: Option<(Entity, Entity)>,
+}
+
+impl AtlasImage {
+    pub fn new(image: Handle<Image>, color: Color) -> Self {
+        AtlasImage {
+            image,
+            color,
+            region: None,
+        }
+    }
+}
+
+/// Marker for the node displaying the atlas for an [`AtlasImage`].
+#[derive(Component)]
+struct AtlasRegionNode;
+
+/// Pack the UI images into the atlas once they're all loaded or failed, each time new ones are
+/// registered. Packing failures keep the individual images.
+fn pack_ui_atlas(
+    asset_server: Res<AssetServer>,
+    mut ui_images: ResMut<UiImages>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !ui_images.dirty {
+        return;
+    }
+    let mut builder = TextureAtlasBuilder::default();
+    let mut count = 0;
+    for handle in ui_images.images.values() {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => {
+                if let Some(image) = images.get(handle) {
+                    builder.add_texture(handle.clone(), image);
+                    count += 1;
+                }
+            }
+            LoadState::Failed => {}
+            _ => return,
+        }
+    }
+    ui_images.dirty = false;
+    if count == 0 {
+        return;
+    }
+    let atlas = match builder.finish(&mut images) {
+        Ok(atlas) => atlas,
+        Err(err) => {
+            warn!("Failed to pack the UI atlas: {:?}", err);
+            return;
+        }
+    };
+    let regions = atlas
+        .texture_handles
+        .iter()
+        .flatten()
+        .map(|(handle, &index)| {
+            let rect = atlas.textures[index];
+            let region = AtlasRegion {
+                min: rect.min,
+                size: rect.max - rect.min,
+                atlas_size: atlas.size,
+            };
+            (handle.clone(), region)
+        })
+        .collect();
+    debug!(
+        "Packed {} UI images into a {}x{} atlas",
+        count, atlas.size.x, atlas.size.y
+    );
+    ui_images.atlas = Some(PackedAtlas {
+        texture: atlas.texture,
+        regions,
+    });
+}
+
+/// Display the atlas region of each [`AtlasImage`], or the individual image until packed. Only
+/// changed values are written, to avoid relayouting the UI each frame.
+fn update_atlas_images(
+    mut commands: Commands,
+    ui_images: Res<UiImages>,
+    mut query: Query<
+        (Entity, &mut AtlasImage, &Node, &mut UiImage, &mut UiColor),
+        Without<AtlasRegionNode>,
+    >,
+    mut region_query: Query<(&mut UiImage, &mut UiColor, &mut Style), With<AtlasRegionNode>>,
+) {
+    for (entity, mut atlas_image, node, mut ui_image, mut ui_color) in query.iter_mut() {
+        let (texture, region) = match ui_images.region(&atlas_image.image) {
+            Some(region) => region,
+            None => {
+                if let Some((clip_entity, _)) = atlas_image.region.take() {
+                    commands.entity(clip_entity).despawn_recursive();
+                }
+                if ui_image.0 != atlas_image.image {
+                    ui_image.0 = atlas_image.image.clone();
+                }
+                if ui_color.0 != atlas_image.color {
+                    ui_color.0 = atlas_image.color;
+                }
+                continue;
+            }
+        };
+
+        if ui_color.0 != Color::NONE {
+            ui_color.0 = Color::NONE;
+        }
+        let (position, size) = region.layout(node.size);
+        let region_style = Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(position.x),
+                top: Val::Px(position.y),
+                ..Default::default()
+            },
+            size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+            ..Default::default()
+        };
+        if let Some((_, region_entity)) = atlas_image.region {
+            // Spawned on a previous frame; not queryable yet if spawned on this one
+            if let Ok((mut region_image, mut region_color, mut style)) =
+                region_query.get_mut(region_entity)
+            {
+                if region_image.0 != texture {
+                    region_image.0 = texture;
+                }
+                if region_color.0 != atlas_image.color {
+                    region_color.0 = atlas_image.color;
+                }
+                if style.position != region_style.position || style.size != region_style.size {
+                    *style = region_style;
+                }
+            }
+            continue;
+        }
+
+        // Clip the atlas to the node with a child covering it, so the node itself doesn't clip
+        // its other children, like texts overflowing it.
+        let region_entity = commands
+            .spawn_bundle(ImageBundle {
+                style: region_style,
+                image: UiImage(texture),
+                color: UiColor(atlas_image.color),
+                ..Default::default()
+            })
+            .insert(Name::new("AtlasRegion"))
+            .insert(AtlasRegionNode)
+            .id();
+        let clip_entity = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect::all(Val::Px(0.0)),
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    overflow: Overflow::Hidden,
+                    ..Default::default()
+                },
+                color: UiColor(Color::NONE),
+                ..Default::default()
+            })
+            .insert(Name::new("AtlasClip"))
+            .push_children(&[region_entity])
+            .id();
+        commands.entity(entity).insert_children(0, &[clip_entity]);
+        atlas_image.region = Some((clip_entity, region_entity));
+    }
+}
+
+/// Plugin for the UI images registry, packing the buildable frames and icons into an atlas.
+pub struct UiAtlasPlugin;
+
+impl Plugin for UiAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UiImages::new())
+            .add_system(pack_ui_atlas.label("pack_ui_atlas"))
+            .add_system(update_atlas_images.after("pack_ui_atlas"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let region = AtlasRegion {
+            min: Vec2::new(128.0, 0.0),
+            size: Vec2::new(128.0, 128.0),
+            atlas_size: Vec2::new(256.0, 512.0),
+        };
+        // Same size as the region: only offset
+        let (position, size) = region.layout(Vec2::new(128.0, 128.0));
+        assert_eq!(position, Vec2::new(-128.0, 0.0));
+        assert_eq!(size, Vec2::new(256.0, 512.0));
+        // Half size: offset and atlas scaled down
+        let (position, size) = region.layout(Vec2::new(64.0, 64.0));
+        assert_eq!(position, Vec2::new(-64.0, 0.0));
+        assert_eq!(size, Vec2::new(128.0, 256.0));
+    }
+}
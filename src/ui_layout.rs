@@ -0,0 +1,461 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::{anim::PlayAnimation, boot::UiResources, cinematic::Hud, text_asset::TextAsset};
+
+/// Layouts built into the game, used for the entries the loaded layouts don't define, or if
+/// they fail to load.
+const BUILTIN_LAYOUTS: &str = include_str!("../assets/ui_layouts.json");
+
+/// Length of a UI node: a number of pixels, or a string like `"50%"` or `"auto"`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "ValArchive")]
+pub struct ValDesc(pub Val);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ValArchive {
+    Px(f32),
+    Keyword(String),
+}
+
+impl TryFrom<ValArchive> for ValDesc {
+    type Error = String;
+
+    fn try_from(archive: ValArchive) -> Result<Self, Self::Error> {
+        match archive {
+            ValArchive::Px(px) => Ok(ValDesc(Val::Px(px))),
+            ValArchive::Keyword(keyword) if keyword == "auto" => Ok(ValDesc(Val::Auto)),
+            ValArchive::Keyword(keyword) => keyword
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim().parse().ok())
+                .map(|percent| ValDesc(Val::Percent(percent)))
+                .ok_or_else(|| format!("invalid UI length '{}'", keyword)),
+        }
+    }
+}
+
+/// Sides of a UI node: a single length for all sides, or an object with the sides set. Unset
+/// sides are left undefined.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(from = "RectArchive")]
+pub struct RectDesc(pub Rect<Val>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RectArchive {
+    All(ValDesc),
+    Sides {
+        #[serde(default)]
+        left: Option<ValDesc>,
+        #[serde(default)]
+        right: Option<ValDesc>,
+        #[serde(default)]
+        top: Option<ValDesc>,
+        #[serde(default)]
+        bottom: Option<ValDesc>,
+    },
+}
+
+impl From<RectArchive> for RectDesc {
+    fn from(archive: RectArchive) -> Self {
+        let val = |side: Option<ValDesc>| side.map_or(Val::Undefined, |side| side.0);
+        match archive {
+            RectArchive::All(all) => RectDesc(Rect::all(all.0)),
+            RectArchive::Sides {
+                left,
+                right,
+                top,
+                bottom,
+            } => RectDesc(Rect {
+                left: val(left),
+                right: val(right),
+                top: val(top),
+                bottom: val(bottom),
+            }),
+        }
+    }
+}
+
+/// Size of a UI node, as `[width, height]`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(from = "[ValDesc; 2]")]
+pub struct SizeDesc(pub Size<Val>);
+
+impl From<[ValDesc; 2]> for SizeDesc {
+    fn from([width, height]: [ValDesc; 2]) -> Self {
+        SizeDesc(Size::new(width.0, height.0))
+    }
+}
+
+/// Flexbox [`Style`] of a UI node. Unset properties keep their default value.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleDesc {
+    pub position_type: Option<PositionType>,
+    pub position: Option<RectDesc>,
+    pub size: Option<SizeDesc>,
+    pub min_size: Option<SizeDesc>,
+    pub max_size: Option<SizeDesc>,
+    pub margin: Option<RectDesc>,
+    pub padding: Option<RectDesc>,
+    pub flex_direction: Option<FlexDirection>,
+    pub justify_content: Option<JustifyContent>,
+    pub align_items: Option<AlignItems>,
+    pub align_self: Option<AlignSelf>,
+    pub align_content: Option<AlignContent>,
+}
+
+impl StyleDesc {
+    pub fn to_style(&self) -> Style {
+        let default = Style::default();
+        Style {
+            position_type: self.position_type.unwrap_or(default.position_type),
+            position: self.position.map_or(default.position, |rect| rect.0),
+            size: self.size.map_or(default.size, |size| size.0),
+            min_size: self.min_size.map_or(default.min_size, |size| size.0),
+            max_size: self.max_size.map_or(default.max_size, |size| size.0),
+            margin: self.margin.map_or(default.margin, |rect| rect.0),
+            padding: self.padding.map_or(default.padding, |rect| rect.0),
+            flex_direction: self.flex_direction.unwrap_or(default.flex_direction),
+            justify_content: self.justify_content.unwrap_or(default.justify_content),
+            align_items: self.align_items.unwrap_or(default.align_items),
+            align_self: self.align_self.unwrap_or(default.align_self),
+            align_content: self.align_content.unwrap_or(default.align_content),
+            ..default
+        }
+    }
+}
+
+/// Font of a text section, one of the fonts of the [`UiResources`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontDesc {
+    Title,
+    #[default]
+    Text,
+}
+
+/// Section of a text, with its own font and color.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionDesc {
+    pub value: String,
+    #[serde(default)]
+    pub font: FontDesc,
+    pub font_size: f32,
+    /// Color of the text, as sRGBA.
+    pub color: [f32; 4],
+}
+
+/// Text of a UI node.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextDesc {
+    pub sections: Vec<SectionDesc>,
+    /// Center the text horizontally and vertically, instead of aligning it to the top left.
+    #[serde(default)]
+    pub centered: bool,
+}
+
+impl TextDesc {
+    pub fn to_text(&self, ui_resources: &UiResources) -> Text {
+        let alignment = if self.centered {
+            TextAlignment {
+                horizontal: HorizontalAlign::Center,
+                vertical: VerticalAlign::Center,
+            }
+        } else {
+            TextAlignment::default()
+        };
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| TextSection {
+                value: section.value.clone(),
+                style: TextStyle {
+                    font: match section.font {
+                        FontDesc::Title => ui_resources.title_font(),
+                        FontDesc::Text => ui_resources.text_font(),
+                    },
+                    font_size: section.font_size,
+                    color: rgba(section.color),
+                },
+            })
+            .collect();
+        Text {
+            sections,
+            alignment,
+        }
+    }
+}
+
+/// Description of a UI node and its children. A node with a text is spawned as a [`TextBundle`],
+/// and as a [`NodeBundle`] otherwise.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeDesc {
+    /// Name of the entity, by which the code finds the node to attach its components to.
+    pub name: String,
+    #[serde(default)]
+    pub style: StyleDesc,
+    /// Background color of the node, as sRGBA. Transparent if unset.
+    #[serde(default)]
+    pub color: Option<[f32; 4]>,
+    #[serde(default)]
+    pub text: Option<TextDesc>,
+    /// Name of the animation of the [`AnimationLibrary`] played when the node spawns.
+    ///
+    /// [`AnimationLibrary`]: crate::anim::AnimationLibrary
+    #[serde(default)]
+    pub animation: Option<String>,
+    #[serde(default)]
+    pub children: Vec<NodeDesc>,
+}
+
+fn rgba([r, g, b, a]: [f32; 4]) -> Color {
+    Color::rgba(r, g, b, a)
+}
+
+/// Entities of a spawned layout, by node name.
+pub struct SpawnedLayout {
+    root: Entity,
+    nodes: HashMap<String, Entity>,
+}
+
+impl SpawnedLayout {
+    /// Entity of the root node, to despawn the layout with.
+    pub fn root(&self) -> Entity {
+        self.root
+    }
+
+    /// Entity of the node with the given name, if any.
+    pub fn node(&self, name: &str) -> Option<Entity> {
+        self.nodes.get(name).copied()
+    }
+}
+
+/// Resource holding the layouts of the UI screens, the positions of the HUD elements, and the
+/// metrics and colors of the UI built by code, loaded from `ui_layouts.json`.
+///
+/// With the `hot_reload` feature, editing the file while the game runs reloads the layouts and
+/// sends a [`UiLayoutsReloadedEvent`] for the screens to rebuild, to tune them without
+/// recompiling.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UiLayouts {
+    /// Root node of each screen, by screen name.
+    #[serde(default)]
+    screens: HashMap<String, NodeDesc>,
+    /// Position of the HUD elements, by entity name.
+    #[serde(default)]
+    hud: HashMap<String, RectDesc>,
+    #[serde(default)]
+    metrics: HashMap<String, f32>,
+    /// Colors as sRGBA.
+    #[serde(default)]
+    colors: HashMap<String, [f32; 4]>,
+    /// Source asset, to reload the layouts when it changes.
+    #[serde(skip)]
+    handle: Handle<TextAsset>,
+}
+
+impl Default for UiLayouts {
+    fn default() -> Self {
+        serde_json::from_str(BUILTIN_LAYOUTS).expect("Invalid built-in UI layouts")
+    }
+}
+
+impl UiLayouts {
+    /// Parse the layouts from JSON, falling back to the built-in ones for the entries not defined.
+    pub fn from_json(json_content: &str) -> serde_json::Result<UiLayouts> {
+        let layouts: UiLayouts = serde_json::from_str(json_content)?;
+        let mut merged = UiLayouts::default();
+        merged.screens.extend(layouts.screens);
+        merged.hud.extend(layouts.hud);
+        merged.metrics.extend(layouts.metrics);
+        merged.colors.extend(layouts.colors);
+        Ok(merged)
+    }
+
+    /// Keep the asset the layouts were loaded from, to reload them when it changes.
+    pub fn with_handle(mut self, handle: Handle<TextAsset>) -> Self {
+        self.handle = handle;
+        self
+    }
+
+    /// Numeric value of the UI built by code, like a font size or a spacing in pixels.
+    pub fn metric(&self, name: &str) -> f32 {
+        self.metrics.get(name).copied().unwrap_or_else(|| {
+            warn!("Unknown UI metric '{}'", name);
+            0.0
+        })
+    }
+
+    /// Color of the UI built by code.
+    pub fn color(&self, name: &str) -> Color {
+        self.colors.get(name).copied().map_or_else(
+            || {
+                warn!("Unknown UI color '{}'", name);
+                Color::WHITE
+            },
+            rgba,
+        )
+    }
+
+    /// Position of the HUD element with the given entity name, if the layouts define it.
+    pub fn hud_position(&self, name: &str) -> Option<Rect<Val>> {
+        self.hud.get(name).map(|rect| rect.0)
+    }
+
+    /// Spawn the nodes of a screen, returning `None` if the screen has no layout.
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        ui_resources: &UiResources,
+        screen: &str,
+    ) -> Option<SpawnedLayout> {
+        let desc = match self.screens.get(screen) {
+            Some(desc) => desc,
+            None => {
+                error!("No UI layout for screen '{}'", screen);
+                return None;
+            }
+        };
+        let mut nodes = HashMap::new();
+        let root = spawn_node(desc, commands, ui_resources, &mut nodes);
+        Some(SpawnedLayout { root, nodes })
+    }
+}
+
+fn spawn_node(
+    desc: &NodeDesc,
+    commands: &mut Commands,
+    ui_resources: &UiResources,
+    nodes: &mut HashMap<String, Entity>,
+) -> Entity {
+    let style = desc.style.to_style();
+    let mut node = if let Some(text) = &desc.text {
+        commands.spawn_bundle(TextBundle {
+            style,
+            text: text.to_text(ui_resources),
+            ..Default::default()
+        })
+    } else {
+        commands.spawn_bundle(NodeBundle {
+            style,
+            color: UiColor(desc.color.map_or(Color::NONE, rgba)),
+            ..Default::default()
+        })
+    };
+    node.insert(Name::new(desc.name.clone()));
+    if let Some(animation) = &desc.animation {
+        node.insert(PlayAnimation::new(animation));
+    }
+    let entity = node.id();
+    let children: Vec<_> = desc
+        .children
+        .iter()
+        .map(|child| spawn_node(child, commands, ui_resources, nodes))
+        .collect();
+    commands.entity(entity).push_children(&children);
+    nodes.insert(desc.name.clone(), entity);
+    entity
+}
+
+/// Event sent once the layouts were reloaded after their file changed.
+pub struct UiLayoutsReloadedEvent;
+
+/// Reload the layouts when their file changes. Only happens with the `hot_reload` feature, which
+/// makes the asset server watch the asset folder.
+fn reload_layouts(
+    mut ev_asset: EventReader<AssetEvent<TextAsset>>,
+    text_assets: Res<Assets<TextAsset>>,
+    mut layouts: ResMut<UiLayouts>,
+    mut ev_reloaded: EventWriter<UiLayoutsReloadedEvent>,
+) {
+    for ev in ev_asset.iter() {
+        let handle = match ev {
+            AssetEvent::Modified { handle } if *handle == layouts.handle => handle,
+            _ => continue,
+        };
+        let json_layouts = match text_assets.get(handle) {
+            Some(json_layouts) => json_layouts,
+            None => continue,
+        };
+        match UiLayouts::from_json(&json_layouts.value[..]) {
+            Ok(reloaded) => {
+                *layouts = reloaded.with_handle(handle.clone());
+                info!("Reloaded ui_layouts.json");
+                ev_reloaded.send(UiLayoutsReloadedEvent);
+            }
+            Err(err) => error!(
+                "Failed to parse ui_layouts.json, keeping the layouts: {}",
+                err
+            ),
+        }
+    }
+}
+
+/// Move the HUD elements to their position from the layouts, when spawned and when the layouts
+/// are reloaded.
+fn place_hud(
+    layouts: Res<UiLayouts>,
+    mut ev_reloaded: EventReader<UiLayoutsReloadedEvent>,
+    mut query: Query<(&Name, &mut Style, ChangeTrackers<Hud>)>,
+) {
+    let reloaded = ev_reloaded.iter().last().is_some();
+    for (name, mut style, hud) in query.iter_mut() {
+        if reloaded || hud.is_added() {
+            if let Some(position) = layouts.hud_position(name.as_str()) {
+                style.position = position;
+            }
+        }
+    }
+}
+
+/// Plugin for the [`UiLayouts`], loaded with the critical assets at boot.
+pub struct UiLayoutPlugin;
+
+impl Plugin for UiLayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UiLayouts::default())
+            .add_event::<UiLayoutsReloadedEvent>()
+            .add_system(reload_layouts.label("reload_layouts"))
+            .add_system(place_hud.after("reload_layouts"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let builtin = UiLayouts::default();
+        assert!(builtin.screens.contains_key("main_menu"));
+        assert_eq!(builtin.metric("inventory.slot_size"), 128.0);
+        assert_eq!(
+            builtin.hud_position("StabilizeText").unwrap().left,
+            Val::Percent(45.0)
+        );
+
+        // Loaded entries override the built-in ones, which fill the gaps
+        let layouts = UiLayouts::from_json(
+            r#"{ "metrics": { "inventory.slot_size": 96 },
+                 "screens": { "end_screen": { "name": "TheEnd",
+                     "style": { "size": ["50%", "auto"], "padding": { "left": 12 } } } } }"#,
+        )
+        .unwrap();
+        assert_eq!(layouts.metric("inventory.slot_size"), 96.0);
+        assert_eq!(layouts.metric("inventory.slot_spacing"), 200.0);
+        assert!(layouts.screens.contains_key("main_menu"));
+        let style = layouts.screens["end_screen"].style.to_style();
+        assert_eq!(style.size, Size::new(Val::Percent(50.0), Val::Auto));
+        assert_eq!(style.padding.left, Val::Px(12.0));
+        assert_eq!(style.padding.right, Val::Undefined);
+
+        assert!(UiLayouts::from_json(r#"{ "hud": { "BudgetText": { "top": "12px" } } }"#).is_err());
+    }
+}
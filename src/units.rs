@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// Unit the weights are displayed in, configured per world in the game data. The simulation
+/// always works in raw weights; units only change how they read, like tons in the meadow or
+/// whimsical "pebbles" elsewhere.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeightUnit {
+    /// Name of the unit for a single unit, like "ton".
+    pub name: Cow<'static, str>,
+    /// Name of the unit for any other amount, like "tons".
+    pub plural: Cow<'static, str>,
+    /// Number of units per raw weight unit.
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Maximum number of decimals displayed. Trailing zeros are dropped.
+    #[serde(default = "default_decimals")]
+    pub decimals: usize,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_decimals() -> usize {
+    1
+}
+
+/// Unit of the worlds which don't configure any.
+pub static TONS: WeightUnit = WeightUnit {
+    name: Cow::Borrowed("ton"),
+    plural: Cow::Borrowed("tons"),
+    scale: 1.0,
+    decimals: 1,
+};
+
+impl Default for WeightUnit {
+    fn default() -> Self {
+        TONS.clone()
+    }
+}
+
+/// Format a raw weight for display in some unit, like "1 ton" or "2.5 tons". All the weight
+/// readouts go through this, for the units to stay consistent between the HUD, the tooltips, and
+/// the encyclopedia.
+pub fn format_weight(weight: f32, unit: &WeightUnit) -> String {
+    let value = format_value(weight, unit);
+    let name = if value == "1" {
+        &unit.name
+    } else {
+        &unit.plural
+    };
+    format!("{} {}", value, name)
+}
+
+/// Format a raw weight varying within some variance for display in some unit, like "2±0.2 tons",
+/// or like [`format_weight()`] without variance.
+pub fn format_weight_range(weight: f32, variance: f32, unit: &WeightUnit) -> String {
+    if variance <= 0.0 {
+        return format_weight(weight, unit);
+    }
+    format!(
+        "{}±{} {}",
+        format_value(weight, unit),
+        format_value(variance, unit),
+        unit.plural
+    )
+}
+
+/// Amount of a raw weight in some unit, without trailing zeros.
+fn format_value(weight: f32, unit: &WeightUnit) -> String {
+    let mut value = format!("{:.*}", unit.decimals, weight * unit.scale);
+    if value.contains('.') {
+        let len = value.trim_end_matches('0').trim_end_matches('.').len();
+        value.truncate(len);
+    }
+    if value == "-0" {
+        value = "0".to_owned();
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        assert_eq!(format_weight(1.0, &TONS), "1 ton");
+        assert_eq!(format_weight(2.5, &TONS), "2.5 tons");
+        assert_eq!(format_weight(3.04, &TONS), "3 tons");
+        assert_eq!(format_weight(0.0, &TONS), "0 tons");
+        let blocks = WeightUnit {
+            name: "block".into(),
+            plural: "blocks".into(),
+            scale: 4.0,
+            decimals: 0,
+        };
+        assert_eq!(format_weight(0.25, &blocks), "1 block");
+        assert_eq!(format_weight(2.6, &blocks), "10 blocks");
+        assert_eq!(format_weight_range(2.0, 0.2, &TONS), "2±0.2 tons");
+        assert_eq!(format_weight_range(1.0, 0.0, &TONS), "1 ton");
+    }
+}
@@ -0,0 +1,674 @@
+use bevy::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+
+use crate::{
+    game::TOPPLE_TILT,
+    inventory::{Buildable, Inventory},
+    layout::GridLayout,
+    script::ScriptTrigger,
+    serialize::{BuildableRegistry, GameDataArchive, LevelDescArchive},
+    solver,
+    tilt::{Pivot, TiltModel},
+    Grid,
+};
+
+/// Severity of a [`Finding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The game data cannot be played as intended.
+    Error,
+    /// The game data is playable, but likely not what the author intended.
+    Warning,
+}
+
+/// Single problem found in the game data.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// What the finding is about, like a level or a buildable.
+    pub subject: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.subject, self.message)
+    }
+}
+
+/// Result of the validation of some game data, printed as a human-readable report.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// Number of levels validated.
+    pub level_count: usize,
+    /// Number of buildables validated.
+    pub buildable_count: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    fn error(&mut self, subject: &str, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Error,
+            subject: subject.to_owned(),
+            message,
+        });
+    }
+
+    fn warning(&mut self, subject: &str, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Warning,
+            subject: subject.to_owned(),
+            message,
+        });
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+
+    /// Does the game data have any error?
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Validated {} levels and {} buildables.",
+            self.level_count, self.buildable_count
+        )?;
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        write!(
+            f,
+            "{} error(s), {} warning(s).",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+/// Registry of the buildables of the game data, without any asset.
+fn registry(game_data: &GameDataArchive) -> BuildableRegistry {
+    let mut names: Vec<_> = game_data.buildables.keys().collect();
+    names.sort();
+    let mut buildables = BuildableRegistry::new();
+    for name in names {
+        let rules = &game_data.buildables[name];
+        let mut buildable = Buildable::without_assets(&rules.name, rules.weight);
+        buildable.set_weight_variance(rules.weight_variance);
+        buildables.register(name, buildable);
+    }
+    buildables
+}
+
+/// Names of the buildables referenced by a level, in its inventory, deliveries, market, and
+/// script.
+fn level_buildable_names(level: &LevelDescArchive) -> Vec<&str> {
+    let mut names: Vec<&str> = level.inventory.keys().map(|name| &name[..]).collect();
+    for delivery in &level.deliveries {
+        names.extend(delivery.inventory.keys().map(|name| &name[..]));
+    }
+    if let Some(market) = &level.market {
+        names.extend(market.weights.keys().map(|name| &name[..]));
+    }
+    names.extend(level.script.iter().filter_map(|rule| match &rule.when {
+        ScriptTrigger::Placed(name) => Some(&name[..]),
+        _ => None,
+    }));
+    names
+}
+
+/// Validate a level, with the names of the worlds and the interludes it can reference.
+fn validate_level(
+    report: &mut ValidationReport,
+    subject: &str,
+    level: &LevelDescArchive,
+    buildables: &BuildableRegistry,
+    worlds: &HashSet<&str>,
+    interludes: &HashSet<&str>,
+) {
+    let mut unknown = false;
+    for name in level_buildable_names(level) {
+        if buildables.id(name).is_none() {
+            report.error(subject, format!("unknown buildable '{}'", name));
+            unknown = true;
+        }
+    }
+    if let Some(world) = &level.world {
+        if !worlds.contains(&world[..]) {
+            report.error(subject, format!("unknown world '{}'", world));
+        }
+    }
+    if let Some(interlude) = &level.interlude {
+        if !interludes.contains(&interlude[..]) {
+            report.error(subject, format!("unknown interlude '{}'", interlude));
+        }
+    }
+    if level.grid_size.x <= 0 || level.grid_size.y <= 0 {
+        report.error(
+            subject,
+            format!("invalid grid size {:?}", level.grid_size.to_array()),
+        );
+        return;
+    }
+    if level.victory_margin <= 0.0 {
+        report.error(
+            subject,
+            format!(
+                "impossible victory margin {}, no layout can be balanced within it",
+                level.victory_margin
+            ),
+        );
+        return;
+    }
+    if level.balance_factor <= 0.0 {
+        report.warning(
+            subject,
+            format!(
+                "balance factor {} does not tilt the plate",
+                level.balance_factor
+            ),
+        );
+    }
+    if level
+        .pivot
+        .offset
+        .abs()
+        .cmpgt(level.grid_size.as_vec2() / 2.0)
+        .any()
+    {
+        report.error(
+            subject,
+            format!(
+                "pivot offset {:?} outside the plate",
+                level.pivot.offset.to_array()
+            ),
+        );
+        return;
+    }
+    if let Some(seesaw) = &level.seesaw {
+        let min_x = -level.grid_size.x / 2;
+        if seesaw.split <= min_x || seesaw.split > min_x + level.grid_size.x - 1 {
+            report.error(
+                subject,
+                format!(
+                    "seesaw split {} leaves a plate without any column",
+                    seesaw.split
+                ),
+            );
+            return;
+        }
+        if level.pivot != Pivot::default() {
+            report.warning(
+                subject,
+                "pivot ignored, the seesaw plates balance on their own center".to_owned(),
+            );
+        }
+    }
+    if let TiltModel::Torque {
+        stiffness,
+        max_tilt,
+        ..
+    } = level.tilt_model
+    {
+        if stiffness <= 0.0 {
+            report.error(
+                subject,
+                format!("tilt stiffness {} must be positive", stiffness),
+            );
+            return;
+        }
+        if max_tilt.x <= 0.0 || max_tilt.y <= 0.0 {
+            report.warning(
+                subject,
+                format!(
+                    "max tilt {:?} locks the plate on at least one axis",
+                    max_tilt.to_array()
+                ),
+            );
+        } else if max_tilt.length() <= TOPPLE_TILT {
+            report.warning(
+                subject,
+                format!(
+                    "max tilt {:?} is too small for the plate to ever topple",
+                    max_tilt.to_array()
+                ),
+            );
+        }
+    }
+    if let Some(terrain) = &level.terrain {
+        if !terrain.fits(level.grid_size) {
+            report.error(
+                subject,
+                format!(
+                    "terrain heights do not match the grid size {:?}",
+                    level.grid_size.to_array()
+                ),
+            );
+            return;
+        }
+        if let Some(height) = terrain.heights.iter().flatten().find(|&&h| h < 0.0) {
+            report.error(
+                subject,
+                format!("terrain height {} sinks below the plate", height),
+            );
+            return;
+        }
+    }
+    let min_pos = -level.grid_size / 2;
+    let max_pos = min_pos + level.grid_size - IVec2::ONE;
+    for tile in &level.fragile {
+        if tile.pos.cmplt(min_pos).any() || tile.pos.cmpgt(max_pos).any() {
+            report.error(
+                subject,
+                format!("fragile tile {:?} outside the plate", tile.pos.to_array()),
+            );
+            return;
+        }
+        if tile.capacity <= 0.0 {
+            report.error(
+                subject,
+                format!(
+                    "fragile tile {:?} capacity {} must be positive",
+                    tile.pos.to_array(),
+                    tile.capacity
+                ),
+            );
+            return;
+        }
+    }
+    for conveyor in &level.conveyors {
+        if conveyor.pos.cmplt(min_pos).any() || conveyor.pos.cmpgt(max_pos).any() {
+            report.error(
+                subject,
+                format!("conveyor {:?} outside the plate", conveyor.pos.to_array()),
+            );
+            return;
+        }
+        if conveyor.direction.abs().x + conveyor.direction.abs().y != 1 {
+            report.error(
+                subject,
+                format!(
+                    "conveyor {:?} direction {:?} is not along a grid axis",
+                    conveyor.pos.to_array(),
+                    conveyor.direction.to_array()
+                ),
+            );
+            return;
+        }
+        if conveyor.interval <= 0.0 {
+            report.error(
+                subject,
+                format!(
+                    "conveyor {:?} interval {} must be positive",
+                    conveyor.pos.to_array(),
+                    conveyor.interval
+                ),
+            );
+            return;
+        }
+    }
+    if let Some(stabilize_time) = level.stabilize_time.filter(|&time| time <= 0.0) {
+        report.warning(
+            subject,
+            format!(
+                "stabilize time {} clears the level as soon as the plate is balanced",
+                stabilize_time
+            ),
+        );
+    }
+    if level.difficulty == Some(0) {
+        report.warning(
+            subject,
+            "difficulty 0 is not a rating, the easiest levels are rated 1".to_owned(),
+        );
+    }
+    if unknown {
+        // The solver would not place the unknown buildables
+        return;
+    }
+
+    // Buildables of the inventory and all the deliveries
+    let level_desc = level.to_desc(buildables, &HashMap::new());
+    let placements: Vec<_> = level_desc
+        .inventory
+        .iter()
+        .chain(
+            level_desc
+                .deliveries
+                .iter()
+                .flat_map(|delivery| delivery.inventory.iter()),
+        )
+        .collect();
+    let placed_count: u32 = placements.iter().map(|(_, &count)| count).sum();
+    let cell_count = (level.grid_size.x * level.grid_size.y) as u32;
+    if placed_count == 0 {
+        report.error(subject, "empty inventory, nothing to place".to_owned());
+        return;
+    }
+    if placed_count > cell_count {
+        report.error(
+            subject,
+            format!(
+                "{} buildables to place on only {} cells",
+                placed_count, cell_count
+            ),
+        );
+        return;
+    }
+
+    let layout = GridLayout {
+        size: level.grid_size,
+        placements: vec![],
+    };
+    let mut grid = match Grid::from_layout(&layout, buildables) {
+        Ok(grid) => grid,
+        Err(err) => {
+            report.error(subject, format!("invalid grid: {:?}", err));
+            return;
+        }
+    };
+    if let Some(terrain) = &level_desc.terrain {
+        grid.set_terrain(terrain.heights.clone(), terrain.lever_arm);
+    }
+    if !level_desc.fragile.is_empty() {
+        grid.set_capacities(&level_desc.fragile);
+    }
+    if !level_desc.conveyors.is_empty() {
+        grid.set_conveyors(&level_desc.conveyors);
+    }
+    grid.set_weight_scale(level_desc.weather.weight_scale());
+
+    // Trivial if even the worst layout, all the weight on the cell farthest from the pivot, is
+    // balanced
+    let max_radius = (grid.min_pos().x..=grid.max_pos().x)
+        .flat_map(|i| (grid.min_pos().y..=grid.max_pos().y).map(move |j| IVec2::new(i, j)))
+        .map(|pos| level.pivot.lever(grid.lever_pos(&pos)).length())
+        .fold(0.0, f32::max);
+    let total_weight: f32 = placements
+        .iter()
+        .map(|(&bref, &count)| count as f32 * buildables.get(bref).map_or(0.0, |b| b.max_weight()))
+        .sum();
+    if total_weight * grid.weight_scale() * max_radius < level.victory_margin {
+        report.warning(
+            subject,
+            format!(
+                "trivial level, any layout is within the victory margin {}",
+                level.victory_margin
+            ),
+        );
+    }
+
+    if level.seesaw.is_some() {
+        // The solver only knows about a single plate
+        return;
+    }
+    let mut inventory = Inventory::new();
+    inventory.reset_from_level(&level_desc);
+    if solver::solve(
+        &grid,
+        &inventory,
+        buildables,
+        &level.pivot,
+        level.victory_margin,
+    )
+    .is_none()
+    {
+        report.error(
+            subject,
+            "no balanced layout found, the level may be impossible".to_owned(),
+        );
+    }
+}
+
+/// Validate game data in the format of `levels.json`: references between buildables, worlds, and
+/// levels, and the solvability of each level.
+pub fn validate_game_data(json_content: &str) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let game_data = match GameDataArchive::from_json(json_content) {
+        Ok(game_data) => game_data,
+        Err(err) => {
+            let message = match serde_json::from_str::<serde_json::Value>(json_content) {
+                Err(err) => format!("invalid JSON: {}", err),
+                Ok(_) => format!("invalid game data: {:?}", err),
+            };
+            report.error("game data", message);
+            return report;
+        }
+    };
+    report.level_count = game_data.levels.len();
+    report.buildable_count = game_data.buildables.len();
+    let buildables = registry(&game_data);
+
+    let world_names = game_data.worlds.keys().map(|name| &name[..]).collect();
+    let interlude_names = game_data.interludes.keys().map(|name| &name[..]).collect();
+    let mut names = HashSet::new();
+    let mut used = HashSet::new();
+    for (index, level) in game_data.levels.iter().enumerate() {
+        let subject = format!("level #{} '{}'", index, level.name);
+        if !names.insert(&level.name[..]) {
+            report.error(&subject, "duplicate level name".to_owned());
+        }
+        used.extend(level_buildable_names(level));
+        validate_level(
+            &mut report,
+            &subject,
+            level,
+            &buildables,
+            &world_names,
+            &interlude_names,
+        );
+    }
+
+    let mut unused: Vec<_> = game_data
+        .buildables
+        .keys()
+        .filter(|name| !used.contains(&name[..]))
+        .collect();
+    unused.sort();
+    for name in unused {
+        report.warning(
+            &format!("buildable '{}'", name),
+            "not used by any level".to_owned(),
+        );
+    }
+    for (name, rules) in &game_data.buildables {
+        let subject = format!("buildable '{}'", name);
+        if rules.weight == 0.0 {
+            report.warning(&subject, "has no weight".to_owned());
+        }
+        if rules.weight_variance < 0.0 {
+            report.error(&subject, "has a negative weight variance".to_owned());
+        } else if rules.weight_variance > 0.0 && rules.weight_variance >= rules.weight.abs() {
+            report.error(
+                &subject,
+                format!(
+                    "weight variance {} could flip the sign of its weight {}",
+                    rules.weight_variance, rules.weight
+                ),
+            );
+        }
+        let skin_parts = rules.skins.iter().filter_map(|skin| skin.parts.as_ref());
+        for parts in std::iter::once(&rules.parts).chain(skin_parts) {
+            for (index, part) in parts.iter().enumerate() {
+                if let Err(err) = part.check() {
+                    report.error(&subject, format!("part #{} {}", index, err));
+                }
+            }
+        }
+    }
+    let mut worlds: Vec<_> = game_data.worlds.iter().collect();
+    worlds.sort_by_key(|(name, _)| *name);
+    let slots: HashSet<_> = game_data
+        .buildables
+        .values()
+        .flat_map(|rules| rules.palette.values())
+        .collect();
+    for (name, world) in worlds {
+        let mut unused: Vec<_> = world
+            .palette
+            .keys()
+            .filter(|slot| !slots.contains(slot))
+            .collect();
+        unused.sort();
+        for slot in unused {
+            report.warning(
+                &format!("world '{}'", name),
+                format!("palette slot '{}' not used by any buildable", slot),
+            );
+        }
+        if let Some(interlude) = &world.interlude {
+            if !game_data.interludes.contains_key(interlude) {
+                report.error(
+                    &format!("world '{}'", name),
+                    format!("unknown interlude '{}'", interlude),
+                );
+            }
+        }
+    }
+    let mut interludes: Vec<_> = game_data.interludes.iter().collect();
+    interludes.sort_by_key(|(name, _)| *name);
+    for (name, interlude) in interludes {
+        if interlude.pages.is_empty() {
+            report.warning(&format!("interlude '{}'", name), "has no pages".to_owned());
+        }
+    }
+    report
+}
+
+/// Validate a level shared outside of the game data, like from a level code, against the buildables
+/// and the worlds of the game. Shared levels can't reference interludes.
+pub fn validate_shared_level(
+    level: &LevelDescArchive,
+    buildables: &BuildableRegistry,
+    worlds: &HashSet<&str>,
+) -> ValidationReport {
+    let mut report = ValidationReport {
+        level_count: 1,
+        ..Default::default()
+    };
+    let subject = format!("level '{}'", level.name);
+    validate_level(
+        &mut report,
+        &subject,
+        level,
+        buildables,
+        worlds,
+        &HashSet::new(),
+    );
+    report
+}
+
+/// Validate the game data of a `levels.json` file, or of a folder containing one like a mod.
+/// Returns an error message if the file cannot be read.
+pub fn validate_path(path: &Path) -> Result<ValidationReport, String> {
+    let path = if path.is_dir() {
+        path.join("levels.json")
+    } else {
+        path.to_owned()
+    };
+    let json_content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read '{}': {}", path.display(), err))?;
+    Ok(validate_game_data(&json_content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_assets_are_valid() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let report = validate_path(&path).unwrap();
+        assert!(!report.has_errors(), "{}", report);
+        assert!(report.level_count > 0);
+    }
+
+    #[test]
+    fn findings() {
+        let json = r#"{
+            "schema_version": 2,
+            "buildables": {
+                "hut": { "name": "Hut", "model": "", "frame": "", "weight": 1.0 },
+                "tower": { "name": "Tower", "model": "", "frame": "", "weight": 3.0,
+                           "weight_variance": 4.0,
+                           "parts": [{ "model": "roof.glb#Scene0", "material": "roof.glb#Material0" }] }
+            },
+            "levels": [
+                { "name": "A", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "victory_margin": 0.0, "inventory": { "hut": 1 } },
+                { "name": "B", "grid_size": [2, 2], "balance_factor": 1.0,
+                  "victory_margin": 0.1, "inventory": { "hut": 1, "castle": 1 } },
+                { "name": "B", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "victory_margin": 100.0, "inventory": { "hut": 2 } },
+                { "name": "C", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "tilt": { "torque": { "max_tilt": [0.05, 0.05] } },
+                  "victory_margin": 0.1, "inventory": { "hut": 1 } },
+                { "name": "D", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "pivot": { "offset": [2.0, 0.0] },
+                  "victory_margin": 0.1, "inventory": { "hut": 1 } },
+                { "name": "E", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "terrain": { "heights": [[0.0, 0.0, 0.0], [0.0, 0.5, 0.0]] },
+                  "victory_margin": 0.1, "inventory": { "hut": 1 } },
+                { "name": "F", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "fragile": [{ "pos": [0, 2], "capacity": 1.0 }],
+                  "victory_margin": 0.1, "inventory": { "hut": 1 } },
+                { "name": "G", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "conveyors": [{ "pos": [0, 1], "direction": [1, 1] }],
+                  "victory_margin": 0.1, "inventory": { "hut": 1 } },
+                { "name": "H", "grid_size": [3, 3], "balance_factor": 1.0,
+                  "interlude": "prologue",
+                  "victory_margin": 0.1, "inventory": { "hut": 1 } }
+            ],
+            "worlds": {
+                "winter": { "sky_top": [1, 1, 1], "sky_horizon": [1, 1, 1],
+                            "palette": { "roof": [0.2, 0.3, 0.8] } }
+            },
+            "interludes": {
+                "epilogue": { "pages": [] }
+            }
+        }"#;
+        let report = validate_game_data(json);
+        let messages: Vec<_> = report.findings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "error: level #0 'A': impossible victory margin 0, no layout can be balanced \
+                within it",
+                "error: level #1 'B': unknown buildable 'castle'",
+                "error: level #2 'B': duplicate level name",
+                "warning: level #2 'B': trivial level, any layout is within the victory margin \
+                100",
+                "warning: level #3 'C': max tilt [0.05, 0.05] is too small for the plate to ever \
+                topple",
+                "error: level #4 'D': pivot offset [2.0, 0.0] outside the plate",
+                "error: level #5 'E': terrain heights do not match the grid size [3, 3]",
+                "error: level #6 'F': fragile tile [0, 2] outside the plate",
+                "error: level #7 'G': conveyor [0, 1] direction [1, 1] is not along a grid axis",
+                "error: level #8 'H': unknown interlude 'prologue'",
+                "warning: buildable 'tower': not used by any level",
+                "error: buildable 'tower': weight variance 4 could flip the sign of its weight 3",
+                "error: buildable 'tower': part #0 material only applies to a mesh",
+                "warning: world 'winter': palette slot 'roof' not used by any buildable",
+                "warning: interlude 'epilogue': has no pages",
+            ]
+        );
+        assert!(report.has_errors());
+    }
+}
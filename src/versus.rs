@@ -0,0 +1,273 @@
+use bevy::prelude::*;
+
+use crate::{
+    boot::UiResources,
+    cinematic::Hud,
+    controls::{ActiveControls, ControlScheme},
+    game::{GameEvent, GameMode, GameplaySystem, TOPPLE_TILT},
+    inventory::Inventory,
+    AppState, BalanceState, Cursor, Level, ResetPlateEvent, RestartLevelEvent,
+};
+
+/// Control scheme of each player.
+const PLAYER_CONTROLS: [ControlScheme; 2] =
+    [ControlScheme::KeyboardLeft, ControlScheme::KeyboardRight];
+
+/// Delay before the next round starts after a round ended, in seconds.
+const ROUND_END_DELAY: f32 = 3.0;
+
+/// Statistics of a single player over a versus session.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlayerStats {
+    /// Number of buildables placed.
+    pub placements: u32,
+    /// Number of rounds won.
+    pub wins: u32,
+    /// Number of rounds lost by tilting the plate over the limit.
+    pub losses: u32,
+    /// Number of rounds ending with an unbalanced plate without anyone tilting it over the limit.
+    pub draws: u32,
+}
+
+/// Resource holding the state of the two-player versus mode.
+pub struct Versus {
+    /// Index of the player whose turn it is.
+    current: usize,
+    /// Per-player statistics.
+    stats: [PlayerStats; 2],
+    /// Number of placements on the plate last frame.
+    last_placed_count: u32,
+    /// Timer before the next round, if the current round ended.
+    round_end_timer: Option<Timer>,
+    /// Outcome of the last round, for display.
+    message: String,
+}
+
+impl Versus {
+    pub fn new() -> Self {
+        Versus {
+            current: 0,
+            stats: [PlayerStats::default(); 2],
+            last_placed_count: 0,
+            round_end_timer: None,
+            message: String::new(),
+        }
+    }
+
+    /// Index of the player whose turn it is.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn stats(&self, player: usize) -> &PlayerStats {
+        &self.stats[player]
+    }
+
+    fn start_turn(&mut self, player: usize, controls: &mut ActiveControls) {
+        self.current = player;
+        controls.0 = PLAYER_CONTROLS[player];
+    }
+
+    fn end_round(&mut self, message: String) {
+        info!("Versus: {}", message);
+        self.message = message;
+        self.round_end_timer = Some(Timer::from_seconds(ROUND_END_DELAY, false));
+    }
+}
+
+/// Marker for the versus status text.
+#[derive(Component)]
+struct VersusText;
+
+fn versus_setup(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    ui_resources: Res<UiResources>,
+    mut versus: ResMut<Versus>,
+    mut controls: ResMut<ActiveControls>,
+) {
+    if *game_mode != GameMode::Versus {
+        return;
+    }
+    *versus = Versus::new();
+    versus.start_turn(0, &mut controls);
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(100.0),
+                    left: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: ui_resources.text_font(),
+                    font_size: 20.0,
+                    color: Color::rgb_u8(111, 188, 165),
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("VersusText"))
+        .insert(Hud)
+        .insert(VersusText);
+}
+
+/// Player 1 starts each new level.
+fn versus_reset(
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    game_mode: Res<GameMode>,
+    mut versus: ResMut<Versus>,
+    mut controls: ResMut<ActiveControls>,
+) {
+    if ev_reset_plate.iter().last().is_none() || *game_mode != GameMode::Versus {
+        return;
+    }
+    versus.last_placed_count = 0;
+    versus.round_end_timer = None;
+    versus.message.clear();
+    versus.start_turn(0, &mut controls);
+}
+
+/// Alternate turns after each placement, and end the round when a player tilts the plate over
+/// the limit or the inventory runs out.
+fn versus_turns(
+    time: Res<Time>,
+    game_mode: Res<GameMode>,
+    balance: Res<BalanceState>,
+    level: Res<Level>,
+    inventory: Res<Inventory>,
+    mut versus: ResMut<Versus>,
+    mut controls: ResMut<ActiveControls>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+    mut ev_game: EventWriter<GameEvent>,
+    mut query: Query<&mut Cursor>,
+) {
+    if *game_mode != GameMode::Versus {
+        return;
+    }
+    if level.desc().is_none() {
+        return;
+    }
+    let mut cursor = query.single_mut();
+
+    // Wait for the next round, the loser of the last round starting it
+    if let Some(timer) = &mut versus.round_end_timer {
+        if timer.tick(time.delta()).just_finished() {
+            versus.round_end_timer = None;
+            versus.message.clear();
+            versus.last_placed_count = 0;
+            cursor.set_enabled(true);
+            ev_restart.send(RestartLevelEvent);
+        }
+        return;
+    }
+
+    let placed_count = inventory.placed_count();
+    if placed_count < versus.last_placed_count {
+        // Level restarted
+        versus.last_placed_count = placed_count;
+        return;
+    }
+    if placed_count == versus.last_placed_count {
+        return;
+    }
+    versus.last_placed_count = placed_count;
+
+    let player = versus.current;
+    let other = 1 - player;
+    versus.stats[player].placements += 1;
+    if balance.tilt > TOPPLE_TILT {
+        ev_game.send(GameEvent::PlateToppled {
+            level_index: level.index(),
+        });
+        versus.stats[player].losses += 1;
+        versus.stats[other].wins += 1;
+        versus.end_round(format!("Player {} tipped the plate!", player + 1));
+        cursor.set_enabled(false);
+    } else if inventory.is_empty() {
+        if !balance.is_balanced() {
+            // Nobody tipped the plate but it's not balanced; replay the level
+            versus.stats[0].draws += 1;
+            versus.stats[1].draws += 1;
+            versus.end_round("Draw! The plate is not balanced.".to_owned());
+            versus.start_turn(other, &mut controls);
+        }
+        // Otherwise the level is cleared, and the game moves to the next one
+    } else {
+        versus.start_turn(other, &mut controls);
+    }
+}
+
+fn versus_text(versus: Res<Versus>, mut query: Query<&mut Text, With<VersusText>>) {
+    for mut text in query.iter_mut() {
+        let player = versus.current();
+        let stats = |index: usize| {
+            let stats = versus.stats(index);
+            format!(
+                "P{}: {}W {}L {}D ({} placed)",
+                index + 1,
+                stats.wins,
+                stats.losses,
+                stats.draws,
+                stats.placements
+            )
+        };
+        let status = if versus.message.is_empty() {
+            format!(
+                "Player {}'s turn ({})",
+                player + 1,
+                PLAYER_CONTROLS[player].description()
+            )
+        } else {
+            versus.message.clone()
+        };
+        text.sections[0].value = format!("{}\n{}\n{}", status, stats(0), stats(1));
+    }
+}
+
+fn versus_cleanup(
+    mut commands: Commands,
+    mut controls: ResMut<ActiveControls>,
+    query: Query<Entity, With<VersusText>>,
+) {
+    controls.0 = ControlScheme::All;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the local two-player versus mode, where players take turns placing buildables
+/// from a shared inventory, and the player tilting the plate over the limit loses.
+pub struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Versus::new())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(versus_setup))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(GameplaySystem::Reset)
+                    .before(GameplaySystem::Cursor)
+                    .with_system(versus_reset),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::VictoryCheck)
+                    .after(GameplaySystem::Balance)
+                    .with_system(versus_turns),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(GameplaySystem::Ui)
+                    .after(GameplaySystem::VictoryCheck)
+                    .with_system(versus_text),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(versus_cleanup));
+    }
+}
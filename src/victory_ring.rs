@@ -0,0 +1,184 @@
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use crate::{balance::BalanceState, game::GameplaySystem, rules::Rules, AppState, Level, Plate};
+
+/// Height of the victory ring above the plate origin, slightly above the top of the tiles
+/// to avoid z-fighting.
+const RING_HEIGHT: f32 = 0.06;
+
+/// Height of the COG marker above the plate origin.
+const MARKER_HEIGHT: f32 = 0.12;
+
+/// Marker for the translucent disc showing the victory margin around the plate center.
+#[derive(Debug, Component)]
+pub struct VictoryRing;
+
+/// Marker for the small sphere showing the live center of gravity (COG) of the plate.
+#[derive(Debug, Component)]
+pub struct CogMarker {
+    /// Material when the COG is within the victory margin.
+    inside_mat: Handle<StandardMaterial>,
+    /// Material when the COG is outside the victory margin.
+    outside_mat: Handle<StandardMaterial>,
+}
+
+/// Create a flat disc mesh of unit radius in the XZ plane, facing up (+Y).
+fn create_disc_mesh(segments: u32) -> Mesh {
+    let mut positions = Vec::with_capacity(segments as usize + 1);
+    let mut normals = Vec::with_capacity(segments as usize + 1);
+    let mut uvs = Vec::with_capacity(segments as usize + 1);
+    positions.push([0.0, 0.0, 0.0]);
+    normals.push([0.0, 1.0, 0.0]);
+    uvs.push([0.5, 0.5]);
+    for i in 0..segments {
+        let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+        let (s, c) = angle.sin_cos();
+        positions.push([c, 0.0, s]);
+        normals.push([0.0, 1.0, 0.0]);
+        uvs.push([0.5 + c * 0.5, 0.5 + s * 0.5]);
+    }
+    let mut indices = Vec::with_capacity(segments as usize * 3);
+    for i in 0..segments {
+        // Counter-clockwise when seen from above
+        indices.push(0);
+        indices.push(1 + (i + 1) % segments);
+        indices.push(1 + i);
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Spawn the victory ring and the COG marker as children of the plate, once the plate is spawned.
+fn spawn_victory_ring(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<Entity, Added<Plate>>,
+) {
+    for plate in query.iter() {
+        trace!("spawn_victory_ring() plate={:?}", plate);
+
+        // Victory ring
+        let ring_mesh = meshes.add(create_disc_mesh(48));
+        let ring_mat = materials.add(StandardMaterial {
+            base_color: Color::rgba(111. / 255., 188. / 255., 165. / 255., 0.35),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        });
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: ring_mesh,
+                material: ring_mat,
+                transform: Transform::from_xyz(0.0, RING_HEIGHT, 0.0),
+                ..Default::default()
+            })
+            .insert(Name::new("VictoryRing"))
+            .insert(VictoryRing)
+            .insert(NotShadowCaster)
+            .insert(Parent(plate));
+
+        // COG marker
+        let marker_mesh = meshes.add(Mesh::from(shape::Icosphere {
+            radius: 0.08,
+            subdivisions: 2,
+        }));
+        let inside_mat = materials.add(StandardMaterial {
+            base_color: Color::rgb(0.3, 0.9, 0.4),
+            unlit: true,
+            ..Default::default()
+        });
+        let outside_mat = materials.add(StandardMaterial {
+            base_color: Color::rgb(0.9, 0.3, 0.3),
+            unlit: true,
+            ..Default::default()
+        });
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: marker_mesh,
+                material: inside_mat.clone(),
+                transform: Transform::from_xyz(0.0, MARKER_HEIGHT, 0.0),
+                ..Default::default()
+            })
+            .insert(Name::new("CogMarker"))
+            .insert(CogMarker {
+                inside_mat,
+                outside_mat,
+            })
+            .insert(NotShadowCaster)
+            .insert(Parent(plate));
+    }
+}
+
+/// Update the victory ring radius from the current level's victory margin, and move the COG marker
+/// to the live COG position, colored depending on whether the COG is within the margin. The
+/// marker is hidden in the fog and in hard mode.
+fn update_victory_ring(
+    level: Res<Level>,
+    rules: Res<Rules>,
+    balance: Res<BalanceState>,
+    mut ring_query: Query<&mut Transform, (With<VictoryRing>, Without<CogMarker>)>,
+    mut marker_query: Query<
+        (
+            &CogMarker,
+            &mut Transform,
+            &mut Handle<StandardMaterial>,
+            &mut Visibility,
+        ),
+        Without<VictoryRing>,
+    >,
+) {
+    let level_desc = match level.desc() {
+        Some(level_desc) => level_desc,
+        None => return,
+    };
+    let margin = balance.victory_margin;
+    let pivot = level_desc.pivot.offset;
+    let cog = balance.offset;
+
+    // The ring and the COG are expressed in grid coordinates relative to the pivot; plate local
+    // space has Z pointing toward -Y.
+    for mut transform in ring_query.iter_mut() {
+        transform.translation = Vec3::new(pivot.x, RING_HEIGHT, -pivot.y);
+        transform.scale = Vec3::new(margin, 1.0, margin);
+    }
+
+    let hidden = level_desc.weather.hides_cog() || rules.hard;
+    for (marker, mut transform, mut material, mut visibility) in marker_query.iter_mut() {
+        if visibility.is_visible == hidden {
+            visibility.is_visible = !hidden;
+        }
+        transform.translation = Vec3::new(pivot.x + cog.x, MARKER_HEIGHT, -pivot.y - cog.y);
+        let mat = if balance.is_balanced() {
+            &marker.inside_mat
+        } else {
+            &marker.outside_mat
+        };
+        if *material != *mat {
+            *material = mat.clone();
+        }
+    }
+}
+
+/// Plugin to visualize the victory margin and the live center of gravity on the plate.
+pub struct VictoryRingPlugin;
+
+impl Plugin for VictoryRingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Ui)
+                .after(GameplaySystem::VictoryCheck)
+                .with_system(spawn_victory_ring)
+                .with_system(update_victory_ring),
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    boot::UiResources,
+    cheats::Cheats,
+    game::GameEvent,
+    inventory::Skin,
+    practice::Practice,
+    scores::{ScoreEvent, MAX_SCORE},
+    serialize::{BuildableId, BuildableRegistry},
+    storage, AppState, Level,
+};
+
+/// Save file of the wardrobe, in the profile storage.
+pub const WARDROBE_FILE: &str = "wardrobe.json";
+
+/// Achievement unlocking cosmetic skins.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "achievement", rename_all = "snake_case")]
+pub enum Achievement {
+    /// Clear the level with the given name.
+    ClearLevel { level: String },
+    /// Reach the maximum score on the level with the given name.
+    PerfectScore { level: String },
+}
+
+impl Achievement {
+    /// Short human-readable description of the achievement, for display.
+    pub fn description(&self) -> String {
+        match self {
+            Achievement::ClearLevel { level } => format!("clear level '{}'", level),
+            Achievement::PerfectScore { level } => format!("perfect score on '{}'", level),
+        }
+    }
+}
+
+/// Resource holding the unlocked achievements and the skin selected for each buildable.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Wardrobe {
+    /// Name of the selected skin, by buildable name. Buildables not listed use their default skin.
+    selected: HashMap<String, String>,
+    /// Achievements unlocked so far.
+    achievements: HashSet<Achievement>,
+}
+
+impl Wardrobe {
+    /// Load the wardrobe of the active profile saved by a previous session, if any.
+    pub fn load() -> Self {
+        storage::read(WARDROBE_FILE)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(wardrobe) => Some(wardrobe),
+                Err(err) => {
+                    let location = storage::location(WARDROBE_FILE);
+                    warn!("Failed to parse wardrobe '{}': {}", location, err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| storage::write(WARDROBE_FILE, &json).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            let location = storage::location(WARDROBE_FILE);
+            warn!("Failed to save wardrobe to '{}': {}", location, err);
+        }
+    }
+
+    pub fn is_unlocked(&self, skin: &Skin) -> bool {
+        skin.unlock()
+            .is_none_or(|achievement| self.achievements.contains(achievement))
+    }
+
+    /// Record an achievement. Returns `true` if it was not unlocked yet.
+    pub fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.achievements.insert(achievement) {
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Select the saved skin of each buildable of the registry, if still unlocked.
+    pub fn apply(&self, buildables: &mut BuildableRegistry) {
+        let ids: Vec<_> = buildables.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            let skin = buildables
+                .name(id)
+                .and_then(|name| self.selected.get(name))
+                .and_then(|skin_name| {
+                    let buildable = buildables.get(id)?;
+                    buildable
+                        .skins()
+                        .iter()
+                        .position(|skin| skin.name() == skin_name && self.is_unlocked(skin))
+                });
+            if let Some(buildable) = buildables.get_mut(id) {
+                buildable.select_skin(skin);
+            }
+        }
+    }
+
+    /// Select the next (or previous) unlocked skin of a buildable, wrapping around through its
+    /// default skin.
+    fn cycle_skin(&mut self, buildables: &mut BuildableRegistry, id: BuildableId, forward: bool) {
+        let name = match buildables.name(id) {
+            Some(name) => name.to_owned(),
+            None => return,
+        };
+        let buildable = match buildables.get_mut(id) {
+            Some(buildable) => buildable,
+            None => return,
+        };
+        // Option 0 is the default skin, option N is the skin at index N-1
+        let count = buildable.skins().len() + 1;
+        let mut option = buildable.selected_skin().map_or(0, |index| index + 1);
+        loop {
+            option = if forward {
+                (option + 1) % count
+            } else {
+                (option + count - 1) % count
+            };
+            if option == 0 || self.is_unlocked(&buildable.skins()[option - 1]) {
+                break;
+            }
+        }
+        if option == 0 {
+            buildable.select_skin(None);
+            self.selected.remove(&name);
+        } else {
+            buildable.select_skin(Some(option - 1));
+            self.selected
+                .insert(name, buildable.skins()[option - 1].name().to_owned());
+        }
+        self.save();
+    }
+}
+
+/// Resource holding the state of the wardrobe menu.
+pub struct WardrobeMenu {
+    /// Root entity of the menu panel, if open.
+    entity: Option<Entity>,
+    /// Index of the highlighted buildable.
+    index: usize,
+}
+
+impl WardrobeMenu {
+    pub fn new() -> Self {
+        WardrobeMenu {
+            entity: None,
+            index: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.entity.is_some()
+    }
+}
+
+/// Marker for the text of the wardrobe menu.
+#[derive(Component)]
+struct WardrobeText;
+
+/// Unlock the achievements of cleared levels and perfect scores.
+fn unlock_achievements(
+    mut ev_game: EventReader<GameEvent>,
+    mut ev_score: EventReader<ScoreEvent>,
+    level: Res<Level>,
+    cheats: Res<Cheats>,
+    practice: Res<Practice>,
+    mut wardrobe: ResMut<Wardrobe>,
+) {
+    if cheats.is_used() || practice.is_used() {
+        return;
+    }
+    let mut achievements = vec![];
+    if ev_game
+        .iter()
+        .filter(|ev| matches!(ev, GameEvent::LevelCleared { .. }))
+        .last()
+        .is_some()
+    {
+        if let Some(level_desc) = level.desc() {
+            achievements.push(Achievement::ClearLevel {
+                level: level_desc.name.clone(),
+            });
+        }
+    }
+    for ev in ev_score.iter() {
+        if ev.0.score >= MAX_SCORE {
+            achievements.push(Achievement::PerfectScore {
+                level: ev.0.level.clone(),
+            });
+        }
+    }
+    for achievement in achievements {
+        let description = achievement.description();
+        if wardrobe.unlock(achievement) {
+            info!("Achievement unlocked: {}", description);
+        }
+    }
+}
+
+/// Open or close the wardrobe menu.
+fn toggle_wardrobe(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    buildables: Res<BuildableRegistry>,
+    ui_resources: Res<UiResources>,
+    mut menu: ResMut<WardrobeMenu>,
+) {
+    // Wait for the game data to be loaded
+    if buildables.iter().next().is_none() {
+        return;
+    }
+    if let Some(entity) = menu.entity {
+        if keyboard_input.just_pressed(KeyCode::W) || keyboard_input.just_pressed(KeyCode::Escape) {
+            commands.entity(entity).despawn_recursive();
+            menu.entity = None;
+            keyboard_input.reset(KeyCode::Escape);
+        }
+        return;
+    }
+    if !keyboard_input.just_pressed(KeyCode::W) {
+        return;
+    }
+    let entity = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.0)),
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+            ..Default::default()
+        })
+        .insert(Name::new("Wardrobe"))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: ui_resources.text_font(),
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(WardrobeText);
+        })
+        .id();
+    menu.entity = Some(entity);
+    menu.index = 0;
+}
+
+/// Navigate the wardrobe menu and change the skin of the highlighted buildable.
+fn wardrobe_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut buildables: ResMut<BuildableRegistry>,
+    mut wardrobe: ResMut<Wardrobe>,
+    mut menu: ResMut<WardrobeMenu>,
+    mut query: Query<&mut Text, With<WardrobeText>>,
+) {
+    let mut text = match query.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let ids: Vec<_> = buildables.iter().map(|(id, _)| id).collect();
+    if ids.is_empty() {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        menu.index = (menu.index + ids.len() - 1) % ids.len();
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        menu.index = (menu.index + 1) % ids.len();
+    }
+    let id = ids[menu.index.min(ids.len() - 1)];
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        wardrobe.cycle_skin(&mut buildables, id, false);
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        wardrobe.cycle_skin(&mut buildables, id, true);
+    }
+
+    let mut lines = vec![
+        "Wardrobe".to_owned(),
+        "[UP]/[DOWN] to choose a buildable, [LEFT]/[RIGHT] to change skin, [W] to close".to_owned(),
+        String::new(),
+    ];
+    for (index, (id, buildable)) in buildables.iter().enumerate() {
+        let selected = buildable.selected_skin();
+        let mut options = vec![if selected.is_none() {
+            "[Default]".to_owned()
+        } else {
+            "Default".to_owned()
+        }];
+        for (skin_index, skin) in buildable.skins().iter().enumerate() {
+            options.push(if !wardrobe.is_unlocked(skin) {
+                format!(
+                    "{} (locked: {})",
+                    skin.name(),
+                    skin.unlock()
+                        .map(Achievement::description)
+                        .unwrap_or_default()
+                )
+            } else if selected == Some(skin_index) {
+                format!("[{}]", skin.name())
+            } else {
+                skin.name().to_owned()
+            });
+        }
+        let marker = if index == menu.index { ">" } else { " " };
+        lines.push(format!(
+            "{} {}: {}",
+            marker,
+            buildable.name(),
+            options.join("  ")
+        ));
+    }
+    text.sections[0].value = lines.join("\n");
+}
+
+fn wardrobe_cleanup(mut commands: Commands, mut menu: ResMut<WardrobeMenu>) {
+    if let Some(entity) = menu.entity.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Plugin for the cosmetic skins of the buildables, unlocked through achievements and selected
+/// in the wardrobe menu of the main menu.
+pub struct WardrobePlugin;
+
+impl Plugin for WardrobePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Wardrobe::load())
+            .insert_resource(WardrobeMenu::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(toggle_wardrobe.label("toggle_wardrobe"))
+                    .with_system(wardrobe_menu.after("toggle_wardrobe")),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(wardrobe_cleanup))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame).with_system(unlock_achievements),
+            );
+    }
+}
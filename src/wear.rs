@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+
+use crate::{game::GameplaySystem, AppState, Grid};
+
+/// Cell weight at which a tile shows its full wear.
+const MAX_WEAR_WEIGHT: f32 = 3.0;
+
+/// Depth a fully worn tile sags below the plate.
+const MAX_SAG: f32 = 0.03;
+
+/// Brightness of the tile material at full wear, relative to an empty tile.
+const MIN_BRIGHTNESS: f32 = 0.6;
+
+/// Number of distinct tints between an empty tile and a fully worn one.
+const WEAR_STEPS: usize = 8;
+
+/// Tile of the plate, at the given grid coordinates.
+#[derive(Debug, Component)]
+pub struct Tile {
+    pub pos: IVec2,
+    /// Wear step currently displayed, if any.
+    step: Option<usize>,
+}
+
+impl Tile {
+    pub fn new(pos: IVec2) -> Self {
+        Tile { pos, step: None }
+    }
+}
+
+/// Resource caching the tinted variants of the grid material, one per wear step.
+#[derive(Debug, Default)]
+pub struct TileWear {
+    /// Grid material the variants were derived from.
+    base: Handle<StandardMaterial>,
+    materials: Vec<Handle<StandardMaterial>>,
+}
+
+impl TileWear {
+    pub fn new() -> Self {
+        TileWear::default()
+    }
+
+    fn material(
+        &mut self,
+        base: &Handle<StandardMaterial>,
+        step: usize,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        if self.base != *base || self.materials.is_empty() {
+            self.base = base.clone();
+            self.materials.clear();
+            if let Some(base_material) = materials.get(base).cloned() {
+                for step in 0..WEAR_STEPS {
+                    let t = step as f32 / (WEAR_STEPS - 1) as f32;
+                    let brightness = 1.0 - (1.0 - MIN_BRIGHTNESS) * t;
+                    self.materials.push(materials.add(StandardMaterial {
+                        base_color: base_material.base_color * brightness,
+                        ..base_material.clone()
+                    }));
+                }
+            }
+        }
+        self.materials.get(step).unwrap_or(base).clone()
+    }
+}
+
+/// Wear step of a cell from its weight.
+fn wear_step(weight: f32) -> usize {
+    let t = (weight.abs() / MAX_WEAR_WEIGHT).clamp(0.0, 1.0);
+    (t * (WEAR_STEPS - 1) as f32).round() as usize
+}
+
+/// Darken and sag the tiles proportionally to the weight placed on them. Only the tiles whose
+/// wear changed are updated.
+fn update_tile_wear(
+    grid: Res<Grid>,
+    mut wear: ResMut<TileWear>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<(&mut Tile, &mut Transform, &mut Handle<StandardMaterial>)>,
+) {
+    let added = query.iter().any(|(tile, _, _)| tile.step.is_none());
+    if !grid.is_changed() && !added {
+        return;
+    }
+    for (mut tile, mut transform, mut material) in query.iter_mut() {
+        // Tiles of the previous grid are despawned at the end of the frame
+        if grid.clamp(tile.pos) != tile.pos {
+            continue;
+        }
+        let step = wear_step(grid.cell(&tile.pos).weight);
+        if tile.step == Some(step) {
+            continue;
+        }
+        tile.step = Some(step);
+        let t = step as f32 / (WEAR_STEPS - 1) as f32;
+        transform.translation.y = grid.tile_transform(&tile.pos).translation.y - MAX_SAG * t;
+        *material = wear.material(grid.material(), step, &mut materials);
+    }
+}
+
+/// Plugin giving feedback on the load distribution by darkening and sagging the tiles of the
+/// plate under heavy buildables.
+pub struct TileWearPlugin;
+
+impl Plugin for TileWearPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TileWear::new()).add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .after(GameplaySystem::Balance)
+                .with_system(update_tile_wear),
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioSource};
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+use crate::{
+    balance::BalanceState,
+    game::{run_if_playing, GameplaySystem, TOPPLE_TILT},
+    journal::LevelJournal,
+    level::PendingDespawn,
+    slide_buildable, AppState, Config, Grid, Level, Placed, ResetPlateEvent,
+};
+
+/// Multiplier applied to the weight of the buildables placed in the rain.
+pub const RAIN_WEIGHT_SCALE: f32 = 1.1;
+
+/// Plate tilt angle, in radians, over which the buildables slide on ice.
+pub const ICE_SLIDE_TILT: f32 = 0.6 * TOPPLE_TILT;
+
+/// Time in seconds the plate needs to stay tilted over [`ICE_SLIDE_TILT`] before the buildables
+/// slide one more cell.
+const ICE_SLIDE_INTERVAL: f32 = 1.0;
+
+/// Number of rain drops falling around the plate.
+const RAIN_DROPS: u32 = 240;
+
+/// Radius of the rain column around the plate.
+const RAIN_RADIUS: f32 = 9.0;
+
+/// Height the rain drops fall from.
+const RAIN_HEIGHT: f32 = 12.0;
+
+/// Falling speed of the rain drops, in units per second.
+const RAIN_SPEED: f32 = 10.0;
+
+/// Number of fog banks around the plate.
+const FOG_BANKS: u32 = 10;
+
+/// Distance of the fog banks to the plate.
+const FOG_DISTANCE: f32 = 9.0;
+
+/// Weather of a level, modifying its gameplay on top of its ambience.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    Clear,
+    /// The buildables soak up water, weighing [`RAIN_WEIGHT_SCALE`] times more.
+    Rain,
+    /// The buildables slide one cell downhill while the plate tilts over [`ICE_SLIDE_TILT`].
+    Ice,
+    /// The center of gravity marker is hidden.
+    Fog,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Weather::Clear
+    }
+}
+
+impl Weather {
+    /// Multiplier applied to the weight of the buildables placed.
+    pub fn weight_scale(self) -> f32 {
+        match self {
+            Weather::Rain => RAIN_WEIGHT_SCALE,
+            _ => 1.0,
+        }
+    }
+
+    /// Do the buildables slide on a tilted plate?
+    pub fn is_slippery(self) -> bool {
+        self == Weather::Ice
+    }
+
+    /// Is the center of gravity marker hidden?
+    pub fn hides_cog(self) -> bool {
+        self == Weather::Fog
+    }
+
+    /// Path to the looping ambience sound, relative to the assets/ folder, if any.
+    fn ambience(self) -> Option<&'static str> {
+        match self {
+            Weather::Rain => Some("audio/rain.wav"),
+            _ => None,
+        }
+    }
+}
+
+/// Grid direction the buildables slide toward on ice, along the steepest axis of the plate, if
+/// the plate tilts over [`ICE_SLIDE_TILT`].
+pub fn downhill(angles: Vec2) -> Option<IVec2> {
+    if angles.length() <= ICE_SLIDE_TILT {
+        None
+    } else if angles.x.abs() >= angles.y.abs() {
+        Some(IVec2::new(angles.x.signum() as i32, 0))
+    } else {
+        Some(IVec2::new(0, angles.y.signum() as i32))
+    }
+}
+
+/// Resource tracking the weather currently displayed.
+#[derive(Debug)]
+pub struct WeatherState {
+    weather: Weather,
+    /// Root entity of the ambience.
+    root: Option<Entity>,
+    /// Time the plate stayed tilted over [`ICE_SLIDE_TILT`] on ice.
+    slide_timer: Timer,
+}
+
+impl WeatherState {
+    pub fn new() -> Self {
+        WeatherState {
+            weather: Weather::Clear,
+            root: None,
+            slide_timer: Timer::from_seconds(ICE_SLIDE_INTERVAL, true),
+        }
+    }
+}
+
+/// Audio channel for the weather ambience, looping along the background music.
+pub struct WeatherChannel;
+
+/// Marker for a rain drop, falling in a loop.
+#[derive(Component)]
+struct RainDrop;
+
+/// Spawn the ambience of the weather of the level whenever the level changes weather.
+fn update_weather(
+    mut commands: Commands,
+    mut ev_reset_plate: EventReader<ResetPlateEvent>,
+    level: Res<Level>,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    audio: Res<AudioChannel<WeatherChannel>>,
+    mut state: ResMut<WeatherState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if ev_reset_plate.iter().last().is_none() {
+        return;
+    }
+    state.slide_timer.reset();
+    let weather = level.desc().map_or(Weather::Clear, |desc| desc.weather);
+    if weather == state.weather {
+        return;
+    }
+    if let Some(root) = state.root.take() {
+        commands.entity(root).despawn_recursive();
+    }
+    audio.stop();
+    state.weather = weather;
+    debug!("Weather: {:?}", weather);
+
+    if let Some(path) = weather.ambience() {
+        if config.sound.enabled {
+            let source: Handle<AudioSource> = asset_server.load(path);
+            audio.set_volume(config.sound.volume * 0.5);
+            audio.play_looped(source);
+        }
+    }
+
+    let root = commands
+        .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+        .insert(Name::new(format!("Weather({:?})", weather)))
+        .id();
+    state.root = Some(root);
+
+    // Spread the rain drops and the fog banks around the plate with the golden angle
+    let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+    match weather {
+        Weather::Rain => {
+            let mesh = meshes.add(Mesh::from(shape::Box::new(0.015, 0.35, 0.015)));
+            let material = materials.add(StandardMaterial {
+                base_color: Color::rgba(0.7, 0.8, 0.95, 0.5),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..Default::default()
+            });
+            for index in 0..RAIN_DROPS {
+                let angle = index as f32 * golden_angle;
+                let radius = RAIN_RADIUS * ((index as f32 + 0.5) / RAIN_DROPS as f32).sqrt();
+                let height = RAIN_HEIGHT * (index as f32 * 0.618_034).fract();
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_xyz(
+                            radius * angle.cos(),
+                            height,
+                            radius * angle.sin(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(RainDrop)
+                    .insert(NotShadowCaster)
+                    .insert(NotShadowReceiver)
+                    .insert(Parent(root));
+            }
+        }
+        Weather::Fog => {
+            let quad = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(12.0, 4.0))));
+            let material = materials.add(StandardMaterial {
+                base_color: Color::rgba(0.9, 0.92, 0.95, 0.35),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                cull_mode: None,
+                ..Default::default()
+            });
+            for index in 0..FOG_BANKS {
+                let angle = index as f32 * golden_angle;
+                let height = 0.5 + ((index * 3) % 4) as f32 * 0.5;
+                let pos = Vec3::new(
+                    FOG_DISTANCE * angle.cos(),
+                    height,
+                    FOG_DISTANCE * angle.sin(),
+                );
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: quad.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(pos)
+                            .looking_at(Vec3::new(0.0, height, 0.0), Vec3::Y),
+                        ..Default::default()
+                    })
+                    .insert(NotShadowCaster)
+                    .insert(NotShadowReceiver)
+                    .insert(Parent(root));
+            }
+        }
+        Weather::Clear | Weather::Ice => {}
+    }
+}
+
+fn rain_system(time: Res<Time>, mut query: Query<&mut Transform, With<RainDrop>>) {
+    for mut transform in query.iter_mut() {
+        transform.translation.y -= RAIN_SPEED * time.delta_seconds();
+        if transform.translation.y < 0.0 {
+            transform.translation.y += RAIN_HEIGHT;
+        }
+    }
+}
+
+/// Slide the buildables one cell downhill on ice, each time the plate stays tilted over
+/// [`ICE_SLIDE_TILT`] for [`ICE_SLIDE_INTERVAL`]. The buildables farthest downhill slide first,
+/// making room for the others, and stop against the edge of the plate or an occupied cell.
+fn ice_slide_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    level: Res<Level>,
+    balance: Res<BalanceState>,
+    mut grid: ResMut<Grid>,
+    mut state: ResMut<WeatherState>,
+    mut journal: ResMut<LevelJournal>,
+    mut query: Query<(Entity, &mut Placed, &Transform), Without<PendingDespawn>>,
+) {
+    match level.desc() {
+        Some(level_desc) if level_desc.weather.is_slippery() && level_desc.seesaw.is_none() => {}
+        _ => return,
+    }
+    let dir = match downhill(balance.tilt_angles) {
+        Some(dir) => dir,
+        None => {
+            state.slide_timer.reset();
+            return;
+        }
+    };
+    if !state.slide_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let mut placed: Vec<_> = query.iter_mut().collect();
+    placed.sort_by_key(|(_, placed, _)| -placed.0.dot(dir));
+    for (entity, placed, transform) in placed.iter_mut() {
+        let to = placed.0 + dir;
+        if grid.clamp(to) == to && grid.can_spawn_item(&to) {
+            slide_buildable(
+                &mut commands,
+                &mut grid,
+                &mut journal,
+                *entity,
+                placed,
+                transform,
+                to,
+            );
+        }
+    }
+}
+
+fn weather_cleanup(
+    mut commands: Commands,
+    audio: Res<AudioChannel<WeatherChannel>>,
+    mut state: ResMut<WeatherState>,
+) {
+    if let Some(root) = state.root.take() {
+        commands.entity(root).despawn_recursive();
+    }
+    audio.stop();
+    state.weather = Weather::Clear;
+}
+
+/// Plugin for the weather of the levels, applying its gameplay modifiers and its ambience.
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_channel::<WeatherChannel>()
+            .insert_resource(WeatherState::new())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(update_weather)
+                    .with_system(rain_system),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_playing)
+                    .label(GameplaySystem::Placement)
+                    .after(GameplaySystem::Cursor)
+                    .with_system(ice_slide_system),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(weather_cleanup));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let weather: Weather = serde_json::from_str(r#""rain""#).unwrap();
+        assert_eq!(weather, Weather::Rain);
+        assert_eq!(weather.weight_scale(), RAIN_WEIGHT_SCALE);
+        assert_eq!(Weather::default().weight_scale(), 1.0);
+    }
+
+    #[test]
+    fn slide() {
+        assert_eq!(downhill(Vec2::ZERO), None);
+        assert_eq!(downhill(Vec2::new(0.5 * ICE_SLIDE_TILT, 0.0)), None);
+        assert_eq!(downhill(Vec2::new(ICE_SLIDE_TILT, 0.1)), Some(IVec2::X));
+        assert_eq!(downhill(Vec2::new(0.1, -ICE_SLIDE_TILT)), Some(-IVec2::Y));
+    }
+}
@@ -0,0 +1,309 @@
+use bevy::{prelude::*, render::camera::PerspectiveProjection};
+
+use crate::{
+    boot::UiResources,
+    game::{GameEvent, GameplaySystem},
+    units::format_weight,
+    AppState, Grid, Level, Plate,
+};
+
+/// Duration of the fade-out at the end of the lifetime of a label, in seconds.
+const FADE_TIME: f32 = 0.4;
+
+/// Margin kept between the labels clamped to the screen edges and the edges, in pixels.
+const SCREEN_MARGIN: f32 = 8.0;
+
+/// Lifetime of the weight popups shown on placement, in seconds.
+const POPUP_LIFETIME: f32 = 1.2;
+
+/// Speed the weight popups rise at, in world units per second.
+const POPUP_RISE: f32 = 0.5;
+
+/// Elevation of the weight popups above the cell of the placed buildable.
+const POPUP_ELEVATION: f32 = 0.6;
+
+/// What a [`WorldLabel`] is attached to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelAnchor {
+    /// Follow an entity, with an offset in its local space. The label is despawned along with
+    /// the entity.
+    Entity(Entity),
+    /// Stay at a fixed world position.
+    Position(Vec3),
+}
+
+/// Text node following a position of the 3D world on screen, projected through the 3D camera
+/// each frame. Insert on a [`TextBundle`] with an absolute position; the label is centered
+/// horizontally above the projected point.
+#[derive(Debug, Component)]
+pub struct WorldLabel {
+    anchor: LabelAnchor,
+    /// Offset from the anchor, in the local space of the anchor entity if any.
+    offset: Vec3,
+    /// Speed the label rises at, in world units per second.
+    rise: f32,
+    /// Time before the label fades out and is despawned, in seconds, if any.
+    lifetime: Option<f32>,
+    /// Keep the label on screen at the nearest edge instead of hiding it once off-screen.
+    clamp: bool,
+    /// Time since the label was spawned, in seconds.
+    age: f32,
+}
+
+impl WorldLabel {
+    pub fn new(anchor: LabelAnchor) -> Self {
+        WorldLabel {
+            anchor,
+            offset: Vec3::ZERO,
+            rise: 0.0,
+            lifetime: None,
+            clamp: false,
+            age: 0.0,
+        }
+    }
+
+    /// Offset the label from its anchor, in the local space of the anchor entity if any.
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Fade out and despawn the label after the given time, in seconds.
+    pub fn with_lifetime(mut self, lifetime: f32) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+
+    /// Make the label rise at the given speed, in world units per second.
+    pub fn with_rise(mut self, rise: f32) -> Self {
+        self.rise = rise;
+        self
+    }
+
+    /// Keep the label on screen at the nearest edge once its anchor is off-screen.
+    pub fn clamped(mut self) -> Self {
+        self.clamp = true;
+        self
+    }
+
+    /// Has the lifetime of the label elapsed?
+    fn is_expired(&self) -> bool {
+        self.lifetime.map_or(false, |lifetime| self.age >= lifetime)
+    }
+
+    /// Opacity of the label, fading out over the last [`FADE_TIME`] of its lifetime.
+    fn alpha(&self) -> f32 {
+        self.lifetime.map_or(1.0, |lifetime| {
+            ((lifetime - self.age) / FADE_TIME).clamp(0.0, 1.0)
+        })
+    }
+}
+
+/// Project a world position on the screen, in pixels from the bottom left corner like the UI
+/// positions. Returns `None` for the positions behind the camera.
+pub fn project(view_proj: Mat4, world_pos: Vec3, screen_size: Vec2) -> Option<Vec2> {
+    let clip = view_proj * world_pos.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip.truncate() / clip.w;
+    Some((ndc.truncate() + Vec2::ONE) / 2.0 * screen_size)
+}
+
+/// Position of the bottom left corner of a label of the given size centered above a screen
+/// position, clamped to the screen edges if `clamp`. Returns `None` if the label would be off
+/// the screen without clamping.
+pub fn place_label(pos: Vec2, size: Vec2, screen_size: Vec2, clamp: bool) -> Option<Vec2> {
+    let corner = pos - Vec2::new(size.x / 2.0, 0.0);
+    let min = Vec2::splat(SCREEN_MARGIN);
+    let max = (screen_size - size - SCREEN_MARGIN).max(min);
+    if clamp {
+        Some(corner.clamp(min, max))
+    } else if corner.cmplt(-size).any() || corner.cmpgt(screen_size).any() {
+        None
+    } else {
+        Some(corner)
+    }
+}
+
+/// Move the labels to the projection of their anchor, fade them out, and despawn them once
+/// expired or once their anchor entity is gone.
+fn update_world_labels(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PerspectiveProjection>>,
+    anchor_query: Query<&GlobalTransform, Without<WorldLabel>>,
+    mut query: Query<(
+        Entity,
+        &mut WorldLabel,
+        &mut Style,
+        &mut Visibility,
+        &mut Text,
+        &Node,
+    )>,
+) {
+    let screen_size = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width(), window.height()),
+        None => return,
+    };
+    let view_proj = camera_query
+        .iter()
+        .next()
+        .map(|(camera, transform)| camera.projection_matrix * transform.compute_matrix().inverse());
+    for (entity, mut label, mut style, mut visibility, mut text, node) in query.iter_mut() {
+        label.age += time.delta_seconds();
+        let anchor_pos = match label.anchor {
+            LabelAnchor::Entity(anchor) => match anchor_query.get(anchor) {
+                Ok(transform) => transform.mul_vec3(label.offset),
+                Err(_) => {
+                    commands.entity(entity).despawn_recursive();
+                    continue;
+                }
+            },
+            LabelAnchor::Position(pos) => pos + label.offset,
+        };
+        if label.is_expired() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let world_pos = anchor_pos + Vec3::Y * label.rise * label.age;
+        let corner = view_proj
+            .and_then(|view_proj| project(view_proj, world_pos, screen_size))
+            .and_then(|pos| place_label(pos, node.size, screen_size, label.clamp));
+        if visibility.is_visible != corner.is_some() {
+            visibility.is_visible = corner.is_some();
+        }
+        if let Some(corner) = corner {
+            let (left, bottom) = (Val::Px(corner.x), Val::Px(corner.y));
+            if style.position.left != left || style.position.bottom != bottom {
+                style.position.left = left;
+                style.position.bottom = bottom;
+            }
+        }
+        let alpha = label.alpha();
+        if text
+            .sections
+            .iter()
+            .any(|section| section.style.color.a() != alpha)
+        {
+            for section in text.sections.iter_mut() {
+                section.style.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+/// Show a popup with the weight of each placed buildable, rising from its cell and fading out.
+fn weight_popups(
+    mut commands: Commands,
+    mut ev_game: EventReader<GameEvent>,
+    grid: Res<Grid>,
+    level: Res<Level>,
+    ui_resources: Res<UiResources>,
+    plate_query: Query<Entity, With<Plate>>,
+) {
+    let plate = match plate_query.get_single() {
+        Ok(plate) => plate,
+        Err(_) => return,
+    };
+    for ev in ev_game.iter() {
+        let pos = match ev {
+            GameEvent::BuildablePlaced { pos, .. } => *pos,
+            _ => continue,
+        };
+        let weight = grid.cell(&pos).weight;
+        let sign = if weight >= 0.0 { "+" } else { "" };
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    format!("{}{}", sign, format_weight(weight, level.weight_unit())),
+                    TextStyle {
+                        font: ui_resources.text_font(),
+                        font_size: 28.0,
+                        color: Color::rgb_u8(255, 220, 120),
+                    },
+                    TextAlignment::default(),
+                ),
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(Name::new("WeightPopup"))
+            .insert(
+                WorldLabel::new(LabelAnchor::Entity(plate))
+                    .with_offset(grid.translation(&pos, POPUP_ELEVATION))
+                    .with_lifetime(POPUP_LIFETIME)
+                    .with_rise(POPUP_RISE),
+            );
+    }
+}
+
+/// Plugin for the [`WorldLabel`]s following positions of the 3D world on screen, and the weight
+/// popups shown with them on placement.
+pub struct WorldLabelPlugin;
+
+impl Plugin for WorldLabelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_world_labels).add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .label(GameplaySystem::Ui)
+                .after(GameplaySystem::VictoryCheck)
+                .with_system(weight_popups),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projection() {
+        let screen_size = Vec2::new(800.0, 600.0);
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let proj = Mat4::perspective_infinite_reverse_rh(1.0, 800.0 / 600.0, 0.1);
+        let view_proj = proj * view;
+        let center = project(view_proj, Vec3::ZERO, screen_size).unwrap();
+        assert!((center - screen_size / 2.0).length() < 1e-3);
+        let above = project(view_proj, Vec3::Y, screen_size).unwrap();
+        assert!(above.y > center.y);
+        assert!((above.x - center.x).abs() < 1e-3);
+        assert!(project(view_proj, Vec3::new(0.0, 0.0, 10.0), screen_size).is_none());
+
+        let size = Vec2::new(40.0, 20.0);
+        assert_eq!(
+            place_label(Vec2::new(400.0, 300.0), size, screen_size, false),
+            Some(Vec2::new(380.0, 300.0))
+        );
+        assert_eq!(
+            place_label(Vec2::new(-100.0, 300.0), size, screen_size, false),
+            None
+        );
+        assert_eq!(
+            place_label(Vec2::new(-100.0, 900.0), size, screen_size, true),
+            Some(Vec2::new(SCREEN_MARGIN, 600.0 - 20.0 - SCREEN_MARGIN))
+        );
+    }
+
+    #[test]
+    fn fade() {
+        let mut label = WorldLabel::new(LabelAnchor::Position(Vec3::ZERO));
+        label.age = 10.0;
+        assert_eq!(label.alpha(), 1.0);
+        assert!(!label.is_expired());
+
+        let mut label = label.with_lifetime(1.0);
+        label.age = 0.5;
+        assert_eq!(label.alpha(), 1.0);
+        label.age = 0.8;
+        assert!((label.alpha() - 0.5).abs() < 1e-5);
+        assert!(!label.is_expired());
+        label.age = 1.0;
+        assert_eq!(label.alpha(), 0.0);
+        assert!(label.is_expired());
+    }
+}
@@ -0,0 +1,253 @@
+//! Headless integration tests, running the game without a renderer, window, or audio device,
+//! and driving it by injecting input events and stepping the schedule manually.
+
+use bevy::{
+    asset::AssetPlugin,
+    ecs::event::{Events, ManualEventReader},
+    input::{keyboard::KeyboardInput, ElementState, InputPlugin},
+    prelude::*,
+    scene::ScenePlugin,
+    text::FontLoader,
+    transform::TransformPlugin,
+};
+use bevy_kira_audio::AudioPlugin;
+use std::{
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+use libracity::{
+    add_game_plugins, solve_current_level, AppState, DefeatReason, Game, GameEvent, GameSequence,
+    InterludePlayer, Onboarding, PlaceBuildableEvent,
+};
+
+/// Real time between two updates. The game sequences run on [`Time`], so the test needs to let
+/// some actual time elapse.
+const STEP: Duration = Duration::from_millis(20);
+
+/// Time allowed for the whole game before the test fails.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Lock held by each test for its whole run. The tests share the data folder of the process, so
+/// run one after the other, each starting from an empty data folder.
+static DATA_LOCK: Mutex<()> = Mutex::new(());
+
+/// Data folder of the save files of the tests, in the temporary folder instead of the one of the
+/// player.
+fn test_data_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("libracity-tests-{}", std::process::id()))
+}
+
+/// Build the game app with the minimal engine plugins, and the asset types the game uses
+/// without the renderer.
+fn headless_app() -> App {
+    let data_dir = test_data_dir();
+    let _ = std::fs::remove_dir_all(&data_dir);
+    std::env::set_var("LIBRACITY_DATA", &data_dir);
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(ScenePlugin)
+        .add_plugin(AudioPlugin)
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_asset::<ColorMaterial>()
+        .add_asset::<Image>()
+        .add_asset::<Font>()
+        .init_asset_loader::<FontLoader>()
+        .insert_resource(ClearColor::default());
+    add_game_plugins(&mut app);
+
+    // Don't wait for the interludes to be skipped, like the autoplay
+    app.world
+        .resource_mut::<InterludePlayer>()
+        .set_enabled(false);
+    // Nor for the controls card of the first run, since the data folder starts empty
+    app.world.resource_mut::<Onboarding>().skip();
+    app
+}
+
+/// Simple test driver stepping the app in real time.
+struct Driver {
+    _lock: MutexGuard<'static, ()>,
+    app: App,
+    start: Instant,
+    ev_level_completed: ManualEventReader<GameEvent>,
+    ev_level_failed: ManualEventReader<GameEvent>,
+}
+
+impl Driver {
+    fn new() -> Self {
+        // A test failing while holding the lock doesn't prevent the other ones from running
+        let lock = DATA_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        Driver {
+            _lock: lock,
+            app: headless_app(),
+            start: Instant::now(),
+            ev_level_completed: ManualEventReader::default(),
+            ev_level_failed: ManualEventReader::default(),
+        }
+    }
+
+    fn state(&self) -> AppState {
+        *self.app.world.resource::<State<AppState>>().current()
+    }
+
+    fn step(&mut self) {
+        assert!(
+            self.start.elapsed() < TIMEOUT,
+            "Timed out in {:?}",
+            self.state()
+        );
+        thread::sleep(STEP);
+        self.app.update();
+    }
+
+    /// Step until the app reaches the given state.
+    fn step_until_state(&mut self, state: AppState) {
+        while self.state() != state {
+            self.step();
+        }
+    }
+
+    /// Press and release a key over two updates.
+    fn tap_key(&mut self, key_code: KeyCode) {
+        for state in [ElementState::Pressed, ElementState::Released] {
+            self.app
+                .world
+                .resource_mut::<Events<KeyboardInput>>()
+                .send(KeyboardInput {
+                    scan_code: 0,
+                    key_code: Some(key_code),
+                    state,
+                });
+            self.step();
+        }
+    }
+
+    /// Indices of the levels completed since the last call.
+    fn completed_levels(&mut self) -> Vec<usize> {
+        let events = self.app.world.resource::<Events<GameEvent>>();
+        self.ev_level_completed
+            .iter(events)
+            .filter_map(|ev| match ev {
+                GameEvent::LevelCleared { level_index } => Some(*level_index),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Reasons of the levels failed since the last call.
+    fn failed_levels(&mut self) -> Vec<DefeatReason> {
+        let events = self.app.world.resource::<Events<GameEvent>>();
+        self.ev_level_failed
+            .iter(events)
+            .filter_map(|ev| match ev {
+                GameEvent::LevelFailed { reason, .. } => Some(*reason),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Move on from the recap card of a cleared level, like the player confirming it. The keys
+    /// of the recap card also place buildables, so the game is told to proceed directly.
+    fn proceed_victory(&mut self) {
+        let game = self.app.world.resource::<Game>();
+        if game.sequence() == GameSequence::Victory {
+            self.app.world.resource_mut::<Game>().proceed();
+        }
+    }
+
+    /// Start a solo game from the main menu, once the menu accepts input.
+    fn start_solo(&mut self) {
+        while self.state() == AppState::MainMenu {
+            self.tap_key(KeyCode::Return);
+        }
+        assert_eq!(self.state(), AppState::InGame);
+    }
+}
+
+#[test]
+fn boot_to_main_menu() {
+    let mut driver = Driver::new();
+    assert_eq!(driver.state(), AppState::Boot);
+    driver.step_until_state(AppState::MainMenu);
+}
+
+#[test]
+fn play_all_levels() {
+    let mut driver = Driver::new();
+    driver.step_until_state(AppState::MainMenu);
+
+    driver.start_solo();
+
+    // Place the solution of each level until the game ends. The placements are ignored until
+    // the level intro is over, so resend them until the inventory is empty.
+    let mut completed = vec![];
+    let mut last_attempt = Instant::now();
+    while driver.state() == AppState::InGame {
+        if last_attempt.elapsed() > Duration::from_millis(500) {
+            last_attempt = Instant::now();
+            if let Some(plan) = solve_current_level(&driver.app.world) {
+                let mut events = driver
+                    .app
+                    .world
+                    .resource_mut::<Events<PlaceBuildableEvent>>();
+                for ev in plan {
+                    events.send(ev);
+                }
+            }
+        }
+        driver.step();
+        completed.extend(driver.completed_levels());
+        driver.proceed_victory();
+    }
+
+    assert_eq!(driver.state(), AppState::TheEnd);
+    assert_eq!(completed, (0..completed.len()).collect::<Vec<_>>());
+    assert!(!completed.is_empty());
+}
+
+#[test]
+fn fail_level_and_back_to_menu() {
+    let mut driver = Driver::new();
+    driver.step_until_state(AppState::MainMenu);
+    driver.start_solo();
+
+    // Place the buildables of the first level in the corner instead of their solution
+    let mut failed = vec![];
+    let mut last_attempt = Instant::now();
+    while failed.is_empty() {
+        if last_attempt.elapsed() > Duration::from_millis(500) {
+            last_attempt = Instant::now();
+            if let Some(plan) = solve_current_level(&driver.app.world) {
+                let mut events = driver
+                    .app
+                    .world
+                    .resource_mut::<Events<PlaceBuildableEvent>>();
+                for (index, ev) in plan.into_iter().enumerate() {
+                    let pos = IVec2::new(1, 1 - index as i32);
+                    events.send(PlaceBuildableEvent { pos, ..ev });
+                }
+            }
+        }
+        driver.step();
+        failed.extend(driver.failed_levels());
+    }
+    assert_eq!(failed, vec![DefeatReason::Unbalanced]);
+
+    // Go back to the menu, and play again
+    driver.tap_key(KeyCode::M);
+    assert_eq!(driver.state(), AppState::MainMenu);
+    driver.start_solo();
+    for _ in 0..10 {
+        driver.step();
+    }
+    assert_eq!(driver.state(), AppState::InGame);
+}